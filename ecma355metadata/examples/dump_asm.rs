@@ -2,16 +2,21 @@ extern crate ecma355metadata;
 
 use std::env;
 use std::fs::File;
+use std::str;
 
-use ecma355metadata::MetadataImage;
+use ecma355metadata::{strong_name, validate, MetadataImage};
+use ecma355metadata::cli::il::{verifier, ImageResolver};
+use ecma355metadata::cli::resources::ResourceSet;
+use ecma355metadata::cli::tables::{AssemblyDecoder, ManifestResourceDecoder, ManifestResourceLocation, MethodDefDecoder,
+                                   TableHandle, TableIndex};
 
 pub fn main() {
     let args: Vec<_> = env::args().collect();
     if args.len() < 2 {
-        println!("Usage: dump_asm <file>");
+        println!("Usage: dump_asm <file> [<referenced assembly>...]");
     } else {
         let file = File::open(&args[1]).unwrap();
-        let image = MetadataImage::load(file).unwrap();
+        let image = MetadataImage::read(file).unwrap();
 
         println!("CLI Header");
         println!("  Size: {}", image.cli_header().header_size);
@@ -62,5 +67,81 @@ pub fn main() {
             );
         }
         println!();
+
+        if let Some(assembly) = image.table::<AssemblyDecoder>().iter().next() {
+            let assembly = assembly.unwrap();
+            println!("Strong Name:");
+            if let Some(public_key) = image.blob_heap().get(assembly.public_key.index()) {
+                let token: Vec<_> = strong_name::public_key_token(public_key)
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                println!("  Public Key Token: {}", token.concat());
+            }
+            match strong_name::verify(&image) {
+                Ok(status) => println!("  Signature: {}", status),
+                Err(e) => println!("  Signature: <{:?}>", e),
+            }
+            println!();
+        }
+
+        println!("Manifest Resources:");
+        for resource in image.table::<ManifestResourceDecoder>().iter() {
+            let resource = resource.unwrap();
+            let name = str::from_utf8(image.string_heap().get(resource.name.index()).unwrap_or(b"<null>")).unwrap();
+            match resource.location().unwrap() {
+                ManifestResourceLocation::Embedded(offset) => {
+                    let data = image.manifest_resource_data(&resource).unwrap().unwrap();
+                    println!("  * {} ({}): Embedded at 0x{:04X}, {} bytes", name, resource.flags, offset, data.len());
+
+                    // Resources compiled from .resx files can be listed
+                    if let Ok(set) = ResourceSet::read(data) {
+                        for entry in set.entries() {
+                            match set.value(entry) {
+                                Ok(value) => println!("    {} = {}", entry.name, value),
+                                Err(e) => println!("    {} = <{:?}>", entry.name, e),
+                            }
+                        }
+                    }
+                }
+                ManifestResourceLocation::File { file, offset } => {
+                    println!("  * {} ({}): In {} at 0x{:04X}", name, resource.flags, file, offset)
+                }
+                ManifestResourceLocation::AssemblyRef(assembly) => {
+                    println!("  * {} ({}): In {}", name, resource.flags, assembly)
+                }
+            }
+        }
+        println!();
+
+        let diagnostics = validate(&image);
+        println!("Validation: {} problems", diagnostics.len());
+        for diagnostic in diagnostics.iter() {
+            println!("  * {}", diagnostic);
+        }
+        println!();
+
+        // Referenced assemblies let the verifier tell which of their types are value types and enums
+        let references: Vec<_> = args[2..]
+            .iter()
+            .map(|path| MetadataImage::read(File::open(path).unwrap()).unwrap())
+            .collect();
+        let resolver = ImageResolver::with_references(&image, references.iter().collect());
+        let mut errors = Vec::new();
+        for index in 1..=image.table::<MethodDefDecoder>().len() {
+            let method = TableHandle::new(index, TableIndex::MethodDef);
+            match image.method_body(method) {
+                Ok(Some(body)) => {
+                    errors.extend(verifier::verify(method.token(), &body, &resolver).iter().map(|e| e.to_string()))
+                }
+                Ok(None) => {}
+                Err(e) => errors.push(format!("[method 0x{:08X}] <{:?}>", method.token(), e)),
+            }
+        }
+        println!("Verification: {} errors", errors.len());
+        for error in errors.iter() {
+            println!("  * {}", error);
+        }
+        println!();
     }
 }
@@ -5,11 +5,10 @@ use std::str;
 use std::fs::File;
 use std::io::Cursor;
 
-use ecma355metadata::{MetadataReader, PeImage};
-use ecma355metadata::cli::{Access, CliHeader, MethodFlags, MethodVTableLayout};
-use ecma355metadata::cli::tables::MethodDef;
+use ecma355metadata::MetadataImage;
+use ecma355metadata::cli::{Access, MethodFlags, MethodVTableLayout};
+use ecma355metadata::cli::tables::{self, MethodDef};
 use ecma355metadata::cli::signatures::MethodSignature;
-use ecma355metadata::pe::DirectoryType;
 
 pub fn main() {
     let args: Vec<_> = env::args().collect();
@@ -18,24 +17,15 @@ pub fn main() {
     } else {
         let file_path = &args[1];
 
-        let mut file = File::open(file_path).unwrap();
-        let pe = PeImage::read(&mut file).unwrap();
-        let cli_header = CliHeader::from_pe_image(&pe).unwrap();
-        let assembly = MetadataReader::new(
-            pe.load(cli_header.metadata.rva, cli_header.metadata.size as usize)
-                .unwrap(),
-        ).unwrap();
-
-        let methods: Vec<_> = assembly
-            .tables()
-            .method_def()
+        let file = File::open(file_path).unwrap();
+        let assembly = MetadataImage::read(file).unwrap();
+
+        let methods: Vec<_> = assembly.table::<tables::MethodDefDecoder>()
             .iter()
             .map(|o| o.unwrap())
             .collect();
 
-        let params: Vec<_> = assembly
-            .tables()
-            .param()
+        let params: Vec<_> = assembly.table::<tables::ParamDecoder>()
             .iter()
             .map(|o| o.unwrap())
             .collect();
@@ -44,7 +34,7 @@ pub fn main() {
             let method = &methods[idx];
 
             // Load the method signature blob
-            let mut sig_blob = Cursor::new(assembly.get_blob(method.signature).unwrap());
+            let mut sig_blob = Cursor::new(assembly.blob_heap().get(method.signature.index()).unwrap());
             let signature = MethodSignature::read(&mut sig_blob).unwrap();
 
             print!(" [0x{:08X}] ", method.rva);
@@ -52,22 +42,23 @@ pub fn main() {
             write_flags(method);
 
             let name =
-                str::from_utf8(assembly.get_string(method.name).unwrap_or(b"<null>")).unwrap();
+                str::from_utf8(assembly.string_heap().get(method.name.index()).unwrap_or(b"<null>")).unwrap();
 
             print!("{} ", signature.return_type);
 
             print!("{}(", name);
 
-            // Identify the end of the param list by looking at the next method
+            // Identify the end of the param list by looking at the next method. Row numbers start at 1.
+            let start = method.params.index() - 1;
             let end = if idx == methods.len() - 1 {
                 params.len()
             } else {
-                methods[idx + 1].params.index()
+                methods[idx + 1].params.index() - 1
             };
 
             // Iterate over the params
             let mut first = true;
-            for (idx, param) in params[method.params.index()..end].iter().enumerate() {
+            for (idx, param) in params[start..end].iter().enumerate() {
                 let param_sig = &signature.parameters[idx];
                 if first {
                     first = false;
@@ -75,7 +66,7 @@ pub fn main() {
                     print!(", ");
                 }
                 let name =
-                    str::from_utf8(assembly.get_string(param.name).unwrap_or(b"<null>")).unwrap();
+                    str::from_utf8(assembly.string_heap().get(param.name.index()).unwrap_or(b"<null>")).unwrap();
                 print!("{} {}", param_sig, name);
             }
             println!(")")
@@ -92,6 +83,7 @@ fn write_flags(method: &MethodDef) {
         Access::FamORAssem => print!("protected internal "),
         Access::Private => print!("private "),
         Access::Public => print!("public "),
+        Access::Reserved => print!("<reserved> "),
     }
     let flags = method.flags.flags();
     if flags.contains(MethodFlags::Static) {
@@ -3,7 +3,9 @@ extern crate ecma355metadata;
 use std::env;
 use std::fs::File;
 
-use ecma355metadata::MetadataImage;
+use ecma355metadata::PeImage;
+use ecma355metadata::pe::{CodeViewData, DebugType, EmbeddedPdb, PdbChecksum};
+use ecma355metadata::pe::resources::{IconGroup, ResourceId};
 
 pub fn main() {
     let args: Vec<_> = env::args().collect();
@@ -11,31 +13,31 @@ pub fn main() {
         println!("Usage: dump_pe <file>");
     } else {
         let file = File::open(&args[1]).unwrap();
-        let image = MetadataImage::read(file).unwrap();
+        let image = PeImage::read(file).unwrap();
 
         println!("COFF Header:");
-        println!("  Machine: 0x{:04X}", image.pe().coff_header().machine);
+        println!("  Machine: 0x{:04X}", image.coff_header().machine);
         println!(
             "  Number of Sections: {}",
-            image.pe().coff_header().number_of_sections
+            image.coff_header().number_of_sections
         );
-        println!("  Timestamp: {}", image.pe().coff_header().timestamp);
+        println!("  Timestamp: {}", image.coff_header().timestamp);
         println!(
             "  Symbol Table Offset: 0x{:04X}",
-            image.pe().coff_header().symbol_table_addr
+            image.coff_header().symbol_table_addr
         );
-        println!("  Symbol Count: {}", image.pe().coff_header().symbol_count);
+        println!("  Symbol Count: {}", image.coff_header().symbol_count);
         println!(
             "  Optional Header Size: {}",
-            image.pe().coff_header().optional_header_size
+            image.coff_header().optional_header_size
         );
         println!(
             "  Characteristics: {}",
-            image.pe().coff_header().characteristics
+            image.coff_header().characteristics
         );
         println!();
 
-        if let Some(pe_header) = image.pe().pe_header() {
+        if let Some(pe_header) = image.pe_header() {
             println!("PE Header:");
             println!("  Magic: {}", pe_header.magic);
             println!(
@@ -101,7 +103,7 @@ pub fn main() {
         }
 
         println!("Sections:");
-        for section in image.pe().sections() {
+        for section in image.sections() {
             println!("  {}", section.name);
             println!("    Virtual Size: 0x{:08X}", section.virtual_size);
             println!("    Virtual Address: 0x{:08X}", section.virtual_address);
@@ -128,5 +130,126 @@ pub fn main() {
             );
             println!("    Characteristics: {}", section.characteristics);
         }
+        println!();
+
+        println!("Imports:");
+        for descriptor in image.imports().unwrap() {
+            println!("  {}", descriptor.dll_name);
+            for import in descriptor.imports.iter() {
+                println!("    {}", import);
+            }
+        }
+        println!();
+
+        if let Some(exports) = image.exports().unwrap() {
+            println!("Exports from {}:", exports.dll_name);
+            for export in exports.exports.iter() {
+                println!("  {}", export);
+            }
+            println!();
+        }
+
+        println!("Base Relocations:");
+        for block in image.base_relocations().unwrap() {
+            println!("  Page 0x{:08X}", block.page_rva);
+            for relocation in block.relocations.iter() {
+                println!("    0x{:08X} {}", relocation.rva, relocation.relocation_type);
+            }
+        }
+        println!();
+
+        println!("Debug Directory:");
+        for entry in image.debug_directory().unwrap() {
+            println!(
+                "  {} (Version {}.{}, Timestamp 0x{:08X}, Size {})",
+                entry.debug_type,
+                entry.major_version,
+                entry.minor_version,
+                entry.timestamp,
+                entry.size_of_data
+            );
+            let data = entry.data(&image).unwrap();
+            match entry.debug_type {
+                DebugType::CODEVIEW => {
+                    let codeview = CodeViewData::read(data).unwrap();
+                    println!("    PDB: {}", codeview.path);
+                    println!("    GUID: {}", codeview.guid);
+                    println!("    Age: {}", codeview.age);
+                    if entry.is_portable_codeview() {
+                        println!("    Format: Portable PDB");
+                    }
+                }
+                DebugType::EMBEDDED_PORTABLE_PDB => {
+                    let pdb = EmbeddedPdb::read(data).unwrap();
+                    println!("    Compressed Size: {}", pdb.compressed_data.len());
+                    println!("    Uncompressed Size: {}", pdb.uncompressed_size);
+                }
+                DebugType::PDB_CHECKSUM => {
+                    let checksum = PdbChecksum::read(data).unwrap();
+                    print!("    {}: ", checksum.algorithm);
+                    for b in checksum.checksum {
+                        print!("{:02X}", b);
+                    }
+                    println!();
+                }
+                DebugType::REPRO => println!("    Deterministic"),
+                _ => {}
+            }
+        }
+
+        println!();
+        println!("Resources:");
+        let resources = image.resources().unwrap();
+        for resource in resources.iter() {
+            println!(
+                "  {} {} (Language 0x{:04X}, Code Page {}, Size {})",
+                resource.resource_type,
+                resource.name,
+                resource.language,
+                resource.code_page,
+                resource.range.len
+            );
+            if resource.resource_type == ResourceId::GROUP_ICON {
+                let group = IconGroup::read(resource.data(&image).unwrap()).unwrap();
+                for entry in group.entries {
+                    println!("    Icon #{}: {}x{}, {} bpp", entry.id, entry.width, entry.height, entry.bit_count);
+                }
+            }
+        }
+
+        if let Some(info) = image.version_info().unwrap() {
+            println!();
+            println!("Version Info:");
+            if let Some(fixed) = info.fixed.as_ref() {
+                println!("  File Version: {}", fixed.file_version);
+                println!("  Product Version: {}", fixed.product_version);
+            }
+            for table in info.string_tables.iter() {
+                println!("  Strings (Language 0x{:04X}, Code Page {}):", table.language, table.code_page);
+                for (key, value) in table.strings.iter() {
+                    println!("    {}: {}", key, value);
+                }
+            }
+        }
+
+        if let Some(manifest) = image.manifest().unwrap() {
+            println!();
+            println!("Manifest:");
+            println!("{}", String::from_utf8_lossy(manifest));
+        }
+
+        let certificates = image.certificates().unwrap();
+        if !certificates.is_empty() {
+            println!();
+            println!("Certificates:");
+            for certificate in certificates.iter() {
+                println!(
+                    "  {} (Revision 0x{:04X}, Size {})",
+                    certificate.certificate_type,
+                    certificate.revision,
+                    certificate.data.len()
+                );
+            }
+        }
     }
 }
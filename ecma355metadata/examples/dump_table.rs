@@ -3,12 +3,8 @@ extern crate ecma355metadata;
 use std::env;
 use std::str;
 use std::fs::File;
-use std::io::Cursor;
-
-use ecma355metadata::{MetadataReader, PeImage};
-use ecma355metadata::cli::CliHeader;
-use ecma355metadata::cli::tables::TableIndex;
-use ecma355metadata::pe::DirectoryType;
+use ecma355metadata::MetadataImage;
+use ecma355metadata::cli::tables::{self, TableIndex};
 use ecma355metadata::Guid;
 
 pub fn main() {
@@ -18,13 +14,8 @@ pub fn main() {
     } else {
         let file_path = &args[1];
 
-        let mut file = File::open(file_path).unwrap();
-        let pe = PeImage::read(&mut file).unwrap();
-        let cli_header = CliHeader::from_pe_image(&pe).unwrap();
-        let assembly = MetadataReader::new(
-            pe.load(cli_header.metadata.rva, cli_header.metadata.size as usize)
-                .unwrap(),
-        ).unwrap();
+        let file = File::open(file_path).unwrap();
+        let assembly = MetadataImage::read(file).unwrap();
 
         if args.len() < 3 {
             dump_table_names(&assembly);
@@ -39,29 +30,51 @@ pub fn main() {
                 TableIndex::Field => dump_field_table(&assembly),
                 TableIndex::MethodDef => dump_method_def_table(&assembly),
                 TableIndex::Param => dump_param_table(&assembly),
+                TableIndex::CustomAttribute => dump_custom_attribute_table(&assembly),
                 x => println!("Table not yet implemented: {}", x),
             }
         }
     }
 }
 
-pub fn dump_table_names(assembly: &MetadataReader) {
+pub fn dump_table_names(assembly: &MetadataImage<Vec<u8>>) {
     println!("Table Row Counts:");
     for idx in TableIndex::each() {
         println!(
             "  {}: {} rows",
             idx,
-            assembly.tables().metadata_sizes().row_count(idx)
+            assembly.metadata_sizes().row_count(idx)
         );
     }
 }
 
-pub fn dump_param_table(assembly: &MetadataReader) {
-    let param_table = assembly.tables().param();
+pub fn dump_custom_attribute_table(assembly: &MetadataImage<Vec<u8>>) {
+    let custom_attribute_table = assembly.table::<tables::CustomAttributeDecoder>();
+    println!("CustomAttribute Table: {} rows", custom_attribute_table.len());
+    for row in custom_attribute_table.iter() {
+        let row = row.unwrap();
+        print!(" * {} on {}:", row.attribute_type, row.parent);
+        match assembly.custom_attribute_value(&row, assembly) {
+            Ok(value) => {
+                for arg in value.fixed_arguments.iter() {
+                    print!(" {}", arg);
+                }
+                for arg in value.named_arguments.iter() {
+                    print!(" {} = {}", arg.name, arg.value);
+                }
+                println!();
+            }
+            Err(e) => println!(" <{:?}>", e),
+        }
+    }
+}
+
+pub fn dump_param_table(assembly: &MetadataImage<Vec<u8>>) {
+    let param_table = assembly.table::<tables::ParamDecoder>();
     println!("Param Table: {} rows", param_table.len());
     for row in param_table.iter() {
         let row = row.unwrap();
-        let name = str::from_utf8(assembly.get_string(row.name).unwrap_or(b"<null>")).unwrap();
+        let name = str::from_utf8(assembly.string_heap().get(row.name.index()).unwrap_or(b"<null>")).unwrap();
         print!("* {} #{}", name, row.sequence);
         if !row.flags.is_empty() {
             print!(" ({})", row.flags);
@@ -70,12 +83,12 @@ pub fn dump_param_table(assembly: &MetadataReader) {
     }
 }
 
-pub fn dump_method_def_table(assembly: &MetadataReader) {
-    let method_def_table = assembly.tables().method_def();
+pub fn dump_method_def_table(assembly: &MetadataImage<Vec<u8>>) {
+    let method_def_table = assembly.table::<tables::MethodDefDecoder>();
     println!("MethodDef Table: {} rows", method_def_table.len());
     for row in method_def_table.iter() {
         let row = row.unwrap();
-        let name = str::from_utf8(assembly.get_string(row.name).unwrap_or(b"<null>")).unwrap();
+        let name = str::from_utf8(assembly.string_heap().get(row.name.index()).unwrap_or(b"<null>")).unwrap();
         println!(
             " * {} @ 0x{:08X} ({}, {}, Sig: 0x{:04X}, Params: {})",
             name,
@@ -88,13 +101,13 @@ pub fn dump_method_def_table(assembly: &MetadataReader) {
     }
 }
 
-pub fn dump_type_def_table(assembly: &MetadataReader) {
-    let type_def_table = assembly.tables().type_def();
+pub fn dump_type_def_table(assembly: &MetadataImage<Vec<u8>>) {
+    let type_def_table = assembly.table::<tables::TypeDefDecoder>();
     println!("TypeDef Table: {} rows", type_def_table.len());
     for row in type_def_table.iter() {
         let row = row.unwrap();
-        let name = assembly.get_string(row.type_name).unwrap_or(b"<null>");
-        let namespace = assembly.get_string(row.type_namespace);
+        let name = assembly.string_heap().get(row.type_name.index()).unwrap_or(b"<null>");
+        let namespace = assembly.string_heap().get(row.type_namespace.index());
 
         print!(" * ");
 
@@ -120,27 +133,27 @@ pub fn dump_type_def_table(assembly: &MetadataReader) {
     println!()
 }
 
-pub fn dump_field_table(assembly: &MetadataReader) {
-    let field_table = assembly.tables().field();
+pub fn dump_field_table(assembly: &MetadataImage<Vec<u8>>) {
+    let field_table = assembly.table::<tables::FieldDecoder>();
     println!("Field Table: {} rows", field_table.len());
     for row in field_table.iter() {
         let row = row.unwrap();
         println!(
             " * {} ({}, Signature: 0x{:X})",
-            str::from_utf8(assembly.get_string(row.name).unwrap_or(b"<null>")).unwrap(),
+            str::from_utf8(assembly.string_heap().get(row.name.index()).unwrap_or(b"<null>")).unwrap(),
             row.flags,
             row.signature.index()
         );
     }
 }
 
-pub fn dump_type_ref_table(assembly: &MetadataReader) {
-    let type_ref_table = assembly.tables().type_ref();
+pub fn dump_type_ref_table(assembly: &MetadataImage<Vec<u8>>) {
+    let type_ref_table = assembly.table::<tables::TypeRefDecoder>();
     println!("TypeRef Table: {} rows", type_ref_table.len());
     for row in type_ref_table.iter() {
         let row = row.unwrap();
-        let name = assembly.get_string(row.name).unwrap_or(b"<null>");
-        let namespace = assembly.get_string(row.namespace);
+        let name = assembly.string_heap().get(row.name.index()).unwrap_or(b"<null>");
+        let namespace = assembly.string_heap().get(row.namespace.index());
 
         if let Some(ns) = namespace {
             println!(
@@ -160,8 +173,8 @@ pub fn dump_type_ref_table(assembly: &MetadataReader) {
     println!()
 }
 
-pub fn dump_module_table(assembly: &MetadataReader) {
-    let module_table = assembly.tables().module();
+pub fn dump_module_table(assembly: &MetadataImage<Vec<u8>>) {
+    let module_table = assembly.table::<tables::ModuleDecoder>();
 
     println!("Module Table: {} rows", module_table.len());
     for row in module_table.iter() {
@@ -169,11 +182,11 @@ pub fn dump_module_table(assembly: &MetadataReader) {
         println!("  Generation: {}", row.generation);
         println!(
             "  Name: {}",
-            str::from_utf8(assembly.get_string(row.name).unwrap()).unwrap()
+            str::from_utf8(assembly.string_heap().get(row.name.index()).unwrap()).unwrap()
         );
         println!(
             "  MVID: {}",
-            assembly.get_guid(row.mvid).unwrap_or(&Guid::EMPTY)
+            assembly.guid_heap().get(row.mvid.index()).unwrap_or(&Guid::EMPTY)
         );
     }
     println!();
@@ -0,0 +1,76 @@
+extern crate ecma355metadata;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use ecma355metadata::MetadataImage;
+use ecma355metadata::cli::tables;
+
+/// Splits an assembly into seed inputs for each of the fuzz targets in `fuzz/fuzz_targets`.
+pub fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() < 3 {
+        println!("Usage: fuzz_seeds <file> <corpus directory>");
+    } else {
+        let file_path = Path::new(&args[1]);
+        let corpus = Path::new(&args[2]);
+        let name = file_path.file_name().unwrap().to_str().unwrap();
+
+        let data = fs::read(file_path).unwrap();
+        let assembly = MetadataImage::load_data(&data[..]).unwrap();
+
+        write_seed(corpus, "pe_image", name, &data);
+        write_seed(corpus, "metadata_image", name, &data);
+
+        // The metadata header and streams are written exactly as they appear in the file
+        let metadata = assembly.pe().map_range(assembly.cli_header().metadata).unwrap();
+        let metadata = &data[metadata];
+        write_seed(corpus, "metadata_header", name, metadata);
+        for stream in assembly.metadata_header().streams.iter() {
+            let start = stream.offset as usize;
+            let contents = &metadata[start..(start + stream.size as usize)];
+            match stream.name.as_str() {
+                "#~" => {
+                    write_seed(corpus, "metadata_sizes", name, contents);
+                    write_seed(corpus, "table_decoders", name, contents);
+                }
+                heap => write_seed(corpus, "heaps", &format!("{}{}", name, heap), contents),
+            }
+        }
+
+        // The signatures target uses the first byte to select the kind of signature
+        let blobs = assembly.blob_heap();
+        for (idx, spec) in assembly.table::<tables::TypeSpecDecoder>().iter().enumerate() {
+            let blob = blobs.get(spec.unwrap().signature.index()).unwrap();
+            write_signature_seed(corpus, &format!("{}-typespec-{}", name, idx), 0, blob);
+        }
+        for (idx, method) in assembly.table::<tables::MethodDefDecoder>().iter().enumerate() {
+            let blob = blobs.get(method.unwrap().signature.index()).unwrap();
+            write_signature_seed(corpus, &format!("{}-methoddef-{}", name, idx), 1, blob);
+        }
+        for (idx, spec) in assembly.table::<tables::MethodSpecDecoder>().iter().enumerate() {
+            let blob = blobs.get(spec.unwrap().instantiation.index()).unwrap();
+            write_signature_seed(corpus, &format!("{}-methodspec-{}", name, idx), 2, blob);
+        }
+
+        for (idx, resource) in assembly.table::<tables::ManifestResourceDecoder>().iter().enumerate() {
+            if let Some(data) = assembly.manifest_resource_data(&resource.unwrap()).unwrap() {
+                write_seed(corpus, "resources", &format!("{}-resource-{}", name, idx), data);
+            }
+        }
+    }
+}
+
+fn write_signature_seed(corpus: &Path, name: &str, kind: u8, blob: &[u8]) {
+    let mut seed = vec![kind];
+    seed.extend_from_slice(blob);
+    write_seed(corpus, "signatures", name, &seed);
+}
+
+fn write_seed(corpus: &Path, target: &str, name: &str, data: &[u8]) {
+    let dir = corpus.join(target);
+    fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join(name)).unwrap().write_all(data).unwrap();
+}
@@ -0,0 +1,18 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ecma355metadata::cli::{BlobHeap, GuidHeap, StringHeap, UserStringHeap};
+
+fuzz_target!(|data: &[u8]| {
+    // Look up every possible offset, plus one past the end
+    for idx in 0..(data.len() + 2) {
+        let _ = BlobHeap::new(data).get(idx);
+        let _ = StringHeap::new(data).get(idx);
+        let _ = UserStringHeap::new(data).get_string(idx);
+    }
+    if let Ok(guids) = GuidHeap::new(data) {
+        for idx in 0..(data.len() / 16 + 2) {
+            let _ = guids.get(idx);
+        }
+    }
+});
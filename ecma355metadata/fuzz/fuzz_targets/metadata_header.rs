@@ -0,0 +1,12 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use std::io::Cursor;
+
+use ecma355metadata::cli::MetadataHeader;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(header) = MetadataHeader::read(&mut Cursor::new(data)) {
+        let _ = header.get_stream("#~");
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ecma355metadata::cli::signatures::MethodSignature;
+use ecma355metadata::cli::tables::{ManifestResourceDecoder, MethodDefDecoder};
+use ecma355metadata::{strong_name, validate, MetadataImage};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(image) = MetadataImage::load_data(data) {
+        // Validation decodes every row of every table and checks each heap reference
+        for diagnostic in validate(&image) {
+            let _ = diagnostic.to_string();
+        }
+
+        for method in image.table::<MethodDefDecoder>().iter() {
+            if let Some(mut blob) = method.ok().and_then(|m| image.blob_heap().get(m.signature.index())) {
+                let _ = MethodSignature::read(&mut blob).map(|s| s.to_string());
+            }
+        }
+
+        for resource in image.table::<ManifestResourceDecoder>().iter() {
+            if let Ok(resource) = resource {
+                let _ = image.manifest_resource_data(&resource);
+            }
+        }
+
+        // Parses the public key and hashes the whole image, so a signed input runs the full verification
+        let _ = strong_name::verify(&image);
+    }
+});
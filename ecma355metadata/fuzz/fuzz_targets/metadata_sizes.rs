@@ -0,0 +1,15 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ecma355metadata::cli::tables::TableIndex;
+use ecma355metadata::cli::MetadataSizes;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(sizes) = MetadataSizes::read(&mut &data[..]) {
+        for idx in 0..(TableIndex::MAX + 1) {
+            if let Some(index) = TableIndex::from_u8(idx as u8) {
+                let _ = sizes.row_count(index);
+            }
+        }
+    }
+});
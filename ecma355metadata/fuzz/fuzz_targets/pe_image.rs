@@ -0,0 +1,56 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ecma355metadata::pe::{CodeViewData, EmbeddedPdb, PdbChecksum};
+use ecma355metadata::pe::resources::{IconGroup, VersionInfo};
+use ecma355metadata::PeImage;
+
+// Reads are zero-filled up to the requested length, so cap it to keep each run fast
+const MAX_READ: usize = 0x10000;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(pe) = PeImage::load(data) {
+        for section in pe.sections() {
+            let _ = pe.read_rva(section.virtual_address, (section.virtual_size as usize).min(MAX_READ));
+        }
+        if let Some(header) = pe.pe_header() {
+            for directory in header.directories() {
+                let _ = pe.read_rva(directory.range.start, (directory.range.len as usize).min(MAX_READ));
+                let _ = pe.map_range(directory.range);
+            }
+        }
+
+        let _ = pe.imports();
+        let _ = pe.exports();
+        let _ = pe.base_relocations();
+        if let Ok(entries) = pe.debug_directory() {
+            for entry in entries {
+                if let Ok(data) = entry.data(&pe) {
+                    let _ = CodeViewData::read(data);
+                    let _ = PdbChecksum::read(data);
+                    if let Ok(pdb) = EmbeddedPdb::read(data) {
+                        // Keep the decompressed size small, so runs stay fast
+                        if pdb.uncompressed_size < 0x10000 {
+                            let _ = pdb.decompress();
+                        }
+                    }
+                }
+            }
+        }
+        if let Ok(certificates) = pe.certificates() {
+            for certificate in certificates.iter() {
+                let _ = certificate.pkcs7();
+            }
+        }
+        if let Ok(resources) = pe.resources() {
+            for resource in resources.iter() {
+                if let Ok(data) = resource.data(&pe) {
+                    let _ = VersionInfo::read(data);
+                    if let Ok(group) = IconGroup::read(data) {
+                        let _ = group.to_ico(&pe, &resources);
+                    }
+                }
+            }
+        }
+    }
+});
@@ -0,0 +1,15 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ecma355metadata::cli::resources::ResourceSet;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(set) = ResourceSet::read(data) {
+        for entry in set.entries() {
+            if let Ok(value) = set.value(entry) {
+                let _ = value.to_string();
+            }
+            let _ = set.get(&entry.name);
+        }
+    }
+});
@@ -0,0 +1,17 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ecma355metadata::cli::signatures::{MethodSignature, MethodSpecSignature, TypeReference};
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the kind of signature, so the fuzzer can explore all of them from one corpus
+    let (kind, mut buf) = match data.split_first() {
+        Some((&kind, rest)) => (kind, rest),
+        None => return,
+    };
+    let _ = match kind % 3 {
+        0 => TypeReference::read(&mut buf).map(|s| s.to_string()),
+        1 => MethodSignature::read(&mut buf).map(|s| s.to_string()),
+        _ => MethodSpecSignature::read(&mut buf).map(|s| s.to_string()),
+    };
+});
@@ -0,0 +1,73 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ecma355metadata::cli::tables::*;
+use ecma355metadata::cli::MetadataSizes;
+
+/// Decodes every row of each table, treating the data after the sizes as the contents of every table.
+macro_rules! decode_tables {
+    ($sizes:expr, $data:expr, $($decoder:ident),*) => {
+        $(
+            if let Ok(table) = Table::new($data, $decoder::new($sizes)) {
+                for row in table.iter() {
+                    let _ = row;
+                }
+            }
+        )*
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut buf = data;
+    if let Ok(sizes) = MetadataSizes::read(&mut buf) {
+        decode_tables!(
+            &sizes,
+            buf,
+            ModuleDecoder,
+            TypeRefDecoder,
+            TypeDefDecoder,
+            FieldPtrDecoder,
+            FieldDecoder,
+            MethodPtrDecoder,
+            MethodDefDecoder,
+            ParamPtrDecoder,
+            ParamDecoder,
+            InterfaceImplDecoder,
+            MemberRefDecoder,
+            ConstantDecoder,
+            CustomAttributeDecoder,
+            FieldMarshalDecoder,
+            DeclSecurityDecoder,
+            ClassLayoutDecoder,
+            FieldLayoutDecoder,
+            StandAloneSigDecoder,
+            EventMapDecoder,
+            EventPtrDecoder,
+            EventDecoder,
+            PropertyMapDecoder,
+            PropertyPtrDecoder,
+            PropertyDecoder,
+            MethodSemanticsDecoder,
+            MethodImplDecoder,
+            ModuleRefDecoder,
+            TypeSpecDecoder,
+            ImplMapDecoder,
+            FieldRvaDecoder,
+            EncLogDecoder,
+            EncMapDecoder,
+            AssemblyDecoder,
+            AssemblyProcessorDecoder,
+            AssemblyOsDecoder,
+            AssemblyRefDecoder,
+            AssemblyRefProcessorDecoder,
+            AssemblyRefOsDecoder,
+            FileDecoder,
+            ExportedTypeDecoder,
+            ManifestResourceDecoder,
+            NestedClassDecoder,
+            GenericParamDecoder,
+            MethodSpecDecoder,
+            GenericParamConstraintDecoder
+        );
+    }
+});
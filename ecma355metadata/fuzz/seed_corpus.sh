@@ -0,0 +1,17 @@
+#!/usr/bin/env bash
+# Builds fx/corlib and apps/HelloWorld with the .NET SDK and splits the assemblies into seeds for each fuzz target.
+#
+# Usage: fuzz/seed_corpus.sh [corpus directory, defaults to fuzz/corpus]
+set -euo pipefail
+
+fuzz_dir="$(cd "$(dirname "$0")" && pwd)"
+repo_root="$(cd "$fuzz_dir/../.." && pwd)"
+corpus="${1:-$fuzz_dir/corpus}"
+
+dotnet build "$repo_root/apps/HelloWorld/HelloWorld.csproj" --configuration Debug
+
+for assembly in \
+    "$repo_root/fx/corlib/bin/Debug/corlib.dll" \
+    "$repo_root/apps/HelloWorld/bin/Debug/HelloWorld.dll"; do
+    cargo run --quiet --manifest-path "$fuzz_dir/../Cargo.toml" --example fuzz_seeds -- "$assembly" "$corpus"
+done
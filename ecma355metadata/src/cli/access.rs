@@ -8,6 +8,9 @@ pub enum Access {
     Family = 4,
     FamORAssem = 5,
     Public = 6,
+
+    /// The access bits are set to a value the ECMA spec doesn't define.
+    Reserved = 7,
 }
 impl_display_via_debug!(Access);
 
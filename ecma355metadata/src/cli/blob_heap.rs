@@ -9,31 +9,29 @@ impl<'a> BlobHeap<'a> {
         BlobHeap { data: Some(data) }
     }
 
-    pub fn get(&self, idx: usize) -> Option<&[u8]> {
+    pub fn get(&self, idx: usize) -> Option<&'a [u8]> {
         if let Some(data) = self.data {
             // Bounds check
             if idx == 0 || idx >= data.len() {
                 None
             } else {
                 // Read the header
-                if data[idx] & 0x80 == 0 {
+                let (start, len) = if data[idx] & 0x80 == 0 {
                     // 1-byte length
-                    let start = idx + 1;
-                    let len = (data[idx] as usize) & 0x7F;
-                    Some(&data[start..(start + len)])
-                } else if data[idx] & 0xC0 == 0 {
+                    (idx + 1, (data[idx] as usize) & 0x7F)
+                } else if data[idx] & 0x40 == 0 {
                     // 2-byte length
-                    let start = idx + 2;
-                    let len = ((data[idx] as usize & 0x3F) << 8) + data[idx + 1] as usize;
-                    Some(&data[start..(start + len)])
+                    let len = ((data[idx] as usize & 0x3F) << 8) + *data.get(idx + 1)? as usize;
+                    (idx + 2, len)
                 } else {
                     // 4-byte length
-                    let start = idx + 4;
-                    let len = ((data[idx] as usize) & 0x1F << 24) + ((data[idx + 1] as usize) << 16)
-                        + ((data[idx + 2] as usize) << 8)
-                        + data[idx + 3] as usize;
-                    Some(&data[start..(start + len)])
-                }
+                    let header = data.get(idx..(idx + 4))?;
+                    let len = ((header[0] as usize & 0x1F) << 24) + ((header[1] as usize) << 16)
+                        + ((header[2] as usize) << 8)
+                        + header[3] as usize;
+                    (idx + 4, len)
+                };
+                data.get(start..(start + len))
             }
         } else {
             None
@@ -22,6 +22,8 @@ pub struct CliHeader {
 }
 
 impl CliHeader {
+    pub const SIZE: usize = 72;
+
     pub fn read<A: Read>(buf: &mut A) -> Result<CliHeader, Error> {
         Ok(CliHeader {
             header_size: buf.read_u32::<LittleEndian>()?,
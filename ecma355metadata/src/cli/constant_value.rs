@@ -0,0 +1,165 @@
+use std::fmt;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+
+/// Represents the value of a constant from the Constant table, decoded using the constant's type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstantValue {
+    Boolean(bool),
+    Char(u16),
+    I1(i8),
+    U1(u8),
+    I2(i16),
+    U2(u16),
+    I4(i32),
+    U4(u32),
+    I8(i64),
+    U8(u64),
+    R4(f32),
+    R8(f64),
+
+    /// The UTF-16 code units of a string, which are not required to be valid UTF-16, like user strings.
+    String(Vec<u16>),
+    Null,
+}
+
+impl ConstantValue {
+    /// Decodes a constant's value blob, using the type from the `constant_type` column of the Constant table.
+    pub fn decode(constant_type: u8, mut blob: &[u8]) -> Result<ConstantValue, Error> {
+        Ok(match constant_type {
+            0x02 => ConstantValue::Boolean(blob.read_u8()? != 0),
+            0x03 => ConstantValue::Char(blob.read_u16::<LittleEndian>()?),
+            0x04 => ConstantValue::I1(blob.read_i8()?),
+            0x05 => ConstantValue::U1(blob.read_u8()?),
+            0x06 => ConstantValue::I2(blob.read_i16::<LittleEndian>()?),
+            0x07 => ConstantValue::U2(blob.read_u16::<LittleEndian>()?),
+            0x08 => ConstantValue::I4(blob.read_i32::<LittleEndian>()?),
+            0x09 => ConstantValue::U4(blob.read_u32::<LittleEndian>()?),
+            0x0A => ConstantValue::I8(blob.read_i64::<LittleEndian>()?),
+            0x0B => ConstantValue::U8(blob.read_u64::<LittleEndian>()?),
+            0x0C => ConstantValue::R4(blob.read_f32::<LittleEndian>()?),
+            0x0D => ConstantValue::R8(blob.read_f64::<LittleEndian>()?),
+            0x0E => {
+                // UTF-16 code units, with no length prefix or terminator
+                if blob.len() & 1 != 0 {
+                    return Err(Error::InvalidStringData);
+                }
+                ConstantValue::String(blob.chunks(2).map(|c| (c[0] as u16) | ((c[1] as u16) << 8)).collect())
+            }
+            0x12 => ConstantValue::Null,
+            x => return Err(Error::UnknownTypeCode(x as u32)),
+        })
+    }
+
+    /// Gets the value as a signed 64-bit integer, if it is an integral constant (such as the literal of an enum).
+    ///
+    /// Returns `None` for a `U8` above `i64::MAX`, which would otherwise wrap to a negative number.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            ConstantValue::Boolean(x) => Some(x as i64),
+            ConstantValue::Char(x) => Some(x as i64),
+            ConstantValue::I1(x) => Some(x as i64),
+            ConstantValue::U1(x) => Some(x as i64),
+            ConstantValue::I2(x) => Some(x as i64),
+            ConstantValue::U2(x) => Some(x as i64),
+            ConstantValue::I4(x) => Some(x as i64),
+            ConstantValue::U4(x) => Some(x as i64),
+            ConstantValue::I8(x) => Some(x),
+            ConstantValue::U8(x) if x <= i64::MAX as u64 => Some(x as i64),
+            _ => None,
+        }
+    }
+
+    /// Gets the value as a string, if it is a string constant, replacing any invalid UTF-16 with the replacement
+    /// character.
+    pub fn as_string(&self) -> Option<String> {
+        match *self {
+            ConstantValue::String(ref x) => Some(String::from_utf16_lossy(x)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConstantValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ConstantValue::Boolean(x) => write!(f, "bool({})", x),
+            ConstantValue::Char(x) => write!(f, "char(0x{:04X})", x),
+            ConstantValue::I1(x) => write!(f, "int8({})", x),
+            ConstantValue::U1(x) => write!(f, "uint8({})", x),
+            ConstantValue::I2(x) => write!(f, "int16({})", x),
+            ConstantValue::U2(x) => write!(f, "uint16({})", x),
+            ConstantValue::I4(x) => write!(f, "int32({})", x),
+            ConstantValue::U4(x) => write!(f, "uint32({})", x),
+            ConstantValue::I8(x) => write!(f, "int64({})", x),
+            ConstantValue::U8(x) => write!(f, "uint64({})", x),
+            ConstantValue::R4(x) => write!(f, "float32({})", x),
+            ConstantValue::R8(x) => write!(f, "float64({})", x),
+            ConstantValue::String(ref x) => write!(f, "{:?}", String::from_utf16_lossy(x)),
+            ConstantValue::Null => write!(f, "nullref"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! constant_tests {
+        ($($name:ident($typ:expr, $blob:expr, $expected:expr);)*) => {
+            $(
+                #[test]
+                pub fn $name() {
+                    assert_eq!($expected, ConstantValue::decode($typ, &$blob).unwrap());
+                }
+            )*
+        };
+    }
+
+    constant_tests! {
+        boolean(0x02, [0x01], ConstantValue::Boolean(true));
+        i1(0x04, [0xFF], ConstantValue::I1(-1));
+        u2(0x07, [0x34, 0x12], ConstantValue::U2(0x1234));
+        i4(0x08, [0xFE, 0xFF, 0xFF, 0xFF], ConstantValue::I4(-2));
+        u8(0x0B, [0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80], ConstantValue::U8(0x8000_0000_0000_0001));
+        r8(0x0D, [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x3F], ConstantValue::R8(1.0));
+        string(0x0E, [0x48, 0x00, 0x69, 0x00], ConstantValue::String(vec![0x48, 0x69]));
+        empty_string(0x0E, [], ConstantValue::String(vec![]));
+        lone_surrogate(0x0E, [0x00, 0xD8], ConstantValue::String(vec![0xD800]));
+        null(0x12, [0x00, 0x00, 0x00, 0x00], ConstantValue::Null);
+    }
+
+    #[test]
+    pub fn truncated_value() {
+        assert!(ConstantValue::decode(0x08, &[0x01, 0x02]).is_err());
+    }
+
+    #[test]
+    pub fn odd_length_string() {
+        assert_eq!(Err(Error::InvalidStringData), ConstantValue::decode(0x0E, &[0x48, 0x00, 0x69]));
+    }
+
+    #[test]
+    pub fn string_as_string() {
+        assert_eq!(Some("Hi".into()), ConstantValue::String(vec![0x48, 0x69]).as_string());
+        assert_eq!(Some("\u{FFFD}".into()), ConstantValue::String(vec![0xD800]).as_string());
+        assert_eq!("\"\u{fffd}\"", format!("{}", ConstantValue::String(vec![0xD800])));
+        assert_eq!(None, ConstantValue::I4(1).as_string());
+    }
+
+    #[test]
+    pub fn unknown_type() {
+        assert_eq!(Err(Error::UnknownTypeCode(0x1C)), ConstantValue::decode(0x1C, &[]));
+    }
+
+    #[test]
+    pub fn enum_literal_as_i64() {
+        assert_eq!(Some(0x7FFF), ConstantValue::I4(0x7FFF).as_i64());
+        assert_eq!(Some(-1), ConstantValue::I8(-1).as_i64());
+        assert_eq!(Some(i64::MAX), ConstantValue::U8(i64::MAX as u64).as_i64());
+        assert_eq!(None, ConstantValue::U8(0x8000_0000_0000_0000).as_i64());
+        assert_eq!(None, ConstantValue::String(vec![0x78]).as_i64());
+    }
+}
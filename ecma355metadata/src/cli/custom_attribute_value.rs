@@ -0,0 +1,453 @@
+use std::fmt;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::signatures::{utils, MethodSignature, TypeReference};
+use cli::tables::TableHandle;
+use error::Error;
+
+/// The prolog that starts every custom attribute value blob.
+const PROLOG: u16 = 0x0001;
+
+/// How deeply boxed arrays can be nested in a custom attribute value, like `MAX_SIGNATURE_DEPTH` for signatures.
+const MAX_VALUE_DEPTH: usize = 16;
+
+/// Finds the underlying types of the enums used in custom attribute arguments.
+///
+/// The value blob only stores the underlying integer, and the enum can be defined in another assembly, so the
+/// caller has to look it up.
+pub trait EnumResolver {
+    /// Gets the underlying type of an enum referenced by a constructor parameter, as a TypeDef or TypeRef.
+    fn resolve_enum(&self, enum_type: TableHandle) -> Option<TypeReference>;
+
+    /// Gets the underlying type of an enum referenced by its serialized name, in a named or boxed argument.
+    ///
+    /// The name is namespace-qualified, and may be followed by the assembly name, such as
+    /// `System.AttributeTargets, System.Runtime, Version=6.0.0.0`.
+    fn resolve_enum_by_name(&self, name: &str) -> Option<TypeReference>;
+}
+
+/// Identifies the enum type of an enum argument, in the form the value blob or constructor refers to it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeEnumType {
+    Handle(TableHandle),
+    Name(String),
+}
+
+/// A custom attribute argument (ECMA-335 II.23.3).
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeValue {
+    Boolean(bool),
+    Char(u16),
+    I1(i8),
+    U1(u8),
+    I2(i16),
+    U2(u16),
+    I4(i32),
+    U4(u32),
+    I8(i64),
+    U8(u64),
+    R4(f32),
+    R8(f64),
+
+    /// A string, or `None` for a null string.
+    String(Option<String>),
+
+    /// A `System.Type`, given by its serialized name, or `None` for a null type.
+    Type(Option<String>),
+
+    /// A value of an enum type, stored as its underlying integral value.
+    Enum(AttributeEnumType, Box<AttributeValue>),
+
+    /// A single-dimensional array, or `None` for a null array.
+    Array(Option<Vec<AttributeValue>>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NamedArgumentKind {
+    Field,
+    Property,
+}
+
+/// A named argument, which sets a field or property of the attribute after it is constructed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamedArgument {
+    pub kind: NamedArgumentKind,
+    pub name: String,
+    pub value: AttributeValue,
+}
+
+/// The decoded value blob of a custom attribute: its constructor arguments, followed by its named arguments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomAttributeValue {
+    pub fixed_arguments: Vec<AttributeValue>,
+    pub named_arguments: Vec<NamedArgument>,
+}
+
+/// The type of an argument, which determines how its value is encoded.
+enum ArgumentType {
+    /// A primitive, given by its element type code.
+    Primitive(u8),
+    String,
+    Type,
+    /// `System.Object`, where the value is preceded by its actual type.
+    Boxed,
+    Enum(AttributeEnumType, u8),
+    Array(Box<ArgumentType>),
+}
+
+impl CustomAttributeValue {
+    /// Decodes a custom attribute value blob, using the signature of the attribute's constructor to find the types
+    /// of the fixed arguments.
+    pub fn decode<R: EnumResolver>(
+        constructor: &MethodSignature,
+        mut blob: &[u8],
+        resolver: &R,
+    ) -> Result<CustomAttributeValue, Error> {
+        if blob.read_u16::<LittleEndian>()? != PROLOG {
+            return Err(Error::InvalidMetadata("Custom attribute value does not start with the prolog."));
+        }
+
+        let mut fixed_arguments = Vec::new();
+        for param in constructor.parameters.iter() {
+            let typ = parameter_type(&param.type_reference, resolver)?;
+            fixed_arguments.push(read_value(&mut blob, &typ, resolver, 0)?);
+        }
+
+        let count = blob.read_u16::<LittleEndian>()?;
+        let mut named_arguments = Vec::new();
+        for _ in 0..count {
+            let kind = match blob.read_u8()? {
+                0x53 => NamedArgumentKind::Field,
+                0x54 => NamedArgumentKind::Property,
+                _ => return Err(Error::InvalidMetadata("Named argument is neither a field nor a property.")),
+            };
+            let typ = read_serialized_type(&mut blob, resolver)?;
+            let name = read_ser_string(&mut blob)?
+                .ok_or(Error::InvalidMetadata("Named argument has a null name."))?;
+            let value = read_value(&mut blob, &typ, resolver, 0)?;
+            named_arguments.push(NamedArgument { kind, name, value });
+        }
+
+        Ok(CustomAttributeValue {
+            fixed_arguments,
+            named_arguments,
+        })
+    }
+}
+
+/// Gets the type of a fixed argument from the type of the constructor parameter.
+fn parameter_type<R: EnumResolver>(typ: &TypeReference, resolver: &R) -> Result<ArgumentType, Error> {
+    Ok(match *typ {
+        TypeReference::Boolean => ArgumentType::Primitive(0x02),
+        TypeReference::Char => ArgumentType::Primitive(0x03),
+        TypeReference::I1 => ArgumentType::Primitive(0x04),
+        TypeReference::U1 => ArgumentType::Primitive(0x05),
+        TypeReference::I2 => ArgumentType::Primitive(0x06),
+        TypeReference::U2 => ArgumentType::Primitive(0x07),
+        TypeReference::I4 => ArgumentType::Primitive(0x08),
+        TypeReference::U4 => ArgumentType::Primitive(0x09),
+        TypeReference::I8 => ArgumentType::Primitive(0x0A),
+        TypeReference::U8 => ArgumentType::Primitive(0x0B),
+        TypeReference::R4 => ArgumentType::Primitive(0x0C),
+        TypeReference::R8 => ArgumentType::Primitive(0x0D),
+        TypeReference::String => ArgumentType::String,
+        TypeReference::Object => ArgumentType::Boxed,
+
+        // System.Type is the only class that can be an attribute argument
+        TypeReference::Class(_) => ArgumentType::Type,
+        TypeReference::ValueType(handle) => {
+            let underlying = resolver.resolve_enum(handle).ok_or(unresolved_enum())?;
+            ArgumentType::Enum(AttributeEnumType::Handle(handle), underlying_code(&underlying)?)
+        }
+        TypeReference::SzArray(_, ref element) => match **element {
+            TypeReference::SzArray(..) => return Err(unsupported_type()),
+            ref element => ArgumentType::Array(Box::new(parameter_type(element, resolver)?)),
+        },
+        _ => return Err(unsupported_type()),
+    })
+}
+
+/// Reads the type that precedes named and boxed arguments (`FieldOrPropType` in the spec).
+fn read_serialized_type<R: EnumResolver>(blob: &mut &[u8], resolver: &R) -> Result<ArgumentType, Error> {
+    Ok(match blob.read_u8()? {
+        code @ 0x02..=0x0D => ArgumentType::Primitive(code),
+        0x0E => ArgumentType::String,
+        0x1D => {
+            // Arrays of arrays can't be serialized, so this recurses at most once
+            if blob.first() == Some(&0x1D) {
+                return Err(unsupported_type());
+            }
+            ArgumentType::Array(Box::new(read_serialized_type(blob, resolver)?))
+        }
+        0x50 => ArgumentType::Type,
+        0x51 => ArgumentType::Boxed,
+        0x55 => {
+            let name = read_ser_string(blob)?.ok_or(unresolved_enum())?;
+            let underlying = resolver.resolve_enum_by_name(&name).ok_or(unresolved_enum())?;
+            ArgumentType::Enum(AttributeEnumType::Name(name), underlying_code(&underlying)?)
+        }
+        _ => return Err(unsupported_type()),
+    })
+}
+
+fn read_value<R: EnumResolver>(
+    blob: &mut &[u8],
+    typ: &ArgumentType,
+    resolver: &R,
+    depth: usize,
+) -> Result<AttributeValue, Error> {
+    if depth >= MAX_VALUE_DEPTH {
+        return Err(Error::InvalidMetadata("Custom attribute value is nested too deeply."));
+    }
+
+    Ok(match *typ {
+        ArgumentType::Primitive(code) => read_primitive(blob, code)?,
+        ArgumentType::String => AttributeValue::String(read_ser_string(blob)?),
+        ArgumentType::Type => AttributeValue::Type(read_ser_string(blob)?),
+        ArgumentType::Boxed => {
+            let actual = read_serialized_type(blob, resolver)?;
+            read_value(blob, &actual, resolver, depth + 1)?
+        }
+        ArgumentType::Enum(ref enum_type, code) => {
+            AttributeValue::Enum(enum_type.clone(), Box::new(read_primitive(blob, code)?))
+        }
+        ArgumentType::Array(ref element) => {
+            let len = blob.read_u32::<LittleEndian>()?;
+            if len == 0xFFFF_FFFF {
+                AttributeValue::Array(None)
+            } else {
+                // Don't trust the length for pre-allocation, every element takes at least one byte
+                if len as usize > blob.len() {
+                    return Err(Error::InvalidMetadata("Custom attribute array is longer than its value blob."));
+                }
+                let mut elements = Vec::new();
+                for _ in 0..len {
+                    elements.push(read_value(blob, element, resolver, depth + 1)?);
+                }
+                AttributeValue::Array(Some(elements))
+            }
+        }
+    })
+}
+
+fn read_primitive(blob: &mut &[u8], code: u8) -> Result<AttributeValue, Error> {
+    Ok(match code {
+        0x02 => AttributeValue::Boolean(blob.read_u8()? != 0),
+        0x03 => AttributeValue::Char(blob.read_u16::<LittleEndian>()?),
+        0x04 => AttributeValue::I1(blob.read_i8()?),
+        0x05 => AttributeValue::U1(blob.read_u8()?),
+        0x06 => AttributeValue::I2(blob.read_i16::<LittleEndian>()?),
+        0x07 => AttributeValue::U2(blob.read_u16::<LittleEndian>()?),
+        0x08 => AttributeValue::I4(blob.read_i32::<LittleEndian>()?),
+        0x09 => AttributeValue::U4(blob.read_u32::<LittleEndian>()?),
+        0x0A => AttributeValue::I8(blob.read_i64::<LittleEndian>()?),
+        0x0B => AttributeValue::U8(blob.read_u64::<LittleEndian>()?),
+        0x0C => AttributeValue::R4(blob.read_f32::<LittleEndian>()?),
+        0x0D => AttributeValue::R8(blob.read_f64::<LittleEndian>()?),
+        _ => return Err(unsupported_type()),
+    })
+}
+
+/// Reads a `SerString`: a compressed length followed by UTF-8, or a single 0xFF byte for a null string.
+fn read_ser_string(blob: &mut &[u8]) -> Result<Option<String>, Error> {
+    if blob.first() == Some(&0xFF) {
+        *blob = &blob[1..];
+        return Ok(None);
+    }
+    let len = utils::read_compressed_u32(blob)? as usize;
+    if len > blob.len() {
+        return Err(Error::InvalidMetadata("Custom attribute string is longer than its value blob."));
+    }
+    let (bytes, rest) = blob.split_at(len);
+    *blob = rest;
+    Ok(Some(String::from_utf8(bytes.to_vec())?))
+}
+
+/// Gets the element type code of the underlying type of an enum.
+fn underlying_code(typ: &TypeReference) -> Result<u8, Error> {
+    Ok(match *typ {
+        TypeReference::Boolean => 0x02,
+        TypeReference::Char => 0x03,
+        TypeReference::I1 => 0x04,
+        TypeReference::U1 => 0x05,
+        TypeReference::I2 => 0x06,
+        TypeReference::U2 => 0x07,
+        TypeReference::I4 => 0x08,
+        TypeReference::U4 => 0x09,
+        TypeReference::I8 => 0x0A,
+        TypeReference::U8 => 0x0B,
+        _ => return Err(Error::InvalidMetadata("An enum's underlying type must be an integral type.")),
+    })
+}
+
+fn unresolved_enum() -> Error {
+    Error::InvalidMetadata("Unable to find the underlying type of an enum in a custom attribute value.")
+}
+
+fn unsupported_type() -> Error {
+    Error::InvalidMetadata("Custom attribute value has a type that can't be serialized.")
+}
+
+impl fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            AttributeValue::Boolean(x) => write!(f, "bool({})", x),
+            AttributeValue::Char(x) => write!(f, "char(0x{:04X})", x),
+            AttributeValue::I1(x) => write!(f, "int8({})", x),
+            AttributeValue::U1(x) => write!(f, "uint8({})", x),
+            AttributeValue::I2(x) => write!(f, "int16({})", x),
+            AttributeValue::U2(x) => write!(f, "uint16({})", x),
+            AttributeValue::I4(x) => write!(f, "int32({})", x),
+            AttributeValue::U4(x) => write!(f, "uint32({})", x),
+            AttributeValue::I8(x) => write!(f, "int64({})", x),
+            AttributeValue::U8(x) => write!(f, "uint64({})", x),
+            AttributeValue::R4(x) => write!(f, "float32({})", x),
+            AttributeValue::R8(x) => write!(f, "float64({})", x),
+            AttributeValue::String(Some(ref x)) => write!(f, "{:?}", x),
+            AttributeValue::Type(Some(ref x)) => write!(f, "type({})", x),
+            AttributeValue::String(None) | AttributeValue::Type(None) | AttributeValue::Array(None) => {
+                write!(f, "nullref")
+            }
+            AttributeValue::Enum(AttributeEnumType::Handle(handle), ref value) => {
+                write!(f, "enum {}({})", handle, value)
+            }
+            AttributeValue::Enum(AttributeEnumType::Name(ref name), ref value) => write!(f, "enum {}({})", name, value),
+            AttributeValue::Array(Some(ref elements)) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cli::tables::TableIndex;
+
+    /// Knows that TypeRef 1 is `System.AttributeTargets`, an int32 enum.
+    struct Enums;
+
+    impl EnumResolver for Enums {
+        fn resolve_enum(&self, enum_type: TableHandle) -> Option<TypeReference> {
+            if enum_type == TableHandle::new(1, TableIndex::TypeRef) {
+                Some(TypeReference::I4)
+            } else {
+                None
+            }
+        }
+
+        fn resolve_enum_by_name(&self, name: &str) -> Option<TypeReference> {
+            match name.split(',').next() {
+                Some("System.AttributeTargets") => Some(TypeReference::I4),
+                _ => None,
+            }
+        }
+    }
+
+    fn constructor(signature: &[u8]) -> MethodSignature {
+        MethodSignature::read(&mut &signature[..]).unwrap()
+    }
+
+    #[test]
+    pub fn target_framework_attribute() {
+        // [assembly: TargetFramework(".NETCoreApp,Version=v6.0", FrameworkDisplayName = ".NET 6.0")], as emitted by
+        // the C# compiler
+        let blob = b"\x01\x00\x18.NETCoreApp,Version=v6.0\x01\x00\x54\x0E\x14FrameworkDisplayName\x08.NET 6.0";
+        let value = CustomAttributeValue::decode(&constructor(&[0x20, 0x01, 0x01, 0x0E]), blob, &Enums).unwrap();
+        assert_eq!(
+            CustomAttributeValue {
+                fixed_arguments: vec![AttributeValue::String(Some(".NETCoreApp,Version=v6.0".into()))],
+                named_arguments: vec![NamedArgument {
+                    kind: NamedArgumentKind::Property,
+                    name: "FrameworkDisplayName".into(),
+                    value: AttributeValue::String(Some(".NET 6.0".into())),
+                }],
+            },
+            value
+        );
+    }
+
+    #[test]
+    pub fn attribute_usage_attribute() {
+        // [AttributeUsage(AttributeTargets.Class, AllowMultiple = true)], where the constructor takes
+        // valuetype [TypeRef 1]
+        let blob = b"\x01\x00\x04\x00\x00\x00\x01\x00\x54\x02\x0DAllowMultiple\x01";
+        let signature = [0x20, 0x01, 0x01, 0x11, 0x05];
+        let value = CustomAttributeValue::decode(&constructor(&signature), blob, &Enums).unwrap();
+        let targets = AttributeEnumType::Handle(TableHandle::new(1, TableIndex::TypeRef));
+        assert_eq!(vec![AttributeValue::Enum(targets, Box::new(AttributeValue::I4(4)))], value.fixed_arguments);
+        assert_eq!(NamedArgumentKind::Property, value.named_arguments[0].kind);
+        assert_eq!("AllowMultiple", value.named_arguments[0].name);
+        assert_eq!(AttributeValue::Boolean(true), value.named_arguments[0].value);
+        assert_eq!("enum TypeRef[0x0001](int32(4))", format!("{}", value.fixed_arguments[0]));
+    }
+
+    #[test]
+    pub fn boxed_arrays_types_and_enum_names() {
+        // (object, string[], Type) = (int16[] { 1, 2 }, null, null), Field = AttributeTargets.All
+        let mut blob = b"\x01\x00\x51\x1D\x06\x02\x00\x00\x00\x01\x00\x02\x00".to_vec();
+        blob.extend_from_slice(b"\xFF\xFF\xFF\xFF\xFF\x01\x00\x53\x55");
+        blob.extend_from_slice(b"\x17System.AttributeTargets\x05Field\xFF\x7F\x00\x00");
+        let signature = [0x20, 0x03, 0x01, 0x1C, 0x1D, 0x0E, 0x12, 0x09];
+        let value = CustomAttributeValue::decode(&constructor(&signature), &blob, &Enums).unwrap();
+        assert_eq!(
+            vec![
+                AttributeValue::Array(Some(vec![AttributeValue::I2(1), AttributeValue::I2(2)])),
+                AttributeValue::Array(None),
+                AttributeValue::Type(None),
+            ],
+            value.fixed_arguments
+        );
+        let targets = AttributeEnumType::Name("System.AttributeTargets".into());
+        assert_eq!(
+            vec![NamedArgument {
+                kind: NamedArgumentKind::Field,
+                name: "Field".into(),
+                value: AttributeValue::Enum(targets, Box::new(AttributeValue::I4(0x7FFF))),
+            }],
+            value.named_arguments
+        );
+        assert_eq!("[int16(1), int16(2)]", format!("{}", value.fixed_arguments[0]));
+    }
+
+    #[test]
+    pub fn invalid_values() {
+        let string_constructor = constructor(&[0x20, 0x01, 0x01, 0x0E]);
+        let decode = |blob: &[u8]| CustomAttributeValue::decode(&string_constructor, blob, &Enums);
+        assert_eq!(
+            Err(Error::InvalidMetadata("Custom attribute value does not start with the prolog.")),
+            decode(b"\x02\x00\xFF\x00\x00")
+        );
+        assert_eq!(
+            Err(Error::InvalidMetadata("Custom attribute string is longer than its value blob.")),
+            decode(b"\x01\x00\x10abc\x00\x00")
+        );
+        assert!(decode(b"\x01\x00\xFF").is_err());
+        assert_eq!(Err(unsupported_type()), decode(b"\x01\x00\xFF\x01\x00\x53\x1D\x1D\x1D\x08"));
+
+        // An enum the resolver doesn't know about
+        let enum_constructor = constructor(&[0x20, 0x01, 0x01, 0x11, 0x09]);
+        let blob = b"\x01\x00\x00\x00\x00\x00\x00\x00";
+        assert_eq!(Err(unresolved_enum()), CustomAttributeValue::decode(&enum_constructor, blob, &Enums));
+
+        // Boxed arrays of boxed arrays, nested too deeply
+        let mut blob = vec![0x01, 0x00];
+        for _ in 0..20 {
+            blob.extend_from_slice(&[0x1D, 0x51, 0x01, 0x00, 0x00, 0x00]);
+        }
+        let object_constructor = constructor(&[0x20, 0x01, 0x01, 0x1C]);
+        assert_eq!(
+            Err(Error::InvalidMetadata("Custom attribute value is nested too deeply.")),
+            CustomAttributeValue::decode(&object_constructor, &blob, &Enums)
+        );
+    }
+}
@@ -0,0 +1,60 @@
+// We want GenericParamAttributes to use the same names as in the ECMA spec, which are PascalCased, not UPPER_SNAKE_CASE
+#![allow(non_upper_case_globals)]
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct GenericParamAttributes(u16);
+
+impl GenericParamAttributes {
+    pub fn new(value: u16) -> GenericParamAttributes {
+        GenericParamAttributes(value)
+    }
+
+    pub fn variance(self) -> GenericParamVariance {
+        match (self.0 & GenericParamVariance::MASK) >> GenericParamVariance::SHIFT {
+            0 => GenericParamVariance::None,
+            1 => GenericParamVariance::Covariant,
+            2 => GenericParamVariance::Contravariant,
+            _ => GenericParamVariance::Reserved,
+        }
+    }
+
+    pub fn constraints(self) -> GenericParamConstraints {
+        GenericParamConstraints::from_bits_truncate(self.0 & CONSTRAINTS_MASK)
+    }
+}
+
+impl ::std::fmt::Display for GenericParamAttributes {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        write!(f, "{}", self.variance())?;
+        if !self.constraints().is_empty() {
+            write!(f, " [{}]", self.constraints())?;
+        }
+        Ok(())
+    }
+}
+
+#[repr(u16)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GenericParamVariance {
+    None = 0,
+    Covariant = 1,
+    Contravariant = 2,
+    Reserved = 3,
+}
+impl_display_via_debug!(GenericParamVariance);
+
+impl GenericParamVariance {
+    const MASK: u16 = 0x0003;
+    const SHIFT: u16 = 0;
+}
+
+const CONSTRAINTS_MASK: u16 = 0x001C;
+
+bitflags! {
+    pub struct GenericParamConstraints : u16 {
+        const ReferenceTypeConstraint = 0x0004;
+        const NotNullableValueTypeConstraint = 0x0008;
+        const DefaultConstructorConstraint = 0x0010;
+    }
+}
+impl_display_via_debug!(GenericParamConstraints);
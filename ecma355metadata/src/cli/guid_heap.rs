@@ -1,4 +1,4 @@
-use std::mem;
+use std::{mem, slice};
 
 use error::Error;
 use Guid;
@@ -17,19 +17,20 @@ impl<'a> GuidHeap<'a> {
                 "GUID stream is not a multiple of 16 bytes in length.",
             ));
         }
-        Ok(GuidHeap { data: Some(unsafe { mem::transmute(data) }) })
+
+        // Guid is a plain 16-byte array, so it has no alignment requirement
+        let count = data.len() / mem::size_of::<Guid>();
+        let guids = unsafe { slice::from_raw_parts(data.as_ptr() as *const Guid, count) };
+        Ok(GuidHeap { data: Some(guids) })
     }
 
-    pub fn get(&self, idx: usize) -> Option<&Guid> {
+    pub fn get(&self, idx: usize) -> Option<&'a Guid> {
         if let Some(data) = self.data {
-            // Determine the offset into the GUID array
-            let offset = idx / 16;
-
-            // Bounds check
-            if offset == 0 || offset >= data.len() {
+            // GUID heap indexes are 1-based indexes into the array, not byte offsets
+            if idx == 0 || idx > data.len() {
                 None
             } else {
-                Some(&data[offset])
+                Some(&data[idx - 1])
             }
         } else {
             None
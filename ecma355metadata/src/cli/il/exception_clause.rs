@@ -0,0 +1,114 @@
+use std::fmt;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::tables::TableHandle;
+use error::Error;
+
+bitflags! {
+    pub struct ExceptionClauseFlags: u32 {
+        const FILTER = 0x0001;
+        const FINALLY = 0x0002;
+        const FAULT = 0x0004;
+    }
+}
+impl_display_via_debug!(ExceptionClauseFlags);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExceptionClauseKind {
+    /// A typed exception handler, catching exceptions of the type referenced by the handle.
+    Catch(TableHandle),
+
+    /// An exception filter, with the IL offset of the filter block.
+    Filter(u32),
+
+    Finally,
+    Fault,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExceptionClause {
+    pub kind: ExceptionClauseKind,
+    pub try_offset: u32,
+    pub try_length: u32,
+    pub handler_offset: u32,
+    pub handler_length: u32,
+}
+
+impl ExceptionClause {
+    pub const SMALL_SIZE: usize = 12;
+    pub const FAT_SIZE: usize = 24;
+
+    pub fn new(kind: ExceptionClauseKind, try_offset: u32, try_length: u32, handler_offset: u32, handler_length: u32) -> ExceptionClause {
+        ExceptionClause {
+            kind,
+            try_offset,
+            try_length,
+            handler_offset,
+            handler_length,
+        }
+    }
+
+    /// Reads a clause from a small exception handling section.
+    pub fn read_small(mut buf: &[u8]) -> Result<ExceptionClause, Error> {
+        let flags = buf.read_u16::<LittleEndian>()? as u32;
+        let try_offset = buf.read_u16::<LittleEndian>()? as u32;
+        let try_length = buf.read_u8()? as u32;
+        let handler_offset = buf.read_u16::<LittleEndian>()? as u32;
+        let handler_length = buf.read_u8()? as u32;
+        let kind = read_kind(flags, buf.read_u32::<LittleEndian>()?)?;
+        Ok(ExceptionClause::new(kind, try_offset, try_length, handler_offset, handler_length))
+    }
+
+    /// Reads a clause from a fat exception handling section.
+    pub fn read_fat(mut buf: &[u8]) -> Result<ExceptionClause, Error> {
+        let flags = buf.read_u32::<LittleEndian>()?;
+        let try_offset = buf.read_u32::<LittleEndian>()?;
+        let try_length = buf.read_u32::<LittleEndian>()?;
+        let handler_offset = buf.read_u32::<LittleEndian>()?;
+        let handler_length = buf.read_u32::<LittleEndian>()?;
+        let kind = read_kind(flags, buf.read_u32::<LittleEndian>()?)?;
+        Ok(ExceptionClause::new(kind, try_offset, try_length, handler_offset, handler_length))
+    }
+
+    /// Gets the offset just past the end of the protected block, saturating rather than overflowing on invalid clauses.
+    pub fn try_end(&self) -> u32 {
+        self.try_offset.saturating_add(self.try_length)
+    }
+
+    /// Gets the offset just past the end of the handler block, saturating rather than overflowing on invalid clauses.
+    pub fn handler_end(&self) -> u32 {
+        self.handler_offset.saturating_add(self.handler_length)
+    }
+}
+
+impl fmt::Display for ExceptionClause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, ".try IL_{:04x} to IL_{:04x} ", self.try_offset, self.try_end())?;
+        match self.kind {
+            ExceptionClauseKind::Catch(ref handle) => write!(f, "catch {} ", handle)?,
+            ExceptionClauseKind::Filter(offset) => write!(f, "filter IL_{:04x} ", offset)?,
+            ExceptionClauseKind::Finally => write!(f, "finally ")?,
+            ExceptionClauseKind::Fault => write!(f, "fault ")?,
+        }
+        write!(f, "handler IL_{:04x} to IL_{:04x}", self.handler_offset, self.handler_end())
+    }
+}
+
+fn read_kind(flags: u32, class_token_or_filter_offset: u32) -> Result<ExceptionClauseKind, Error> {
+    let flags = ExceptionClauseFlags::from_bits(flags)
+        .ok_or(Error::InvalidMetadata("Exception clause has unknown flags."))?;
+    if flags.is_empty() {
+        let handle = TableHandle::from_token(class_token_or_filter_offset)
+            .ok_or(Error::InvalidMetadata("Exception clause has an invalid class token."))?;
+        Ok(ExceptionClauseKind::Catch(handle))
+    } else if flags == ExceptionClauseFlags::FILTER {
+        Ok(ExceptionClauseKind::Filter(class_token_or_filter_offset))
+    } else if flags == ExceptionClauseFlags::FINALLY {
+        Ok(ExceptionClauseKind::Finally)
+    } else if flags == ExceptionClauseFlags::FAULT {
+        Ok(ExceptionClauseKind::Fault)
+    } else {
+        Err(Error::InvalidMetadata("Exception clause has more than one kind."))
+    }
+}
@@ -0,0 +1,571 @@
+use std::collections::HashSet;
+use std::ops::Deref;
+
+use cli::il::verifier::{CallSite, CallSiteResolver, FieldSite, RuntimeHandleKind, StackType};
+use cli::signatures::{FieldSignature, LocalVarSignature, MethodSignature, MethodSpecSignature, SignatureKind,
+                      TypeReference};
+use cli::tables::{self, TableHandle, TableIndex};
+use metadata_image::MetadataImage;
+
+/// Whether a type is a class, a value type or an enum.
+enum TypeKind {
+    Class,
+    ValueType,
+    Enum(StackType),
+}
+
+/// Resolves the tokens in an image's method bodies for the verifier, using the image's metadata.
+///
+/// Whether a TypeRef is a class, value type or enum is looked up by name in the referenced images, if they are
+/// provided. Otherwise the primitive types in System are recognized by name, and other TypeRefs are value types if
+/// the image's signatures ever refer to them as one, and classes if not. Enums from other assemblies can only be
+/// recognized with their referenced image.
+pub struct ImageResolver<'a, D: 'a + Deref<Target = [u8]>> {
+    image: &'a MetadataImage<D>,
+    references: Vec<&'a MetadataImage<D>>,
+
+    /// The TypeRef rows that the image's signatures refer to as value types.
+    value_type_refs: HashSet<usize>,
+
+    /// The TypeDef row that each MethodDef and Field row belongs to, or zero if it couldn't be found.
+    method_owners: Vec<usize>,
+    field_owners: Vec<usize>,
+}
+
+impl<'a, D: 'a + Deref<Target = [u8]>> ImageResolver<'a, D> {
+    pub fn new(image: &'a MetadataImage<D>) -> ImageResolver<'a, D> {
+        ImageResolver::with_references(image, Vec::new())
+    }
+
+    /// Creates a resolver that looks up the TypeRefs of `image` in the images it references, such as the framework
+    /// assemblies.
+    pub fn with_references(image: &'a MetadataImage<D>, references: Vec<&'a MetadataImage<D>>) -> ImageResolver<'a, D> {
+        let type_def_count = image.table::<tables::TypeDefDecoder>().len();
+        let mut method_owners = vec![0; image.metadata_sizes().row_count(TableIndex::MethodDef)];
+        let mut field_owners = vec![0; image.metadata_sizes().row_count(TableIndex::Field)];
+        for index in 1..=type_def_count {
+            let type_def = TableHandle::new(index, TableIndex::TypeDef);
+            for method in image.type_def_methods(type_def).into_iter().flatten() {
+                if let Some(owner) = method_owners.get_mut(method.index().wrapping_sub(1)) {
+                    *owner = index;
+                }
+            }
+            for field in image.type_def_fields(type_def).into_iter().flatten() {
+                if let Some(owner) = field_owners.get_mut(field.index().wrapping_sub(1)) {
+                    *owner = index;
+                }
+            }
+        }
+
+        ImageResolver {
+            image,
+            references,
+            value_type_refs: value_type_refs(image),
+            method_owners,
+            field_owners,
+        }
+    }
+
+    /// Gets the stack type of a value of the type referred to by a TypeDef, TypeRef or TypeSpec.
+    fn value_type(&self, handle: TableHandle) -> Option<StackType> {
+        let kind = match handle.table() {
+            TableIndex::TypeSpec => {
+                let row = self.image.table::<tables::TypeSpecDecoder>().get(handle.index()).ok()?;
+                let spec = TypeReference::read(&mut self.image.read_blob(row.signature).ok()?).ok()?;
+                return self.stack_type(&spec, &[], &[]);
+            }
+            TableIndex::TypeDef => {
+                let (namespace, name) = self.image.type_name(handle).ok()?;
+                if let Some(primitive) = primitive_type(namespace, name) {
+                    return Some(primitive);
+                }
+                type_def_kind(self.image, handle)?
+            }
+            TableIndex::TypeRef => {
+                let (namespace, name) = self.image.type_name(handle).ok()?;
+                if let Some(primitive) = primitive_type(namespace, name) {
+                    return Some(primitive);
+                }
+                let reference = self.references
+                    .iter()
+                    .filter_map(|r| r.find_type_def(namespace, name).map(|t| (r, t)))
+                    .next();
+                match reference {
+                    Some((reference, type_def)) => type_def_kind(reference, type_def)?,
+                    None if self.value_type_refs.contains(&handle.index()) => TypeKind::ValueType,
+                    None => TypeKind::Class,
+                }
+            }
+            _ => return None,
+        };
+        Some(match kind {
+            TypeKind::Class => StackType::Object,
+            TypeKind::ValueType => StackType::ValueType(handle.token()),
+            TypeKind::Enum(underlying) => underlying,
+        })
+    }
+
+    /// Gets the stack type of a type in a signature, substituting the arguments of the generic type and method.
+    fn stack_type(
+        &self,
+        type_reference: &TypeReference,
+        type_args: &[StackType],
+        method_args: &[StackType],
+    ) -> Option<StackType> {
+        Some(match *type_reference {
+            TypeReference::Boolean | TypeReference::Char | TypeReference::I1 | TypeReference::U1 |
+            TypeReference::I2 | TypeReference::U2 | TypeReference::I4 | TypeReference::U4 => StackType::Int32,
+            TypeReference::I8 | TypeReference::U8 => StackType::Int64,
+            TypeReference::I | TypeReference::U | TypeReference::Ptr(..) | TypeReference::FnPtr(_) => {
+                StackType::NativeInt
+            }
+            TypeReference::R4 | TypeReference::R8 => StackType::Float,
+            TypeReference::String | TypeReference::Object | TypeReference::Class(_) | TypeReference::Array(..) |
+            TypeReference::SzArray(..) => StackType::Object,
+            TypeReference::ValueType(handle) => self.value_type(handle)?,
+            TypeReference::GenericInst(ref generic, _) => match **generic {
+                TypeReference::ValueType(handle) => self.value_type(handle)?,
+                _ => StackType::Object,
+            },
+            TypeReference::ByRef(ref inner) => {
+                StackType::ByRef(Box::new(self.stack_type(inner, type_args, method_args)?))
+            }
+            TypeReference::TypedByRef => StackType::TypedReference,
+            TypeReference::Var(index) => type_args.get(index as usize).cloned().unwrap_or(StackType::Var(index)),
+            TypeReference::MVar(index) => method_args.get(index as usize).cloned().unwrap_or(StackType::MVar(index)),
+            TypeReference::Void | TypeReference::End | TypeReference::Sentinel => return None,
+        })
+    }
+
+    /// Gets the type that declares a member with the specified MemberRef parent, along with the arguments of the
+    /// generic type instantiation it belongs to. Global functions and methods on arrays have no declaring type.
+    fn member_ref_parent(&self, class: TableHandle) -> Option<(Option<TableHandle>, Vec<StackType>)> {
+        match class.table() {
+            TableIndex::TypeDef | TableIndex::TypeRef => Some((Some(class), Vec::new())),
+            TableIndex::TypeSpec => {
+                let row = self.image.table::<tables::TypeSpecDecoder>().get(class.index()).ok()?;
+                match TypeReference::read(&mut self.image.read_blob(row.signature).ok()?).ok()? {
+                    TypeReference::GenericInst(generic, args) => {
+                        let generic = match *generic {
+                            TypeReference::Class(handle) | TypeReference::ValueType(handle) => handle,
+                            _ => return None,
+                        };
+                        let args: Option<Vec<_>> = args.iter().map(|a| self.stack_type(a, &[], &[])).collect();
+                        Some((Some(generic), args?))
+                    }
+                    _ => Some((None, Vec::new())),
+                }
+            }
+            TableIndex::ModuleRef => Some((None, Vec::new())),
+            TableIndex::MethodDef => Some((Some(self.owner(&self.method_owners, class)?), Vec::new())),
+            _ => None,
+        }
+    }
+
+    fn owner(&self, owners: &[usize], member: TableHandle) -> Option<TableHandle> {
+        match owners.get(member.index().wrapping_sub(1)) {
+            Some(&owner) if owner != 0 => Some(TableHandle::new(owner, TableIndex::TypeDef)),
+            _ => None,
+        }
+    }
+
+    /// Gets the type of `this` for the members of a type: a managed pointer for value types, and an object
+    /// reference otherwise.
+    fn this_type(&self, declaring_type: Option<TableHandle>) -> Option<StackType> {
+        match declaring_type {
+            Some(handle) => match self.value_type(handle)? {
+                StackType::Object => Some(StackType::Object),
+                value => Some(StackType::ByRef(Box::new(value))),
+            },
+            None => Some(StackType::Object),
+        }
+    }
+
+    fn has_base_class(&self, declaring_type: Option<TableHandle>) -> bool {
+        let handle = match declaring_type {
+            Some(handle) => handle,
+            None => return false,
+        };
+        let extends = match handle.table() {
+            TableIndex::TypeDef => match self.image.table::<tables::TypeDefDecoder>().get(handle.index()) {
+                Ok(row) => row.extends.index() != 0,
+                Err(_) => false,
+            },
+            _ => self.image.type_name(handle).ok() != Some(("System", "Object")),
+        };
+        extends && self.value_type(handle) == Some(StackType::Object)
+    }
+
+    fn call_site(
+        &self,
+        signature: &MethodSignature,
+        name: &str,
+        declaring_type: Option<TableHandle>,
+        type_args: &[StackType],
+        method_args: &[StackType],
+    ) -> Option<CallSite> {
+        // With an explicit this, its type is the first parameter
+        let this_type = if signature.header.has_this() && !signature.header.explicit_this() {
+            Some(self.this_type(declaring_type)?)
+        } else {
+            None
+        };
+        let parameters: Option<Vec<_>> = signature
+            .parameters
+            .iter()
+            .map(|p| self.stack_type(&p.type_reference, type_args, method_args))
+            .collect();
+        let return_type = match signature.return_type.type_reference {
+            TypeReference::Void => None,
+            ref t => Some(self.stack_type(t, type_args, method_args)?),
+        };
+        let is_constructor = this_type.is_some() && name == ".ctor";
+        Some(CallSite {
+            this_type,
+            parameters: parameters?,
+            return_type,
+            is_constructor,
+            has_base_class: self.has_base_class(declaring_type),
+        })
+    }
+
+    fn method_site(&self, method: TableHandle, method_args: &[StackType]) -> Option<CallSite> {
+        match method.table() {
+            TableIndex::MethodDef => {
+                let row = self.image.table::<tables::MethodDefDecoder>().get(method.index()).ok()?;
+                let signature = MethodSignature::read(&mut self.image.read_blob(row.signature).ok()?).ok()?;
+                let name = self.image.read_string(row.name).ok()?;
+                let owner = self.owner(&self.method_owners, method)?;
+                self.call_site(&signature, name, Some(owner), &[], method_args)
+            }
+            TableIndex::MemberRef => {
+                let row = self.image.table::<tables::MemberRefDecoder>().get(method.index()).ok()?;
+                let blob = self.image.read_blob(row.signature).ok()?;
+                if blob.first() == Some(&(SignatureKind::Field as u8)) {
+                    return None;
+                }
+                let signature = MethodSignature::read(&mut &blob[..]).ok()?;
+                let name = self.image.read_string(row.name).ok()?;
+                let (declaring_type, type_args) = self.member_ref_parent(row.class)?;
+                self.call_site(&signature, name, declaring_type, &type_args, method_args)
+            }
+            TableIndex::MethodSpec => {
+                let row = self.image.table::<tables::MethodSpecDecoder>().get(method.index()).ok()?;
+                let instantiation = MethodSpecSignature::read(&mut self.image.read_blob(row.instantiation).ok()?).ok()?;
+                let args: Option<Vec<_>> =
+                    instantiation.arguments.iter().map(|a| self.stack_type(a, &[], &[])).collect();
+                self.method_site(row.method, &args?)
+            }
+            TableIndex::StandAloneSig => {
+                let row = self.image.table::<tables::StandAloneSigDecoder>().get(method.index()).ok()?;
+                let signature = MethodSignature::read(&mut self.image.read_blob(row.signature).ok()?).ok()?;
+                self.call_site(&signature, "", None, &[], &[])
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a, D: 'a + Deref<Target = [u8]>> CallSiteResolver for ImageResolver<'a, D> {
+    fn resolve_call_site(&self, token: u32) -> Option<CallSite> {
+        self.method_site(TableHandle::from_token(token)?, &[])
+    }
+
+    fn resolve_field(&self, token: u32) -> Option<FieldSite> {
+        let handle = TableHandle::from_token(token)?;
+        let (signature, declaring_type, type_args) = match handle.table() {
+            TableIndex::Field => {
+                let row = self.image.table::<tables::FieldDecoder>().get(handle.index()).ok()?;
+                let owner = self.owner(&self.field_owners, handle)?;
+                (row.signature, Some(owner), Vec::new())
+            }
+            TableIndex::MemberRef => {
+                let row = self.image.table::<tables::MemberRefDecoder>().get(handle.index()).ok()?;
+                let (declaring_type, type_args) = self.member_ref_parent(row.class)?;
+                (row.signature, declaring_type, type_args)
+            }
+            _ => return None,
+        };
+        let signature = FieldSignature::read(&mut self.image.read_blob(signature).ok()?).ok()?;
+        Some(FieldSite {
+            instance_type: self.this_type(declaring_type)?,
+            field_type: self.stack_type(&signature.field_type, &type_args, &[])?,
+        })
+    }
+
+    fn resolve_type(&self, token: u32) -> Option<StackType> {
+        self.value_type(TableHandle::from_token(token)?)
+    }
+
+    fn resolve_locals(&self, token: u32) -> Option<Vec<StackType>> {
+        let handle = TableHandle::from_token(token).filter(|h| h.table() == TableIndex::StandAloneSig)?;
+        let row = self.image.table::<tables::StandAloneSigDecoder>().get(handle.index()).ok()?;
+        let signature = LocalVarSignature::read(&mut self.image.read_blob(row.signature).ok()?).ok()?;
+        signature.locals.iter().map(|l| self.stack_type(&l.local_type, &[], &[])).collect()
+    }
+
+    fn resolve_runtime_handle(&self, kind: RuntimeHandleKind) -> Option<StackType> {
+        let name = match kind {
+            RuntimeHandleKind::Type => "RuntimeTypeHandle",
+            RuntimeHandleKind::Method => "RuntimeMethodHandle",
+            RuntimeHandleKind::Field => "RuntimeFieldHandle",
+            RuntimeHandleKind::Argument => "RuntimeArgumentHandle",
+        };
+        let type_ref = (1..=self.image.table::<tables::TypeRefDecoder>().len())
+            .map(|index| TableHandle::new(index, TableIndex::TypeRef))
+            .find(|&handle| self.image.type_name(handle).ok() == Some(("System", name)));
+        let handle = type_ref.or_else(|| self.image.find_type_def("System", name))?;
+        Some(StackType::ValueType(handle.token()))
+    }
+}
+
+/// Gets the stack type of the primitive value types, which are always recognized by name.
+fn primitive_type(namespace: &str, name: &str) -> Option<StackType> {
+    if namespace != "System" {
+        return None;
+    }
+    match name {
+        "Boolean" | "Char" | "SByte" | "Byte" | "Int16" | "UInt16" | "Int32" | "UInt32" => Some(StackType::Int32),
+        "Int64" | "UInt64" => Some(StackType::Int64),
+        "IntPtr" | "UIntPtr" => Some(StackType::NativeInt),
+        "Single" | "Double" => Some(StackType::Float),
+        "TypedReference" => Some(StackType::TypedReference),
+        _ => None,
+    }
+}
+
+/// Determines the kind of a TypeDef from its base type. System.Enum and System.ValueType are themselves classes.
+fn type_def_kind<D: Deref<Target = [u8]>>(image: &MetadataImage<D>, type_def: TableHandle) -> Option<TypeKind> {
+    let row = image.table::<tables::TypeDefDecoder>().get(type_def.index()).ok()?;
+    let base = match row.extends.table() {
+        TableIndex::TypeDef | TableIndex::TypeRef if row.extends.index() != 0 => image.type_name(row.extends).ok()?,
+        _ => return Some(TypeKind::Class),
+    };
+    if image.type_name(type_def).ok()? == ("System", "Enum") {
+        return Some(TypeKind::Class);
+    }
+    Some(match base {
+        ("System", "Enum") => match image.enum_underlying_type(type_def) {
+            Ok(Some(ref underlying)) => TypeKind::Enum(match *underlying {
+                TypeReference::I8 | TypeReference::U8 => StackType::Int64,
+                TypeReference::I | TypeReference::U => StackType::NativeInt,
+                TypeReference::R4 | TypeReference::R8 => StackType::Float,
+                _ => StackType::Int32,
+            }),
+            _ => TypeKind::ValueType,
+        },
+        ("System", "ValueType") => TypeKind::ValueType,
+        _ => TypeKind::Class,
+    })
+}
+
+/// Finds the TypeRefs that the image's signatures refer to as value types.
+fn value_type_refs<D: Deref<Target = [u8]>>(image: &MetadataImage<D>) -> HashSet<usize> {
+    let mut types = Vec::new();
+    {
+        let mut read = |blob: Result<&[u8], _>| {
+            let mut blob = match blob {
+                Ok(blob) => blob,
+                Err(_) => return,
+            };
+            let signature_types = match blob.first().cloned() {
+                Some(kind) if kind == SignatureKind::Field as u8 => {
+                    FieldSignature::read(&mut blob).map(|s| vec![s.field_type])
+                }
+                Some(kind) if kind == SignatureKind::LocalVariables as u8 => {
+                    LocalVarSignature::read(&mut blob).map(|s| s.locals.into_iter().map(|l| l.local_type).collect())
+                }
+                Some(kind) if kind == SignatureKind::MethodSpecification as u8 => {
+                    MethodSpecSignature::read(&mut blob).map(|s| s.arguments)
+                }
+                _ => MethodSignature::read(&mut blob).map(|s| {
+                    let mut types: Vec<_> = s.parameters.into_iter().map(|p| p.type_reference).collect();
+                    types.push(s.return_type.type_reference);
+                    types
+                }),
+            };
+            types.extend(signature_types.into_iter().flatten());
+        };
+
+        for row in image.table::<tables::FieldDecoder>().iter().flatten() {
+            read(image.read_blob(row.signature));
+        }
+        for row in image.table::<tables::MethodDefDecoder>().iter().flatten() {
+            read(image.read_blob(row.signature));
+        }
+        for row in image.table::<tables::MemberRefDecoder>().iter().flatten() {
+            read(image.read_blob(row.signature));
+        }
+        for row in image.table::<tables::StandAloneSigDecoder>().iter().flatten() {
+            read(image.read_blob(row.signature));
+        }
+        for row in image.table::<tables::MethodSpecDecoder>().iter().flatten() {
+            read(image.read_blob(row.instantiation));
+        }
+    }
+    for row in image.table::<tables::TypeSpecDecoder>().iter().flatten() {
+        if let Ok(spec) = image.read_blob(row.signature).and_then(|mut b| TypeReference::read(&mut b)) {
+            types.push(spec);
+        }
+    }
+
+    let mut refs = HashSet::new();
+    while let Some(type_reference) = types.pop() {
+        match type_reference {
+            TypeReference::ValueType(handle) if handle.table() == TableIndex::TypeRef => {
+                refs.insert(handle.index());
+            }
+            TypeReference::Ptr(_, inner) | TypeReference::ByRef(inner) | TypeReference::Array(inner, _) |
+            TypeReference::SzArray(_, inner) => types.push(*inner),
+            TypeReference::GenericInst(generic, args) => {
+                types.push(*generic);
+                types.extend(args);
+            }
+            TypeReference::FnPtr(signature) => {
+                let signature = *signature;
+                types.extend(signature.parameters.into_iter().map(|p| p.type_reference));
+                types.push(signature.return_type.type_reference);
+            }
+            _ => {}
+        }
+    }
+    refs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cli::il::verifier::{self, VerificationErrorKind};
+    use test_metadata::{build_code_image, write_u16, write_u32, CODE_RVA};
+
+    /// Builds an image with `class Program` and a TypeRef to the value type System.Guid, where Program has an int32
+    /// field, `static void Main()`, `static int32 Add(int32, int32)` and a constructor, with the specified tiny bodies
+    /// for Add and the constructor.
+    fn program_image(add: &[u8], ctor: &[u8]) -> MetadataImage<Vec<u8>> {
+        let strings = b"\0<Module>\0System\0Object\0Program\0Main\0Add\0.ctor\0count\0Guid\0";
+        // int32 field, Main, Add, the constructors and the locals (int32, class Program, valuetype Guid)
+        let blobs = [0, 2, 0x06, 0x08, 3, 0x00, 0x00, 0x01, 5, 0x00, 0x02, 0x08, 0x08, 0x08, 3, 0x20, 0x00, 0x01, 7,
+                     0x07, 0x03, 0x08, 0x12, 0x08, 0x11, 0x09];
+
+        // Main: newobj Program::.ctor; stloc.1; ldc.i4.1; ldc.i4.2; call Add; stloc.0; ret
+        let mut code = Vec::new();
+        write_u16(&mut code, 0x3013);
+        write_u16(&mut code, 2);
+        write_u32(&mut code, 15);
+        write_u32(&mut code, 0x11000001);
+        code.extend_from_slice(&[0x73, 0x03, 0x00, 0x00, 0x06, 0x0B, 0x17, 0x18, 0x28, 0x02, 0x00, 0x00, 0x06, 0x0A]);
+        code.push(0x2A);
+        let mut rvas = vec![CODE_RVA];
+        for body in &[add, ctor] {
+            code.resize((code.len() + 3) & !3, 0);
+            rvas.push(CODE_RVA + code.len() as u32);
+            code.push(((body.len() as u8) << 2) | 2);
+            code.extend_from_slice(body);
+        }
+
+        let mut module = Vec::new();
+        for &val in &[0, 1, 1, 0, 0] {
+            write_u16(&mut module, val);
+        }
+        let mut type_refs = Vec::new();
+        for &val in &[0, 17, 10, 0, 53, 10] {
+            write_u16(&mut type_refs, val);
+        }
+        let mut type_defs = Vec::new();
+        for &(name, extends) in &[(1, 0), (24, 5)] {
+            write_u32(&mut type_defs, 0);
+            for &val in &[name, 0, extends, 1, 1] {
+                write_u16(&mut type_defs, val);
+            }
+        }
+        let mut fields = Vec::new();
+        for &val in &[0x0001, 47, 1] {
+            write_u16(&mut fields, val);
+        }
+        let mut methods = Vec::new();
+        let rows = [(0x0016, 32, 4), (0x0016, 37, 8), (0x1806, 41, 14)];
+        for (&rva, &(flags, name, signature)) in rvas.iter().zip(&rows) {
+            write_u32(&mut methods, rva);
+            for &val in &[0, flags, name, signature, 1] {
+                write_u16(&mut methods, val);
+            }
+        }
+        let mut member_refs = Vec::new();
+        for &val in &[0x0009, 41, 14] {
+            write_u16(&mut member_refs, val);
+        }
+        let mut stand_alone_sigs = Vec::new();
+        write_u16(&mut stand_alone_sigs, 18);
+
+        let tables = vec![
+            (TableIndex::Module, 1, module),
+            (TableIndex::TypeRef, 2, type_refs),
+            (TableIndex::TypeDef, 2, type_defs),
+            (TableIndex::Field, 1, fields),
+            (TableIndex::MethodDef, 3, methods),
+            (TableIndex::MemberRef, 1, member_refs),
+            (TableIndex::StandAloneSig, 1, stand_alone_sigs),
+        ];
+        MetadataImage::load_data(build_code_image(&tables, strings, &blobs, &code)).unwrap()
+    }
+
+    fn verify_method(image: &MetadataImage<Vec<u8>>, index: usize) -> Vec<(u32, VerificationErrorKind)> {
+        let method = TableHandle::new(index, TableIndex::MethodDef);
+        let body = image.method_body(method).unwrap().unwrap();
+        verifier::verify(method.token(), &body, &ImageResolver::new(image))
+            .into_iter()
+            .map(|e| (e.offset, e.kind))
+            .collect()
+    }
+
+    // ldarg.0; ldarg.1; add; ret
+    const ADD: &[u8] = &[0x02, 0x03, 0x58, 0x2A];
+
+    // ldarg.0; call Object::.ctor; ldarg.0; ldc.i4.0; stfld count; ret
+    const CTOR: &[u8] = &[0x02, 0x28, 0x01, 0x00, 0x00, 0x0A, 0x02, 0x16, 0x7D, 0x01, 0x00, 0x00, 0x04, 0x2A];
+
+    #[test]
+    pub fn resolve_tokens() {
+        let image = program_image(ADD, CTOR);
+        let resolver = ImageResolver::new(&image);
+
+        assert_eq!(
+            Some(vec![StackType::Int32, StackType::Object, StackType::ValueType(0x01000002)]),
+            resolver.resolve_locals(0x11000001)
+        );
+        assert_eq!(None, resolver.resolve_locals(0x11000002));
+        assert_eq!(
+            Some(FieldSite { instance_type: StackType::Object, field_type: StackType::Int32 }),
+            resolver.resolve_field(0x04000001)
+        );
+        assert_eq!(Some(StackType::Object), resolver.resolve_type(0x02000002));
+
+        let add = resolver.resolve_call_site(0x06000002).unwrap();
+        assert_eq!((None, vec![StackType::Int32; 2]), (add.this_type, add.parameters));
+        assert_eq!(Some(StackType::Int32), add.return_type);
+
+        let ctor = resolver.resolve_call_site(0x06000003).unwrap();
+        assert_eq!(Some(StackType::Object), ctor.this_type);
+        assert!(ctor.is_constructor && ctor.has_base_class);
+        let object_ctor = resolver.resolve_call_site(0x0A000001).unwrap();
+        assert!(object_ctor.is_constructor && !object_ctor.has_base_class);
+        assert_eq!(None, resolver.resolve_call_site(0x04000001));
+    }
+
+    #[test]
+    pub fn verify_image_methods() {
+        let image = program_image(ADD, CTOR);
+        for index in 1..4 {
+            assert_eq!(Vec::<(u32, VerificationErrorKind)>::new(), verify_method(&image, index));
+        }
+
+        // ldarg.0; ldstr; add; ret
+        let image = program_image(&[0x02, 0x72, 0x01, 0x00, 0x00, 0x70, 0x58, 0x2A], &CTOR[6..]);
+        assert_eq!(
+            vec![(6, VerificationErrorKind::InvalidOperandTypes(StackType::Int32, StackType::Object))],
+            verify_method(&image, 2)
+        );
+        assert_eq!(vec![(7, VerificationErrorKind::ThisNotInitialized)], verify_method(&image, 3));
+    }
+}
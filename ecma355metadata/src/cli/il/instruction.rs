@@ -0,0 +1,246 @@
+use std::fmt;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::il::{Opcode, OperandType};
+use error::Error;
+
+#[derive(Debug, PartialEq)]
+pub enum Operand {
+    None,
+    Int8(i8),
+    Int32(i32),
+    Int64(i64),
+    Float32(f32),
+    Float64(f64),
+
+    /// A metadata token, referring to a method, field, type, string or signature.
+    Token(u32),
+
+    /// The IL offset of a branch target, relative to the start of the method body.
+    ///
+    /// Branch targets aren't checked when decoding, so this may be negative or past the end of the method.
+    BranchTarget(i64),
+
+    /// The IL offsets of the targets of a `switch` instruction.
+    Switch(Vec<i64>),
+
+    /// The index of an argument or local variable.
+    Variable(u16),
+}
+
+/// A single IL instruction, decoded from a method body.
+#[derive(Debug, PartialEq)]
+pub struct Instruction {
+    pub offset: u32,
+    pub size: u32,
+    pub opcode: Opcode,
+    pub operand: Operand,
+}
+
+impl Instruction {
+    /// Gets the offset of the instruction that immediately follows this one.
+    pub fn next_offset(&self) -> u32 {
+        self.offset + self.size
+    }
+
+    /// Gets the branch targets of the instruction, if it is a branch, `leave` or `switch` instruction.
+    pub fn branch_targets(&self) -> &[i64] {
+        match self.operand {
+            Operand::BranchTarget(ref target) => ::std::slice::from_ref(target),
+            Operand::Switch(ref targets) => targets,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "IL_{:04x}: {}", self.offset, self.opcode)?;
+        match self.operand {
+            Operand::None => Ok(()),
+            Operand::Int8(x) => write!(f, " {}", x),
+            Operand::Int32(x) => write!(f, " {}", x),
+            Operand::Int64(x) => write!(f, " {}", x),
+            Operand::Float32(x) => write!(f, " {}", x),
+            Operand::Float64(x) => write!(f, " {}", x),
+            Operand::Token(x) => write!(f, " 0x{:08X}", x),
+            Operand::BranchTarget(x) => write!(f, " IL_{:04x}", x),
+            Operand::Switch(ref targets) => {
+                write!(f, " (")?;
+                for (i, x) in targets.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "IL_{:04x}", x)?;
+                }
+                write!(f, ")")
+            }
+            Operand::Variable(x) => write!(f, " {}", x),
+        }
+    }
+}
+
+/// Decodes the instructions in the code of a method body, in order.
+///
+/// Iteration stops after the first invalid or truncated instruction.
+pub struct InstructionReader<'a> {
+    code: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> InstructionReader<'a> {
+    pub fn new(code: &'a [u8]) -> InstructionReader<'a> {
+        InstructionReader { code, offset: 0 }
+    }
+
+    fn read_instruction(&self) -> Result<Instruction, Error> {
+        let mut buf = &self.code[self.offset..];
+        let first = buf.read_u8()?;
+        let value = if first == Opcode::EXTENDED_PREFIX {
+            0xFE00 | (buf.read_u8().map_err(|_| truncated())? as u16)
+        } else {
+            first as u16
+        };
+        let opcode = Opcode::from_value(value).ok_or(Error::InvalidMetadata("Unknown IL opcode."))?;
+
+        let operand_start = buf.len();
+        let operand = read_operand(opcode, &mut buf).map_err(|_| truncated())?;
+        let size = opcode.size() + (operand_start - buf.len());
+
+        // Branch targets are relative to the start of the next instruction
+        let next_offset = (self.offset + size) as i64;
+        let operand = match operand {
+            Operand::BranchTarget(delta) => Operand::BranchTarget(next_offset + delta),
+            Operand::Switch(deltas) => Operand::Switch(deltas.into_iter().map(|d| next_offset + d).collect()),
+            x => x,
+        };
+
+        Ok(Instruction {
+            offset: self.offset as u32,
+            size: size as u32,
+            opcode,
+            operand,
+        })
+    }
+}
+
+impl<'a> Iterator for InstructionReader<'a> {
+    type Item = Result<Instruction, Error>;
+
+    fn next(&mut self) -> Option<Result<Instruction, Error>> {
+        if self.offset >= self.code.len() {
+            return None;
+        }
+
+        let result = self.read_instruction();
+        match result {
+            Ok(ref instruction) => self.offset += instruction.size as usize,
+            Err(_) => self.offset = self.code.len(),
+        }
+        Some(result)
+    }
+}
+
+fn read_operand(opcode: Opcode, buf: &mut &[u8]) -> Result<Operand, Error> {
+    Ok(match opcode.operand_type() {
+        OperandType::InlineNone => Operand::None,
+        OperandType::ShortInlineI => Operand::Int8(buf.read_i8()?),
+        OperandType::InlineI => Operand::Int32(buf.read_i32::<LittleEndian>()?),
+        OperandType::InlineI8 => Operand::Int64(buf.read_i64::<LittleEndian>()?),
+        OperandType::ShortInlineR => Operand::Float32(buf.read_f32::<LittleEndian>()?),
+        OperandType::InlineR => Operand::Float64(buf.read_f64::<LittleEndian>()?),
+        OperandType::ShortInlineBrTarget => Operand::BranchTarget(buf.read_i8()? as i64),
+        OperandType::InlineBrTarget => Operand::BranchTarget(buf.read_i32::<LittleEndian>()? as i64),
+        OperandType::InlineMethod
+        | OperandType::InlineField
+        | OperandType::InlineType
+        | OperandType::InlineString
+        | OperandType::InlineSig
+        | OperandType::InlineTok => Operand::Token(buf.read_u32::<LittleEndian>()?),
+        OperandType::InlineSwitch => {
+            let count = buf.read_u32::<LittleEndian>()? as usize;
+
+            // Check the length up front, so a bogus count can't cause a huge allocation
+            if buf.len() / 4 < count {
+                return Err(truncated());
+            }
+            let mut targets = Vec::with_capacity(count);
+            for _ in 0..count {
+                targets.push(buf.read_i32::<LittleEndian>()? as i64);
+            }
+            Operand::Switch(targets)
+        }
+        OperandType::ShortInlineVar => Operand::Variable(buf.read_u8()? as u16),
+        OperandType::InlineVar => Operand::Variable(buf.read_u16::<LittleEndian>()?),
+    })
+}
+
+fn truncated() -> Error {
+    Error::InvalidMetadata("IL instruction extends past the end of the method body.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(code: &[u8]) -> Result<Vec<Instruction>, Error> {
+        InstructionReader::new(code).collect()
+    }
+
+    #[test]
+    pub fn decode_simple_method() {
+        // ldstr 0x70000001; call 0x0A000002; ret
+        let instructions = decode(&[0x72, 0x01, 0x00, 0x00, 0x70, 0x28, 0x02, 0x00, 0x00, 0x0A, 0x2A]).unwrap();
+        assert_eq!(
+            vec![
+                Instruction { offset: 0, size: 5, opcode: Opcode::Ldstr, operand: Operand::Token(0x70000001) },
+                Instruction { offset: 5, size: 5, opcode: Opcode::Call, operand: Operand::Token(0x0A000002) },
+                Instruction { offset: 10, size: 1, opcode: Opcode::Ret, operand: Operand::None },
+            ],
+            instructions
+        );
+    }
+
+    #[test]
+    pub fn decode_branches() {
+        // br.s -2; switch (+0, -7); ldloc 0x0102
+        let instructions = decode(&[0x2B, 0xFE, 0x45, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF9, 0xFF, 0xFF, 0xFF, 0xFE, 0x0C, 0x02, 0x01]).unwrap();
+        assert_eq!(Operand::BranchTarget(0), instructions[0].operand);
+        assert_eq!(Operand::Switch(vec![15, 8]), instructions[1].operand);
+        assert_eq!(&[15, 8], instructions[1].branch_targets());
+        assert_eq!(Opcode::Ldloc, instructions[2].opcode);
+        assert_eq!(Operand::Variable(0x0102), instructions[2].operand);
+        assert_eq!(19, instructions[2].next_offset());
+    }
+
+    #[test]
+    pub fn decode_display() {
+        let instructions = decode(&[0x1F, 0xFB, 0x2D, 0x00, 0xFE, 0x16, 0x05, 0x00, 0x00, 0x02]).unwrap();
+        let text: Vec<_> = instructions.iter().map(|i| format!("{}", i)).collect();
+        assert_eq!(vec!["IL_0000: ldc.i4.s -5", "IL_0002: brtrue.s IL_0004", "IL_0004: constrained. 0x02000005"], text);
+    }
+
+    #[test]
+    pub fn decode_unknown_opcode() {
+        assert_eq!(Err(Error::InvalidMetadata("Unknown IL opcode.")), decode(&[0x00, 0x24]));
+        assert_eq!(Err(Error::InvalidMetadata("Unknown IL opcode.")), decode(&[0xFE, 0x08]));
+    }
+
+    #[test]
+    pub fn decode_truncated() {
+        let expected = Err(Error::InvalidMetadata("IL instruction extends past the end of the method body."));
+        assert_eq!(expected, decode(&[0x20, 0x01, 0x02]));
+        assert_eq!(expected, decode(&[0xFE]));
+        assert_eq!(expected, decode(&[0x45, 0xFF, 0xFF, 0xFF, 0xFF]));
+    }
+
+    #[test]
+    pub fn opcode_round_trip() {
+        for value in 0..0xFF1F {
+            if let Some(opcode) = Opcode::from_value(value) {
+                assert_eq!(value, opcode.value());
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::il::ExceptionClause;
+use error::Error;
+
+const TINY_FORMAT: u8 = 0x2;
+const FAT_FORMAT: u8 = 0x3;
+const FORMAT_MASK: u8 = 0x3;
+const TINY_MAX_STACK: u16 = 8;
+
+bitflags! {
+    pub struct MethodBodyFlags: u16 {
+        const MORE_SECTS = 0x08;
+        const INIT_LOCALS = 0x10;
+    }
+}
+impl_display_via_debug!(MethodBodyFlags);
+
+bitflags! {
+    pub struct MethodSectionFlags: u8 {
+        const EH_TABLE = 0x01;
+        const OPT_IL_TABLE = 0x02;
+        const FAT_FORMAT = 0x40;
+        const MORE_SECTS = 0x80;
+    }
+}
+impl_display_via_debug!(MethodSectionFlags);
+
+/// Represents the body of an IL method (ECMA-335 II.25.4), including any exception handling clauses.
+pub struct MethodBody<'a> {
+    pub max_stack: u16,
+    pub init_locals: bool,
+    pub local_var_sig_token: u32,
+    pub code: &'a [u8],
+    pub exception_clauses: Vec<ExceptionClause>,
+}
+
+impl<'a> MethodBody<'a> {
+    /// Reads a method body from the provided buffer, which must start at the method header (the location
+    /// referred to by the `rva` column of the MethodDef table).
+    pub fn read(data: &'a [u8]) -> Result<MethodBody<'a>, Error> {
+        let first = *data.first().ok_or(Error::InvalidMetadata("Method body is empty."))?;
+        match first & FORMAT_MASK {
+            TINY_FORMAT => {
+                let code_size = (first >> 2) as usize;
+                Ok(MethodBody {
+                    max_stack: TINY_MAX_STACK,
+                    init_locals: false,
+                    local_var_sig_token: 0,
+                    code: slice(data, 1, code_size)?,
+                    exception_clauses: Vec::new(),
+                })
+            }
+            FAT_FORMAT => read_fat(data),
+            _ => Err(Error::InvalidMetadata("Method body has an unknown header format.")),
+        }
+    }
+}
+
+fn read_fat<'a>(data: &'a [u8]) -> Result<MethodBody<'a>, Error> {
+    let mut buf = data;
+    let flags_and_size = buf.read_u16::<LittleEndian>()?;
+    let flags = MethodBodyFlags::from_bits_truncate(flags_and_size & 0x0FFF);
+    let header_size = ((flags_and_size >> 12) as usize) * 4;
+    let max_stack = buf.read_u16::<LittleEndian>()?;
+    let code_size = buf.read_u32::<LittleEndian>()? as usize;
+    let local_var_sig_token = buf.read_u32::<LittleEndian>()?;
+
+    if header_size < 12 {
+        return Err(Error::InvalidMetadata("Fat method header is too small."));
+    }
+
+    let code = slice(data, header_size, code_size)?;
+
+    let mut exception_clauses = Vec::new();
+    let mut more_sections = flags.contains(MethodBodyFlags::MORE_SECTS);
+    let mut offset = header_size + code_size;
+    while more_sections {
+        // Sections are 4-byte aligned
+        offset = (offset + 3) & !3;
+        let kind = MethodSectionFlags::from_bits_truncate(*data.get(offset).ok_or(Error::InvalidMetadata("Method data section is out of range."))?);
+        let (data_size, clause_size) = if kind.contains(MethodSectionFlags::FAT_FORMAT) {
+            let mut size_buf = slice(data, offset + 1, 3)?;
+            (size_buf.read_u24::<LittleEndian>()? as usize, ExceptionClause::FAT_SIZE)
+        } else {
+            (*slice(data, offset + 1, 1)?.first().unwrap() as usize, ExceptionClause::SMALL_SIZE)
+        };
+
+        if data_size < 4 {
+            return Err(Error::InvalidMetadata("Method data section is too small."));
+        }
+
+        if kind.contains(MethodSectionFlags::EH_TABLE) {
+            let clauses = slice(data, offset + 4, data_size - 4)?;
+            for clause in clauses.chunks(clause_size).filter(|c| c.len() == clause_size) {
+                exception_clauses.push(if clause_size == ExceptionClause::FAT_SIZE {
+                    ExceptionClause::read_fat(clause)?
+                } else {
+                    ExceptionClause::read_small(clause)?
+                });
+            }
+        }
+
+        more_sections = kind.contains(MethodSectionFlags::MORE_SECTS);
+        offset += data_size;
+    }
+
+    Ok(MethodBody {
+        max_stack,
+        init_locals: flags.contains(MethodBodyFlags::INIT_LOCALS),
+        local_var_sig_token,
+        code,
+        exception_clauses,
+    })
+}
+
+fn slice(data: &[u8], start: usize, len: usize) -> Result<&[u8], Error> {
+    data.get(start..(start + len))
+        .ok_or(Error::InvalidMetadata("Method body extends past the end of the available data."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cli::il::ExceptionClauseKind;
+    use cli::tables::{TableHandle, TableIndex};
+
+    #[test]
+    pub fn tiny_body() {
+        // ldstr 0x70000001; call 0x0A000002; ret
+        let data = [0x2E, 0x72, 0x01, 0x00, 0x00, 0x70, 0x28, 0x02, 0x00, 0x00, 0x0A, 0x2A, 0xFF];
+        let body = MethodBody::read(&data).unwrap();
+        assert_eq!(8, body.max_stack);
+        assert!(!body.init_locals);
+        assert_eq!(0, body.local_var_sig_token);
+        assert_eq!(&data[1..12], body.code);
+        assert!(body.exception_clauses.is_empty());
+    }
+
+    #[test]
+    pub fn fat_body_with_small_eh_section() {
+        let data = [
+            // Flags (MoreSects | InitLocals | Fat), size 3, max stack 2, code size 6, local sig 0x11000001
+            0x1B, 0x30, 0x02, 0x00, 0x06, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x11,
+            // Code: nop; leave.s +2; pop; leave.s +0 (padded to 4 bytes)
+            0x00, 0xDE, 0x02, 0x26, 0xDE, 0x00, 0x00, 0x00,
+            // Small EH section, two clauses
+            0x01, 0x1C, 0x00, 0x00,
+            // Catch [TypeRef 0x05]: try 0x00+3, handler 0x03+3
+            0x00, 0x00, 0x00, 0x00, 0x03, 0x03, 0x00, 0x03, 0x05, 0x00, 0x00, 0x01,
+            // Finally: try 0x00+6, handler 0x06+0
+            0x02, 0x00, 0x00, 0x00, 0x06, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let body = MethodBody::read(&data).unwrap();
+        assert_eq!(2, body.max_stack);
+        assert!(body.init_locals);
+        assert_eq!(0x11000001, body.local_var_sig_token);
+        assert_eq!(&data[12..18], body.code);
+        assert_eq!(
+            vec![
+                ExceptionClause::new(ExceptionClauseKind::Catch(TableHandle::new(5, TableIndex::TypeRef)), 0, 3, 3, 3),
+                ExceptionClause::new(ExceptionClauseKind::Finally, 0, 6, 6, 0),
+            ],
+            body.exception_clauses
+        );
+    }
+
+    #[test]
+    pub fn fat_body_with_fat_eh_section() {
+        let data = [
+            // Flags (MoreSects | Fat), size 3, max stack 1, code size 2, no locals
+            0x0B, 0x30, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            // Code: nop; ret (padded to 4 bytes)
+            0x00, 0x2A, 0x00, 0x00,
+            // Fat EH section, one clause
+            0x41, 0x1C, 0x00, 0x00,
+            // Filter at 0x10: try 0x00+1, handler 0x01+1
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00,
+        ];
+        let body = MethodBody::read(&data).unwrap();
+        assert_eq!(
+            vec![ExceptionClause::new(ExceptionClauseKind::Filter(0x10), 0, 1, 1, 1)],
+            body.exception_clauses
+        );
+    }
+
+    #[test]
+    pub fn truncated_body() {
+        assert_eq!(
+            Err(Error::InvalidMetadata("Method body extends past the end of the available data.")),
+            MethodBody::read(&[0x2E, 0x00]).map(|b| b.code.len())
+        );
+    }
+}
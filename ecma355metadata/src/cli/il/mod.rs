@@ -0,0 +1,13 @@
+mod exception_clause;
+mod image_resolver;
+mod instruction;
+mod method_body;
+mod opcode;
+
+pub mod verifier;
+
+pub use self::image_resolver::ImageResolver;
+pub use self::exception_clause::{ExceptionClause, ExceptionClauseFlags, ExceptionClauseKind};
+pub use self::instruction::{Instruction, InstructionReader, Operand};
+pub use self::method_body::{MethodBody, MethodBodyFlags, MethodSectionFlags};
+pub use self::opcode::{FlowControl, Opcode, OperandType, StackCount};
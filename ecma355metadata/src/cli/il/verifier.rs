@@ -0,0 +1,1463 @@
+use std::fmt;
+
+use cli::il::{ExceptionClauseKind, FlowControl, Instruction, InstructionReader, MethodBody, Opcode, Operand};
+use cli::tables::TableIndex;
+
+/// The type of a value on the evaluation stack, as tracked by the verifier (ECMA-335 III.1.8.1.2).
+///
+/// Integers smaller than 32 bits are widened to `Int32`, and enums are tracked as their underlying type. The class
+/// of an object reference isn't tracked, so any two object references are compatible.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StackType {
+    Int32,
+    Int64,
+    NativeInt,
+
+    /// A floating point number, `F`.
+    Float,
+
+    /// An object reference, `O`.
+    Object,
+
+    /// A managed pointer, `&`, to a value of the given type.
+    ByRef(Box<StackType>),
+
+    /// A value type other than a primitive type or an enum, by the TypeDef or TypeRef token that refers to it.
+    /// Instantiations of a generic value type use the token of the generic type.
+    ValueType(u32),
+
+    TypedReference,
+
+    /// A value of a generic parameter of the enclosing type.
+    Var(u32),
+
+    /// A value of a generic parameter of the method.
+    MVar(u32),
+
+    /// `this` in a constructor, before another constructor of the class or its base class has been called on it.
+    UninitializedThis,
+}
+
+impl StackType {
+    /// Returns `true` if a value of this type can be stored in a location of the `target` type, such as a parameter,
+    /// local variable or field. `int32` and `native int` can be used interchangeably.
+    pub fn is_assignable_to(&self, target: &StackType) -> bool {
+        match (self, target) {
+            (&StackType::Int32, &StackType::NativeInt) | (&StackType::NativeInt, &StackType::Int32) => true,
+            (a, b) => a == b && *a != StackType::UninitializedThis,
+        }
+    }
+
+    fn is_integer(&self) -> bool {
+        matches!(*self, StackType::Int32 | StackType::Int64 | StackType::NativeInt)
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.is_integer() || *self == StackType::Float
+    }
+
+    fn is_by_ref(&self) -> bool {
+        matches!(*self, StackType::ByRef(_))
+    }
+}
+
+impl fmt::Display for StackType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            StackType::Int32 => write!(f, "int32"),
+            StackType::Int64 => write!(f, "int64"),
+            StackType::NativeInt => write!(f, "native int"),
+            StackType::Float => write!(f, "F"),
+            StackType::Object => write!(f, "O"),
+            StackType::ByRef(ref inner) => write!(f, "&({})", inner),
+            StackType::ValueType(token) => write!(f, "valuetype 0x{:08X}", token),
+            StackType::TypedReference => write!(f, "typedref"),
+            StackType::Var(index) => write!(f, "!{}", index),
+            StackType::MVar(index) => write!(f, "!!{}", index),
+            StackType::UninitializedThis => write!(f, "uninitialized this"),
+        }
+    }
+}
+
+/// The signature of a method, as referenced by a call instruction's token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallSite {
+    /// The type of `this`, which is `O` for classes and interfaces and a managed pointer to the value for value
+    /// types, or `None` for static methods.
+    pub this_type: Option<StackType>,
+
+    pub parameters: Vec<StackType>,
+
+    /// The return type, or `None` if the method returns `void`.
+    pub return_type: Option<StackType>,
+
+    /// Whether the method is an instance constructor, `.ctor`.
+    pub is_constructor: bool,
+
+    /// Whether the method belongs to a class that has a base class, so its constructors must call another
+    /// constructor before using `this` or returning. This is `false` for value types and System.Object.
+    pub has_base_class: bool,
+}
+
+/// The field referenced by the token of a field instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldSite {
+    /// The type of the instance that contains the field, like `CallSite::this_type`.
+    pub instance_type: StackType,
+    pub field_type: StackType,
+}
+
+/// The runtime handle types pushed by `ldtoken`, `refanytype` and `arglist`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RuntimeHandleKind {
+    Type,
+    Method,
+    Field,
+    Argument,
+}
+
+/// Looks up the types and signatures referenced by the tokens in a method body.
+pub trait CallSiteResolver {
+    /// Gets the signature of a MethodDef, MemberRef or MethodSpec token, or of a StandAloneSig token for `calli`.
+    fn resolve_call_site(&self, token: u32) -> Option<CallSite>;
+
+    /// Gets the field referenced by a Field or MemberRef token.
+    fn resolve_field(&self, token: u32) -> Option<FieldSite>;
+
+    /// Gets the stack type of a value of the type referenced by a TypeDef, TypeRef or TypeSpec token.
+    fn resolve_type(&self, token: u32) -> Option<StackType>;
+
+    /// Gets the types of the local variables in a StandAloneSig token.
+    fn resolve_locals(&self, token: u32) -> Option<Vec<StackType>>;
+
+    /// Gets the value type used for a kind of runtime handle, such as System.RuntimeTypeHandle.
+    fn resolve_runtime_handle(&self, kind: RuntimeHandleKind) -> Option<StackType>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerificationErrorKind {
+    /// The instruction could not be decoded.
+    InvalidInstruction,
+
+    /// A branch target is outside the method body, or not at the start of an instruction.
+    InvalidBranchTarget(i64),
+
+    /// Execution can run past the last instruction of the method body.
+    FallsThroughEnd,
+
+    StackUnderflow,
+    StackOverflow { height: u16, max_stack: u16 },
+
+    /// Two paths reach the same instruction with different stack heights.
+    StackHeightMismatch { expected: u16, actual: u16 },
+
+    /// Two paths reach the same instruction with values of different types on the stack.
+    StackTypeMismatch { expected: StackType, actual: StackType },
+
+    /// The stack must be empty after `ret` pops the return value (if any).
+    ReturnStackHeight { expected: u16, actual: u16 },
+
+    /// The stack must be empty for `jmp`, `endfilter` (after popping its result) and on entry to a try block.
+    StackNotEmpty,
+
+    /// A value can't be used as an argument, or stored in a variable, field or array element, of another type.
+    TypeMismatch { expected: StackType, actual: StackType },
+
+    /// A value on the stack has a type the instruction can't operate on.
+    InvalidStackType(StackType),
+
+    /// The two values on the stack can't be used together by a binary operation, comparison or branch.
+    InvalidOperandTypes(StackType, StackType),
+
+    /// An argument or local variable index is out of range.
+    InvalidVariable(u16),
+
+    /// `newobj` refers to a method that isn't an instance constructor.
+    NotAConstructor(u32),
+
+    /// `this` is used before a constructor has been called on it.
+    UninitializedThis,
+
+    /// A constructor returns without calling a constructor of its class or base class.
+    ThisNotInitialized,
+
+    /// The method, type, field or signature referenced by the token could not be resolved.
+    UnresolvedToken(u32),
+
+    /// An exception clause's blocks are empty, outside the method body, or not on instruction boundaries.
+    InvalidExceptionClause,
+
+    /// Two exception handling blocks overlap without one being nested in the other.
+    OverlappingExceptionClauses,
+
+    /// Control enters a protected block or handler other than at the start of a try block.
+    EntersRegion,
+
+    /// Control leaves a protected block or handler without using `leave`, `throw`, `endfinally` or `endfilter`.
+    ExitsRegion,
+
+    /// `leave` exits a finally, fault or filter block.
+    LeaveFromHandler,
+
+    ReturnInsideRegion,
+    EndfinallyOutsideFinally,
+    EndfilterOutsideFilter,
+    RethrowOutsideCatch,
+}
+
+impl fmt::Display for VerificationErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            VerificationErrorKind::InvalidInstruction => write!(f, "Invalid instruction."),
+            VerificationErrorKind::InvalidBranchTarget(target) => {
+                write!(f, "Branch target IL_{:04x} is not a valid instruction.", target)
+            }
+            VerificationErrorKind::FallsThroughEnd => write!(f, "Execution falls through the end of the method."),
+            VerificationErrorKind::StackUnderflow => write!(f, "Stack underflow."),
+            VerificationErrorKind::StackOverflow { height, max_stack } => {
+                write!(f, "Stack height {} exceeds MaxStack {}.", height, max_stack)
+            }
+            VerificationErrorKind::StackHeightMismatch { expected, actual } => {
+                write!(f, "Stack height {} does not match height {} from another path.", actual, expected)
+            }
+            VerificationErrorKind::StackTypeMismatch { ref expected, ref actual } => {
+                write!(f, "Stack type {} does not match type {} from another path.", actual, expected)
+            }
+            VerificationErrorKind::ReturnStackHeight { expected, actual } => {
+                write!(f, "Stack height {} on return, expected {}.", actual, expected)
+            }
+            VerificationErrorKind::StackNotEmpty => write!(f, "Stack must be empty."),
+            VerificationErrorKind::TypeMismatch { ref expected, ref actual } => {
+                write!(f, "Type {} is not compatible with {}.", actual, expected)
+            }
+            VerificationErrorKind::InvalidStackType(ref actual) => {
+                write!(f, "Unexpected type {} on the stack.", actual)
+            }
+            VerificationErrorKind::InvalidOperandTypes(ref a, ref b) => {
+                write!(f, "Types {} and {} can't be used together.", a, b)
+            }
+            VerificationErrorKind::InvalidVariable(index) => write!(f, "Variable {} is out of range.", index),
+            VerificationErrorKind::NotAConstructor(token) => write!(f, "0x{:08X} is not a constructor.", token),
+            VerificationErrorKind::UninitializedThis => write!(f, "Uninitialized this is used."),
+            VerificationErrorKind::ThisNotInitialized => {
+                write!(f, "Constructor returns without calling a base class constructor.")
+            }
+            VerificationErrorKind::UnresolvedToken(token) => write!(f, "Unable to resolve token 0x{:08X}.", token),
+            VerificationErrorKind::InvalidExceptionClause => write!(f, "Invalid exception clause."),
+            VerificationErrorKind::OverlappingExceptionClauses => {
+                write!(f, "Exception handling blocks overlap without nesting.")
+            }
+            VerificationErrorKind::EntersRegion => {
+                write!(f, "Control enters a protected block or handler other than at the start of a try block.")
+            }
+            VerificationErrorKind::ExitsRegion => {
+                write!(f, "Control leaves a protected block or handler without using leave.")
+            }
+            VerificationErrorKind::LeaveFromHandler => write!(f, "Leave exits a finally, fault or filter block."),
+            VerificationErrorKind::ReturnInsideRegion => write!(f, "Return from inside a protected block or handler."),
+            VerificationErrorKind::EndfinallyOutsideFinally => {
+                write!(f, "Endfinally outside of a finally or fault block.")
+            }
+            VerificationErrorKind::EndfilterOutsideFilter => write!(f, "Endfilter outside of a filter block."),
+            VerificationErrorKind::RethrowOutsideCatch => write!(f, "Rethrow outside of a catch block."),
+        }
+    }
+}
+
+/// A verification failure, reported against the method token and IL offset where it was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerificationError {
+    pub method_token: u32,
+    pub offset: u32,
+    pub kind: VerificationErrorKind,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "[method 0x{:08X}] IL_{:04x}: {}", self.method_token, self.offset, self.kind)
+    }
+}
+
+/// Verifies a method body, which must belong to the method referred to by `method_token`.
+///
+/// This checks that its instructions decode, branches land on instructions and exception handling blocks are
+/// properly nested and entered and exited according to ECMA-335 Partition I, 12.4.2.8. The types of the values on
+/// the evaluation stack are tracked as in Partition III, 1.8, so that each instruction's operands, the arguments of
+/// calls and the values stored in variables and fields have compatible types, and the stack has the same height and
+/// types wherever paths join. Constructors of classes must also call a constructor on `this` before using it.
+pub fn verify<R: CallSiteResolver>(method_token: u32, body: &MethodBody, resolver: &R) -> Vec<VerificationError> {
+    let mut verifier = Verifier {
+        method_token,
+        max_stack: body.max_stack,
+        code_size: body.code.len() as u32,
+        arguments: Vec::new(),
+        locals: Vec::new(),
+        return_type: None,
+        tracks_this: false,
+        instructions: Vec::new(),
+        regions: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    match resolver.resolve_call_site(method_token) {
+        Some(method) => {
+            verifier.tracks_this = method.is_constructor && method.has_base_class;
+            verifier.arguments.extend(method.this_type);
+            verifier.arguments.extend(method.parameters);
+            verifier.return_type = method.return_type;
+        }
+        None => {
+            verifier.report(0, VerificationErrorKind::UnresolvedToken(method_token));
+            return verifier.errors;
+        }
+    }
+    if body.local_var_sig_token != 0 {
+        match resolver.resolve_locals(body.local_var_sig_token) {
+            Some(locals) => verifier.locals = locals,
+            None => {
+                verifier.report(0, VerificationErrorKind::UnresolvedToken(body.local_var_sig_token));
+                return verifier.errors;
+            }
+        }
+    }
+
+    for result in InstructionReader::new(body.code) {
+        match result {
+            Ok(instruction) => verifier.instructions.push(instruction),
+            Err(_) => {
+                let offset = verifier.instructions.last().map(|i| i.next_offset()).unwrap_or(0);
+                verifier.report(offset, VerificationErrorKind::InvalidInstruction);
+                return verifier.errors;
+            }
+        }
+    }
+
+    if verifier.instructions.is_empty() {
+        verifier.report(0, VerificationErrorKind::FallsThroughEnd);
+        return verifier.errors;
+    }
+
+    verifier.check_exception_clauses(body);
+    verifier.check_branch_targets();
+    verifier.check_flow(resolver);
+
+    verifier.errors.sort_by_key(|e| e.offset);
+    verifier.errors.dedup();
+    verifier.errors
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum RegionKind {
+    Try,
+    Catch,
+    Filter,
+    Finally,
+    Fault,
+}
+
+struct Region {
+    kind: RegionKind,
+    start: u32,
+    end: u32,
+
+    /// The index of the exception clause, which the try block shares with its handler and filter.
+    clause: usize,
+}
+
+impl Region {
+    fn contains(&self, offset: u32) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    fn encloses(&self, other: &Region) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    fn overlaps(&self, other: &Region) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// The state before an instruction: the types on the evaluation stack, and whether `this` has been initialized.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct State {
+    stack: Vec<StackType>,
+    this_initialized: bool,
+}
+
+struct Verifier {
+    method_token: u32,
+    max_stack: u16,
+    code_size: u32,
+    arguments: Vec<StackType>,
+    locals: Vec<StackType>,
+    return_type: Option<StackType>,
+
+    /// Whether the method is a constructor that must initialize `this`.
+    tracks_this: bool,
+
+    instructions: Vec<Instruction>,
+    regions: Vec<Region>,
+    errors: Vec<VerificationError>,
+}
+
+impl Verifier {
+    fn report(&mut self, offset: u32, kind: VerificationErrorKind) {
+        self.errors.push(VerificationError {
+            method_token: self.method_token,
+            offset,
+            kind,
+        });
+    }
+
+    fn instruction_at(&self, offset: i64) -> Option<usize> {
+        if offset < 0 || offset > u32::MAX as i64 {
+            return None;
+        }
+        self.instructions.binary_search_by_key(&(offset as u32), |i| i.offset).ok()
+    }
+
+    fn is_boundary(&self, offset: u64) -> bool {
+        offset == self.code_size as u64 || self.instruction_at(offset as i64).is_some()
+    }
+
+    fn check_exception_clauses(&mut self, body: &MethodBody) {
+        for (index, clause) in body.exception_clauses.iter().enumerate() {
+            let try_end = clause.try_offset as u64 + clause.try_length as u64;
+            let handler_end = clause.handler_offset as u64 + clause.handler_length as u64;
+            let mut blocks = vec![
+                (RegionKind::Try, clause.try_offset as u64, try_end),
+                (
+                    match clause.kind {
+                        ExceptionClauseKind::Catch(_) | ExceptionClauseKind::Filter(_) => RegionKind::Catch,
+                        ExceptionClauseKind::Finally => RegionKind::Finally,
+                        ExceptionClauseKind::Fault => RegionKind::Fault,
+                    },
+                    clause.handler_offset as u64,
+                    handler_end,
+                ),
+            ];
+            if let ExceptionClauseKind::Filter(filter_offset) = clause.kind {
+                blocks.push((RegionKind::Filter, filter_offset as u64, clause.handler_offset as u64));
+            }
+
+            let valid = blocks
+                .iter()
+                .all(|&(_, start, end)| start < end && self.is_boundary(start) && self.is_boundary(end));
+            if !valid {
+                self.report(clause.try_offset, VerificationErrorKind::InvalidExceptionClause);
+                continue;
+            }
+
+            let regions: Vec<_> = blocks
+                .into_iter()
+                .map(|(kind, start, end)| Region { kind, start: start as u32, end: end as u32, clause: index })
+                .collect();
+
+            // The blocks of a single clause must be disjoint
+            if regions.iter().enumerate().any(|(i, a)| regions[(i + 1)..].iter().any(|b| a.overlaps(b))) {
+                self.report(clause.try_offset, VerificationErrorKind::OverlappingExceptionClauses);
+                continue;
+            }
+
+            self.regions.extend(regions);
+        }
+
+        let mut overlapping = Vec::new();
+        for (i, a) in self.regions.iter().enumerate() {
+            for b in self.regions[(i + 1)..].iter() {
+                if a.overlaps(b) && !a.encloses(b) && !b.encloses(a) {
+                    overlapping.push(a.start.max(b.start));
+                }
+            }
+        }
+        for offset in overlapping {
+            self.report(offset, VerificationErrorKind::OverlappingExceptionClauses);
+        }
+    }
+
+    fn check_branch_targets(&mut self) {
+        let mut invalid = Vec::new();
+        for instruction in self.instructions.iter() {
+            for &target in instruction.branch_targets() {
+                if self.instruction_at(target).is_none() {
+                    invalid.push((instruction.offset, target));
+                }
+            }
+        }
+        for (offset, target) in invalid {
+            self.report(offset, VerificationErrorKind::InvalidBranchTarget(target));
+        }
+    }
+
+    fn check_flow<R: CallSiteResolver>(&mut self, resolver: &R) {
+        let mut states: Vec<Option<State>> = vec![None; self.instructions.len()];
+        let entry = State {
+            stack: Vec::new(),
+            this_initialized: !self.tracks_this,
+        };
+        states[0] = Some(entry);
+        let mut pending = vec![0];
+
+        while let Some(index) = pending.pop() {
+            let state = states[index].clone().unwrap();
+            for (target, target_state) in self.step(index, &state, resolver) {
+                self.merge(target, target_state, &mut states, &mut pending);
+            }
+
+            // Any instruction in a try block can transfer control to its handlers, with the exception (if any) as
+            // the only value on the stack.
+            let offset = self.instructions[index].offset;
+            let handlers: Vec<_> = self.regions
+                .iter()
+                .filter(|r| r.kind == RegionKind::Try && r.contains(offset))
+                .flat_map(|t| self.regions.iter().filter(move |r| r.clause == t.clause && r.kind != RegionKind::Try))
+                .map(|r| (r.start, r.kind))
+                .collect();
+            for (start, kind) in handlers {
+                let stack = match kind {
+                    RegionKind::Catch | RegionKind::Filter => vec![StackType::Object],
+                    _ => Vec::new(),
+                };
+                let handler_state = State { stack, this_initialized: state.this_initialized };
+                if let Some(target) = self.instruction_at(start as i64) {
+                    self.merge(target, handler_state, &mut states, &mut pending);
+                }
+            }
+        }
+    }
+
+    /// Merges the state from one path into the state of an instruction that other paths may already have reached.
+    fn merge(&mut self, target: usize, incoming: State, states: &mut [Option<State>], pending: &mut Vec<usize>) {
+        let offset = self.instructions[target].offset;
+        let existing = match states[target] {
+            Some(ref mut existing) => existing,
+            None => {
+                states[target] = Some(incoming);
+                pending.push(target);
+                return;
+            }
+        };
+
+        if existing.stack.len() != incoming.stack.len() {
+            let expected = existing.stack.len() as u16;
+            let actual = incoming.stack.len() as u16;
+            self.report(offset, VerificationErrorKind::StackHeightMismatch { expected, actual });
+            return;
+        }
+        let mismatch = existing.stack.iter().zip(incoming.stack.iter()).find(|&(a, b)| a != b);
+        if let Some((expected, actual)) = mismatch {
+            let kind = VerificationErrorKind::StackTypeMismatch { expected: expected.clone(), actual: actual.clone() };
+            self.report(offset, kind);
+            return;
+        }
+
+        // `this` is only initialized after the join if it was on every path
+        if existing.this_initialized && !incoming.this_initialized {
+            existing.this_initialized = false;
+            pending.push(target);
+        }
+    }
+
+    /// Applies the effect of an instruction to the state, and returns the instructions that execution can continue
+    /// with, along with the state at each one.
+    fn step<R: CallSiteResolver>(&mut self, index: usize, state: &State, resolver: &R) -> Vec<(usize, State)> {
+        let (offset, opcode) = (self.instructions[index].offset, self.instructions[index].opcode);
+
+        let height = state.stack.len() as u16;
+        let mut state = state.clone();
+        if let Err(kind) = self.apply(index, &mut state, resolver) {
+            self.report(offset, kind);
+            return Vec::new();
+        }
+        if state.stack.len() > self.max_stack as usize {
+            let height = state.stack.len().min(u16::MAX as usize) as u16;
+            self.report(offset, VerificationErrorKind::StackOverflow { height, max_stack: self.max_stack });
+            return Vec::new();
+        }
+
+        match opcode {
+            Opcode::Ret if !self.enclosing_regions(offset).is_empty() => {
+                self.report(offset, VerificationErrorKind::ReturnInsideRegion)
+            }
+            Opcode::Jmp if height != 0 => self.report(offset, VerificationErrorKind::StackNotEmpty),
+            Opcode::Endfinally if !self.is_inside(offset, &[RegionKind::Finally, RegionKind::Fault]) => {
+                self.report(offset, VerificationErrorKind::EndfinallyOutsideFinally)
+            }
+            Opcode::Endfilter => {
+                if !self.is_inside(offset, &[RegionKind::Filter]) {
+                    self.report(offset, VerificationErrorKind::EndfilterOutsideFilter);
+                }
+                if !state.stack.is_empty() {
+                    self.report(offset, VerificationErrorKind::StackNotEmpty);
+                }
+            }
+            Opcode::Rethrow if !self.is_inside(offset, &[RegionKind::Catch]) => {
+                self.report(offset, VerificationErrorKind::RethrowOutsideCatch)
+            }
+            _ => {}
+        }
+
+        let mut successors = Vec::new();
+        let flow = opcode.flow_control();
+        if flow == FlowControl::Branch || flow == FlowControl::CondBranch {
+            // leave empties the evaluation stack
+            let mut target_state = state.clone();
+            if opcode.is_leave() {
+                target_state.stack.clear();
+            }
+            let targets: Vec<_> = self.instructions[index]
+                .branch_targets()
+                .iter()
+                .filter_map(|&t| self.instruction_at(t))
+                .collect();
+            for target in targets {
+                if self.check_transfer(index, target, target_state.stack.len(), opcode.is_leave()) {
+                    successors.push((target, target_state.clone()));
+                }
+            }
+        }
+        let falls_through = match flow {
+            FlowControl::Next | FlowControl::Break | FlowControl::Prefix | FlowControl::CondBranch => true,
+            FlowControl::Call => opcode != Opcode::Jmp,
+            FlowControl::Branch | FlowControl::Return | FlowControl::Throw => false,
+        };
+        if falls_through {
+            if index + 1 < self.instructions.len() {
+                if self.check_transfer(index, index + 1, state.stack.len(), false) {
+                    successors.push((index + 1, state));
+                }
+            } else {
+                self.report(offset, VerificationErrorKind::FallsThroughEnd);
+            }
+        }
+        successors
+    }
+
+    /// Pops the instruction's operands from the stack, checking their types, and pushes its result.
+    fn apply<R: CallSiteResolver>(
+        &self,
+        index: usize,
+        state: &mut State,
+        resolver: &R,
+    ) -> Result<(), VerificationErrorKind> {
+        let instruction = &self.instructions[index];
+        let stack = &mut state.stack;
+        let token = match instruction.operand {
+            Operand::Token(token) => token,
+            _ => 0,
+        };
+        let resolve_type = |token| resolver.resolve_type(token).ok_or(VerificationErrorKind::UnresolvedToken(token));
+
+        match instruction.opcode {
+            Opcode::Nop | Opcode::Break | Opcode::Jmp | Opcode::Endfinally | Opcode::Leave | Opcode::LeaveS |
+            Opcode::BrS | Opcode::Br | Opcode::Rethrow => {}
+            Opcode::Unaligned | Opcode::Volatile | Opcode::Tail | Opcode::Constrained | Opcode::No |
+            Opcode::Readonly => {}
+
+            Opcode::Ldarg0 | Opcode::Ldarg1 | Opcode::Ldarg2 | Opcode::Ldarg3 | Opcode::LdargS | Opcode::Ldarg => {
+                let index = variable_index(instruction);
+                if index == 0 && !state.this_initialized {
+                    stack.push(StackType::UninitializedThis);
+                } else {
+                    stack.push(self.argument(index)?.clone());
+                }
+            }
+            Opcode::LdargaS | Opcode::Ldarga => {
+                let argument = self.argument(variable_index(instruction))?.clone();
+                stack.push(StackType::ByRef(Box::new(argument)));
+            }
+            Opcode::StargS | Opcode::Starg => {
+                let argument = self.argument(variable_index(instruction))?;
+                check_assignable(&pop(stack)?, argument)?;
+            }
+            Opcode::Ldloc0 | Opcode::Ldloc1 | Opcode::Ldloc2 | Opcode::Ldloc3 | Opcode::LdlocS | Opcode::Ldloc => {
+                stack.push(self.local(variable_index(instruction))?.clone());
+            }
+            Opcode::LdlocaS | Opcode::Ldloca => {
+                let local = self.local(variable_index(instruction))?.clone();
+                stack.push(StackType::ByRef(Box::new(local)));
+            }
+            Opcode::Stloc0 | Opcode::Stloc1 | Opcode::Stloc2 | Opcode::Stloc3 | Opcode::StlocS | Opcode::Stloc => {
+                let local = self.local(variable_index(instruction))?;
+                check_assignable(&pop(stack)?, local)?;
+            }
+
+            Opcode::Ldnull | Opcode::Ldstr => stack.push(StackType::Object),
+            Opcode::LdcI4M1 | Opcode::LdcI40 | Opcode::LdcI41 | Opcode::LdcI42 | Opcode::LdcI43 | Opcode::LdcI44 |
+            Opcode::LdcI45 | Opcode::LdcI46 | Opcode::LdcI47 | Opcode::LdcI48 | Opcode::LdcI4S | Opcode::LdcI4 => {
+                stack.push(StackType::Int32)
+            }
+            Opcode::LdcI8 => stack.push(StackType::Int64),
+            Opcode::LdcR4 | Opcode::LdcR8 => stack.push(StackType::Float),
+            Opcode::Dup => {
+                let value = pop(stack)?;
+                stack.push(value.clone());
+                stack.push(value);
+            }
+            Opcode::Pop => {
+                pop(stack)?;
+            }
+
+            Opcode::Call | Opcode::Callvirt | Opcode::Newobj | Opcode::Calli => {
+                let site = resolver.resolve_call_site(token).ok_or(VerificationErrorKind::UnresolvedToken(token))?;
+                if instruction.opcode == Opcode::Calli {
+                    expect(pop(stack)?, |t| *t == StackType::NativeInt)?;
+                }
+                for parameter in site.parameters.iter().rev() {
+                    check_assignable(&pop(stack)?, parameter)?;
+                }
+                if instruction.opcode == Opcode::Newobj {
+                    // newobj allocates 'this' itself, and pushes the new object or value
+                    match site.this_type {
+                        Some(StackType::ByRef(value)) if site.is_constructor => stack.push(*value),
+                        Some(this_type) if site.is_constructor => stack.push(this_type),
+                        _ => return Err(VerificationErrorKind::NotAConstructor(token)),
+                    }
+                    return Ok(());
+                }
+                if let Some(ref this_type) = site.this_type {
+                    let this = pop(stack)?;
+                    if this == StackType::UninitializedThis {
+                        // Calling a constructor on 'this' initializes it, including any copies on the stack
+                        if instruction.opcode != Opcode::Call || !site.is_constructor {
+                            return Err(VerificationErrorKind::UninitializedThis);
+                        }
+                        state.this_initialized = true;
+                        for value in stack.iter_mut().filter(|v| **v == StackType::UninitializedThis) {
+                            *value = this_type.clone();
+                        }
+                    } else if index > 0 && self.instructions[index - 1].opcode == Opcode::Constrained {
+                        // constrained. callvirt takes a pointer to the value, whatever its type
+                        expect(this, StackType::is_by_ref)?;
+                    } else {
+                        check_assignable(&this, this_type)?;
+                    }
+                }
+                stack.extend(site.return_type);
+            }
+            Opcode::Ret => {
+                let expected = self.return_type.is_some() as u16;
+                if stack.len() != expected as usize {
+                    return Err(VerificationErrorKind::ReturnStackHeight { expected, actual: stack.len() as u16 });
+                }
+                if let Some(ref return_type) = self.return_type {
+                    check_assignable(&pop(stack)?, return_type)?;
+                }
+                if !state.this_initialized {
+                    return Err(VerificationErrorKind::ThisNotInitialized);
+                }
+            }
+            Opcode::Ldftn => {
+                resolver.resolve_call_site(token).ok_or(VerificationErrorKind::UnresolvedToken(token))?;
+                stack.push(StackType::NativeInt);
+            }
+            Opcode::Ldvirtftn => {
+                resolver.resolve_call_site(token).ok_or(VerificationErrorKind::UnresolvedToken(token))?;
+                expect(pop(stack)?, |t| *t == StackType::Object)?;
+                stack.push(StackType::NativeInt);
+            }
+
+            Opcode::BrfalseS | Opcode::BrtrueS | Opcode::Brfalse | Opcode::Brtrue => {
+                expect(pop(stack)?, |t| t.is_integer() || t.is_by_ref() || *t == StackType::Object)?;
+            }
+            Opcode::Switch => {
+                expect(pop(stack)?, |t| *t == StackType::Int32 || *t == StackType::NativeInt)?;
+            }
+            Opcode::BeqS | Opcode::Beq | Opcode::BneUnS | Opcode::BneUn => compare(stack, true)?,
+            Opcode::BgeS | Opcode::BgtS | Opcode::BleS | Opcode::BltS | Opcode::BgeUnS | Opcode::BgtUnS |
+            Opcode::BleUnS | Opcode::BltUnS | Opcode::Bge | Opcode::Bgt | Opcode::Ble | Opcode::Blt |
+            Opcode::BgeUn | Opcode::BgtUn | Opcode::BleUn | Opcode::BltUn => compare(stack, false)?,
+            Opcode::Ceq | Opcode::CgtUn | Opcode::Cgt | Opcode::Clt | Opcode::CltUn => {
+                let opcode = instruction.opcode;
+                compare(stack, opcode == Opcode::Ceq || opcode == Opcode::CgtUn)?;
+                stack.push(StackType::Int32);
+            }
+
+            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div | Opcode::Rem => binary(stack, true)?,
+            Opcode::DivUn | Opcode::RemUn | Opcode::And | Opcode::Or | Opcode::Xor | Opcode::AddOvf |
+            Opcode::AddOvfUn | Opcode::MulOvf | Opcode::MulOvfUn | Opcode::SubOvf | Opcode::SubOvfUn => {
+                binary(stack, false)?
+            }
+            Opcode::Shl | Opcode::Shr | Opcode::ShrUn => {
+                expect(pop(stack)?, |t| *t == StackType::Int32 || *t == StackType::NativeInt)?;
+                let value = expect(pop(stack)?, StackType::is_integer)?;
+                stack.push(value);
+            }
+            Opcode::Neg => {
+                let value = expect(pop(stack)?, StackType::is_numeric)?;
+                stack.push(value);
+            }
+            Opcode::Not => {
+                let value = expect(pop(stack)?, StackType::is_integer)?;
+                stack.push(value);
+            }
+            Opcode::Ckfinite => {
+                expect(pop(stack)?, |t| *t == StackType::Float)?;
+                stack.push(StackType::Float);
+            }
+            Opcode::ConvI1 | Opcode::ConvI2 | Opcode::ConvI4 | Opcode::ConvU1 | Opcode::ConvU2 | Opcode::ConvU4 |
+            Opcode::ConvOvfI1 | Opcode::ConvOvfI2 | Opcode::ConvOvfI4 | Opcode::ConvOvfU1 | Opcode::ConvOvfU2 |
+            Opcode::ConvOvfU4 | Opcode::ConvOvfI1Un | Opcode::ConvOvfI2Un | Opcode::ConvOvfI4Un |
+            Opcode::ConvOvfU1Un | Opcode::ConvOvfU2Un | Opcode::ConvOvfU4Un => convert(stack, StackType::Int32)?,
+            Opcode::ConvI8 | Opcode::ConvU8 | Opcode::ConvOvfI8 | Opcode::ConvOvfU8 | Opcode::ConvOvfI8Un |
+            Opcode::ConvOvfU8Un => convert(stack, StackType::Int64)?,
+            Opcode::ConvI | Opcode::ConvU | Opcode::ConvOvfI | Opcode::ConvOvfU | Opcode::ConvOvfIUn |
+            Opcode::ConvOvfUUn => convert(stack, StackType::NativeInt)?,
+            Opcode::ConvR4 | Opcode::ConvR8 | Opcode::ConvRUn => convert(stack, StackType::Float)?,
+
+            Opcode::LdindI1 | Opcode::LdindU1 | Opcode::LdindI2 | Opcode::LdindU2 | Opcode::LdindI4 |
+            Opcode::LdindU4 | Opcode::LdindI8 | Opcode::LdindI | Opcode::LdindR4 | Opcode::LdindR8 |
+            Opcode::LdindRef => {
+                let value_type = indirect_type(instruction.opcode);
+                check_address(&pop(stack)?, &value_type)?;
+                stack.push(value_type);
+            }
+            Opcode::StindRef | Opcode::StindI1 | Opcode::StindI2 | Opcode::StindI4 | Opcode::StindI8 |
+            Opcode::StindR4 | Opcode::StindR8 | Opcode::StindI => {
+                let value_type = indirect_type(instruction.opcode);
+                check_assignable(&pop(stack)?, &value_type)?;
+                check_address(&pop(stack)?, &value_type)?;
+            }
+            Opcode::Ldobj => {
+                let value_type = resolve_type(token)?;
+                check_address(&pop(stack)?, &value_type)?;
+                stack.push(value_type);
+            }
+            Opcode::Stobj => {
+                let value_type = resolve_type(token)?;
+                check_assignable(&pop(stack)?, &value_type)?;
+                check_address(&pop(stack)?, &value_type)?;
+            }
+            Opcode::Cpobj => {
+                let value_type = resolve_type(token)?;
+                check_address(&pop(stack)?, &value_type)?;
+                check_address(&pop(stack)?, &value_type)?;
+            }
+            Opcode::Initobj => {
+                let value_type = resolve_type(token)?;
+                check_address(&pop(stack)?, &value_type)?;
+            }
+            Opcode::Cpblk | Opcode::Initblk => {
+                expect(pop(stack)?, |t| *t == StackType::Int32)?;
+                pop(stack)?;
+                expect(pop(stack)?, |t| *t == StackType::NativeInt || t.is_by_ref())?;
+            }
+            Opcode::Localloc => {
+                expect(pop(stack)?, |t| *t == StackType::Int32 || *t == StackType::NativeInt)?;
+                stack.push(StackType::NativeInt);
+            }
+            Opcode::Sizeof => {
+                resolve_type(token)?;
+                stack.push(StackType::Int32);
+            }
+
+            Opcode::Ldfld | Opcode::Ldflda | Opcode::Stfld | Opcode::Ldsfld | Opcode::Ldsflda | Opcode::Stsfld => {
+                let field = resolver.resolve_field(token).ok_or(VerificationErrorKind::UnresolvedToken(token))?;
+                let field_type = field.field_type;
+                match instruction.opcode {
+                    Opcode::Ldfld => {
+                        check_instance(&pop(stack)?, &field.instance_type, true)?;
+                        stack.push(field_type);
+                    }
+                    Opcode::Ldflda => {
+                        check_instance(&pop(stack)?, &field.instance_type, false)?;
+                        stack.push(StackType::ByRef(Box::new(field_type)));
+                    }
+                    Opcode::Stfld => {
+                        check_assignable(&pop(stack)?, &field_type)?;
+                        check_instance(&pop(stack)?, &field.instance_type, false)?;
+                    }
+                    Opcode::Ldsfld => stack.push(field_type),
+                    Opcode::Ldsflda => stack.push(StackType::ByRef(Box::new(field_type))),
+                    _ => check_assignable(&pop(stack)?, &field_type)?,
+                }
+            }
+
+            Opcode::Box => {
+                check_assignable(&pop(stack)?, &resolve_type(token)?)?;
+                stack.push(StackType::Object);
+            }
+            Opcode::Unbox => {
+                let value_type = resolve_type(token)?;
+                expect(pop(stack)?, |t| *t == StackType::Object)?;
+                stack.push(StackType::ByRef(Box::new(value_type)));
+            }
+            Opcode::UnboxAny => {
+                let value_type = resolve_type(token)?;
+                expect(pop(stack)?, |t| *t == StackType::Object)?;
+                stack.push(value_type);
+            }
+            Opcode::Castclass | Opcode::Isinst => {
+                resolve_type(token)?;
+                expect(pop(stack)?, |t| *t == StackType::Object)?;
+                stack.push(StackType::Object);
+            }
+            Opcode::Throw => {
+                expect(pop(stack)?, |t| *t == StackType::Object)?;
+            }
+            Opcode::Endfilter => {
+                expect(pop(stack)?, |t| *t == StackType::Int32)?;
+            }
+
+            Opcode::Newarr => {
+                resolve_type(token)?;
+                expect(pop(stack)?, |t| *t == StackType::Int32 || *t == StackType::NativeInt)?;
+                stack.push(StackType::Object);
+            }
+            Opcode::Ldlen => {
+                expect(pop(stack)?, |t| *t == StackType::Object)?;
+                stack.push(StackType::NativeInt);
+            }
+            Opcode::Ldelema => {
+                let element_type = resolve_type(token)?;
+                pop_array_index(stack)?;
+                stack.push(StackType::ByRef(Box::new(element_type)));
+            }
+            Opcode::LdelemI1 | Opcode::LdelemU1 | Opcode::LdelemI2 | Opcode::LdelemU2 | Opcode::LdelemI4 |
+            Opcode::LdelemU4 | Opcode::LdelemI8 | Opcode::LdelemI | Opcode::LdelemR4 | Opcode::LdelemR8 |
+            Opcode::LdelemRef | Opcode::Ldelem => {
+                let element_type = match instruction.opcode {
+                    Opcode::Ldelem => resolve_type(token)?,
+                    opcode => element_type(opcode),
+                };
+                pop_array_index(stack)?;
+                stack.push(element_type);
+            }
+            Opcode::StelemI | Opcode::StelemI1 | Opcode::StelemI2 | Opcode::StelemI4 | Opcode::StelemI8 |
+            Opcode::StelemR4 | Opcode::StelemR8 | Opcode::StelemRef | Opcode::Stelem => {
+                let element_type = match instruction.opcode {
+                    Opcode::Stelem => resolve_type(token)?,
+                    opcode => element_type(opcode),
+                };
+                check_assignable(&pop(stack)?, &element_type)?;
+                pop_array_index(stack)?;
+            }
+
+            Opcode::Mkrefany => {
+                let value_type = resolve_type(token)?;
+                check_address(&pop(stack)?, &value_type)?;
+                stack.push(StackType::TypedReference);
+            }
+            Opcode::Refanyval => {
+                let value_type = resolve_type(token)?;
+                expect(pop(stack)?, |t| *t == StackType::TypedReference)?;
+                stack.push(StackType::ByRef(Box::new(value_type)));
+            }
+            Opcode::Refanytype => {
+                expect(pop(stack)?, |t| *t == StackType::TypedReference)?;
+                stack.push(runtime_handle(resolver, RuntimeHandleKind::Type, token)?);
+            }
+            Opcode::Arglist => stack.push(runtime_handle(resolver, RuntimeHandleKind::Argument, token)?),
+            Opcode::Ldtoken => {
+                let kind = match TableIndex::from_u8((token >> 24) as u8) {
+                    Some(TableIndex::TypeDef) | Some(TableIndex::TypeRef) | Some(TableIndex::TypeSpec) => {
+                        RuntimeHandleKind::Type
+                    }
+                    Some(TableIndex::Field) => RuntimeHandleKind::Field,
+                    Some(TableIndex::MemberRef) if resolver.resolve_field(token).is_some() => RuntimeHandleKind::Field,
+                    _ => RuntimeHandleKind::Method,
+                };
+                stack.push(runtime_handle(resolver, kind, token)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn argument(&self, index: u16) -> Result<&StackType, VerificationErrorKind> {
+        self.arguments.get(index as usize).ok_or(VerificationErrorKind::InvalidVariable(index))
+    }
+
+    fn local(&self, index: u16) -> Result<&StackType, VerificationErrorKind> {
+        self.locals.get(index as usize).ok_or(VerificationErrorKind::InvalidVariable(index))
+    }
+
+    /// Checks that control can pass from one instruction to another without improperly entering or exiting
+    /// exception handling blocks.
+    fn check_transfer(&mut self, from: usize, to: usize, height: usize, is_leave: bool) -> bool {
+        let from_offset = self.instructions[from].offset;
+        let to_offset = self.instructions[to].offset;
+        let from_regions = self.enclosing_regions(from_offset);
+        let to_regions = self.enclosing_regions(to_offset);
+
+        let mut error = None;
+        let mut enters_try = false;
+        for &r in to_regions.iter().filter(|r| !from_regions.contains(r)) {
+            let region = &self.regions[r];
+            if region.kind == RegionKind::Try && region.start == to_offset {
+                enters_try = true;
+            } else {
+                error = Some(VerificationErrorKind::EntersRegion);
+            }
+        }
+        for &r in from_regions.iter().filter(|r| !to_regions.contains(r)) {
+            match (is_leave, self.regions[r].kind) {
+                (false, _) => error = Some(VerificationErrorKind::ExitsRegion),
+                (true, RegionKind::Finally) | (true, RegionKind::Fault) | (true, RegionKind::Filter) => {
+                    error = Some(VerificationErrorKind::LeaveFromHandler)
+                }
+                (true, _) => {}
+            }
+        }
+        if error.is_none() && enters_try && height != 0 {
+            error = Some(VerificationErrorKind::StackNotEmpty);
+        }
+
+        match error {
+            Some(kind) => {
+                self.report(from_offset, kind);
+                false
+            }
+            None => true,
+        }
+    }
+
+    fn enclosing_regions(&self, offset: u32) -> Vec<usize> {
+        self.regions
+            .iter()
+            .enumerate()
+            .filter(|&(_, r)| r.contains(offset))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn is_inside(&self, offset: u32, kinds: &[RegionKind]) -> bool {
+        self.regions.iter().any(|r| r.contains(offset) && kinds.contains(&r.kind))
+    }
+}
+
+/// Gets the argument or local variable index used by an instruction, including the short forms like `ldarg.0`.
+fn variable_index(instruction: &Instruction) -> u16 {
+    match instruction.opcode {
+        Opcode::Ldarg0 | Opcode::Ldloc0 | Opcode::Stloc0 => 0,
+        Opcode::Ldarg1 | Opcode::Ldloc1 | Opcode::Stloc1 => 1,
+        Opcode::Ldarg2 | Opcode::Ldloc2 | Opcode::Stloc2 => 2,
+        Opcode::Ldarg3 | Opcode::Ldloc3 | Opcode::Stloc3 => 3,
+        _ => match instruction.operand {
+            Operand::Variable(index) => index,
+            _ => u16::MAX,
+        },
+    }
+}
+
+fn pop(stack: &mut Vec<StackType>) -> Result<StackType, VerificationErrorKind> {
+    stack.pop().ok_or(VerificationErrorKind::StackUnderflow)
+}
+
+/// Checks that a value satisfies `valid`, returning it if so.
+fn expect<F: Fn(&StackType) -> bool>(value: StackType, valid: F) -> Result<StackType, VerificationErrorKind> {
+    if valid(&value) {
+        Ok(value)
+    } else if value == StackType::UninitializedThis {
+        Err(VerificationErrorKind::UninitializedThis)
+    } else {
+        Err(VerificationErrorKind::InvalidStackType(value))
+    }
+}
+
+fn check_assignable(value: &StackType, target: &StackType) -> Result<(), VerificationErrorKind> {
+    if value.is_assignable_to(target) {
+        Ok(())
+    } else if *value == StackType::UninitializedThis {
+        Err(VerificationErrorKind::UninitializedThis)
+    } else {
+        Err(VerificationErrorKind::TypeMismatch { expected: target.clone(), actual: value.clone() })
+    }
+}
+
+/// Checks that an address refers to a value that can be loaded or stored as `value_type`.
+fn check_address(address: &StackType, value_type: &StackType) -> Result<(), VerificationErrorKind> {
+    match *address {
+        StackType::ByRef(ref target) if target.is_assignable_to(value_type) && value_type.is_assignable_to(target) => {
+            Ok(())
+        }
+        _ => {
+            let expected = StackType::ByRef(Box::new(value_type.clone()));
+            Err(VerificationErrorKind::TypeMismatch { expected, actual: address.clone() })
+        }
+    }
+}
+
+/// Checks the object or value that contains a field. Constructors can also set the fields of uninitialized `this`.
+fn check_instance(
+    instance: &StackType,
+    instance_type: &StackType,
+    allow_value: bool,
+) -> Result<(), VerificationErrorKind> {
+    match (instance, instance_type) {
+        (&StackType::UninitializedThis, &StackType::Object) => Ok(()),
+        (value, StackType::ByRef(inner)) if allow_value && value == &**inner => Ok(()),
+        (value, _) => check_assignable(value, instance_type),
+    }
+}
+
+fn pop_array_index(stack: &mut Vec<StackType>) -> Result<(), VerificationErrorKind> {
+    expect(pop(stack)?, |t| *t == StackType::Int32 || *t == StackType::NativeInt)?;
+    expect(pop(stack)?, |t| *t == StackType::Object)?;
+    Ok(())
+}
+
+/// Applies a binary numeric operation (ECMA-335 III.1.5, tables III.2 and III.5), with floating point operands
+/// only allowed if `allow_float` is set.
+fn binary(stack: &mut Vec<StackType>, allow_float: bool) -> Result<(), VerificationErrorKind> {
+    let b = pop(stack)?;
+    let a = pop(stack)?;
+    let result = match (&a, &b) {
+        (&StackType::Int32, &StackType::Int32) => StackType::Int32,
+        (&StackType::Int32, &StackType::NativeInt) |
+        (&StackType::NativeInt, &StackType::Int32) |
+        (&StackType::NativeInt, &StackType::NativeInt) => StackType::NativeInt,
+        (&StackType::Int64, &StackType::Int64) => StackType::Int64,
+        (&StackType::Float, &StackType::Float) if allow_float => StackType::Float,
+        _ => return Err(VerificationErrorKind::InvalidOperandTypes(a, b)),
+    };
+    stack.push(result);
+    Ok(())
+}
+
+/// Checks the operands of a comparison or comparing branch (ECMA-335 III.1.5, table III.4). Object references can
+/// only be compared for equality, which includes `cgt.un` for comparing with null.
+fn compare(stack: &mut Vec<StackType>, equality: bool) -> Result<(), VerificationErrorKind> {
+    let b = pop(stack)?;
+    let a = pop(stack)?;
+    let valid = match (&a, &b) {
+        (&StackType::Int32, &StackType::Int32) |
+        (&StackType::Int32, &StackType::NativeInt) |
+        (&StackType::NativeInt, &StackType::Int32) |
+        (&StackType::NativeInt, &StackType::NativeInt) |
+        (&StackType::Int64, &StackType::Int64) |
+        (&StackType::Float, &StackType::Float) |
+        (&StackType::ByRef(_), &StackType::ByRef(_)) => true,
+        (&StackType::Object, &StackType::Object) => equality,
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(VerificationErrorKind::InvalidOperandTypes(a, b))
+    }
+}
+
+fn convert(stack: &mut Vec<StackType>, result: StackType) -> Result<(), VerificationErrorKind> {
+    expect(pop(stack)?, StackType::is_numeric)?;
+    stack.push(result);
+    Ok(())
+}
+
+/// Gets the stack type of the value loaded or stored by `ldind` and `stind` instructions.
+fn indirect_type(opcode: Opcode) -> StackType {
+    match opcode {
+        Opcode::LdindI8 | Opcode::StindI8 => StackType::Int64,
+        Opcode::LdindI | Opcode::StindI => StackType::NativeInt,
+        Opcode::LdindR4 | Opcode::LdindR8 | Opcode::StindR4 | Opcode::StindR8 => StackType::Float,
+        Opcode::LdindRef | Opcode::StindRef => StackType::Object,
+        _ => StackType::Int32,
+    }
+}
+
+/// Gets the stack type of the element loaded or stored by the `ldelem` and `stelem` instructions with a type suffix.
+fn element_type(opcode: Opcode) -> StackType {
+    match opcode {
+        Opcode::LdelemI8 | Opcode::StelemI8 => StackType::Int64,
+        Opcode::LdelemI | Opcode::StelemI => StackType::NativeInt,
+        Opcode::LdelemR4 | Opcode::LdelemR8 | Opcode::StelemR4 | Opcode::StelemR8 => StackType::Float,
+        Opcode::LdelemRef | Opcode::StelemRef => StackType::Object,
+        _ => StackType::Int32,
+    }
+}
+
+fn runtime_handle<R: CallSiteResolver>(
+    resolver: &R,
+    kind: RuntimeHandleKind,
+    token: u32,
+) -> Result<StackType, VerificationErrorKind> {
+    resolver.resolve_runtime_handle(kind).ok_or(VerificationErrorKind::UnresolvedToken(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cli::il::ExceptionClause;
+    use cli::tables::{TableHandle, TableIndex};
+
+    use self::StackType::*;
+    use self::VerificationErrorKind::*;
+
+    /// Resolves the tokens used by the tests, where 0x06000001 is the method being verified.
+    struct Resolver {
+        method: CallSite,
+    }
+
+    fn method(this_type: Option<StackType>, parameters: Vec<StackType>, return_type: Option<StackType>) -> CallSite {
+        CallSite {
+            this_type,
+            parameters,
+            return_type,
+            is_constructor: false,
+            has_base_class: true,
+        }
+    }
+
+    fn constructor(has_base_class: bool, parameters: Vec<StackType>) -> CallSite {
+        CallSite {
+            is_constructor: true,
+            has_base_class,
+            ..method(Some(Object), parameters, None)
+        }
+    }
+
+    impl CallSiteResolver for Resolver {
+        fn resolve_call_site(&self, token: u32) -> Option<CallSite> {
+            match token {
+                0x06000001 => Some(self.method.clone()),
+                // static void WriteLine(string)
+                0x0A000001 => Some(method(None, vec![Object], None)),
+                // instance int32 GetHashCode()
+                0x0A000002 => Some(method(Some(Object), vec![], Some(Int32))),
+                // System.Object::.ctor()
+                0x0A000003 => Some(constructor(false, vec![])),
+                _ => None,
+            }
+        }
+
+        fn resolve_field(&self, token: u32) -> Option<FieldSite> {
+            match token {
+                // An int32 field of a class
+                0x04000001 => Some(FieldSite { instance_type: Object, field_type: Int32 }),
+                _ => None,
+            }
+        }
+
+        fn resolve_type(&self, token: u32) -> Option<StackType> {
+            match token {
+                0x01000001 => Some(Object),
+                0x01000002 => Some(ValueType(token)),
+                _ => None,
+            }
+        }
+
+        fn resolve_locals(&self, token: u32) -> Option<Vec<StackType>> {
+            match token {
+                0x11000001 => Some(vec![Int32, Object]),
+                _ => None,
+            }
+        }
+
+        fn resolve_runtime_handle(&self, _: RuntimeHandleKind) -> Option<StackType> {
+            Some(ValueType(0x01000003))
+        }
+    }
+
+    fn body(max_stack: u16, code: &[u8], exception_clauses: Vec<ExceptionClause>) -> MethodBody<'_> {
+        MethodBody {
+            max_stack,
+            init_locals: false,
+            local_var_sig_token: 0,
+            code,
+            exception_clauses,
+        }
+    }
+
+    fn kinds(body: &MethodBody, method: CallSite) -> Vec<(u32, VerificationErrorKind)> {
+        verify(0x06000001, body, &Resolver { method })
+            .into_iter()
+            .map(|e| (e.offset, e.kind))
+            .collect()
+    }
+
+    /// A static method with the specified parameters that returns void.
+    fn void(parameters: Vec<StackType>) -> CallSite {
+        method(None, parameters, None)
+    }
+
+    #[test]
+    pub fn valid_method() {
+        // ldstr; call WriteLine; ldarg.0; callvirt GetHashCode; ret
+        let code = [0x72, 0x01, 0x00, 0x00, 0x70, 0x28, 0x01, 0x00, 0x00, 0x0A, 0x02, 0x6F, 0x02, 0x00, 0x00, 0x0A, 0x2A];
+        let method = method(None, vec![Object], Some(Int32));
+        assert_eq!(Vec::<(u32, VerificationErrorKind)>::new(), kinds(&body(1, &code, vec![]), method));
+
+        // ldloca.s 0; ldind.i4; ldarg.0; ldlen; add; stloc.0; ldloc.1; ldarg.0; ceq; pop; ret
+        let code = [0x12, 0x00, 0x4A, 0x02, 0x8E, 0x58, 0x0A, 0x07, 0x02, 0xFE, 0x01, 0x26, 0x2A];
+        let mut locals = body(2, &code, vec![]);
+        locals.local_var_sig_token = 0x11000001;
+        assert_eq!(Vec::<(u32, VerificationErrorKind)>::new(), kinds(&locals, void(vec![Object])));
+    }
+
+    #[test]
+    pub fn stack_errors() {
+        // pop; ret
+        assert_eq!(vec![(0, StackUnderflow)], kinds(&body(8, &[0x26, 0x2A], vec![]), void(vec![])));
+
+        // ldc.i4.0; dup; pop; pop; ret
+        assert_eq!(
+            vec![(1, StackOverflow { height: 2, max_stack: 1 })],
+            kinds(&body(1, &[0x16, 0x25, 0x26, 0x26, 0x2A], vec![]), void(vec![]))
+        );
+
+        // ldc.i4.0; ret (from a void method)
+        assert_eq!(
+            vec![(1, ReturnStackHeight { expected: 0, actual: 1 })],
+            kinds(&body(8, &[0x16, 0x2A], vec![]), void(vec![]))
+        );
+
+        // ldarg.0; brtrue.s +1; ldc.i4.0; ret
+        assert_eq!(
+            vec![(4, StackHeightMismatch { expected: 0, actual: 1 })],
+            kinds(&body(8, &[0x02, 0x2D, 0x01, 0x16, 0x2A], vec![]), void(vec![Int32]))
+        );
+    }
+
+    #[test]
+    pub fn type_errors() {
+        // ldc.i4.0; ldc.i8 0; add; pop; ret
+        let code = [0x16, 0x21, 0, 0, 0, 0, 0, 0, 0, 0, 0x58, 0x26, 0x2A];
+        assert_eq!(vec![(10, InvalidOperandTypes(Int32, Int64))], kinds(&body(8, &code, vec![]), void(vec![])));
+
+        // ldc.i4.0; call WriteLine; ret
+        let code = [0x16, 0x28, 0x01, 0x00, 0x00, 0x0A, 0x2A];
+        assert_eq!(
+            vec![(1, TypeMismatch { expected: Object, actual: Int32 })],
+            kinds(&body(8, &code, vec![]), void(vec![]))
+        );
+
+        // ldnull; stloc.0; ldloc.2; ret
+        let code = [0x14, 0x0A, 0x08, 0x2A];
+        let mut locals = body(8, &code, vec![]);
+        locals.local_var_sig_token = 0x11000001;
+        assert_eq!(
+            vec![(1, TypeMismatch { expected: Int32, actual: Object })],
+            kinds(&locals, void(vec![]))
+        );
+        locals.code = &code[2..];
+        assert_eq!(vec![(0, InvalidVariable(2))], kinds(&locals, void(vec![])));
+
+        // ldarg.0; ret, from a method returning int64
+        assert_eq!(
+            vec![(1, TypeMismatch { expected: Int64, actual: Object })],
+            kinds(&body(8, &[0x02, 0x2A], vec![]), method(None, vec![Object], Some(Int64)))
+        );
+
+        // ldc.i4.0; ldfld; ret
+        let code = [0x16, 0x7B, 0x01, 0x00, 0x00, 0x04, 0x2A];
+        assert_eq!(
+            vec![(1, TypeMismatch { expected: Object, actual: Int32 })],
+            kinds(&body(8, &code, vec![]), method(None, vec![], Some(Int32)))
+        );
+
+        // ldarg.0; brtrue.s +3; ldc.i4.0; br.s +1; ldnull; pop; ret
+        let code = [0x02, 0x2D, 0x03, 0x16, 0x2B, 0x01, 0x14, 0x26, 0x2A];
+        assert_eq!(
+            vec![(7, StackTypeMismatch { expected: Int32, actual: Object })],
+            kinds(&body(8, &code, vec![]), void(vec![Int32]))
+        );
+    }
+
+    #[test]
+    pub fn constructors() {
+        // ldarg.0; call Object::.ctor; ldarg.0; ldarg.1; stfld; ret
+        let code = [0x02, 0x28, 0x03, 0x00, 0x00, 0x0A, 0x02, 0x03, 0x7D, 0x01, 0x00, 0x00, 0x04, 0x2A];
+        let ctor = || constructor(true, vec![Int32]);
+        assert_eq!(Vec::<(u32, VerificationErrorKind)>::new(), kinds(&body(2, &code, vec![]), ctor()));
+
+        // Fields can be stored before the base constructor is called, but it must be called before returning
+        assert_eq!(vec![(7, ThisNotInitialized)], kinds(&body(2, &code[6..], vec![]), ctor()));
+
+        // System.Object's constructor doesn't need to call another one
+        let object_ctor = kinds(&body(2, &code[13..], vec![]), constructor(false, vec![]));
+        assert_eq!(Vec::<(u32, VerificationErrorKind)>::new(), object_ctor);
+
+        // ldarg.0; callvirt GetHashCode; pop; ret
+        let code = [0x02, 0x6F, 0x02, 0x00, 0x00, 0x0A, 0x26, 0x2A];
+        assert_eq!(vec![(1, VerificationErrorKind::UninitializedThis)], kinds(&body(1, &code, vec![]), ctor()));
+
+        // ldarg.1; brtrue.s +6; ldarg.0; call Object::.ctor; ret
+        let code = [0x03, 0x2D, 0x06, 0x02, 0x28, 0x03, 0x00, 0x00, 0x0A, 0x2A];
+        assert_eq!(vec![(9, ThisNotInitialized)], kinds(&body(1, &code, vec![]), ctor()));
+
+        // newobj GetHashCode
+        let code = [0x73, 0x02, 0x00, 0x00, 0x0A, 0x26, 0x2A];
+        assert_eq!(vec![(0, NotAConstructor(0x0A000002))], kinds(&body(1, &code, vec![]), void(vec![])));
+    }
+
+    #[test]
+    pub fn control_flow_errors() {
+        // br.s +2; nop; ret
+        assert_eq!(
+            vec![(0, InvalidBranchTarget(4))],
+            kinds(&body(8, &[0x2B, 0x02, 0x00, 0x2A], vec![]), void(vec![]))
+        );
+
+        // nop
+        assert_eq!(vec![(0, FallsThroughEnd)], kinds(&body(8, &[0x00], vec![]), void(vec![])));
+
+        // call <unknown>; ret
+        assert_eq!(
+            vec![(0, UnresolvedToken(0x0A000009))],
+            kinds(&body(8, &[0x28, 0x09, 0x00, 0x00, 0x0A, 0x2A], vec![]), void(vec![]))
+        );
+
+        // nop; <unknown opcode>
+        assert_eq!(vec![(1, InvalidInstruction)], kinds(&body(8, &[0x00, 0x24], vec![]), void(vec![])));
+    }
+
+    #[test]
+    pub fn exception_handling() {
+        // try { nop; leave.s end } catch { pop; leave.s end } end: ret
+        let code = [0x00, 0xDE, 0x03, 0x26, 0xDE, 0x00, 0x2A];
+        let catch = |length| {
+            let exception_type = TableHandle::new(1, TableIndex::TypeRef);
+            ExceptionClause::new(ExceptionClauseKind::Catch(exception_type), 0, 3, 3, length)
+        };
+        assert_eq!(Vec::<(u32, VerificationErrorKind)>::new(), kinds(&body(1, &code, vec![catch(3)]), void(vec![])));
+
+        // Handler that ends in the middle of the leave.s
+        assert_eq!(vec![(0, InvalidExceptionClause)], kinds(&body(1, &code, vec![catch(2)]), void(vec![])));
+
+        // try { nop; ret } finally { endfinally } ret
+        let code = [0x00, 0x2A, 0xDC, 0x2A];
+        let finally = |length| ExceptionClause::new(ExceptionClauseKind::Finally, 0, 2, 2, length);
+        assert_eq!(vec![(1, ReturnInsideRegion)], kinds(&body(1, &code, vec![finally(1)]), void(vec![])));
+
+        // try { nop } falls into finally { leave.s +0 } ret
+        let code = [0x00, 0xDE, 0x00, 0x2A];
+        assert_eq!(
+            vec![(0, ExitsRegion), (1, LeaveFromHandler)],
+            kinds(&body(1, &code, vec![ExceptionClause::new(ExceptionClauseKind::Finally, 0, 1, 1, 2)]), void(vec![]))
+        );
+
+        // try { leave.s end } finally { pop; endfinally } end: ret, where the finally is entered with an empty stack
+        let code = [0xDE, 0x02, 0x26, 0xDC, 0x2A];
+        assert_eq!(
+            vec![(2, StackUnderflow)],
+            kinds(&body(1, &code, vec![ExceptionClause::new(ExceptionClauseKind::Finally, 0, 2, 2, 2)]), void(vec![]))
+        );
+    }
+
+    #[test]
+    pub fn assignability() {
+        assert!(Int32.is_assignable_to(&NativeInt));
+        assert!(NativeInt.is_assignable_to(&Int32));
+        assert!(!Int32.is_assignable_to(&Int64));
+        assert!(ValueType(0x01000002).is_assignable_to(&ValueType(0x01000002)));
+        assert!(!ValueType(0x01000002).is_assignable_to(&ValueType(0x02000002)));
+        assert!(!StackType::UninitializedThis.is_assignable_to(&StackType::UninitializedThis));
+        assert!(!Object.is_assignable_to(&ByRef(Box::new(Object))));
+    }
+
+    #[test]
+    pub fn display() {
+        let error = VerificationError {
+            method_token: 0x06000001,
+            offset: 0x0004,
+            kind: StackUnderflow,
+        };
+        assert_eq!("[method 0x06000001] IL_0004: Stack underflow.", format!("{}", error));
+        assert_eq!("&(valuetype 0x01000002)", format!("{}", ByRef(Box::new(ValueType(0x01000002)))));
+        assert_eq!(
+            "IL_0007: Type int32 is not compatible with O.",
+            format!("IL_0007: {}", TypeMismatch { expected: Object, actual: Int32 })
+        );
+    }
+}
@@ -0,0 +1,11 @@
+// We want ManifestResourceAttributes to use the same names as in the ECMA spec, which are PascalCased, not UPPER_SNAKE_CASE
+#![allow(non_upper_case_globals)]
+
+bitflags! {
+    pub struct ManifestResourceAttributes : u32 {
+        const Public = 0x0001;
+        const Private = 0x0002;
+    }
+}
+
+impl_display_via_debug!(ManifestResourceAttributes);
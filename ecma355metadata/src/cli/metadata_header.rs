@@ -44,10 +44,8 @@ impl MetadataHeader {
             // Check if it's aligned
             if current_file_pos & 0x3 != 0 {
                 // Get the next 4-byte aligned value
-                let flags_start = (current_file_pos + 4) & !0x4u64;
-                if flags_start != current_file_pos {
-                    buf.seek(SeekFrom::Start(flags_start))?;
-                }
+                let flags_start = (current_file_pos + 3) & !0x3u64;
+                buf.seek(SeekFrom::Start(flags_start))?;
             }
 
             let flags = buf.read_u16::<LittleEndian>()?;
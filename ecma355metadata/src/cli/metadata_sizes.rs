@@ -26,6 +26,8 @@ impl ::std::fmt::Display for HeapSizes {
 
 pub struct MetadataSizes {
     heap_sizes: HeapSizes,
+    valid_tables: TableMask,
+    sorted_tables: TableMask,
     row_counts: [usize; TableIndex::MAX + 1],
 }
 
@@ -41,8 +43,10 @@ impl MetadataSizes {
         buf.read_u8()?;
 
         // Read valid and sorted vectors
-        let valid_mask = TableMask::from_bits_truncate(buf.read_u64::<LittleEndian>()?);
-        let _sorted_mask = TableMask::from_bits_truncate(buf.read_u64::<LittleEndian>()?);
+        // Row counts are only present for valid tables, so if we don't recognize a table we can't find the rest
+        let valid_mask = TableMask::from_bits(buf.read_u64::<LittleEndian>()?)
+            .ok_or(Error::InvalidMetadata("The '#~' stream contains a table that is not recognized."))?;
+        let sorted_mask = TableMask::from_bits_truncate(buf.read_u64::<LittleEndian>()?);
 
         // Load row counts
         let mut row_counts = [0; TableIndex::MAX + 1];
@@ -53,8 +57,15 @@ impl MetadataSizes {
             }
         }
 
+        // Skip the extra data value, if there is one. It comes before the tables themselves.
+        if heap_sizes.contains(HeapSizes::EXTRA_DATA) {
+            buf.read_u32::<LittleEndian>()?;
+        }
+
         Ok(MetadataSizes {
             heap_sizes: heap_sizes,
+            valid_tables: valid_mask,
+            sorted_tables: sorted_mask,
             row_counts: row_counts,
         })
     }
@@ -63,6 +74,16 @@ impl MetadataSizes {
         self.heap_sizes
     }
 
+    /// Gets the set of tables that are present in the image.
+    pub fn valid_tables(&self) -> TableMask {
+        self.valid_tables
+    }
+
+    /// Gets the set of tables that the image claims are sorted by their primary key.
+    pub fn sorted_tables(&self) -> TableMask {
+        self.sorted_tables
+    }
+
     pub fn row_count(&self, idx: TableIndex) -> usize {
         let idx = idx as usize;
         if idx > self.row_counts.len() {
@@ -81,11 +102,14 @@ impl MetadataSizes {
         }
     }
 
-    pub fn coded_index_size(&self, tables: TableMask) -> usize {
+    /// Gets the size of a coded index that can refer to the specified tables, using `tag_bits` bits for the tag.
+    ///
+    /// A coded index only fits in 2 bytes if every table it can refer to has fewer than `2^(16 - tag_bits)` rows.
+    pub fn coded_index_size(&self, tables: TableMask, tag_bits: usize) -> usize {
+        let max_small_rows = 1usize << (16 - tag_bits);
         let need_large_index = TableIndex::each()
             .filter(|&i| tables.has_table(i))
-            .map(|i| self.index_size(i))
-            .any(|i| i == LARGE_INDEX_SIZE);
+            .any(|i| self.row_count(i) >= max_small_rows);
 
         if need_large_index {
             LARGE_INDEX_SIZE
@@ -94,3 +118,33 @@ impl MetadataSizes {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sizes_with_type_defs(count: u32) -> MetadataSizes {
+        let mut data = vec![0, 0, 0, 0, 2, 0, 0, 1];
+        data.extend_from_slice(&[0x04, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[0; 8]);
+        data.push(count as u8);
+        data.push((count >> 8) as u8);
+        data.push((count >> 16) as u8);
+        data.push((count >> 24) as u8);
+        MetadataSizes::read(&mut &data[..]).unwrap()
+    }
+
+    #[test]
+    pub fn coded_index_size_accounts_for_tag_bits() {
+        let tables = TableMask::from_index(TableIndex::TypeDef) | TableMask::from_index(TableIndex::TypeRef);
+
+        let sizes = sizes_with_type_defs(0x07FF);
+        assert_eq!(SMALL_INDEX_SIZE, sizes.index_size(TableIndex::TypeDef));
+        assert_eq!(SMALL_INDEX_SIZE, sizes.coded_index_size(tables, 5));
+
+        let sizes = sizes_with_type_defs(0x0800);
+        assert_eq!(SMALL_INDEX_SIZE, sizes.index_size(TableIndex::TypeDef));
+        assert_eq!(SMALL_INDEX_SIZE, sizes.coded_index_size(tables, 2));
+        assert_eq!(LARGE_INDEX_SIZE, sizes.coded_index_size(tables, 5));
+    }
+}
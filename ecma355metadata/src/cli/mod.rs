@@ -7,15 +7,24 @@ mod heap_handle;
 mod string_heap;
 mod guid_heap;
 mod blob_heap;
+mod user_string_heap;
 mod metadata_sizes;
 mod type_attributes;
 mod field_attributes;
 mod method_attributes;
 mod method_impl_attributes;
 mod param_attributes;
+mod generic_param_attributes;
+mod pinvoke_attributes;
+mod native_type;
+mod constant_value;
+mod custom_attribute_value;
+mod manifest_resource_attributes;
 
+pub mod il;
 pub mod tables;
 pub mod signatures;
+pub mod resources;
 
 pub use self::access::Access;
 pub use self::cli_header::CliHeader;
@@ -27,11 +36,21 @@ pub use self::heap_handle::{BlobHandle, BlobHandleReader, GuidHandle, GuidHandle
 pub use self::guid_heap::GuidHeap;
 pub use self::blob_heap::BlobHeap;
 pub use self::string_heap::StringHeap;
+pub use self::user_string_heap::UserStringHeap;
 pub use self::type_attributes::{TypeAttributes, TypeFlags, TypeLayout, TypeSemantics,
                                 TypeStringFormat, TypeVisibility};
 pub use self::field_attributes::{FieldAttributes, FieldFlags};
 pub use self::method_attributes::{MethodAttributes, MethodFlags, MethodVTableLayout};
 pub use self::method_impl_attributes::{MethodCodeType, MethodImplAttributes, MethodImplFlags};
 pub use self::param_attributes::ParamAttributes;
+pub use self::generic_param_attributes::{GenericParamAttributes, GenericParamConstraints,
+                                         GenericParamVariance};
+pub use self::pinvoke_attributes::{PInvokeAttributes, PInvokeCallingConvention, PInvokeCharSet,
+                                   PInvokeFlags};
+pub use self::native_type::NativeType;
+pub use self::constant_value::ConstantValue;
+pub use self::custom_attribute_value::{AttributeEnumType, AttributeValue, CustomAttributeValue, EnumResolver,
+                                        NamedArgument, NamedArgumentKind};
+pub use self::manifest_resource_attributes::ManifestResourceAttributes;
 pub use self::metadata_sizes::{HeapSizes, MetadataSizes, LARGE_INDEX_SIZE, SMALL_INDEX_SIZE,
                                SMALL_TABLE_MAX_SIZE};
@@ -0,0 +1,78 @@
+use error::Error;
+
+/// The native type a FieldMarshal row's marshaling descriptor gives a field or parameter (ECMA-335 II.23.4), as
+/// `[MarshalAs]` does in C#.
+///
+/// Only the leading `NATIVE_TYPE` is decoded; the element types and sizes that arrays add follow it in the blob.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NativeType {
+    Boolean,
+    I1,
+    U1,
+    I2,
+    U2,
+    I4,
+    U4,
+    I8,
+    U8,
+    R4,
+    R8,
+    LPStr,
+    LPWStr,
+    Int,
+    UInt,
+    Func,
+    Array,
+
+    /// A UTF-8 string, which .NET added after ECMA-335.
+    LPUtf8Str,
+
+    /// A native type the runtime doesn't know.
+    Other(u8),
+}
+impl_display_via_debug!(NativeType);
+
+impl NativeType {
+    pub fn read(descriptor: &[u8]) -> Result<NativeType, Error> {
+        let value = match descriptor.first() {
+            Some(&value) => value,
+            None => return Err(Error::InvalidSignature),
+        };
+        Ok(match value {
+            0x02 => NativeType::Boolean,
+            0x03 => NativeType::I1,
+            0x04 => NativeType::U1,
+            0x05 => NativeType::I2,
+            0x06 => NativeType::U2,
+            0x07 => NativeType::I4,
+            0x08 => NativeType::U4,
+            0x09 => NativeType::I8,
+            0x0a => NativeType::U8,
+            0x0b => NativeType::R4,
+            0x0c => NativeType::R8,
+            0x14 => NativeType::LPStr,
+            0x15 => NativeType::LPWStr,
+            0x1f => NativeType::Int,
+            0x20 => NativeType::UInt,
+            0x26 => NativeType::Func,
+            0x2a => NativeType::Array,
+            0x30 => NativeType::LPUtf8Str,
+            other => NativeType::Other(other),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn read_native_types() {
+        // [MarshalAs(UnmanagedType.U1)], [MarshalAs(UnmanagedType.LPUTF8Str)], and an array of ints
+        assert_eq!(NativeType::U1, NativeType::read(&[0x04]).unwrap());
+        assert_eq!(NativeType::LPUtf8Str, NativeType::read(&[0x30]).unwrap());
+        assert_eq!(NativeType::Array, NativeType::read(&[0x2a, 0x07]).unwrap());
+        assert_eq!(NativeType::Other(0x1d), NativeType::read(&[0x1d]).unwrap());
+        assert!(NativeType::read(&[]).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+// We want PInvokeAttributes to use the same names as in the ECMA spec, which are PascalCased, not UPPER_SNAKE_CASE
+#![allow(non_upper_case_globals)]
+
+/// The `MappingFlags` of an ImplMap row, describing how a P/Invoke method is bound and called (ECMA-335 II.23.1.8).
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct PInvokeAttributes(u16);
+
+impl PInvokeAttributes {
+    pub fn new(value: u16) -> PInvokeAttributes {
+        PInvokeAttributes(value)
+    }
+
+    pub fn char_set(self) -> PInvokeCharSet {
+        match (self.0 & PInvokeCharSet::MASK) >> PInvokeCharSet::SHIFT {
+            0 => PInvokeCharSet::NotSpec,
+            1 => PInvokeCharSet::Ansi,
+            2 => PInvokeCharSet::Unicode,
+            _ => PInvokeCharSet::Auto,
+        }
+    }
+
+    pub fn calling_convention(self) -> PInvokeCallingConvention {
+        match (self.0 & PInvokeCallingConvention::MASK) >> PInvokeCallingConvention::SHIFT {
+            1 => PInvokeCallingConvention::Platformapi,
+            2 => PInvokeCallingConvention::Cdecl,
+            3 => PInvokeCallingConvention::Stdcall,
+            4 => PInvokeCallingConvention::Thiscall,
+            5 => PInvokeCallingConvention::Fastcall,
+            _ => PInvokeCallingConvention::Reserved,
+        }
+    }
+
+    pub fn flags(self) -> PInvokeFlags {
+        PInvokeFlags::from_bits_truncate(self.0 & FLAGS_MASK)
+    }
+}
+
+impl ::std::fmt::Display for PInvokeAttributes {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> Result<(), ::std::fmt::Error> {
+        write!(f, "{} {}", self.calling_convention(), self.char_set())?;
+        if !self.flags().is_empty() {
+            write!(f, " [{}]", self.flags())?;
+        }
+        Ok(())
+    }
+}
+
+#[repr(u16)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum PInvokeCharSet {
+    NotSpec = 0,
+    Ansi = 1,
+    Unicode = 2,
+    Auto = 3,
+}
+impl_display_via_debug!(PInvokeCharSet);
+
+impl PInvokeCharSet {
+    const MASK: u16 = 0x0006;
+    const SHIFT: u16 = 1;
+}
+
+#[repr(u16)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum PInvokeCallingConvention {
+    Reserved = 0,
+    Platformapi = 1,
+    Cdecl = 2,
+    Stdcall = 3,
+    Thiscall = 4,
+    Fastcall = 5,
+}
+impl_display_via_debug!(PInvokeCallingConvention);
+
+impl PInvokeCallingConvention {
+    const MASK: u16 = 0x0700;
+    const SHIFT: u16 = 8;
+}
+
+const FLAGS_MASK: u16 = !(PInvokeCharSet::MASK | PInvokeCallingConvention::MASK);
+
+bitflags! {
+    pub struct PInvokeFlags : u16 {
+        const NoMangle = 0x0001;
+        const BestFitEnabled = 0x0010;
+        const BestFitDisabled = 0x0020;
+        const SupportsLastError = 0x0040;
+        const ThrowOnUnmappableCharEnabled = 0x1000;
+        const ThrowOnUnmappableCharDisabled = 0x2000;
+    }
+}
+impl_display_via_debug!(PInvokeFlags);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn decode_dll_import() {
+        // [DllImport("libc", CharSet = CharSet.Unicode, SetLastError = true)]
+        let attrs = PInvokeAttributes::new(0x0145);
+        assert_eq!(PInvokeCharSet::Unicode, attrs.char_set());
+        assert_eq!(PInvokeCallingConvention::Platformapi, attrs.calling_convention());
+        assert_eq!(PInvokeFlags::NoMangle | PInvokeFlags::SupportsLastError, attrs.flags());
+    }
+}
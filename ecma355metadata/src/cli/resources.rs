@@ -0,0 +1,510 @@
+use std::fmt;
+use std::str;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+
+/// A value from a `.resources` file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceValue<'a> {
+    Null,
+    String(&'a str),
+    Boolean(bool),
+    Char(u16),
+    Byte(u8),
+    SByte(i8),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Single(f32),
+    Double(f64),
+
+    /// A `System.Decimal`, as the four parts returned by `decimal.GetBits`.
+    Decimal([i32; 4]),
+
+    /// A `System.DateTime`, in the format returned by `DateTime.ToBinary`.
+    DateTime(i64),
+
+    /// A `System.TimeSpan`, in ticks.
+    TimeSpan(i64),
+    ByteArray(&'a [u8]),
+    Stream(&'a [u8]),
+
+    /// A value of any other type, which was serialized by the writer, usually with `BinaryFormatter`.
+    Serialized { type_name: &'a str, data: &'a [u8] },
+}
+
+impl<'a> fmt::Display for ResourceValue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ResourceValue::Null => write!(f, "null"),
+            ResourceValue::String(x) => write!(f, "{:?}", x),
+            ResourceValue::Boolean(x) => write!(f, "bool({})", x),
+            ResourceValue::Char(x) => write!(f, "char(0x{:04X})", x),
+            ResourceValue::Byte(x) => write!(f, "uint8({})", x),
+            ResourceValue::SByte(x) => write!(f, "int8({})", x),
+            ResourceValue::Int16(x) => write!(f, "int16({})", x),
+            ResourceValue::UInt16(x) => write!(f, "uint16({})", x),
+            ResourceValue::Int32(x) => write!(f, "int32({})", x),
+            ResourceValue::UInt32(x) => write!(f, "uint32({})", x),
+            ResourceValue::Int64(x) => write!(f, "int64({})", x),
+            ResourceValue::UInt64(x) => write!(f, "uint64({})", x),
+            ResourceValue::Single(x) => write!(f, "float32({})", x),
+            ResourceValue::Double(x) => write!(f, "float64({})", x),
+            ResourceValue::Decimal(x) => write!(f, "decimal({})", format_decimal(x)),
+            ResourceValue::DateTime(x) => write!(f, "DateTime(0x{:016X})", x),
+            ResourceValue::TimeSpan(x) => write!(f, "TimeSpan({} ticks)", x),
+            ResourceValue::ByteArray(x) => write!(f, "byte[{}]", x.len()),
+            ResourceValue::Stream(x) => write!(f, "Stream({} bytes)", x.len()),
+            ResourceValue::Serialized { type_name, data } => write!(f, "{} ({} bytes)", type_name, data.len()),
+        }
+    }
+}
+
+fn format_decimal(bits: [i32; 4]) -> String {
+    // The low three parts are a 96-bit integer, the flags hold the sign and the number of digits after the point
+    let value = (bits[0] as u32 as u128) | ((bits[1] as u32 as u128) << 32) | ((bits[2] as u32 as u128) << 64);
+    let scale = ((bits[3] >> 16) & 0xFF) as usize;
+    let mut digits = value.to_string();
+    if scale > 0 {
+        if digits.len() <= scale {
+            digits = format!("{}{}", "0".repeat(scale + 1 - digits.len()), digits);
+        }
+        digits.insert(digits.len() - scale, '.');
+    }
+    if bits[3] < 0 {
+        digits.insert(0, '-');
+    }
+    digits
+}
+
+/// A named resource in a `.resources` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceEntry {
+    pub name: String,
+
+    /// The offset of the value, relative to the start of the data section.
+    pub data_offset: u32,
+}
+
+/// A `.resources` file, in the format written by `System.Resources.ResourceWriter`.
+///
+/// This is the format of the `.resources` manifest resources that compilers embed from `.resx` files. Names are
+/// read up front, but values are only decoded when they are requested.
+pub struct ResourceSet<'a> {
+    data: &'a [u8],
+    reader_type: Option<&'a str>,
+    resource_set_type: Option<&'a str>,
+    version: u32,
+    types: Vec<&'a str>,
+    hashes: Vec<i32>,
+    entries: Vec<ResourceEntry>,
+    data_section: usize,
+
+    /// The distinct data offsets, in order, which bound the size of each serialized value.
+    data_offsets: Vec<u32>,
+}
+
+impl<'a> ResourceSet<'a> {
+    pub const MAGIC: u32 = 0xBEEF_CACE;
+
+    /// Reads the headers, name hashes and names of a `.resources` file.
+    pub fn read(data: &'a [u8]) -> Result<ResourceSet<'a>, Error> {
+        let mut buf = data;
+        if buf.read_u32::<LittleEndian>()? != ResourceSet::MAGIC {
+            return Err(invalid("The resources do not start with the resource manager signature."));
+        }
+
+        // The resource manager header names the types that can read the file, later versions may add to it
+        let header_version = buf.read_u32::<LittleEndian>()?;
+        let header_len = buf.read_u32::<LittleEndian>()? as usize;
+        let mut header = take(&mut buf, header_len)?;
+        let (reader_type, resource_set_type) = if header_version == 1 {
+            (Some(read_string(&mut header)?), Some(read_string(&mut header)?))
+        } else {
+            (None, None)
+        };
+
+        let version = buf.read_u32::<LittleEndian>()?;
+        if version != 2 {
+            return Err(invalid("Only version 2 of the resources format is supported."));
+        }
+        let count = buf.read_u32::<LittleEndian>()? as usize;
+        let type_count = buf.read_u32::<LittleEndian>()? as usize;
+        let mut types = Vec::new();
+        for _ in 0..type_count {
+            types.push(read_string(&mut buf)?);
+        }
+
+        // The hashes are aligned to 8 bytes, relative to the start of the file
+        let padding = (8 - (data.len() - buf.len()) % 8) % 8;
+        take(&mut buf, padding)?;
+
+        let array_len = count.checked_mul(4).ok_or(invalid("The resources contain too many entries."))?;
+        let mut hash_data = take(&mut buf, array_len)?;
+        let mut name_offsets = take(&mut buf, array_len)?;
+        let data_section = buf.read_u32::<LittleEndian>()? as usize;
+        if data_section > data.len() {
+            return Err(invalid("The data section of the resources is outside the resources."));
+        }
+        let names = buf;
+
+        // Names in a valid file don't overlap, so limit the total length to keep overlapping names from being slow
+        let mut remaining = names.len();
+        let mut hashes = Vec::new();
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            hashes.push(hash_data.read_i32::<LittleEndian>()?);
+            let offset = name_offsets.read_u32::<LittleEndian>()? as usize;
+            let mut buf = names.get(offset..).ok_or(invalid("A resource name is outside the name section."))?;
+            let len = read_7bit_int(&mut buf)? as usize;
+            remaining = remaining
+                .checked_sub(len)
+                .ok_or(invalid("The resource names are longer than the name section."))?;
+            let name = read_utf16(take(&mut buf, len)?)?;
+            entries.push(ResourceEntry {
+                name,
+                data_offset: buf.read_u32::<LittleEndian>()?,
+            });
+        }
+
+        let mut data_offsets: Vec<u32> = entries.iter().map(|e| e.data_offset).collect();
+        data_offsets.sort_unstable();
+        data_offsets.dedup();
+
+        Ok(ResourceSet {
+            data,
+            reader_type,
+            resource_set_type,
+            version,
+            types,
+            hashes,
+            entries,
+            data_section,
+            data_offsets,
+        })
+    }
+
+    /// Gets the name of the `IResourceReader` type that reads the file, which is `System.Resources.ResourceReader`
+    /// for the files this reads.
+    pub fn reader_type(&self) -> Option<&'a str> {
+        self.reader_type
+    }
+
+    pub fn resource_set_type(&self) -> Option<&'a str> {
+        self.resource_set_type
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Gets the names of the types of the serialized values in the file.
+    pub fn types(&self) -> &[&'a str] {
+        &self.types
+    }
+
+    /// Gets the resources, which are sorted by the hash of their names.
+    pub fn entries(&self) -> &[ResourceEntry] {
+        &self.entries
+    }
+
+    /// Decodes the value of a resource.
+    pub fn value(&self, entry: &ResourceEntry) -> Result<ResourceValue<'a>, Error> {
+        // A value extends up to the next value, which is only needed for serialized values
+        let start = self.data_section
+            .checked_add(entry.data_offset as usize)
+            .filter(|&start| start <= self.data.len())
+            .ok_or(invalid("A resource value is outside the resources."))?;
+        let next = self.data_offsets
+            .get(self.data_offsets.partition_point(|&offset| offset <= entry.data_offset))
+            .map(|&offset| self.data_section + offset as usize);
+        let end = match next {
+            Some(end) if end <= self.data.len() => end,
+            _ => self.data.len(),
+        };
+        let mut buf = &self.data[start..end];
+
+        Ok(match read_7bit_int(&mut buf)? {
+            0x00 => ResourceValue::Null,
+            0x01 => ResourceValue::String(read_string(&mut buf)?),
+            0x02 => ResourceValue::Boolean(buf.read_u8()? != 0),
+            0x03 => ResourceValue::Char(buf.read_u16::<LittleEndian>()?),
+            0x04 => ResourceValue::Byte(buf.read_u8()?),
+            0x05 => ResourceValue::SByte(buf.read_i8()?),
+            0x06 => ResourceValue::Int16(buf.read_i16::<LittleEndian>()?),
+            0x07 => ResourceValue::UInt16(buf.read_u16::<LittleEndian>()?),
+            0x08 => ResourceValue::Int32(buf.read_i32::<LittleEndian>()?),
+            0x09 => ResourceValue::UInt32(buf.read_u32::<LittleEndian>()?),
+            0x0A => ResourceValue::Int64(buf.read_i64::<LittleEndian>()?),
+            0x0B => ResourceValue::UInt64(buf.read_u64::<LittleEndian>()?),
+            0x0C => ResourceValue::Single(buf.read_f32::<LittleEndian>()?),
+            0x0D => ResourceValue::Double(buf.read_f64::<LittleEndian>()?),
+            0x0E => {
+                let mut bits = [0; 4];
+                buf.read_i32_into::<LittleEndian>(&mut bits)?;
+                ResourceValue::Decimal(bits)
+            }
+            0x0F => ResourceValue::DateTime(buf.read_i64::<LittleEndian>()?),
+            0x10 => ResourceValue::TimeSpan(buf.read_i64::<LittleEndian>()?),
+            0x20 => {
+                let len = buf.read_u32::<LittleEndian>()? as usize;
+                ResourceValue::ByteArray(take(&mut buf, len)?)
+            }
+            0x21 => {
+                let len = buf.read_u32::<LittleEndian>()? as usize;
+                ResourceValue::Stream(take(&mut buf, len)?)
+            }
+            x if x >= 0x40 => ResourceValue::Serialized {
+                type_name: self.types
+                    .get((x - 0x40) as usize)
+                    .ok_or(invalid("A resource value has a type that is not in the type table."))?,
+                data: buf,
+            },
+            x => return Err(Error::UnknownTypeCode(x)),
+        })
+    }
+
+    /// Looks up a resource by name, using the name hashes.
+    pub fn get(&self, name: &str) -> Result<Option<ResourceValue<'a>>, Error> {
+        let hash = hash_name(name);
+        let start = self.hashes.partition_point(|&h| h < hash);
+        for idx in start..self.hashes.len() {
+            if self.hashes[idx] != hash {
+                break;
+            }
+            if self.entries[idx].name == name {
+                return Ok(Some(self.value(&self.entries[idx])?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Hashes a resource name, using the same function as `System.Resources.FastResourceComparer`.
+pub fn hash_name(name: &str) -> i32 {
+    name.encode_utf16()
+        .fold(5381u32, |hash, c| ((hash << 5).wrapping_add(hash)) ^ c as u32) as i32
+}
+
+fn invalid(message: &'static str) -> Error {
+    Error::InvalidResources(message)
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if len > buf.len() {
+        return Err(invalid("A length in the resources extends past the end of the resources."));
+    }
+    let (data, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(data)
+}
+
+/// Reads an integer in the 7-bit encoding used by `BinaryWriter`, with the low seven bits first.
+fn read_7bit_int(buf: &mut &[u8]) -> Result<u32, Error> {
+    let mut value = 0u32;
+    for shift in 0..5 {
+        let b = buf.read_u8()?;
+        value |= ((b & 0x7F) as u32) << (shift * 7);
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("A 7-bit encoded integer in the resources is too long."))
+}
+
+/// Reads a string in the format used by `BinaryWriter`, which is UTF-8 prefixed by its length in bytes.
+fn read_string<'a>(buf: &mut &'a [u8]) -> Result<&'a str, Error> {
+    let len = read_7bit_int(buf)? as usize;
+    Ok(str::from_utf8(take(buf, len)?)?)
+}
+
+fn read_utf16(mut data: &[u8]) -> Result<String, Error> {
+    let mut units = Vec::with_capacity(data.len() / 2);
+    while data.len() >= 2 {
+        units.push(data.read_u16::<LittleEndian>()?);
+    }
+    if !data.is_empty() {
+        return Err(Error::InvalidStringData);
+    }
+    Ok(String::from_utf16(&units)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_7bit_int(buf: &mut Vec<u8>, mut val: u32) {
+        while val >= 0x80 {
+            buf.push(val as u8 | 0x80);
+            val >>= 7;
+        }
+        buf.push(val as u8);
+    }
+
+    fn write_string(buf: &mut Vec<u8>, val: &str) {
+        write_7bit_int(buf, val.len() as u32);
+        buf.extend_from_slice(val.as_bytes());
+    }
+
+    /// Builds a `.resources` file the way `ResourceWriter` does, from names and encoded values.
+    fn build_resources(types: &[&str], resources: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut header = Vec::new();
+        write_string(&mut header, "System.Resources.ResourceReader, mscorlib");
+        write_string(&mut header, "System.Resources.RuntimeResourceSet");
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&ResourceSet::MAGIC.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        data.extend(header);
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&(resources.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(types.len() as u32).to_le_bytes());
+        for name in types {
+            write_string(&mut data, name);
+        }
+        let mut pad = b"PAD".iter().cycle();
+        while data.len() % 8 != 0 {
+            data.push(*pad.next().unwrap());
+        }
+
+        // The names are written in hash order, the values in the order they were added
+        let mut sorted: Vec<_> = resources.iter().enumerate().collect();
+        sorted.sort_by_key(|&(_, &(name, _))| hash_name(name));
+        let mut value_offsets = Vec::new();
+        let mut values = Vec::new();
+        for (_, value) in resources {
+            value_offsets.push(values.len() as u32);
+            values.extend_from_slice(value);
+        }
+        let mut names = Vec::new();
+        let mut name_offsets = Vec::new();
+        for &(idx, &(name, _)) in sorted.iter() {
+            data.extend_from_slice(&hash_name(name).to_le_bytes());
+            name_offsets.push(names.len() as u32);
+            let name: Vec<u8> = name.encode_utf16().flat_map(|c| c.to_le_bytes().to_vec()).collect();
+            write_7bit_int(&mut names, name.len() as u32);
+            names.extend(name);
+            names.extend_from_slice(&value_offsets[idx].to_le_bytes());
+        }
+        for offset in name_offsets {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        let data_section = data.len() + 4 + names.len();
+        data.extend_from_slice(&(data_section as u32).to_le_bytes());
+        data.extend(names);
+        data.extend(values);
+        data
+    }
+
+    fn sample_resources() -> Vec<u8> {
+        let mut greeting = vec![0x01];
+        write_string(&mut greeting, "Hello, World");
+        build_resources(
+            &["System.Drawing.Point, System.Drawing"],
+            &[
+                ("Greeting", greeting),
+                ("Enabled", vec![0x02, 0x01]),
+                ("Count", vec![0x08, 0xFE, 0xFF, 0xFF, 0xFF]),
+                ("Ratio", [vec![0x0D], 0.5f64.to_le_bytes().to_vec()].concat()),
+                ("Price", vec![0x0E, 0x39, 0x30, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02, 0x80]),
+                ("Logo", vec![0x20, 3, 0, 0, 0, 1, 2, 3]),
+                ("Location", vec![0x40, 0xAA, 0xBB]),
+                ("Nothing", vec![0x00]),
+            ],
+        )
+    }
+
+    #[test]
+    pub fn read_resource_set() {
+        let data = sample_resources();
+        let set = ResourceSet::read(&data).unwrap();
+        assert_eq!(Some("System.Resources.ResourceReader, mscorlib"), set.reader_type());
+        assert_eq!(Some("System.Resources.RuntimeResourceSet"), set.resource_set_type());
+        assert_eq!(2, set.version());
+        assert_eq!(&["System.Drawing.Point, System.Drawing"], set.types());
+        assert_eq!(8, set.entries().len());
+
+        assert_eq!(Ok(Some(ResourceValue::String("Hello, World"))), set.get("Greeting"));
+        assert_eq!(Ok(Some(ResourceValue::Boolean(true))), set.get("Enabled"));
+        assert_eq!(Ok(Some(ResourceValue::Int32(-2))), set.get("Count"));
+        assert_eq!(Ok(Some(ResourceValue::Double(0.5))), set.get("Ratio"));
+        assert_eq!(Ok(Some(ResourceValue::ByteArray(&[1, 2, 3]))), set.get("Logo"));
+        assert_eq!(Ok(Some(ResourceValue::Null)), set.get("Nothing"));
+        assert_eq!(Ok(None), set.get("Missing"));
+
+        // Serialized values extend up to the next value
+        assert_eq!(
+            Ok(Some(ResourceValue::Serialized {
+                type_name: "System.Drawing.Point, System.Drawing",
+                data: &[0xAA, 0xBB],
+            })),
+            set.get("Location")
+        );
+        assert_eq!("decimal(-123.45)", format!("{}", set.get("Price").unwrap().unwrap()));
+    }
+
+    #[test]
+    pub fn hash_names() {
+        assert_eq!(0x2B5E4, hash_name("A"));
+        assert_eq!(5381, hash_name(""));
+    }
+
+    #[test]
+    pub fn format_decimals() {
+        assert_eq!("0.05", format_decimal([5, 0, 0, 0x0002_0000]));
+        assert_eq!("79228162514264337593543950335", format_decimal([-1, -1, -1, 0]));
+    }
+
+    #[test]
+    pub fn invalid_resources() {
+        assert_eq!(
+            Some(invalid("The resources do not start with the resource manager signature.")),
+            ResourceSet::read(&[0, 0, 0, 0]).err()
+        );
+
+        let data = build_resources(&[], &[("A", vec![0x01, 0x7F])]);
+        let set = ResourceSet::read(&data).unwrap();
+        assert_eq!(
+            Some(invalid("A length in the resources extends past the end of the resources.")),
+            set.get("A").err()
+        );
+
+        let data = build_resources(&[], &[("A", vec![0x11])]);
+        let set = ResourceSet::read(&data).unwrap();
+        assert_eq!(Some(Error::UnknownTypeCode(0x11)), set.get("A").err());
+
+        let data = build_resources(&[], &[("A", vec![0x41])]);
+        let set = ResourceSet::read(&data).unwrap();
+        assert_eq!(
+            Some(invalid("A resource value has a type that is not in the type table.")),
+            set.get("A").err()
+        );
+    }
+
+    #[test]
+    pub fn overlapping_names_are_bounded() {
+        // A long name, which hashes lower than the empty names so it is written first, at name offset 0
+        let long_name = "x".repeat(0x100);
+        let mut resources = vec![(&long_name[..], vec![0x00])];
+        resources.extend((0..0x100).map(|_| ("", vec![0x00])));
+        let mut data = build_resources(&[], &resources);
+        assert!(ResourceSet::read(&data).is_ok());
+
+        // Pointing every entry at the long name would take a long time to read in a bigger file
+        let header_len: usize = 12 + 42 + 36 + 12;
+        let name_offsets = header_len.div_ceil(8) * 8 + resources.len() * 4;
+        for byte in data[name_offsets..(name_offsets + resources.len() * 4)].iter_mut() {
+            *byte = 0;
+        }
+        assert_eq!(
+            Some(invalid("The resource names are longer than the name section.")),
+            ResourceSet::read(&data).err()
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+use std::io::Read;
+
+use byteorder::ReadBytesExt;
+
+use cli::signatures::{CustomModifier, SignatureKind, TypeReference};
+use cli::signatures::utils;
+
+use error::Error;
+
+/// Represents the type of a field, stored in the Field and MemberRef tables.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FieldSignature {
+    pub modifiers: Vec<CustomModifier>,
+    pub field_type: TypeReference,
+}
+
+impl FieldSignature {
+    pub fn new(modifiers: Vec<CustomModifier>, field_type: TypeReference) -> FieldSignature {
+        FieldSignature {
+            modifiers,
+            field_type,
+        }
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<FieldSignature, Error> {
+        if reader.read_u8()? != SignatureKind::Field as u8 {
+            return Err(Error::InvalidMetadata("Field signature does not start with FIELD."));
+        }
+
+        let (mods, typ) = utils::read_modifiers_and_type(reader, 0)?;
+        Ok(FieldSignature::new(mods, typ))
+    }
+}
+
+impl fmt::Display for FieldSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write_list!(f, self.modifiers.iter(), " ");
+        write!(f, "{}", self.field_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use cli::tables::{TableHandle, TableIndex};
+
+    #[test]
+    pub fn volatile_field() {
+        // modreq([TypeRef 1]) int32
+        let mut buf = Cursor::new([0x06, 0x1F, 0x05, 0x08]);
+        let sig = FieldSignature::read(&mut buf).unwrap();
+        let modifier = CustomModifier::new(true, TableHandle::new(1, TableIndex::TypeRef));
+        assert_eq!(FieldSignature::new(vec![modifier], TypeReference::I4), sig);
+    }
+
+    #[test]
+    pub fn wrong_header() {
+        let mut buf = Cursor::new([0x07, 0x08]);
+        assert!(FieldSignature::read(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+use std::io::Read;
+
+use byteorder::ReadBytesExt;
+
+use cli::signatures::{CustomModifier, SignatureKind, TypeReference};
+use cli::signatures::utils;
+
+use error::Error;
+
+const ELEMENT_TYPE_CMOD_REQD: u32 = 0x1F;
+const ELEMENT_TYPE_CMOD_OPT: u32 = 0x20;
+const ELEMENT_TYPE_PINNED: u32 = 0x45;
+
+/// A local variable in a `LocalVarSignature`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LocalVariable {
+    pub modifiers: Vec<CustomModifier>,
+    pub pinned: bool,
+    pub local_type: TypeReference,
+}
+
+impl LocalVariable {
+    pub fn new(modifiers: Vec<CustomModifier>, pinned: bool, local_type: TypeReference) -> LocalVariable {
+        LocalVariable {
+            modifiers,
+            pinned,
+            local_type,
+        }
+    }
+}
+
+impl fmt::Display for LocalVariable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write_list!(f, self.modifiers.iter(), " ");
+        write!(f, "{}", self.local_type)?;
+        if self.pinned {
+            write!(f, " pinned")?;
+        }
+        Ok(())
+    }
+}
+
+/// Represents the local variables of a method body, stored in the StandAloneSig table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LocalVarSignature {
+    pub locals: Vec<LocalVariable>,
+}
+
+impl LocalVarSignature {
+    pub fn new(locals: Vec<LocalVariable>) -> LocalVarSignature {
+        LocalVarSignature { locals }
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<LocalVarSignature, Error> {
+        if reader.read_u8()? != SignatureKind::LocalVariables as u8 {
+            return Err(Error::InvalidMetadata("Local variable signature does not start with LOCAL_SIG."));
+        }
+
+        // Don't trust the count for pre-allocation, it could be huge
+        let count = utils::read_compressed_u32(reader)?;
+        let mut locals = Vec::new();
+        for _ in 0..count {
+            // Custom modifiers and the pinned constraint can appear in any order before the type
+            let mut modifiers = Vec::new();
+            let mut pinned = false;
+            let mut cur = utils::read_compressed_u32(reader)?;
+            loop {
+                match cur {
+                    ELEMENT_TYPE_PINNED => pinned = true,
+                    ELEMENT_TYPE_CMOD_REQD | ELEMENT_TYPE_CMOD_OPT => {
+                        let modifier_type = utils::read_type_def_or_ref_spec_encoded(reader)?;
+                        modifiers.push(CustomModifier::new(cur == ELEMENT_TYPE_CMOD_REQD, modifier_type));
+                    }
+                    _ => break,
+                }
+                cur = utils::read_compressed_u32(reader)?;
+            }
+            let local_type = utils::read_type(cur, reader, 0)?;
+            locals.push(LocalVariable::new(modifiers, pinned, local_type));
+        }
+        Ok(LocalVarSignature::new(locals))
+    }
+}
+
+impl fmt::Display for LocalVarSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "(")?;
+        for (i, local) in self.locals.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", local)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use cli::tables::{TableHandle, TableIndex};
+
+    #[test]
+    pub fn pinned_and_modified_locals() {
+        // (int32, string pinned, modopt([TypeRef 1]) ref int64)
+        let mut buf = Cursor::new([0x07, 0x03, 0x08, 0x45, 0x0E, 0x20, 0x05, 0x10, 0x0A]);
+        let sig = LocalVarSignature::read(&mut buf).unwrap();
+        let modifier = CustomModifier::new(false, TableHandle::new(1, TableIndex::TypeRef));
+        assert_eq!(
+            LocalVarSignature::new(vec![
+                LocalVariable::new(vec![], false, TypeReference::I4),
+                LocalVariable::new(vec![], true, TypeReference::String),
+                LocalVariable::new(vec![modifier], false, TypeReference::ByRef(Box::new(TypeReference::I8))),
+            ]),
+            sig
+        );
+        assert_eq!("(int32, string pinned, modopt(TypeRef[0x0001]) ref int64)", format!("{}", sig));
+    }
+
+    #[test]
+    pub fn wrong_header() {
+        let mut buf = Cursor::new([0x06, 0x01, 0x08]);
+        assert!(LocalVarSignature::read(&mut buf).is_err());
+
+        // Truncated after the pinned constraint
+        let mut buf = Cursor::new([0x07, 0x01, 0x45]);
+        assert!(LocalVarSignature::read(&mut buf).is_err());
+    }
+}
@@ -1,8 +1,6 @@
 use std::fmt;
 use std::io::Read;
 
-use byteorder::ReadBytesExt;
-
 use cli::signatures::{Param, RetType, SignatureCallingConvention, SignatureHeader, TypeReference};
 use cli::signatures::utils;
 
@@ -35,25 +33,34 @@ impl MethodSignature {
     }
 
     pub fn read<R: Read>(reader: &mut R) -> Result<MethodSignature, Error> {
-        let header = SignatureHeader::new(reader.read_u8()?);
+        MethodSignature::read_nested(reader, 0)
+    }
+
+    /// Reads a method signature that is nested inside `depth` other types, such as the target of a function pointer.
+    pub fn read_nested<R: Read>(reader: &mut R, depth: usize) -> Result<MethodSignature, Error> {
+        let header = SignatureHeader::read(reader)?;
         let generic_param_count = if header.is_generic() {
             utils::read_compressed_u32(reader)?
         } else {
             0
         };
         let param_count = utils::read_compressed_u32(reader)?;
-        let return_type = RetType::read(reader)?;
+        let (mods, typ) = utils::read_modifiers_and_type(reader, depth)?;
+        let return_type = RetType::new(mods, typ);
 
-        let mut parameters = Vec::with_capacity(param_count as usize);
+        // Don't trust the count for pre-allocation, it could be huge
+        let mut parameters = Vec::new();
         let mut required_parameter_count = None;
         for idx in 0..param_count {
-            let mut param = Param::read(reader)?;
-            if param.type_reference == TypeReference::Sentinel {
+            let (mut mods, mut typ) = utils::read_modifiers_and_type(reader, depth)?;
+            if typ == TypeReference::Sentinel {
                 // This is the marker for the varargs param
                 required_parameter_count = Some(idx);
-                param = Param::read(reader)?;
+                let param = utils::read_modifiers_and_type(reader, depth)?;
+                mods = param.0;
+                typ = param.1;
             }
-            parameters.push(param);
+            parameters.push(Param::new(mods, typ));
         }
 
         Ok(MethodSignature::new(
@@ -137,4 +144,13 @@ mod tests {
             sig
         );
     }
+
+    #[test]
+    pub fn invalid_header() {
+        // Found by fuzzing, this used to panic in SignatureHeader::new
+        assert_eq!(
+            Err(Error::InvalidMetadata("Signature header contains an unknown calling convention or kind.")),
+            MethodSignature::read(&mut Cursor::new([0x9E]))
+        );
+    }
 }
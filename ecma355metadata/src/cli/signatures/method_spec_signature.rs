@@ -0,0 +1,71 @@
+use std::fmt;
+use std::io::Read;
+
+use byteorder::ReadBytesExt;
+
+use cli::signatures::{SignatureKind, TypeReference};
+use cli::signatures::utils;
+
+use error::Error;
+
+/// Represents the instantiation of a generic method, stored in the MethodSpec table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MethodSpecSignature {
+    pub arguments: Vec<TypeReference>,
+}
+
+impl MethodSpecSignature {
+    pub fn new(arguments: Vec<TypeReference>) -> MethodSpecSignature {
+        MethodSpecSignature { arguments }
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<MethodSpecSignature, Error> {
+        if reader.read_u8()? != SignatureKind::MethodSpecification as u8 {
+            return Err(Error::InvalidMetadata("MethodSpec signature does not start with GENERICINST."));
+        }
+
+        let arg_count = utils::read_compressed_u32(reader)?;
+        let mut arguments = Vec::new();
+        for _ in 0..arg_count {
+            arguments.push(TypeReference::read(reader)?);
+        }
+        Ok(MethodSpecSignature::new(arguments))
+    }
+}
+
+impl fmt::Display for MethodSpecSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "<")?;
+        let mut first = true;
+        for arg in self.arguments.iter() {
+            if first {
+                first = false;
+            } else {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        write!(f, ">")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    pub fn two_arguments() {
+        let mut buf = Cursor::new([0x0A, 0x02, 0x08, 0x13, 0x00]);
+        let sig = MethodSpecSignature::read(&mut buf).unwrap();
+        assert_eq!(MethodSpecSignature::new(vec![TypeReference::I4, TypeReference::Var(0)]), sig);
+        assert_eq!("<int32, !0>", format!("{}", sig));
+    }
+
+    #[test]
+    pub fn wrong_header() {
+        let mut buf = Cursor::new([0x06, 0x01, 0x08]);
+        assert!(MethodSpecSignature::read(&mut buf).is_err());
+    }
+}
@@ -12,7 +12,10 @@ macro_rules! write_list {
 }
 
 mod custom_modifier;
+mod field_signature;
+mod local_var_signature;
 mod method_signature;
+mod method_spec_signature;
 mod param;
 mod ret_type;
 mod signature_header;
@@ -21,7 +24,10 @@ mod type_reference;
 pub mod utils;
 
 pub use self::custom_modifier::CustomModifier;
+pub use self::field_signature::FieldSignature;
+pub use self::local_var_signature::{LocalVarSignature, LocalVariable};
 pub use self::method_signature::MethodSignature;
+pub use self::method_spec_signature::MethodSpecSignature;
 pub use self::param::Param;
 pub use self::ret_type::RetType;
 pub use self::signature_header::{SignatureAttributes, SignatureCallingConvention, SignatureHeader,
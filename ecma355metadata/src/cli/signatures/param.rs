@@ -21,7 +21,7 @@ impl Param {
     }
 
     pub fn read<R: Read>(reader: &mut R) -> Result<Param, Error> {
-        let (mods, typ) = utils::read_modifiers_and_type(reader)?;
+        let (mods, typ) = utils::read_modifiers_and_type(reader, 0)?;
         Ok(Param::new(mods, typ))
     }
 }
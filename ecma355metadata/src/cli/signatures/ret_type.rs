@@ -21,7 +21,7 @@ impl RetType {
     }
 
     pub fn read<R: Read>(reader: &mut R) -> Result<RetType, Error> {
-        let (mods, typ) = utils::read_modifiers_and_type(reader)?;
+        let (mods, typ) = utils::read_modifiers_and_type(reader, 0)?;
         Ok(RetType::new(mods, typ))
     }
 }
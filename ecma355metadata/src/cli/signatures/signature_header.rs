@@ -1,8 +1,13 @@
 // Based on:
 // https://github.com/dotnet/corefx/blob/master/src/System.Reflection.Metadata/src/System/Reflection/Metadata/Signatures/SignatureHeader.cs
 
+use std::io::Read;
 use std::mem;
 
+use byteorder::ReadBytesExt;
+
+use error::Error;
+
 const CONV_OR_KIND_MASK: u8 = 0x0F;
 const MAX_CALLING_CONVENTION: u8 = SignatureCallingConvention::VarArgs as u8;
 const MAX_HEADER_VALUE: u8 = SignatureKind::MethodSpecification as u8;
@@ -50,6 +55,17 @@ impl SignatureHeader {
         SignatureHeader(value)
     }
 
+    /// Reads a header from signature data, failing rather than panicking if it contains an unknown calling convention or kind.
+    pub fn read<R: Read>(reader: &mut R) -> Result<SignatureHeader, Error> {
+        let value = reader.read_u8()?;
+        let calling_convention_or_kind = value & CONV_OR_KIND_MASK;
+        if calling_convention_or_kind > MAX_HEADER_VALUE || calling_convention_or_kind == 0x09 {
+            Err(Error::InvalidMetadata("Signature header contains an unknown calling convention or kind."))
+        } else {
+            Ok(SignatureHeader(value))
+        }
+    }
+
     pub fn is_generic(self) -> bool {
         self.attributes().contains(SignatureAttributes::GENERIC)
     }
@@ -129,4 +145,15 @@ mod tests {
         method_sig_fastcall_explicit_this: (0x44, SignatureKind::Method, SignatureCallingConvention::FastCall, SignatureAttributes::EXPLICIT_THIS);
         method_sig_stdcall_everything: (0x72, SignatureKind::Method, SignatureCallingConvention::StdCall, SignatureAttributes::GENERIC | SignatureAttributes::HAS_THIS | SignatureAttributes::EXPLICIT_THIS);
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn read_invalid_header() {
+        for &value in &[0x09, 0x0B, 0x0F, 0x9E] {
+            assert_eq!(
+                Err(Error::InvalidMetadata("Signature header contains an unknown calling convention or kind.")),
+                SignatureHeader::read(&mut &[value][..])
+            );
+        }
+        assert_eq!(SignatureHeader::new(0x20), SignatureHeader::read(&mut &[0x20][..]).unwrap());
+    }
+}
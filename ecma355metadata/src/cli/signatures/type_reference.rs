@@ -11,11 +11,14 @@ use error::Error;
 pub struct ArrayShape {
     pub rank: u32,
     pub sizes: Vec<u32>,
-    pub lo_bounds: Vec<u32>,
+    pub lo_bounds: Vec<i32>,
 }
 
 impl ArrayShape {
-    pub fn new(rank: u32, sizes: Vec<u32>, lo_bounds: Vec<u32>) -> ArrayShape {
+    /// The largest rank the runtime supports, larger ranks are rejected when reading.
+    pub const MAX_RANK: u32 = 32;
+
+    pub fn new(rank: u32, sizes: Vec<u32>, lo_bounds: Vec<i32>) -> ArrayShape {
         ArrayShape {
             rank,
             sizes,
@@ -25,15 +28,26 @@ impl ArrayShape {
 
     pub fn read<R: Read>(reader: &mut R) -> Result<ArrayShape, Error> {
         let rank = utils::read_compressed_u32(reader)?;
+        if rank > ArrayShape::MAX_RANK {
+            return Err(Error::InvalidMetadata("Array rank is larger than 32."));
+        }
+
+        // Each dimension has at most one size and one lower bound
         let num_sizes = utils::read_compressed_u32(reader)?;
-        let mut sizes = Vec::with_capacity(num_sizes as usize);
+        if num_sizes > rank {
+            return Err(Error::InvalidMetadata("Array shape has more sizes than dimensions."));
+        }
+        let mut sizes = Vec::new();
         for _ in 0..num_sizes {
             sizes.push(utils::read_compressed_u32(reader)?);
         }
         let num_lo_bounds = utils::read_compressed_u32(reader)?;
-        let mut lo_bounds = Vec::with_capacity(num_lo_bounds as usize);
+        if num_lo_bounds > rank {
+            return Err(Error::InvalidMetadata("Array shape has more lower bounds than dimensions."));
+        }
+        let mut lo_bounds = Vec::new();
         for _ in 0..num_lo_bounds {
-            lo_bounds.push(utils::read_compressed_u32(reader)?);
+            lo_bounds.push(utils::read_compressed_i32(reader)?);
         }
 
         Ok(ArrayShape::new(rank, sizes, lo_bounds))
@@ -41,8 +55,25 @@ impl ArrayShape {
 }
 
 impl fmt::Display for ArrayShape {
-    fn fmt(&self, _f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        unimplemented!()
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        // Uses the ILAsm syntax, where each dimension is written as "lo...hi", "lo..." or left empty
+        write!(f, "[")?;
+        for dim in 0..(self.rank as usize) {
+            if dim > 0 {
+                write!(f, ",")?;
+            }
+            match (self.lo_bounds.get(dim), self.sizes.get(dim)) {
+                (lo, Some(&size)) => {
+                    let lo = lo.cloned().unwrap_or(0) as i64;
+                    write!(f, "{}...{}", lo, lo + (size as i64) - 1)?;
+                }
+                (Some(lo), None) => write!(f, "{}...", lo)?,
+                // A lone empty dimension would look like an SZ array, so make it explicit
+                (None, None) if self.rank == 1 => write!(f, "...")?,
+                (None, None) => {}
+            }
+        }
+        write!(f, "]")
     }
 }
 
@@ -82,7 +113,7 @@ pub enum TypeReference {
 
 impl TypeReference {
     pub fn read<R: Read>(reader: &mut R) -> Result<TypeReference, Error> {
-        utils::read_type(utils::read_compressed_u32(reader)?, reader)
+        utils::read_type(utils::read_compressed_u32(reader)?, reader, 0)
     }
 }
 
@@ -116,7 +147,7 @@ impl fmt::Display for TypeReference {
             TypeReference::ByRef(ref inner) => write!(f, "ref {}", inner),
             TypeReference::ValueType(ref handle) => write!(f, "struct({})", handle),
             TypeReference::Class(ref handle) => write!(f, "class({})", handle),
-            TypeReference::Var(idx) => write!(f, "!!{}", idx),
+            TypeReference::Var(idx) => write!(f, "!{}", idx),
             TypeReference::MVar(idx) => write!(f, "!!{}", idx),
             TypeReference::Array(ref inner, ref shape) => write!(f, "{}{}", inner, shape),
             TypeReference::GenericInst(ref inner, ref types) => {
@@ -246,4 +277,60 @@ mod tests {
             ]
         ))));
     }
+
+    #[test]
+    pub fn array_shape_display() {
+        assert_eq!("[0...9]", format!("{}", ArrayShape::new(1, vec![10], vec![0])));
+        assert_eq!("[...]", format!("{}", ArrayShape::new(1, vec![], vec![])));
+        assert_eq!("[,]", format!("{}", ArrayShape::new(2, vec![], vec![])));
+        assert_eq!("[-1...1,2...,]", format!("{}", ArrayShape::new(3, vec![3], vec![-1, 2])));
+        assert_eq!(
+            "boolean[0...9]",
+            format!("{}", TypeReference::Array(Box::new(TypeReference::Boolean), ArrayShape::new(1, vec![10], vec![0])))
+        );
+    }
+
+    #[test]
+    pub fn nesting_limit() {
+        // 63 levels of ByRef around an int32 is fine, 64 is too deep
+        let mut data = vec![0x10; utils::MAX_SIGNATURE_DEPTH - 1];
+        data.push(0x08);
+        assert!(TypeReference::read(&mut &data[..]).is_ok());
+
+        let mut data = vec![0x10; utils::MAX_SIGNATURE_DEPTH];
+        data.push(0x08);
+        assert_eq!(Err(Error::InvalidMetadata("Signature is nested too deeply.")), TypeReference::read(&mut &data[..]));
+    }
+
+    #[test]
+    pub fn deeply_nested_signatures_fail() {
+        let expected = Err(Error::InvalidMetadata("Signature is nested too deeply."));
+        for pattern in &[&[0x10][..], &[0x0F, 0x10], &[0x1D], &[0x15, 0x12, 0x42, 0x01], &[0x1B, 0x00, 0x00]] {
+            let data: Vec<u8> = pattern.iter().cloned().cycle().take(pattern.len() * 100_000).collect();
+            assert_eq!(expected, TypeReference::read(&mut &data[..]));
+        }
+    }
+
+    #[test]
+    pub fn huge_counts_fail_without_allocating() {
+        // A generic instantiation claiming 0x1FFFFFFF arguments, with no data after the count
+        assert!(TypeReference::read(&mut &[0x15, 0x12, 0x42, 0xDF, 0xFF, 0xFF, 0xFF][..]).is_err());
+    }
+
+    #[test]
+    pub fn invalid_array_shapes() {
+        // Found by fuzzing, a rank of 0x1E114100 took minutes to display
+        assert_eq!(
+            Err(Error::InvalidMetadata("Array rank is larger than 32.")),
+            TypeReference::read(&mut &[0x14, 0x01, 0xFE, 0x11, 0x41, 0x00, 0x00, 0x00][..])
+        );
+        assert_eq!(
+            Err(Error::InvalidMetadata("Array shape has more sizes than dimensions.")),
+            TypeReference::read(&mut &[0x14, 0x08, 0x01, 0xDF, 0xFF, 0xFF, 0xFF][..])
+        );
+        assert_eq!(
+            Err(Error::InvalidMetadata("Array shape has more lower bounds than dimensions.")),
+            TypeReference::read(&mut &[0x14, 0x08, 0x01, 0x00, 0x02, 0x00, 0x00][..])
+        );
+    }
 }
@@ -6,6 +6,11 @@ use cli::signatures::{ArrayShape, CustomModifier, TypeReference, MethodSignature
 
 use error::Error;
 
+/// The deepest that types can be nested inside a signature, such as `ref *ref int32`.
+///
+/// Real signatures don't come close, but without a limit a malicious one could overflow the stack.
+pub const MAX_SIGNATURE_DEPTH: usize = 64;
+
 // Utilities for reading, used by types within this module
 pub fn read_type_def_or_ref_spec_encoded<R: Read>(reader: &mut R) -> Result<TableHandle, Error> {
     let val = read_compressed_u32(reader)?;
@@ -22,7 +27,12 @@ pub fn read_type_def_or_ref_spec_encoded<R: Read>(reader: &mut R) -> Result<Tabl
     Ok(TableHandle::new(index as usize, table))
 }
 
-pub fn read_type<R: Read>(discriminator: u32, reader: &mut R) -> Result<TypeReference, Error> {
+/// Reads the rest of a type, given its first value. `depth` is the number of types that contain this one.
+pub fn read_type<R: Read>(discriminator: u32, reader: &mut R, depth: usize) -> Result<TypeReference, Error> {
+    if depth >= MAX_SIGNATURE_DEPTH {
+        return Err(Error::InvalidMetadata("Signature is nested too deeply."));
+    }
+
     match discriminator {
         0x00 => Ok(TypeReference::End),
         0x01 => Ok(TypeReference::Void),
@@ -41,10 +51,10 @@ pub fn read_type<R: Read>(discriminator: u32, reader: &mut R) -> Result<TypeRefe
         0x0E => Ok(TypeReference::String),
         0x0F => {
             // Ptr
-            let (mods, typ) = read_modifiers_and_type(reader)?;
+            let (mods, typ) = read_modifiers_and_type(reader, depth + 1)?;
             Ok(TypeReference::Ptr(mods, Box::new(typ)))
         },
-        0x10 => Ok(TypeReference::ByRef(Box::new(TypeReference::read(reader)?))),
+        0x10 => Ok(TypeReference::ByRef(Box::new(read_nested_type(reader, depth)?))),
         0x11 => {
             // ValueType
             let typ = read_type_def_or_ref_spec_encoded(reader)?;
@@ -58,28 +68,30 @@ pub fn read_type<R: Read>(discriminator: u32, reader: &mut R) -> Result<TypeRefe
         0x13 => Ok(TypeReference::Var(read_compressed_u32(reader)?)),
         0x14 => {
             // Array
-            let element_type = TypeReference::read(reader)?;
+            let element_type = read_nested_type(reader, depth)?;
             let shape = ArrayShape::read(reader)?;
             Ok(TypeReference::Array(Box::new(element_type), shape))
         },
         0x15 => {
             // GenericInst
-            let inst_type = TypeReference::read(reader)?;
+            let inst_type = read_nested_type(reader, depth)?;
             let arg_count = read_compressed_u32(reader)?;
-            let mut args = Vec::with_capacity(arg_count as usize);
+
+            // Don't trust the count for pre-allocation, it could be huge
+            let mut args = Vec::new();
             for _ in 0..arg_count {
-                args.push(TypeReference::read(reader)?);
+                args.push(read_nested_type(reader, depth)?);
             }
             Ok(TypeReference::GenericInst(Box::new(inst_type), args))
         },
         0x16 => Ok(TypeReference::TypedByRef),
         0x18 => Ok(TypeReference::I),
         0x19 => Ok(TypeReference::U),
-        0x1B => Ok(TypeReference::FnPtr(Box::new(MethodSignature::read(reader)?))),
+        0x1B => Ok(TypeReference::FnPtr(Box::new(MethodSignature::read_nested(reader, depth + 1)?))),
         0x1C => Ok(TypeReference::Object),
         0x1D => {
             // SzArray
-            let (mods, typ) = read_modifiers_and_type(reader)?;
+            let (mods, typ) = read_modifiers_and_type(reader, depth + 1)?;
             Ok(TypeReference::SzArray(mods, Box::new(typ)))
         }
         0x1E => Ok(TypeReference::MVar(read_compressed_u32(reader)?)),
@@ -88,7 +100,14 @@ pub fn read_type<R: Read>(discriminator: u32, reader: &mut R) -> Result<TypeRefe
     }
 }
 
-pub fn read_modifiers_and_type<R: Read>(reader: &mut R) -> Result<(Vec<CustomModifier>, TypeReference), Error> {
+fn read_nested_type<R: Read>(reader: &mut R, depth: usize) -> Result<TypeReference, Error> {
+    read_type(read_compressed_u32(reader)?, reader, depth + 1)
+}
+
+pub fn read_modifiers_and_type<R: Read>(
+    reader: &mut R,
+    depth: usize,
+) -> Result<(Vec<CustomModifier>, TypeReference), Error> {
     let mut cur = read_compressed_u32(reader)?;
     let mut mods = Vec::new();
     while cur == 0x20 || cur == 0x1F {
@@ -104,7 +123,7 @@ pub fn read_modifiers_and_type<R: Read>(reader: &mut R) -> Result<(Vec<CustomMod
         mods.push(CustomModifier::new(required, read_type_def_or_ref_spec_encoded(reader)?));
         cur = read_compressed_u32(reader)?;
     }
-    let typ = read_type(cur, reader)?;
+    let typ = read_type(cur, reader, depth)?;
     Ok((mods, typ))
 }
 
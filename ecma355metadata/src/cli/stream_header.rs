@@ -39,9 +39,34 @@ fn read_nul_terminated_bytes<A: Read>(stream: &mut A, max: usize) -> Result<Vec<
             }
 
             bytes.push(*b);
-
-            // Panic if we go over the max
-            assert!(bytes.len() <= max);
+            if bytes.len() > max {
+                return Err(Error::InvalidMetadata("Stream name is longer than 32 bytes."));
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    pub fn read_stream_header() {
+        let header = StreamHeader::read(&mut Cursor::new(b"\x6C\0\0\0\x10\0\0\0#~\0\0")).unwrap();
+        assert_eq!(0x6C, header.offset);
+        assert_eq!(0x10, header.size);
+        assert_eq!("#~", header.name);
+    }
+
+    #[test]
+    pub fn read_stream_header_with_long_name() {
+        let mut data = vec![0u8; 8];
+        data.extend(vec![b'a'; 40]);
+        assert_eq!(
+            Err(Error::InvalidMetadata("Stream name is longer than 32 bytes.")),
+            StreamHeader::read(&mut Cursor::new(data)).map(|_| ())
+        );
+    }
+}
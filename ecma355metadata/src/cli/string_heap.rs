@@ -1,6 +1,3 @@
-use std::ffi::CStr;
-use std::mem;
-
 pub struct StringHeap<'a> {
     data: Option<&'a [u8]>,
 }
@@ -12,22 +9,15 @@ impl<'a> StringHeap<'a> {
         StringHeap { data: Some(data) }
     }
 
-    pub fn get(&self, idx: usize) -> Option<&[u8]> {
+    pub fn get(&self, idx: usize) -> Option<&'a [u8]> {
         if let Some(data) = self.data {
             // Bounds check
             if idx == 0 || idx >= data.len() {
                 None
             } else {
-                unsafe {
-                    // Find the start point and convert it to an unsafe pointer
-                    let ptr = mem::transmute(&data[idx]);
-
-                    // Load as a CStr
-                    let cstr = CStr::from_ptr(ptr);
-
-                    // Convert to an &str and return it
-                    Some(cstr.to_bytes())
-                }
+                // Strings are nul-terminated, a string that runs off the end of the heap is invalid
+                let bytes = &data[idx..];
+                bytes.iter().position(|&b| b == 0).map(|end| &bytes[..end])
             }
         } else {
             None
@@ -0,0 +1,61 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes, StringHandle, StringHandleReader};
+use cli::tables::{TableDecoder, TableIndex};
+use error::Error;
+
+pub struct Assembly {
+    pub hash_alg_id: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub build_number: u16,
+    pub revision_number: u16,
+    pub flags: u32,
+    pub public_key: BlobHandle,
+    pub name: StringHandle,
+    pub culture: StringHandle,
+}
+
+pub struct AssemblyDecoder {
+    count: usize,
+    blob_reader: BlobHandleReader,
+    string_reader: StringHandleReader,
+}
+
+impl TableDecoder for AssemblyDecoder {
+    type Item = Assembly;
+    const INDEX: TableIndex = TableIndex::Assembly;
+
+    fn new(sizes: &MetadataSizes) -> AssemblyDecoder {
+        AssemblyDecoder {
+            count: sizes.row_count(Self::INDEX),
+            blob_reader: BlobHandleReader::new(sizes),
+            string_reader: StringHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        (2 * size_of::<u32>()) + (4 * size_of::<u16>()) + self.blob_reader.size()
+            + (2 * self.string_reader.size())
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<Assembly, Error> {
+        Ok(Assembly {
+            hash_alg_id: buf.read_u32::<LittleEndian>()?,
+            major_version: buf.read_u16::<LittleEndian>()?,
+            minor_version: buf.read_u16::<LittleEndian>()?,
+            build_number: buf.read_u16::<LittleEndian>()?,
+            revision_number: buf.read_u16::<LittleEndian>()?,
+            flags: buf.read_u32::<LittleEndian>()?,
+            public_key: self.blob_reader.read(&mut buf)?,
+            name: self.string_reader.read(&mut buf)?,
+            culture: self.string_reader.read(&mut buf)?,
+        })
+    }
+}
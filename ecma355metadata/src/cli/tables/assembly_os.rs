@@ -0,0 +1,44 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableIndex};
+use error::Error;
+
+pub struct AssemblyOs {
+    pub os_platform_id: u32,
+    pub os_major_version: u32,
+    pub os_minor_version: u32,
+}
+
+pub struct AssemblyOsDecoder {
+    count: usize,
+}
+
+impl TableDecoder for AssemblyOsDecoder {
+    type Item = AssemblyOs;
+    const INDEX: TableIndex = TableIndex::AssemblyOS;
+
+    fn new(sizes: &MetadataSizes) -> AssemblyOsDecoder {
+        AssemblyOsDecoder {
+            count: sizes.row_count(Self::INDEX),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        3 * size_of::<u32>()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<AssemblyOs, Error> {
+        Ok(AssemblyOs {
+            os_platform_id: buf.read_u32::<LittleEndian>()?,
+            os_major_version: buf.read_u32::<LittleEndian>()?,
+            os_minor_version: buf.read_u32::<LittleEndian>()?,
+        })
+    }
+}
@@ -0,0 +1,40 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableIndex};
+use error::Error;
+
+pub struct AssemblyProcessor {
+    pub processor: u32,
+}
+
+pub struct AssemblyProcessorDecoder {
+    count: usize,
+}
+
+impl TableDecoder for AssemblyProcessorDecoder {
+    type Item = AssemblyProcessor;
+    const INDEX: TableIndex = TableIndex::AssemblyProcessor;
+
+    fn new(sizes: &MetadataSizes) -> AssemblyProcessorDecoder {
+        AssemblyProcessorDecoder {
+            count: sizes.row_count(Self::INDEX),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u32>()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<AssemblyProcessor, Error> {
+        Ok(AssemblyProcessor {
+            processor: buf.read_u32::<LittleEndian>()?,
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct AssemblyRefOs {
+    pub os_platform_id: u32,
+    pub os_major_version: u32,
+    pub os_minor_version: u32,
+    pub assembly_ref: TableHandle,
+}
+
+pub struct AssemblyRefOsDecoder {
+    count: usize,
+    assembly_ref_reader: TableHandleReader,
+}
+
+impl TableDecoder for AssemblyRefOsDecoder {
+    type Item = AssemblyRefOs;
+    const INDEX: TableIndex = TableIndex::AssemblyRefOS;
+
+    fn new(sizes: &MetadataSizes) -> AssemblyRefOsDecoder {
+        AssemblyRefOsDecoder {
+            count: sizes.row_count(Self::INDEX),
+            assembly_ref_reader: index_reader!(sizes, TableIndex::AssemblyRef),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        (3 * size_of::<u32>()) + self.assembly_ref_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<AssemblyRefOs, Error> {
+        Ok(AssemblyRefOs {
+            os_platform_id: buf.read_u32::<LittleEndian>()?,
+            os_major_version: buf.read_u32::<LittleEndian>()?,
+            os_minor_version: buf.read_u32::<LittleEndian>()?,
+            assembly_ref: self.assembly_ref_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,44 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct AssemblyRefProcessor {
+    pub processor: u32,
+    pub assembly_ref: TableHandle,
+}
+
+pub struct AssemblyRefProcessorDecoder {
+    count: usize,
+    assembly_ref_reader: TableHandleReader,
+}
+
+impl TableDecoder for AssemblyRefProcessorDecoder {
+    type Item = AssemblyRefProcessor;
+    const INDEX: TableIndex = TableIndex::AssemblyRefProcessor;
+
+    fn new(sizes: &MetadataSizes) -> AssemblyRefProcessorDecoder {
+        AssemblyRefProcessorDecoder {
+            count: sizes.row_count(Self::INDEX),
+            assembly_ref_reader: index_reader!(sizes, TableIndex::AssemblyRef),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u32>() + self.assembly_ref_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<AssemblyRefProcessor, Error> {
+        Ok(AssemblyRefProcessor {
+            processor: buf.read_u32::<LittleEndian>()?,
+            assembly_ref: self.assembly_ref_reader.read(&mut buf)?,
+        })
+    }
+}
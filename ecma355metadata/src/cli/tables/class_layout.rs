@@ -0,0 +1,46 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct ClassLayout {
+    pub packing_size: u16,
+    pub class_size: u32,
+    pub parent: TableHandle,
+}
+
+pub struct ClassLayoutDecoder {
+    count: usize,
+    parent_reader: TableHandleReader,
+}
+
+impl TableDecoder for ClassLayoutDecoder {
+    type Item = ClassLayout;
+    const INDEX: TableIndex = TableIndex::ClassLayout;
+
+    fn new(sizes: &MetadataSizes) -> ClassLayoutDecoder {
+        ClassLayoutDecoder {
+            count: sizes.row_count(Self::INDEX),
+            parent_reader: index_reader!(sizes, TableIndex::TypeDef),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u16>() + size_of::<u32>() + self.parent_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<ClassLayout, Error> {
+        Ok(ClassLayout {
+            packing_size: buf.read_u16::<LittleEndian>()?,
+            class_size: buf.read_u32::<LittleEndian>()?,
+            parent: self.parent_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,56 @@
+use std::mem::size_of;
+
+use byteorder::ReadBytesExt;
+
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct Constant {
+    pub constant_type: u8,
+    pub parent: TableHandle,
+    pub value: BlobHandle,
+}
+
+pub struct ConstantDecoder {
+    count: usize,
+    has_constant_reader: TableHandleReader,
+    blob_reader: BlobHandleReader,
+}
+
+impl TableDecoder for ConstantDecoder {
+    type Item = Constant;
+    const INDEX: TableIndex = TableIndex::Constant;
+
+    fn new(sizes: &MetadataSizes) -> ConstantDecoder {
+        ConstantDecoder {
+            count: sizes.row_count(Self::INDEX),
+            has_constant_reader: index_reader!(sizes,
+                0 => TableIndex::Field,
+                1 => TableIndex::Param,
+                2 => TableIndex::Property),
+            blob_reader: BlobHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        (2 * size_of::<u8>()) + self.has_constant_reader.size() + self.blob_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<Constant, Error> {
+        let constant_type = buf.read_u8()?;
+
+        // Skip the padding byte
+        buf.read_u8()?;
+
+        Ok(Constant {
+            constant_type,
+            parent: self.has_constant_reader.read(&mut buf)?,
+            value: self.blob_reader.read(&mut buf)?,
+        })
+    }
+}
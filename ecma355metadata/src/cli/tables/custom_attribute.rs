@@ -0,0 +1,92 @@
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct CustomAttribute {
+    pub parent: TableHandle,
+    pub attribute_type: TableHandle,
+    pub value: BlobHandle,
+}
+
+pub struct CustomAttributeDecoder {
+    count: usize,
+    has_custom_attribute_reader: TableHandleReader,
+    custom_attribute_type_reader: TableHandleReader,
+    blob_reader: BlobHandleReader,
+}
+
+impl TableDecoder for CustomAttributeDecoder {
+    type Item = CustomAttribute;
+    const INDEX: TableIndex = TableIndex::CustomAttribute;
+
+    fn new(sizes: &MetadataSizes) -> CustomAttributeDecoder {
+        CustomAttributeDecoder {
+            count: sizes.row_count(Self::INDEX),
+            has_custom_attribute_reader: index_reader!(sizes,
+                0 => TableIndex::MethodDef,
+                1 => TableIndex::Field,
+                2 => TableIndex::TypeRef,
+                3 => TableIndex::TypeDef,
+                4 => TableIndex::Param,
+                5 => TableIndex::InterfaceImpl,
+                6 => TableIndex::MemberRef,
+                7 => TableIndex::Module,
+                8 => TableIndex::DeclSecurity,
+                9 => TableIndex::Property,
+                10 => TableIndex::Event,
+                11 => TableIndex::StandAloneSig,
+                12 => TableIndex::ModuleRef,
+                13 => TableIndex::TypeSpec,
+                14 => TableIndex::Assembly,
+                15 => TableIndex::AssemblyRef,
+                16 => TableIndex::File,
+                17 => TableIndex::ExportedType,
+                18 => TableIndex::ManifestResource,
+                19 => TableIndex::GenericParam,
+                20 => TableIndex::GenericParamConstraint,
+                21 => TableIndex::MethodSpec),
+            // Tags 0, 1 and 4 are reserved, but still take up space in the tag
+            custom_attribute_type_reader: index_reader!(sizes, tag_bits = 3;
+                2 => TableIndex::MethodDef,
+                3 => TableIndex::MemberRef),
+            blob_reader: BlobHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.has_custom_attribute_reader.size() + self.custom_attribute_type_reader.size()
+            + self.blob_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<CustomAttribute, Error> {
+        Ok(CustomAttribute {
+            parent: self.has_custom_attribute_reader.read(&mut buf)?,
+            attribute_type: self.custom_attribute_type_reader.read(&mut buf)?,
+            value: self.blob_reader.read(&mut buf)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn decode_member_ref_constructor() {
+        // A #~ stream header with no tables and small heaps
+        let mut header = &[0u8; 24][..];
+        let sizes = MetadataSizes::read(&mut header).unwrap();
+        let decoder = CustomAttributeDecoder::new(&sizes);
+        assert_eq!(6, decoder.row_size());
+
+        // Parent: Assembly 1 (tag 14), Type: MemberRef 2 (tag 3, in a 3-bit tag), Value: blob 0x10
+        let row = decoder.decode(&[0x2E, 0x00, 0x13, 0x00, 0x10, 0x00]).unwrap();
+        assert_eq!(TableHandle::new(1, TableIndex::Assembly), row.parent);
+        assert_eq!(TableHandle::new(2, TableIndex::MemberRef), row.attribute_type);
+        assert_eq!(0x10, row.value.index());
+    }
+}
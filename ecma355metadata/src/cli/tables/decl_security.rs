@@ -0,0 +1,51 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct DeclSecurity {
+    pub action: u16,
+    pub parent: TableHandle,
+    pub permission_set: BlobHandle,
+}
+
+pub struct DeclSecurityDecoder {
+    count: usize,
+    has_decl_security_reader: TableHandleReader,
+    blob_reader: BlobHandleReader,
+}
+
+impl TableDecoder for DeclSecurityDecoder {
+    type Item = DeclSecurity;
+    const INDEX: TableIndex = TableIndex::DeclSecurity;
+
+    fn new(sizes: &MetadataSizes) -> DeclSecurityDecoder {
+        DeclSecurityDecoder {
+            count: sizes.row_count(Self::INDEX),
+            has_decl_security_reader: index_reader!(sizes,
+                0 => TableIndex::TypeDef,
+                1 => TableIndex::MethodDef,
+                2 => TableIndex::Assembly),
+            blob_reader: BlobHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u16>() + self.has_decl_security_reader.size() + self.blob_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<DeclSecurity, Error> {
+        Ok(DeclSecurity {
+            action: buf.read_u16::<LittleEndian>()?,
+            parent: self.has_decl_security_reader.read(&mut buf)?,
+            permission_set: self.blob_reader.read(&mut buf)?,
+        })
+    }
+}
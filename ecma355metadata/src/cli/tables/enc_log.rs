@@ -0,0 +1,42 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableIndex};
+use error::Error;
+
+pub struct EncLog {
+    pub token: u32,
+    pub func_code: u32,
+}
+
+pub struct EncLogDecoder {
+    count: usize,
+}
+
+impl TableDecoder for EncLogDecoder {
+    type Item = EncLog;
+    const INDEX: TableIndex = TableIndex::EncLog;
+
+    fn new(sizes: &MetadataSizes) -> EncLogDecoder {
+        EncLogDecoder {
+            count: sizes.row_count(Self::INDEX),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        2 * size_of::<u32>()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<EncLog, Error> {
+        Ok(EncLog {
+            token: buf.read_u32::<LittleEndian>()?,
+            func_code: buf.read_u32::<LittleEndian>()?,
+        })
+    }
+}
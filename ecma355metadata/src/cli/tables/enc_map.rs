@@ -0,0 +1,40 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableIndex};
+use error::Error;
+
+pub struct EncMap {
+    pub token: u32,
+}
+
+pub struct EncMapDecoder {
+    count: usize,
+}
+
+impl TableDecoder for EncMapDecoder {
+    type Item = EncMap;
+    const INDEX: TableIndex = TableIndex::EncMap;
+
+    fn new(sizes: &MetadataSizes) -> EncMapDecoder {
+        EncMapDecoder {
+            count: sizes.row_count(Self::INDEX),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u32>()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<EncMap, Error> {
+        Ok(EncMap {
+            token: buf.read_u32::<LittleEndian>()?,
+        })
+    }
+}
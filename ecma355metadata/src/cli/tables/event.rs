@@ -0,0 +1,51 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::{MetadataSizes, StringHandle, StringHandleReader};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct Event {
+    pub flags: u16,
+    pub name: StringHandle,
+    pub event_type: TableHandle,
+}
+
+pub struct EventDecoder {
+    count: usize,
+    string_reader: StringHandleReader,
+    type_def_or_ref_reader: TableHandleReader,
+}
+
+impl TableDecoder for EventDecoder {
+    type Item = Event;
+    const INDEX: TableIndex = TableIndex::Event;
+
+    fn new(sizes: &MetadataSizes) -> EventDecoder {
+        EventDecoder {
+            count: sizes.row_count(Self::INDEX),
+            string_reader: StringHandleReader::new(sizes),
+            type_def_or_ref_reader: index_reader!(sizes,
+                0 => TableIndex::TypeDef,
+                1 => TableIndex::TypeRef,
+                2 => TableIndex::TypeSpec),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u16>() + self.string_reader.size() + self.type_def_or_ref_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<Event, Error> {
+        Ok(Event {
+            flags: buf.read_u16::<LittleEndian>()?,
+            name: self.string_reader.read(&mut buf)?,
+            event_type: self.type_def_or_ref_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,42 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct EventMap {
+    pub parent: TableHandle,
+    pub event_list: TableHandle,
+}
+
+pub struct EventMapDecoder {
+    count: usize,
+    type_def_reader: TableHandleReader,
+    event_reader: TableHandleReader,
+}
+
+impl TableDecoder for EventMapDecoder {
+    type Item = EventMap;
+    const INDEX: TableIndex = TableIndex::EventMap;
+
+    fn new(sizes: &MetadataSizes) -> EventMapDecoder {
+        EventMapDecoder {
+            count: sizes.row_count(Self::INDEX),
+            type_def_reader: index_reader!(sizes, TableIndex::TypeDef),
+            event_reader: index_reader!(sizes, TableIndex::Event),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.type_def_reader.size() + self.event_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<EventMap, Error> {
+        Ok(EventMap {
+            parent: self.type_def_reader.read(&mut buf)?,
+            event_list: self.event_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,38 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct EventPtr {
+    pub event: TableHandle,
+}
+
+pub struct EventPtrDecoder {
+    count: usize,
+    event_reader: TableHandleReader,
+}
+
+impl TableDecoder for EventPtrDecoder {
+    type Item = EventPtr;
+    const INDEX: TableIndex = TableIndex::EventPtr;
+
+    fn new(sizes: &MetadataSizes) -> EventPtrDecoder {
+        EventPtrDecoder {
+            count: sizes.row_count(Self::INDEX),
+            event_reader: index_reader!(sizes, TableIndex::Event),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.event_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<EventPtr, Error> {
+        Ok(EventPtr {
+            event: self.event_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,55 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::{MetadataSizes, StringHandle, StringHandleReader, TypeAttributes};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct ExportedType {
+    pub flags: TypeAttributes,
+    pub type_def_id: u32,
+    pub type_name: StringHandle,
+    pub type_namespace: StringHandle,
+    pub implementation: TableHandle,
+}
+
+pub struct ExportedTypeDecoder {
+    count: usize,
+    string_reader: StringHandleReader,
+    implementation_reader: TableHandleReader,
+}
+
+impl TableDecoder for ExportedTypeDecoder {
+    type Item = ExportedType;
+    const INDEX: TableIndex = TableIndex::ExportedType;
+
+    fn new(sizes: &MetadataSizes) -> ExportedTypeDecoder {
+        ExportedTypeDecoder {
+            count: sizes.row_count(Self::INDEX),
+            string_reader: StringHandleReader::new(sizes),
+            implementation_reader: index_reader!(sizes,
+                0 => TableIndex::File,
+                1 => TableIndex::AssemblyRef,
+                2 => TableIndex::ExportedType),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        (2 * size_of::<u32>()) + (2 * self.string_reader.size()) + self.implementation_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<ExportedType, Error> {
+        Ok(ExportedType {
+            flags: TypeAttributes::new(buf.read_u32::<LittleEndian>()?),
+            type_def_id: buf.read_u32::<LittleEndian>()?,
+            type_name: self.string_reader.read(&mut buf)?,
+            type_namespace: self.string_reader.read(&mut buf)?,
+            implementation: self.implementation_reader.read(&mut buf)?,
+        })
+    }
+}
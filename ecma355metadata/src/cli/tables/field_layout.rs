@@ -0,0 +1,44 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct FieldLayout {
+    pub offset: u32,
+    pub field: TableHandle,
+}
+
+pub struct FieldLayoutDecoder {
+    count: usize,
+    field_reader: TableHandleReader,
+}
+
+impl TableDecoder for FieldLayoutDecoder {
+    type Item = FieldLayout;
+    const INDEX: TableIndex = TableIndex::FieldLayout;
+
+    fn new(sizes: &MetadataSizes) -> FieldLayoutDecoder {
+        FieldLayoutDecoder {
+            count: sizes.row_count(Self::INDEX),
+            field_reader: index_reader!(sizes, TableIndex::Field),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u32>() + self.field_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<FieldLayout, Error> {
+        Ok(FieldLayout {
+            offset: buf.read_u32::<LittleEndian>()?,
+            field: self.field_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,44 @@
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct FieldMarshal {
+    pub parent: TableHandle,
+    pub native_type: BlobHandle,
+}
+
+pub struct FieldMarshalDecoder {
+    count: usize,
+    has_field_marshal_reader: TableHandleReader,
+    blob_reader: BlobHandleReader,
+}
+
+impl TableDecoder for FieldMarshalDecoder {
+    type Item = FieldMarshal;
+    const INDEX: TableIndex = TableIndex::FieldMarshal;
+
+    fn new(sizes: &MetadataSizes) -> FieldMarshalDecoder {
+        FieldMarshalDecoder {
+            count: sizes.row_count(Self::INDEX),
+            has_field_marshal_reader: index_reader!(sizes,
+                0 => TableIndex::Field,
+                1 => TableIndex::Param),
+            blob_reader: BlobHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.has_field_marshal_reader.size() + self.blob_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<FieldMarshal, Error> {
+        Ok(FieldMarshal {
+            parent: self.has_field_marshal_reader.read(&mut buf)?,
+            native_type: self.blob_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,38 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct FieldPtr {
+    pub field: TableHandle,
+}
+
+pub struct FieldPtrDecoder {
+    count: usize,
+    field_reader: TableHandleReader,
+}
+
+impl TableDecoder for FieldPtrDecoder {
+    type Item = FieldPtr;
+    const INDEX: TableIndex = TableIndex::FieldPtr;
+
+    fn new(sizes: &MetadataSizes) -> FieldPtrDecoder {
+        FieldPtrDecoder {
+            count: sizes.row_count(Self::INDEX),
+            field_reader: index_reader!(sizes, TableIndex::Field),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.field_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<FieldPtr, Error> {
+        Ok(FieldPtr {
+            field: self.field_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,46 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+/// Records the initial value of a static field whose data is mapped into the image (such as the
+/// data used by `RuntimeHelpers.InitializeArray`).
+pub struct FieldRva {
+    pub rva: u32,
+    pub field: TableHandle,
+}
+
+pub struct FieldRvaDecoder {
+    count: usize,
+    field_reader: TableHandleReader,
+}
+
+impl TableDecoder for FieldRvaDecoder {
+    type Item = FieldRva;
+    const INDEX: TableIndex = TableIndex::FieldRva;
+
+    fn new(sizes: &MetadataSizes) -> FieldRvaDecoder {
+        FieldRvaDecoder {
+            count: sizes.row_count(Self::INDEX),
+            field_reader: index_reader!(sizes, TableIndex::Field),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u32>() + self.field_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<FieldRva, Error> {
+        Ok(FieldRva {
+            rva: buf.read_u32::<LittleEndian>()?,
+            field: self.field_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes, StringHandle, StringHandleReader};
+use cli::tables::{TableDecoder, TableIndex};
+use error::Error;
+
+pub struct File {
+    pub flags: u32,
+    pub name: StringHandle,
+    pub hash_value: BlobHandle,
+}
+
+pub struct FileDecoder {
+    count: usize,
+    string_reader: StringHandleReader,
+    blob_reader: BlobHandleReader,
+}
+
+impl TableDecoder for FileDecoder {
+    type Item = File;
+    const INDEX: TableIndex = TableIndex::File;
+
+    fn new(sizes: &MetadataSizes) -> FileDecoder {
+        FileDecoder {
+            count: sizes.row_count(Self::INDEX),
+            string_reader: StringHandleReader::new(sizes),
+            blob_reader: BlobHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u32>() + self.string_reader.size() + self.blob_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<File, Error> {
+        Ok(File {
+            flags: buf.read_u32::<LittleEndian>()?,
+            name: self.string_reader.read(&mut buf)?,
+            hash_value: self.blob_reader.read(&mut buf)?,
+        })
+    }
+}
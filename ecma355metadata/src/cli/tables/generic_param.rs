@@ -0,0 +1,52 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::{GenericParamAttributes, MetadataSizes, StringHandle, StringHandleReader};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct GenericParam {
+    pub number: u16,
+    pub flags: GenericParamAttributes,
+    pub owner: TableHandle,
+    pub name: StringHandle,
+}
+
+pub struct GenericParamDecoder {
+    count: usize,
+    type_or_method_def_reader: TableHandleReader,
+    string_reader: StringHandleReader,
+}
+
+impl TableDecoder for GenericParamDecoder {
+    type Item = GenericParam;
+    const INDEX: TableIndex = TableIndex::GenericParam;
+
+    fn new(sizes: &MetadataSizes) -> GenericParamDecoder {
+        GenericParamDecoder {
+            count: sizes.row_count(Self::INDEX),
+            type_or_method_def_reader: index_reader!(sizes,
+                0 => TableIndex::TypeDef,
+                1 => TableIndex::MethodDef),
+            string_reader: StringHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        (2 * size_of::<u16>()) + self.type_or_method_def_reader.size() + self.string_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<GenericParam, Error> {
+        Ok(GenericParam {
+            number: buf.read_u16::<LittleEndian>()?,
+            flags: GenericParamAttributes::new(buf.read_u16::<LittleEndian>()?),
+            owner: self.type_or_method_def_reader.read(&mut buf)?,
+            name: self.string_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,45 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct GenericParamConstraint {
+    pub owner: TableHandle,
+    pub constraint: TableHandle,
+}
+
+pub struct GenericParamConstraintDecoder {
+    count: usize,
+    owner_reader: TableHandleReader,
+    type_def_or_ref_reader: TableHandleReader,
+}
+
+impl TableDecoder for GenericParamConstraintDecoder {
+    type Item = GenericParamConstraint;
+    const INDEX: TableIndex = TableIndex::GenericParamConstraint;
+
+    fn new(sizes: &MetadataSizes) -> GenericParamConstraintDecoder {
+        GenericParamConstraintDecoder {
+            count: sizes.row_count(Self::INDEX),
+            owner_reader: index_reader!(sizes, TableIndex::GenericParam),
+            type_def_or_ref_reader: index_reader!(sizes,
+                0 => TableIndex::TypeDef,
+                1 => TableIndex::TypeRef,
+                2 => TableIndex::TypeSpec),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.owner_reader.size() + self.type_def_or_ref_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<GenericParamConstraint, Error> {
+        Ok(GenericParamConstraint {
+            owner: self.owner_reader.read(&mut buf)?,
+            constraint: self.type_def_or_ref_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,55 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::{MetadataSizes, PInvokeAttributes, StringHandle, StringHandleReader};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct ImplMap {
+    pub mapping_flags: PInvokeAttributes,
+    pub member_forwarded: TableHandle,
+    pub import_name: StringHandle,
+    pub import_scope: TableHandle,
+}
+
+pub struct ImplMapDecoder {
+    count: usize,
+    member_forwarded_reader: TableHandleReader,
+    string_reader: StringHandleReader,
+    module_ref_reader: TableHandleReader,
+}
+
+impl TableDecoder for ImplMapDecoder {
+    type Item = ImplMap;
+    const INDEX: TableIndex = TableIndex::ImplMap;
+
+    fn new(sizes: &MetadataSizes) -> ImplMapDecoder {
+        ImplMapDecoder {
+            count: sizes.row_count(Self::INDEX),
+            member_forwarded_reader: index_reader!(sizes,
+                0 => TableIndex::Field,
+                1 => TableIndex::MethodDef),
+            string_reader: StringHandleReader::new(sizes),
+            module_ref_reader: index_reader!(sizes, TableIndex::ModuleRef),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u16>() + self.member_forwarded_reader.size() + self.string_reader.size()
+            + self.module_ref_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<ImplMap, Error> {
+        Ok(ImplMap {
+            mapping_flags: PInvokeAttributes::new(buf.read_u16::<LittleEndian>()?),
+            member_forwarded: self.member_forwarded_reader.read(&mut buf)?,
+            import_name: self.string_reader.read(&mut buf)?,
+            import_scope: self.module_ref_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,45 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct InterfaceImpl {
+    pub class: TableHandle,
+    pub interface: TableHandle,
+}
+
+pub struct InterfaceImplDecoder {
+    count: usize,
+    class_reader: TableHandleReader,
+    type_def_or_ref_reader: TableHandleReader,
+}
+
+impl TableDecoder for InterfaceImplDecoder {
+    type Item = InterfaceImpl;
+    const INDEX: TableIndex = TableIndex::InterfaceImpl;
+
+    fn new(sizes: &MetadataSizes) -> InterfaceImplDecoder {
+        InterfaceImplDecoder {
+            count: sizes.row_count(Self::INDEX),
+            class_reader: index_reader!(sizes, TableIndex::TypeDef),
+            type_def_or_ref_reader: index_reader!(sizes,
+                0 => TableIndex::TypeDef,
+                1 => TableIndex::TypeRef,
+                2 => TableIndex::TypeSpec),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.class_reader.size() + self.type_def_or_ref_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<InterfaceImpl, Error> {
+        Ok(InterfaceImpl {
+            class: self.class_reader.read(&mut buf)?,
+            interface: self.type_def_or_ref_reader.read(&mut buf)?,
+        })
+    }
+}
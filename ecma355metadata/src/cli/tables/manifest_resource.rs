@@ -0,0 +1,126 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::{ManifestResourceAttributes, MetadataSizes, StringHandle, StringHandleReader};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct ManifestResource {
+    pub offset: u32,
+    pub flags: ManifestResourceAttributes,
+    pub name: StringHandle,
+    pub implementation: TableHandle,
+}
+
+/// Where the contents of a manifest resource are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestResourceLocation {
+    /// The resource is embedded in this image, at the specified offset in the CLI header's resources.
+    Embedded(u32),
+
+    /// The resource is stored in another file of this assembly, at the specified offset in that file.
+    File { file: TableHandle, offset: u32 },
+
+    /// The resource is stored in another assembly.
+    AssemblyRef(TableHandle),
+}
+
+impl ManifestResource {
+    pub fn location(&self) -> Result<ManifestResourceLocation, Error> {
+        if self.implementation.index() == 0 {
+            return Ok(ManifestResourceLocation::Embedded(self.offset));
+        }
+        match self.implementation.table() {
+            TableIndex::File => Ok(ManifestResourceLocation::File {
+                file: self.implementation,
+                offset: self.offset,
+            }),
+            TableIndex::AssemblyRef => Ok(ManifestResourceLocation::AssemblyRef(self.implementation)),
+            _ => Err(Error::InvalidMetadata("A manifest resource must be implemented by a File or an AssemblyRef.")),
+        }
+    }
+}
+
+pub struct ManifestResourceDecoder {
+    count: usize,
+    string_reader: StringHandleReader,
+    implementation_reader: TableHandleReader,
+}
+
+impl TableDecoder for ManifestResourceDecoder {
+    type Item = ManifestResource;
+    const INDEX: TableIndex = TableIndex::ManifestResource;
+
+    fn new(sizes: &MetadataSizes) -> ManifestResourceDecoder {
+        ManifestResourceDecoder {
+            count: sizes.row_count(Self::INDEX),
+            string_reader: StringHandleReader::new(sizes),
+            implementation_reader: index_reader!(sizes,
+                0 => TableIndex::File,
+                1 => TableIndex::AssemblyRef,
+                2 => TableIndex::ExportedType),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        (2 * size_of::<u32>()) + self.string_reader.size() + self.implementation_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<ManifestResource, Error> {
+        Ok(ManifestResource {
+            offset: buf.read_u32::<LittleEndian>()?,
+            flags: ManifestResourceAttributes::from_bits_truncate(buf.read_u32::<LittleEndian>()?),
+            name: self.string_reader.read(&mut buf)?,
+            implementation: self.implementation_reader.read(&mut buf)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(row: &[u8]) -> ManifestResource {
+        // A #~ stream header with no tables and small heaps
+        let mut header = &[0u8; 24][..];
+        let sizes = MetadataSizes::read(&mut header).unwrap();
+        let decoder = ManifestResourceDecoder::new(&sizes);
+        assert_eq!(12, decoder.row_size());
+        decoder.decode(row).unwrap()
+    }
+
+    #[test]
+    pub fn decode_embedded_resource() {
+        let row = decode(&[0x10, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00]);
+        assert_eq!(ManifestResourceAttributes::Public, row.flags);
+        assert_eq!(0x20, row.name.index());
+        assert_eq!(Ok(ManifestResourceLocation::Embedded(0x10)), row.location());
+    }
+
+    #[test]
+    pub fn decode_resource_locations() {
+        // Implementation: File 1 (tag 0)
+        let row = decode(&[0x10, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x20, 0x00, 0x04, 0x00]);
+        assert_eq!(ManifestResourceAttributes::Private, row.flags);
+        assert_eq!(
+            Ok(ManifestResourceLocation::File { file: TableHandle::new(1, TableIndex::File), offset: 0x10 }),
+            row.location()
+        );
+
+        // Implementation: AssemblyRef 3 (tag 1)
+        let row = decode(&[0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x20, 0x00, 0x0D, 0x00]);
+        assert_eq!(
+            Ok(ManifestResourceLocation::AssemblyRef(TableHandle::new(3, TableIndex::AssemblyRef))),
+            row.location()
+        );
+
+        // Implementation: ExportedType 1 (tag 2), which the coded index allows but resources can't use
+        let row = decode(&[0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x20, 0x00, 0x06, 0x00]);
+        assert!(row.location().is_err());
+    }
+}
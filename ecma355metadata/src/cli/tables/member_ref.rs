@@ -0,0 +1,51 @@
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes, StringHandle, StringHandleReader};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct MemberRef {
+    pub class: TableHandle,
+    pub name: StringHandle,
+    pub signature: BlobHandle,
+}
+
+pub struct MemberRefDecoder {
+    count: usize,
+    member_ref_parent_reader: TableHandleReader,
+    string_reader: StringHandleReader,
+    blob_reader: BlobHandleReader,
+}
+
+impl TableDecoder for MemberRefDecoder {
+    type Item = MemberRef;
+    const INDEX: TableIndex = TableIndex::MemberRef;
+
+    fn new(sizes: &MetadataSizes) -> MemberRefDecoder {
+        MemberRefDecoder {
+            count: sizes.row_count(Self::INDEX),
+            member_ref_parent_reader: index_reader!(sizes,
+                0 => TableIndex::TypeDef,
+                1 => TableIndex::TypeRef,
+                2 => TableIndex::ModuleRef,
+                3 => TableIndex::MethodDef,
+                4 => TableIndex::TypeSpec),
+            string_reader: StringHandleReader::new(sizes),
+            blob_reader: BlobHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.member_ref_parent_reader.size() + self.string_reader.size() + self.blob_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<MemberRef, Error> {
+        Ok(MemberRef {
+            class: self.member_ref_parent_reader.read(&mut buf)?,
+            name: self.string_reader.read(&mut buf)?,
+            signature: self.blob_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+/// Records that `method_body` provides the implementation of `method_declaration` for `class`, as with
+/// explicit interface implementations.
+pub struct MethodImpl {
+    pub class: TableHandle,
+    pub method_body: TableHandle,
+    pub method_declaration: TableHandle,
+}
+
+pub struct MethodImplDecoder {
+    count: usize,
+    class_reader: TableHandleReader,
+    method_def_or_ref_reader: TableHandleReader,
+}
+
+impl TableDecoder for MethodImplDecoder {
+    type Item = MethodImpl;
+    const INDEX: TableIndex = TableIndex::MethodImpl;
+
+    fn new(sizes: &MetadataSizes) -> MethodImplDecoder {
+        MethodImplDecoder {
+            count: sizes.row_count(Self::INDEX),
+            class_reader: index_reader!(sizes, TableIndex::TypeDef),
+            method_def_or_ref_reader: index_reader!(sizes,
+                0 => TableIndex::MethodDef,
+                1 => TableIndex::MemberRef),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.class_reader.size() + (2 * self.method_def_or_ref_reader.size())
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<MethodImpl, Error> {
+        Ok(MethodImpl {
+            class: self.class_reader.read(&mut buf)?,
+            method_body: self.method_def_or_ref_reader.read(&mut buf)?,
+            method_declaration: self.method_def_or_ref_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,38 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct MethodPtr {
+    pub method: TableHandle,
+}
+
+pub struct MethodPtrDecoder {
+    count: usize,
+    method_def_reader: TableHandleReader,
+}
+
+impl TableDecoder for MethodPtrDecoder {
+    type Item = MethodPtr;
+    const INDEX: TableIndex = TableIndex::MethodPtr;
+
+    fn new(sizes: &MetadataSizes) -> MethodPtrDecoder {
+        MethodPtrDecoder {
+            count: sizes.row_count(Self::INDEX),
+            method_def_reader: index_reader!(sizes, TableIndex::MethodDef),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.method_def_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<MethodPtr, Error> {
+        Ok(MethodPtr {
+            method: self.method_def_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,50 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct MethodSemantics {
+    pub semantics: u16,
+    pub method: TableHandle,
+    pub association: TableHandle,
+}
+
+pub struct MethodSemanticsDecoder {
+    count: usize,
+    method_def_reader: TableHandleReader,
+    has_semantics_reader: TableHandleReader,
+}
+
+impl TableDecoder for MethodSemanticsDecoder {
+    type Item = MethodSemantics;
+    const INDEX: TableIndex = TableIndex::MethodSemantics;
+
+    fn new(sizes: &MetadataSizes) -> MethodSemanticsDecoder {
+        MethodSemanticsDecoder {
+            count: sizes.row_count(Self::INDEX),
+            method_def_reader: index_reader!(sizes, TableIndex::MethodDef),
+            has_semantics_reader: index_reader!(sizes,
+                0 => TableIndex::Event,
+                1 => TableIndex::Property),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u16>() + self.method_def_reader.size() + self.has_semantics_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<MethodSemantics, Error> {
+        Ok(MethodSemantics {
+            semantics: buf.read_u16::<LittleEndian>()?,
+            method: self.method_def_reader.read(&mut buf)?,
+            association: self.has_semantics_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,44 @@
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes};
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex, TableMask};
+use error::Error;
+
+pub struct MethodSpec {
+    pub method: TableHandle,
+    pub instantiation: BlobHandle,
+}
+
+pub struct MethodSpecDecoder {
+    count: usize,
+    method_def_or_ref_reader: TableHandleReader,
+    blob_reader: BlobHandleReader,
+}
+
+impl TableDecoder for MethodSpecDecoder {
+    type Item = MethodSpec;
+    const INDEX: TableIndex = TableIndex::MethodSpec;
+
+    fn new(sizes: &MetadataSizes) -> MethodSpecDecoder {
+        MethodSpecDecoder {
+            count: sizes.row_count(Self::INDEX),
+            method_def_or_ref_reader: index_reader!(sizes,
+                0 => TableIndex::MethodDef,
+                1 => TableIndex::MemberRef),
+            blob_reader: BlobHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.method_def_or_ref_reader.size() + self.blob_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<MethodSpec, Error> {
+        Ok(MethodSpec {
+            method: self.method_def_or_ref_reader.read(&mut buf)?,
+            instantiation: self.blob_reader.read(&mut buf)?,
+        })
+    }
+}
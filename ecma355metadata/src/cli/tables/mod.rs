@@ -7,7 +7,7 @@ macro_rules! index_reader {
             |_| Some($idx))
     };
     (
-        $sizes:expr, $(
+        $sizes:expr, tag_bits = $bits:expr; $(
             $tag:expr => $idx:expr
         ),*
     ) => {
@@ -16,8 +16,8 @@ macro_rules! index_reader {
                 TableMask::from_index($idx)
             )|+;
             TableHandleReader::for_coded_index(
-                $sizes.coded_index_size(tables) == $crate::cli::LARGE_INDEX_SIZE,
-                tables,
+                $sizes.coded_index_size(tables, $bits) == $crate::cli::LARGE_INDEX_SIZE,
+                $bits,
                 |tag| match tag {
                     $(
                         $tag => Some($idx)
@@ -25,7 +25,19 @@ macro_rules! index_reader {
                     _ => None
                 })
         }
-    }
+    };
+    (
+        $sizes:expr, $(
+            $tag:expr => $idx:expr
+        ),*
+    ) => {
+        {
+            let tables = $(
+                TableMask::from_index($idx)
+            )|+;
+            index_reader!($sizes, tag_bits = TableHandleReader::tag_bits(tables); $($tag => $idx),*)
+        }
+    };
 }
 
 mod module;
@@ -34,10 +46,49 @@ mod type_def;
 mod field;
 mod method_def;
 mod param;
+mod type_spec;
+mod method_spec;
+mod generic_param;
+mod generic_param_constraint;
+mod class_layout;
+mod field_layout;
+mod constant;
+mod field_rva;
+mod module_ref;
+mod impl_map;
+mod field_marshal;
+mod stand_alone_sig;
+mod interface_impl;
+mod method_impl;
+mod member_ref;
+mod custom_attribute;
+mod field_ptr;
+mod method_ptr;
+mod param_ptr;
+mod decl_security;
+mod event_map;
+mod event_ptr;
+mod event;
+mod property_map;
+mod property_ptr;
+mod property;
+mod method_semantics;
+mod enc_log;
+mod enc_map;
+mod assembly;
+mod assembly_processor;
+mod assembly_os;
+mod assembly_ref;
+mod assembly_ref_processor;
+mod assembly_ref_os;
+mod file;
+mod exported_type;
+mod manifest_resource;
+mod nested_class;
+mod table;
 mod table_decoder;
 mod table_handle;
 mod table_index;
-// mod table_stream;
 
 pub use self::module::{Module, ModuleDecoder};
 pub use self::type_ref::{TypeRef, TypeRefDecoder};
@@ -45,7 +96,46 @@ pub use self::type_def::{TypeDef, TypeDefDecoder};
 pub use self::field::{Field, FieldDecoder};
 pub use self::method_def::{MethodDef, MethodDefDecoder};
 pub use self::param::{Param, ParamDecoder};
+pub use self::type_spec::{TypeSpec, TypeSpecDecoder};
+pub use self::method_spec::{MethodSpec, MethodSpecDecoder};
+pub use self::generic_param::{GenericParam, GenericParamDecoder};
+pub use self::generic_param_constraint::{GenericParamConstraint, GenericParamConstraintDecoder};
+pub use self::class_layout::{ClassLayout, ClassLayoutDecoder};
+pub use self::field_layout::{FieldLayout, FieldLayoutDecoder};
+pub use self::constant::{Constant, ConstantDecoder};
+pub use self::field_rva::{FieldRva, FieldRvaDecoder};
+pub use self::module_ref::{ModuleRef, ModuleRefDecoder};
+pub use self::impl_map::{ImplMap, ImplMapDecoder};
+pub use self::field_marshal::{FieldMarshal, FieldMarshalDecoder};
+pub use self::stand_alone_sig::{StandAloneSig, StandAloneSigDecoder};
+pub use self::interface_impl::{InterfaceImpl, InterfaceImplDecoder};
+pub use self::method_impl::{MethodImpl, MethodImplDecoder};
+pub use self::member_ref::{MemberRef, MemberRefDecoder};
+pub use self::custom_attribute::{CustomAttribute, CustomAttributeDecoder};
+pub use self::field_ptr::{FieldPtr, FieldPtrDecoder};
+pub use self::method_ptr::{MethodPtr, MethodPtrDecoder};
+pub use self::param_ptr::{ParamPtr, ParamPtrDecoder};
+pub use self::decl_security::{DeclSecurity, DeclSecurityDecoder};
+pub use self::event_map::{EventMap, EventMapDecoder};
+pub use self::event_ptr::{EventPtr, EventPtrDecoder};
+pub use self::event::{Event, EventDecoder};
+pub use self::property_map::{PropertyMap, PropertyMapDecoder};
+pub use self::property_ptr::{PropertyPtr, PropertyPtrDecoder};
+pub use self::property::{Property, PropertyDecoder};
+pub use self::method_semantics::{MethodSemantics, MethodSemanticsDecoder};
+pub use self::enc_log::{EncLog, EncLogDecoder};
+pub use self::enc_map::{EncMap, EncMapDecoder};
+pub use self::assembly::{Assembly, AssemblyDecoder};
+pub use self::assembly_processor::{AssemblyProcessor, AssemblyProcessorDecoder};
+pub use self::assembly_os::{AssemblyOs, AssemblyOsDecoder};
+pub use self::assembly_ref::{AssemblyRef, AssemblyRefDecoder};
+pub use self::assembly_ref_processor::{AssemblyRefProcessor, AssemblyRefProcessorDecoder};
+pub use self::assembly_ref_os::{AssemblyRefOs, AssemblyRefOsDecoder};
+pub use self::file::{File, FileDecoder};
+pub use self::exported_type::{ExportedType, ExportedTypeDecoder};
+pub use self::manifest_resource::{ManifestResource, ManifestResourceDecoder, ManifestResourceLocation};
+pub use self::nested_class::{NestedClass, NestedClassDecoder};
+pub use self::table::{row_size, Table, TableIter};
 pub use self::table_decoder::TableDecoder;
 pub use self::table_handle::{TableHandle, TableHandleReader};
 pub use self::table_index::{TableIndex, TableMask};
-// pub use self::table_stream::TableStream;
@@ -0,0 +1,38 @@
+use cli::{MetadataSizes, StringHandle, StringHandleReader};
+use cli::tables::{TableDecoder, TableIndex};
+use error::Error;
+
+pub struct ModuleRef {
+    pub name: StringHandle,
+}
+
+pub struct ModuleRefDecoder {
+    count: usize,
+    string_reader: StringHandleReader,
+}
+
+impl TableDecoder for ModuleRefDecoder {
+    type Item = ModuleRef;
+    const INDEX: TableIndex = TableIndex::ModuleRef;
+
+    fn new(sizes: &MetadataSizes) -> ModuleRefDecoder {
+        ModuleRefDecoder {
+            count: sizes.row_count(Self::INDEX),
+            string_reader: StringHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.string_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<ModuleRef, Error> {
+        Ok(ModuleRef {
+            name: self.string_reader.read(&mut buf)?,
+        })
+    }
+}
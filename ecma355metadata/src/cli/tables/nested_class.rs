@@ -0,0 +1,40 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct NestedClass {
+    pub nested_class: TableHandle,
+    pub enclosing_class: TableHandle,
+}
+
+pub struct NestedClassDecoder {
+    count: usize,
+    type_def_reader: TableHandleReader,
+}
+
+impl TableDecoder for NestedClassDecoder {
+    type Item = NestedClass;
+    const INDEX: TableIndex = TableIndex::NestedClass;
+
+    fn new(sizes: &MetadataSizes) -> NestedClassDecoder {
+        NestedClassDecoder {
+            count: sizes.row_count(Self::INDEX),
+            type_def_reader: index_reader!(sizes, TableIndex::TypeDef),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        2 * self.type_def_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<NestedClass, Error> {
+        Ok(NestedClass {
+            nested_class: self.type_def_reader.read(&mut buf)?,
+            enclosing_class: self.type_def_reader.read(&mut buf)?,
+        })
+    }
+}
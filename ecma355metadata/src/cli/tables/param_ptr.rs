@@ -0,0 +1,38 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct ParamPtr {
+    pub param: TableHandle,
+}
+
+pub struct ParamPtrDecoder {
+    count: usize,
+    param_reader: TableHandleReader,
+}
+
+impl TableDecoder for ParamPtrDecoder {
+    type Item = ParamPtr;
+    const INDEX: TableIndex = TableIndex::ParamPtr;
+
+    fn new(sizes: &MetadataSizes) -> ParamPtrDecoder {
+        ParamPtrDecoder {
+            count: sizes.row_count(Self::INDEX),
+            param_reader: index_reader!(sizes, TableIndex::Param),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.param_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<ParamPtr, Error> {
+        Ok(ParamPtr {
+            param: self.param_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use std::mem::size_of;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes, StringHandle, StringHandleReader};
+use cli::tables::{TableDecoder, TableIndex};
+use error::Error;
+
+pub struct Property {
+    pub flags: u16,
+    pub name: StringHandle,
+    pub signature: BlobHandle,
+}
+
+pub struct PropertyDecoder {
+    count: usize,
+    string_reader: StringHandleReader,
+    blob_reader: BlobHandleReader,
+}
+
+impl TableDecoder for PropertyDecoder {
+    type Item = Property;
+    const INDEX: TableIndex = TableIndex::Property;
+
+    fn new(sizes: &MetadataSizes) -> PropertyDecoder {
+        PropertyDecoder {
+            count: sizes.row_count(Self::INDEX),
+            string_reader: StringHandleReader::new(sizes),
+            blob_reader: BlobHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        size_of::<u16>() + self.string_reader.size() + self.blob_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<Property, Error> {
+        Ok(Property {
+            flags: buf.read_u16::<LittleEndian>()?,
+            name: self.string_reader.read(&mut buf)?,
+            signature: self.blob_reader.read(&mut buf)?,
+        })
+    }
+}
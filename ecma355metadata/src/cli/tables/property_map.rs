@@ -0,0 +1,42 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct PropertyMap {
+    pub parent: TableHandle,
+    pub property_list: TableHandle,
+}
+
+pub struct PropertyMapDecoder {
+    count: usize,
+    type_def_reader: TableHandleReader,
+    property_reader: TableHandleReader,
+}
+
+impl TableDecoder for PropertyMapDecoder {
+    type Item = PropertyMap;
+    const INDEX: TableIndex = TableIndex::PropertyMap;
+
+    fn new(sizes: &MetadataSizes) -> PropertyMapDecoder {
+        PropertyMapDecoder {
+            count: sizes.row_count(Self::INDEX),
+            type_def_reader: index_reader!(sizes, TableIndex::TypeDef),
+            property_reader: index_reader!(sizes, TableIndex::Property),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.type_def_reader.size() + self.property_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<PropertyMap, Error> {
+        Ok(PropertyMap {
+            parent: self.type_def_reader.read(&mut buf)?,
+            property_list: self.property_reader.read(&mut buf)?,
+        })
+    }
+}
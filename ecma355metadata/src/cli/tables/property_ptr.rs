@@ -0,0 +1,38 @@
+use cli::MetadataSizes;
+use cli::tables::{TableDecoder, TableHandle, TableHandleReader, TableIndex};
+use error::Error;
+
+pub struct PropertyPtr {
+    pub property: TableHandle,
+}
+
+pub struct PropertyPtrDecoder {
+    count: usize,
+    property_reader: TableHandleReader,
+}
+
+impl TableDecoder for PropertyPtrDecoder {
+    type Item = PropertyPtr;
+    const INDEX: TableIndex = TableIndex::PropertyPtr;
+
+    fn new(sizes: &MetadataSizes) -> PropertyPtrDecoder {
+        PropertyPtrDecoder {
+            count: sizes.row_count(Self::INDEX),
+            property_reader: index_reader!(sizes, TableIndex::Property),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.property_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<PropertyPtr, Error> {
+        Ok(PropertyPtr {
+            property: self.property_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,40 @@
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes};
+use cli::tables::{TableDecoder, TableIndex};
+use error::Error;
+
+/// A signature that isn't attached to a member, referenced by the tokens used with `calli` and by
+/// method bodies for their local variables.
+pub struct StandAloneSig {
+    pub signature: BlobHandle,
+}
+
+pub struct StandAloneSigDecoder {
+    count: usize,
+    blob_reader: BlobHandleReader,
+}
+
+impl TableDecoder for StandAloneSigDecoder {
+    type Item = StandAloneSig;
+    const INDEX: TableIndex = TableIndex::StandAloneSig;
+
+    fn new(sizes: &MetadataSizes) -> StandAloneSigDecoder {
+        StandAloneSigDecoder {
+            count: sizes.row_count(Self::INDEX),
+            blob_reader: BlobHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.blob_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<StandAloneSig, Error> {
+        Ok(StandAloneSig {
+            signature: self.blob_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -0,0 +1,122 @@
+use cli::MetadataSizes;
+use cli::tables::*;
+use error::Error;
+
+/// A view over the rows of a single metadata table.
+pub struct Table<'a, T: TableDecoder> {
+    data: &'a [u8],
+    decoder: T,
+}
+
+impl<'a, T: TableDecoder> Table<'a, T> {
+    /// Creates a table from the provided data, which must contain at least `decoder.row_count()` rows.
+    pub fn new(data: &'a [u8], decoder: T) -> Result<Table<'a, T>, Error> {
+        let size = decoder.row_count() * decoder.row_size();
+        if data.len() < size {
+            Err(Error::InvalidMetadata("There is insufficient space in the metadata stream for this table."))
+        } else {
+            Ok(Table {
+                data: &data[..size],
+                decoder,
+            })
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.decoder.row_count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decodes the row with the specified 1-based index, as used by metadata tokens and table handles.
+    pub fn get(&self, index: usize) -> Result<T::Item, Error> {
+        if index == 0 || index > self.len() {
+            Err(Error::InvalidTableReference)
+        } else {
+            let row_size = self.decoder.row_size();
+            let start = (index - 1) * row_size;
+            self.decoder.decode(&self.data[start..(start + row_size)])
+        }
+    }
+
+    pub fn iter<'t>(&'t self) -> TableIter<'t, 'a, T> {
+        TableIter {
+            table: self,
+            next: 1,
+        }
+    }
+}
+
+pub struct TableIter<'t, 'a: 't, T: TableDecoder + 't> {
+    table: &'t Table<'a, T>,
+    next: usize,
+}
+
+impl<'t, 'a: 't, T: TableDecoder + 't> Iterator for TableIter<'t, 'a, T> {
+    type Item = Result<T::Item, Error>;
+
+    fn next(&mut self) -> Option<Result<T::Item, Error>> {
+        if self.next > self.table.len() {
+            None
+        } else {
+            self.next += 1;
+            Some(self.table.get(self.next - 1))
+        }
+    }
+}
+
+/// Gets the size of a row in the specified table, or `None` if the table can't be decoded.
+pub fn row_size(index: TableIndex, sizes: &MetadataSizes) -> Option<usize> {
+    Some(match index {
+        TableIndex::Module => ModuleDecoder::new(sizes).row_size(),
+        TableIndex::TypeRef => TypeRefDecoder::new(sizes).row_size(),
+        TableIndex::TypeDef => TypeDefDecoder::new(sizes).row_size(),
+        TableIndex::FieldPtr => FieldPtrDecoder::new(sizes).row_size(),
+        TableIndex::Field => FieldDecoder::new(sizes).row_size(),
+        TableIndex::MethodPtr => MethodPtrDecoder::new(sizes).row_size(),
+        TableIndex::MethodDef => MethodDefDecoder::new(sizes).row_size(),
+        TableIndex::ParamPtr => ParamPtrDecoder::new(sizes).row_size(),
+        TableIndex::Param => ParamDecoder::new(sizes).row_size(),
+        TableIndex::InterfaceImpl => InterfaceImplDecoder::new(sizes).row_size(),
+        TableIndex::MemberRef => MemberRefDecoder::new(sizes).row_size(),
+        TableIndex::Constant => ConstantDecoder::new(sizes).row_size(),
+        TableIndex::CustomAttribute => CustomAttributeDecoder::new(sizes).row_size(),
+        TableIndex::FieldMarshal => FieldMarshalDecoder::new(sizes).row_size(),
+        TableIndex::DeclSecurity => DeclSecurityDecoder::new(sizes).row_size(),
+        TableIndex::ClassLayout => ClassLayoutDecoder::new(sizes).row_size(),
+        TableIndex::FieldLayout => FieldLayoutDecoder::new(sizes).row_size(),
+        TableIndex::StandAloneSig => StandAloneSigDecoder::new(sizes).row_size(),
+        TableIndex::EventMap => EventMapDecoder::new(sizes).row_size(),
+        TableIndex::EventPtr => EventPtrDecoder::new(sizes).row_size(),
+        TableIndex::Event => EventDecoder::new(sizes).row_size(),
+        TableIndex::PropertyMap => PropertyMapDecoder::new(sizes).row_size(),
+        TableIndex::PropertyPtr => PropertyPtrDecoder::new(sizes).row_size(),
+        TableIndex::Property => PropertyDecoder::new(sizes).row_size(),
+        TableIndex::MethodSemantics => MethodSemanticsDecoder::new(sizes).row_size(),
+        TableIndex::MethodImpl => MethodImplDecoder::new(sizes).row_size(),
+        TableIndex::ModuleRef => ModuleRefDecoder::new(sizes).row_size(),
+        TableIndex::TypeSpec => TypeSpecDecoder::new(sizes).row_size(),
+        TableIndex::ImplMap => ImplMapDecoder::new(sizes).row_size(),
+        TableIndex::FieldRva => FieldRvaDecoder::new(sizes).row_size(),
+        TableIndex::EncLog => EncLogDecoder::new(sizes).row_size(),
+        TableIndex::EncMap => EncMapDecoder::new(sizes).row_size(),
+        TableIndex::Assembly => AssemblyDecoder::new(sizes).row_size(),
+        TableIndex::AssemblyProcessor => AssemblyProcessorDecoder::new(sizes).row_size(),
+        TableIndex::AssemblyOS => AssemblyOsDecoder::new(sizes).row_size(),
+        TableIndex::AssemblyRef => AssemblyRefDecoder::new(sizes).row_size(),
+        TableIndex::AssemblyRefProcessor => AssemblyRefProcessorDecoder::new(sizes).row_size(),
+        TableIndex::AssemblyRefOS => AssemblyRefOsDecoder::new(sizes).row_size(),
+        TableIndex::File => FileDecoder::new(sizes).row_size(),
+        TableIndex::ExportedType => ExportedTypeDecoder::new(sizes).row_size(),
+        TableIndex::ManifestResource => ManifestResourceDecoder::new(sizes).row_size(),
+        TableIndex::NestedClass => NestedClassDecoder::new(sizes).row_size(),
+        TableIndex::GenericParam => GenericParamDecoder::new(sizes).row_size(),
+        TableIndex::MethodSpec => MethodSpecDecoder::new(sizes).row_size(),
+        TableIndex::GenericParamConstraint => GenericParamConstraintDecoder::new(sizes).row_size(),
+
+        // Portable PDB tables live in a separate "#Pdb" image, which we don't support yet.
+        _ => return None,
+    })
+}
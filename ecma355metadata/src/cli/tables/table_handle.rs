@@ -7,7 +7,7 @@ use cli::{LARGE_INDEX_SIZE, SMALL_INDEX_SIZE};
 use cli::tables::{TableIndex, TableMask};
 use error::Error;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct TableHandle {
     index: usize,
     table: TableIndex,
@@ -21,6 +21,18 @@ impl TableHandle {
         }
     }
 
+    /// Creates a handle from a metadata token, which has the table index in the high byte and the row number in
+    /// the low three bytes.
+    pub fn from_token(token: u32) -> Option<TableHandle> {
+        TableIndex::from_u8((token >> 24) as u8)
+            .map(|table| TableHandle::new((token & 0x00FF_FFFF) as usize, table))
+    }
+
+    /// Gets the metadata token for the row, the inverse of `from_token`.
+    pub fn token(&self) -> u32 {
+        ((self.table as u32) << 24) | self.index as u32
+    }
+
     pub fn table(&self) -> TableIndex {
         self.table
     }
@@ -58,28 +70,29 @@ impl TableHandleReader {
 
     pub fn for_coded_index(
         is_large: bool,
-        tables: TableMask,
+        tag_bits: usize,
         table_map: fn(usize) -> Option<TableIndex>,
     ) -> TableHandleReader {
+        TableHandleReader {
+            is_large,
+            tag_mask: (1 << tag_bits) - 1,
+            shift_distance: tag_bits,
+            table_map,
+        }
+    }
+
+    /// Calculates the number of tag bits needed for a coded index that can refer to the specified tables.
+    pub fn tag_bits(tables: TableMask) -> usize {
         assert!(tables.bits() != 0);
 
-        // Calculate the tag mask and shift distance
         let table_count = tables.bits().count_ones();
         let mut current = table_count - 1;
-        let mut tag_mask = 0;
-        let mut shift_distance = 0;
+        let mut tag_bits = 0;
         while current > 0 {
-            tag_mask = (tag_mask << 1) | 1;
             current >>= 1;
-            shift_distance += 1;
-        }
-
-        TableHandleReader {
-            is_large,
-            tag_mask,
-            shift_distance,
-            table_map,
+            tag_bits += 1;
         }
+        tag_bits
     }
 
     pub fn size(&self) -> usize {
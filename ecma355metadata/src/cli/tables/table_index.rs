@@ -135,6 +135,10 @@ impl TableIndex {
         TableIndexIter(Some(TableIndex::Module))
     }
 
+    pub fn from_u8(value: u8) -> Option<TableIndex> {
+        TableIndex::each().find(|&idx| idx as u8 == value)
+    }
+
     fn next(self) -> Option<TableIndex> {
         let val = self as u8;
         let next_val = if val < 0x2Cu8 {
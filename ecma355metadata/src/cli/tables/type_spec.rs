@@ -0,0 +1,38 @@
+use cli::{BlobHandle, BlobHandleReader, MetadataSizes};
+use cli::tables::{TableDecoder, TableIndex};
+use error::Error;
+
+pub struct TypeSpec {
+    pub signature: BlobHandle,
+}
+
+pub struct TypeSpecDecoder {
+    count: usize,
+    blob_reader: BlobHandleReader,
+}
+
+impl TableDecoder for TypeSpecDecoder {
+    type Item = TypeSpec;
+    const INDEX: TableIndex = TableIndex::TypeSpec;
+
+    fn new(sizes: &MetadataSizes) -> TypeSpecDecoder {
+        TypeSpecDecoder {
+            count: sizes.row_count(Self::INDEX),
+            blob_reader: BlobHandleReader::new(sizes),
+        }
+    }
+
+    fn row_size(&self) -> usize {
+        self.blob_reader.size()
+    }
+
+    fn row_count(&self) -> usize {
+        self.count
+    }
+
+    fn decode(&self, mut buf: &[u8]) -> Result<TypeSpec, Error> {
+        Ok(TypeSpec {
+            signature: self.blob_reader.read(&mut buf)?,
+        })
+    }
+}
@@ -51,7 +51,7 @@ impl ::std::fmt::Display for TypeAttributes {
         }
 
         if self.layout() != TypeLayout::AutoLayout {
-            write!(f, "{} ", self.visibility())?;
+            write!(f, "{} ", self.layout())?;
         }
 
         write!(f, "{}", self.semantics())?;
@@ -89,6 +89,9 @@ pub enum TypeLayout {
     AutoLayout = 0,
     SequentialLayout = 1,
     ExplicitLayout = 2,
+
+    /// The layout bits are set to a value the ECMA spec doesn't define.
+    Reserved = 3,
 }
 impl_display_via_debug!(TypeLayout);
 
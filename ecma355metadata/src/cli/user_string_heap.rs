@@ -0,0 +1,76 @@
+use cli::BlobHeap;
+
+/// Provides access to the `#US` heap, which holds the string literals referenced by `ldstr` instructions.
+///
+/// Each entry is encoded like a blob, containing UTF-16 code units followed by a single terminal byte
+/// that indicates whether the string contains any characters that need special handling.
+pub struct UserStringHeap<'a> {
+    blobs: BlobHeap<'a>,
+}
+
+impl<'a> UserStringHeap<'a> {
+    pub const EMPTY: UserStringHeap<'static> = UserStringHeap { blobs: BlobHeap::EMPTY };
+
+    pub fn new(data: &'a [u8]) -> UserStringHeap<'a> {
+        UserStringHeap { blobs: BlobHeap::new(data) }
+    }
+
+    /// Gets the UTF-16 code units of the string at the specified offset.
+    ///
+    /// Code units are returned rather than a `String` because user strings are not required to be valid UTF-16.
+    pub fn get(&self, idx: usize) -> Option<Vec<u16>> {
+        let blob = self.blobs.get(idx)?;
+
+        // Drop the terminal byte
+        let len = blob.len() & !1;
+        Some(blob[..len]
+            .chunks(2)
+            .map(|c| (c[0] as u16) | ((c[1] as u16) << 8))
+            .collect())
+    }
+
+    /// Gets the string at the specified offset, replacing any invalid UTF-16 with the replacement character.
+    pub fn get_string(&self, idx: usize) -> Option<String> {
+        self.get(idx).map(|units| String::from_utf16_lossy(&units))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEAP: [u8; 14] = [
+        0x00,
+        // "Hi"
+        0x05, 0x48, 0x00, 0x69, 0x00, 0x00,
+        // ""
+        0x01, 0x00,
+        // Lone surrogate
+        0x03, 0x00, 0xD8, 0x01,
+        // Truncated
+        0x09,
+    ];
+
+    #[test]
+    pub fn get_string() {
+        let heap = UserStringHeap::new(&HEAP);
+        assert_eq!(Some("Hi".to_owned()), heap.get_string(1));
+        assert_eq!(Some("".to_owned()), heap.get_string(7));
+    }
+
+    #[test]
+    pub fn get_invalid_utf16() {
+        let heap = UserStringHeap::new(&HEAP);
+        assert_eq!(Some(vec![0xD800]), heap.get(9));
+        assert_eq!(Some("\u{FFFD}".to_owned()), heap.get_string(9));
+    }
+
+    #[test]
+    pub fn get_out_of_range() {
+        let heap = UserStringHeap::new(&HEAP);
+        assert_eq!(None, heap.get(0));
+        assert_eq!(None, heap.get(13));
+        assert_eq!(None, heap.get(14));
+        assert_eq!(None, UserStringHeap::EMPTY.get(1));
+    }
+}
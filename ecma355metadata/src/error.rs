@@ -10,12 +10,21 @@ pub enum Error {
     /// Indicates that the file is not a PE file, and thus has no PE header.
     NotAPortableExecutable,
 
+    /// The PE headers are inconsistent with the file, for example by pointing past its end.
+    InvalidPeHeader(&'static str),
+
     /// The requested PE data directory was not found.
     DirectoryNotFound,
 
+    /// A PE data directory, such as the import table, contains invalid data.
+    InvalidDirectory(&'static str),
+
     /// The requested section was not found.
     SectionNotFound,
 
+    /// The requested RVA range extends past the end of its section, or its data is missing from the file.
+    RvaOutOfRange,
+
     /// The image does not contain a CLI header
     CliHeaderNotFound,
 
@@ -37,8 +46,17 @@ pub enum Error {
     /// The Coded Index data was invalid
     InvalidCodedIndex,
 
+    /// A table row was requested that does not exist.
+    InvalidTableReference,
+
     /// The type code is not recognized
     UnknownTypeCode(u32),
+
+    /// A `.resources` file, such as an embedded manifest resource, contains invalid data.
+    InvalidResources(&'static str),
+
+    /// A strong-name public key or signature is malformed, or uses an unsupported algorithm.
+    InvalidStrongName(&'static str),
 }
 
 // Manual implementation because io::Error doesn't implement PartialEq, so we can't derive... but it's
@@ -50,8 +68,11 @@ impl PartialEq for Error {
         match (self, other) {
             (&Error::InvalidSignature, &Error::InvalidSignature) => true,
             (&Error::NotAPortableExecutable, &Error::NotAPortableExecutable) => true,
+            (&Error::InvalidPeHeader(lhs), &Error::InvalidPeHeader(rhs)) => lhs.eq(rhs),
             (&Error::DirectoryNotFound, &Error::DirectoryNotFound) => true,
+            (&Error::InvalidDirectory(lhs), &Error::InvalidDirectory(rhs)) => lhs.eq(rhs),
             (&Error::SectionNotFound, &Error::SectionNotFound) => true,
+            (&Error::RvaOutOfRange, &Error::RvaOutOfRange) => true,
             (&Error::CliHeaderNotFound, &Error::CliHeaderNotFound) => true,
             (&Error::InvalidStringData, &Error::InvalidStringData) => true,
             (&Error::StreamNotFound, &Error::StreamNotFound) => true,
@@ -59,7 +80,10 @@ impl PartialEq for Error {
             (&Error::InvalidHeapReference, &Error::InvalidHeapReference) => true,
             (&Error::UnknownTableName, &Error::UnknownTableName) => true,
             (&Error::InvalidCodedIndex, &Error::InvalidCodedIndex) => true,
+            (&Error::InvalidTableReference, &Error::InvalidTableReference) => true,
             (&Error::UnknownTypeCode(lhs), &Error::UnknownTypeCode(rhs)) => lhs == rhs,
+            (&Error::InvalidResources(lhs), &Error::InvalidResources(rhs)) => lhs.eq(rhs),
+            (&Error::InvalidStrongName(lhs), &Error::InvalidStrongName(rhs)) => lhs.eq(rhs),
             _ => false, // Type mismatches and IoError are never equal
         }
     }
@@ -1,10 +1,14 @@
 use std::fmt::{Display, Error, Formatter};
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Guid([u8; 16]);
 
 impl Guid {
     pub const EMPTY: Guid = Guid([0u8; 16]);
+
+    pub fn new(bytes: [u8; 16]) -> Guid {
+        Guid(bytes)
+    }
 }
 
 impl Display for Guid {
@@ -10,6 +10,10 @@ macro_rules! impl_display_via_debug {
 }
 
 extern crate byteorder;
+extern crate miniz_oxide;
+extern crate rsa;
+extern crate sha1;
+extern crate sha2;
 
 #[macro_use]
 extern crate bitflags;
@@ -19,15 +23,25 @@ mod utils;
 mod guid;
 mod metadata_image;
 
+#[cfg(test)]
+mod test_metadata;
+
 /// Contains CLI metadata structures
 pub mod cli;
 
 /// Contains PE structures
 pub mod pe;
 
+/// Contains structural validation of CLI metadata
+pub mod validation;
+
+/// Contains strong-name signature verification
+pub mod strong_name;
+
 pub use error::Error;
 
 pub use pe::PeImage;
 pub use cli::CliHeader;
 pub use guid::Guid;
 pub use metadata_image::MetadataImage;
+pub use validation::validate;
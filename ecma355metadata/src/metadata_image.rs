@@ -1,12 +1,28 @@
-use std::ops::Deref;
-use std::io::Read;
+use std::ops::{Deref, Range};
+use std::io::{Cursor, Read};
 
-use pe::{DirectoryType, PeImage};
-use cli::{CliHeader, MetadataHeader};
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use pe::{DirectoryType, MemoryRange, PeImage};
+use cli::{BlobHandle, BlobHeap, CliHeader, ConstantValue, CustomAttributeValue, EnumResolver, FieldFlags, GuidHeap,
+          MetadataHeader, MetadataSizes, MethodCodeType, StringHandle, StringHeap, UserStringHeap};
+use cli::il::MethodBody;
+use cli::signatures::{FieldSignature, MethodSignature, TypeReference};
+use cli::tables::{self, CustomAttribute, ImplMap, ManifestResource, ManifestResourceLocation, Table, TableDecoder,
+                  TableHandle, TableIndex, TypeDef};
 use error::Error;
 
+/// A PE image containing CLI metadata, with the location of each metadata heap and table resolved.
 pub struct MetadataImage<D: Deref<Target = [u8]>> {
     pe: PeImage<D>,
+    cli_header: CliHeader,
+    metadata_header: MetadataHeader,
+    metadata_sizes: MetadataSizes,
+    strings: Range<usize>,
+    blobs: Range<usize>,
+    guids: Range<usize>,
+    user_strings: Range<usize>,
+    tables: Vec<Range<usize>>,
 }
 
 impl<D: Deref<Target = [u8]>> MetadataImage<D> {
@@ -15,49 +31,84 @@ impl<D: Deref<Target = [u8]>> MetadataImage<D> {
     }
 
     pub fn load(pe: PeImage<D>) -> Result<MetadataImage<D>, Error> {
-        // // Load the CLI header
-        // let cli_header = {
-        //     let cli_header_dir = pe.get_directory(DirectoryType::CliHeader)
-        //         .ok_or(Error::CliHeaderNotFound)?;
-
-        //     // Map the virtual range to a physical one
-        //     let phys = pe.map_virtual_range(cli_header_dir.range)
-        //         .ok_or(Error::CliHeaderNotFound)?;
-
-        //     // Seek and load the Cli Header
-        //     reader.seek(SeekFrom::Start(phys.start as u64))?;
-        //     CliHeader::read(&mut reader)?
-        // };
-
-        // // Load the metadata header
-        // let (metadata_start, metadata_header) = {
-        //     let phys = pe.map_virtual_range(cli_header.metadata)
-        //         .ok_or(Error::CliHeaderNotFound)?;
-
-        //     // Seek and load the Metadata Header
-        //     reader.seek(SeekFrom::Start(phys.start as u64))?;
-        //     (phys.start, MetadataHeader::read(&mut reader)?)
-        // };
-
-        // // Load metadata sizes from the "#~" stream
-        // let (tables_start, metadata_sizes) = {
-        //     let stream = metadata_header
-        //         .get_stream("#~")
-        //         .ok_or(Error::InvalidMetadata(
-        //             "Image does not contain a '#~' metadata stream",
-        //         ))?;
-
-        //     // Seek to the stream
-        //     let start = metadata_start + stream.offset;
-        //     reader.seek(SeekFrom::Start(start as u64))?;
-        //     let sizes = MetadataSizes::read(&mut reader)?;
-
-        //     // Load the metadata sizes and record the offset of the start of tables
-        //     (reader.seek(SeekFrom::Current(0))?, sizes)
-        // };
+        // Load the CLI header, which has a fixed size, so only that much is read whatever the directory says
+        let cli_header = {
+            let range = pe.pe_header()
+                .and_then(|h| h.directories().iter().find(|d| d.directory_type == DirectoryType::CliHeader))
+                .map(|d| d.range)
+                .filter(|r| r.len as usize >= CliHeader::SIZE)
+                .ok_or(Error::CliHeaderNotFound)?;
+            let data = pe.read_rva(range.start, CliHeader::SIZE).map_err(|_| Error::CliHeaderNotFound)?;
+            CliHeader::read(&mut &data[..])?
+        };
+
+        // Load the metadata header
+        let metadata = pe.map_range(cli_header.metadata)
+            .ok_or(Error::InvalidMetadata("The CLI header refers to metadata outside the image."))?;
+        let metadata_header = MetadataHeader::read(&mut Cursor::new(&pe.data()[metadata.clone()]))?;
+
+        // Find each of the streams, which are located relative to the start of the metadata
+        let stream_range = |name: &str| -> Result<Range<usize>, Error> {
+            match metadata_header.get_stream(name) {
+                Some(stream) => {
+                    let start = metadata.start + stream.offset as usize;
+                    let end = start + stream.size as usize;
+                    if end > metadata.end {
+                        Err(Error::InvalidMetadata("A metadata stream extends past the end of the metadata."))
+                    } else {
+                        Ok(start..end)
+                    }
+                }
+                None => Ok(0..0),
+            }
+        };
+        let strings = stream_range("#Strings")?;
+        let blobs = stream_range("#Blob")?;
+        let guids = stream_range("#GUID")?;
+        let user_strings = stream_range("#US")?;
+        let table_stream = stream_range("#~")?;
+        if table_stream.start == table_stream.end {
+            return Err(Error::StreamNotFound);
+        }
+
+        // Make sure the GUID heap is usable, so the accessor doesn't need to fail
+        GuidHeap::new(&pe.data()[guids.clone()])?;
+
+        // Load metadata sizes from the "#~" stream, the tables follow immediately after them, in order.
+        let (metadata_sizes, tables) = {
+            let mut buf = &pe.data()[table_stream.clone()];
+            let metadata_sizes = MetadataSizes::read(&mut buf)?;
+
+            let mut start = table_stream.end - buf.len();
+            let mut tables = Vec::with_capacity(TableIndex::MAX + 1);
+            for idx in 0..(TableIndex::MAX + 1) {
+                let size = match TableIndex::from_u8(idx as u8) {
+                    Some(index) if metadata_sizes.row_count(index) > 0 => {
+                        let row_size = tables::row_size(index, &metadata_sizes)
+                            .ok_or(Error::InvalidMetadata("The '#~' stream contains a table that is not supported."))?;
+                        metadata_sizes.row_count(index).checked_mul(row_size).ok_or(table_too_large())?
+                    }
+                    _ => 0,
+                };
+                let end = start.checked_add(size)
+                    .filter(|&end| end <= table_stream.end)
+                    .ok_or(table_too_large())?;
+                tables.push(start..end);
+                start = end;
+            }
+            (metadata_sizes, tables)
+        };
 
         Ok(MetadataImage {
             pe,
+            cli_header,
+            metadata_header,
+            metadata_sizes,
+            strings,
+            blobs,
+            guids,
+            user_strings,
+            tables,
         })
     }
 
@@ -65,13 +116,332 @@ impl<D: Deref<Target = [u8]>> MetadataImage<D> {
         &self.pe
     }
 
-    // pub fn cli_header(&self) -> &CliHeader {
-    //     &self.cli_header
-    // }
+    pub fn cli_header(&self) -> &CliHeader {
+        &self.cli_header
+    }
+
+    pub fn metadata_header(&self) -> &MetadataHeader {
+        &self.metadata_header
+    }
+
+    pub fn metadata_sizes(&self) -> &MetadataSizes {
+        &self.metadata_sizes
+    }
+
+    pub fn string_heap(&self) -> StringHeap<'_> {
+        StringHeap::new(&self.pe.data()[self.strings.clone()])
+    }
+
+    pub fn blob_heap(&self) -> BlobHeap<'_> {
+        BlobHeap::new(&self.pe.data()[self.blobs.clone()])
+    }
+
+    pub fn guid_heap(&self) -> GuidHeap<'_> {
+        // Validated when the image was loaded
+        GuidHeap::new(&self.pe.data()[self.guids.clone()]).unwrap()
+    }
+
+    pub fn user_string_heap(&self) -> UserStringHeap<'_> {
+        UserStringHeap::new(&self.pe.data()[self.user_strings.clone()])
+    }
+
+    /// Gets the table decoded by `T`.
+    pub fn table<T: TableDecoder>(&self) -> Table<'_, T> {
+        let range = self.tables[T::INDEX as usize].clone();
+
+        // The table's size was checked when the image was loaded
+        Table::new(&self.pe.data()[range], T::new(&self.metadata_sizes)).unwrap()
+    }
+
+    /// Reads a string from the `#Strings` heap, where the null handle refers to the empty string.
+    pub fn read_string(&self, handle: StringHandle) -> Result<&str, Error> {
+        if handle.index() == 0 {
+            return Ok("");
+        }
+        let bytes = self.string_heap().get(handle.index()).ok_or(Error::InvalidHeapReference)?;
+        ::std::str::from_utf8(bytes).map_err(|_| Error::InvalidStringData)
+    }
+
+    /// Gets a blob from the `#Blob` heap.
+    pub fn read_blob(&self, handle: BlobHandle) -> Result<&[u8], Error> {
+        self.blob_heap().get(handle.index()).ok_or(Error::InvalidHeapReference)
+    }
+
+    /// Gets the namespace and name of a TypeDef or TypeRef.
+    pub fn type_name(&self, handle: TableHandle) -> Result<(&str, &str), Error> {
+        let (namespace, name) = match handle.table() {
+            TableIndex::TypeDef => {
+                let row = self.table::<tables::TypeDefDecoder>().get(handle.index())?;
+                (row.type_namespace, row.type_name)
+            }
+            TableIndex::TypeRef => {
+                let row = self.table::<tables::TypeRefDecoder>().get(handle.index())?;
+                (row.namespace, row.name)
+            }
+            _ => return Err(Error::InvalidTableReference),
+        };
+        Ok((self.read_string(namespace)?, self.read_string(name)?))
+    }
+
+    /// Gets the Field rows that belong to a TypeDef, following the FieldPtr table if there is one.
+    pub fn type_def_fields(&self, type_def: TableHandle) -> Result<Vec<TableHandle>, Error> {
+        let pointers = self.table::<tables::FieldPtrDecoder>();
+        self.member_list(type_def, TableIndex::Field, pointers.len(), |row| row.field_list, |index| {
+            Ok(pointers.get(index)?.field)
+        })
+    }
+
+    /// Gets the MethodDef rows that belong to a TypeDef, following the MethodPtr table if there is one.
+    pub fn type_def_methods(&self, type_def: TableHandle) -> Result<Vec<TableHandle>, Error> {
+        let pointers = self.table::<tables::MethodPtrDecoder>();
+        self.member_list(type_def, TableIndex::MethodDef, pointers.len(), |row| row.method_list, |index| {
+            Ok(pointers.get(index)?.method)
+        })
+    }
+
+    /// Reads the IL body of a MethodDef, or `None` if the method is abstract, runtime-implemented or native.
+    ///
+    /// The body's size is only known once its header is read, so it may extend to the end of its section's raw data.
+    pub fn method_body(&self, method: TableHandle) -> Result<Option<MethodBody<'_>>, Error> {
+        let row = self.table::<tables::MethodDefDecoder>().get(method.index())?;
+        if row.rva == 0 || row.impl_flags.code_type() != MethodCodeType::IL {
+            return Ok(None);
+        }
+        let section = self.pe
+            .sections()
+            .iter()
+            .find(|x| x.contains_rva(row.rva))
+            .ok_or(Error::SectionNotFound)?;
+        let end = section.virtual_address + ::std::cmp::min(section.size_of_raw_data, section.virtual_size);
+        if row.rva >= end {
+            return Err(Error::RvaOutOfRange);
+        }
+        let data = self.pe.read_raw(MemoryRange::new(row.rva, end - row.rva))?;
+        Ok(Some(MethodBody::read(data)?))
+    }
+
+    /// Gets the Param rows that belong to a MethodDef, following the ParamPtr table if there is one.
+    pub fn method_params(&self, method: TableHandle) -> Result<Vec<TableHandle>, Error> {
+        let methods = self.table::<tables::MethodDefDecoder>();
+        let pointers = self.table::<tables::ParamPtrDecoder>();
+        let start = methods.get(method.index())?.params.index();
+        let next = if method.index() < methods.len() {
+            Some(methods.get(method.index() + 1)?.params.index())
+        } else {
+            None
+        };
+        self.list_run(start, next, TableIndex::Param, pointers.len(), |index| Ok(pointers.get(index)?.param))
+    }
+
+    /// Gets the rows in a TypeDef's run of a member list, which continues until the start of the next TypeDef's run.
+    fn member_list<L, P>(
+        &self,
+        type_def: TableHandle,
+        member_table: TableIndex,
+        pointer_count: usize,
+        list: L,
+        pointer: P,
+    ) -> Result<Vec<TableHandle>, Error>
+    where
+        L: Fn(&TypeDef) -> TableHandle,
+        P: Fn(usize) -> Result<TableHandle, Error>,
+    {
+        let type_defs = self.table::<tables::TypeDefDecoder>();
+        let start = list(&type_defs.get(type_def.index())?).index();
+        let next = if type_def.index() < type_defs.len() {
+            Some(list(&type_defs.get(type_def.index() + 1)?).index())
+        } else {
+            None
+        };
+        self.list_run(start, next, member_table, pointer_count, pointer)
+    }
+
+    /// Gets the rows from the start of a run of a list to the start of the next run, or the end of the table if it
+    /// is the last.
+    fn list_run<P>(
+        &self,
+        start: usize,
+        next: Option<usize>,
+        member_table: TableIndex,
+        pointer_count: usize,
+        pointer: P,
+    ) -> Result<Vec<TableHandle>, Error>
+    where
+        P: Fn(usize) -> Result<TableHandle, Error>,
+    {
+        let len = match pointer_count {
+            0 => self.metadata_sizes.row_count(member_table),
+            n => n,
+        };
+        let end = next.unwrap_or(len + 1);
+        if start == 0 || start > end || end > len + 1 {
+            return Err(Error::InvalidMetadata("A member list is out of range."));
+        }
+
+        (start..end)
+            .map(|index| match pointer_count {
+                0 => Ok(TableHandle::new(index, member_table)),
+                _ => pointer(index),
+            })
+            .collect()
+    }
+
+    /// Gets the underlying type of an enum, or `None` if the TypeDef isn't an enum.
+    ///
+    /// An enum extends `System.Enum`, and its only instance field, which compilers name `value__`, has the
+    /// underlying type. Its literals are static fields with values in the Constant table.
+    pub fn enum_underlying_type(&self, type_def: TableHandle) -> Result<Option<TypeReference>, Error> {
+        let row = self.table::<tables::TypeDefDecoder>().get(type_def.index())?;
+        let extends_enum = match row.extends.table() {
+            TableIndex::TypeDef | TableIndex::TypeRef if row.extends.index() != 0 => {
+                self.type_name(row.extends)? == ("System", "Enum")
+            }
+            _ => false,
+        };
+        if !extends_enum {
+            return Ok(None);
+        }
+
+        let fields = self.table::<tables::FieldDecoder>();
+        let mut underlying_type = None;
+        for handle in self.type_def_fields(type_def)? {
+            let field = fields.get(handle.index())?;
+            if field.flags.flags().contains(FieldFlags::Static) {
+                continue;
+            }
+            if underlying_type.is_some() {
+                return Err(invalid_enum());
+            }
+            underlying_type = Some(FieldSignature::read(&mut self.read_blob(field.signature)?)?.field_type);
+        }
+        underlying_type.map(Some).ok_or_else(invalid_enum)
+    }
+
+    /// Finds a top-level TypeDef by its namespace and name.
+    pub fn find_type_def(&self, namespace: &str, name: &str) -> Option<TableHandle> {
+        (1..=self.table::<tables::TypeDefDecoder>().len())
+            .map(|index| TableHandle::new(index, TableIndex::TypeDef))
+            .find(|&handle| self.type_name(handle).ok() == Some((namespace, name)))
+    }
+
+    /// Gets the custom attributes applied to a row, such as a TypeDef, MethodDef or the Assembly.
+    pub fn custom_attributes(&self, parent: TableHandle) -> Result<Vec<CustomAttribute>, Error> {
+        let mut attributes = Vec::new();
+        for attribute in self.table::<tables::CustomAttributeDecoder>().iter() {
+            let attribute = attribute?;
+            if attribute.parent == parent {
+                attributes.push(attribute);
+            }
+        }
+        Ok(attributes)
+    }
+
+    /// Gets the value the Constant table gives a Field, Param or Property, if it has one.
+    pub fn constant(&self, parent: TableHandle) -> Result<Option<ConstantValue>, Error> {
+        for constant in self.table::<tables::ConstantDecoder>().iter() {
+            let constant = constant?;
+            if constant.parent == parent {
+                return ConstantValue::decode(constant.constant_type, self.read_blob(constant.value)?).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Gets the size a TypeDef's ClassLayout row gives its instances, or `None` if it has no row or leaves the size
+    /// to the runtime.
+    pub fn class_size(&self, type_def: TableHandle) -> Result<Option<u32>, Error> {
+        for layout in self.table::<tables::ClassLayoutDecoder>().iter() {
+            let layout = layout?;
+            if layout.parent == type_def {
+                return Ok(Some(layout.class_size).filter(|&size| size != 0));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Gets the `size` bytes of initial data the FieldRva table maps a static field to, if it has any.
+    pub fn field_data(&self, field: TableHandle, size: u32) -> Result<Option<&[u8]>, Error> {
+        for field_rva in self.table::<tables::FieldRvaDecoder>().iter() {
+            let field_rva = field_rva?;
+            if field_rva.field == field {
+                return Ok(Some(self.pe.read_raw(MemoryRange::new(field_rva.rva, size))?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Gets the ImplMap row that makes a method or field a P/Invoke, if it has one.
+    pub fn impl_map(&self, member: TableHandle) -> Result<Option<ImplMap>, Error> {
+        for impl_map in self.table::<tables::ImplMapDecoder>().iter() {
+            let impl_map = impl_map?;
+            if impl_map.member_forwarded == member {
+                return Ok(Some(impl_map));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Gets the marshaling descriptor the FieldMarshal table gives a field or parameter, if it has one.
+    pub fn field_marshal(&self, parent: TableHandle) -> Result<Option<&[u8]>, Error> {
+        for field_marshal in self.table::<tables::FieldMarshalDecoder>().iter() {
+            let field_marshal = field_marshal?;
+            if field_marshal.parent == parent {
+                return Ok(Some(self.read_blob(field_marshal.native_type)?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Decodes the value of a custom attribute, using the signature of its constructor.
+    ///
+    /// The image can be used as the resolver if the attribute only uses enums defined in the image.
+    pub fn custom_attribute_value<R: EnumResolver>(
+        &self,
+        attribute: &CustomAttribute,
+        resolver: &R,
+    ) -> Result<CustomAttributeValue, Error> {
+        let index = attribute.attribute_type.index();
+        let signature = match attribute.attribute_type.table() {
+            TableIndex::MethodDef => self.table::<tables::MethodDefDecoder>().get(index)?.signature,
+            TableIndex::MemberRef => self.table::<tables::MemberRefDecoder>().get(index)?.signature,
+            _ => return Err(Error::InvalidTableReference),
+        };
+        let constructor = MethodSignature::read(&mut self.read_blob(signature)?)?;
+        CustomAttributeValue::decode(&constructor, self.read_blob(attribute.value)?, resolver)
+    }
 
-    // pub fn metadata_header(&self) -> &MetadataHeader {
-    //     &self.metadata_header
-    // }
+    /// Gets the contents of a manifest resource, or `None` if it is stored in another file or assembly.
+    pub fn manifest_resource_data(&self, resource: &ManifestResource) -> Result<Option<&[u8]>, Error> {
+        match resource.location()? {
+            ManifestResourceLocation::Embedded(offset) => {
+                if self.cli_header.resources.len == 0 {
+                    return Err(resource_out_of_range());
+                }
+                let resources = self.pe.read_raw(self.cli_header.resources)?;
+                Ok(Some(read_length_prefixed(resources, offset)?))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Resolves the enums defined in the image, but not those referenced from other assemblies.
+impl<D: Deref<Target = [u8]>> EnumResolver for MetadataImage<D> {
+    fn resolve_enum(&self, enum_type: TableHandle) -> Option<TypeReference> {
+        match enum_type.table() {
+            TableIndex::TypeDef => self.enum_underlying_type(enum_type).ok()?,
+            _ => None,
+        }
+    }
+
+    fn resolve_enum_by_name(&self, name: &str) -> Option<TypeReference> {
+        let full_name = name.split(',').next()?.trim();
+        let (namespace, name) = match full_name.rfind('.') {
+            Some(dot) => (&full_name[..dot], &full_name[(dot + 1)..]),
+            None => ("", full_name),
+        };
+        self.enum_underlying_type(self.find_type_def(namespace, name)?).ok()?
+    }
 }
 
 impl MetadataImage<Vec<u8>> {
@@ -79,3 +449,148 @@ impl MetadataImage<Vec<u8>> {
         MetadataImage::load(PeImage::read(reader)?)
     }
 }
+
+/// Reads an embedded resource, which is stored as a 4-byte length followed by the data.
+fn read_length_prefixed(resources: &[u8], offset: u32) -> Result<&[u8], Error> {
+    let mut buf = resources.get(offset as usize..).ok_or(resource_out_of_range())?;
+    let len = buf.read_u32::<LittleEndian>().map_err(|_| resource_out_of_range())? as usize;
+    buf.get(..len).ok_or(resource_out_of_range())
+}
+
+fn resource_out_of_range() -> Error {
+    Error::InvalidMetadata("A manifest resource extends past the end of the CLI resources.")
+}
+
+fn invalid_enum() -> Error {
+    Error::InvalidMetadata("An enum must have exactly one instance field.")
+}
+
+fn table_too_large() -> Error {
+    Error::InvalidMetadata("There is insufficient space in the metadata stream for this table.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cli::{AttributeEnumType, AttributeValue, CliFlags};
+    use pe::test_image::{put_u32, set_virtual_size};
+    use test_metadata::{build_assembly_image, build_image, write_u16, write_u32};
+
+    #[test]
+    pub fn read_embedded_resources() {
+        let resources = [3, 0, 0, 0, b'a', b'b', b'c', 0, 0, 0, 0, 0xFF, 0xFF, 0xFF];
+        assert_eq!(Ok(&b"abc"[..]), read_length_prefixed(&resources, 0));
+        assert_eq!(Ok(&b""[..]), read_length_prefixed(&resources, 7));
+        assert_eq!(Err(resource_out_of_range()), read_length_prefixed(&resources, 11));
+        assert_eq!(Err(resource_out_of_range()), read_length_prefixed(&resources, 12));
+        assert_eq!(Err(resource_out_of_range()), read_length_prefixed(&resources, 0x1000));
+    }
+
+    #[test]
+    pub fn cli_header_in_zero_filled_tail() {
+        // A huge CLI header directory in the zero-filled tail of a section used to allocate its whole stated size
+        let mut data = build_image(&[], b"\0");
+        set_virtual_size(&mut data, false, 0xF000_0000);
+        put_u32(&mut data, 0x58 + 96 + 14 * 8 + 4, 0xE000_0000);
+        let image = MetadataImage::load_data(data).unwrap();
+        assert_eq!(72, image.cli_header().header_size);
+
+        let mut data = build_image(&[], b"\0");
+        put_u32(&mut data, 0x58 + 96 + 14 * 8 + 4, 8);
+        assert_eq!(Some(Error::CliHeaderNotFound), MetadataImage::load_data(data).err());
+    }
+
+    /// Builds an image with `enum Color { Red }`, which extends a TypeRef to System.Enum, and `class Foo` with an
+    /// instance field. Foo has the attribute `[Attr(Color.Red, Field = Color.Red)]`, where Attr is a TypeRef.
+    fn enum_image(red_flags: u16) -> MetadataImage<Vec<u8>> {
+        let strings = b"\0<Module>\0Color\0System\0Enum\0value__\0Red\0Foo\0.ctor\0";
+        // int32, valuetype Color, the constructor signature and the attribute value
+        let mut blobs = vec![0, 2, 0x06, 0x08, 3, 0x06, 0x11, 0x08, 5, 0x20, 0x01, 0x01, 0x11, 0x08, 26];
+        blobs.extend_from_slice(b"\x01\x00\x02\x00\x00\x00\x01\x00\x53\x55\x05Color\x05Field\x02\x00\x00\x00");
+
+        let mut module = Vec::new();
+        for &val in &[0, 1, 1, 0, 0] {
+            write_u16(&mut module, val);
+        }
+        let mut type_refs = Vec::new();
+        for &val in &[0, 23, 16] {
+            write_u16(&mut type_refs, val);
+        }
+        let mut type_defs = Vec::new();
+        for &(flags, name, extends, field_list) in &[(0, 1, 0, 1), (0x101, 10, 5, 1), (0x1, 40, 0, 3)] {
+            write_u32(&mut type_defs, flags);
+            for &val in &[name, 0, extends, field_list, 1] {
+                write_u16(&mut type_defs, val);
+            }
+        }
+        let mut fields = Vec::new();
+        for &(flags, name, signature) in &[(0x0606, 28, 1), (red_flags, 36, 4), (0x0006, 28, 1)] {
+            for &val in &[flags, name, signature] {
+                write_u16(&mut fields, val);
+            }
+        }
+
+        let mut member_refs = Vec::new();
+        for &val in &[0x0009, 44, 8] {
+            write_u16(&mut member_refs, val);
+        }
+        let mut custom_attributes = Vec::new();
+        for &val in &[0x0063, 0x000B, 14] {
+            write_u16(&mut custom_attributes, val);
+        }
+
+        let tables = vec![
+            (TableIndex::Module, 1, module),
+            (TableIndex::TypeRef, 1, type_refs),
+            (TableIndex::TypeDef, 3, type_defs),
+            (TableIndex::Field, 3, fields),
+            (TableIndex::MemberRef, 1, member_refs),
+            (TableIndex::CustomAttribute, 1, custom_attributes),
+        ];
+        MetadataImage::load_data(build_assembly_image(&tables, strings, &blobs, CliFlags::empty(), 0)).unwrap()
+    }
+
+    #[test]
+    pub fn enum_underlying_type() {
+        let image = enum_image(0x8056);
+        let type_def = |index| TableHandle::new(index, TableIndex::TypeDef);
+        let field = |index| TableHandle::new(index, TableIndex::Field);
+
+        assert_eq!(("System", "Enum"), image.type_name(TableHandle::new(1, TableIndex::TypeRef)).unwrap());
+        assert_eq!(("", "Color"), image.type_name(type_def(2)).unwrap());
+        assert_eq!(Vec::<TableHandle>::new(), image.type_def_fields(type_def(1)).unwrap());
+        assert_eq!(vec![field(1), field(2)], image.type_def_fields(type_def(2)).unwrap());
+        assert_eq!(vec![field(3)], image.type_def_fields(type_def(3)).unwrap());
+
+        assert_eq!(Ok(Some(TypeReference::I4)), image.enum_underlying_type(type_def(2)));
+        assert_eq!(Ok(None), image.enum_underlying_type(type_def(3)));
+        assert_eq!(Ok(None), image.enum_underlying_type(type_def(1)));
+        assert_eq!(Err(Error::InvalidTableReference), image.enum_underlying_type(type_def(4)));
+
+        // Red is no longer static, so the enum has two instance fields
+        let image = enum_image(0x0006);
+        assert_eq!(Err(invalid_enum()), image.enum_underlying_type(type_def(2)));
+    }
+
+    #[test]
+    pub fn custom_attribute_values() {
+        let image = enum_image(0x8056);
+        let foo = TableHandle::new(3, TableIndex::TypeDef);
+        let color = AttributeEnumType::Handle(TableHandle::new(2, TableIndex::TypeDef));
+
+        assert_eq!(Some(TableHandle::new(2, TableIndex::TypeDef)), image.find_type_def("", "Color"));
+        assert_eq!(None, image.find_type_def("System", "Color"));
+        assert!(image.custom_attributes(TableHandle::new(2, TableIndex::TypeDef)).unwrap().is_empty());
+
+        let attributes = image.custom_attributes(foo).unwrap();
+        assert_eq!(1, attributes.len());
+        assert_eq!(TableHandle::new(1, TableIndex::MemberRef), attributes[0].attribute_type);
+        let value = image.custom_attribute_value(&attributes[0], &image).unwrap();
+        assert_eq!(vec![AttributeValue::Enum(color, Box::new(AttributeValue::I4(2)))], value.fixed_arguments);
+        assert_eq!(
+            AttributeValue::Enum(AttributeEnumType::Name("Color".into()), Box::new(AttributeValue::I4(2))),
+            value.named_arguments[0].value
+        );
+    }
+}
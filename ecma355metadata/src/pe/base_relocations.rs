@@ -0,0 +1,122 @@
+use std::fmt;
+use std::ops::Deref;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use pe::{MemoryRange, PeImage};
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct RelocationType(u8);
+
+impl RelocationType {
+    /// Padding, which is skipped by the loader.
+    pub const ABSOLUTE: RelocationType = RelocationType(0);
+    pub const HIGH: RelocationType = RelocationType(1);
+    pub const LOW: RelocationType = RelocationType(2);
+    pub const HIGHLOW: RelocationType = RelocationType(3);
+    pub const HIGHADJ: RelocationType = RelocationType(4);
+    pub const DIR64: RelocationType = RelocationType(10);
+
+    pub fn new(val: u8) -> RelocationType {
+        RelocationType(val)
+    }
+}
+
+impl fmt::Display for RelocationType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            RelocationType::ABSOLUTE => f.write_str("Absolute"),
+            RelocationType::HIGH => f.write_str("High"),
+            RelocationType::LOW => f.write_str("Low"),
+            RelocationType::HIGHLOW => f.write_str("HighLow"),
+            RelocationType::HIGHADJ => f.write_str("HighAdj"),
+            RelocationType::DIR64 => f.write_str("Dir64"),
+            RelocationType(x) => write!(f, "0x{:X}", x),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Relocation {
+    pub relocation_type: RelocationType,
+    pub rva: u32,
+}
+
+/// A block of base relocations, which all apply to the same 4K page.
+///
+/// Managed images normally have a single relocation, for the jump to `_CorExeMain` in the entry point stub.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RelocationBlock {
+    pub page_rva: u32,
+    pub relocations: Vec<Relocation>,
+}
+
+impl RelocationBlock {
+    pub const HEADER_SIZE: usize = 8;
+
+    /// Reads all of the blocks in the specified range.
+    pub fn read_table<D: Deref<Target = [u8]>>(
+        pe: &PeImage<D>,
+        range: MemoryRange,
+    ) -> Result<Vec<RelocationBlock>, Error> {
+        let mut buf = pe.read_raw(range)?;
+
+        let mut blocks = Vec::new();
+        while !buf.is_empty() {
+            let page_rva = buf.read_u32::<LittleEndian>()?;
+            let block_size = buf.read_u32::<LittleEndian>()? as usize;
+            if block_size < RelocationBlock::HEADER_SIZE || block_size - RelocationBlock::HEADER_SIZE > buf.len() {
+                return Err(Error::InvalidDirectory("A base relocation block has an invalid size."));
+            }
+
+            let (mut entries, rest) = buf.split_at(block_size - RelocationBlock::HEADER_SIZE);
+            let mut relocations = Vec::new();
+            while entries.len() >= 2 {
+                let entry = entries.read_u16::<LittleEndian>()?;
+                relocations.push(Relocation {
+                    relocation_type: RelocationType::new((entry >> 12) as u8),
+                    rva: page_rva.wrapping_add((entry & 0xFFF) as u32),
+                });
+            }
+            blocks.push(RelocationBlock { page_rva, relocations });
+            buf = rest;
+        }
+        Ok(blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pe::test_image::{build_image, SECTION_RVA};
+
+    #[test]
+    pub fn read_relocations() {
+        // The block a C# compiler writes for the entry point stub, padded with an absolute relocation
+        let data = [0x00, 0x20, 0x00, 0x00, 0x0C, 0x00, 0x00, 0x00, 0xA2, 0x36, 0x00, 0x00];
+        let image = PeImage::load(build_image(false, &[(5, MemoryRange::new(SECTION_RVA, 12))], &data)).unwrap();
+
+        assert_eq!(
+            vec![RelocationBlock {
+                page_rva: 0x2000,
+                relocations: vec![
+                    Relocation { relocation_type: RelocationType::HIGHLOW, rva: 0x26A2 },
+                    Relocation { relocation_type: RelocationType::ABSOLUTE, rva: 0x2000 },
+                ],
+            }],
+            image.base_relocations().unwrap()
+        );
+    }
+
+    #[test]
+    pub fn invalid_block_size() {
+        let data = [0x00, 0x20, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00];
+        let image = PeImage::load(build_image(false, &[(5, MemoryRange::new(SECTION_RVA, 8))], &data)).unwrap();
+        assert_eq!(
+            Err(Error::InvalidDirectory("A base relocation block has an invalid size.")),
+            image.base_relocations()
+        );
+    }
+}
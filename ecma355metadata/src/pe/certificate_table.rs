@@ -0,0 +1,146 @@
+use std::fmt;
+use std::ops::Deref;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use pe::{MemoryRange, PeImage};
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct CertificateType(u16);
+
+impl CertificateType {
+    pub const X509: CertificateType = CertificateType(1);
+
+    /// A PKCS#7 `SignedData` structure, which is how Authenticode signatures are stored.
+    pub const PKCS_SIGNED_DATA: CertificateType = CertificateType(2);
+    pub const TS_STACK_SIGNED: CertificateType = CertificateType(4);
+
+    pub fn new(val: u16) -> CertificateType {
+        CertificateType(val)
+    }
+}
+
+impl fmt::Display for CertificateType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            CertificateType::X509 => f.write_str("X509"),
+            CertificateType::PKCS_SIGNED_DATA => f.write_str("PKCS#7 SignedData"),
+            CertificateType::TS_STACK_SIGNED => f.write_str("TS Stack Signed"),
+            CertificateType(x) => write!(f, "0x{:X}", x),
+        }
+    }
+}
+
+/// An entry in the certificate table (`WIN_CERTIFICATE`), which holds an Authenticode signature.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Certificate {
+    pub revision: u16,
+    pub certificate_type: CertificateType,
+    pub data: Vec<u8>,
+}
+
+impl Certificate {
+    pub const HEADER_SIZE: usize = 8;
+
+    /// Reads the entries in the certificate table, whose range is a file offset rather than an RVA.
+    ///
+    /// The certificate table isn't loaded into memory, so it is usually at the end of the file, after the sections.
+    pub fn read_table<D: Deref<Target = [u8]>>(pe: &PeImage<D>, range: MemoryRange) -> Result<Vec<Certificate>, Error> {
+        let start = range.start as usize;
+        let mut buf = start.checked_add(range.len as usize)
+            .and_then(|end| pe.data().get(start..end))
+            .ok_or(Error::InvalidDirectory("The certificate table extends past the end of the file."))?;
+
+        let mut certificates = Vec::new();
+        while !buf.is_empty() {
+            let len = buf.read_u32::<LittleEndian>()? as usize;
+            let revision = buf.read_u16::<LittleEndian>()?;
+            let certificate_type = CertificateType::new(buf.read_u16::<LittleEndian>()?);
+            if len < Certificate::HEADER_SIZE || len - Certificate::HEADER_SIZE > buf.len() {
+                return Err(Error::InvalidDirectory("A certificate has an invalid length."));
+            }
+            let (data, rest) = buf.split_at(len - Certificate::HEADER_SIZE);
+            certificates.push(Certificate {
+                revision,
+                certificate_type,
+                data: data.to_vec(),
+            });
+
+            // Each entry is padded to a multiple of 8 bytes
+            let padding = (8 - len % 8) % 8;
+            buf = rest.get(padding..).unwrap_or(&[]);
+        }
+        Ok(certificates)
+    }
+
+    /// Gets the PKCS#7 `SignedData` blob of an Authenticode signature, if this is one.
+    pub fn pkcs7(&self) -> Option<&[u8]> {
+        if self.certificate_type == CertificateType::PKCS_SIGNED_DATA {
+            Some(&self.data)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pe::test_image::{build_image, put_u32};
+
+    /// Appends a certificate table to a PE32 image from `build_image`, and points the directory entry at it.
+    pub fn append_certificates(image: &mut Vec<u8>, certificates: &[(u16, &[u8])]) -> MemoryRange {
+        let start = image.len() as u32;
+        for &(certificate_type, data) in certificates {
+            image.extend_from_slice(&((data.len() + 8) as u32).to_le_bytes());
+            image.extend_from_slice(&0x0200u16.to_le_bytes());
+            image.extend_from_slice(&certificate_type.to_le_bytes());
+            image.extend_from_slice(data);
+            let padded = (image.len() + 7) & !7;
+            image.resize(padded, 0);
+        }
+        let range = MemoryRange::new(start, image.len() as u32 - start);
+
+        // The certificate table is directory 4
+        let directory = 0x58 + 96 + 4 * 8;
+        put_u32(image, directory, range.start);
+        put_u32(image, directory + 4, range.len);
+        range
+    }
+
+    #[test]
+    pub fn read_certificates() {
+        let mut image = build_image(false, &[], &[0u8; 0x10]);
+        append_certificates(&mut image, &[(2, b"\x30\x82\x01"), (1, b"cert")]);
+        let image = PeImage::load(image).unwrap();
+
+        let certificates = image.certificates().unwrap();
+        assert_eq!(
+            vec![
+                Certificate { revision: 0x200, certificate_type: CertificateType::PKCS_SIGNED_DATA, data: vec![0x30, 0x82, 0x01] },
+                Certificate { revision: 0x200, certificate_type: CertificateType::X509, data: b"cert".to_vec() },
+            ],
+            certificates
+        );
+        assert_eq!(Some(&[0x30, 0x82, 0x01][..]), certificates[0].pkcs7());
+        assert_eq!(None, certificates[1].pkcs7());
+        assert_eq!("PKCS#7 SignedData", format!("{}", certificates[0].certificate_type));
+    }
+
+    #[test]
+    pub fn invalid_certificate_table() {
+        let mut image = build_image(false, &[], &[0u8; 0x10]);
+        let range = append_certificates(&mut image, &[(2, b"data")]);
+        image[range.start as usize] = 0xFF;
+        let pe = PeImage::load(&image[..]).unwrap();
+        assert_eq!(Err(Error::InvalidDirectory("A certificate has an invalid length.")), pe.certificates());
+
+        let pe = PeImage::load(build_image(false, &[(4, MemoryRange::new(0x300, 8))], &[0u8; 0x10])).unwrap();
+        assert_eq!(
+            Err(Error::InvalidDirectory("The certificate table extends past the end of the file.")),
+            pe.certificates()
+        );
+    }
+}
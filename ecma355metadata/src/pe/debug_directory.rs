@@ -0,0 +1,301 @@
+use std::fmt;
+use std::io::Read;
+use std::ops::Deref;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use miniz_oxide::inflate;
+
+use error::Error;
+use pe::{MemoryRange, PeImage};
+use Guid;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct DebugType(u32);
+
+impl DebugType {
+    pub const UNKNOWN: DebugType = DebugType(0);
+    pub const COFF: DebugType = DebugType(1);
+    pub const CODEVIEW: DebugType = DebugType(2);
+    pub const FPO: DebugType = DebugType(3);
+    pub const MISC: DebugType = DebugType(4);
+    pub const EXCEPTION: DebugType = DebugType(5);
+    pub const FIXUP: DebugType = DebugType(6);
+    pub const BORLAND: DebugType = DebugType(9);
+    pub const CLSID: DebugType = DebugType(11);
+    pub const VC_FEATURE: DebugType = DebugType(12);
+    pub const POGO: DebugType = DebugType(13);
+    pub const ILTCG: DebugType = DebugType(14);
+
+    /// Marks the image as deterministic, meaning that its timestamps are derived from a hash of its contents.
+    pub const REPRO: DebugType = DebugType(16);
+
+    /// A portable PDB, compressed and embedded in the image.
+    pub const EMBEDDED_PORTABLE_PDB: DebugType = DebugType(17);
+
+    /// The checksum of the PDB associated with the image.
+    pub const PDB_CHECKSUM: DebugType = DebugType(19);
+
+    pub fn new(val: u32) -> DebugType {
+        DebugType(val)
+    }
+}
+
+impl fmt::Display for DebugType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            DebugType::UNKNOWN => f.write_str("Unknown"),
+            DebugType::COFF => f.write_str("COFF"),
+            DebugType::CODEVIEW => f.write_str("CodeView"),
+            DebugType::FPO => f.write_str("FPO"),
+            DebugType::MISC => f.write_str("Misc"),
+            DebugType::EXCEPTION => f.write_str("Exception"),
+            DebugType::FIXUP => f.write_str("Fixup"),
+            DebugType::BORLAND => f.write_str("Borland"),
+            DebugType::CLSID => f.write_str("CLSID"),
+            DebugType::VC_FEATURE => f.write_str("VCFeature"),
+            DebugType::POGO => f.write_str("POGO"),
+            DebugType::ILTCG => f.write_str("ILTCG"),
+            DebugType::REPRO => f.write_str("Reproducible"),
+            DebugType::EMBEDDED_PORTABLE_PDB => f.write_str("EmbeddedPortablePdb"),
+            DebugType::PDB_CHECKSUM => f.write_str("PdbChecksum"),
+            DebugType(x) => write!(f, "0x{:X}", x),
+        }
+    }
+}
+
+/// An entry in the debug directory, describing one piece of debug information.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DebugDirectoryEntry {
+    pub characteristics: u32,
+    pub timestamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub debug_type: DebugType,
+    pub size_of_data: u32,
+    pub address_of_raw_data: u32,
+    pub pointer_to_raw_data: u32,
+}
+
+impl DebugDirectoryEntry {
+    pub const SIZE: usize = 28;
+
+    /// The minor version of CodeView entries that refer to a portable PDB, rather than a Windows PDB.
+    pub const PORTABLE_CODEVIEW_VERSION: u16 = 0x504D;
+
+    pub fn read<A: Read>(buf: &mut A) -> Result<DebugDirectoryEntry, Error> {
+        Ok(DebugDirectoryEntry {
+            characteristics: buf.read_u32::<LittleEndian>()?,
+            timestamp: buf.read_u32::<LittleEndian>()?,
+            major_version: buf.read_u16::<LittleEndian>()?,
+            minor_version: buf.read_u16::<LittleEndian>()?,
+            debug_type: DebugType::new(buf.read_u32::<LittleEndian>()?),
+            size_of_data: buf.read_u32::<LittleEndian>()?,
+            address_of_raw_data: buf.read_u32::<LittleEndian>()?,
+            pointer_to_raw_data: buf.read_u32::<LittleEndian>()?,
+        })
+    }
+
+    /// Reads all of the entries in the specified range.
+    pub fn read_table<D: Deref<Target = [u8]>>(
+        pe: &PeImage<D>,
+        range: MemoryRange,
+    ) -> Result<Vec<DebugDirectoryEntry>, Error> {
+        if !(range.len as usize).is_multiple_of(DebugDirectoryEntry::SIZE) {
+            return Err(Error::InvalidDirectory("The debug directory size is not a multiple of the entry size."));
+        }
+        pe.read_raw(range)?
+            .chunks(DebugDirectoryEntry::SIZE)
+            .map(|mut chunk| DebugDirectoryEntry::read(&mut chunk))
+            .collect()
+    }
+
+    /// Indicates if this is a CodeView entry for a portable PDB.
+    pub fn is_portable_codeview(&self) -> bool {
+        self.debug_type == DebugType::CODEVIEW && self.minor_version == DebugDirectoryEntry::PORTABLE_CODEVIEW_VERSION
+    }
+
+    /// Gets the data of the entry, which may not be mapped into memory, in which case it is read from the file.
+    ///
+    /// Like the directory itself, the data must be present in the file, so a bogus size can't cause a huge
+    /// allocation.
+    pub fn data<'a, D: Deref<Target = [u8]>>(&self, pe: &'a PeImage<D>) -> Result<&'a [u8], Error> {
+        if self.size_of_data == 0 {
+            Ok(&[])
+        } else if self.address_of_raw_data != 0 {
+            pe.read_raw(MemoryRange::new(self.address_of_raw_data, self.size_of_data))
+        } else {
+            let start = self.pointer_to_raw_data as usize;
+            start.checked_add(self.size_of_data as usize)
+                .and_then(|end| pe.data().get(start..end))
+                .ok_or(Error::InvalidDirectory("Debug data extends past the end of the file."))
+        }
+    }
+}
+
+/// The data of a CodeView entry, which identifies the PDB that matches the image.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CodeViewData {
+    pub guid: Guid,
+    pub age: u32,
+    pub path: String,
+}
+
+impl CodeViewData {
+    const SIGNATURE: &'static [u8] = b"RSDS";
+
+    pub fn read(mut data: &[u8]) -> Result<CodeViewData, Error> {
+        if !data.starts_with(CodeViewData::SIGNATURE) {
+            return Err(Error::InvalidSignature);
+        }
+        data = &data[CodeViewData::SIGNATURE.len()..];
+
+        let mut guid = [0u8; 16];
+        data.read_exact(&mut guid)?;
+        let age = data.read_u32::<LittleEndian>()?;
+        let path = data.split(|&b| b == 0).next().unwrap_or(&[]);
+        Ok(CodeViewData {
+            guid: Guid::new(guid),
+            age,
+            path: String::from_utf8(path.to_vec())?,
+        })
+    }
+}
+
+/// The data of an embedded portable PDB entry: the PDB, compressed with deflate.
+#[derive(Debug, PartialEq, Eq)]
+pub struct EmbeddedPdb<'a> {
+    pub uncompressed_size: u32,
+    pub compressed_data: &'a [u8],
+}
+
+impl<'a> EmbeddedPdb<'a> {
+    const SIGNATURE: &'static [u8] = b"MPDB";
+
+    pub fn read(data: &'a [u8]) -> Result<EmbeddedPdb<'a>, Error> {
+        if !data.starts_with(EmbeddedPdb::SIGNATURE) {
+            return Err(Error::InvalidSignature);
+        }
+        let mut buf = &data[EmbeddedPdb::SIGNATURE.len()..];
+        let uncompressed_size = buf.read_u32::<LittleEndian>()?;
+        Ok(EmbeddedPdb {
+            uncompressed_size,
+            compressed_data: buf,
+        })
+    }
+
+    /// Decompresses the PDB, which must be exactly `uncompressed_size` bytes.
+    pub fn decompress(&self) -> Result<Vec<u8>, Error> {
+        let size = self.uncompressed_size as usize;
+        match inflate::decompress_to_vec_with_limit(self.compressed_data, size) {
+            Ok(ref pdb) if pdb.len() != size => Err(Error::InvalidDirectory("The embedded PDB is smaller than its stated size.")),
+            Ok(pdb) => Ok(pdb),
+            Err(_) => Err(Error::InvalidDirectory("The embedded PDB could not be decompressed.")),
+        }
+    }
+}
+
+/// The data of a PDB checksum entry: the name of a hash algorithm, such as `SHA256`, and the PDB's hash.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PdbChecksum<'a> {
+    pub algorithm: String,
+    pub checksum: &'a [u8],
+}
+
+impl<'a> PdbChecksum<'a> {
+    pub fn read(data: &'a [u8]) -> Result<PdbChecksum<'a>, Error> {
+        let len = data.iter()
+            .position(|&b| b == 0)
+            .ok_or(Error::InvalidDirectory("The PDB checksum algorithm name is not terminated."))?;
+        Ok(PdbChecksum {
+            algorithm: String::from_utf8(data[..len].to_vec())?,
+            checksum: &data[(len + 1)..],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pe::test_image::{build_image, set_virtual_size, SECTION_RVA};
+
+    fn entry(debug_type: DebugType, minor_version: u16, size: usize, address: u32) -> Vec<u8> {
+        let mut entry = vec![0u8; DebugDirectoryEntry::SIZE];
+        entry[4..8].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+        entry[8..10].copy_from_slice(&0x0100u16.to_le_bytes());
+        entry[10..12].copy_from_slice(&minor_version.to_le_bytes());
+        entry[12..16].copy_from_slice(&debug_type.0.to_le_bytes());
+        entry[16..20].copy_from_slice(&(size as u32).to_le_bytes());
+        entry[20..24].copy_from_slice(&address.to_le_bytes());
+        entry
+    }
+
+    #[test]
+    pub fn read_debug_directory() {
+        let mut codeview = b"RSDS".to_vec();
+        codeview.extend(1..17);
+        codeview.extend(&[1, 0, 0, 0]);
+        codeview.extend(b"/src/HelloWorld.pdb\0");
+
+        // A CodeView entry for a portable PDB, followed by the entry that marks a deterministic build
+        let mut data = entry(DebugType::CODEVIEW, 0x504D, codeview.len(), SECTION_RVA + 0x38);
+        data.extend(entry(DebugType::REPRO, 0, 0, 0));
+        data.extend(&codeview);
+        let image = PeImage::load(build_image(false, &[(6, MemoryRange::new(SECTION_RVA, 0x38))], &data)).unwrap();
+
+        let entries = image.debug_directory().unwrap();
+        assert_eq!(2, entries.len());
+        assert!(entries[0].is_portable_codeview());
+        assert_eq!(DebugType::REPRO, entries[1].debug_type);
+        assert!(entries[1].data(&image).unwrap().is_empty());
+
+        let codeview = image.codeview().unwrap().unwrap();
+        assert_eq!(1, codeview.age);
+        assert_eq!("/src/HelloWorld.pdb", codeview.path);
+        assert_eq!("{01020304-0506-0708-090A-0B0C0D0E0F10}", format!("{}", codeview.guid));
+    }
+
+    #[test]
+    pub fn decompress_embedded_pdb() {
+        let pdb = b"BSJB and the rest of a portable PDB".to_vec();
+        let mut data = b"MPDB".to_vec();
+        data.extend(&(pdb.len() as u32).to_le_bytes());
+        data.extend(miniz_oxide::deflate::compress_to_vec(&pdb, 6));
+
+        let embedded = EmbeddedPdb::read(&data).unwrap();
+        assert_eq!(pdb, embedded.decompress().unwrap());
+
+        // A stated size that doesn't match the data is an error, rather than a huge allocation
+        data[4..8].copy_from_slice(&0xFFFF_FFFFu32.to_le_bytes());
+        assert!(EmbeddedPdb::read(&data).unwrap().decompress().is_err());
+    }
+
+    #[test]
+    pub fn read_pdb_checksum() {
+        let checksum = PdbChecksum::read(b"SHA256\0\x01\x02").unwrap();
+        assert_eq!("SHA256", checksum.algorithm);
+        assert_eq!(&[1, 2], checksum.checksum);
+    }
+
+    #[test]
+    pub fn directory_in_zero_filled_tail() {
+        // Found by fuzzing, a huge directory in the zero-filled tail of a section used to decode millions of entries
+        let data = entry(DebugType::REPRO, 0, 0, 0);
+        let mut image = build_image(false, &[(6, MemoryRange::new(SECTION_RVA, 0x7000_0000))], &data);
+        set_virtual_size(&mut image, false, 0xFFFF_0000);
+        let image = PeImage::load(image).unwrap();
+        assert_eq!(Err(Error::RvaOutOfRange), image.debug_directory());
+    }
+
+    #[test]
+    pub fn data_in_zero_filled_tail() {
+        // Debug data that is only in the zero-filled tail of a section used to allocate its whole stated size
+        let data = entry(DebugType::CODEVIEW, 0, 0x7000_0000, SECTION_RVA + 0x100);
+        let mut image = build_image(false, &[(6, MemoryRange::new(SECTION_RVA, 0x1C))], &data);
+        set_virtual_size(&mut image, false, 0xFFFF_0000);
+        let image = PeImage::load(image).unwrap();
+        let entries = image.debug_directory().unwrap();
+        assert_eq!(Err(Error::RvaOutOfRange), entries[0].data(&image));
+        assert_eq!(Err(Error::RvaOutOfRange), image.codeview());
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt;
+use std::ops::Deref;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use pe::{MemoryRange, PeImage};
+
+/// The location of an exported function.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExportTarget {
+    /// The RVA of the function within this image.
+    Rva(u32),
+
+    /// The function is forwarded to another DLL, written as `DLL.Function` or `DLL.#Ordinal`.
+    Forwarder(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Export {
+    pub ordinal: u32,
+    pub name: Option<String>,
+    pub target: ExportTarget,
+}
+
+impl fmt::Display for Export {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "#{} {}", self.ordinal, self.name.as_ref().map(|n| &n[..]).unwrap_or("<unnamed>"))?;
+        match self.target {
+            ExportTarget::Rva(rva) => write!(f, " = 0x{:08X}", rva),
+            ExportTarget::Forwarder(ref forwarder) => write!(f, " -> {}", forwarder),
+        }
+    }
+}
+
+/// The export table of an image, listing the functions it exports to native code.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExportTable {
+    pub characteristics: u32,
+    pub timestamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub dll_name: String,
+    pub ordinal_base: u32,
+    pub exports: Vec<Export>,
+}
+
+impl ExportTable {
+    pub const SIZE: usize = 40;
+
+    pub fn read<D: Deref<Target = [u8]>>(pe: &PeImage<D>, range: MemoryRange) -> Result<ExportTable, Error> {
+        let data = pe.read_rva(range.start, ExportTable::SIZE)?;
+        let mut buf = &data[..];
+        let characteristics = buf.read_u32::<LittleEndian>()?;
+        let timestamp = buf.read_u32::<LittleEndian>()?;
+        let major_version = buf.read_u16::<LittleEndian>()?;
+        let minor_version = buf.read_u16::<LittleEndian>()?;
+        let name = buf.read_u32::<LittleEndian>()?;
+        let ordinal_base = buf.read_u32::<LittleEndian>()?;
+        let function_count = buf.read_u32::<LittleEndian>()? as usize;
+        let name_count = buf.read_u32::<LittleEndian>()? as usize;
+        let functions = buf.read_u32::<LittleEndian>()?;
+        let names = buf.read_u32::<LittleEndian>()?;
+        let name_ordinals = buf.read_u32::<LittleEndian>()?;
+
+        // Reading the tables up front bounds the counts by the size of the file
+        let functions = read_array(pe, functions, function_count, 4)?;
+        let names = read_array(pe, names, name_count, 4)?;
+        let name_ordinals = read_array(pe, name_ordinals, name_count, 2)?;
+
+        // The name ordinal table maps each name to an index in the function table
+        let mut function_names = vec![None; function_count];
+        for idx in 0..name_count {
+            let index = (&name_ordinals[(idx * 2)..]).read_u16::<LittleEndian>()? as usize;
+            let name = (&names[(idx * 4)..]).read_u32::<LittleEndian>()?;
+            let slot = function_names.get_mut(index)
+                .ok_or(Error::InvalidDirectory("An export name refers to a function that does not exist."))?;
+            *slot = Some(pe.read_rva_string(name)?);
+        }
+
+        let mut exports = Vec::new();
+        for (idx, name) in function_names.into_iter().enumerate() {
+            let rva = (&functions[(idx * 4)..]).read_u32::<LittleEndian>()?;
+            if rva == 0 {
+                // Unused ordinal
+                continue;
+            }
+
+            // Forwarders are stored as strings within the export directory itself
+            let target = if rva >= range.start && rva - range.start < range.len {
+                ExportTarget::Forwarder(pe.read_rva_string(rva)?)
+            } else {
+                ExportTarget::Rva(rva)
+            };
+            exports.push(Export {
+                ordinal: ordinal_base.wrapping_add(idx as u32),
+                name,
+                target,
+            });
+        }
+
+        Ok(ExportTable {
+            characteristics,
+            timestamp,
+            major_version,
+            minor_version,
+            dll_name: pe.read_rva_string(name)?,
+            ordinal_base,
+            exports,
+        })
+    }
+}
+
+fn read_array<D: Deref<Target = [u8]>>(pe: &PeImage<D>, rva: u32, count: usize, size: usize) -> Result<&[u8], Error> {
+    if count == 0 {
+        return Ok(&[]);
+    }
+    let len = count.checked_mul(size).filter(|&len| len <= u32::MAX as usize).ok_or(Error::RvaOutOfRange)?;
+    pe.read_raw(MemoryRange::new(rva, len as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pe::test_image::{build_image, put_u32, SECTION_RVA};
+
+    #[test]
+    pub fn read_exports() {
+        // Three functions with ordinals 5-7: a named function, an unused ordinal and an unnamed forwarder
+        let mut data = vec![0u8; 0x80];
+        put_u32(&mut data, 0x0C, SECTION_RVA + 0x70);
+        put_u32(&mut data, 0x10, 5);
+        put_u32(&mut data, 0x14, 3);
+        put_u32(&mut data, 0x18, 1);
+        put_u32(&mut data, 0x1C, SECTION_RVA + 0x28);
+        put_u32(&mut data, 0x20, SECTION_RVA + 0x34);
+        put_u32(&mut data, 0x24, SECTION_RVA + 0x38);
+        put_u32(&mut data, 0x28, 0x2000);
+        put_u32(&mut data, 0x30, SECTION_RVA + 0x50);
+        put_u32(&mut data, 0x34, SECTION_RVA + 0x60);
+        data[0x50..0x5D].copy_from_slice(b"other.Forward");
+        data[0x60..0x64].copy_from_slice(b"Main");
+        data[0x70..0x7B].copy_from_slice(b"library.dll");
+        let image = PeImage::load(build_image(false, &[(0, MemoryRange::new(SECTION_RVA, 0x60))], &data)).unwrap();
+
+        let table = image.exports().unwrap().unwrap();
+        assert_eq!("library.dll", table.dll_name);
+        assert_eq!(
+            vec![
+                Export { ordinal: 5, name: Some("Main".to_string()), target: ExportTarget::Rva(0x2000) },
+                Export { ordinal: 7, name: None, target: ExportTarget::Forwarder("other.Forward".to_string()) },
+            ],
+            table.exports
+        );
+        assert_eq!("#5 Main = 0x00002000", format!("{}", table.exports[0]));
+    }
+
+    #[test]
+    pub fn huge_export_counts() {
+        let mut data = vec![0u8; 0x28];
+        put_u32(&mut data, 0x14, 0xFFFF_FFFF);
+        put_u32(&mut data, 0x1C, SECTION_RVA);
+        let image = PeImage::load(build_image(false, &[(0, MemoryRange::new(SECTION_RVA, 0x28))], &data)).unwrap();
+        assert_eq!(Some(Error::RvaOutOfRange), image.exports().err());
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+use std::ops::Deref;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use pe::{MemoryRange, PeImage};
+
+/// A function imported from a DLL, by ordinal or by name.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Import {
+    Ordinal(u16),
+
+    /// An import by name, with a hint for the index of the name in the DLL's export name table.
+    Name { hint: u16, name: String },
+}
+
+impl fmt::Display for Import {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Import::Ordinal(ordinal) => write!(f, "#{}", ordinal),
+            Import::Name { hint, ref name } => write!(f, "{} (Hint: {})", name, hint),
+        }
+    }
+}
+
+/// An entry in the import table, describing the functions imported from a single DLL.
+///
+/// Managed images import only `_CorExeMain` or `_CorDllMain` from `mscoree.dll`, for the entry point stub.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ImportDescriptor {
+    pub dll_name: String,
+    pub timestamp: u32,
+    pub forwarder_chain: u32,
+    pub import_lookup_table: u32,
+    pub import_address_table: u32,
+    pub imports: Vec<Import>,
+}
+
+impl ImportDescriptor {
+    pub const SIZE: usize = 20;
+
+    /// Reads the import descriptors in the specified range, up to the terminating all-zero descriptor.
+    pub fn read_table<D: Deref<Target = [u8]>>(
+        pe: &PeImage<D>,
+        range: MemoryRange,
+    ) -> Result<Vec<ImportDescriptor>, Error> {
+        let pe32plus = pe.pe_header().map(|h| h.magic.is_pe32plus()).unwrap_or(false);
+
+        let mut descriptors = Vec::new();
+        let mut rva = range.start;
+        loop {
+            let data = pe.read_rva(rva, ImportDescriptor::SIZE)?;
+            let mut buf = &data[..];
+            let import_lookup_table = buf.read_u32::<LittleEndian>()?;
+            let timestamp = buf.read_u32::<LittleEndian>()?;
+            let forwarder_chain = buf.read_u32::<LittleEndian>()?;
+            let name = buf.read_u32::<LittleEndian>()?;
+            let import_address_table = buf.read_u32::<LittleEndian>()?;
+            if import_lookup_table == 0 && name == 0 && import_address_table == 0 {
+                return Ok(descriptors);
+            }
+
+            // The lookup table is optional, the address table has the same contents until the image is bound
+            let lookup_table = if import_lookup_table != 0 {
+                import_lookup_table
+            } else {
+                import_address_table
+            };
+
+            descriptors.push(ImportDescriptor {
+                dll_name: pe.read_rva_string(name)?,
+                timestamp,
+                forwarder_chain,
+                import_lookup_table,
+                import_address_table,
+                imports: read_lookup_table(pe, lookup_table, pe32plus)?,
+            });
+            rva = rva.checked_add(ImportDescriptor::SIZE as u32)
+                .ok_or(Error::InvalidDirectory("The import table is not terminated."))?;
+        }
+    }
+}
+
+fn read_lookup_table<D: Deref<Target = [u8]>>(pe: &PeImage<D>, start: u32, pe32plus: bool) -> Result<Vec<Import>, Error> {
+    let entry_size = if pe32plus { 8 } else { 4 };
+
+    let mut imports = Vec::new();
+    let mut rva = start;
+    loop {
+        let data = pe.read_rva(rva, entry_size)?;
+        let (entry, by_ordinal) = if pe32plus {
+            let entry = (&data[..]).read_u64::<LittleEndian>()?;
+            (entry & 0x7FFF_FFFF, entry & (1 << 63) != 0)
+        } else {
+            let entry = (&data[..]).read_u32::<LittleEndian>()? as u64;
+            (entry & 0x7FFF_FFFF, entry & (1 << 31) != 0)
+        };
+        if entry == 0 && !by_ordinal {
+            return Ok(imports);
+        }
+
+        if by_ordinal {
+            imports.push(Import::Ordinal(entry as u16));
+        } else {
+            // The entry is the RVA of a hint followed by the name
+            let hint = (&pe.read_rva(entry as u32, 2)?[..]).read_u16::<LittleEndian>()?;
+            let name = pe.read_rva_string(entry as u32 + 2)?;
+            imports.push(Import::Name { hint, name });
+        }
+        rva = rva.checked_add(entry_size as u32)
+            .ok_or(Error::InvalidDirectory("The import lookup table is not terminated."))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pe::test_image::{build_image, SECTION_RVA};
+
+    #[test]
+    pub fn read_mscoree_import() {
+        // The import table, then the lookup table, hint/name entry and DLL name, as written by the C# compiler
+        let mut data = vec![0u8; 0x60];
+        data[0x00..0x04].copy_from_slice(&(SECTION_RVA + 0x28).to_le_bytes());
+        data[0x0C..0x10].copy_from_slice(&(SECTION_RVA + 0x50).to_le_bytes());
+        data[0x10..0x14].copy_from_slice(&(SECTION_RVA + 0x28).to_le_bytes());
+        data[0x28..0x2C].copy_from_slice(&(SECTION_RVA + 0x30).to_le_bytes());
+        data[0x32..0x3D].copy_from_slice(b"_CorExeMain");
+        data[0x50..0x5B].copy_from_slice(b"mscoree.dll");
+        let image = PeImage::load(build_image(false, &[(1, MemoryRange::new(SECTION_RVA, 0x28))], &data)).unwrap();
+
+        let imports = image.imports().unwrap();
+        assert_eq!(1, imports.len());
+        assert_eq!("mscoree.dll", imports[0].dll_name);
+        assert_eq!(SECTION_RVA + 0x28, imports[0].import_address_table);
+        assert_eq!(vec![Import::Name { hint: 0, name: "_CorExeMain".to_string() }], imports[0].imports);
+    }
+
+    #[test]
+    pub fn read_pe32plus_import_by_ordinal() {
+        let mut data = vec![0u8; 0x50];
+        data[0x0C..0x10].copy_from_slice(&(SECTION_RVA + 0x40).to_le_bytes());
+        data[0x10..0x14].copy_from_slice(&(SECTION_RVA + 0x28).to_le_bytes());
+        data[0x28..0x30].copy_from_slice(&(0x8000_0000_0000_0007u64).to_le_bytes());
+        data[0x40..0x4A].copy_from_slice(b"user32.dll");
+        let image = PeImage::load(build_image(true, &[(1, MemoryRange::new(SECTION_RVA, 0x28))], &data)).unwrap();
+
+        let imports = image.imports().unwrap();
+        assert_eq!("user32.dll", imports[0].dll_name);
+        assert_eq!(vec![Import::Ordinal(7)], imports[0].imports);
+        assert_eq!("#7", format!("{}", imports[0].imports[0]));
+    }
+
+    #[test]
+    pub fn unterminated_import_table() {
+        let data = vec![0xFFu8; 0x14];
+        let image = PeImage::load(build_image(false, &[(1, MemoryRange::new(SECTION_RVA, 0x14))], &data)).unwrap();
+        assert!(image.imports().is_err());
+    }
+}
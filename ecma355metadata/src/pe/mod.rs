@@ -1,13 +1,22 @@
+mod base_relocations;
+mod certificate_table;
 mod characteristics;
 mod coff_header;
+mod debug_directory;
 mod directory_entry;
+mod export_table;
+mod import_table;
 mod pe_header;
 mod pe_image;
 mod pe_magic;
+pub mod resources;
 mod section_header;
 mod memory_range;
 mod subsystem;
 
+#[cfg(test)]
+pub mod test_image;
+
 pub use self::coff_header::CoffHeader;
 pub use self::pe_header::PeHeader;
 pub use self::pe_magic::PeMagic;
@@ -17,3 +26,8 @@ pub use self::section_header::SectionHeader;
 pub use self::memory_range::MemoryRange;
 pub use self::pe_image::PeImage;
 pub use self::characteristics::{FileCharacteristics, SectionCharacteristics};
+pub use self::import_table::{Import, ImportDescriptor};
+pub use self::export_table::{Export, ExportTable, ExportTarget};
+pub use self::base_relocations::{Relocation, RelocationBlock, RelocationType};
+pub use self::certificate_table::{Certificate, CertificateType};
+pub use self::debug_directory::{CodeViewData, DebugDirectoryEntry, DebugType, EmbeddedPdb, PdbChecksum};
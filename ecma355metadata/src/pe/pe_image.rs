@@ -1,9 +1,12 @@
-use std::ops::{Deref, Index, Range};
+use std::borrow::Cow;
+use std::ops::{Deref, Range};
 use std::io::{Cursor, Read, Seek, SeekFrom};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 
-use pe::{CoffHeader, PeHeader, SectionHeader};
+use pe::{Certificate, CodeViewData, CoffHeader, DebugDirectoryEntry, DebugType, DirectoryType, ExportTable, ImportDescriptor,
+         MemoryRange, PeHeader, RelocationBlock, SectionHeader};
+use pe::resources::{Resource, ResourceId, VersionInfo};
 use error::Error;
 
 // TODO: We could probably use a trait other than Deref in order to
@@ -19,51 +22,67 @@ pub struct PeImage<D: Deref<Target = [u8]>> {
 
 const DOS_SIGNATURE: u16 = 0x5A4D;
 const PE_SIGNATURE: u32 = 0x00004550;
+const LFANEW_OFFSET: u64 = 0x3C;
+
+/// The largest read `read_rva` will zero-fill, so a bogus section size can't force a huge allocation.
+const MAX_ZERO_FILL: usize = 0x1000;
 
-/// Represents a PE Image in memory
-/// 
-/// This type can be indexed or sliced like a [u8], but the offsets are RVAs (relative virtual adresses)
 impl<D: Deref<Target = [u8]>> PeImage<D> {
     pub fn load(data: D) -> Result<PeImage<D>, Error> {
         let (coff_header, pe_header, sections) = {
+            let file_len = data.len() as u64;
             let mut reader = Cursor::new(data.deref());
 
             // Verify the MZ signature
             let mz_sig = reader.read_u16::<LittleEndian>()?;
             if mz_sig != DOS_SIGNATURE {
                 return Err(Error::InvalidSignature);
-            } else {
-                // Seek to the lfanew field
-                reader.seek(SeekFrom::Start(0x3C))?;
+            }
+
+            // Seek to the lfanew field
+            if file_len < LFANEW_OFFSET + 4 {
+                return Err(Error::InvalidPeHeader("The file is too small to contain a PE header."));
+            }
+            reader.seek(SeekFrom::Start(LFANEW_OFFSET))?;
 
-                // Read the lfanew offset
-                let lfanew = reader.read_u32::<LittleEndian>()?;
+            // Read the lfanew offset, and make sure there's room for the signature and COFF header
+            let lfanew = reader.read_u32::<LittleEndian>()? as u64;
+            if lfanew + 4 + CoffHeader::SIZE as u64 > file_len {
+                return Err(Error::InvalidPeHeader("The PE header offset is past the end of the file."));
+            }
 
-                // Seek to the PE header
-                reader.seek(SeekFrom::Start(lfanew as u64))?;
+            // Seek to the PE header
+            reader.seek(SeekFrom::Start(lfanew))?;
 
-                // Read the PE signature
-                let pe_sig = reader.read_u32::<LittleEndian>()?;
+            // Read the PE signature
+            let pe_sig = reader.read_u32::<LittleEndian>()?;
 
-                // Read the COFF header
-                let coff_header = CoffHeader::read(&mut reader)?;
+            // Read the COFF header
+            let coff_header = CoffHeader::read(&mut reader)?;
+            let optional_header_start = reader.position();
 
-                // Read the PE header if there is one
-                let pe_header = if pe_sig != PE_SIGNATURE {
-                    None
-                } else {
-                    Some(PeHeader::read(&mut reader)?)
-                };
+            // Read the PE header if there is one
+            let pe_header = if pe_sig != PE_SIGNATURE {
+                None
+            } else {
+                Some(PeHeader::read(&mut reader)?)
+            };
 
-                // Read section headers
-                let section_count = coff_header.number_of_sections as usize;
-                let mut sections = Vec::with_capacity(section_count);
-                for _ in 0..section_count {
-                    sections.push(SectionHeader::read(&mut reader)?);
-                }
+            // The section table follows the optional header, which may not be the size we expect
+            let section_table_start = optional_header_start + coff_header.optional_header_size as u64;
+            let section_count = coff_header.number_of_sections as usize;
+            if section_table_start + (section_count * SectionHeader::SIZE) as u64 > file_len {
+                return Err(Error::InvalidPeHeader("The section table extends past the end of the file."));
+            }
+            reader.seek(SeekFrom::Start(section_table_start))?;
 
-                (coff_header, pe_header, sections)
+            // Read section headers
+            let mut sections = Vec::with_capacity(section_count);
+            for _ in 0..section_count {
+                sections.push(SectionHeader::read(&mut reader)?);
             }
+
+            (coff_header, pe_header, sections)
         };
 
         Ok(PeImage {
@@ -90,11 +109,168 @@ impl<D: Deref<Target = [u8]>> PeImage<D> {
         &self.data
     }
 
-    fn map_rva(&self, rva: usize) -> Option<(usize, usize)> {
-        self.sections.iter().find(|x| x.contains_rva(rva as u32)).map(|x| {
-            let offset = rva - x.virtual_address as usize;
-            (x.pointer_to_raw_data as usize + offset, x.size_of_raw_data as usize - offset)
-        })
+    /// Reads `len` bytes of the image, starting at the specified RVA (relative virtual address).
+    ///
+    /// The range must be entirely within one section. Sections can be larger in memory than on disk, in which case
+    /// the rest of the section is filled with zeros, so data is only copied if the range includes part of that
+    /// zero-filled tail. Such ranges are limited to a few kilobytes; larger data should be read with `read_raw`.
+    pub fn read_rva(&self, rva: u32, len: usize) -> Result<Cow<'_, [u8]>, Error> {
+        let section = self.sections
+            .iter()
+            .find(|x| x.contains_rva(rva))
+            .ok_or(Error::SectionNotFound)?;
+        let offset = (rva - section.virtual_address) as usize;
+        let end = offset.checked_add(len).ok_or(Error::RvaOutOfRange)?;
+        if end > section.virtual_size as usize {
+            return Err(Error::RvaOutOfRange);
+        }
+
+        let raw = self.raw_data(section).ok_or(Error::RvaOutOfRange)?;
+        if end <= raw.len() {
+            Ok(Cow::Borrowed(&raw[offset..end]))
+        } else if len > MAX_ZERO_FILL {
+            Err(Error::RvaOutOfRange)
+        } else {
+            let mut buf = vec![0; len];
+            if offset < raw.len() {
+                buf[..(raw.len() - offset)].copy_from_slice(&raw[offset..]);
+            }
+            Ok(Cow::Owned(buf))
+        }
+    }
+
+    /// Reads a range of RVAs that must be entirely within the raw data of one section.
+    ///
+    /// Unlike `read_rva`, nothing is zero-filled, so the amount of data returned is bounded by the size of the file.
+    /// This is used for tables whose size comes from the image, so a bogus size can't cause a huge allocation.
+    pub fn read_raw(&self, range: MemoryRange) -> Result<&[u8], Error> {
+        let range = self.map_range(range).ok_or(Error::RvaOutOfRange)?;
+        Ok(&self.data[range])
+    }
+
+    /// Gets the range of the specified data directory, or `None` if the image doesn't have one.
+    pub fn directory(&self, directory_type: DirectoryType) -> Option<MemoryRange> {
+        self.pe_header()?
+            .directories()
+            .iter()
+            .find(|d| d.directory_type == directory_type && d.range.start != 0 && d.range.len != 0)
+            .map(|d| d.range)
+    }
+
+    /// Reads a nul-terminated string starting at the specified RVA, such as an imported DLL or function name.
+    ///
+    /// The string must be entirely within one section, though it may end in the section's zero-filled tail.
+    pub fn read_rva_string(&self, rva: u32) -> Result<String, Error> {
+        let section = self.sections
+            .iter()
+            .find(|x| x.contains_rva(rva))
+            .ok_or(Error::SectionNotFound)?;
+        let offset = (rva - section.virtual_address) as usize;
+        let raw = self.raw_data(section).ok_or(Error::RvaOutOfRange)?;
+        let bytes = raw.get(offset..).unwrap_or(&[]);
+        let bytes = match bytes.iter().position(|&b| b == 0) {
+            Some(len) => &bytes[..len],
+            None if raw.len() < section.virtual_size as usize => bytes,
+            None => return Err(Error::RvaOutOfRange),
+        };
+        Ok(String::from_utf8(bytes.to_vec())?)
+    }
+
+    /// Reads the import table, or returns an empty list if the image doesn't import anything.
+    pub fn imports(&self) -> Result<Vec<ImportDescriptor>, Error> {
+        match self.directory(DirectoryType::ImportTable) {
+            Some(range) => ImportDescriptor::read_table(self, range),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Reads the export table, if the image has one.
+    pub fn exports(&self) -> Result<Option<ExportTable>, Error> {
+        match self.directory(DirectoryType::ExportTable) {
+            Some(range) => Ok(Some(ExportTable::read(self, range)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn base_relocations(&self) -> Result<Vec<RelocationBlock>, Error> {
+        match self.directory(DirectoryType::BaseRelocationTable) {
+            Some(range) => RelocationBlock::read_table(self, range),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn debug_directory(&self) -> Result<Vec<DebugDirectoryEntry>, Error> {
+        match self.directory(DirectoryType::DebugData) {
+            Some(range) => DebugDirectoryEntry::read_table(self, range),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Reads the first CodeView debug entry, which identifies the PDB for the image, if there is one.
+    pub fn codeview(&self) -> Result<Option<CodeViewData>, Error> {
+        for entry in self.debug_directory()? {
+            if entry.debug_type == DebugType::CODEVIEW {
+                return Ok(Some(CodeViewData::read(entry.data(self)?)?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the certificate table, which holds the Authenticode signatures of the image, or returns an empty list
+    /// if the image isn't signed.
+    pub fn certificates(&self) -> Result<Vec<Certificate>, Error> {
+        match self.directory(DirectoryType::CertificateTable) {
+            Some(range) => Certificate::read_table(self, range),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Reads every resource in the resource directory, or returns an empty list if the image has no resources.
+    pub fn resources(&self) -> Result<Vec<Resource>, Error> {
+        match self.directory(DirectoryType::ResourceTable) {
+            Some(range) => Resource::read_table(self, range),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Reads the first version resource, if the image has one.
+    pub fn version_info(&self) -> Result<Option<VersionInfo>, Error> {
+        match self.resources()?.iter().find(|r| r.resource_type == ResourceId::VERSION) {
+            Some(resource) => Ok(Some(VersionInfo::read(resource.data(self)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the contents of the first application manifest resource, if the image has one.
+    pub fn manifest(&self) -> Result<Option<&[u8]>, Error> {
+        match self.resources()?.iter().find(|r| r.resource_type == ResourceId::MANIFEST) {
+            Some(resource) => Ok(Some(resource.data(self)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Maps a range of RVAs to the range of the image data that contains it.
+    ///
+    /// Returns `None` if the range isn't entirely within the raw data of a single section.
+    pub fn map_range(&self, range: MemoryRange) -> Option<Range<usize>> {
+        let section = self.sections.iter().find(|x| x.contains_rva(range.start))?;
+        let raw = self.raw_data(section)?;
+        let offset = (range.start - section.virtual_address) as usize;
+        let end = offset.checked_add(range.len as usize)?;
+        if end > raw.len() {
+            None
+        } else {
+            let start = section.pointer_to_raw_data as usize + offset;
+            Some(start..(start + range.len as usize))
+        }
+    }
+
+    /// Gets the data of a section that is present in the file, or `None` if it extends past the end of the file.
+    fn raw_data(&self, section: &SectionHeader) -> Option<&[u8]> {
+        // Raw data is padded to the file alignment, so it can be larger than the section itself.
+        let start = section.pointer_to_raw_data as usize;
+        let len = ::std::cmp::min(section.size_of_raw_data, section.virtual_size) as usize;
+        self.data.get(start..start.checked_add(len)?)
     }
 }
 
@@ -109,22 +285,95 @@ impl PeImage<Vec<u8>> {
     }
 }
 
-impl<D: Deref<Target=[u8]>> Index<usize> for PeImage<D> {
-    type Output = u8;
-    fn index(&self, index: usize) -> &u8 {
-        let (phys, _) = self.map_rva(index).expect("RVA out of range");
-        &self.data()[phys]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pe::test_image::{build_image, put_u16, put_u32, set_virtual_size};
+
+    /// Builds a PE32 image with a single section, which is 0x20 bytes in memory but only has 0x10 bytes of raw data.
+    fn build_test_image() -> Vec<u8> {
+        let data: Vec<u8> = (1..0x11).collect();
+        let mut image = build_image(false, &[], &data);
+        set_virtual_size(&mut image, false, 0x20);
+        image
     }
-}
 
-impl<D: Deref<Target=[u8]>> Index<Range<usize>> for PeImage<D> {
-    type Output = [u8];
-    fn index(&self, index: Range<usize>) -> &[u8] {
-        let requested_size = index.end - index.start;
-        let (phys, size) = self.map_rva(index.start).expect("RVA out of range");
-        if size < requested_size {
-            panic!("RVA range is larger than physical range");
-        }
-        &self.data()[phys..(phys + requested_size)]
+    #[test]
+    pub fn read_rva_zero_fills_section_tail() {
+        let image = PeImage::load(build_test_image()).unwrap();
+
+        let data = image.read_rva(0x1004, 4).unwrap();
+        assert!(matches!(data, Cow::Borrowed(_)));
+        assert_eq!(&[5, 6, 7, 8], &data[..]);
+
+        let data = image.read_rva(0x100E, 4).unwrap();
+        assert_eq!(&[15, 16, 0, 0], &data[..]);
+        assert_eq!(&[0, 0], &image.read_rva(0x101E, 2).unwrap()[..]);
+
+        assert_eq!(Some(0x204..0x208), image.map_range(MemoryRange::new(0x1004, 4)));
+        assert_eq!(None, image.map_range(MemoryRange::new(0x100E, 4)));
+        assert_eq!(Ok(&[5, 6, 7, 8][..]), image.read_raw(MemoryRange::new(0x1004, 4)));
+        assert_eq!(Err(Error::RvaOutOfRange), image.read_raw(MemoryRange::new(0x100E, 4)));
+    }
+
+    #[test]
+    pub fn read_rva_out_of_range() {
+        let image = PeImage::load(build_test_image()).unwrap();
+        assert_eq!(Err(Error::SectionNotFound), image.read_rva(0x0FFF, 1));
+        assert_eq!(Err(Error::SectionNotFound), image.read_rva(0x1020, 1));
+        assert_eq!(Err(Error::RvaOutOfRange), image.read_rva(0x101E, 4));
+        assert_eq!(Err(Error::RvaOutOfRange), image.read_rva(0x1000, usize::MAX));
+
+        // The section's raw data is missing from the file
+        let mut data = build_test_image();
+        data.truncate(0x208);
+        let image = PeImage::load(data).unwrap();
+        assert_eq!(Err(Error::RvaOutOfRange), image.read_rva(0x1000, 1));
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn read_rva_limits_zero_fill() {
+        let mut data = build_test_image();
+        set_virtual_size(&mut data, false, 0xF000_0000);
+        let image = PeImage::load(data).unwrap();
+        assert_eq!(MAX_ZERO_FILL, image.read_rva(0x1008, MAX_ZERO_FILL).unwrap().len());
+        assert_eq!(Err(Error::RvaOutOfRange), image.read_rva(0x1008, MAX_ZERO_FILL + 1));
+        assert_eq!(Err(Error::RvaOutOfRange), image.read_rva(0x1000, 0xE000_0000));
+    }
+
+    #[test]
+    pub fn load_validates_headers_against_file_length() {
+        let mut data = build_test_image();
+        put_u32(&mut data, 0x3C, 0xFFFF_FFF0);
+        assert_eq!(
+            Some(Error::InvalidPeHeader("The PE header offset is past the end of the file.")),
+            PeImage::load(data).err()
+        );
+
+        let mut data = build_test_image();
+        put_u16(&mut data, 0x46, 0xFFFF);
+        assert_eq!(
+            Some(Error::InvalidPeHeader("The section table extends past the end of the file.")),
+            PeImage::load(data).err()
+        );
+        assert_eq!(
+            Some(Error::InvalidPeHeader("The file is too small to contain a PE header.")),
+            PeImage::load(&b"MZ\0\0"[..]).err()
+        );
+    }
+
+    #[test]
+    pub fn read_rva_string() {
+        let mut data = build_test_image();
+        data[0x205] = 0;
+        let image = PeImage::load(data).unwrap();
+        assert_eq!("\u{2}\u{3}\u{4}\u{5}", image.read_rva_string(0x1001).unwrap());
+
+        // Strings can run into the zero-filled tail of the section
+        assert_eq!("\u{F}\u{10}", image.read_rva_string(0x100E).unwrap());
+        assert_eq!("", image.read_rva_string(0x1018).unwrap());
+        assert_eq!(Err(Error::SectionNotFound), image.read_rva_string(0x1020));
+        assert!(image.directory(DirectoryType::ImportTable).is_none());
+    }
+}
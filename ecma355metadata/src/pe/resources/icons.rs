@@ -0,0 +1,124 @@
+use std::ops::Deref;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use pe::PeImage;
+use pe::resources::{invalid, Resource, ResourceId};
+
+/// An entry in an icon group, describing one of the images of the icon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconGroupEntry {
+    pub width: u8,
+    pub height: u8,
+    pub color_count: u8,
+    pub planes: u16,
+    pub bit_count: u16,
+    pub size: u32,
+
+    /// The name of the `RT_ICON` resource that holds the image.
+    pub id: u16,
+}
+
+/// An icon group resource (`GRPICONDIR`), which lists the images that make up an icon.
+///
+/// The images themselves are stored as separate `RT_ICON` resources, without the header of a `.ico` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconGroup {
+    pub entries: Vec<IconGroupEntry>,
+}
+
+impl IconGroup {
+    const ICON_TYPE: u16 = 1;
+    const HEADER_SIZE: usize = 6;
+    const ICO_ENTRY_SIZE: usize = 16;
+
+    /// Reads an icon group from the contents of an `RT_GROUP_ICON` resource.
+    pub fn read(data: &[u8]) -> Result<IconGroup, Error> {
+        let mut buf = data;
+        let _reserved = buf.read_u16::<LittleEndian>()?;
+        if buf.read_u16::<LittleEndian>()? != IconGroup::ICON_TYPE {
+            return Err(invalid("An icon group resource does not contain icons."));
+        }
+        let count = buf.read_u16::<LittleEndian>()?;
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let width = buf.read_u8()?;
+            let height = buf.read_u8()?;
+            let color_count = buf.read_u8()?;
+            let _reserved = buf.read_u8()?;
+            entries.push(IconGroupEntry {
+                width,
+                height,
+                color_count,
+                planes: buf.read_u16::<LittleEndian>()?,
+                bit_count: buf.read_u16::<LittleEndian>()?,
+                size: buf.read_u32::<LittleEndian>()?,
+                id: buf.read_u16::<LittleEndian>()?,
+            });
+        }
+        Ok(IconGroup { entries })
+    }
+
+    /// Builds a `.ico` file from the images of the icon, which are looked up in the resources of the image.
+    pub fn to_ico<D: Deref<Target = [u8]>>(&self, pe: &PeImage<D>, resources: &[Resource]) -> Result<Vec<u8>, Error> {
+        let mut images = Vec::new();
+        for entry in self.entries.iter() {
+            let icon = resources
+                .iter()
+                .find(|r| r.resource_type == ResourceId::ICON && r.name == ResourceId::Id(entry.id))
+                .ok_or(invalid("An icon group refers to an icon that does not exist."))?;
+            images.push(icon.data(pe)?);
+        }
+
+        let mut ico = Vec::new();
+        ico.extend_from_slice(&0u16.to_le_bytes());
+        ico.extend_from_slice(&IconGroup::ICON_TYPE.to_le_bytes());
+        ico.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+
+        // The .ico entries have the same layout as the group's, except that the ID is replaced by a file offset
+        let mut offset = IconGroup::HEADER_SIZE + self.entries.len() * IconGroup::ICO_ENTRY_SIZE;
+        for (entry, image) in self.entries.iter().zip(images.iter()) {
+            ico.extend_from_slice(&[entry.width, entry.height, entry.color_count, 0]);
+            ico.extend_from_slice(&entry.planes.to_le_bytes());
+            ico.extend_from_slice(&entry.bit_count.to_le_bytes());
+            ico.extend_from_slice(&(image.len() as u32).to_le_bytes());
+            ico.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += image.len();
+        }
+        for image in images {
+            ico.extend_from_slice(image);
+        }
+        Ok(ico)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn read_icon_group() {
+        let data = [
+            0, 0, 1, 0, 2, 0,
+            16, 16, 0, 0, 1, 0, 32, 0, 0x68, 0x04, 0, 0, 1, 0,
+            0, 0, 0, 0, 1, 0, 32, 0, 0x10, 0x27, 0, 0, 2, 0,
+        ];
+        let group = IconGroup::read(&data).unwrap();
+        assert_eq!(
+            vec![
+                IconGroupEntry { width: 16, height: 16, color_count: 0, planes: 1, bit_count: 32, size: 0x468, id: 1 },
+                IconGroupEntry { width: 0, height: 0, color_count: 0, planes: 1, bit_count: 32, size: 0x2710, id: 2 },
+            ],
+            group.entries
+        );
+
+        // Cursor groups have a different entry layout
+        assert_eq!(
+            Err(Error::InvalidDirectory("An icon group resource does not contain icons.")),
+            IconGroup::read(&[0, 0, 2, 0, 0, 0])
+        );
+        assert!(IconGroup::read(&data[..20]).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use std::fmt;
+use std::ops::Deref;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use pe::{MemoryRange, PeImage};
+
+mod icons;
+mod version_info;
+
+pub use self::icons::{IconGroup, IconGroupEntry};
+pub use self::version_info::{FileVersion, FixedFileInfo, StringTable, VersionInfo};
+
+/// Identifies a resource type or resource, either by a numeric ID or by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceId {
+    Id(u16),
+    Name(String),
+}
+
+impl ResourceId {
+    pub const CURSOR: ResourceId = ResourceId::Id(1);
+    pub const BITMAP: ResourceId = ResourceId::Id(2);
+    pub const ICON: ResourceId = ResourceId::Id(3);
+    pub const MENU: ResourceId = ResourceId::Id(4);
+    pub const DIALOG: ResourceId = ResourceId::Id(5);
+    pub const STRING: ResourceId = ResourceId::Id(6);
+    pub const GROUP_CURSOR: ResourceId = ResourceId::Id(12);
+    pub const GROUP_ICON: ResourceId = ResourceId::Id(14);
+    pub const VERSION: ResourceId = ResourceId::Id(16);
+    pub const MANIFEST: ResourceId = ResourceId::Id(24);
+}
+
+impl fmt::Display for ResourceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ResourceId::Id(id) => write!(f, "#{}", id),
+            ResourceId::Name(ref name) => f.write_str(name),
+        }
+    }
+}
+
+/// A single resource, identified by its type, name and language, which are the three levels of the resource tree.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Resource {
+    pub resource_type: ResourceId,
+    pub name: ResourceId,
+    pub language: u16,
+    pub code_page: u32,
+    pub range: MemoryRange,
+}
+
+impl Resource {
+    const DIRECTORY_SIZE: usize = 16;
+    const ENTRY_SIZE: usize = 8;
+    const DATA_ENTRY_SIZE: usize = 16;
+    const SUBDIRECTORY_FLAG: u32 = 0x8000_0000;
+
+    /// Reads every resource in the resource directory in the specified range.
+    pub fn read_table<D: Deref<Target = [u8]>>(pe: &PeImage<D>, range: MemoryRange) -> Result<Vec<Resource>, Error> {
+        let mut reader = TreeReader {
+            directory: pe.read_raw(range)?,
+            // Entries in a valid tree don't overlap, which bounds the work done for trees that reuse subdirectories
+            remaining_entries: range.len as usize / Resource::ENTRY_SIZE,
+        };
+
+        let mut resources = Vec::new();
+        for (resource_type, types) in reader.read_directory(0)? {
+            for (name, names) in reader.read_subdirectory(types)? {
+                for (language, data) in reader.read_subdirectory(names)? {
+                    let language = match language {
+                        ResourceId::Id(language) => language,
+                        ResourceId::Name(_) => return Err(invalid("A resource language must be a numeric ID.")),
+                    };
+                    let (range, code_page) = reader.read_data_entry(data)?;
+                    resources.push(Resource {
+                        resource_type: resource_type.clone(),
+                        name: name.clone(),
+                        language,
+                        code_page,
+                        range,
+                    });
+                }
+            }
+        }
+        Ok(resources)
+    }
+
+    /// Gets the contents of the resource.
+    pub fn data<'a, D: Deref<Target = [u8]>>(&self, pe: &'a PeImage<D>) -> Result<&'a [u8], Error> {
+        pe.read_raw(self.range)
+    }
+}
+
+struct TreeReader<'a> {
+    directory: &'a [u8],
+    remaining_entries: usize,
+}
+
+impl<'a> TreeReader<'a> {
+    /// Reads the entries of the directory referred to by a directory entry, as IDs and the offsets they refer to.
+    fn read_subdirectory(&mut self, target: u32) -> Result<Vec<(ResourceId, u32)>, Error> {
+        if target & Resource::SUBDIRECTORY_FLAG == 0 {
+            return Err(invalid("A resource directory entry refers to data instead of a subdirectory."));
+        }
+        self.read_directory(target & !Resource::SUBDIRECTORY_FLAG)
+    }
+
+    fn read_directory(&mut self, offset: u32) -> Result<Vec<(ResourceId, u32)>, Error> {
+        let mut buf = self.slice(offset, Resource::DIRECTORY_SIZE)?;
+
+        // Skip the characteristics, timestamp and version, which are always zero
+        buf = &buf[12..];
+        let count = buf.read_u16::<LittleEndian>()? as usize + buf.read_u16::<LittleEndian>()? as usize;
+        self.remaining_entries = self.remaining_entries
+            .checked_sub(count)
+            .ok_or(invalid("The resource directory contains more entries than fit in it."))?;
+
+        let start = offset + Resource::DIRECTORY_SIZE as u32;
+        let mut buf = self.slice(start, count * Resource::ENTRY_SIZE)?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let name = buf.read_u32::<LittleEndian>()?;
+            let target = buf.read_u32::<LittleEndian>()?;
+            let id = if name & Resource::SUBDIRECTORY_FLAG != 0 {
+                ResourceId::Name(self.read_name(name & !Resource::SUBDIRECTORY_FLAG)?)
+            } else {
+                ResourceId::Id(name as u16)
+            };
+            entries.push((id, target));
+        }
+        Ok(entries)
+    }
+
+    /// Reads a data entry, which holds the RVA and size of a resource, and its code page.
+    fn read_data_entry(&self, offset: u32) -> Result<(MemoryRange, u32), Error> {
+        if offset & Resource::SUBDIRECTORY_FLAG != 0 {
+            return Err(invalid("The resource directory is nested more than three levels deep."));
+        }
+        let mut buf = self.slice(offset, Resource::DATA_ENTRY_SIZE)?;
+        let range = MemoryRange::read(&mut buf)?;
+        let code_page = buf.read_u32::<LittleEndian>()?;
+        Ok((range, code_page))
+    }
+
+    /// Reads a name, which is stored as a length-prefixed UTF-16 string.
+    fn read_name(&self, offset: u32) -> Result<String, Error> {
+        let len = self.slice(offset, 2)?.read_u16::<LittleEndian>()? as usize;
+        let mut buf = self.slice(offset + 2, len * 2)?;
+        let mut units = Vec::with_capacity(len);
+        for _ in 0..len {
+            units.push(buf.read_u16::<LittleEndian>()?);
+        }
+        Ok(String::from_utf16(&units)?)
+    }
+
+    fn slice(&self, offset: u32, len: usize) -> Result<&'a [u8], Error> {
+        let start = offset as usize;
+        start.checked_add(len)
+            .and_then(|end| self.directory.get(start..end))
+            .ok_or(invalid("A resource directory entry refers to data outside the resource directory."))
+    }
+}
+
+fn invalid(message: &'static str) -> Error {
+    Error::InvalidDirectory(message)
+}
+
+/// Reads a UTF-16 string, up to the first nul or the end of the data, and returns the rest of the data.
+fn read_utf16_string(data: &[u8]) -> Result<(String, &[u8]), Error> {
+    let mut units = Vec::new();
+    let mut rest = data;
+    while rest.len() >= 2 {
+        let unit = rest.read_u16::<LittleEndian>()?;
+        if unit == 0 {
+            break;
+        }
+        units.push(unit);
+    }
+    Ok((String::from_utf16(&units)?, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pe::test_image::{build_image, put_u16, put_u32, SECTION_RVA};
+
+    /// Builds a resource directory with a version resource `#1` and a manifest named `APP`, both in language 0x409.
+    fn build_resources() -> Vec<u8> {
+        let mut data = vec![0u8; 0xC0];
+
+        // Root: the version and manifest types
+        put_u16(&mut data, 0x0E, 2);
+        put_u32(&mut data, 0x10, 16);
+        put_u32(&mut data, 0x14, 0x8000_0028);
+        put_u32(&mut data, 0x18, 24);
+        put_u32(&mut data, 0x1C, 0x8000_0040);
+
+        // Version names, then manifest names
+        put_u16(&mut data, 0x28 + 0x0E, 1);
+        put_u32(&mut data, 0x38, 1);
+        put_u32(&mut data, 0x3C, 0x8000_0058);
+        put_u16(&mut data, 0x40 + 0x0C, 1);
+        put_u32(&mut data, 0x50, 0x8000_00A8);
+        put_u32(&mut data, 0x54, 0x8000_0070);
+
+        // Languages
+        put_u16(&mut data, 0x58 + 0x0E, 1);
+        put_u32(&mut data, 0x68, 0x409);
+        put_u32(&mut data, 0x6C, 0x88);
+        put_u16(&mut data, 0x70 + 0x0E, 1);
+        put_u32(&mut data, 0x80, 0x409);
+        put_u32(&mut data, 0x84, 0x98);
+
+        // Data entries, then the manifest's name
+        put_u32(&mut data, 0x88, SECTION_RVA + 0xB0);
+        put_u32(&mut data, 0x8C, 4);
+        put_u32(&mut data, 0x90, 1200);
+        put_u32(&mut data, 0x98, SECTION_RVA + 0xB4);
+        put_u32(&mut data, 0x9C, 10);
+        put_u16(&mut data, 0xA8, 3);
+        data[0xAA..0xB0].copy_from_slice(b"A\0P\0P\0");
+        data[0xB0..0xBE].copy_from_slice(b"\x01\x02\x03\x04<assembly>");
+        data
+    }
+
+    #[test]
+    pub fn read_resource_tree() {
+        let data = build_resources();
+        let image = PeImage::load(build_image(false, &[(2, MemoryRange::new(SECTION_RVA, 0xB0))], &data)).unwrap();
+
+        let resources = image.resources().unwrap();
+        assert_eq!(2, resources.len());
+        assert_eq!(ResourceId::VERSION, resources[0].resource_type);
+        assert_eq!(ResourceId::Id(1), resources[0].name);
+        assert_eq!(0x409, resources[0].language);
+        assert_eq!(1200, resources[0].code_page);
+        assert_eq!(&[1, 2, 3, 4], resources[0].data(&image).unwrap());
+
+        assert_eq!(ResourceId::MANIFEST, resources[1].resource_type);
+        assert_eq!(ResourceId::Name("APP".to_string()), resources[1].name);
+        assert_eq!("APP", format!("{}", resources[1].name));
+        assert_eq!(&b"<assembly>"[..], resources[1].data(&image).unwrap());
+    }
+
+    /// Builds a resource directory with the specified type, name and contents for each resource, grouped by type.
+    fn build_tree(resources: &[(u16, u16, &[u8])]) -> (Vec<u8>, u32) {
+        let mut types: Vec<u16> = resources.iter().map(|r| r.0).collect();
+        types.dedup();
+        let type_dirs = 16 + types.len() * 8;
+        let name_dirs = type_dirs + types.len() * 16 + resources.len() * 8;
+        let data_entries = name_dirs + resources.len() * 24;
+        let dir_len = data_entries + resources.len() * 16;
+        let mut data = vec![0u8; dir_len];
+
+        put_u16(&mut data, 0x0E, types.len() as u16);
+        let mut type_dir = type_dirs;
+        for (t, &resource_type) in types.iter().enumerate() {
+            put_u32(&mut data, 16 + t * 8, resource_type as u32);
+            put_u32(&mut data, 16 + t * 8 + 4, 0x8000_0000 | type_dir as u32);
+            let names: Vec<_> = resources.iter().enumerate().filter(|&(_, r)| r.0 == resource_type).collect();
+            put_u16(&mut data, type_dir + 0x0E, names.len() as u16);
+            for (n, &(idx, resource)) in names.iter().enumerate() {
+                let name_dir = name_dirs + idx * 24;
+                let data_entry = data_entries + idx * 16;
+                put_u32(&mut data, type_dir + 16 + n * 8, resource.1 as u32);
+                put_u32(&mut data, type_dir + 16 + n * 8 + 4, 0x8000_0000 | name_dir as u32);
+                put_u16(&mut data, name_dir + 0x0E, 1);
+                put_u32(&mut data, name_dir + 16, 0x409);
+                put_u32(&mut data, name_dir + 20, data_entry as u32);
+                let rva = SECTION_RVA + data.len() as u32;
+                put_u32(&mut data, data_entry, rva);
+                put_u32(&mut data, data_entry + 4, resource.2.len() as u32);
+                data.extend_from_slice(resource.2);
+            }
+            type_dir += 16 + names.len() * 8;
+        }
+        (data, dir_len as u32)
+    }
+
+    #[test]
+    pub fn read_well_known_resources() {
+        let version = version_info::tests::build_version_info();
+        let group = [0, 0, 1, 0, 2, 0, 16, 16, 0, 0, 1, 0, 32, 0, 3, 0, 0, 0, 7, 0, 32, 32, 0, 0, 1, 0, 32, 0, 2, 0, 0, 0, 8, 0];
+        let (data, len) = build_tree(&[
+            (3, 7, b"abc"),
+            (3, 8, b"de"),
+            (14, 1, &group),
+            (16, 1, &version),
+            (24, 1, b"<assembly />"),
+        ]);
+        let image = PeImage::load(build_image(false, &[(2, MemoryRange::new(SECTION_RVA, len))], &data)).unwrap();
+
+        let info = image.version_info().unwrap().unwrap();
+        assert_eq!("1.2.3.4", format!("{}", info.fixed.as_ref().unwrap().file_version));
+        assert_eq!(Some("Contoso"), info.get("CompanyName"));
+        assert_eq!(Some(&b"<assembly />"[..]), image.manifest().unwrap());
+
+        // The icon file has a header and two entries, followed by the images
+        let resources = image.resources().unwrap();
+        let group = resources.iter().find(|r| r.resource_type == ResourceId::GROUP_ICON).unwrap();
+        let ico = IconGroup::read(group.data(&image).unwrap()).unwrap().to_ico(&image, &resources).unwrap();
+        assert_eq!(&[0, 0, 1, 0, 2, 0], &ico[0..6]);
+        assert_eq!(&[16, 16, 0, 0, 1, 0, 32, 0, 3, 0, 0, 0, 38, 0, 0, 0], &ico[6..22]);
+        assert_eq!(&[32, 32, 0, 0, 1, 0, 32, 0, 2, 0, 0, 0, 41, 0, 0, 0], &ico[22..38]);
+        assert_eq!(b"abcde", &ico[38..]);
+    }
+
+    #[test]
+    pub fn missing_resources() {
+        let image = PeImage::load(build_image(false, &[], &[0u8; 16])).unwrap();
+        assert_eq!(Ok(Vec::new()), image.resources());
+        assert_eq!(Ok(None), image.version_info());
+        assert_eq!(Ok(None), image.manifest());
+
+        // An icon group that refers to an icon that isn't there
+        let group = [0, 0, 1, 0, 1, 0, 16, 16, 0, 0, 1, 0, 32, 0, 3, 0, 0, 0, 9, 0];
+        let (data, len) = build_tree(&[(14, 1, &group)]);
+        let image = PeImage::load(build_image(false, &[(2, MemoryRange::new(SECTION_RVA, len))], &data)).unwrap();
+        assert_eq!(
+            Err(Error::InvalidDirectory("An icon group refers to an icon that does not exist.")),
+            IconGroup::read(&group).unwrap().to_ico(&image, &image.resources().unwrap())
+        );
+    }
+
+    #[test]
+    pub fn reused_subdirectories_are_bounded() {
+        // Every level refers back to a directory with many entries, which would take forever to expand
+        let mut data = vec![0u8; 0x10 + 0x100 * 8];
+        put_u16(&mut data, 0x0E, 0x100);
+        for idx in 0..0x100 {
+            put_u32(&mut data, 0x14 + idx * 8, 0x8000_0000);
+        }
+        let len = data.len() as u32;
+        let image = PeImage::load(build_image(false, &[(2, MemoryRange::new(SECTION_RVA, len))], &data)).unwrap();
+        assert_eq!(
+            Err(Error::InvalidDirectory("The resource directory contains more entries than fit in it.")),
+            image.resources()
+        );
+    }
+}
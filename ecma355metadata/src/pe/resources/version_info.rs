@@ -0,0 +1,338 @@
+use std::cmp;
+use std::fmt;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use error::Error;
+use pe::resources::{invalid, read_utf16_string};
+
+/// A four-part version number, as stored in the fixed file info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FileVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub build: u16,
+    pub revision: u16,
+}
+
+impl FileVersion {
+    pub fn new(major: u16, minor: u16, build: u16, revision: u16) -> FileVersion {
+        FileVersion { major, minor, build, revision }
+    }
+
+    fn read<R: ReadBytesExt>(reader: &mut R) -> Result<FileVersion, Error> {
+        let high = reader.read_u32::<LittleEndian>()?;
+        let low = reader.read_u32::<LittleEndian>()?;
+        Ok(FileVersion::new((high >> 16) as u16, high as u16, (low >> 16) as u16, low as u16))
+    }
+}
+
+impl fmt::Display for FileVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}.{}.{}.{}", self.major, self.minor, self.build, self.revision)
+    }
+}
+
+/// The language-independent part of a version resource (`VS_FIXEDFILEINFO`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedFileInfo {
+    pub struct_version: u32,
+    pub file_version: FileVersion,
+    pub product_version: FileVersion,
+    pub flags_mask: u32,
+    pub flags: u32,
+    pub os: u32,
+    pub file_type: u32,
+    pub file_subtype: u32,
+    pub date: u64,
+}
+
+impl FixedFileInfo {
+    pub const SIGNATURE: u32 = 0xFEEF_04BD;
+    pub const SIZE: usize = 52;
+
+    pub fn read(data: &[u8]) -> Result<FixedFileInfo, Error> {
+        if data.len() < FixedFileInfo::SIZE {
+            return Err(invalid("The fixed file info in a version resource is too short."));
+        }
+        let mut buf = data;
+        if buf.read_u32::<LittleEndian>()? != FixedFileInfo::SIGNATURE {
+            return Err(invalid("The fixed file info in a version resource has an invalid signature."));
+        }
+        let struct_version = buf.read_u32::<LittleEndian>()?;
+        let file_version = FileVersion::read(&mut buf)?;
+        let product_version = FileVersion::read(&mut buf)?;
+        let flags_mask = buf.read_u32::<LittleEndian>()?;
+        let flags = buf.read_u32::<LittleEndian>()?;
+        let os = buf.read_u32::<LittleEndian>()?;
+        let file_type = buf.read_u32::<LittleEndian>()?;
+        let file_subtype = buf.read_u32::<LittleEndian>()?;
+        let date_high = buf.read_u32::<LittleEndian>()? as u64;
+        let date_low = buf.read_u32::<LittleEndian>()? as u64;
+        Ok(FixedFileInfo {
+            struct_version,
+            file_version,
+            product_version,
+            flags_mask,
+            flags,
+            os,
+            file_type,
+            file_subtype,
+            date: (date_high << 32) | date_low,
+        })
+    }
+}
+
+/// The strings of a version resource in a single language, such as `CompanyName` and `ProductVersion`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringTable {
+    pub language: u16,
+    pub code_page: u16,
+    pub strings: Vec<(String, String)>,
+}
+
+impl StringTable {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.strings.iter().find(|&(k, _)| k == key).map(|(_, v)| &v[..])
+    }
+}
+
+/// A decoded version resource (`VS_VERSIONINFO`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub fixed: Option<FixedFileInfo>,
+    pub string_tables: Vec<StringTable>,
+
+    /// The language and code page pairs the file supports, from the `Translation` value.
+    pub translations: Vec<(u16, u16)>,
+}
+
+impl VersionInfo {
+    /// Reads the version info from the contents of a version resource.
+    pub fn read(data: &[u8]) -> Result<VersionInfo, Error> {
+        let (root, _) = Block::read(data)?;
+        if root.key != "VS_VERSION_INFO" {
+            return Err(invalid("A version resource does not start with a VS_VERSION_INFO block."));
+        }
+
+        let fixed = if root.value.is_empty() {
+            None
+        } else {
+            Some(FixedFileInfo::read(root.value)?)
+        };
+
+        let mut string_tables = Vec::new();
+        let mut translations = Vec::new();
+        for child in Block::read_children(root.children)? {
+            match &child.key[..] {
+                "StringFileInfo" => for table in Block::read_children(child.children)? {
+                    string_tables.push(read_string_table(table)?);
+                },
+                "VarFileInfo" => for var in Block::read_children(child.children)? {
+                    if var.key == "Translation" {
+                        let mut buf = var.value;
+                        while buf.len() >= 4 {
+                            translations.push((buf.read_u16::<LittleEndian>()?, buf.read_u16::<LittleEndian>()?));
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        Ok(VersionInfo {
+            fixed,
+            string_tables,
+            translations,
+        })
+    }
+
+    /// Gets a string, such as `FileVersion` or `CompanyName`, from the first string table that has it.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.string_tables.iter().filter_map(|t| t.get(key)).next()
+    }
+}
+
+fn read_string_table(table: Block) -> Result<StringTable, Error> {
+    // The key is the language and code page as eight hex digits, such as "040904B0"
+    let parse = |digits: Option<&str>| {
+        digits
+            .and_then(|d| u16::from_str_radix(d, 16).ok())
+            .ok_or(invalid("A version resource string table has an invalid language and code page."))
+    };
+    let language = parse(table.key.get(0..4))?;
+    let code_page = parse(table.key.get(4..8).filter(|_| table.key.len() == 8))?;
+
+    let mut strings = Vec::new();
+    for string in Block::read_children(table.children)? {
+        let (value, _) = read_utf16_string(string.value)?;
+        strings.push((string.key, value));
+    }
+    Ok(StringTable {
+        language,
+        code_page,
+        strings,
+    })
+}
+
+/// A block of a version resource, which every level of the version resource is made of.
+struct Block<'a> {
+    key: String,
+    value: &'a [u8],
+    children: &'a [u8],
+}
+
+impl<'a> Block<'a> {
+    const HEADER_SIZE: usize = 6;
+
+    /// Reads the block at the start of the data, and returns the data that follows it.
+    fn read(data: &'a [u8]) -> Result<(Block<'a>, &'a [u8]), Error> {
+        let mut buf = data;
+        let len = buf.read_u16::<LittleEndian>()? as usize;
+        let value_len = buf.read_u16::<LittleEndian>()? as usize;
+        let is_text = buf.read_u16::<LittleEndian>()? == 1;
+        if len < Block::HEADER_SIZE || len > data.len() {
+            return Err(invalid("A version resource block has an invalid length."));
+        }
+
+        // The key, value and children are each aligned to four bytes, relative to the block
+        let block = &data[..len];
+        let (key, rest) = read_utf16_string(&block[Block::HEADER_SIZE..])?;
+        let value_start = cmp::min(align(len - rest.len()), len);
+
+        // Text values are measured in characters rather than bytes
+        let value_len = if is_text { value_len * 2 } else { value_len };
+        let value_end = cmp::min(value_start + value_len, len);
+        let children_start = cmp::min(align(value_end), len);
+
+        let next = cmp::min(align(len), data.len());
+        Ok((
+            Block {
+                key,
+                value: &block[value_start..value_end],
+                children: &block[children_start..],
+            },
+            &data[next..],
+        ))
+    }
+
+    fn read_children(mut data: &'a [u8]) -> Result<Vec<Block<'a>>, Error> {
+        let mut children = Vec::new();
+        while data.len() >= Block::HEADER_SIZE {
+            let (child, rest) = Block::read(data)?;
+            children.push(child);
+            data = rest;
+        }
+        Ok(children)
+    }
+}
+
+fn align(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Builds a version block, with the value written as UTF-16 text if it is a string.
+    pub fn block(key: &str, value: &[u8], is_text: bool, children: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0u8; 6];
+        for unit in key.encode_utf16().chain(Some(0)) {
+            data.extend_from_slice(&unit.to_le_bytes());
+        }
+        data.resize(align(data.len()), 0);
+        data.extend_from_slice(value);
+        for child in children {
+            data.resize(align(data.len()), 0);
+            data.extend_from_slice(child);
+        }
+
+        let value_len = if is_text { value.len() / 2 } else { value.len() };
+        let len = data.len() as u16;
+        data[0..2].copy_from_slice(&len.to_le_bytes());
+        data[2..4].copy_from_slice(&(value_len as u16).to_le_bytes());
+        data[4..6].copy_from_slice(&(is_text as u16).to_le_bytes());
+        data
+    }
+
+    pub fn text(value: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        for unit in value.encode_utf16().chain(Some(0)) {
+            data.extend_from_slice(&unit.to_le_bytes());
+        }
+        data
+    }
+
+    /// Builds the version resource of a library with version 1.2.3.4.
+    pub fn build_version_info() -> Vec<u8> {
+        let mut fixed = Vec::new();
+        for val in &[FixedFileInfo::SIGNATURE, 0x10000, 0x10002, 0x30004, 0x10002, 0x30000, 0x3F, 0, 4, 2, 0, 0, 0] {
+            fixed.extend_from_slice(&val.to_le_bytes());
+        }
+        let strings = block(
+            "040904B0",
+            &[],
+            true,
+            &[
+                block("CompanyName", &text("Contoso"), true, &[]),
+                block("FileVersion", &text("1.2.3.4"), true, &[]),
+                block("Comments", &[], true, &[]),
+            ],
+        );
+        let translation = block("Translation", &[0x09, 0x04, 0xB0, 0x04], false, &[]);
+        block(
+            "VS_VERSION_INFO",
+            &fixed,
+            false,
+            &[
+                block("StringFileInfo", &[], true, &[strings]),
+                block("VarFileInfo", &[], true, &[translation]),
+            ],
+        )
+    }
+
+    #[test]
+    pub fn read_version_info() {
+        let info = VersionInfo::read(&build_version_info()).unwrap();
+
+        let fixed = info.fixed.as_ref().unwrap();
+        assert_eq!(FileVersion::new(1, 2, 3, 4), fixed.file_version);
+        assert_eq!(FileVersion::new(1, 2, 3, 0), fixed.product_version);
+        assert_eq!("1.2.3.4", format!("{}", fixed.file_version));
+        assert_eq!(2, fixed.file_type);
+
+        assert_eq!(1, info.string_tables.len());
+        assert_eq!(0x409, info.string_tables[0].language);
+        assert_eq!(0x4B0, info.string_tables[0].code_page);
+        assert_eq!(Some("Contoso"), info.get("CompanyName"));
+        assert_eq!(Some("1.2.3.4"), info.get("FileVersion"));
+        assert_eq!(Some(""), info.get("Comments"));
+        assert_eq!(None, info.get("ProductName"));
+        assert_eq!(vec![(0x409, 0x4B0)], info.translations);
+    }
+
+    #[test]
+    pub fn invalid_version_info() {
+        assert_eq!(
+            Err(Error::InvalidDirectory("A version resource does not start with a VS_VERSION_INFO block.")),
+            VersionInfo::read(&block("VS_VERSION", &[], false, &[]))
+        );
+
+        // A block that claims to be shorter than its header would never advance
+        let mut data = build_version_info();
+        data[0] = 2;
+        data[1] = 0;
+        assert_eq!(
+            Err(Error::InvalidDirectory("A version resource block has an invalid length.")),
+            VersionInfo::read(&data)
+        );
+
+        let mut data = build_version_info();
+        data[40] ^= 0xFF;
+        assert_eq!(
+            Err(Error::InvalidDirectory("The fixed file info in a version resource has an invalid signature.")),
+            VersionInfo::read(&data)
+        );
+    }
+}
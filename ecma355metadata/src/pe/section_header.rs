@@ -24,7 +24,7 @@ impl SectionHeader {
 
     pub fn read<A: Read>(buf: &mut A) -> Result<SectionHeader, Error> {
         let mut name_bytes = [0u8; 8];
-        buf.read(&mut name_bytes)?;
+        buf.read_exact(&mut name_bytes)?;
         let end = match name_bytes.iter().position(|x| *x == 0) {
             Some(x) => x,
             None => 8,
@@ -52,6 +52,7 @@ impl SectionHeader {
     }
 
     pub fn contains_rva(&self, rva: u32) -> bool {
-        rva >= self.virtual_address && rva < self.virtual_end()
+        // Written this way so a corrupt section can't overflow virtual_end
+        rva >= self.virtual_address && rva - self.virtual_address < self.virtual_size
     }
 }
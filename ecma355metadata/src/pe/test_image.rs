@@ -0,0 +1,47 @@
+use pe::MemoryRange;
+
+/// The RVA of the only section in images built by `build_image`.
+pub const SECTION_RVA: u32 = 0x1000;
+
+pub fn put_u16(buf: &mut [u8], offset: usize, val: u16) {
+    buf[offset..(offset + 2)].copy_from_slice(&val.to_le_bytes());
+}
+
+pub fn put_u32(buf: &mut [u8], offset: usize, val: u32) {
+    buf[offset..(offset + 4)].copy_from_slice(&val.to_le_bytes());
+}
+
+/// Sets the size of the section in an image built by `build_image`, so it can have a zero-filled tail in memory.
+pub fn set_virtual_size(image: &mut [u8], pe32plus: bool, size: u32) {
+    let section_header = if pe32plus { 0x58 + 240 } else { 0x58 + 224 };
+    put_u32(image, section_header + 8, size);
+}
+
+/// Builds an image with a single section at `SECTION_RVA` containing `data`, and the specified data directories,
+/// given as their index in the directory table and their range.
+pub fn build_image(pe32plus: bool, directories: &[(usize, MemoryRange)], data: &[u8]) -> Vec<u8> {
+    let (optional_header_size, directory_table) = if pe32plus { (240, 112) } else { (224, 96) };
+
+    let mut image = vec![0u8; 0x200];
+    image[0..2].copy_from_slice(b"MZ");
+    put_u32(&mut image, 0x3C, 0x40);
+    image[0x40..0x44].copy_from_slice(b"PE\0\0");
+    image[0x46] = 1;
+    image[0x54] = optional_header_size as u8;
+    image[0x58] = 0x0B;
+    image[0x59] = if pe32plus { 0x02 } else { 0x01 };
+    put_u32(&mut image, 0x58 + directory_table - 4, 16);
+    for &(index, range) in directories {
+        put_u32(&mut image, 0x58 + directory_table + index * 8, range.start);
+        put_u32(&mut image, 0x58 + directory_table + index * 8 + 4, range.len);
+    }
+
+    let section_header = 0x58 + optional_header_size;
+    image[section_header..(section_header + 5)].copy_from_slice(b".text");
+    put_u32(&mut image, section_header + 8, data.len() as u32);
+    put_u32(&mut image, section_header + 12, SECTION_RVA);
+    put_u32(&mut image, section_header + 16, data.len() as u32);
+    put_u32(&mut image, section_header + 20, 0x200);
+    image.extend_from_slice(data);
+    image
+}
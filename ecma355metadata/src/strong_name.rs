@@ -0,0 +1,488 @@
+use std::ops::{Deref, Range};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use rsa::pkcs8::AssociatedOid;
+use sha1::{Digest, Sha1};
+use sha2::{Sha256, Sha384, Sha512};
+
+use cli::CliFlags;
+use cli::tables::AssemblyDecoder;
+use error::Error;
+use metadata_image::MetadataImage;
+use pe::{CoffHeader, MemoryRange, PeImage, SectionHeader};
+
+/// The placeholder key that framework assemblies are built with. The runtime substitutes the real key, which isn't
+/// in the image, so signatures made with it can't be verified.
+pub const ECMA_KEY: [u8; 16] = [0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0];
+
+pub const CALG_RSA_SIGN: u32 = 0x2400;
+pub const CALG_SHA1: u32 = 0x8004;
+pub const CALG_SHA256: u32 = 0x800C;
+pub const CALG_SHA384: u32 = 0x800D;
+pub const CALG_SHA512: u32 = 0x800E;
+
+/// The result of verifying the strong-name signature of an assembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrongNameStatus {
+    /// The assembly has no public key.
+    NotSigned,
+
+    /// The assembly has a public key and space for a signature, but is not marked as signed.
+    DelaySigned,
+
+    /// The assembly is signed with the ECMA key, so the signature can't be checked.
+    EcmaKey,
+
+    /// The signature matches the contents of the image.
+    Valid,
+
+    /// The image has been modified since it was signed, or was signed with a different key.
+    Invalid,
+}
+
+impl_display_via_debug!(StrongNameStatus);
+
+/// An RSA public key, as stored in the Assembly table.
+///
+/// The blob is a header giving the signature and hash algorithms, followed by a CryptoAPI `PUBLICKEYBLOB`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey {
+    pub signature_algorithm: u32,
+    pub hash_algorithm: u32,
+    pub bit_length: u32,
+    pub exponent: u32,
+
+    /// The modulus, in big-endian order.
+    pub modulus: Vec<u8>,
+}
+
+impl PublicKey {
+    const PUBLICKEYBLOB: u8 = 0x06;
+    const RSA1: u32 = 0x3141_5352;
+
+    pub fn read(blob: &[u8]) -> Result<PublicKey, Error> {
+        // The header and the fixed-size part of the PUBLICKEYBLOB are 32 bytes
+        if blob.len() < 32 {
+            return Err(invalid_key());
+        }
+        let mut buf = blob;
+        let signature_algorithm = buf.read_u32::<LittleEndian>()?;
+        let hash_algorithm = buf.read_u32::<LittleEndian>()?;
+        let key_len = buf.read_u32::<LittleEndian>()? as usize;
+        if key_len != buf.len() {
+            return Err(invalid_key());
+        }
+        if signature_algorithm != CALG_RSA_SIGN {
+            return Err(Error::InvalidStrongName("The public key uses an unsupported signature algorithm."));
+        }
+
+        let blob_type = buf.read_u8()?;
+        let _version = buf.read_u8()?;
+        let _reserved = buf.read_u16::<LittleEndian>()?;
+        let _key_algorithm = buf.read_u32::<LittleEndian>()?;
+        let magic = buf.read_u32::<LittleEndian>()?;
+        if blob_type != PublicKey::PUBLICKEYBLOB || magic != PublicKey::RSA1 {
+            return Err(Error::InvalidStrongName("The public key is not an RSA public key."));
+        }
+        let bit_length = buf.read_u32::<LittleEndian>()?;
+        let exponent = buf.read_u32::<LittleEndian>()?;
+
+        // The modulus is stored little-endian
+        let modulus_len = bit_length as usize / 8;
+        if bit_length % 8 != 0 || modulus_len != buf.len() {
+            return Err(Error::InvalidStrongName("The public key modulus does not match its bit length."));
+        }
+        let mut modulus = buf.to_vec();
+        modulus.reverse();
+
+        Ok(PublicKey {
+            signature_algorithm,
+            hash_algorithm,
+            bit_length,
+            exponent,
+            modulus,
+        })
+    }
+
+    /// Gets the size of a signature made with this key.
+    pub fn signature_len(&self) -> usize {
+        self.modulus.len()
+    }
+}
+
+/// Computes the public key token of a public key blob, which is the last 8 bytes of its SHA-1 hash, reversed.
+pub fn public_key_token(public_key: &[u8]) -> [u8; 8] {
+    let hash = Sha1::digest(public_key);
+    let mut token = [0u8; 8];
+    for (i, b) in hash.iter().rev().take(8).enumerate() {
+        token[i] = *b;
+    }
+    token
+}
+
+/// Verifies the strong-name signature of an assembly against the public key in its Assembly table.
+pub fn verify<D: Deref<Target = [u8]>>(image: &MetadataImage<D>) -> Result<StrongNameStatus, Error> {
+    let assembly = match image.table::<AssemblyDecoder>().iter().next() {
+        Some(assembly) => assembly?,
+        None => return Ok(StrongNameStatus::NotSigned),
+    };
+    let blob = match assembly.public_key.index() {
+        0 => &[][..],
+        index => image.blob_heap().get(index).ok_or(Error::InvalidHeapReference)?,
+    };
+
+    if blob.is_empty() {
+        return Ok(StrongNameStatus::NotSigned);
+    } else if blob == ECMA_KEY {
+        return Ok(StrongNameStatus::EcmaKey);
+    }
+    let public_key = PublicKey::read(blob)?;
+
+    let cli_header = image.cli_header();
+    if !cli_header.flags.contains(CliFlags::STRONGNAMESIGNED) {
+        Ok(StrongNameStatus::DelaySigned)
+    } else if cli_header.strong_name.len == 0 {
+        Err(Error::InvalidStrongName("The image is marked as strong-name signed, but has no signature."))
+    } else if verify_signature(image.pe(), &public_key, cli_header.strong_name)? {
+        Ok(StrongNameStatus::Valid)
+    } else {
+        Ok(StrongNameStatus::Invalid)
+    }
+}
+
+/// Verifies the signature blob at the specified RVA range against the contents of the image.
+///
+/// The hash covers the headers and the raw data of every section, except for the checksum, the certificate table
+/// directory entry and the signature itself, which can all change after signing.
+pub fn verify_signature<D: Deref<Target = [u8]>>(
+    pe: &PeImage<D>,
+    public_key: &PublicKey,
+    signature: MemoryRange,
+) -> Result<bool, Error> {
+    if signature.len as usize != public_key.signature_len() {
+        return Err(Error::InvalidStrongName("The signature is not the same size as the public key."));
+    }
+    let signature = pe.map_range(signature)
+        .ok_or(Error::InvalidStrongName("The signature is not within the raw data of a section."))?;
+
+    match public_key.hash_algorithm {
+        0 | CALG_SHA1 => verify_hash::<Sha1, D>(pe, public_key, signature),
+        CALG_SHA256 => verify_hash::<Sha256, D>(pe, public_key, signature),
+        CALG_SHA384 => verify_hash::<Sha384, D>(pe, public_key, signature),
+        CALG_SHA512 => verify_hash::<Sha512, D>(pe, public_key, signature),
+        _ => Err(Error::InvalidStrongName("The public key uses an unsupported hash algorithm.")),
+    }
+}
+
+fn verify_hash<H: Digest + AssociatedOid, D: Deref<Target = [u8]>>(
+    pe: &PeImage<D>,
+    public_key: &PublicKey,
+    signature: Range<usize>,
+) -> Result<bool, Error> {
+    let key = RsaPublicKey::new(
+        BigUint::from_bytes_be(&public_key.modulus),
+        BigUint::from(public_key.exponent),
+    ).map_err(|_| Error::InvalidStrongName("The public key is not a valid RSA key."))?;
+
+    let hash = hash_image::<H, D>(pe, &signature)?;
+
+    // The signature is stored little-endian, like the modulus
+    let mut signature = pe.data()[signature].to_vec();
+    signature.reverse();
+    Ok(key.verify(Pkcs1v15Sign::new::<H>(), &hash, &signature).is_ok())
+}
+
+fn hash_image<H: Digest, D: Deref<Target = [u8]>>(pe: &PeImage<D>, signature: &Range<usize>) -> Result<Vec<u8>, Error> {
+    let data = pe.data();
+    let pe_header = pe.pe_header().ok_or(Error::NotAPortableExecutable)?;
+
+    // Like the runtime, hash everything before the NT headers, then the NT headers with an optional header of the
+    // standard size, then the section table, which follows the optional header of the size in the COFF header
+    let lfanew = (&data[0x3C..]).read_u32::<LittleEndian>()? as usize;
+    let optional_header = lfanew + 4 + CoffHeader::SIZE;
+    let (optional_header_size, directory_table) = if pe_header.magic.is_pe32plus() { (240, 112) } else { (224, 96) };
+    let mut nt_headers = data.get(lfanew..(optional_header + optional_header_size))
+        .ok_or(Error::InvalidStrongName("The PE headers extend past the end of the file."))?
+        .to_vec();
+
+    // The checksum and the certificate table directory entry are excluded, because they are set after signing
+    let checksum = optional_header - lfanew + 64;
+    let certificate_table = optional_header - lfanew + directory_table + 4 * 8;
+    for range in &[checksum..(checksum + 4), certificate_table..(certificate_table + 8)] {
+        for b in nt_headers[range.clone()].iter_mut() {
+            *b = 0;
+        }
+    }
+
+    // The section table was checked when the image was loaded
+    let section_table = optional_header + pe.coff_header().optional_header_size as usize;
+    let section_table_end = section_table + pe.sections().len() * SectionHeader::SIZE;
+
+    let mut hasher = H::new();
+    hasher.update(&data[..lfanew]);
+    hasher.update(&nt_headers);
+    hasher.update(&data[section_table..section_table_end]);
+    for section in pe.sections() {
+        let start = section.pointer_to_raw_data as usize;
+        let end = start.checked_add(section.size_of_raw_data as usize)
+            .filter(|&end| end <= data.len())
+            .ok_or(Error::InvalidStrongName("A section extends past the end of the file."))?;
+
+        if signature.start >= end || signature.end <= start {
+            hasher.update(&data[start..end]);
+        } else {
+            hasher.update(&data[start..signature.start.max(start)]);
+            hasher.update(&data[signature.end.min(end)..end]);
+        }
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+fn invalid_key() -> Error {
+    Error::InvalidStrongName("The public key blob has an invalid length.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rsa::RsaPrivateKey;
+
+    use cli::tables::TableIndex;
+    use pe::test_image::{build_image, SECTION_RVA};
+    use test_metadata::{build_assembly_image, write_u16, write_u32};
+
+    const MODULUS: &str = "cf8fd481fd71ffa08d0e549c22084da074a0027ca3e3902224c484e5e8c008f859d3e6fcf8e98db792e8465a5d638cfc99a0003ad6b01d059eb87e36446dd4e3";
+    const PRIVATE_EXPONENT: &str = "6206545f5bb9c5f72606d990af45ea9b57d8e5b3c596c85047bb03e4acaa5cd14b63e0a60a9302359158b4b6fc57868eb8a9285c354a7183e4b58226ab2f80f9";
+    const PRIME1: &str = "db61143954cd9391c3d372d19ac9f21b6b28fdc48b4a64587b618ebce8bb673f";
+    const PRIME2: &str = "f235bfb76a1a771eacb22aebdd4da4c95145553eb0c792105936e709393ced5d";
+
+    fn hex(digits: &str) -> Vec<u8> {
+        (0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..(i + 2)], 16).unwrap()).collect()
+    }
+
+    fn private_key() -> RsaPrivateKey {
+        let n = BigUint::from_bytes_be(&hex(MODULUS));
+        let d = BigUint::from_bytes_be(&hex(PRIVATE_EXPONENT));
+        let primes = vec![BigUint::from_bytes_be(&hex(PRIME1)), BigUint::from_bytes_be(&hex(PRIME2))];
+        RsaPrivateKey::from_components(n, BigUint::from(65537u32), d, primes).unwrap()
+    }
+
+    /// Builds the public key blob for the test key, as it would appear in the Assembly table.
+    fn public_key_blob(hash_algorithm: u32) -> Vec<u8> {
+        let mut modulus = hex(MODULUS);
+        modulus.reverse();
+
+        let mut blob = Vec::new();
+        for val in &[CALG_RSA_SIGN, hash_algorithm, 20 + modulus.len() as u32] {
+            blob.extend_from_slice(&val.to_le_bytes());
+        }
+        blob.extend_from_slice(&[PublicKey::PUBLICKEYBLOB, 2, 0, 0]);
+        for val in &[0x2400, PublicKey::RSA1, 512, 65537] {
+            blob.extend_from_slice(&val.to_le_bytes());
+        }
+        blob.extend_from_slice(&modulus);
+        blob
+    }
+
+    const SIGNATURE: MemoryRange = MemoryRange { start: SECTION_RVA + 0x80, len: 64 };
+
+    /// Builds an image with space for a signature, and signs it with the test key.
+    fn build_signed_image(hash_algorithm: u32) -> Vec<u8> {
+        let data: Vec<u8> = (0..0x100).map(|i| i as u8).collect();
+        let mut image = build_image(false, &[], &data);
+        let public_key = PublicKey::read(&public_key_blob(hash_algorithm)).unwrap();
+        sign(&mut image, &public_key, SIGNATURE);
+        image
+    }
+
+    fn sign(image: &mut [u8], public_key: &PublicKey, signature: MemoryRange) {
+        let (range, hash, padding) = {
+            let pe = PeImage::load(&image[..]).unwrap();
+            let range = pe.map_range(signature).unwrap();
+            let (hash, padding) = match public_key.hash_algorithm {
+                CALG_SHA256 => (hash_image::<Sha256, _>(&pe, &range).unwrap(), Pkcs1v15Sign::new::<Sha256>()),
+                _ => (hash_image::<Sha1, _>(&pe, &range).unwrap(), Pkcs1v15Sign::new::<Sha1>()),
+            };
+            (range, hash, padding)
+        };
+        let mut signature = private_key().sign(padding, &hash).unwrap();
+        signature.reverse();
+        image[range].copy_from_slice(&signature);
+    }
+
+    fn verify_image(image: &[u8], hash_algorithm: u32) -> Result<bool, Error> {
+        let public_key = PublicKey::read(&public_key_blob(hash_algorithm)).unwrap();
+        verify_signature(&PeImage::load(image).unwrap(), &public_key, SIGNATURE)
+    }
+
+    #[test]
+    pub fn read_public_key() {
+        let public_key = PublicKey::read(&public_key_blob(CALG_SHA1)).unwrap();
+        assert_eq!(CALG_RSA_SIGN, public_key.signature_algorithm);
+        assert_eq!(CALG_SHA1, public_key.hash_algorithm);
+        assert_eq!(512, public_key.bit_length);
+        assert_eq!(65537, public_key.exponent);
+        assert_eq!(hex(MODULUS), public_key.modulus);
+
+        let blob = public_key_blob(CALG_SHA1);
+        assert_eq!(Err(invalid_key()), PublicKey::read(&blob[..(blob.len() - 1)]));
+        assert_eq!(Err(invalid_key()), PublicKey::read(&ECMA_KEY));
+
+        let mut blob = public_key_blob(CALG_SHA1);
+        blob[12] = 0x07;
+        assert_eq!(Err(Error::InvalidStrongName("The public key is not an RSA public key.")), PublicKey::read(&blob));
+
+        // CALG_DSS_SIGN
+        let mut blob = public_key_blob(CALG_SHA1);
+        blob[1] = 0x22;
+        assert_eq!(
+            Err(Error::InvalidStrongName("The public key uses an unsupported signature algorithm.")),
+            PublicKey::read(&blob)
+        );
+    }
+
+    /// The key that .NET Framework assemblies such as `System.Web` are signed with, whose token is published.
+    const MICROSOFT_KEY: &str = "002400000480000094000000060200000024000052534131000400000100010007d1fa57c4aed9f0a32e84aa0faefd0de9e8fd6aec8f87fb03766c834c99921eb23be79ad9d5dcc1dd9ad236132102900b723cf980957fc4e177108fc607774f29e8320e92ea05ece4e821c0a5efe8f1645c4c0c93c1ab99285d622caa652c1dfad63d745d6f2de5f17e5eaf0fc4963d261c8a12436518206dc093344d5ad293";
+
+    #[test]
+    pub fn compute_public_key_token() {
+        assert_eq!([0xB7, 0x7A, 0x5C, 0x56, 0x19, 0x34, 0xE0, 0x89], public_key_token(&ECMA_KEY));
+        assert_eq!([0xB0, 0x3F, 0x5F, 0x7F, 0x11, 0xD5, 0x0A, 0x3A], public_key_token(&hex(MICROSOFT_KEY)));
+    }
+
+    #[test]
+    pub fn read_published_public_key() {
+        let public_key = PublicKey::read(&hex(MICROSOFT_KEY)).unwrap();
+        assert_eq!(CALG_SHA1, public_key.hash_algorithm);
+        assert_eq!(1024, public_key.bit_length);
+        assert_eq!(65537, public_key.exponent);
+        assert_eq!(128, public_key.signature_len());
+
+        // The modulus is reversed into big-endian order
+        assert_eq!(0x93, public_key.modulus[0]);
+        assert_eq!(0x07, public_key.modulus[127]);
+    }
+
+    #[test]
+    pub fn verify_signed_image() {
+        for &hash_algorithm in &[CALG_SHA1, CALG_SHA256] {
+            let image = build_signed_image(hash_algorithm);
+            assert_eq!(Ok(true), verify_image(&image, hash_algorithm));
+
+            // The signature only matches the hash algorithm it was made with
+            let other = if hash_algorithm == CALG_SHA1 { CALG_SHA256 } else { CALG_SHA1 };
+            assert_eq!(Ok(false), verify_image(&image, other));
+        }
+    }
+
+    #[test]
+    pub fn verify_ignores_excluded_fields() {
+        let mut image = build_signed_image(CALG_SHA1);
+
+        // The checksum and the certificate table are updated after the assembly is strong-name signed
+        let optional_header = 0x40 + 4 + 20;
+        image[optional_header + 64] = 0xAB;
+        image[optional_header + 96 + 32] = 0x10;
+        image[optional_header + 96 + 36] = 0x08;
+        image.extend_from_slice(&[0x10, 0, 0, 0, 0, 2, 2, 0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(Ok(true), verify_image(&image, CALG_SHA1));
+    }
+
+    #[test]
+    pub fn verify_tampered_image() {
+        let mut image = build_signed_image(CALG_SHA1);
+        image[0x210] ^= 1;
+        assert_eq!(Ok(false), verify_image(&image, CALG_SHA1));
+
+        let mut image = build_signed_image(CALG_SHA1);
+        image[0x44] ^= 1;
+        assert_eq!(Ok(false), verify_image(&image, CALG_SHA1));
+
+        // The signature itself isn't hashed, but changing it still breaks it
+        let mut image = build_signed_image(CALG_SHA1);
+        image[0x2A0] ^= 1;
+        assert_eq!(Ok(false), verify_image(&image, CALG_SHA1));
+    }
+
+    #[test]
+    pub fn verify_malformed_signature() {
+        let image = build_signed_image(CALG_SHA1);
+        let pe = PeImage::load(&image[..]).unwrap();
+        let public_key = PublicKey::read(&public_key_blob(CALG_SHA1)).unwrap();
+        assert_eq!(
+            Err(Error::InvalidStrongName("The signature is not the same size as the public key.")),
+            verify_signature(&pe, &public_key, MemoryRange::new(SIGNATURE.start, 32))
+        );
+        assert_eq!(
+            Err(Error::InvalidStrongName("The signature is not within the raw data of a section.")),
+            verify_signature(&pe, &public_key, MemoryRange::new(SECTION_RVA + 0xF0, 64))
+        );
+        assert_eq!(
+            Err(Error::InvalidStrongName("The public key uses an unsupported hash algorithm.")),
+            verify_signature(&pe, &PublicKey::read(&public_key_blob(0x8003)).unwrap(), SIGNATURE)
+        );
+    }
+
+    const NAME: u16 = 1;
+
+    /// Builds an assembly image with the specified public key, which is signed with the test key if `flags` includes
+    /// `STRONGNAMESIGNED` and there is space for the signature.
+    fn build_assembly(public_key: &[u8], flags: CliFlags, signature_len: u32) -> Vec<u8> {
+        let mut blobs = vec![0];
+        if !public_key.is_empty() {
+            blobs.push(public_key.len() as u8);
+            blobs.extend_from_slice(public_key);
+        }
+
+        // The Assembly table, with the PublicKey flag set if there is a public key
+        let mut row = Vec::new();
+        write_u32(&mut row, CALG_SHA1);
+        for &val in &[1, 0, 0, 0] {
+            write_u16(&mut row, val);
+        }
+        write_u32(&mut row, !public_key.is_empty() as u32);
+        for &val in &[!public_key.is_empty() as u16, NAME, 0] {
+            write_u16(&mut row, val);
+        }
+
+        let mut image = build_assembly_image(&[(TableIndex::Assembly, 1, row)], b"\0Test\0", &blobs, flags, signature_len);
+        if flags.contains(CliFlags::STRONGNAMESIGNED) && signature_len != 0 {
+            let signature = MetadataImage::load_data(&image[..]).unwrap().cli_header().strong_name;
+            sign(&mut image, &PublicKey::read(&public_key_blob(CALG_SHA1)).unwrap(), signature);
+        }
+        image
+    }
+
+    fn verify_assembly(image: &[u8]) -> Result<StrongNameStatus, Error> {
+        verify(&MetadataImage::load_data(image).unwrap())
+    }
+
+    #[test]
+    pub fn verify_assembly_status() {
+        let key = public_key_blob(CALG_SHA1);
+        let signed = CliFlags::ILONLY | CliFlags::STRONGNAMESIGNED;
+
+        assert_eq!(Ok(StrongNameStatus::NotSigned), verify_assembly(&build_assembly(&[], CliFlags::ILONLY, 0)));
+        assert_eq!(Ok(StrongNameStatus::DelaySigned), verify_assembly(&build_assembly(&key, CliFlags::ILONLY, 64)));
+        assert_eq!(Ok(StrongNameStatus::EcmaKey), verify_assembly(&build_assembly(&ECMA_KEY, signed, 0)));
+        assert_eq!(Ok(StrongNameStatus::Valid), verify_assembly(&build_assembly(&key, signed, 64)));
+        assert_eq!(
+            Err(Error::InvalidStrongName("The image is marked as strong-name signed, but has no signature.")),
+            verify_assembly(&build_assembly(&key, signed, 0))
+        );
+
+        // Changing the assembly's name invalidates the signature
+        let mut image = build_assembly(&key, signed, 64);
+        let name = image.windows(6).position(|w| w == b"\0Test\0").unwrap();
+        image[name + 1] = b'B';
+        assert_eq!(Ok(StrongNameStatus::Invalid), verify_assembly(&image));
+    }
+
+    #[test]
+    pub fn verify_assembly_without_manifest() {
+        let image = build_assembly_image(&[], b"\0", &[0], CliFlags::ILONLY, 0);
+        assert_eq!(Ok(StrongNameStatus::NotSigned), verify_assembly(&image));
+    }
+}
@@ -0,0 +1,132 @@
+use cli::CliFlags;
+use cli::tables::TableIndex;
+use pe::MemoryRange;
+use pe::test_image::{self, SECTION_RVA};
+
+pub fn write_u16(buf: &mut Vec<u8>, val: u16) {
+    buf.extend_from_slice(&val.to_le_bytes());
+}
+
+pub fn write_u32(buf: &mut Vec<u8>, val: u32) {
+    buf.extend_from_slice(&val.to_le_bytes());
+}
+
+fn pad(buf: &mut Vec<u8>) {
+    let padded = (buf.len() + 3) & !3;
+    buf.resize(padded, 0);
+}
+
+/// Builds a minimal PE image, with a single section containing the CLI header and the metadata.
+///
+/// Tables must be in order, and all heaps and tables must be small enough to use 2-byte indexes.
+pub fn build_image(tables: &[(TableIndex, u32, Vec<u8>)], strings: &[u8]) -> Vec<u8> {
+    build_assembly_image(tables, strings, &[0], CliFlags::empty(), 0)
+}
+
+/// Builds an image like `build_image`, with the specified blob heap and CLI header flags, and `signature_len`
+/// zeroed bytes after the metadata for a strong-name signature.
+pub fn build_assembly_image(
+    tables: &[(TableIndex, u32, Vec<u8>)],
+    strings: &[u8],
+    blobs: &[u8],
+    flags: CliFlags,
+    signature_len: u32,
+) -> Vec<u8> {
+    build(tables, strings, blobs, flags, signature_len, &[])
+}
+
+/// The RVA of the code passed to `build_code_image`, immediately after the CLI header.
+pub const CODE_RVA: u32 = SECTION_RVA + 72;
+
+/// Builds an image like `build_image`, with the specified blob heap, and method bodies at `CODE_RVA`.
+pub fn build_code_image(tables: &[(TableIndex, u32, Vec<u8>)], strings: &[u8], blobs: &[u8], code: &[u8]) -> Vec<u8> {
+    build(tables, strings, blobs, CliFlags::empty(), 0, code)
+}
+
+fn build(
+    tables: &[(TableIndex, u32, Vec<u8>)],
+    strings: &[u8],
+    blobs: &[u8],
+    flags: CliFlags,
+    signature_len: u32,
+    code: &[u8],
+) -> Vec<u8> {
+    // The "#~" stream
+    let mut table_stream = Vec::new();
+    write_u32(&mut table_stream, 0);
+    table_stream.extend_from_slice(&[2, 0, 0, 1]);
+    let valid = tables.iter().fold(0u64, |mask, t| mask | (1 << t.0 as u64));
+    write_u32(&mut table_stream, valid as u32);
+    write_u32(&mut table_stream, (valid >> 32) as u32);
+    table_stream.extend_from_slice(&[0; 8]);
+    for &(_, rows, _) in tables {
+        write_u32(&mut table_stream, rows);
+    }
+    for (_, _, data) in tables {
+        table_stream.extend_from_slice(data);
+    }
+    pad(&mut table_stream);
+
+    let mut strings = strings.to_vec();
+    pad(&mut strings);
+    let mut blobs = blobs.to_vec();
+    pad(&mut blobs);
+    let streams: Vec<(&[u8], Vec<u8>)> = vec![
+        (b"#~", table_stream),
+        (b"#Strings", strings),
+        (b"#Blob", blobs),
+        (b"#GUID", vec![0x11; 16]),
+    ];
+
+    // The metadata header and stream headers
+    let mut metadata = Vec::new();
+    write_u32(&mut metadata, 0x424A5342);
+    write_u16(&mut metadata, 1);
+    write_u16(&mut metadata, 1);
+    write_u32(&mut metadata, 0);
+    write_u32(&mut metadata, 12);
+    metadata.extend_from_slice(b"v4.0.30319\0\0");
+    write_u16(&mut metadata, 0);
+    write_u16(&mut metadata, streams.len() as u16);
+    let header_size = metadata.len() + streams.iter().map(|s| 8 + (s.0.len() + 4) / 4 * 4).sum::<usize>();
+    let mut offset = header_size;
+    for &(name, ref data) in &streams {
+        write_u32(&mut metadata, offset as u32);
+        write_u32(&mut metadata, data.len() as u32);
+        metadata.extend_from_slice(name);
+        metadata.extend(vec![0; 4 - name.len() % 4]);
+        offset += data.len();
+    }
+    for (_, data) in &streams {
+        metadata.extend_from_slice(data);
+    }
+
+    // The section, containing the CLI header followed by the code, the metadata and the signature
+    let mut code = code.to_vec();
+    pad(&mut code);
+    let metadata_rva = CODE_RVA + code.len() as u32;
+    let signature = if signature_len == 0 {
+        MemoryRange::new(0, 0)
+    } else {
+        MemoryRange::new(metadata_rva + metadata.len() as u32, signature_len)
+    };
+    let mut section = Vec::new();
+    write_u32(&mut section, 72);
+    write_u16(&mut section, 2);
+    write_u16(&mut section, 5);
+    write_u32(&mut section, metadata_rva);
+    write_u32(&mut section, metadata.len() as u32);
+    write_u32(&mut section, flags.bits());
+    write_u32(&mut section, 0);
+    write_u32(&mut section, 0);
+    write_u32(&mut section, 0);
+    write_u32(&mut section, signature.start);
+    write_u32(&mut section, signature.len);
+    section.extend_from_slice(&[0; 32]);
+    section.extend(code);
+    section.extend(metadata);
+    section.extend(vec![0; signature_len as usize]);
+
+    // The CLI header is directory 14
+    test_image::build_image(false, &[(14, MemoryRange::new(SECTION_RVA, 72))], &section)
+}
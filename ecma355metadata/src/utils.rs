@@ -1,11 +1,11 @@
-use std::io::Read;
+use std::io::{self, Read};
 
-pub fn read_bytes<R: Read>(reader: &mut R, count: usize) -> Result<Vec<u8>, ::std::io::Error> {
-    unsafe {
-        let mut vec = Vec::with_capacity(count);
-        vec.reserve_exact(count);
-        vec.set_len(count);
-        reader.read_exact(vec.as_mut_slice())?;
-        Ok(vec)
+pub fn read_bytes<R: Read>(reader: &mut R, count: usize) -> Result<Vec<u8>, io::Error> {
+    // Read incrementally rather than allocating `count` bytes up front, since the count may come from untrusted data
+    let mut vec = Vec::new();
+    reader.take(count as u64).read_to_end(&mut vec)?;
+    if vec.len() < count {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer"));
     }
-}
\ No newline at end of file
+    Ok(vec)
+}
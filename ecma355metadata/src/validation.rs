@@ -0,0 +1,759 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Deref;
+
+use cli::{Access, BlobHandle, GuidHandle, MethodAttributes, MethodFlags, MethodVTableLayout, StringHandle,
+          TypeAttributes, TypeFlags, TypeLayout, TypeSemantics, TypeVisibility};
+use cli::tables::{self, TableDecoder, TableHandle, TableIndex};
+use error::Error;
+use metadata_image::MetadataImage;
+
+// The tables each coded index can refer to, in tag order. Sorted tables are sorted by the encoded value, so
+// rows with the same index are ordered by tag.
+const TYPE_DEF_OR_REF: &[TableIndex] = &[TableIndex::TypeDef, TableIndex::TypeRef, TableIndex::TypeSpec];
+const HAS_CONSTANT: &[TableIndex] = &[TableIndex::Field, TableIndex::Param, TableIndex::Property];
+const HAS_CUSTOM_ATTRIBUTE: &[TableIndex] = &[
+    TableIndex::MethodDef,
+    TableIndex::Field,
+    TableIndex::TypeRef,
+    TableIndex::TypeDef,
+    TableIndex::Param,
+    TableIndex::InterfaceImpl,
+    TableIndex::MemberRef,
+    TableIndex::Module,
+    TableIndex::DeclSecurity,
+    TableIndex::Property,
+    TableIndex::Event,
+    TableIndex::StandAloneSig,
+    TableIndex::ModuleRef,
+    TableIndex::TypeSpec,
+    TableIndex::Assembly,
+    TableIndex::AssemblyRef,
+    TableIndex::File,
+    TableIndex::ExportedType,
+    TableIndex::ManifestResource,
+    TableIndex::GenericParam,
+    TableIndex::GenericParamConstraint,
+    TableIndex::MethodSpec,
+];
+const HAS_FIELD_MARSHAL: &[TableIndex] = &[TableIndex::Field, TableIndex::Param];
+const HAS_DECL_SECURITY: &[TableIndex] = &[TableIndex::TypeDef, TableIndex::MethodDef, TableIndex::Assembly];
+const HAS_SEMANTICS: &[TableIndex] = &[TableIndex::Event, TableIndex::Property];
+const MEMBER_FORWARDED: &[TableIndex] = &[TableIndex::Field, TableIndex::MethodDef];
+const TYPE_OR_METHOD_DEF: &[TableIndex] = &[TableIndex::TypeDef, TableIndex::MethodDef];
+
+/// A structural problem found in the metadata of an image.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    /// The row containing the problem.
+    pub location: TableHandle,
+    pub kind: DiagnosticKind,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}: {}", self.location, self.kind)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiagnosticKind {
+    /// The row could not be decoded.
+    InvalidRow(Error),
+
+    /// The table is required to be sorted by its primary key, but this row is out of order.
+    NotSorted,
+
+    /// A column refers to a row that does not exist.
+    InvalidReference { column: &'static str, target: TableHandle },
+
+    /// A column that must refer to a row is null.
+    NullReference { column: &'static str },
+
+    /// A column that marks the start of a run of rows in another table is out of range, or comes before the
+    /// start of the previous row's run.
+    InvalidList { column: &'static str },
+
+    /// A column refers to an entry that does not exist in a heap.
+    InvalidHeapReference { column: &'static str, heap: Heap },
+
+    /// An earlier type has the same name, namespace and enclosing type.
+    DuplicateTypeName,
+
+    /// The flags of a TypeDef are not a valid combination.
+    InvalidTypeAttributes(&'static str),
+
+    /// The flags of a MethodDef are not a valid combination.
+    InvalidMethodAttributes(&'static str),
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            DiagnosticKind::InvalidRow(ref e) => write!(f, "The row could not be decoded: {:?}", e),
+            DiagnosticKind::NotSorted => write!(f, "The row is not sorted by the table's primary key."),
+            DiagnosticKind::InvalidReference { column, target } => {
+                write!(f, "{} refers to {}, which does not exist.", column, target)
+            }
+            DiagnosticKind::NullReference { column } => write!(f, "{} must not be null.", column),
+            DiagnosticKind::InvalidList { column } => {
+                write!(f, "{} is out of range, or less than in the previous row.", column)
+            }
+            DiagnosticKind::InvalidHeapReference { column, heap } => {
+                write!(f, "{} is not a valid index into the {} heap.", column, heap)
+            }
+            DiagnosticKind::DuplicateTypeName => write!(f, "A type with the same name is already defined."),
+            DiagnosticKind::InvalidTypeAttributes(msg) | DiagnosticKind::InvalidMethodAttributes(msg) => {
+                f.write_str(msg)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Heap {
+    String,
+    Blob,
+    Guid,
+}
+impl_display_via_debug!(Heap);
+
+/// Checks the metadata in an image for structural problems that the decoders themselves don't catch.
+///
+/// This checks that tables the ECMA spec requires to be sorted are sorted, that table and heap references are in
+/// range, that the member lists of TypeDef, MethodDef, EventMap and PropertyMap rows are in order, that type names
+/// are unique, and that TypeDef and MethodDef flags are valid combinations. Other tools can trust the image's
+/// references once this returns no diagnostics.
+pub fn validate<D: Deref<Target = [u8]>>(image: &MetadataImage<D>) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        image,
+        diagnostics: Vec::new(),
+    };
+    validator.validate();
+    validator.diagnostics
+}
+
+struct Validator<'i, D: Deref<Target = [u8]> + 'i> {
+    image: &'i MetadataImage<D>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'i, D: Deref<Target = [u8]> + 'i> Validator<'i, D> {
+    fn validate(&mut self) {
+        for (row, r) in self.rows::<tables::ModuleDecoder>() {
+            self.string(row, "Name", r.name);
+            self.guid(row, "Mvid", r.mvid);
+            self.guid(row, "EncId", r.enc_id);
+            self.guid(row, "EncBaseId", r.enc_base_id);
+        }
+
+        for (row, r) in self.rows::<tables::TypeRefDecoder>() {
+            self.reference(row, "ResolutionScope", r.resolution_scope);
+            self.string(row, "TypeName", r.name);
+            self.string(row, "TypeNamespace", r.namespace);
+        }
+
+        let interface_methods = self.validate_type_defs();
+
+        self.pointers::<tables::FieldPtrDecoder, _>("Field", |r| r.field);
+        self.pointers::<tables::MethodPtrDecoder, _>("Method", |r| r.method);
+        self.pointers::<tables::ParamPtrDecoder, _>("Param", |r| r.param);
+        self.pointers::<tables::EventPtrDecoder, _>("Event", |r| r.event);
+        self.pointers::<tables::PropertyPtrDecoder, _>("Property", |r| r.property);
+
+        for (row, r) in self.rows::<tables::FieldDecoder>() {
+            self.string(row, "Name", r.name);
+            self.blob(row, "Signature", r.signature);
+        }
+
+        let mut params = Vec::new();
+        for (row, r) in self.rows::<tables::MethodDefDecoder>() {
+            self.string(row, "Name", r.name);
+            self.blob(row, "Signature", r.signature);
+            self.method_attributes(row, r.flags, interface_methods.contains(&row.index()));
+            params.push((row, r.params));
+        }
+        self.list("ParamList", TableIndex::ParamPtr, &params);
+
+        for (row, r) in self.rows::<tables::ParamDecoder>() {
+            self.string(row, "Name", r.name);
+        }
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::InterfaceImplDecoder>() {
+            self.required_reference(row, "Class", r.class);
+            self.required_reference(row, "Interface", r.interface);
+            keys.push((row, (r.class.index(), coded_key(r.interface, TYPE_DEF_OR_REF))));
+        }
+        self.sorted(&keys);
+
+        for (row, r) in self.rows::<tables::MemberRefDecoder>() {
+            self.required_reference(row, "Class", r.class);
+            self.string(row, "Name", r.name);
+            self.blob(row, "Signature", r.signature);
+        }
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::ConstantDecoder>() {
+            self.required_reference(row, "Parent", r.parent);
+            self.blob(row, "Value", r.value);
+            keys.push((row, coded_key(r.parent, HAS_CONSTANT)));
+        }
+        self.sorted(&keys);
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::CustomAttributeDecoder>() {
+            self.required_reference(row, "Parent", r.parent);
+            self.required_reference(row, "Type", r.attribute_type);
+            self.blob(row, "Value", r.value);
+            keys.push((row, coded_key(r.parent, HAS_CUSTOM_ATTRIBUTE)));
+        }
+        self.sorted(&keys);
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::FieldMarshalDecoder>() {
+            self.required_reference(row, "Parent", r.parent);
+            self.blob(row, "NativeType", r.native_type);
+            keys.push((row, coded_key(r.parent, HAS_FIELD_MARSHAL)));
+        }
+        self.sorted(&keys);
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::DeclSecurityDecoder>() {
+            self.required_reference(row, "Parent", r.parent);
+            self.blob(row, "PermissionSet", r.permission_set);
+            keys.push((row, coded_key(r.parent, HAS_DECL_SECURITY)));
+        }
+        self.sorted(&keys);
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::ClassLayoutDecoder>() {
+            self.required_reference(row, "Parent", r.parent);
+            keys.push((row, r.parent.index()));
+        }
+        self.sorted(&keys);
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::FieldLayoutDecoder>() {
+            self.required_reference(row, "Field", r.field);
+            keys.push((row, r.field.index()));
+        }
+        self.sorted(&keys);
+
+        for (row, r) in self.rows::<tables::StandAloneSigDecoder>() {
+            self.blob(row, "Signature", r.signature);
+        }
+
+        let mut events = Vec::new();
+        for (row, r) in self.rows::<tables::EventMapDecoder>() {
+            self.required_reference(row, "Parent", r.parent);
+            events.push((row, r.event_list));
+        }
+        self.list("EventList", TableIndex::EventPtr, &events);
+
+        for (row, r) in self.rows::<tables::EventDecoder>() {
+            self.string(row, "Name", r.name);
+            self.reference(row, "EventType", r.event_type);
+        }
+
+        let mut properties = Vec::new();
+        for (row, r) in self.rows::<tables::PropertyMapDecoder>() {
+            self.required_reference(row, "Parent", r.parent);
+            properties.push((row, r.property_list));
+        }
+        self.list("PropertyList", TableIndex::PropertyPtr, &properties);
+
+        for (row, r) in self.rows::<tables::PropertyDecoder>() {
+            self.string(row, "Name", r.name);
+            self.blob(row, "Type", r.signature);
+        }
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::MethodSemanticsDecoder>() {
+            self.required_reference(row, "Method", r.method);
+            self.required_reference(row, "Association", r.association);
+            keys.push((row, coded_key(r.association, HAS_SEMANTICS)));
+        }
+        self.sorted(&keys);
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::MethodImplDecoder>() {
+            self.required_reference(row, "Class", r.class);
+            self.required_reference(row, "MethodBody", r.method_body);
+            self.required_reference(row, "MethodDeclaration", r.method_declaration);
+            keys.push((row, r.class.index()));
+        }
+        self.sorted(&keys);
+
+        for (row, r) in self.rows::<tables::ModuleRefDecoder>() {
+            self.string(row, "Name", r.name);
+        }
+
+        for (row, r) in self.rows::<tables::TypeSpecDecoder>() {
+            self.blob(row, "Signature", r.signature);
+        }
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::ImplMapDecoder>() {
+            self.required_reference(row, "MemberForwarded", r.member_forwarded);
+            self.string(row, "ImportName", r.import_name);
+            self.required_reference(row, "ImportScope", r.import_scope);
+            keys.push((row, coded_key(r.member_forwarded, MEMBER_FORWARDED)));
+        }
+        self.sorted(&keys);
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::FieldRvaDecoder>() {
+            self.required_reference(row, "Field", r.field);
+            keys.push((row, r.field.index()));
+        }
+        self.sorted(&keys);
+
+        for (row, r) in self.rows::<tables::AssemblyDecoder>() {
+            self.blob(row, "PublicKey", r.public_key);
+            self.string(row, "Name", r.name);
+            self.string(row, "Culture", r.culture);
+        }
+
+        for (row, r) in self.rows::<tables::AssemblyRefDecoder>() {
+            self.blob(row, "PublicKeyOrToken", r.public_key_or_token);
+            self.string(row, "Name", r.name);
+            self.string(row, "Culture", r.culture);
+            self.blob(row, "HashValue", r.hash_value);
+        }
+
+        for (row, r) in self.rows::<tables::AssemblyRefProcessorDecoder>() {
+            self.required_reference(row, "AssemblyRef", r.assembly_ref);
+        }
+
+        for (row, r) in self.rows::<tables::AssemblyRefOsDecoder>() {
+            self.required_reference(row, "AssemblyRef", r.assembly_ref);
+        }
+
+        for (row, r) in self.rows::<tables::FileDecoder>() {
+            self.string(row, "Name", r.name);
+            self.blob(row, "HashValue", r.hash_value);
+        }
+
+        for (row, r) in self.rows::<tables::ExportedTypeDecoder>() {
+            self.string(row, "TypeName", r.type_name);
+            self.string(row, "TypeNamespace", r.type_namespace);
+            self.reference(row, "Implementation", r.implementation);
+        }
+
+        for (row, r) in self.rows::<tables::ManifestResourceDecoder>() {
+            self.string(row, "Name", r.name);
+            self.reference(row, "Implementation", r.implementation);
+        }
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::GenericParamDecoder>() {
+            self.required_reference(row, "Owner", r.owner);
+            self.string(row, "Name", r.name);
+            keys.push((row, (coded_key(r.owner, TYPE_OR_METHOD_DEF), r.number)));
+        }
+        self.sorted(&keys);
+
+        for (row, r) in self.rows::<tables::MethodSpecDecoder>() {
+            self.required_reference(row, "Method", r.method);
+            self.blob(row, "Instantiation", r.instantiation);
+        }
+
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::GenericParamConstraintDecoder>() {
+            self.required_reference(row, "Owner", r.owner);
+            self.required_reference(row, "Constraint", r.constraint);
+            keys.push((row, r.owner.index()));
+        }
+        self.sorted(&keys);
+    }
+
+    /// Validates the TypeDef table, and returns the MethodDef rows that belong to interfaces.
+    fn validate_type_defs(&mut self) -> HashSet<usize> {
+        // Nested types only need unique names within their enclosing type, and have different visibility rules
+        let mut enclosing_types = HashMap::new();
+        let mut keys = Vec::new();
+        for (row, r) in self.rows::<tables::NestedClassDecoder>() {
+            self.required_reference(row, "NestedClass", r.nested_class);
+            self.required_reference(row, "EnclosingClass", r.enclosing_class);
+            enclosing_types.insert(r.nested_class.index(), r.enclosing_class.index());
+            keys.push((row, r.nested_class.index()));
+        }
+        self.sorted(&keys);
+
+        let image = self.image;
+        let strings = image.string_heap();
+        let mut names = HashMap::new();
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+        let mut interfaces = Vec::new();
+        for (row, r) in self.rows::<tables::TypeDefDecoder>() {
+            self.string(row, "TypeName", r.type_name);
+            self.string(row, "TypeNamespace", r.type_namespace);
+            self.reference(row, "Extends", r.extends);
+            fields.push((row, r.field_list));
+            methods.push((row, r.method_list));
+            interfaces.push(r.flags.semantics() == TypeSemantics::Interface);
+
+            let enclosing_type = enclosing_types.get(&row.index()).cloned();
+            self.type_attributes(row, r.flags, enclosing_type.is_some());
+
+            let name = strings.get(r.type_name.index()).unwrap_or(b"");
+            let namespace = strings.get(r.type_namespace.index()).unwrap_or(b"");
+            if names.insert((enclosing_type, namespace, name), row).is_some() {
+                self.report(row, DiagnosticKind::DuplicateTypeName);
+            }
+        }
+        self.list("FieldList", TableIndex::FieldPtr, &fields);
+        self.list("MethodList", TableIndex::MethodPtr, &methods);
+        self.interface_methods(&methods, &interfaces)
+    }
+
+    /// Finds the MethodDef rows in the method lists of the TypeDef rows that are interfaces.
+    fn interface_methods(&mut self, methods: &[(TableHandle, TableHandle)], interfaces: &[bool]) -> HashSet<usize> {
+        let pointers: Vec<_> = self.rows::<tables::MethodPtrDecoder>().iter().map(|(_, r)| r.method.index()).collect();
+        let len = match pointers.len() {
+            0 => self.row_count(TableIndex::MethodDef),
+            n => n,
+        };
+
+        let mut rows = HashSet::new();
+        for (i, &(_, start)) in methods.iter().enumerate() {
+            if !interfaces[i] {
+                continue;
+            }
+            // Lists that go backwards or past the end of the table have already been reported
+            let end = methods.get(i + 1).map_or(len + 1, |&(_, next)| next.index()).min(len + 1);
+            for index in start.index()..end {
+                rows.insert(pointers.get(index.wrapping_sub(1)).cloned().unwrap_or(index));
+            }
+        }
+        rows
+    }
+
+    fn type_attributes(&mut self, row: TableHandle, flags: TypeAttributes, is_nested: bool) {
+        let is_interface = flags.semantics() == TypeSemantics::Interface;
+        let has_nested_visibility = !matches!(flags.visibility(), TypeVisibility::NotPublic | TypeVisibility::Public);
+
+        let problem = if flags.layout() == TypeLayout::Reserved {
+            Some("The layout of the type is set to a reserved value.")
+        } else if is_interface && !flags.flags().contains(TypeFlags::Abstract) {
+            Some("An interface must be abstract.")
+        } else if is_interface && flags.flags().contains(TypeFlags::Sealed) {
+            Some("An interface must not be sealed.")
+        } else if has_nested_visibility && !is_nested {
+            Some("A type with nested visibility must be nested in another type.")
+        } else if !has_nested_visibility && is_nested {
+            Some("A nested type must have nested visibility.")
+        } else {
+            None
+        };
+
+        if let Some(msg) = problem {
+            self.report(row, DiagnosticKind::InvalidTypeAttributes(msg));
+        }
+    }
+
+    fn method_attributes(&mut self, row: TableHandle, flags: MethodAttributes, in_interface: bool) {
+        let is_static = flags.flags().contains(MethodFlags::Static);
+        let is_virtual = flags.flags().contains(MethodFlags::Virtual);
+        let is_new_slot = flags.vtable_layout() == MethodVTableLayout::NewSlot;
+
+        // Interfaces can have static virtual and static abstract members, which classes implement with MethodImpls
+        let problem = if flags.access() == Access::Reserved {
+            Some("The access of the method is set to a reserved value.")
+        } else if is_static && flags.flags().contains(MethodFlags::Final) {
+            Some("A static method must not be final.")
+        } else if is_static
+            && !in_interface
+            && (is_virtual || is_new_slot || flags.flags().contains(MethodFlags::Abstract))
+        {
+            Some("A static method must not be virtual, abstract or new slot outside an interface.")
+        } else if !is_virtual
+            && (is_new_slot || flags.flags().intersects(MethodFlags::Final | MethodFlags::Abstract | MethodFlags::Strict))
+        {
+            Some("A final, abstract, strict or new slot method must be virtual.")
+        } else {
+            None
+        };
+
+        if let Some(msg) = problem {
+            self.report(row, DiagnosticKind::InvalidMethodAttributes(msg));
+        }
+    }
+
+    fn report(&mut self, location: TableHandle, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic { location, kind });
+    }
+
+    /// Decodes every row of a table, reporting any that can't be decoded.
+    fn rows<T: TableDecoder>(&mut self) -> Vec<(TableHandle, T::Item)> {
+        let image = self.image;
+        let mut rows = Vec::new();
+        for (i, row) in image.table::<T>().iter().enumerate() {
+            let handle = TableHandle::new(i + 1, T::INDEX);
+            match row {
+                Ok(row) => rows.push((handle, row)),
+                Err(e) => self.report(handle, DiagnosticKind::InvalidRow(e)),
+            }
+        }
+        rows
+    }
+
+    fn pointers<T: TableDecoder, F: Fn(&T::Item) -> TableHandle>(&mut self, column: &'static str, target: F) {
+        for (row, r) in self.rows::<T>() {
+            self.required_reference(row, column, target(&r));
+        }
+    }
+
+    fn row_count(&self, table: TableIndex) -> usize {
+        self.image.metadata_sizes().row_count(table)
+    }
+
+    /// Checks a reference that may be null.
+    fn reference(&mut self, row: TableHandle, column: &'static str, target: TableHandle) {
+        if target.index() > self.row_count(target.table()) {
+            self.report(row, DiagnosticKind::InvalidReference { column, target });
+        }
+    }
+
+    fn required_reference(&mut self, row: TableHandle, column: &'static str, target: TableHandle) {
+        if target.index() == 0 {
+            self.report(row, DiagnosticKind::NullReference { column });
+        } else {
+            self.reference(row, column, target);
+        }
+    }
+
+    /// Checks the columns that mark the start of each row's run of rows in another table.
+    ///
+    /// Each run continues until the start of the next row's run, so the starts can't go backwards. The start can be
+    /// one past the last row, for an empty run at the end of the table.
+    fn list(&mut self, column: &'static str, pointer_table: TableIndex, starts: &[(TableHandle, TableHandle)]) {
+        let mut previous = 1;
+        for &(row, start) in starts {
+            // If there's an indirection table, the list refers to it instead
+            let len = match self.row_count(pointer_table) {
+                0 => self.row_count(start.table()),
+                n => n,
+            };
+
+            if start.index() < previous || start.index() > len + 1 {
+                self.report(row, DiagnosticKind::InvalidList { column });
+            } else {
+                previous = start.index();
+            }
+        }
+    }
+
+    /// Checks that the rows of a table are sorted by the provided keys, reporting the first row that isn't.
+    fn sorted<K: PartialOrd>(&mut self, keys: &[(TableHandle, K)]) {
+        let unsorted = keys.windows(2).find(|w| w[1].1 < w[0].1);
+        if let Some(w) = unsorted {
+            self.report(w[1].0, DiagnosticKind::NotSorted);
+        }
+    }
+
+    fn string(&mut self, row: TableHandle, column: &'static str, handle: StringHandle) {
+        if handle.index() != 0 && self.image.string_heap().get(handle.index()).is_none() {
+            self.report(row, DiagnosticKind::InvalidHeapReference { column, heap: Heap::String });
+        }
+    }
+
+    fn blob(&mut self, row: TableHandle, column: &'static str, handle: BlobHandle) {
+        if handle.index() != 0 && self.image.blob_heap().get(handle.index()).is_none() {
+            self.report(row, DiagnosticKind::InvalidHeapReference { column, heap: Heap::Blob });
+        }
+    }
+
+    fn guid(&mut self, row: TableHandle, column: &'static str, handle: GuidHandle) {
+        if handle.index() != 0 && self.image.guid_heap().get(handle.index()).is_none() {
+            self.report(row, DiagnosticKind::InvalidHeapReference { column, heap: Heap::Guid });
+        }
+    }
+}
+
+/// Gets a key that sorts coded index values the same way as their encoded values.
+fn coded_key(handle: TableHandle, tables: &[TableIndex]) -> (usize, usize) {
+    let tag = tables.iter().position(|&t| t == handle.table()).unwrap_or(tables.len());
+    (handle.index(), tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_metadata::{self, write_u16, write_u32};
+
+    const STRINGS: &[u8] = b"\0<Module>\0Foo\0Bar\0NS\0";
+    const MODULE: u16 = 1;
+    const FOO: u16 = 10;
+    const BAR: u16 = 14;
+    const NS: u16 = 18;
+
+    fn build_image(tables: &[(TableIndex, u32, Vec<u8>)]) -> Vec<u8> {
+        test_metadata::build_image(tables, STRINGS)
+    }
+
+    fn module() -> (TableIndex, u32, Vec<u8>) {
+        let mut row = Vec::new();
+        for &val in &[0, MODULE, 1, 0, 0] {
+            write_u16(&mut row, val);
+        }
+        (TableIndex::Module, 1, row)
+    }
+
+    fn type_defs(rows: &[(u32, u16, u16, u16, u16, u16)]) -> (TableIndex, u32, Vec<u8>) {
+        let mut data = Vec::new();
+        for &(flags, name, namespace, extends, field_list, method_list) in rows {
+            write_u32(&mut data, flags);
+            for &val in &[name, namespace, extends, field_list, method_list] {
+                write_u16(&mut data, val);
+            }
+        }
+        (TableIndex::TypeDef, rows.len() as u32, data)
+    }
+
+    fn fields(names: &[u16]) -> (TableIndex, u32, Vec<u8>) {
+        let mut data = Vec::new();
+        for &name in names {
+            for &val in &[0, name, 0] {
+                write_u16(&mut data, val);
+            }
+        }
+        (TableIndex::Field, names.len() as u32, data)
+    }
+
+    fn method_defs(flags: &[u16]) -> (TableIndex, u32, Vec<u8>) {
+        let mut data = Vec::new();
+        for &flags in flags {
+            write_u32(&mut data, 0);
+            for &val in &[0, flags, BAR, 0, 1] {
+                write_u16(&mut data, val);
+            }
+        }
+        (TableIndex::MethodDef, flags.len() as u32, data)
+    }
+
+    fn load(tables: &[(TableIndex, u32, Vec<u8>)]) -> MetadataImage<Vec<u8>> {
+        MetadataImage::load_data(build_image(tables)).unwrap()
+    }
+
+    #[test]
+    pub fn valid_image_has_no_diagnostics() {
+        let image = load(&[
+            module(),
+            type_defs(&[(0, MODULE, 0, 0, 1, 1), (0x00100001, FOO, NS, 0, 1, 1)]),
+            fields(&[BAR]),
+            method_defs(&[0x0006]),
+        ]);
+
+        assert_eq!(Vec::<Diagnostic>::new(), validate(&image));
+
+        let type_defs = image.table::<tables::TypeDefDecoder>();
+        assert_eq!(2, type_defs.len());
+        assert_eq!(b"Foo", image.string_heap().get(type_defs.get(2).unwrap().type_name.index()).unwrap());
+        assert_eq!(Err(Error::InvalidTableReference), type_defs.get(3).map(|_| ()));
+    }
+
+    #[test]
+    pub fn detects_invalid_type_defs() {
+        let image = load(&[
+            module(),
+            type_defs(&[
+                (0, MODULE, 0, 0, 1, 1),
+                (0x01, FOO, NS, 0, 2, 1),
+                // Extends TypeRef 1, which doesn't exist, and the field list goes backwards
+                (0x01, FOO, NS, 0x05, 1, 1),
+                // Not abstract, and the name is past the end of the heap
+                (0x21, 100, 0, 0, 2, 1),
+            ]),
+            fields(&[BAR]),
+        ]);
+
+        let row = |index| TableHandle::new(index, TableIndex::TypeDef);
+        assert_eq!(
+            vec![
+                Diagnostic {
+                    location: row(3),
+                    kind: DiagnosticKind::InvalidReference {
+                        column: "Extends",
+                        target: TableHandle::new(1, TableIndex::TypeRef),
+                    },
+                },
+                Diagnostic { location: row(3), kind: DiagnosticKind::DuplicateTypeName },
+                Diagnostic {
+                    location: row(4),
+                    kind: DiagnosticKind::InvalidHeapReference { column: "TypeName", heap: Heap::String },
+                },
+                Diagnostic {
+                    location: row(4),
+                    kind: DiagnosticKind::InvalidTypeAttributes("An interface must be abstract."),
+                },
+                Diagnostic { location: row(3), kind: DiagnosticKind::InvalidList { column: "FieldList" } },
+            ],
+            validate(&image)
+        );
+    }
+
+    #[test]
+    pub fn detects_unsorted_tables_and_invalid_method_flags() {
+        let mut nested_classes = Vec::new();
+        for &val in &[4, 2, 3, 2] {
+            write_u16(&mut nested_classes, val);
+        }
+        let image = load(&[
+            module(),
+            type_defs(&[
+                (0, MODULE, 0, 0, 1, 1),
+                (0x01, FOO, NS, 0, 1, 1),
+                (0x02, BAR, 0, 0, 1, 1),
+                (0x02, NS, 0, 0, 1, 1),
+            ]),
+            // Static virtual, abstract but not virtual, and reserved access
+            method_defs(&[0x0056, 0x0406, 0x0007]),
+            (TableIndex::NestedClass, 2, nested_classes),
+        ]);
+
+        let diagnostics: Vec<_> = validate(&image).iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            vec![
+                "NestedClass[0x0002]: The row is not sorted by the table's primary key.",
+                "MethodDef[0x0001]: A static method must not be virtual, abstract or new slot outside an interface.",
+                "MethodDef[0x0002]: A final, abstract, strict or new slot method must be virtual.",
+                "MethodDef[0x0003]: The access of the method is set to a reserved value.",
+            ],
+            diagnostics
+        );
+    }
+
+    #[test]
+    pub fn allows_static_virtual_interface_methods() {
+        // Static abstract and static virtual methods in an interface, followed by the same in a class
+        let image = load(&[
+            module(),
+            type_defs(&[(0, MODULE, 0, 0, 1, 1), (0xA1, FOO, NS, 0, 1, 1), (0x01, BAR, NS, 0, 1, 3)]),
+            method_defs(&[0x0456, 0x0056, 0x0456, 0x0056, 0x0076]),
+        ]);
+
+        let diagnostics: Vec<_> = validate(&image).iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            vec![
+                "MethodDef[0x0003]: A static method must not be virtual, abstract or new slot outside an interface.",
+                "MethodDef[0x0004]: A static method must not be virtual, abstract or new slot outside an interface.",
+                "MethodDef[0x0005]: A static method must not be final.",
+            ],
+            diagnostics
+        );
+    }
+
+    #[test]
+    pub fn load_rejects_tables_past_end_of_stream() {
+        let (index, _, data) = type_defs(&[(0, MODULE, 0, 0, 1, 1)]);
+        let result = MetadataImage::load_data(build_image(&[module(), (index, 100, data)]));
+        assert_eq!(
+            Some(Error::InvalidMetadata("There is insufficient space in the metadata stream for this table.")),
+            result.err()
+        );
+    }
+}
@@ -43,11 +43,23 @@ fn do_main() -> i32 {
 
         set_stdlog_logger(logger.new(o!("stdlog" => true))).unwrap();
 
-        // Create a runtime
-        let mut rt = RuntimeBuilder::new()
+        // Create a runtime, picking up the app's config files if `dotnet build` produced them
+        let mut builder = RuntimeBuilder::new()
             .base_directory(base_dir)
-            .logger(logger)
-            .build();
+            .framework_root(&Path::new(env!("CARGO_MANIFEST_DIR")).join("fx"))
+            .logger(logger);
+
+        let runtime_config = base_dir.join(format!("{}.runtimeconfig.json", assembly));
+        if runtime_config.exists() {
+            builder = builder.runtime_config(&runtime_config);
+        }
+
+        let deps_file = base_dir.join(format!("{}.deps.json", assembly));
+        if deps_file.exists() {
+            builder = builder.deps_file(&deps_file);
+        }
+
+        let mut rt = builder.build().unwrap();
 
         // Execute the assembly
         rt.execute(assembly).unwrap()
@@ -0,0 +1,8 @@
+namespace System
+{
+    public delegate void Action();
+
+    public delegate void Action<in T>(T obj);
+
+    public delegate void Action<in T1, in T2>(T1 arg1, T2 arg2);
+}
\ No newline at end of file
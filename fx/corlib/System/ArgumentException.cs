@@ -0,0 +1,14 @@
+namespace System
+{
+    public class ArgumentException : SystemException
+    {
+        public ArgumentException()
+        {
+        }
+
+        public ArgumentException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
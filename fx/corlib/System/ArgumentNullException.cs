@@ -0,0 +1,14 @@
+namespace System
+{
+    public class ArgumentNullException : ArgumentException
+    {
+        public ArgumentNullException()
+        {
+        }
+
+        public ArgumentNullException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
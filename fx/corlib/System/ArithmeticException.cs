@@ -0,0 +1,14 @@
+namespace System
+{
+    public class ArithmeticException : SystemException
+    {
+        public ArithmeticException()
+        {
+        }
+
+        public ArithmeticException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+namespace System
+{
+    public abstract class Array
+    {
+    }
+}
\ No newline at end of file
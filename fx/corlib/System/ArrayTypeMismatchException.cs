@@ -0,0 +1,14 @@
+namespace System
+{
+    public class ArrayTypeMismatchException : SystemException
+    {
+        public ArrayTypeMismatchException()
+        {
+        }
+
+        public ArrayTypeMismatchException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
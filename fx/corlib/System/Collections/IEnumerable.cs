@@ -0,0 +1,7 @@
+namespace System.Collections
+{
+    public interface IEnumerable
+    {
+        IEnumerator GetEnumerator();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+namespace System.Collections
+{
+    public interface IEnumerator
+    {
+        object Current { get; }
+
+        bool MoveNext();
+
+        void Reset();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,16 @@
+using System.Runtime.CompilerServices;
+
+namespace System
+{
+    public abstract class Delegate
+    {
+        internal object _target;
+        internal IntPtr _methodPtr;
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static Delegate Combine(Delegate a, Delegate b);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static Delegate Remove(Delegate source, Delegate value);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System
+{
+    public class DivideByZeroException : ArithmeticException
+    {
+        public DivideByZeroException()
+        {
+        }
+
+        public DivideByZeroException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System
+{
+    public class DllNotFoundException : TypeLoadException
+    {
+        public DllNotFoundException()
+        {
+        }
+
+        public DllNotFoundException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System
+{
+    public class EntryPointNotFoundException : TypeLoadException
+    {
+        public EntryPointNotFoundException()
+        {
+        }
+
+        public EntryPointNotFoundException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
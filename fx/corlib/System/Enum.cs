@@ -1,6 +1,10 @@
+using System.Runtime.CompilerServices;
+
 namespace System
 {
-    public abstract class Enum
+    public abstract class Enum : ValueType
     {
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern override string ToString();
     }
 }
\ No newline at end of file
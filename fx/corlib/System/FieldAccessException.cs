@@ -0,0 +1,14 @@
+namespace System
+{
+    public class FieldAccessException : SystemException
+    {
+        public FieldAccessException()
+        {
+        }
+
+        public FieldAccessException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
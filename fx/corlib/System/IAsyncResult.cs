@@ -0,0 +1,6 @@
+namespace System
+{
+    public interface IAsyncResult
+    {
+    }
+}
\ No newline at end of file
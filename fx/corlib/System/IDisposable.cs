@@ -0,0 +1,7 @@
+namespace System
+{
+    public interface IDisposable
+    {
+        void Dispose();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System
+{
+    public class IndexOutOfRangeException : SystemException
+    {
+        public IndexOutOfRangeException()
+        {
+        }
+
+        public IndexOutOfRangeException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
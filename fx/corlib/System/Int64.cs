@@ -0,0 +1,27 @@
+namespace System
+{
+    public struct Int64
+    {
+        // The runtime sets this field automatically
+        #pragma warning disable 0649
+        private long _value;
+        #pragma warning restore 0649
+
+        public override bool Equals(object other)
+        {
+            if (other is Int64 i)
+            {
+                return i._value == _value;
+            }
+            else
+            {
+                return false;
+            }
+        }
+
+        public override int GetHashCode()
+        {
+            return (int)_value ^ (int)(_value >> 32);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+namespace System
+{
+    public struct IntPtr
+    {
+        // The runtime sets this field automatically
+        #pragma warning disable 0649
+        private long _value;
+        #pragma warning restore 0649
+    }
+}
\ No newline at end of file
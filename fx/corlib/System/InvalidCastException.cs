@@ -0,0 +1,14 @@
+namespace System
+{
+    public class InvalidCastException : SystemException
+    {
+        public InvalidCastException()
+        {
+        }
+
+        public InvalidCastException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System
+{
+    public class InvalidOperationException : SystemException
+    {
+        public InvalidOperationException()
+        {
+        }
+
+        public InvalidOperationException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
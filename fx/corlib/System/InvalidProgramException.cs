@@ -0,0 +1,14 @@
+namespace System
+{
+    public class InvalidProgramException : SystemException
+    {
+        public InvalidProgramException()
+        {
+        }
+
+        public InvalidProgramException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System
+{
+    public class MissingMethodException : SystemException
+    {
+        public MissingMethodException()
+        {
+        }
+
+        public MissingMethodException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
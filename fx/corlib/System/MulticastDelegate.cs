@@ -0,0 +1,7 @@
+namespace System
+{
+    public abstract class MulticastDelegate : Delegate
+    {
+        internal object _invocationList;
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System
+{
+    public class NotSupportedException : SystemException
+    {
+        public NotSupportedException()
+        {
+        }
+
+        public NotSupportedException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System
+{
+    public class NullReferenceException : SystemException
+    {
+        public NullReferenceException()
+        {
+        }
+
+        public NullReferenceException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
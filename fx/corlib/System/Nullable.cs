@@ -0,0 +1,37 @@
+namespace System
+{
+    public struct Nullable<T> where T : struct
+    {
+        // The runtime boxes a Nullable<T> as its value, so it reads these fields by name
+        private bool hasValue;
+        private T value;
+
+        public Nullable(T value)
+        {
+            this.value = value;
+            hasValue = true;
+        }
+
+        public bool HasValue
+        {
+            get { return hasValue; }
+        }
+
+        public T Value
+        {
+            get
+            {
+                if (!hasValue)
+                {
+                    throw new InvalidOperationException();
+                }
+                return value;
+            }
+        }
+
+        public T GetValueOrDefault()
+        {
+            return value;
+        }
+    }
+}
\ No newline at end of file
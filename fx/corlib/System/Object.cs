@@ -1,3 +1,5 @@
+using System.Runtime.CompilerServices;
+
 namespace System
 {
     public class Object
@@ -12,11 +14,8 @@ namespace System
             return GetType().ToString();
         }
 
-        public virtual Type GetType()
-        {
-            // No idea;
-            return new Type();
-        }
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern Type GetType();
 
         public virtual bool Equals(object other)
         {
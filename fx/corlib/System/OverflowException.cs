@@ -0,0 +1,14 @@
+namespace System
+{
+    public class OverflowException : ArithmeticException
+    {
+        public OverflowException()
+        {
+        }
+
+        public OverflowException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System
+{
+    public class PlatformNotSupportedException : NotSupportedException
+    {
+        public PlatformNotSupportedException()
+        {
+        }
+
+        public PlatformNotSupportedException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
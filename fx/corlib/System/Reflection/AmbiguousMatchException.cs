@@ -0,0 +1,14 @@
+namespace System.Reflection
+{
+    public class AmbiguousMatchException : SystemException
+    {
+        public AmbiguousMatchException()
+        {
+        }
+
+        public AmbiguousMatchException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
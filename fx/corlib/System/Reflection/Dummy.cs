@@ -1,8 +0,0 @@
-// Needed right now just to satisfy needs of the auto-generated AssemblyAttributes file
-// We'll need a System.Reflection namespace someday anyway :)
-namespace System.Reflection
-{
-    public class Dummy
-    {
-    }
-}
\ No newline at end of file
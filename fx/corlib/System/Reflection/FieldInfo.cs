@@ -0,0 +1,25 @@
+using System.Runtime.CompilerServices;
+
+namespace System.Reflection
+{
+    public abstract class FieldInfo : MemberInfo
+    {
+        public extern Type FieldType
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        public extern bool IsStatic
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern object GetValue(object obj);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern void SetValue(object obj, object value);
+    }
+}
\ No newline at end of file
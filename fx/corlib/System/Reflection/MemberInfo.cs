@@ -0,0 +1,30 @@
+using System.Runtime.CompilerServices;
+
+namespace System.Reflection
+{
+    public abstract class MemberInfo
+    {
+        // The runtime creates the objects that reflect members, and sets this field to the member each one reflects
+        #pragma warning disable 0649
+        internal IntPtr _value;
+        #pragma warning restore 0649
+
+        public extern string Name
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        public extern Type DeclaringType
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern object[] GetCustomAttributes(bool inherit);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern object[] GetCustomAttributes(Type attributeType, bool inherit);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,28 @@
+using System.Runtime.CompilerServices;
+
+namespace System.Reflection
+{
+    public abstract class MethodBase : MemberInfo
+    {
+        public extern bool IsStatic
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        public extern bool IsVirtual
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static MethodBase GetMethodFromHandle(RuntimeMethodHandle handle);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern Type[] GetParameterTypes();
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern object Invoke(object obj, object[] parameters);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+using System.Runtime.CompilerServices;
+
+namespace System.Reflection
+{
+    public abstract class MethodInfo : MethodBase
+    {
+        public extern Type ReturnType
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+    }
+}
\ No newline at end of file
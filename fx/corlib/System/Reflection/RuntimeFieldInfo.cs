@@ -0,0 +1,10 @@
+namespace System.Reflection
+{
+    // The runtime creates the FieldInfo of each field
+    internal sealed class RuntimeFieldInfo : FieldInfo
+    {
+        private RuntimeFieldInfo()
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+namespace System.Reflection
+{
+    // The runtime creates the MethodInfo of each method
+    internal sealed class RuntimeMethodInfo : MethodInfo
+    {
+        private RuntimeMethodInfo()
+        {
+        }
+    }
+}
\ No newline at end of file
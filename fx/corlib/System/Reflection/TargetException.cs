@@ -0,0 +1,14 @@
+namespace System.Reflection
+{
+    public class TargetException : ApplicationException
+    {
+        public TargetException()
+        {
+        }
+
+        public TargetException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,15 @@
+namespace System.Reflection
+{
+    public class TargetInvocationException : ApplicationException
+    {
+        public TargetInvocationException(Exception inner)
+            : base("Exception has been thrown by the target of an invocation.", inner)
+        {
+        }
+
+        public TargetInvocationException(string message, Exception inner)
+            : base(message, inner)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System.Reflection
+{
+    public class TargetParameterCountException : ApplicationException
+    {
+        public TargetParameterCountException()
+        {
+        }
+
+        public TargetParameterCountException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+namespace System.Runtime.CompilerServices
+{
+    public static class RuntimeHelpers
+    {
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static void InitializeArray(Array array, RuntimeFieldHandle fldHandle);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+namespace System.Runtime.InteropServices
+{
+    public enum CharSet
+    {
+        None = 1,
+        Ansi = 2,
+        Unicode = 3,
+        Auto = 4
+    }
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+using System.Runtime.CompilerServices;
+
+namespace System.Runtime.InteropServices
+{
+    public static class Marshal
+    {
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static int GetLastWin32Error();
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static int GetLastPInvokeError();
+    }
+}
\ No newline at end of file
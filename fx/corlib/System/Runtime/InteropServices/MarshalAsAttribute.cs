@@ -0,0 +1,18 @@
+namespace System.Runtime.InteropServices
+{
+    [AttributeUsage(AttributeTargets.Field | AttributeTargets.Parameter | AttributeTargets.ReturnValue, Inherited = false)]
+    public sealed class MarshalAsAttribute : Attribute
+    {
+        public MarshalAsAttribute(UnmanagedType unmanagedType)
+        {
+            Value = unmanagedType;
+        }
+
+        public MarshalAsAttribute(short unmanagedType)
+        {
+            Value = (UnmanagedType)unmanagedType;
+        }
+
+        public UnmanagedType Value { get; }
+    }
+}
\ No newline at end of file
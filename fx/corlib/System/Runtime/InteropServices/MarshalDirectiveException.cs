@@ -0,0 +1,14 @@
+namespace System.Runtime.InteropServices
+{
+    public class MarshalDirectiveException : SystemException
+    {
+        public MarshalDirectiveException()
+        {
+        }
+
+        public MarshalDirectiveException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,24 @@
+namespace System.Runtime.InteropServices
+{
+    public enum UnmanagedType
+    {
+        Bool = 0x2,
+        I1 = 0x3,
+        U1 = 0x4,
+        I2 = 0x5,
+        U2 = 0x6,
+        I4 = 0x7,
+        U4 = 0x8,
+        I8 = 0x9,
+        U8 = 0xa,
+        R4 = 0xb,
+        R8 = 0xc,
+        LPStr = 0x14,
+        LPWStr = 0x15,
+        SysInt = 0x1f,
+        SysUInt = 0x20,
+        FunctionPtr = 0x26,
+        LPArray = 0x2a,
+        LPUTF8Str = 0x30
+    }
+}
\ No newline at end of file
@@ -0,0 +1,15 @@
+namespace System
+{
+    public struct RuntimeFieldHandle
+    {
+        // The runtime sets this field when it loads the handle with ldtoken
+        #pragma warning disable 0649
+        private IntPtr _value;
+        #pragma warning restore 0649
+
+        public IntPtr Value
+        {
+            get { return _value; }
+        }
+    }
+}
\ No newline at end of file
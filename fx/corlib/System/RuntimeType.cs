@@ -0,0 +1,10 @@
+namespace System
+{
+    // The runtime creates the Type of each type
+    internal sealed class RuntimeType : Type
+    {
+        private RuntimeType()
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,7 @@
+namespace System
+{
+    [AttributeUsage(AttributeTargets.Field, Inherited = false)]
+    public class ThreadStaticAttribute : Attribute
+    {
+    }
+}
\ No newline at end of file
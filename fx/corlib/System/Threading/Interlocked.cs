@@ -0,0 +1,46 @@
+using System.Runtime.CompilerServices;
+
+namespace System.Threading
+{
+    public static class Interlocked
+    {
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static int Increment(ref int location);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static long Increment(ref long location);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static int Decrement(ref int location);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static long Decrement(ref long location);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static int Add(ref int location1, int value);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static long Add(ref long location1, long value);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static int Exchange(ref int location1, int value);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static long Exchange(ref long location1, long value);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static object Exchange(ref object location1, object value);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static int CompareExchange(ref int location1, int value, int comparand);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static long CompareExchange(ref long location1, long value, long comparand);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static object CompareExchange(ref object location1, object value, object comparand);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static void MemoryBarrier();
+    }
+}
\ No newline at end of file
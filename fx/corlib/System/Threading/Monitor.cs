@@ -0,0 +1,28 @@
+using System.Runtime.CompilerServices;
+
+namespace System.Threading
+{
+    public static class Monitor
+    {
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static void Enter(object obj);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static void Enter(object obj, ref bool lockTaken);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static void Exit(object obj);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static bool IsEntered(object obj);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static bool Wait(object obj);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static void Pulse(object obj);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static void PulseAll(object obj);
+    }
+}
\ No newline at end of file
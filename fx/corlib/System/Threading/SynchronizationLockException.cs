@@ -0,0 +1,14 @@
+namespace System.Threading
+{
+    public class SynchronizationLockException : SystemException
+    {
+        public SynchronizationLockException()
+        {
+        }
+
+        public SynchronizationLockException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,55 @@
+using System.Runtime.CompilerServices;
+
+namespace System.Threading
+{
+    public sealed class Thread
+    {
+        private ThreadStart _start;
+
+        // The runtime sets these fields: the id it gives the thread the first time it is asked for one, and the
+        // thread it runs the delegate on once the thread is started
+        #pragma warning disable 0649
+        private int _managedThreadId;
+        private IntPtr _thread;
+        #pragma warning restore 0649
+
+        public Thread(ThreadStart start)
+        {
+            if (start == null)
+            {
+                throw new ArgumentNullException("start");
+            }
+            _start = start;
+        }
+
+        public static extern Thread CurrentThread
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        public extern int ManagedThreadId
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern void Start();
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern void Join();
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static void Sleep(int millisecondsTimeout);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static bool Yield();
+
+        // The runtime calls this on the new thread when the thread is started
+        private void StartCallback()
+        {
+            _start();
+        }
+    }
+}
\ No newline at end of file
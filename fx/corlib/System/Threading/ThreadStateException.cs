@@ -0,0 +1,14 @@
+namespace System.Threading
+{
+    public class ThreadStateException : SystemException
+    {
+        public ThreadStateException()
+        {
+        }
+
+        public ThreadStateException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
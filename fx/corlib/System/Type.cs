@@ -1,10 +1,64 @@
+using System.Reflection;
+using System.Runtime.CompilerServices;
+
 namespace System
 {
-    public class Type
+    public abstract class Type : MemberInfo
     {
+        public extern string Namespace
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        public extern string FullName
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        public extern Type BaseType
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        public extern bool IsValueType
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        public extern bool IsInterface
+        {
+            [MethodImpl(MethodImplOptions.InternalCall)]
+            get;
+        }
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern static Type GetTypeFromHandle(RuntimeTypeHandle handle);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern bool IsAssignableFrom(Type c);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern Type[] GetInterfaces();
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern MethodInfo[] GetMethods();
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern MethodInfo GetMethod(string name);
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern FieldInfo[] GetFields();
+
+        [MethodImpl(MethodImplOptions.InternalCall)]
+        public extern FieldInfo GetField(string name);
+
         public override string ToString()
         {
-            return "Something";
+            return FullName;
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,19 @@
+namespace System
+{
+    public sealed class TypeInitializationException : SystemException
+    {
+        private string _typeName;
+
+        // The runtime creates this exception when a type initializer throws, with the message it formats
+        internal TypeInitializationException(string fullTypeName, string message, Exception innerException)
+            : base(message, innerException)
+        {
+            _typeName = fullTypeName;
+        }
+
+        public string TypeName
+        {
+            get { return _typeName; }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+namespace System
+{
+    public class TypeLoadException : SystemException
+    {
+        public TypeLoadException()
+        {
+        }
+
+        public TypeLoadException(string message)
+            : base(message)
+        {
+        }
+    }
+}
\ No newline at end of file
@@ -1,15 +1,21 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::{Path, PathBuf};
 
 use slog;
 use memmap;
 
+use ecma355metadata::strong_name::{self, StrongNameStatus};
+
 use error::Error;
 use assembly::Assembly;
-use loader;
 
 pub struct AppContext {
     base_directory: PathBuf,
+    probe_directories: Vec<PathBuf>,
+    framework_directories: Vec<PathBuf>,
+    known_assemblies: HashMap<String, PathBuf>,
+    verify_strong_names: bool,
     logger: slog::Logger,
 }
 
@@ -17,19 +23,47 @@ impl AppContext {
     pub fn new<P: Into<PathBuf>>(base_directory: P, logger: slog::Logger) -> AppContext {
         AppContext {
             base_directory: base_directory.into(),
+            probe_directories: Vec::new(),
+            framework_directories: Vec::new(),
+            known_assemblies: HashMap::new(),
+            verify_strong_names: false,
             logger: logger,
         }
     }
 
+    /// Gets the application's directory.
+    pub fn base_directory(&self) -> &Path {
+        &self.base_directory
+    }
+
+    /// Adds a directory to probe for assemblies, after the base directory and any directories already added.
+    pub fn add_probe_directory<P: Into<PathBuf>>(&mut self, directory: P) {
+        self.probe_directories.push(directory.into());
+    }
+
+    /// Adds a directory containing the implementation of a shared framework, which is probed after all other
+    /// directories. Assemblies loaded from it may be signed with the ECMA key.
+    pub fn add_framework_directory<P: Into<PathBuf>>(&mut self, directory: P) {
+        self.framework_directories.push(directory.into());
+    }
+
+    /// Records the exact path for an assembly (from a `.deps.json` file, for example). Known assemblies are
+    /// used before any directory probing takes place.
+    pub fn add_known_assembly<S: Into<String>, P: Into<PathBuf>>(&mut self, assembly_name: S, path: P) {
+        self.known_assemblies.insert(assembly_name.into(), path.into());
+    }
+
+    /// Sets whether to verify the strong-name signature of each assembly as it is loaded, and refuse to load any
+    /// assembly whose signature can't be shown to match its contents.
+    pub fn set_verify_strong_names(&mut self, verify_strong_names: bool) {
+        self.verify_strong_names = verify_strong_names;
+    }
+
     pub fn load(&mut self, assembly_name: &str) -> Result<Assembly, Error> {
         // Resolve the path
         let logger = self.logger
             .new(o!("assembly_name" => assembly_name.to_owned()));
-        let assembly_path = resolve_assembly(
-            &self.base_directory,
-            assembly_name,
-            &logger,
-        )?;
+        let (assembly_path, from_framework) = self.resolve_assembly(assembly_name, &logger)?;
 
         info!(logger, "loading {} from {}", assembly_name, assembly_path.display());
 
@@ -40,26 +74,108 @@ impl AppContext {
                 .map(&file)?
         };
 
-        let asm = Assembly::load(mmap, &logger)?;
-        unimplemented!()
+        let assembly = Assembly::load(mmap, &logger)?;
+        if self.verify_strong_names {
+            let status = strong_name::verify(assembly.image())?;
+            debug!(logger, "strong name signature: {}", status);
+            if !strong_name_allowed(status, from_framework) {
+                return Err(Error::InvalidStrongName(assembly_name.into()));
+            }
+        }
+        Ok(assembly)
+    }
+
+    /// Finds the path of an assembly, and whether it was found in a framework directory.
+    fn resolve_assembly(&self, assembly_name: &str, logger: &slog::Logger) -> Result<(PathBuf, bool), Error> {
+        if let Some(path) = self.known_assemblies.get(assembly_name) {
+            debug!(logger, "using known path: {}", path.display(); "candidate_path" => path.display());
+            return Ok((path.clone(), false));
+        }
+
+        let app_directories = ::std::iter::once(&self.base_directory)
+            .chain(self.probe_directories.iter())
+            .map(|dir| (dir, false));
+        let framework_directories = self.framework_directories.iter().map(|dir| (dir, true));
+        app_directories
+            .chain(framework_directories)
+            .filter_map(|(dir, from_framework)| {
+                probe_directory(dir, assembly_name, logger).map(|path| (path, from_framework))
+            })
+            .next()
+            .ok_or(Error::AssemblyNotFound(assembly_name.into()))
+    }
+}
+
+/// Decides whether an assembly with the specified strong-name status can be loaded when signatures are verified.
+///
+/// Clearing the signed flag turns a tampered assembly into a delay-signed one, and the ECMA key can be put on any
+/// assembly, so delay-signed assemblies are refused and the ECMA key is only trusted for the framework itself.
+fn strong_name_allowed(status: StrongNameStatus, from_framework: bool) -> bool {
+    match status {
+        StrongNameStatus::Valid | StrongNameStatus::NotSigned => true,
+        StrongNameStatus::EcmaKey => from_framework,
+        StrongNameStatus::DelaySigned | StrongNameStatus::Invalid => false,
     }
 }
 
 const ASSEMBLY_EXTENSIONS: [&'static str; 2] = ["exe", "dll"];
-fn resolve_assembly(
-    base_directory: &Path,
+fn probe_directory(
+    directory: &Path,
     assembly_name: &str,
     logger: &slog::Logger,
-) -> Result<PathBuf, Error> {
-    let result = ASSEMBLY_EXTENSIONS
+) -> Option<PathBuf> {
+    ASSEMBLY_EXTENSIONS
         .iter()
         .map(|ext| {
             let p = PathBuf::from(assembly_name).with_extension(ext);
-            base_directory.join(p)
+            directory.join(p)
         })
         .find(|p| {
             debug!(logger, "trying path: {}", p.display(); "candidate_path" => p.to_path_buf().display());
             p.exists()
-        });
-    result.ok_or(Error::AssemblyNotFound(assembly_name.into()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use test_directory::TestDirectory;
+
+    #[test]
+    pub fn strong_name_policy() {
+        for &from_framework in &[false, true] {
+            assert!(strong_name_allowed(StrongNameStatus::Valid, from_framework));
+            assert!(strong_name_allowed(StrongNameStatus::NotSigned, from_framework));
+            assert!(!strong_name_allowed(StrongNameStatus::DelaySigned, from_framework));
+            assert!(!strong_name_allowed(StrongNameStatus::Invalid, from_framework));
+        }
+        assert!(strong_name_allowed(StrongNameStatus::EcmaKey, true));
+        assert!(!strong_name_allowed(StrongNameStatus::EcmaKey, false));
+    }
+
+    #[test]
+    pub fn resolves_framework_assemblies_last() {
+        let root = TestDirectory::new("app_context");
+        let app_dir = root.join("app");
+        let fx_dir = root.join("fx");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::create_dir_all(&fx_dir).unwrap();
+        fs::write(app_dir.join("App.exe"), b"").unwrap();
+        fs::write(fx_dir.join("App.dll"), b"").unwrap();
+        fs::write(fx_dir.join("corlib.dll"), b"").unwrap();
+
+        let logger = slog::Logger::root(slog::Discard, o!());
+        let mut context = AppContext::new(&app_dir, logger.clone());
+        context.add_framework_directory(&fx_dir);
+
+        // An app assembly can't claim to come from the framework by sharing a name with one
+        assert_eq!((app_dir.join("App.exe"), false), context.resolve_assembly("App", &logger).unwrap());
+        assert_eq!((fx_dir.join("corlib.dll"), true), context.resolve_assembly("corlib", &logger).unwrap());
+
+        context.add_known_assembly("corlib", app_dir.join("App.exe"));
+        assert_eq!((app_dir.join("App.exe"), false), context.resolve_assembly("corlib", &logger).unwrap());
+    }
 }
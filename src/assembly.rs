@@ -1,34 +1,24 @@
-use std::ops::Deref;
-
 use memmap;
 use slog;
 
 use error::Error;
 
-use ecma355metadata;
+use ecma355metadata::MetadataImage;
 
 pub struct Assembly {
-    image: 
+    image: MetadataImage<memmap::Mmap>,
 }
 
 impl Assembly {
     pub fn load(data: memmap::Mmap, logger: &slog::Logger) -> Result<Assembly, Error> {
-        let mut cursor = ::std::io::Cursor::new(data.deref());
-
-        debug!(logger, "loading PE image...");
-        let image = ecma355metadata::PeImage::read(&mut cursor)?;
-        debug!(logger, "loaded PE image.");
+        debug!(logger, "loading metadata image...");
+        let image = MetadataImage::load_data(data)?;
+        debug!(logger, "loaded metadata image.");
 
-        // Load CLI metadata
-        debug!(logger, "reading CLI header...");
-        let cli_header = ecma355metadata::CliHeader::from_pe_image(&image)?;
-        debug!(logger, "read CLI header.");
-
-        // Create a metadata reader
-        debug!(logger, "reading metadata headers...");
-        let metadata = ecma355metadata::MetadataReader::from_pe_image(&image, &cli_header)?;
-        debug!(logger, "read metadata headers.");
+        Ok(Assembly { image })
+    }
 
-        unimplemented!()
+    pub fn image(&self) -> &MetadataImage<memmap::Mmap> {
+        &self.image
     }
 }
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use serde::de::IgnoredAny;
+use serde_json;
+
+use error::Error;
+
+/// Represents the contents of an application's `<app>.deps.json` file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DepsFile {
+    runtime_target: RuntimeTarget,
+    #[serde(default)]
+    targets: HashMap<String, HashMap<String, TargetLibrary>>,
+    #[serde(default)]
+    libraries: HashMap<String, Library>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuntimeTarget {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TargetLibrary {
+    #[serde(default)]
+    runtime: HashMap<String, IgnoredAny>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Library {
+    #[serde(rename = "type")]
+    library_type: String,
+    path: Option<String>,
+}
+
+/// A runtime assembly listed by a `.deps.json` file, resolved to a path on disk.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DependencyAssembly {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl DepsFile {
+    pub fn load(path: &Path) -> Result<DepsFile, Error> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub fn parse(content: &str) -> Result<DepsFile, Error> {
+        Ok(serde_json::from_str(content)?)
+    }
+
+    /// Resolves the runtime assemblies for the runtime target to paths on disk.
+    ///
+    /// Assemblies from `project` libraries live in the application directory. Assemblies from `package`
+    /// libraries are looked up in each of the `package_roots` (using the library's `path`), falling back to the
+    /// application directory for published applications. Assemblies that can't be found are skipped, and left
+    /// for normal probing.
+    pub fn resolve_assemblies(&self, app_directory: &Path, package_roots: &[PathBuf]) -> Vec<DependencyAssembly> {
+        let target = match self.targets.get(&self.runtime_target.name) {
+            Some(x) => x,
+            None => return Vec::new(),
+        };
+
+        let mut assemblies = Vec::new();
+        for (library_name, target_library) in target.iter() {
+            let library = self.libraries.get(library_name);
+            for asset in target_library.runtime.keys() {
+                let asset_path = Path::new(asset);
+                let name = match asset_path.file_stem().and_then(|x| x.to_str()) {
+                    Some(x) => x.to_owned(),
+                    None => continue,
+                };
+
+                let mut candidates = Vec::new();
+                if let Some(&Library { ref library_type, path: Some(ref path) }) = library {
+                    if library_type == "package" {
+                        for root in package_roots {
+                            candidates.push(root.join(path).join(asset_path));
+                        }
+                    }
+                }
+                if let Some(file_name) = asset_path.file_name() {
+                    candidates.push(app_directory.join(file_name));
+                }
+
+                if let Some(path) = candidates.into_iter().find(|p| p.exists()) {
+                    assemblies.push(DependencyAssembly { name, path });
+                }
+            }
+        }
+        assemblies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use test_directory::TestDirectory;
+
+    const DEPS: &str = r#"{
+        "runtimeTarget": { "name": "CrustyCLR,Version=v0.1" },
+        "targets": {
+            "CrustyCLR,Version=v0.1": {
+                "HelloWorld/1.0.0": {
+                    "dependencies": { "Utils": "2.0.0" },
+                    "runtime": { "HelloWorld.dll": {} }
+                },
+                "Utils/2.0.0": {
+                    "runtime": { "lib/crustyclr0.1/Utils.dll": { "assemblyVersion": "2.0.0.0" } }
+                },
+                "Missing/1.0.0": {
+                    "runtime": { "lib/crustyclr0.1/Missing.dll": {} }
+                }
+            },
+            "Other,Version=v1.0": {
+                "Unrelated/1.0.0": { "runtime": { "Unrelated.dll": {} } }
+            }
+        },
+        "libraries": {
+            "HelloWorld/1.0.0": { "type": "project", "serviceable": false, "sha512": "" },
+            "Utils/2.0.0": { "type": "package", "path": "utils/2.0.0" },
+            "Missing/1.0.0": { "type": "package", "path": "missing/1.0.0" }
+        }
+    }"#;
+
+    #[test]
+    pub fn resolves_project_and_package_assemblies() {
+        let root = TestDirectory::new("deps_file");
+        let app_dir = root.join("app");
+        let packages = root.join("packages");
+        let package_lib_dir = packages.join("utils/2.0.0/lib/crustyclr0.1");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::create_dir_all(&package_lib_dir).unwrap();
+        File::create(app_dir.join("HelloWorld.dll")).unwrap();
+        File::create(package_lib_dir.join("Utils.dll")).unwrap();
+
+        let deps = DepsFile::parse(DEPS).unwrap();
+        let mut assemblies = deps.resolve_assemblies(&app_dir, ::std::slice::from_ref(&packages));
+        assemblies.sort_by(|l, r| l.name.cmp(&r.name));
+
+        assert_eq!(
+            vec![
+                DependencyAssembly { name: "HelloWorld".into(), path: app_dir.join("HelloWorld.dll") },
+                DependencyAssembly { name: "Utils".into(), path: package_lib_dir.join("Utils.dll") },
+            ],
+            assemblies
+        );
+    }
+}
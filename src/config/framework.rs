@@ -0,0 +1,164 @@
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use xml::reader::{EventReader, XmlEvent};
+
+use config::FrameworkReference;
+use error::Error;
+
+/// Represents a shared framework, resolved against a framework root (the `fx` directory).
+///
+/// A framework named `Name` with version `X.Y` is described by `<fx>/referenceAssemblies/Name/vX.Y`, which must have a
+/// `RedistList/FrameworkList.xml` file listing the assemblies it provides. Those are reference assemblies, which are
+/// only for compiling against; the implementation of each listed assembly is the output of building the project in
+/// `<fx>/<AssemblyName>` (for example, `<fx>/corlib/bin/Debug/<tfm>/corlib.dll`).
+#[derive(Debug)]
+pub struct Framework {
+    pub name: String,
+    pub version: String,
+    pub directory: PathBuf,
+    pub assemblies: Vec<String>,
+}
+
+impl Framework {
+    pub fn resolve(fx_root: &Path, reference: &FrameworkReference) -> Result<Framework, Error> {
+        let directory = fx_root
+            .join("referenceAssemblies")
+            .join(&reference.name)
+            .join(format!("v{}", reference.version));
+        let framework_list = directory.join("RedistList").join("FrameworkList.xml");
+        if !framework_list.exists() {
+            return Err(Error::FrameworkNotFound(format!("{} v{}", reference.name, reference.version)));
+        }
+
+        let assemblies = read_framework_list(BufReader::new(File::open(&framework_list)?))?;
+
+        Ok(Framework {
+            name: reference.name.clone(),
+            version: reference.version.clone(),
+            directory,
+            assemblies,
+        })
+    }
+
+    /// Gets the directories to probe for the implementations of the assemblies in this framework.
+    ///
+    /// These are the build output directories of each assembly's project, `bin/<Configuration>` and the target
+    /// framework directories inside it, with `Debug` (the default for `dotnet build`) ahead of `Release`.
+    pub fn probe_directories(&self, fx_root: &Path) -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+        for assembly in self.assemblies.iter() {
+            for configuration in BUILD_CONFIGURATIONS.iter() {
+                let output = fx_root.join(assembly).join("bin").join(configuration);
+                if output.is_dir() {
+                    let target_frameworks = subdirectories(&output);
+                    dirs.push(output);
+                    dirs.extend(target_frameworks);
+                }
+            }
+        }
+        dirs
+    }
+}
+
+const BUILD_CONFIGURATIONS: [&str; 2] = ["Debug", "Release"];
+
+/// Lists the directories in a directory, sorted so that probing is deterministic.
+fn subdirectories(directory: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<_> = fs::read_dir(directory)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect())
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+fn read_framework_list<R: Read>(reader: R) -> Result<Vec<String>, Error> {
+    let mut assemblies = Vec::new();
+    for event in EventReader::new(reader) {
+        match event {
+            Ok(XmlEvent::StartElement { ref name, ref attributes, .. }) if name.local_name == "File" => {
+                if let Some(attr) = attributes.iter().find(|a| a.name.local_name == "AssemblyName") {
+                    assemblies.push(attr.value.clone());
+                }
+            }
+            Ok(_) => {}
+            Err(e) => return Err(Error::InvalidConfiguration(format!("invalid FrameworkList.xml: {}", e))),
+        }
+    }
+    Ok(assemblies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use test_directory::TestDirectory;
+
+    #[test]
+    pub fn reads_framework_list() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+            <FileList Name="CrustyCLR v0.1">
+                <File AssemblyName="corlib" Version="0.1.0.0" />
+                <File AssemblyName="System.Other" />
+            </FileList>"#;
+        assert_eq!(
+            vec!["corlib".to_owned(), "System.Other".to_owned()],
+            read_framework_list(xml.as_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn resolves_repository_framework() {
+        let fx_root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fx");
+        let reference = FrameworkReference {
+            name: "CrustyCLR".into(),
+            version: "0.1".into(),
+        };
+        let framework = Framework::resolve(&fx_root, &reference).unwrap();
+        assert_eq!(fx_root.join("referenceAssemblies/CrustyCLR/v0.1"), framework.directory);
+        assert_eq!(vec!["corlib".to_owned()], framework.assemblies);
+    }
+
+    #[test]
+    pub fn probes_build_output() {
+        let fx_root = TestDirectory::new("framework");
+        let reference_dir = fx_root.join("referenceAssemblies/CrustyCLR/v0.1");
+        fs::create_dir_all(reference_dir.join("RedistList")).unwrap();
+        fs::write(
+            reference_dir.join("RedistList/FrameworkList.xml"),
+            r#"<FileList><File AssemblyName="corlib" /></FileList>"#,
+        ).unwrap();
+        fs::create_dir_all(fx_root.join("corlib/bin/Debug/crustyclr0.1")).unwrap();
+        fs::create_dir_all(fx_root.join("corlib/bin/Release")).unwrap();
+
+        let reference = FrameworkReference {
+            name: "CrustyCLR".into(),
+            version: "0.1".into(),
+        };
+        let framework = Framework::resolve(&fx_root, &reference).unwrap();
+
+        // The reference assemblies are never probed
+        assert_eq!(
+            vec![
+                fx_root.join("corlib/bin/Debug"),
+                fx_root.join("corlib/bin/Debug/crustyclr0.1"),
+                fx_root.join("corlib/bin/Release"),
+            ],
+            framework.probe_directories(&fx_root)
+        );
+    }
+
+    #[test]
+    pub fn missing_framework() {
+        let fx_root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fx");
+        let reference = FrameworkReference {
+            name: "CrustyCLR".into(),
+            version: "9.9".into(),
+        };
+        assert_eq!(
+            Error::FrameworkNotFound("CrustyCLR v9.9".into()),
+            Framework::resolve(&fx_root, &reference).unwrap_err()
+        );
+    }
+}
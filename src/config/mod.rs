@@ -0,0 +1,7 @@
+mod deps_file;
+mod framework;
+mod runtime_config;
+
+pub use self::deps_file::{DependencyAssembly, DepsFile};
+pub use self::framework::Framework;
+pub use self::runtime_config::{FrameworkReference, RuntimeConfig, RuntimeOptions};
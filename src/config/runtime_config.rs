@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use error::Error;
+
+/// Represents the contents of an application's `<app>.runtimeconfig.json` file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeConfig {
+    #[serde(default)]
+    pub runtime_options: RuntimeOptions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeOptions {
+    pub tfm: Option<String>,
+    pub framework: Option<FrameworkReference>,
+    #[serde(default)]
+    pub frameworks: Vec<FrameworkReference>,
+    #[serde(default)]
+    pub additional_probing_paths: Vec<PathBuf>,
+}
+
+/// A reference to a shared framework, by name and version.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FrameworkReference {
+    pub name: String,
+    pub version: String,
+}
+
+impl RuntimeConfig {
+    pub fn load(path: &Path) -> Result<RuntimeConfig, Error> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub fn parse(content: &str) -> Result<RuntimeConfig, Error> {
+        Ok(serde_json::from_str(content)?)
+    }
+
+    /// Gets all the frameworks referenced by the application.
+    ///
+    /// Older files use a single `framework` property, newer ones use a `frameworks` array.
+    /// If both are present, the single framework comes first.
+    pub fn frameworks(&self) -> Vec<&FrameworkReference> {
+        self.runtime_options
+            .framework
+            .iter()
+            .chain(self.runtime_options.frameworks.iter())
+            .collect()
+    }
+
+    pub fn additional_probing_paths(&self) -> &Vec<PathBuf> {
+        &self.runtime_options.additional_probing_paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn single_framework() {
+        let config = RuntimeConfig::parse(
+            r#"{
+                "runtimeOptions": {
+                    "tfm": "crustyclr0.1",
+                    "framework": { "name": "CrustyCLR", "version": "0.1" }
+                }
+            }"#,
+        ).unwrap();
+
+        assert_eq!(Some("crustyclr0.1"), config.runtime_options.tfm.as_deref());
+        assert_eq!(
+            vec![&FrameworkReference { name: "CrustyCLR".into(), version: "0.1".into() }],
+            config.frameworks()
+        );
+        assert!(config.additional_probing_paths().is_empty());
+    }
+
+    #[test]
+    pub fn framework_array_and_probing_paths() {
+        let config = RuntimeConfig::parse(
+            r#"{
+                "runtimeOptions": {
+                    "frameworks": [
+                        { "name": "CrustyCLR", "version": "0.1" },
+                        { "name": "Other", "version": "1.0" }
+                    ],
+                    "additionalProbingPaths": [ "/packages" ]
+                }
+            }"#,
+        ).unwrap();
+
+        assert_eq!(2, config.frameworks().len());
+        assert_eq!("Other", config.frameworks()[1].name);
+        assert_eq!(&vec![PathBuf::from("/packages")], config.additional_probing_paths());
+    }
+
+    #[test]
+    pub fn empty_config() {
+        let config = RuntimeConfig::parse("{}").unwrap();
+        assert!(config.frameworks().is_empty());
+    }
+}
@@ -1,12 +1,127 @@
 use std::io;
 
 use ecma355metadata;
+use serde_json;
 
 #[derive(Debug)]
 pub enum Error {
     AssemblyNotFound(String),
     BadImageFormat(ecma355metadata::Error),
+
+    /// An exception the runtime raised while executing managed code, such as when it dereferenced null. The
+    /// interpreter throws it to the program as an instance of the corresponding corlib exception type.
+    Exception(ExceptionKind),
+    FrameworkNotFound(String),
+    InvalidConfiguration(String),
+    InvalidProgram(String),
+    InvalidStrongName(String),
     IoError(io::Error),
+    MissingField(String),
+    MissingMethod(String),
+    TypeLoad(String),
+    UnhandledException(String),
+}
+
+/// An exception the runtime raises while executing managed code, such as when it dereferences null.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExceptionKind {
+    AmbiguousMatch,
+    Argument,
+    ArgumentNull,
+    ArrayTypeMismatch,
+    DivideByZero,
+    DllNotFound,
+    EntryPointNotFound,
+    FieldAccess,
+    IndexOutOfRange,
+    InvalidCast,
+    InvalidProgram,
+    MarshalDirective,
+    MissingMethod,
+    NotSupported,
+    NullReference,
+    Overflow,
+    PlatformNotSupported,
+    SynchronizationLock,
+    Target,
+    TargetParameterCount,
+    ThreadState,
+}
+
+impl ExceptionKind {
+    /// Gets the message of exceptions the runtime raises, like the ones the .NET runtime gives.
+    pub fn message(self) -> &'static str {
+        match self {
+            ExceptionKind::AmbiguousMatch => "Ambiguous match found.",
+            ExceptionKind::Argument => "Value does not fall within the expected range.",
+            ExceptionKind::ArgumentNull => "Value cannot be null.",
+            ExceptionKind::ArrayTypeMismatch => "Attempted to access an element as a type incompatible with the array.",
+            ExceptionKind::DivideByZero => "Attempted to divide by zero.",
+            ExceptionKind::DllNotFound => "Dll was not found.",
+            ExceptionKind::EntryPointNotFound => "Entry point was not found.",
+            ExceptionKind::FieldAccess => "Attempted to access a field that is not accessible by the caller.",
+            ExceptionKind::IndexOutOfRange => "Index was outside the bounds of the array.",
+            ExceptionKind::InvalidCast => "Specified cast is not valid.",
+            ExceptionKind::InvalidProgram => "Common Language Runtime detected an invalid program.",
+            ExceptionKind::MarshalDirective => "Marshaling directives are invalid.",
+            ExceptionKind::MissingMethod => "Attempted to access a missing method.",
+            ExceptionKind::NotSupported => "Specified method is not supported.",
+            ExceptionKind::NullReference => "Object reference not set to an instance of an object.",
+            ExceptionKind::Overflow => "Arithmetic operation resulted in an overflow.",
+            ExceptionKind::PlatformNotSupported => "Operation is not supported on this platform.",
+            ExceptionKind::SynchronizationLock => {
+                "Object synchronization method was called from an unsynchronized block of code."
+            }
+            ExceptionKind::Target => "Error in the application.",
+            ExceptionKind::TargetParameterCount => "Parameter count mismatch.",
+            ExceptionKind::ThreadState => "Thread is running or terminated; it cannot restart.",
+        }
+    }
+
+    /// Gets the namespace of the exception type.
+    pub fn namespace(self) -> &'static str {
+        match self {
+            ExceptionKind::MarshalDirective => "System.Runtime.InteropServices",
+            ExceptionKind::AmbiguousMatch | ExceptionKind::Target | ExceptionKind::TargetParameterCount => {
+                "System.Reflection"
+            }
+            ExceptionKind::SynchronizationLock | ExceptionKind::ThreadState => "System.Threading",
+            _ => "System",
+        }
+    }
+
+    /// Gets the name of the exception type in its namespace.
+    pub fn type_name(self) -> &'static str {
+        match self {
+            ExceptionKind::AmbiguousMatch => "AmbiguousMatchException",
+            ExceptionKind::Argument => "ArgumentException",
+            ExceptionKind::ArgumentNull => "ArgumentNullException",
+            ExceptionKind::ArrayTypeMismatch => "ArrayTypeMismatchException",
+            ExceptionKind::DivideByZero => "DivideByZeroException",
+            ExceptionKind::DllNotFound => "DllNotFoundException",
+            ExceptionKind::EntryPointNotFound => "EntryPointNotFoundException",
+            ExceptionKind::FieldAccess => "FieldAccessException",
+            ExceptionKind::IndexOutOfRange => "IndexOutOfRangeException",
+            ExceptionKind::InvalidCast => "InvalidCastException",
+            ExceptionKind::InvalidProgram => "InvalidProgramException",
+            ExceptionKind::MarshalDirective => "MarshalDirectiveException",
+            ExceptionKind::MissingMethod => "MissingMethodException",
+            ExceptionKind::NotSupported => "NotSupportedException",
+            ExceptionKind::NullReference => "NullReferenceException",
+            ExceptionKind::Overflow => "OverflowException",
+            ExceptionKind::PlatformNotSupported => "PlatformNotSupportedException",
+            ExceptionKind::SynchronizationLock => "SynchronizationLockException",
+            ExceptionKind::Target => "TargetException",
+            ExceptionKind::TargetParameterCount => "TargetParameterCountException",
+            ExceptionKind::ThreadState => "ThreadStateException",
+        }
+    }
+}
+
+impl From<ExceptionKind> for Error {
+    fn from(v: ExceptionKind) -> Error {
+        Error::Exception(v)
+    }
 }
 
 impl From<io::Error> for Error {
@@ -21,6 +136,12 @@ impl From<ecma355metadata::Error> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(v: serde_json::Error) -> Error {
+        Error::InvalidConfiguration(v.to_string())
+    }
+}
+
 // Manual implementation because io::Error doesn't implement PartialEq, so we can't derive... but it's
 // OK with us if IoError != IoError because this is mostly for testing.
 // We don't implement Eq though, because Eq implies `l.eq(r)` will always be `true` for the same `l`, `r`
@@ -30,6 +151,15 @@ impl PartialEq for Error {
         match (self, other) {
             (&Error::AssemblyNotFound(ref lhs), &Error::AssemblyNotFound(ref rhs)) => lhs.eq(rhs),
             (&Error::BadImageFormat(ref lhs), &Error::BadImageFormat(ref rhs)) => lhs.eq(rhs),
+            (&Error::Exception(lhs), &Error::Exception(rhs)) => lhs == rhs,
+            (&Error::FrameworkNotFound(ref lhs), &Error::FrameworkNotFound(ref rhs)) => lhs.eq(rhs),
+            (&Error::InvalidConfiguration(ref lhs), &Error::InvalidConfiguration(ref rhs)) => lhs.eq(rhs),
+            (&Error::InvalidProgram(ref lhs), &Error::InvalidProgram(ref rhs)) => lhs.eq(rhs),
+            (&Error::InvalidStrongName(ref lhs), &Error::InvalidStrongName(ref rhs)) => lhs.eq(rhs),
+            (&Error::MissingField(ref lhs), &Error::MissingField(ref rhs)) => lhs.eq(rhs),
+            (&Error::MissingMethod(ref lhs), &Error::MissingMethod(ref rhs)) => lhs.eq(rhs),
+            (&Error::TypeLoad(ref lhs), &Error::TypeLoad(ref rhs)) => lhs.eq(rhs),
+            (&Error::UnhandledException(ref lhs), &Error::UnhandledException(ref rhs)) => lhs.eq(rhs),
             _ => false, // Type mismatches and IoError are never equal
         }
     }
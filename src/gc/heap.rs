@@ -0,0 +1,628 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::ptr;
+
+use gc::object::{self, ObjectLayout, ObjectRef, HEADER_SIZE, MARK_BIT};
+use types::TypeId;
+
+/// The size of the segments objects are allocated in. Larger objects get a segment of their own.
+const SEGMENT_SIZE: usize = 1 << 20;
+
+/// The size of the nursery new objects are allocated in, in generational mode.
+const NURSERY_SIZE: usize = 1 << 20;
+
+/// Objects larger than this are allocated straight into the old generation, in generational mode.
+const LARGE_OBJECT_SIZE: usize = 64 << 10;
+
+/// The number of bytes of a segment each card in its card table covers.
+const CARD_SIZE: usize = 512;
+
+/// How many bytes can be allocated before the first collection. After that, a collection happens once as many
+/// bytes have been allocated as survived the last one, or this many, whichever is larger.
+const INITIAL_THRESHOLD: usize = 4 << 20;
+
+/// The type word of a free block, whose size is in its sync word.
+const FREE: usize = !0;
+
+/// The type word of a nursery object that has been copied to the old generation, whose new address is in its sync
+/// word.
+const FORWARDED: usize = !1;
+
+/// How the heap collects garbage.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GcMode {
+    /// Objects never move. Each collection marks the whole heap, and sweeps unreachable objects into free lists.
+    MarkSweep,
+
+    /// New objects are allocated in a nursery, and each collection copies the ones that survive into the old
+    /// generation. Once enough has been copied, the collection also compacts the old generation.
+    Generational,
+}
+
+/// Counters describing the managed heap and its collections.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// The number of collections so far.
+    pub collections: u64,
+
+    /// The number of collections that collected the whole heap, rather than just the nursery.
+    pub full_collections: u64,
+
+    /// The number of objects, and the bytes they use, allocated since the heap was created.
+    pub objects_allocated: u64,
+    pub bytes_allocated: u64,
+
+    /// The bytes copied from the nursery to the old generation.
+    pub bytes_promoted: u64,
+
+    /// The bytes used by objects that survived the last full collection.
+    pub live_bytes: usize,
+
+    /// The bytes reserved for segments and the nursery, whether they are used or not.
+    pub heap_size: usize,
+}
+
+struct Segment {
+    memory: Box<[u64]>,
+
+    /// The number of bytes at the start of the segment that are in use, by objects or free blocks.
+    top: usize,
+
+    /// Whether each card of the segment has had object references stored in it since the last collection, and
+    /// whether any has.
+    cards: Box<[bool]>,
+    dirty: bool,
+}
+
+impl Segment {
+    fn new(size: usize) -> Segment {
+        Segment {
+            memory: vec![0; size / 8].into_boxed_slice(),
+            top: 0,
+            cards: vec![false; size.div_ceil(CARD_SIZE)].into_boxed_slice(),
+            dirty: false,
+        }
+    }
+
+    fn start(&self) -> usize {
+        self.memory.as_ptr() as usize
+    }
+
+    fn size(&self) -> usize {
+        self.memory.len() * 8
+    }
+
+    fn contains(&self, address: usize) -> bool {
+        address >= self.start() && address < self.start() + self.top
+    }
+
+    fn mark_cards(&mut self, offset: usize, size: usize) {
+        for card in &mut self.cards[offset / CARD_SIZE..=(offset + size - 1) / CARD_SIZE] {
+            *card = true;
+        }
+        self.dirty = true;
+    }
+
+    fn is_dirty(&self, offset: usize, size: usize) -> bool {
+        self.cards[offset / CARD_SIZE..=(offset + size - 1) / CARD_SIZE].contains(&true)
+    }
+
+    fn clear_cards(&mut self) {
+        if self.dirty {
+            self.cards.iter_mut().for_each(|card| *card = false);
+            self.dirty = false;
+        }
+    }
+}
+
+/// The managed heap, which allocates objects and frees unreachable ones in one of the `GcMode`s.
+///
+/// The old generation (the whole heap, in mark-sweep mode) is a list of segments. Free space in it is tracked in a
+/// first-fit free list, which only mark-sweep mode leaves anything in. In generational mode, stores of object
+/// references into the old generation must be recorded with `write_barrier`, so that the nursery objects they refer
+/// to survive.
+pub struct Heap {
+    mode: GcMode,
+    stress: bool,
+    segments: Vec<Segment>,
+    free: Vec<(usize, usize)>,
+    nursery: Box<[u64]>,
+    nursery_top: usize,
+    layouts: Vec<Option<ObjectLayout>>,
+
+    /// The bytes allocated in the old generation since the last full collection.
+    allocated_since_collection: usize,
+    threshold: usize,
+    stats: GcStats,
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap {
+            mode: GcMode::MarkSweep,
+            stress: false,
+            segments: Vec::new(),
+            free: Vec::new(),
+            nursery: Box::new([]),
+            nursery_top: 0,
+            layouts: Vec::new(),
+            allocated_since_collection: 0,
+            threshold: INITIAL_THRESHOLD,
+            stats: GcStats::default(),
+        }
+    }
+
+    /// Sets how garbage is collected, which can only be done before anything is allocated.
+    pub fn set_mode(&mut self, mode: GcMode) {
+        assert_eq!(0, self.stats.objects_allocated, "The GC mode can't change once objects are allocated");
+        self.mode = mode;
+        self.nursery = match mode {
+            GcMode::MarkSweep => Box::new([]),
+            GcMode::Generational => vec![0; NURSERY_SIZE / 8].into_boxed_slice(),
+        };
+        self.stats.heap_size = self.nursery_size();
+    }
+
+    /// Sets whether every allocation is preceded by a full collection, which is slow but makes objects move (in
+    /// generational mode) or be freed as soon as possible, exposing references the roots don't include.
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    /// Records the layout of a type's instances, which must be done before any are allocated.
+    pub fn set_layout(&mut self, type_id: TypeId, layout: ObjectLayout) {
+        if self.layouts.len() <= type_id.index() {
+            self.layouts.resize(type_id.index() + 1, None);
+        }
+        self.layouts[type_id.index()] = Some(layout);
+    }
+
+    pub fn layout(&self, type_id: TypeId) -> Option<&ObjectLayout> {
+        self.layouts.get(type_id.index()).and_then(|layout| layout.as_ref())
+    }
+
+    unsafe fn object_layout(&self, object: ObjectRef) -> &ObjectLayout {
+        self.layout(object.type_id()).expect("An object's type has no layout")
+    }
+
+    /// Gets the size of an object or free block in a segment.
+    unsafe fn block_size(&self, address: usize) -> usize {
+        let object = ObjectRef(address);
+        if *(address as *const usize) == FREE {
+            *object.sync_word()
+        } else {
+            self.object_layout(object).object_size(object)
+        }
+    }
+
+    fn nursery_size(&self) -> usize {
+        self.nursery.len() * 8
+    }
+
+    fn in_nursery(&self, object: ObjectRef) -> bool {
+        let start = self.nursery.as_ptr() as usize;
+        object.0 >= start && object.0 < start + self.nursery_top
+    }
+
+    /// Returns `true` if allocating `size` more bytes should be preceded by a collection.
+    pub fn should_collect(&self, size: usize) -> bool {
+        if self.stress {
+            return true;
+        }
+        match self.mode {
+            GcMode::Generational if size <= LARGE_OBJECT_SIZE => self.nursery_top + size > self.nursery_size(),
+            _ => self.allocated_since_collection + size > self.threshold,
+        }
+    }
+
+    /// Allocates a zeroed object of `size` bytes, including its header, for a type with a layout.
+    pub fn allocate(&mut self, type_id: TypeId, size: usize) -> ObjectRef {
+        debug_assert!(self.layout(type_id).is_some() && size >= HEADER_SIZE && size & 7 == 0);
+        let address = if size <= LARGE_OBJECT_SIZE && self.nursery_top + size <= self.nursery_size() {
+            let address = self.nursery.as_ptr() as usize + self.nursery_top;
+            self.nursery_top += size;
+            address
+        } else {
+            self.allocated_since_collection += size;
+            self.allocate_old(size)
+        };
+        unsafe {
+            ptr::write_bytes(address as *mut u8, 0, size);
+            *(address as *mut usize) = type_id.index();
+        }
+
+        self.stats.objects_allocated += 1;
+        self.stats.bytes_allocated += size as u64;
+        ObjectRef(address)
+    }
+
+    /// Allocates space for an object in the old generation, without initializing it.
+    fn allocate_old(&mut self, size: usize) -> usize {
+        match self.take_free_block(size) {
+            Some(address) => address,
+            None => self.bump(size),
+        }
+    }
+
+    fn take_free_block(&mut self, size: usize) -> Option<usize> {
+        // A smaller remainder couldn't hold the header of a free block
+        let index = self.free
+            .iter()
+            .position(|&(_, block)| block == size || block >= size + HEADER_SIZE)?;
+        let (address, block) = self.free[index];
+        if block == size {
+            self.free.swap_remove(index);
+        } else {
+            self.free[index] = (address + size, block - size);
+            unsafe { write_free_block(address + size, block - size) };
+        }
+        Some(address)
+    }
+
+    fn bump(&mut self, size: usize) -> usize {
+        let segment = match self.segments.iter().position(|segment| segment.size() - segment.top >= size) {
+            Some(segment) => segment,
+            None => {
+                self.segments.push(Segment::new(cmp::max(SEGMENT_SIZE, size)));
+                self.stats.heap_size += self.segments.last().unwrap().size();
+                self.segments.len() - 1
+            }
+        };
+        let segment = &mut self.segments[segment];
+        let address = segment.start() + segment.top;
+        segment.top += size;
+        address
+    }
+
+    /// Records that object references were stored in the `size` bytes at `location`, which may be a field or array
+    /// element of an object, or anywhere else.
+    ///
+    /// In generational mode, a minor collection only finds the nursery objects that old objects refer to through
+    /// the cards this marks.
+    pub fn write_barrier(&mut self, location: *const u8, size: usize) {
+        if self.mode != GcMode::Generational || size == 0 {
+            return;
+        }
+        let address = location as usize;
+        if let Some(segment) = self.segments.iter_mut().find(|segment| segment.contains(address)) {
+            let offset = address - segment.start();
+            segment.mark_cards(offset, size);
+        }
+    }
+
+    /// Frees every object that can't be reached from the roots, or, in generational mode, may just promote the
+    /// nursery objects that can be reached.
+    ///
+    /// Each root is the location of an object reference (which may be null or unaligned), such as a local variable
+    /// or a static field. The locations must stay valid for the duration of the collection, and are updated if the
+    /// objects they refer to move.
+    pub unsafe fn collect(&mut self, roots: &[*mut u8]) {
+        let full = match self.mode {
+            GcMode::MarkSweep => {
+                self.mark_reachable(roots);
+                self.sweep();
+                true
+            }
+            GcMode::Generational => {
+                self.collect_nursery(roots);
+                let full = self.stress || self.allocated_since_collection > self.threshold;
+                if full {
+                    self.mark_reachable(roots);
+                    self.compact(roots);
+                }
+                full
+            }
+        };
+
+        self.stats.collections += 1;
+        if full {
+            self.stats.full_collections += 1;
+            self.allocated_since_collection = 0;
+            self.threshold = cmp::max(INITIAL_THRESHOLD, self.stats.live_bytes);
+        }
+    }
+
+    /// Marks every object reachable from the roots.
+    unsafe fn mark_reachable(&self, roots: &[*mut u8]) {
+        let mut stack = Vec::new();
+        for &root in roots {
+            mark(object::read_ref(root), &mut stack);
+        }
+        while let Some(object) = stack.pop() {
+            self.object_layout(object).visit_refs(object, |location| mark(object::read_ref(location), &mut stack));
+        }
+    }
+
+    /// Clears the mark on each live object, and turns each run of unmarked objects and free blocks into a single free
+    /// block.
+    unsafe fn sweep(&mut self) {
+        let mut free = Vec::new();
+        let mut live_bytes = 0;
+        for index in 0..self.segments.len() {
+            let (start, top) = (self.segments[index].start(), self.segments[index].top);
+            let mut offset = 0;
+            let mut run: Option<usize> = None;
+            while offset < top {
+                let address = start + offset;
+                let object = ObjectRef(address);
+                let size = self.block_size(address);
+                if *(address as *const usize) != FREE && is_marked(object) {
+                    *object.sync_word() &= !MARK_BIT;
+                    live_bytes += size;
+                    if let Some(run_start) = run.take() {
+                        write_free_block(start + run_start, offset - run_start);
+                        free.push((start + run_start, offset - run_start));
+                    }
+                } else {
+                    run = run.or(Some(offset));
+                }
+                offset += size;
+            }
+            // Free space at the end of the segment can be used by bump allocation
+            if let Some(run_start) = run {
+                self.segments[index].top = run_start;
+            }
+        }
+
+        self.release_empty_segments();
+        self.free = free;
+        self.stats.live_bytes = live_bytes;
+    }
+
+    /// Copies the nursery objects that are reachable from the roots, or from old objects with dirty cards, into the
+    /// old generation, and empties the nursery.
+    unsafe fn collect_nursery(&mut self, roots: &[*mut u8]) {
+        let mut locations = roots.to_vec();
+        for segment in self.segments.iter().filter(|segment| segment.dirty) {
+            let mut offset = 0;
+            while offset < segment.top {
+                let address = segment.start() + offset;
+                let size = self.block_size(address);
+                if *(address as *const usize) != FREE && segment.is_dirty(offset, size) {
+                    self.object_layout(ObjectRef(address)).visit_refs(ObjectRef(address), |location| {
+                        locations.push(location)
+                    });
+                }
+                offset += size;
+            }
+        }
+
+        // Copy each object the first time a reference to it is found, leaving its new address behind for the others
+        while let Some(location) = locations.pop() {
+            let object = object::read_ref(location);
+            if !self.in_nursery(object) {
+                continue;
+            }
+            let promoted = if *(object.0 as *const usize) == FORWARDED {
+                ObjectRef(*object.sync_word())
+            } else {
+                let size = self.object_layout(object).object_size(object);
+                let promoted = ObjectRef(self.allocate_old(size));
+                ptr::copy_nonoverlapping(object.0 as *const u8, promoted.0 as *mut u8, size);
+                *(object.0 as *mut usize) = FORWARDED;
+                *object.sync_word() = promoted.0;
+                self.allocated_since_collection += size;
+                self.stats.bytes_promoted += size as u64;
+                self.object_layout(promoted).visit_refs(promoted, |location| locations.push(location));
+                promoted
+            };
+            object::write_ref(location, promoted);
+        }
+
+        for segment in &mut self.segments {
+            segment.clear_cards();
+        }
+        self.nursery_top = 0;
+    }
+
+    /// Slides the marked objects in the old generation towards its start, updating the references to them, and
+    /// clears their marks.
+    unsafe fn compact(&mut self, roots: &[*mut u8]) {
+        // Work out where each object goes, filling the segments in order. An object never moves past where it was.
+        let mut moves = Vec::new();
+        let mut forwarding = HashMap::new();
+        let mut tops = vec![0; self.segments.len()];
+        let mut target = 0;
+        for segment in &self.segments {
+            let mut offset = 0;
+            while offset < segment.top {
+                let address = segment.start() + offset;
+                let size = self.block_size(address);
+                if *(address as *const usize) != FREE && is_marked(ObjectRef(address)) {
+                    while self.segments[target].size() - tops[target] < size {
+                        target += 1;
+                    }
+                    let new_address = self.segments[target].start() + tops[target];
+                    tops[target] += size;
+                    forwarding.insert(address, new_address);
+                    moves.push((address, new_address, size));
+                }
+                offset += size;
+            }
+        }
+
+        let forward = |location: *mut u8| {
+            if let Some(&new_address) = forwarding.get(&object::read_ref(location).0) {
+                object::write_ref(location, ObjectRef(new_address));
+            }
+        };
+        for &root in roots {
+            forward(root);
+        }
+        for &(address, _, _) in &moves {
+            self.object_layout(ObjectRef(address)).visit_refs(ObjectRef(address), forward);
+        }
+
+        let mut live_bytes = 0;
+        for &(address, new_address, size) in &moves {
+            *ObjectRef(address).sync_word() &= !MARK_BIT;
+            ptr::copy(address as *const u8, new_address as *mut u8, size);
+            live_bytes += size;
+        }
+        for (segment, top) in self.segments.iter_mut().zip(tops) {
+            segment.top = top;
+        }
+
+        self.release_empty_segments();
+        self.free.clear();
+        self.stats.live_bytes = live_bytes;
+    }
+
+    /// Releases empty segments, but keeps one for the next allocations.
+    fn release_empty_segments(&mut self) {
+        let mut kept_empty = false;
+        let mut released = 0;
+        self.segments.retain(|segment| {
+            if segment.top > 0 || (!kept_empty && segment.size() == SEGMENT_SIZE) {
+                kept_empty |= segment.top == 0;
+                true
+            } else {
+                released += segment.size();
+                false
+            }
+        });
+        self.stats.heap_size -= released;
+    }
+}
+
+unsafe fn is_marked(object: ObjectRef) -> bool {
+    *object.sync_word() & MARK_BIT != 0
+}
+
+unsafe fn mark(object: ObjectRef, stack: &mut Vec<ObjectRef>) {
+    if !object.is_null() && !is_marked(object) {
+        *object.sync_word() |= MARK_BIT;
+        stack.push(object);
+    }
+}
+
+unsafe fn write_free_block(address: usize, size: usize) {
+    *(address as *mut usize) = FREE;
+    *((address + 8) as *mut usize) = size;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODE: TypeId = TypeId(0);
+    const BYTES: TypeId = TypeId(1);
+
+    fn heap() -> Heap {
+        let mut heap = Heap::new();
+        // A node has a reference to the next node, and a number
+        heap.set_layout(NODE, ObjectLayout::Fixed {
+            size: 16,
+            ref_offsets: vec![0],
+        });
+        heap.set_layout(BYTES, ObjectLayout::Array {
+            element_size: 1,
+            ref_offsets: vec![],
+        });
+        heap
+    }
+
+    fn node(heap: &mut Heap, next: ObjectRef) -> ObjectRef {
+        let node = heap.allocate(NODE, ObjectLayout::fixed_size(16));
+        unsafe { object::write_ref(node.data(), next) };
+        node
+    }
+
+    #[test]
+    pub fn collect_unreachable_objects() {
+        let mut heap = heap();
+        let mut list = ObjectRef::NULL;
+        for _ in 0..10 {
+            list = node(&mut heap, list);
+        }
+        let garbage = node(&mut heap, ObjectRef::NULL);
+
+        unsafe { heap.collect(&[&mut list as *mut ObjectRef as *mut u8]) };
+        let stats = heap.stats();
+        assert_eq!(1, stats.collections);
+        assert_eq!(11, stats.objects_allocated);
+        assert_eq!(10 * 32, stats.live_bytes);
+
+        // The list survived, and the garbage's space is reused
+        assert_eq!(10, length(list));
+        assert_eq!(garbage, node(&mut heap, ObjectRef::NULL));
+    }
+
+    fn length(list: ObjectRef) -> usize {
+        let mut length = 0;
+        let mut current = list;
+        while !current.is_null() {
+            length += 1;
+            current = unsafe { object::read_ref(current.data()) };
+        }
+        length
+    }
+
+    #[test]
+    pub fn generational_mode_promotes_and_compacts() {
+        let mut heap = heap();
+        heap.set_mode(GcMode::Generational);
+        let mut list = ObjectRef::NULL;
+        for _ in 0..10 {
+            list = node(&mut heap, list);
+        }
+        let nursery_list = list;
+        let root = &mut list as *mut ObjectRef as *mut u8;
+
+        // The list survives by being copied out of the nursery
+        unsafe { heap.collect(&[root]) };
+        assert_ne!(nursery_list, list);
+        assert_eq!(10, length(list));
+        assert_eq!(10 * 32, heap.stats().bytes_promoted);
+        assert_eq!(0, heap.stats().full_collections);
+
+        // A nursery object only an old object refers to survives, as long as the store went through the barrier
+        let young = node(&mut heap, ObjectRef::NULL);
+        unsafe {
+            *(young.data().add(8) as *mut u64) = 42;
+            object::write_ref(list.data(), young);
+        }
+        heap.write_barrier(list.data(), 8);
+        unsafe { heap.collect(&[root]) };
+        let young = unsafe { object::read_ref(list.data()) };
+        assert_ne!(0, young.address());
+        assert_eq!(42, unsafe { *(young.data().add(8) as *const u64) });
+
+        // The nine nodes that fell off the list are compacted away
+        heap.set_stress(true);
+        unsafe { heap.collect(&[root]) };
+        let stats = heap.stats();
+        assert_eq!(1, stats.full_collections);
+        assert_eq!(3, stats.collections);
+        assert_eq!(2 * 32, stats.live_bytes);
+        assert_eq!(2, length(list));
+        assert_eq!(42, unsafe { *(object::read_ref(list.data()).data().add(8) as *const u64) });
+    }
+
+    #[test]
+    pub fn heap_stays_bounded() {
+        let mut heap = heap();
+        let mut kept = ObjectRef::NULL;
+        for i in 0..20_000 {
+            // Keep every 100th node, and allocate arrays of varying size that are all garbage
+            if heap.should_collect(1024) {
+                unsafe { heap.collect(&[&mut kept as *mut ObjectRef as *mut u8]) };
+            }
+            if i % 100 == 0 {
+                kept = node(&mut heap, kept);
+            }
+            let size = ObjectLayout::array_size(1, 100 + i % 900);
+            let array = heap.allocate(BYTES, size);
+            unsafe { *(array.data() as *mut usize) = 100 + i % 900 };
+        }
+
+        let stats = heap.stats();
+        assert!(stats.collections > 0);
+        assert!(stats.bytes_allocated > 2 * INITIAL_THRESHOLD as u64);
+        assert!(stats.heap_size <= 2 * INITIAL_THRESHOLD + SEGMENT_SIZE, "{:?}", stats);
+    }
+}
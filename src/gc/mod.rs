@@ -0,0 +1,6 @@
+mod heap;
+mod object;
+
+pub use self::heap::{GcMode, GcStats, Heap};
+pub use self::object::{md_array_elements, read_ref, write_ref, ObjectLayout, ObjectRef, ARRAY_ELEMENTS, HEADER_SIZE,
+                       STRING_CHARS};
@@ -0,0 +1,191 @@
+use std::ptr;
+use std::slice;
+
+use types::TypeId;
+
+/// The size of the header at the start of every object: the object's type, then its sync/hash word.
+pub const HEADER_SIZE: usize = 16;
+
+/// The offset of an SZ array's elements from the start of its data, after its length.
+pub const ARRAY_ELEMENTS: usize = 8;
+
+/// The offset of the elements of an array with a rank from the start of its data, after its total length and the
+/// length and lower bound of each dimension.
+pub fn md_array_elements(rank: u32) -> usize {
+    ARRAY_ELEMENTS + 8 * rank as usize
+}
+
+/// The offset of a string's characters from the start of its data, after its length.
+pub const STRING_CHARS: usize = 4;
+
+/// The bit in the sync word that marks an object as reachable during a collection.
+pub const MARK_BIT: usize = 1 << 63;
+
+/// The bits of the sync word that hold the index of an object's sync block plus one, or 0 if it has none.
+pub const SYNC_BLOCK_BITS: usize = 0xFFFF_FFFF;
+
+/// A reference to an object on the managed heap: the address of its header, or 0 for null.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectRef(pub(crate) usize);
+
+impl ObjectRef {
+    pub const NULL: ObjectRef = ObjectRef(0);
+
+    pub fn is_null(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn address(self) -> usize {
+        self.0
+    }
+
+    /// Gets the address of the object's data, which follows its header.
+    pub fn data(self) -> *mut u8 {
+        (self.0 + HEADER_SIZE) as *mut u8
+    }
+
+    pub unsafe fn type_id(self) -> TypeId {
+        TypeId(*(self.0 as *const usize) as u32)
+    }
+
+    pub(crate) unsafe fn sync_word(self) -> *mut usize {
+        (self.0 + 8) as *mut usize
+    }
+
+    /// Gets the index of the object's sync block, if it has been given one.
+    pub(crate) unsafe fn sync_block(self) -> Option<usize> {
+        (*self.sync_word() & SYNC_BLOCK_BITS).checked_sub(1)
+    }
+
+    /// Gives the object a sync block, by its index.
+    pub(crate) unsafe fn set_sync_block(self, index: usize) {
+        *self.sync_word() = (*self.sync_word() & !SYNC_BLOCK_BITS) | (index + 1);
+    }
+
+    /// Gets the length of an SZ array.
+    pub unsafe fn array_length(self) -> usize {
+        *(self.data() as *const usize)
+    }
+
+    /// Gets the address of the element at an index of an SZ array whose elements are `element_size` bytes.
+    pub unsafe fn array_element(self, index: usize, element_size: usize) -> *mut u8 {
+        self.data().add(ARRAY_ELEMENTS + index * element_size)
+    }
+
+    /// Gets the lengths of the dimensions of an array with a rank, which are followed by their lower bounds.
+    pub unsafe fn md_array_bounds(self) -> *mut i32 {
+        self.data().add(ARRAY_ELEMENTS) as *mut i32
+    }
+
+    /// Gets the address of the element at an index (counting through all the dimensions) of an array with a rank.
+    pub unsafe fn md_array_element(self, rank: u32, index: usize, element_size: usize) -> *mut u8 {
+        self.data().add(md_array_elements(rank) + index * element_size)
+    }
+
+    /// Gets the length of a string, in UTF-16 code units.
+    pub unsafe fn string_length(self) -> usize {
+        *(self.data() as *const u32) as usize
+    }
+
+    /// Gets the characters of a string, as UTF-16 code units.
+    pub unsafe fn string_chars<'a>(self) -> &'a [u16] {
+        slice::from_raw_parts(self.data().add(STRING_CHARS) as *const u16, self.string_length())
+    }
+}
+
+/// Reads an object reference from a field, array element or other location, which may not be aligned.
+pub unsafe fn read_ref(location: *const u8) -> ObjectRef {
+    ObjectRef(ptr::read_unaligned(location as *const usize))
+}
+
+/// Writes an object reference to a location, which may not be aligned.
+pub unsafe fn write_ref(location: *mut u8, value: ObjectRef) {
+    ptr::write_unaligned(location as *mut usize, value.0)
+}
+
+/// Describes where the object references are in the instances of a type, and how large its instances are.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ObjectLayout {
+    /// An object of fixed size, with `size` bytes of fields after its header.
+    Fixed { size: u32, ref_offsets: Vec<u32> },
+
+    /// An SZ array, with the offsets of the references within each element.
+    Array { element_size: u32, ref_offsets: Vec<u32> },
+
+    /// An array with a rank, which has the bounds of its dimensions before its elements.
+    MdArray { rank: u32, element_size: u32, ref_offsets: Vec<u32> },
+
+    String,
+}
+
+impl ObjectLayout {
+    /// Gets the size of an object, including its header and rounded up to a multiple of 8 bytes.
+    pub unsafe fn object_size(&self, object: ObjectRef) -> usize {
+        match *self {
+            ObjectLayout::Fixed { size, .. } => ObjectLayout::fixed_size(size),
+            ObjectLayout::Array { element_size, .. } => ObjectLayout::array_size(element_size, object.array_length()),
+            ObjectLayout::MdArray { rank, element_size, .. } => {
+                ObjectLayout::md_array_size(rank, element_size, object.array_length())
+            }
+            ObjectLayout::String => ObjectLayout::string_size(object.string_length()),
+        }
+    }
+
+    pub fn fixed_size(size: u32) -> usize {
+        align8(HEADER_SIZE + size as usize)
+    }
+
+    pub fn array_size(element_size: u32, length: usize) -> usize {
+        align8(HEADER_SIZE + ARRAY_ELEMENTS + element_size as usize * length)
+    }
+
+    pub fn md_array_size(rank: u32, element_size: u32, length: usize) -> usize {
+        align8(HEADER_SIZE + md_array_elements(rank) + element_size as usize * length)
+    }
+
+    pub fn string_size(length: usize) -> usize {
+        align8(HEADER_SIZE + STRING_CHARS + 2 * length)
+    }
+
+    /// Calls `visit` with the location of each object reference in an object.
+    pub unsafe fn visit_refs<F: FnMut(*mut u8)>(&self, object: ObjectRef, mut visit: F) {
+        match *self {
+            ObjectLayout::Fixed { ref ref_offsets, .. } => {
+                for &offset in ref_offsets {
+                    visit(object.data().add(offset as usize));
+                }
+            }
+            ObjectLayout::Array { element_size, ref ref_offsets } => {
+                visit_elements(object.array_element(0, 0), object.array_length(), element_size, ref_offsets, visit)
+            }
+            ObjectLayout::MdArray { rank, element_size, ref ref_offsets } => {
+                let elements = object.md_array_element(rank, 0, 0);
+                visit_elements(elements, object.array_length(), element_size, ref_offsets, visit)
+            }
+            ObjectLayout::String => {}
+        }
+    }
+}
+
+/// Calls `visit` with the location of each object reference in `length` array elements, starting at `elements`.
+unsafe fn visit_elements<F: FnMut(*mut u8)>(
+    elements: *mut u8,
+    length: usize,
+    element_size: u32,
+    ref_offsets: &[u32],
+    mut visit: F,
+) {
+    if ref_offsets.is_empty() {
+        return;
+    }
+    for index in 0..length {
+        let element = elements.add(index * element_size as usize);
+        for &offset in ref_offsets {
+            visit(element.add(offset as usize));
+        }
+    }
+}
+
+pub fn align8(size: usize) -> usize {
+    (size + 7) & !7
+}
@@ -0,0 +1,783 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{self, AtomicI32, AtomicI64, AtomicUsize, Ordering};
+
+use error::{Error, ExceptionKind};
+use gc::ObjectRef;
+use interpreter::{handle_id, Member, Value};
+use types::{FieldId, MethodId, Primitive, TypeId, TypeKind, TypeSystem};
+use vm::Vm;
+
+/// A Rust function implementing a method marked `[MethodImpl(MethodImplOptions.InternalCall)]`. It is given the
+/// method's arguments, including `this`, and returns what the method returns, or `None` for `void`.
+///
+/// Returning `Error::Exception` throws the corresponding exception to the calling method.
+pub type InternalCall = Rc<dyn Fn(&mut CallContext, Vec<Value>) -> Result<Option<Value>, Error>>;
+
+/// The functions internal calls are bound to, by the full name and parameter types of their methods, such as
+/// `System.Console::WriteLine(System.String)`.
+#[derive(Clone)]
+pub struct InternalCalls {
+    calls: HashMap<String, InternalCall>,
+}
+
+impl InternalCalls {
+    /// Creates a registry with the intrinsics the core library needs.
+    pub fn new() -> InternalCalls {
+        let mut calls = InternalCalls {
+            calls: HashMap::new(),
+        };
+        calls.register("System.Console::WriteLine(System.String)", write_line);
+        calls.register("System.Enum::ToString()", enum_to_string);
+        calls.register("System.Delegate::Combine(System.Delegate, System.Delegate)", delegate_combine);
+        calls.register("System.Delegate::Remove(System.Delegate, System.Delegate)", delegate_remove);
+        calls.register("System.Runtime.InteropServices.Marshal::GetLastWin32Error()", get_last_error);
+        calls.register("System.Runtime.InteropServices.Marshal::GetLastPInvokeError()", get_last_error);
+        calls.register(
+            "System.Runtime.CompilerServices.RuntimeHelpers::InitializeArray(System.Array, System.RuntimeFieldHandle)",
+            initialize_array,
+        );
+        calls.register("System.Object::GetType()", get_type);
+        calls.register("System.Type::GetTypeFromHandle(System.RuntimeTypeHandle)", get_type_from_handle);
+        calls.register("System.Type::get_Namespace()", type_namespace);
+        calls.register("System.Type::get_FullName()", type_full_name);
+        calls.register("System.Type::get_BaseType()", type_base_type);
+        calls.register("System.Type::get_IsValueType()", type_is_value_type);
+        calls.register("System.Type::get_IsInterface()", type_is_interface);
+        calls.register("System.Type::IsAssignableFrom(System.Type)", type_is_assignable_from);
+        calls.register("System.Type::GetInterfaces()", type_get_interfaces);
+        calls.register("System.Type::GetMethods()", type_get_methods);
+        calls.register("System.Type::GetMethod(System.String)", type_get_method);
+        calls.register("System.Type::GetFields()", type_get_fields);
+        calls.register("System.Type::GetField(System.String)", type_get_field);
+        calls.register("System.Reflection.MemberInfo::get_Name()", member_name);
+        calls.register("System.Reflection.MemberInfo::get_DeclaringType()", member_declaring_type);
+        calls.register("System.Reflection.MemberInfo::GetCustomAttributes(System.Boolean)", member_custom_attributes);
+        calls.register(
+            "System.Reflection.MemberInfo::GetCustomAttributes(System.Type, System.Boolean)",
+            member_custom_attributes,
+        );
+        calls.register(
+            "System.Reflection.MethodBase::GetMethodFromHandle(System.RuntimeMethodHandle)",
+            get_method_from_handle,
+        );
+        calls.register("System.Reflection.MethodBase::get_IsStatic()", method_is_static);
+        calls.register("System.Reflection.MethodBase::get_IsVirtual()", method_is_virtual);
+        calls.register("System.Reflection.MethodBase::GetParameterTypes()", method_parameter_types);
+        calls.register("System.Reflection.MethodBase::Invoke(System.Object, System.Object[])", method_invoke);
+        calls.register("System.Reflection.MethodInfo::get_ReturnType()", method_return_type);
+        calls.register("System.Reflection.FieldInfo::get_FieldType()", field_type);
+        calls.register("System.Reflection.FieldInfo::get_IsStatic()", field_is_static);
+        calls.register("System.Reflection.FieldInfo::GetValue(System.Object)", field_get_value);
+        calls.register("System.Reflection.FieldInfo::SetValue(System.Object, System.Object)", field_set_value);
+        calls.register("System.Threading.Thread::get_CurrentThread()", current_thread);
+        calls.register("System.Threading.Thread::get_ManagedThreadId()", managed_thread_id);
+        calls.register("System.Threading.Thread::Start()", thread_start);
+        calls.register("System.Threading.Thread::Join()", thread_join);
+        calls.register("System.Threading.Thread::Sleep(System.Int32)", thread_sleep);
+        calls.register("System.Threading.Thread::Yield()", thread_yield);
+        calls.register("System.Threading.Monitor::Enter(System.Object)", monitor_enter);
+        calls.register("System.Threading.Monitor::Enter(System.Object, System.Boolean&)", monitor_enter);
+        calls.register("System.Threading.Monitor::Exit(System.Object)", monitor_exit);
+        calls.register("System.Threading.Monitor::IsEntered(System.Object)", monitor_is_entered);
+        calls.register("System.Threading.Monitor::Wait(System.Object)", monitor_wait);
+        calls.register("System.Threading.Monitor::Pulse(System.Object)", monitor_pulse);
+        calls.register("System.Threading.Monitor::PulseAll(System.Object)", monitor_pulse_all);
+        calls.register("System.Threading.Interlocked::Increment(System.Int32&)", |_, args| {
+            interlocked_add_i32(&args[0], 1)
+        });
+        calls.register("System.Threading.Interlocked::Increment(System.Int64&)", |_, args| {
+            interlocked_add_i64(&args[0], 1)
+        });
+        calls.register("System.Threading.Interlocked::Decrement(System.Int32&)", |_, args| {
+            interlocked_add_i32(&args[0], -1)
+        });
+        calls.register("System.Threading.Interlocked::Decrement(System.Int64&)", |_, args| {
+            interlocked_add_i64(&args[0], -1)
+        });
+        calls.register("System.Threading.Interlocked::Add(System.Int32&, System.Int32)", |_, args| {
+            interlocked_add_i32(&args[0], integer(&args[1])? as i32)
+        });
+        calls.register("System.Threading.Interlocked::Add(System.Int64&, System.Int64)", |_, args| {
+            interlocked_add_i64(&args[0], integer(&args[1])?)
+        });
+        calls.register("System.Threading.Interlocked::Exchange(System.Int32&, System.Int32)", interlocked_exchange_i32);
+        calls.register("System.Threading.Interlocked::Exchange(System.Int64&, System.Int64)", interlocked_exchange_i64);
+        calls.register(
+            "System.Threading.Interlocked::Exchange(System.Object&, System.Object)",
+            interlocked_exchange_object,
+        );
+        calls.register(
+            "System.Threading.Interlocked::CompareExchange(System.Int32&, System.Int32, System.Int32)",
+            interlocked_compare_exchange_i32,
+        );
+        calls.register(
+            "System.Threading.Interlocked::CompareExchange(System.Int64&, System.Int64, System.Int64)",
+            interlocked_compare_exchange_i64,
+        );
+        calls.register(
+            "System.Threading.Interlocked::CompareExchange(System.Object&, System.Object, System.Object)",
+            interlocked_compare_exchange_object,
+        );
+        calls.register("System.Threading.Interlocked::MemoryBarrier()", |_, _| {
+            atomic::fence(Ordering::SeqCst);
+            Ok(None)
+        });
+        calls
+    }
+
+    /// Binds the methods with a name to a function, replacing the function they were bound to.
+    pub fn register<F>(&mut self, name: &str, call: F)
+    where
+        F: Fn(&mut CallContext, Vec<Value>) -> Result<Option<Value>, Error> + 'static,
+    {
+        self.calls.insert(name.into(), Rc::new(call));
+    }
+
+    pub fn get(&self, name: &str) -> Option<InternalCall> {
+        self.calls.get(name).cloned()
+    }
+}
+
+impl Default for InternalCalls {
+    fn default() -> InternalCalls {
+        InternalCalls::new()
+    }
+}
+
+impl fmt::Debug for InternalCalls {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut names: Vec<_> = self.calls.keys().collect();
+        names.sort();
+        f.debug_set().entries(names).finish()
+    }
+}
+
+/// What an internal call can do with the runtime that calls it.
+///
+/// Allocating may move objects, so object references an internal call was given must not be used after it
+/// allocates.
+pub struct CallContext<'a> {
+    pub(crate) vm: &'a mut Vm,
+}
+
+impl<'a> CallContext<'a> {
+    /// Gets the contents of a string, or `None` if the value is null or isn't a string.
+    pub fn string(&self, value: &Value) -> Option<String> {
+        match *value {
+            Value::Ref(object) if !object.is_null() => {
+                if self.vm.types.get(self.vm.object_type(object)).kind != TypeKind::String {
+                    return None;
+                }
+                Some(String::from_utf16_lossy(unsafe { object.string_chars() }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Allocates a string.
+    pub fn new_string(&mut self, value: &str) -> Result<Value, Error> {
+        let chars: Vec<u16> = value.encode_utf16().collect();
+        Ok(Value::Ref(self.vm.new_string(&chars)?))
+    }
+}
+
+/// Gets the name an internal call is bound by: the full name of its type, its name, and the full names of its
+/// parameter types.
+pub(crate) fn internal_call_name(types: &TypeSystem, method: MethodId) -> String {
+    let method = types.method(method);
+    let params: Vec<_> = method.signature.params.iter().map(|&param| types.get(param).to_string()).collect();
+    format!("{}::{}({})", types.get(method.owner), method.name, params.join(", "))
+}
+
+/// `Console.WriteLine(string)`, which writes the line to stdout.
+fn write_line(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    println!("{}", context.string(&args[0]).unwrap_or_default());
+    Ok(None)
+}
+
+/// `Enum.ToString()`, which formats a boxed enum value as the names of the literals that make it up, or as a number
+/// if there are none.
+fn enum_to_string(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let object = match args[0] {
+        Value::Ref(object) if !object.is_null() => object,
+        _ => return Err(ExceptionKind::NullReference.into()),
+    };
+    let ty = context.vm.object_type(object);
+    let primitive = match context.vm.types.get(ty).kind {
+        TypeKind::Enum(primitive) => primitive,
+        _ => return Err(Error::InvalidProgram(format!("{} is not an enum", context.vm.types.get(ty)))),
+    };
+    let (storage, size) = (context.vm.types.get(ty).storage(), context.vm.types.get(ty).value_size());
+    let value = unsafe { Value::load(object.data(), storage, ty, size) }.as_i64().unwrap_or(0);
+    let value = match primitive {
+        Primitive::U4 => value as u32 as i64,
+        _ => value,
+    };
+    let name = match context.vm.types.enum_name(ty, value)? {
+        Some(name) => name,
+        None if primitive == Primitive::U8 => (value as u64).to_string(),
+        None => value.to_string(),
+    };
+    context.new_string(&name).map(Some)
+}
+
+/// `Delegate.Combine(Delegate, Delegate)`, which creates a delegate that invokes the methods of both delegates in
+/// order.
+fn delegate_combine(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    context.vm.combine_delegates(&args[0], &args[1]).map(Some)
+}
+
+/// `Delegate.Remove(Delegate, Delegate)`, which creates a delegate that invokes the methods of the first delegate
+/// without the last occurrence of the second's.
+fn delegate_remove(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    context.vm.remove_delegate(&args[0], &args[1]).map(Some)
+}
+
+/// `Marshal.GetLastWin32Error()` and `Marshal.GetLastPInvokeError()`, which get the error code the last P/Invoke
+/// with `SetLastError = true` got from its function.
+fn get_last_error(context: &mut CallContext, _: Vec<Value>) -> Result<Option<Value>, Error> {
+    Ok(Some(Value::I32(context.vm.last_error)))
+}
+
+/// `RuntimeHelpers.InitializeArray(Array, RuntimeFieldHandle)`, which copies the data of a static field with an RVA
+/// into the elements of an array of primitive values, as the C# compiler initializes arrays.
+fn initialize_array(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let array = match args[0] {
+        Value::Ref(array) if array.is_null() => return Err(ExceptionKind::NullReference.into()),
+        Value::Ref(array) => array,
+        _ => return Err(Error::InvalidProgram("InitializeArray is given a non-array".into())),
+    };
+    let field = match handle_id(&args[1]) {
+        Some(id) => FieldId(id),
+        None => return Err(Error::InvalidProgram("InitializeArray is given an invalid field handle".into())),
+    };
+    let vm = &mut *context.vm;
+    let (element, elements) = match vm.types.get(vm.object_type(array)).kind {
+        TypeKind::SzArray(element) => (element, unsafe { array.array_element(0, 0) }),
+        TypeKind::Array(element, rank) => (element, unsafe { array.md_array_element(rank, 0, 0) }),
+        _ => return Err(Error::InvalidProgram("InitializeArray is given a non-array".into())),
+    };
+    let (element_size, has_refs) = {
+        let element = vm.types.get(element);
+        (element.value_size() as usize, !element.value_ref_offsets().is_empty())
+    };
+    if has_refs {
+        let message = format!("InitializeArray is given an array of {}", vm.types.get(element));
+        return Err(Error::InvalidProgram(message));
+    }
+    let size = unsafe { array.array_length() } * element_size;
+    let data = match vm.types.field_data(field)? {
+        Some(data) if data.len() >= size => data,
+        _ => return Err(Error::InvalidProgram("InitializeArray is given too little data".into())),
+    };
+    unsafe { ::std::ptr::copy_nonoverlapping(data.as_ptr(), elements, size) };
+    Ok(None)
+}
+
+/// `Object.GetType()`, which gets the `Type` of an object's type.
+fn get_type(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    match args[0] {
+        Value::Ref(object) if object.is_null() => Err(ExceptionKind::NullReference.into()),
+        Value::Ref(object) => reflect_type(context, Some(context.vm.object_type(object))),
+        _ => Err(Error::InvalidProgram("GetType is called on a non-object".into())),
+    }
+}
+
+/// `Type.GetTypeFromHandle(RuntimeTypeHandle)`, which gets the `Type` of the type `ldtoken` loaded a handle for.
+fn get_type_from_handle(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    reflect_type(context, handle_id(&args[0]).map(TypeId))
+}
+
+/// `Type.Namespace`, which is null for types without a namespace.
+fn type_namespace(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    match context.vm.types.get(ty).namespace.clone() {
+        ref namespace if namespace.is_empty() => Ok(Some(Value::null())),
+        namespace => context.new_string(&namespace).map(Some),
+    }
+}
+
+/// `Type.FullName`, the name of a type with its namespace.
+fn type_full_name(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    let full_name = context.vm.types.get(ty).to_string();
+    context.new_string(&full_name).map(Some)
+}
+
+/// `Type.BaseType`, which is null for `object` and interfaces.
+fn type_base_type(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    let base = context.vm.types.get(ty).base;
+    reflect_type(context, base)
+}
+
+/// `Type.IsValueType`.
+fn type_is_value_type(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    Ok(Some(Value::I32(context.vm.types.get(ty).is_value_type() as i32)))
+}
+
+/// `Type.IsInterface`.
+fn type_is_interface(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    Ok(Some(Value::I32((context.vm.types.get(ty).kind == TypeKind::Interface) as i32)))
+}
+
+/// `Type.IsAssignableFrom(Type)`, which returns `true` if values of the other type can be stored in locations of
+/// the type, and `false` for null.
+fn type_is_assignable_from(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    let assignable = match context.vm.reflected_type(&args[1])? {
+        Some(from) => {
+            context.vm.types.prepare(from)?;
+            context.vm.types.prepare(ty)?;
+            context.vm.types.is_assignable_to(from, ty)
+        }
+        None => false,
+    };
+    Ok(Some(Value::I32(assignable as i32)))
+}
+
+/// `Type.GetInterfaces()`, which gets the interfaces a type implements.
+fn type_get_interfaces(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    context.vm.types.prepare(ty)?;
+    let interfaces = &context.vm.types.get(ty).interfaces;
+    let interfaces: Vec<_> = interfaces.iter().map(|&interface| Member::Type(interface)).collect();
+    reflect_all(context, "System", "Type", &interfaces)
+}
+
+/// `Type.GetMethods()`, which gets the public methods of a type.
+fn type_get_methods(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    let methods: Vec<_> = context.vm.public_methods(ty)?.into_iter().map(Member::Method).collect();
+    reflect_all(context, "System.Reflection", "MethodInfo", &methods)
+}
+
+/// `Type.GetMethod(string)`, which finds the public method of a type with a name.
+fn type_get_method(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    let name = context.string(&args[1]).ok_or(ExceptionKind::NullReference)?;
+    match context.vm.public_method(ty, &name)? {
+        Some(method) => context.vm.reflect(Member::Method(method)).map(|method| Some(Value::Ref(method))),
+        None => Ok(Some(Value::null())),
+    }
+}
+
+/// `Type.GetFields()`, which gets the public fields of a type.
+fn type_get_fields(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    let fields: Vec<_> = context.vm.public_fields(ty)?.into_iter().map(Member::Field).collect();
+    reflect_all(context, "System.Reflection", "FieldInfo", &fields)
+}
+
+/// `Type.GetField(string)`, which finds the public field of a type with a name.
+fn type_get_field(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let ty = reflected_type(context, &args[0])?;
+    let name = context.string(&args[1]).ok_or(ExceptionKind::NullReference)?;
+    let fields = context.vm.public_fields(ty)?;
+    match fields.into_iter().find(|&field| context.vm.types.field(field).name == name) {
+        Some(field) => context.vm.reflect(Member::Field(field)).map(|field| Some(Value::Ref(field))),
+        None => Ok(Some(Value::null())),
+    }
+}
+
+/// `MemberInfo.Name`.
+fn member_name(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let member = context.vm.reflected(&args[0])?;
+    let name = context.vm.member_name(member);
+    context.new_string(&name).map(Some)
+}
+
+/// `MemberInfo.DeclaringType`, the type a method or field belongs to, or the type a type is nested in.
+fn member_declaring_type(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let member = context.vm.reflected(&args[0])?;
+    let declaring_type = context.vm.declaring_type(member)?;
+    reflect_type(context, declaring_type)
+}
+
+/// `MemberInfo.GetCustomAttributes(bool)` and `MemberInfo.GetCustomAttributes(Type, bool)`, which create the custom
+/// attributes applied to a member, or the ones of a type.
+fn member_custom_attributes(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let member = context.vm.reflected(&args[0])?;
+    let attribute_type = match args.len() {
+        3 => Some(context.vm.reflected_type(&args[1])?.ok_or(ExceptionKind::Argument)?),
+        _ => None,
+    };
+    let inherit = args.last().and_then(Value::as_i64) == Some(1);
+    context.vm.custom_attributes(member, attribute_type, inherit).map(Some)
+}
+
+/// `MethodBase.GetMethodFromHandle(RuntimeMethodHandle)`, which gets the `MethodInfo` of the method `ldtoken` loaded
+/// a handle for.
+fn get_method_from_handle(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let method = handle_id(&args[0]).map(MethodId).ok_or(ExceptionKind::Argument)?;
+    context.vm.reflect(Member::Method(method)).map(|method| Some(Value::Ref(method)))
+}
+
+/// `MethodBase.IsStatic`.
+fn method_is_static(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let method = reflected_method(context, &args[0])?;
+    Ok(Some(Value::I32(context.vm.types.method(method).is_static() as i32)))
+}
+
+/// `MethodBase.IsVirtual`.
+fn method_is_virtual(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let method = reflected_method(context, &args[0])?;
+    Ok(Some(Value::I32(context.vm.types.method(method).is_virtual() as i32)))
+}
+
+/// `MethodBase.GetParameterTypes()`, which gets the types of a method's parameters.
+fn method_parameter_types(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let method = reflected_method(context, &args[0])?;
+    let params = &context.vm.types.method(method).signature.params;
+    let params: Vec<_> = params.iter().map(|&param| Member::Type(param)).collect();
+    reflect_all(context, "System", "Type", &params)
+}
+
+/// `MethodBase.Invoke(object, object[])`, which calls a method with arguments unboxed from an array.
+fn method_invoke(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let method = reflected_method(context, &args[0])?;
+    context.vm.invoke_reflected(method, &args[1], &args[2]).map(Some)
+}
+
+/// `MethodInfo.ReturnType`, which is `void` for methods that return nothing.
+fn method_return_type(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let method = reflected_method(context, &args[0])?;
+    let return_type = match context.vm.types.method(method).signature.ret {
+        Some(return_type) => return_type,
+        None => context.vm.types.corlib_type("System", "Void")?,
+    };
+    reflect_type(context, Some(return_type))
+}
+
+/// `FieldInfo.FieldType`.
+fn field_type(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let field = reflected_field(context, &args[0])?;
+    let field_type = context.vm.types.field(field).field_type;
+    reflect_type(context, Some(field_type))
+}
+
+/// `FieldInfo.IsStatic`, which is also `true` for constants.
+fn field_is_static(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let field = reflected_field(context, &args[0])?;
+    Ok(Some(Value::I32(context.vm.types.field(field).is_static() as i32)))
+}
+
+/// `FieldInfo.GetValue(object)`, which gets the value of a field of an object, or of a static field, boxed.
+fn field_get_value(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let field = reflected_field(context, &args[0])?;
+    context.vm.field_value(field, &args[1]).map(Some)
+}
+
+/// `FieldInfo.SetValue(object, object)`, which sets a field of an object, or a static field, to a value unboxed from
+/// an object.
+fn field_set_value(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let field = reflected_field(context, &args[0])?;
+    context.vm.set_field_value(field, &args[1], &args[2]).map(|()| None)
+}
+
+/// `Thread.CurrentThread`.
+fn current_thread(context: &mut CallContext, _: Vec<Value>) -> Result<Option<Value>, Error> {
+    context.vm.current_thread_object().map(|thread| Some(Value::Ref(thread)))
+}
+
+/// `Thread.ManagedThreadId`, which numbers threads from 1 in the order they are first asked for their ids.
+fn managed_thread_id(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let thread = object(&args[0])?;
+    context.vm.managed_thread_id(thread).map(|id| Some(Value::I32(id)))
+}
+
+/// `Thread.Start()`, which runs the thread's delegate on a new OS thread.
+fn thread_start(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let thread = object(&args[0])?;
+    context.vm.start_thread(thread).map(|()| None)
+}
+
+/// `Thread.Join()`, which blocks until the thread finishes.
+fn thread_join(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let thread = object(&args[0])?;
+    context.vm.join_thread(thread).map(|()| None)
+}
+
+/// `Thread.Sleep(int)`.
+fn thread_sleep(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let milliseconds = integer(&args[0])? as i32;
+    context.vm.sleep(milliseconds).map(|()| None)
+}
+
+/// `Thread.Yield()`, which lets the threads waiting for a turn run, and always returns `true`.
+fn thread_yield(context: &mut CallContext, _: Vec<Value>) -> Result<Option<Value>, Error> {
+    context.vm.yield_turn();
+    Ok(Some(Value::I32(1)))
+}
+
+/// `Monitor.Enter(object)` and `Monitor.Enter(object, ref bool)`.
+fn monitor_enter(context: &mut CallContext, mut args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let lock_taken = if args.len() > 1 { args.pop() } else { None };
+    context.vm.monitor_enter(object_or_null(&args[0])?, lock_taken).map(|()| None)
+}
+
+/// `Monitor.Exit(object)`.
+fn monitor_exit(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    context.vm.monitor_exit(object_or_null(&args[0])?).map(|()| None)
+}
+
+/// `Monitor.IsEntered(object)`.
+fn monitor_is_entered(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let entered = context.vm.monitor_is_entered(object_or_null(&args[0])?)?;
+    Ok(Some(Value::I32(entered as i32)))
+}
+
+/// `Monitor.Wait(object)`, which returns `true` once the monitor has been pulsed and entered again.
+fn monitor_wait(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    context.vm.monitor_wait(object_or_null(&args[0])?)?;
+    Ok(Some(Value::I32(1)))
+}
+
+/// `Monitor.Pulse(object)`.
+fn monitor_pulse(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    context.vm.monitor_pulse(object_or_null(&args[0])?, false).map(|()| None)
+}
+
+/// `Monitor.PulseAll(object)`.
+fn monitor_pulse_all(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    context.vm.monitor_pulse(object_or_null(&args[0])?, true).map(|()| None)
+}
+
+/// `Interlocked.Increment`, `Decrement` and `Add` for `int`, which return the new value.
+fn interlocked_add_i32(location: &Value, value: i32) -> Result<Option<Value>, Error> {
+    let location = unsafe { AtomicI32::from_ptr(address(location)? as *mut i32) };
+    Ok(Some(Value::I32(location.fetch_add(value, Ordering::SeqCst).wrapping_add(value))))
+}
+
+/// `Interlocked.Increment`, `Decrement` and `Add` for `long`, which return the new value.
+fn interlocked_add_i64(location: &Value, value: i64) -> Result<Option<Value>, Error> {
+    let location = unsafe { AtomicI64::from_ptr(address(location)? as *mut i64) };
+    Ok(Some(Value::I64(location.fetch_add(value, Ordering::SeqCst).wrapping_add(value))))
+}
+
+/// `Interlocked.Exchange(ref int, int)`, which returns the old value.
+fn interlocked_exchange_i32(_: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let location = unsafe { AtomicI32::from_ptr(address(&args[0])? as *mut i32) };
+    Ok(Some(Value::I32(location.swap(integer(&args[1])? as i32, Ordering::SeqCst))))
+}
+
+/// `Interlocked.Exchange(ref long, long)`, which returns the old value.
+fn interlocked_exchange_i64(_: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let location = unsafe { AtomicI64::from_ptr(address(&args[0])? as *mut i64) };
+    Ok(Some(Value::I64(location.swap(integer(&args[1])?, Ordering::SeqCst))))
+}
+
+/// `Interlocked.Exchange(ref object, object)`, which returns the old value.
+fn interlocked_exchange_object(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let address = address(&args[0])?;
+    let location = unsafe { AtomicUsize::from_ptr(address as *mut usize) };
+    let old = location.swap(object_or_null(&args[1])?.address(), Ordering::SeqCst);
+    context.vm.heap.write_barrier(address, 8);
+    Ok(Some(Value::Ref(ObjectRef(old))))
+}
+
+/// `Interlocked.CompareExchange(ref int, int, int)`, which stores the value if the location holds the comparand, and
+/// returns the old value.
+fn interlocked_compare_exchange_i32(_: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let location = unsafe { AtomicI32::from_ptr(address(&args[0])? as *mut i32) };
+    let (value, comparand) = (integer(&args[1])? as i32, integer(&args[2])? as i32);
+    let old = location.compare_exchange(comparand, value, Ordering::SeqCst, Ordering::SeqCst);
+    Ok(Some(Value::I32(old.unwrap_or_else(|old| old))))
+}
+
+/// `Interlocked.CompareExchange(ref long, long, long)`, which stores the value if the location holds the comparand,
+/// and returns the old value.
+fn interlocked_compare_exchange_i64(_: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let location = unsafe { AtomicI64::from_ptr(address(&args[0])? as *mut i64) };
+    let (value, comparand) = (integer(&args[1])?, integer(&args[2])?);
+    let old = location.compare_exchange(comparand, value, Ordering::SeqCst, Ordering::SeqCst);
+    Ok(Some(Value::I64(old.unwrap_or_else(|old| old))))
+}
+
+/// `Interlocked.CompareExchange(ref object, object, object)`, which stores the value if the location refers to the
+/// same object as the comparand, and returns the old value.
+fn interlocked_compare_exchange_object(context: &mut CallContext, args: Vec<Value>) -> Result<Option<Value>, Error> {
+    let address = address(&args[0])?;
+    let location = unsafe { AtomicUsize::from_ptr(address as *mut usize) };
+    let (value, comparand) = (object_or_null(&args[1])?.address(), object_or_null(&args[2])?.address());
+    let old = location.compare_exchange(comparand, value, Ordering::SeqCst, Ordering::SeqCst);
+    context.vm.heap.write_barrier(address, 8);
+    Ok(Some(Value::Ref(ObjectRef(old.unwrap_or_else(|old| old)))))
+}
+
+/// Gets the object an internal call is given, failing for null.
+fn object(value: &Value) -> Result<ObjectRef, Error> {
+    match *value {
+        Value::Ref(object) if object.is_null() => Err(ExceptionKind::NullReference.into()),
+        Value::Ref(object) => Ok(object),
+        _ => Err(Error::InvalidProgram(format!("{:?} is not an object", value))),
+    }
+}
+
+/// Gets the object or null an internal call is given.
+fn object_or_null(value: &Value) -> Result<ObjectRef, Error> {
+    value.as_ref().ok_or_else(|| Error::InvalidProgram(format!("{:?} is not an object", value)))
+}
+
+fn integer(value: &Value) -> Result<i64, Error> {
+    value.as_i64().ok_or_else(|| Error::InvalidProgram(format!("{:?} is not an integer", value)))
+}
+
+/// Gets the address a `ref` argument points to, failing for null.
+fn address(value: &Value) -> Result<*mut u8, Error> {
+    match *value {
+        Value::ByRef(pointer) if !pointer.is_null() => Ok(pointer.address()),
+        Value::NativeInt(address) if address != 0 => Ok(address as *mut u8),
+        _ => Err(ExceptionKind::NullReference.into()),
+    }
+}
+
+/// Gets the `Type` of a type, or null.
+fn reflect_type(context: &mut CallContext, ty: Option<TypeId>) -> Result<Option<Value>, Error> {
+    match ty {
+        Some(ty) => context.vm.reflect(Member::Type(ty)).map(|ty| Some(Value::Ref(ty))),
+        None => Ok(Some(Value::null())),
+    }
+}
+
+/// Creates an array of the objects reflecting members, whose element type is a reflection type in the core library.
+fn reflect_all(
+    context: &mut CallContext,
+    namespace: &'static str,
+    name: &'static str,
+    members: &[Member],
+) -> Result<Option<Value>, Error> {
+    let element_type = context.vm.types.corlib_type(namespace, name)?;
+    context.vm.reflect_all(element_type, members).map(Some)
+}
+
+fn reflected_type(context: &mut CallContext, value: &Value) -> Result<TypeId, Error> {
+    match context.vm.reflected(value)? {
+        Member::Type(ty) => Ok(ty),
+        _ => Err(Error::InvalidProgram(format!("{:?} is not a Type", value))),
+    }
+}
+
+fn reflected_method(context: &mut CallContext, value: &Value) -> Result<MethodId, Error> {
+    match context.vm.reflected(value)? {
+        Member::Method(method) => Ok(method),
+        _ => Err(Error::InvalidProgram(format!("{:?} is not a MethodBase", value))),
+    }
+}
+
+fn reflected_field(context: &mut CallContext, value: &Value) -> Result<FieldId, Error> {
+    match context.vm.reflected(value)? {
+        Member::Field(field) => Ok(field),
+        _ => Err(Error::InvalidProgram(format!("{:?} is not a FieldInfo", value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use ecma355metadata::cli::il::Opcode;
+
+    use error::{Error, ExceptionKind};
+    use interpreter::Value;
+    use runtime::RuntimeBuilder;
+    use test_assembly::*;
+
+    #[test]
+    pub fn internal_calls_are_bound_to_intrinsics_and_registered_functions() {
+        // enum Color { Red = 1, Green = 2 }
+        // static extern int Add(int a, int b);
+        // Console.WriteLine(((Color)2).ToString()); Console.WriteLine(((Color)3).ToString()); return Add(40, 2);
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let system_enum = app.corlib_type("System", "Enum");
+        let console = app.corlib_type("System", "Console");
+        let write_line = app.member_ref(console, "WriteLine", &method_sig(false, Ty::Void, &[Ty::String]));
+        let to_string = app.member_ref(object, "ToString", &method_sig(true, Ty::String, &[]));
+        let color = app.type_def(PUBLIC | SEALED, "", "Color", system_enum);
+        app.field(0, "value__", Ty::I4);
+        for &(name, value) in &[("Red", 1), ("Green", 2)] {
+            let field = app.field(LITERAL_FIELD, name, Ty::ValueType(color));
+            app.constant(field, Ty::I4, value);
+        }
+        app.type_def(PUBLIC, "", "Program", object);
+        let add = app.internal_method(STATIC, "Add", &method_sig(false, Ty::I4, &[Ty::I4, Ty::I4]));
+
+        let mut il = Il::new();
+        for &value in &[2, 3] {
+            il.ldc_i4(value).arg(Opcode::Box, color as i64).arg(Opcode::Callvirt, to_string as i64);
+            il.arg(Opcode::Call, write_line as i64);
+        }
+        il.ldc_i4(40).ldc_i4(2).arg(Opcode::Call, add as i64).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let written = lines.clone();
+        let builder = RuntimeBuilder::new()
+            .internal_call("System.Console::WriteLine(System.String)", move |context, args| {
+                written.borrow_mut().push(context.string(&args[0]).unwrap());
+                Ok(None)
+            })
+            .internal_call("Program::Add(System.Int32, System.Int32)", |_, args| match (&args[0], &args[1]) {
+                (&Value::I32(a), &Value::I32(b)) => Ok(Some(Value::I32(a + b))),
+                _ => Err(Error::InvalidProgram("Add is given non-ints".into())),
+            });
+        let (_directory, mut runtime) = runtime_with("internal_calls", &corlib(), &app, builder);
+        assert_eq!(Ok(42), runtime.run_main("App"));
+        assert_eq!(vec!["Green".to_string(), "3".to_string()], *lines.borrow());
+    }
+
+    #[test]
+    pub fn internal_calls_throw_exceptions_to_their_callers() {
+        // static extern void Fail();
+        // try { Fail(); return 0; } catch (DivideByZeroException) { return 7; }
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let divide_by_zero = app.corlib_type("System", "DivideByZeroException");
+        app.type_def(PUBLIC, "", "Program", object);
+        let fail = app.internal_method(STATIC, "Fail", &method_sig(false, Ty::Void, &[]));
+        let mut il = Il::new();
+        let labels: Vec<_> = (0..4).map(|_| il.label()).collect();
+        il.mark(labels[0]).arg(Opcode::Call, fail as i64).branch(Opcode::LeaveS, labels[3]);
+        il.mark(labels[1]).op(Opcode::Pop).ldc_i4(7).op(Opcode::Stloc0).branch(Opcode::LeaveS, labels[3]);
+        il.mark(labels[2]).mark(labels[3]).op(Opcode::Ldloc0).op(Opcode::Ret);
+        let body = Body::new(vec![Ty::I4], il).with_clauses(vec![Clause {
+            handler: Handler::Catch(divide_by_zero),
+            try_start: labels[0],
+            try_end: labels[1],
+            handler_start: labels[1],
+            handler_end: labels[2],
+        }]);
+        add_main(&mut app, body);
+
+        let builder = RuntimeBuilder::new().internal_call("Program::Fail()", |_, _| {
+            Err(ExceptionKind::DivideByZero.into())
+        });
+        let (_directory, mut runtime) = runtime_with("throwing_internal_call", &corlib(), &app, builder);
+        assert_eq!(Ok(7), runtime.run_main("App"));
+    }
+
+    #[test]
+    pub fn unbound_internal_calls_throw_missing_method_exceptions() {
+        // static extern int Missing(string name); return Missing(null);
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        app.type_def(PUBLIC, "", "Program", object);
+        let missing = app.internal_method(STATIC, "Missing", &method_sig(false, Ty::I4, &[Ty::String]));
+        let mut il = Il::new();
+        il.op(Opcode::Ldnull).arg(Opcode::Call, missing as i64).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        let report = concat!(
+            "System.MissingMethodException: Method not found: 'Program::Missing(System.String)'.\n",
+            "   at Program.Main"
+        );
+        let (_directory, mut runtime) = runtime("unbound_internal_call", &corlib(), &app);
+        assert_eq!(Err(Error::UnhandledException(report.into())), runtime.run_main("App"));
+    }
+}
@@ -0,0 +1,380 @@
+use ecma355metadata::cli::il::Opcode;
+
+use error::{Error, ExceptionKind};
+use gc::{self, ObjectRef};
+use interpreter::{Pointer, Value};
+use types::{MethodId, Storage, TypeId, TypeKind};
+use vm::Vm;
+
+impl Vm {
+    /// Executes `newarr`, `ldlen`, or an instruction that loads, stores or gets the address of an element of an SZ
+    /// array. `ty` is the type operand of the instructions that have one.
+    pub(super) fn array_instruction(&mut self, opcode: Opcode, ty: Option<TypeId>) -> Result<(), Error> {
+        match opcode {
+            Opcode::Newarr => {
+                let element = ty.ok_or_else(|| Error::InvalidProgram(format!("{} has no element type", opcode)))?;
+                let length = self.pop_index()?;
+                if length < 0 {
+                    return Err(ExceptionKind::Overflow.into());
+                }
+                let array_type = self.types.sz_array(element)?;
+                let array = self.new_array(array_type, length as usize)?;
+                self.push(Value::Ref(array));
+            }
+            Opcode::Ldlen => {
+                let array = self.pop_array()?;
+                self.push(Value::NativeInt(unsafe { array.array_length() } as isize));
+            }
+            Opcode::Ldelema => {
+                let ty = ty.ok_or_else(|| Error::InvalidProgram(format!("{} has no element type", opcode)))?;
+                let (pointer, element) = self.pop_element()?;
+                // The element could be read through the pointer as `ty`, but not written, unless it is exactly `ty`
+                if element != ty && !self.types.get(element).is_value_type() {
+                    return Err(ExceptionKind::ArrayTypeMismatch.into());
+                }
+                self.push(Value::ByRef(pointer));
+            }
+            Opcode::LdelemI1 | Opcode::LdelemU1 | Opcode::LdelemI2 | Opcode::LdelemU2 | Opcode::LdelemI4
+            | Opcode::LdelemU4 | Opcode::LdelemI8 | Opcode::LdelemI | Opcode::LdelemR4 | Opcode::LdelemR8
+            | Opcode::LdelemRef | Opcode::Ldelem => {
+                let (pointer, element) = self.pop_element()?;
+                let (ty, storage, size) = match ty {
+                    Some(ty) => self.value_layout(ty),
+                    None => (element, element_storage(opcode), 8),
+                };
+                let value = unsafe { Value::load(pointer.address(), storage, ty, size) };
+                self.push(value);
+            }
+            Opcode::StelemI | Opcode::StelemI1 | Opcode::StelemI2 | Opcode::StelemI4 | Opcode::StelemI8
+            | Opcode::StelemR4 | Opcode::StelemR8 | Opcode::StelemRef | Opcode::Stelem => {
+                let value = self.pop()?;
+                let (pointer, element) = self.pop_element()?;
+                let (storage, size) = match ty {
+                    Some(ty) => {
+                        let (_, storage, size) = self.value_layout(ty);
+                        (storage, size)
+                    }
+                    None => (element_storage(opcode), 8),
+                };
+                self.check_element(element, &value)?;
+                self.store_value(pointer.address(), &value, storage, size);
+            }
+            _ => return Err(Error::InvalidProgram(format!("{} is not an array instruction", opcode))),
+        }
+        Ok(())
+    }
+
+    /// Executes `newobj` with a constructor of an array with a rank, which takes the length of each dimension, or
+    /// the lower bound and length of each.
+    pub(super) fn new_md_array_with(&mut self, constructor: MethodId) -> Result<(), Error> {
+        let (array_type, param_count) = {
+            let constructor = self.types.method(constructor);
+            (constructor.owner, constructor.signature.params.len())
+        };
+        let rank = self.rank(array_type)?;
+        let mut args = Vec::with_capacity(param_count);
+        for _ in 0..param_count {
+            let arg = self.pop_index()?;
+            args.insert(0, arg as i32);
+        }
+        let bounds: Vec<(i32, i32)> = if param_count == rank as usize {
+            args.iter().map(|&length| (0, length)).collect()
+        } else {
+            args.chunks(2).map(|bounds| (bounds[0], bounds[1])).collect()
+        };
+        let array = self.new_md_array(array_type, &bounds)?;
+        self.push(Value::Ref(array));
+        Ok(())
+    }
+
+    /// Calls `Get`, `Set` or `Address` of an array with a rank, which the runtime provides.
+    pub(super) fn md_array_method(&mut self, method: MethodId) -> Result<(), Error> {
+        let (array_type, name) = {
+            let method = self.types.method(method);
+            (method.owner, method.name.clone())
+        };
+        let rank = self.rank(array_type)?;
+        let element = match self.types.get(array_type).kind {
+            TypeKind::Array(element, _) => element,
+            _ => unreachable!(),
+        };
+        let value = if name == "Set" { Some(self.pop()?) } else { None };
+        let mut indices = Vec::with_capacity(rank as usize);
+        for _ in 0..rank {
+            let index = self.pop_index()?;
+            indices.insert(0, index);
+        }
+        let array = self.pop_ref()?;
+        if array.is_null() {
+            return Err(ExceptionKind::NullReference.into());
+        }
+
+        // Find the element's position, counting through the dimensions with the last varying fastest
+        let mut position = 0usize;
+        for (dimension, &index) in indices.iter().enumerate() {
+            let (length, lower_bound) = unsafe {
+                let bounds = array.md_array_bounds();
+                (*bounds.add(dimension) as i64, *bounds.add(rank as usize + dimension) as i64)
+            };
+            if index < lower_bound || index - lower_bound >= length {
+                return Err(ExceptionKind::IndexOutOfRange.into());
+            }
+            position = position * length as usize + (index - lower_bound) as usize;
+        }
+        let (ty, storage, size) = self.value_layout(element);
+        let pointer = Pointer::into_object(array, gc::md_array_elements(rank) + position * size as usize);
+        match &name[..] {
+            "Get" => {
+                let value = unsafe { Value::load(pointer.address(), storage, ty, size) };
+                self.push(value);
+            }
+            "Set" => {
+                let value = value.unwrap();
+                self.check_element(element, &value)?;
+                self.store_value(pointer.address(), &value, storage, size);
+            }
+            "Address" => self.push(Value::ByRef(pointer)),
+            _ => return Err(Error::MissingMethod(format!("{}::{}", self.types.get(array_type), name))),
+        }
+        Ok(())
+    }
+
+    /// Gets the rank of an array type with one.
+    fn rank(&self, array_type: TypeId) -> Result<u32, Error> {
+        match self.types.get(array_type).kind {
+            TypeKind::Array(_, rank) => Ok(rank),
+            _ => Err(Error::InvalidProgram(format!("{} is not an array with a rank", self.types.get(array_type)))),
+        }
+    }
+
+    /// Pops an array index or length, which is an `int32` or a native integer.
+    fn pop_index(&mut self) -> Result<i64, Error> {
+        match self.pop()? {
+            Value::I32(index) => Ok(index as i64),
+            Value::NativeInt(index) => Ok(index as i64),
+            _ => Err(Error::InvalidProgram(format!("{} indexes an array with a non-integer", self.current()))),
+        }
+    }
+
+    fn pop_ref(&mut self) -> Result<ObjectRef, Error> {
+        match self.pop()? {
+            Value::Ref(object) => Ok(object),
+            _ => Err(Error::InvalidProgram(format!("{} uses a non-object as an array", self.current()))),
+        }
+    }
+
+    /// Pops an SZ array, throwing `NullReferenceException` if it is null.
+    fn pop_array(&mut self) -> Result<ObjectRef, Error> {
+        let array = self.pop_ref()?;
+        if array.is_null() {
+            return Err(ExceptionKind::NullReference.into());
+        }
+        match self.types.get(self.object_type(array)).kind {
+            TypeKind::SzArray(_) => Ok(array),
+            _ => Err(Error::InvalidProgram(format!("{} uses a non-array as an SZ array", self.current()))),
+        }
+    }
+
+    /// Pops an SZ array and an index into it, and gets a pointer to the element and the array's element type.
+    fn pop_element(&mut self) -> Result<(Pointer, TypeId), Error> {
+        let index = self.pop_index()?;
+        let array = self.pop_array()?;
+        let element = match self.types.get(self.object_type(array)).kind {
+            TypeKind::SzArray(element) => element,
+            _ => unreachable!(),
+        };
+        if index < 0 || index as usize >= unsafe { array.array_length() } {
+            return Err(ExceptionKind::IndexOutOfRange.into());
+        }
+        let size = self.types.get(element).value_size() as usize;
+        Ok((Pointer::into_object(array, gc::ARRAY_ELEMENTS + index as usize * size), element))
+    }
+
+    /// Checks that a value can be stored in an array with an element type. Arrays of reference types are
+    /// covariant, so an object can only be stored if it can be used as the array's actual element type.
+    fn check_element(&self, element: TypeId, value: &Value) -> Result<(), Error> {
+        if let Value::Ref(object) = *value {
+            if !object.is_null() && !self.types.is_assignable_to(self.object_type(object), element) {
+                return Err(ExceptionKind::ArrayTypeMismatch.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Gets the storage the `ldelem` and `stelem` instructions without a type operand read or write.
+pub(super) fn element_storage(opcode: Opcode) -> Storage {
+    match opcode {
+        Opcode::LdelemI1 | Opcode::StelemI1 => Storage::I1,
+        Opcode::LdelemU1 => Storage::U1,
+        Opcode::LdelemI2 | Opcode::StelemI2 => Storage::I2,
+        Opcode::LdelemU2 => Storage::U2,
+        Opcode::LdelemI4 | Opcode::StelemI4 => Storage::I4,
+        Opcode::LdelemU4 => Storage::U4,
+        Opcode::LdelemI8 | Opcode::StelemI8 => Storage::I8,
+        Opcode::LdelemR4 | Opcode::StelemR4 => Storage::R4,
+        Opcode::LdelemR8 | Opcode::StelemR8 => Storage::R8,
+        Opcode::LdelemRef | Opcode::StelemRef => Storage::Ref,
+        _ => Storage::NativeInt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecma355metadata::cli::il::Opcode;
+
+    use error::Error;
+    use gc::GcMode;
+    use runtime::RuntimeBuilder;
+    use test_assembly::*;
+
+    #[test]
+    pub fn sz_arrays_check_their_bounds_and_element_types() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let int32 = app.corlib_type("System", "Int32") as i64;
+        let int64 = app.corlib_type("System", "Int64") as i64;
+        let string = app.corlib_type("System", "String") as i64;
+        let x = app.user_string("x") as i64;
+
+        // int[] a = new int[10]; for (int i = 0; i < a.Length; i++) a[i] = i * i;
+        // long[] l = new long[2]; l[1] = 5; object[] o = new string[2]; o[0] = "x";
+        // return a[3] + 10 * a[9] + 1000 * l[1] + 10000 * (o[0] == "x" ? 1 : 0)
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        let (head, end) = (il.label(), il.label());
+        il.ldc_i4(10).arg(Opcode::Newarr, int32).op(Opcode::Stloc0).op(Opcode::LdcI40).op(Opcode::Stloc1);
+        il.mark(head).op(Opcode::Ldloc1).op(Opcode::Ldloc0).op(Opcode::Ldlen).op(Opcode::ConvI4);
+        il.branch(Opcode::Bge, end).op(Opcode::Ldloc0).op(Opcode::Ldloc1).op(Opcode::Ldloc1).op(Opcode::Ldloc1);
+        il.op(Opcode::Mul).op(Opcode::StelemI4).op(Opcode::Ldloc1).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc1);
+        il.branch(Opcode::Br, head);
+        il.mark(end).op(Opcode::Ldloc0).op(Opcode::LdcI43).op(Opcode::LdelemI4);
+        il.op(Opcode::Ldloc0).ldc_i4(9).arg(Opcode::Ldelem, int32).ldc_i4(10).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::LdcI42).arg(Opcode::Newarr, int64).op(Opcode::Stloc2);
+        il.op(Opcode::Ldloc2).op(Opcode::LdcI41).arg(Opcode::LdcI8, 5).arg(Opcode::Stelem, int64);
+        il.op(Opcode::Ldloc2).op(Opcode::LdcI41).arg(Opcode::Ldelema, int64).op(Opcode::LdindI8).op(Opcode::ConvI4);
+        il.ldc_i4(1000).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::LdcI42).arg(Opcode::Newarr, string).op(Opcode::Stloc3);
+        il.op(Opcode::Ldloc3).op(Opcode::LdcI40).arg(Opcode::Ldstr, x).op(Opcode::StelemRef);
+        il.op(Opcode::Ldloc3).op(Opcode::LdcI40).op(Opcode::LdelemRef).arg(Opcode::Ldstr, x).op(Opcode::Ceq);
+        il.ldc_i4(10000).op(Opcode::Mul).op(Opcode::Add).op(Opcode::Ret);
+        let locals = vec![Ty::sz_array(Ty::I4), Ty::I4, Ty::sz_array(Ty::I8), Ty::sz_array(Ty::Object)];
+        add_main(&mut app, Body::new(locals, il));
+        assert_eq!(Ok(15_819), run("sz_arrays", &corlib(), &app));
+
+        // Storing an int[] in a string[] through an object[] throws, as does getting the address of an element as
+        // an object, since an object could then be written there
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let int32 = app.corlib_type("System", "Int32") as i64;
+        let string = app.corlib_type("System", "String") as i64;
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::LdcI41).arg(Opcode::Newarr, string).op(Opcode::LdcI40);
+        il.op(Opcode::LdcI41).arg(Opcode::Newarr, int32).op(Opcode::StelemRef).op(Opcode::LdcI40).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+        let (_directory, mut stelem) = runtime("array_type_mismatch", &corlib(), &app);
+        let report = concat!(
+            "System.ArrayTypeMismatchException: Attempted to access an element as a type incompatible with the array.",
+            "\n   at Program.Main"
+        );
+        assert_eq!(Err(Error::UnhandledException(report.into())), stelem.run_main("App"));
+
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let string = app.corlib_type("System", "String") as i64;
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::LdcI41).arg(Opcode::Newarr, string).op(Opcode::LdcI40).arg(Opcode::Ldelema, object as i64);
+        il.op(Opcode::Pop).op(Opcode::LdcI40).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+        let (_directory, mut ldelema) = runtime("ldelema_type_mismatch", &corlib(), &app);
+        assert_eq!(Err(Error::UnhandledException(report.into())), ldelema.run_main("App"));
+
+        // Indexing past the end throws
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let int32 = app.corlib_type("System", "Int32") as i64;
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::LdcI41).arg(Opcode::Newarr, int32).op(Opcode::LdcI41).op(Opcode::LdelemI4).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+        let (_directory, mut out_of_range) = runtime("index_out_of_range", &corlib(), &app);
+        let report = "System.IndexOutOfRangeException: Index was outside the bounds of the array.\n   at Program.Main";
+        assert_eq!(Err(Error::UnhandledException(report.into())), out_of_range.run_main("App"));
+    }
+
+    #[test]
+    pub fn arrays_keep_the_objects_they_hold_alive() {
+        // object[] a = new object[100]; for (int i = 0; i < 100; i++) a[i] = i; then return the sum of the elements,
+        // with every allocation collecting garbage and moving the array and its elements
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let int32 = app.corlib_type("System", "Int32") as i64;
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        let (fill, sum, end) = (il.label(), il.label(), il.label());
+        il.ldc_i4(100).arg(Opcode::Newarr, object as i64).op(Opcode::Stloc0);
+        il.mark(fill).op(Opcode::Ldloc1).ldc_i4(100).branch(Opcode::Bge, sum);
+        il.op(Opcode::Ldloc0).op(Opcode::Ldloc1).op(Opcode::Ldloc1).arg(Opcode::Box, int32).op(Opcode::StelemRef);
+        il.op(Opcode::Ldloc1).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc1).branch(Opcode::Br, fill);
+        il.mark(sum).op(Opcode::Ldloc1).op(Opcode::LdcI41).op(Opcode::Sub).op(Opcode::Stloc1);
+        il.op(Opcode::Ldloc1).op(Opcode::LdcI40).branch(Opcode::Blt, end);
+        il.op(Opcode::Ldloc2).op(Opcode::Ldloc0).op(Opcode::Ldloc1).op(Opcode::LdelemRef);
+        il.arg(Opcode::UnboxAny, int32).op(Opcode::Add).op(Opcode::Stloc2).branch(Opcode::Br, sum);
+        il.mark(end).op(Opcode::Ldloc2).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::sz_array(Ty::Object), Ty::I4, Ty::I4], il));
+
+        let builder = RuntimeBuilder::new().gc_mode(GcMode::Generational).gc_stress(true);
+        let (_directory, mut runtime) = runtime_with("array_roots", &corlib(), &app, builder);
+        assert_eq!(Ok(4950), runtime.execute("App"));
+        assert_eq!(101, runtime.gc_stats().collections);
+    }
+
+    #[test]
+    pub fn arrays_with_a_rank_are_accessed_through_their_methods() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let matrix = app.type_spec(Ty::array(Ty::I4, 2));
+        let new_matrix = app.member_ref(matrix, ".ctor", &method_sig(true, Ty::Void, &[Ty::I4, Ty::I4]));
+        let new_bounded = app.member_ref(matrix, ".ctor", &method_sig(true, Ty::Void, &vec![Ty::I4; 4]));
+        let get = app.member_ref(matrix, "Get", &method_sig(true, Ty::I4, &[Ty::I4, Ty::I4])) as i64;
+        let set = app.member_ref(matrix, "Set", &method_sig(true, Ty::Void, &[Ty::I4, Ty::I4, Ty::I4])) as i64;
+        let address = app.member_ref(matrix, "Address", &method_sig(true, Ty::by_ref(Ty::I4), &[Ty::I4, Ty::I4]));
+
+        // int[,] m = new int[2, 3]; m[1, 2] = 12; m[0, 1] = 1;
+        // int[,] n = new int[1..2, 5..7]; n[2, 7] = 9;
+        // return m[1, 2] + 100 * m[0, 1] + 1000 * n[2, 7]
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::LdcI42).op(Opcode::LdcI43).arg(Opcode::Newobj, new_matrix as i64).op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI41).op(Opcode::LdcI42).ldc_i4(12).arg(Opcode::Call, set);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI40).op(Opcode::LdcI41).op(Opcode::LdcI41).arg(Opcode::Call, set);
+        il.op(Opcode::LdcI41).op(Opcode::LdcI42).op(Opcode::LdcI45).op(Opcode::LdcI43);
+        il.arg(Opcode::Newobj, new_bounded as i64).op(Opcode::Stloc1);
+        il.op(Opcode::Ldloc1).op(Opcode::LdcI42).op(Opcode::LdcI47).ldc_i4(9).arg(Opcode::Call, set);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI41).op(Opcode::LdcI42).arg(Opcode::Call, get);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI40).op(Opcode::LdcI41).arg(Opcode::Call, address as i64);
+        il.op(Opcode::LdindI4).ldc_i4(100).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::Ldloc1).op(Opcode::LdcI42).op(Opcode::LdcI47).arg(Opcode::Call, get);
+        il.ldc_i4(1000).op(Opcode::Mul).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::array(Ty::I4, 2), Ty::array(Ty::I4, 2)], il));
+        assert_eq!(Ok(9112), run("md_arrays", &corlib(), &app));
+
+        // Indices below a dimension's lower bound are out of range
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let matrix = app.type_spec(Ty::array(Ty::I4, 2));
+        let new_bounded = app.member_ref(matrix, ".ctor", &method_sig(true, Ty::Void, &vec![Ty::I4; 4]));
+        let get = app.member_ref(matrix, "Get", &method_sig(true, Ty::I4, &[Ty::I4, Ty::I4])) as i64;
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::LdcI41).op(Opcode::LdcI42).op(Opcode::LdcI45).op(Opcode::LdcI43);
+        il.arg(Opcode::Newobj, new_bounded as i64).op(Opcode::LdcI40).op(Opcode::LdcI45).arg(Opcode::Call, get);
+        il.op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+        let (_directory, mut out_of_range) = runtime("md_index_out_of_range", &corlib(), &app);
+        let report = "System.IndexOutOfRangeException: Index was outside the bounds of the array.\n   at Program.Main";
+        assert_eq!(Err(Error::UnhandledException(report.into())), out_of_range.run_main("App"));
+    }
+}
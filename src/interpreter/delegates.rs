@@ -0,0 +1,627 @@
+use std::os::raw::c_void;
+
+use ecma355metadata::cli::signatures::SignatureCallingConvention;
+
+use error::{Error, ExceptionKind};
+use gc::{self, ObjectRef};
+use interpreter::{Pointer, Value};
+use types::{AssemblyId, GenericContext, MethodId, TypeId};
+use vm::Vm;
+
+/// The offsets of the fields of `System.Delegate` and `System.MulticastDelegate` the runtime uses.
+struct DelegateLayout {
+    /// The object an instance method is called on, or the first argument a static method is called with, if the
+    /// delegate is closed over it.
+    target: usize,
+
+    /// The function pointer to the method.
+    method_ptr: usize,
+
+    /// The array of delegates a multicast delegate invokes in order, or null for a delegate that invokes one method.
+    invocation_list: usize,
+}
+
+impl Vm {
+    /// Gets the function pointer to a method, creating it the first time.
+    pub(super) fn method_pointer(&mut self, method: MethodId) -> Value {
+        if let Some(pointer) = self.method_pointers.get(&method) {
+            return Value::NativeInt(&**pointer as *const MethodId as isize);
+        }
+        let pointer = Box::new(method);
+        let address = &*pointer as *const MethodId as usize;
+        self.method_pointers.insert(method, pointer);
+        self.pointer_methods.insert(address, method);
+        Value::NativeInt(address as isize)
+    }
+
+    /// Gets the method a function pointer points to, or `None` if `ldftn` or `ldvirtftn` didn't load it.
+    fn pointer_method(&self, pointer: &Value) -> Option<MethodId> {
+        let address = match *pointer {
+            Value::NativeInt(address) => address as usize,
+            Value::I64(address) => address as usize,
+            _ => return None,
+        };
+        self.pointer_methods.get(&address).cloned()
+    }
+
+    /// Executes `calli`: calls the function a pointer on the stack points to, with the arguments below it. A method
+    /// must have the call site's signature, and a native function is called if the signature has an unmanaged
+    /// calling convention.
+    pub(super) fn indirect_call(
+        &mut self,
+        assembly: AssemblyId,
+        token: u32,
+        generics: &GenericContext,
+    ) -> Result<(), Error> {
+        let (calling_convention, signature) = self.types.resolve_call_site_token(assembly, token, generics)?;
+        let pointer = self.pop()?;
+        if pointer.as_i64() == Some(0) {
+            return Err(ExceptionKind::NullReference.into());
+        }
+        let method = self.pointer_method(&pointer);
+        match (calling_convention, method) {
+            (SignatureCallingConvention::Default, Some(method)) => {
+                if self.types.method(method).signature != signature {
+                    let method = self.types.method(method);
+                    return Err(Error::InvalidProgram(format!(
+                        "{} calls {}::{} through a pointer with another signature",
+                        self.current(),
+                        self.types.get(method.owner),
+                        method
+                    )));
+                }
+                self.call(method, false)
+            }
+            (SignatureCallingConvention::VarArgs, _) => Err(Error::InvalidProgram(format!(
+                "{} calls a function with a variable number of arguments through a pointer",
+                self.current()
+            ))),
+            (SignatureCallingConvention::Default, None) | (_, Some(_)) => Err(Error::InvalidProgram(format!(
+                "{} calls {:?} with a calling convention it doesn't have",
+                self.current(),
+                pointer
+            ))),
+            (_, None) => match pointer {
+                Value::NativeInt(address) => self.unmanaged_call(address as *const c_void, &signature),
+                _ => Err(Error::InvalidProgram(format!("{} calls {:?}", self.current(), pointer))),
+            },
+        }
+    }
+
+    /// Executes a method of a delegate type, which the runtime provides: the constructor, which binds the delegate
+    /// to a method, and `Invoke`. Asynchronous delegate invocation isn't supported, as in .NET Core.
+    pub(super) fn delegate_call(&mut self, method: MethodId) -> Result<(), Error> {
+        let (owner, name, arg_count) = {
+            let method = self.types.method(method);
+            (method.owner, method.name.clone(), method.arg_count())
+        };
+        let multicast_delegate = self.types.corlib_type("System", "MulticastDelegate")?;
+        if !self.types.is_subclass_of(owner, multicast_delegate) {
+            let message = format!("{}::{} has no code", self.types.get(owner), name);
+            return Err(Error::InvalidProgram(message));
+        }
+        match name.as_str() {
+            ".ctor" => {
+                let args = self.pop_args(arg_count)?;
+                self.construct_delegate(&args[0], &args[1..])
+            }
+            "Invoke" => self.invoke_delegate(method),
+            "BeginInvoke" | "EndInvoke" => Err(ExceptionKind::PlatformNotSupported.into()),
+            _ => Err(Error::InvalidProgram(format!("{}::{} has no code", self.types.get(owner), name))),
+        }
+    }
+
+    /// Binds a delegate to the method a function pointer points to, and the object it is closed over.
+    ///
+    /// A delegate is closed if the method takes one more argument than `Invoke` has parameters: an instance method
+    /// is called on the object, and a static method is called with it as its first argument. Otherwise it is open,
+    /// and `Invoke` passes its arguments on as they are.
+    pub(super) fn construct_delegate(&mut self, this: &Value, args: &[Value]) -> Result<(), Error> {
+        let (delegate, target, pointer) = match (this, args) {
+            (&Value::Ref(delegate), &[Value::Ref(target), ref pointer]) => (delegate, target, pointer),
+            _ => return Err(Error::InvalidProgram(format!("{} creates a delegate with {:?}", self.current(), args))),
+        };
+        let method = self.pointer_method(pointer).ok_or_else(|| {
+            Error::InvalidProgram(format!("{} creates a delegate with a pointer to no method", self.current()))
+        })?;
+        let ty = self.object_type(delegate);
+        let invoke = self.delegate_invoke(ty)?;
+        let param_count = self.types.method(invoke).signature.params.len();
+        let (arg_count, has_this) = {
+            let method = self.types.method(method);
+            (method.arg_count(), method.signature.has_this)
+        };
+        let message = if arg_count != param_count && arg_count != param_count + 1 {
+            Some(concat!(
+                "Cannot bind to the target method because its signature is not compatible with that of the delegate ",
+                "type."
+            ))
+        } else if arg_count == param_count + 1 && has_this && target.is_null() {
+            Some("Delegate to an instance method cannot have null 'this'.")
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            let thrown = self.raise_with_message(ExceptionKind::Argument, message)?;
+            return Err(self.throw_from_runtime(thrown));
+        }
+
+        let layout = self.delegate_layout()?;
+        unsafe {
+            let location = delegate.data().add(layout.target);
+            gc::write_ref(location, target);
+            self.heap.write_barrier(location, 8);
+            *(delegate.data().add(layout.method_ptr) as *mut isize) = pointer.as_i64().unwrap_or(0) as isize;
+        }
+        Ok(())
+    }
+
+    /// Gets the `Invoke` method of a delegate type.
+    fn delegate_invoke(&mut self, ty: TypeId) -> Result<MethodId, Error> {
+        self.types.prepare(ty)?;
+        let runtime_type = self.types.get(ty);
+        runtime_type.methods
+            .iter()
+            .cloned()
+            .find(|&method| self.types.method(method).name == "Invoke")
+            .ok_or_else(|| Error::TypeLoad(format!("{} has no Invoke method", runtime_type)))
+    }
+
+    /// Executes a delegate's `Invoke` method, calling each method in its invocation list in order with the arguments
+    /// on the stack. It returns what the last method returns.
+    fn invoke_delegate(&mut self, invoke: MethodId) -> Result<(), Error> {
+        let arg_count = self.types.method(invoke).arg_count();
+        let layout = self.delegate_layout()?;
+        let base = self.frame().stack.len().checked_sub(arg_count).ok_or_else(|| {
+            Error::InvalidProgram(format!("{} pops from an empty stack", self.current()))
+        })?;
+        let delegate = match self.frame().stack[base].clone() {
+            Value::Ref(delegate) if delegate.is_null() => return Err(ExceptionKind::NullReference.into()),
+            Value::Ref(delegate) => delegate,
+            other => return Err(Error::InvalidProgram(format!("{} invokes {:?}", self.current(), other))),
+        };
+        let count = match unsafe { gc::read_ref(delegate.data().add(layout.invocation_list)) } {
+            list if list.is_null() => 1,
+            list => unsafe { list.array_length() },
+        };
+
+        // The delegates before the last are called to completion with copies of the arguments, which stay on the
+        // stack (along with the delegate and its invocation list) in case they move while the methods run
+        for index in 0..count.saturating_sub(1) {
+            let entry = self.delegate_entry(base, index, &layout);
+            let args = self.frame().stack[(base + 1)..].to_vec();
+            let (method, args) = self.delegate_target(entry, args, &layout)?;
+            self.call_nested(method, args)?;
+        }
+        let entry = self.delegate_entry(base, count.saturating_sub(1), &layout);
+        let mut args = self.pop_args(arg_count)?;
+        let (method, args) = self.delegate_target(entry, args.split_off(1), &layout)?;
+        for arg in args {
+            self.push(arg);
+        }
+        self.call(method, false)
+    }
+
+    /// Gets the delegate at an index of the invocation list of the delegate at an index of the stack, which is the
+    /// delegate itself if it has no invocation list.
+    fn delegate_entry(&mut self, index_on_stack: usize, index: usize, layout: &DelegateLayout) -> ObjectRef {
+        let delegate = self.frame().stack[index_on_stack].as_ref().unwrap_or(ObjectRef::NULL);
+        unsafe {
+            let list = gc::read_ref(delegate.data().add(layout.invocation_list));
+            if list.is_null() {
+                delegate
+            } else {
+                gc::read_ref(list.array_element(index, 8))
+            }
+        }
+    }
+
+    /// Gets the method a delegate calls, and the arguments it calls it with given the arguments of `Invoke`.
+    fn delegate_target(
+        &mut self,
+        delegate: ObjectRef,
+        mut args: Vec<Value>,
+        layout: &DelegateLayout,
+    ) -> Result<(MethodId, Vec<Value>), Error> {
+        let (target, pointer) = unsafe {
+            let pointer = *(delegate.data().add(layout.method_ptr) as *const isize);
+            (gc::read_ref(delegate.data().add(layout.target)), Value::NativeInt(pointer))
+        };
+        let method = self.pointer_method(&pointer).ok_or_else(|| {
+            Error::InvalidProgram(format!("{} invokes a delegate with a pointer to no method", self.current()))
+        })?;
+        let (arg_count, has_this, owner) = {
+            let method = self.types.method(method);
+            (method.arg_count(), method.signature.has_this, method.owner)
+        };
+        if arg_count == args.len() + 1 {
+            // A value type's methods take a pointer to the value, which is the boxed target's data
+            let this = if has_this && self.types.get(owner).is_value_type() {
+                Value::ByRef(Pointer::into_object(target, 0))
+            } else {
+                Value::Ref(target)
+            };
+            args.insert(0, this);
+        }
+        Ok((method, args))
+    }
+
+    /// Calls a method and runs it to completion, and gets what it returns.
+    pub(super) fn call_nested(&mut self, method: MethodId, args: Vec<Value>) -> Result<Option<Value>, Error> {
+        let base = self.frames.len();
+        for arg in args {
+            self.push(arg);
+        }
+        self.call(method, false)?;
+        if self.frames.len() > base {
+            let result = self.run(base);
+            self.frames.truncate(base);
+            result
+        } else if self.types.method(method).signature.ret.is_some() {
+            self.pop().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Combines two delegates of the same type into one that invokes the methods of the first, then the second, as
+    /// `Delegate.Combine` does. Combining with null gets the other delegate.
+    pub(crate) fn combine_delegates(&mut self, first: &Value, second: &Value) -> Result<Value, Error> {
+        let (first, second) = match (first.as_ref(), second.as_ref()) {
+            (Some(first), Some(second)) => (first, second),
+            _ => return Err(Error::InvalidProgram("Delegate.Combine is given a non-delegate".into())),
+        };
+        if first.is_null() {
+            return Ok(Value::Ref(second));
+        } else if second.is_null() {
+            return Ok(Value::Ref(first));
+        }
+        let ty = self.object_type(first);
+        if self.object_type(second) != ty {
+            let thrown = self.raise_with_message(ExceptionKind::Argument, "Delegates must be of the same type.")?;
+            return Err(self.throw_from_runtime(thrown));
+        }
+        let layout = self.delegate_layout()?;
+        let mut entries = self.invocation_list(first, &layout);
+        entries.extend(self.invocation_list(second, &layout));
+        self.delegate_with_list(ty, entries, &layout)
+    }
+
+    /// Removes the last occurrence of a delegate's invocation list from another's, as `Delegate.Remove` does. Gets
+    /// null if nothing is left, and the delegate it is removed from if it doesn't occur.
+    pub(crate) fn remove_delegate(&mut self, source: &Value, value: &Value) -> Result<Value, Error> {
+        let (source, value) = match (source.as_ref(), value.as_ref()) {
+            (Some(source), Some(value)) => (source, value),
+            _ => return Err(Error::InvalidProgram("Delegate.Remove is given a non-delegate".into())),
+        };
+        if source.is_null() || value.is_null() {
+            return Ok(Value::Ref(source));
+        }
+        let layout = self.delegate_layout()?;
+        let mut entries = self.invocation_list(source, &layout);
+        let removed = self.invocation_list(value, &layout);
+        let same = |first: ObjectRef, second: ObjectRef| unsafe {
+            self.object_type(first) == self.object_type(second)
+                && gc::read_ref(first.data().add(layout.target)) == gc::read_ref(second.data().add(layout.target))
+                && *(first.data().add(layout.method_ptr) as *const isize)
+                    == *(second.data().add(layout.method_ptr) as *const isize)
+        };
+        let found = (0..(entries.len() + 1).saturating_sub(removed.len())).rev().find(|&start| {
+            removed.iter().enumerate().all(|(index, &entry)| same(entries[start + index], entry))
+        });
+        match found {
+            Some(start) => {
+                entries.drain(start..(start + removed.len()));
+                let ty = self.object_type(source);
+                self.delegate_with_list(ty, entries, &layout)
+            }
+            None => Ok(Value::Ref(source)),
+        }
+    }
+
+    /// Gets the delegates a delegate invokes, which is the delegate itself if it has no invocation list.
+    fn invocation_list(&self, delegate: ObjectRef, layout: &DelegateLayout) -> Vec<ObjectRef> {
+        unsafe {
+            let list = gc::read_ref(delegate.data().add(layout.invocation_list));
+            if list.is_null() {
+                return vec![delegate];
+            }
+            (0..list.array_length()).map(|index| gc::read_ref(list.array_element(index, 8))).collect()
+        }
+    }
+
+    /// Gets a delegate of a type that invokes the delegates in a list, which is null for an empty list and the only
+    /// delegate in a list of one.
+    fn delegate_with_list(
+        &mut self,
+        ty: TypeId,
+        entries: Vec<ObjectRef>,
+        layout: &DelegateLayout,
+    ) -> Result<Value, Error> {
+        match entries.len() {
+            0 => return Ok(Value::null()),
+            1 => return Ok(Value::Ref(entries[0])),
+            _ => {}
+        }
+
+        // The delegates are held in handles while the list and the delegate are allocated, in case they move
+        let base = self.handles.len();
+        self.handles.extend(&entries);
+        let result = self.new_object(ty).and_then(|delegate| {
+            self.handles.push(delegate);
+            let object = self.types.object()?;
+            let array_type = self.types.sz_array(object)?;
+            self.new_array(array_type, entries.len())
+        });
+        let list = match result {
+            Ok(list) => list,
+            Err(error) => {
+                self.handles.truncate(base);
+                return Err(error);
+            }
+        };
+        let delegate = self.handles.pop().unwrap();
+        let entries: Vec<_> = self.handles.drain(base..).collect();
+
+        // A multicast delegate's target and method are its last delegate's, as `Delegate.Target` and
+        // `Delegate.Method` are
+        let last = *entries.last().unwrap();
+        unsafe {
+            for (index, &entry) in entries.iter().enumerate() {
+                gc::write_ref(list.array_element(index, 8), entry);
+            }
+            self.heap.write_barrier(list.array_element(0, 8), entries.len() * 8);
+            gc::write_ref(delegate.data().add(layout.target), gc::read_ref(last.data().add(layout.target)));
+            self.heap.write_barrier(delegate.data().add(layout.target), 8);
+            gc::write_ref(delegate.data().add(layout.invocation_list), list);
+            self.heap.write_barrier(delegate.data().add(layout.invocation_list), 8);
+            let pointer = *(last.data().add(layout.method_ptr) as *const isize);
+            *(delegate.data().add(layout.method_ptr) as *mut isize) = pointer;
+        }
+        Ok(Value::Ref(delegate))
+    }
+
+    fn delegate_layout(&mut self) -> Result<DelegateLayout, Error> {
+        let delegate = self.types.corlib_type("System", "Delegate")?;
+        let multicast_delegate = self.types.corlib_type("System", "MulticastDelegate")?;
+        Ok(DelegateLayout {
+            target: self.field_offset(delegate, "_target")?,
+            method_ptr: self.field_offset(delegate, "_methodPtr")?,
+            invocation_list: self.field_offset(multicast_delegate, "_invocationList")?,
+        })
+    }
+
+    pub(super) fn field_offset(&mut self, ty: TypeId, name: &str) -> Result<usize, Error> {
+        match self.types.find_field(ty, name)? {
+            Some(field) => Ok(self.types.field(field).offset as usize),
+            None => Err(Error::TypeLoad(format!("{} has no {} field", self.types.get(ty), name))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecma355metadata::cli::il::Opcode;
+    use libc;
+
+    use error::Error;
+    use interpreter::Value;
+    use runtime::RuntimeBuilder;
+    use test_assembly::*;
+
+    /// Defines a static `int Twice(int x)` in the type defined last.
+    fn define_twice(app: &mut AssemblyBuilder) -> u32 {
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).op(Opcode::LdcI42).op(Opcode::Mul).op(Opcode::Ret);
+        app.method(STATIC, "Twice", &method_sig(false, Ty::I4, &[Ty::I4]), Some(Body::new(vec![], il)))
+    }
+
+    #[test]
+    pub fn delegates_call_static_and_instance_methods_open_or_closed() {
+        // class Adder { int amount; virtual int Add(int x) { return amount + x; } }
+        // class Doubler : Adder { override int Add(int x) { return amount + x * 2; } }
+        // delegate int Transform(int x); delegate int Open(Adder adder, int x);
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+        let adder = app.type_def(PUBLIC, "", "Adder", object);
+        let amount = app.field(0, "amount", Ty::I4);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64).op(Opcode::Ret);
+        let default_ctor = method_sig(true, Ty::Void, &[]);
+        let adder_ctor = app.method(CONSTRUCTOR, ".ctor", &default_ctor, Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Ldfld, amount as i64).op(Opcode::Ldarg1).op(Opcode::Add).op(Opcode::Ret);
+        let add_sig = method_sig(true, Ty::I4, &[Ty::I4]);
+        let add = app.method(VIRTUAL | NEW_SLOT, "Add", &add_sig, Some(Body::new(vec![], il)));
+        app.type_def(PUBLIC, "", "Doubler", adder);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, adder_ctor as i64).op(Opcode::Ret);
+        let doubler_ctor = app.method(CONSTRUCTOR, ".ctor", &default_ctor, Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Ldfld, amount as i64).op(Opcode::Ldarg1).op(Opcode::LdcI42).op(Opcode::Mul);
+        il.op(Opcode::Add).op(Opcode::Ret);
+        app.method(VIRTUAL, "Add", &add_sig, Some(Body::new(vec![], il)));
+        let (_, transform, transform_invoke) = delegate_type(&mut app, "Transform", Ty::I4, &[Ty::I4]);
+        let (_, open, open_invoke) = delegate_type(&mut app, "Open", Ty::I4, &[Ty::Class(adder), Ty::I4]);
+
+        // static int Scale(Adder adder, int x) { return adder.amount * x; }
+        app.type_def(PUBLIC, "", "Program", object);
+        let twice = define_twice(&mut app);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Ldfld, amount as i64).op(Opcode::Ldarg1).op(Opcode::Mul).op(Opcode::Ret);
+        let signature = method_sig(false, Ty::I4, &[Ty::Class(adder), Ty::I4]);
+        let scale = app.method(STATIC, "Scale", &signature, Some(Body::new(vec![], il)));
+
+        // Adder adder = new Adder { amount = 3 };
+        // int result = new Transform(adder.Add)(4) * 100 + new Transform(Twice)(5);
+        // result = result * 100 + new Transform(adder.Scale)(6);
+        // result = result * 100 + new Open(Adder.Add)(adder, 1);
+        // return result * 100 + new Transform(new Doubler { amount = 3 }.Add)(5);
+        let mut il = Il::new();
+        il.arg(Opcode::Newobj, adder_ctor as i64).op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI43).arg(Opcode::Stfld, amount as i64);
+        il.op(Opcode::Ldloc0).arg(Opcode::Ldftn, add as i64).arg(Opcode::Newobj, transform as i64);
+        il.op(Opcode::LdcI44).arg(Opcode::Callvirt, transform_invoke as i64).ldc_i4(100).op(Opcode::Mul);
+        il.op(Opcode::Ldnull).arg(Opcode::Ldftn, twice as i64).arg(Opcode::Newobj, transform as i64);
+        il.op(Opcode::LdcI45).arg(Opcode::Callvirt, transform_invoke as i64).op(Opcode::Add);
+        il.ldc_i4(100).op(Opcode::Mul);
+        il.op(Opcode::Ldloc0).arg(Opcode::Ldftn, scale as i64).arg(Opcode::Newobj, transform as i64);
+        il.op(Opcode::LdcI46).arg(Opcode::Callvirt, transform_invoke as i64).op(Opcode::Add);
+        il.ldc_i4(100).op(Opcode::Mul);
+        il.op(Opcode::Ldnull).arg(Opcode::Ldftn, add as i64).arg(Opcode::Newobj, open as i64);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI41).arg(Opcode::Callvirt, open_invoke as i64).op(Opcode::Add);
+        il.ldc_i4(100).op(Opcode::Mul);
+        il.arg(Opcode::Newobj, doubler_ctor as i64);
+        il.op(Opcode::Dup).op(Opcode::LdcI43).arg(Opcode::Stfld, amount as i64);
+        il.op(Opcode::Dup).arg(Opcode::Ldvirtftn, add as i64).arg(Opcode::Newobj, transform as i64);
+        il.op(Opcode::LdcI45).arg(Opcode::Callvirt, transform_invoke as i64).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::Class(adder)], il));
+        assert_eq!(Ok(7_10_18_04_13), run("delegates", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn multicast_delegates_invoke_each_method_in_order() {
+        // delegate int Counter();
+        // static int trace; static int One() { trace = trace * 10 + 1; return 1; }, and Two and Three likewise
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let delegate = app.corlib_type("System", "Delegate");
+        let signature = method_sig(false, Ty::Class(delegate), &[Ty::Class(delegate), Ty::Class(delegate)]);
+        let combine = app.member_ref(delegate, "Combine", &signature);
+        let remove = app.member_ref(delegate, "Remove", &signature);
+        let (counter, counter_ctor, invoke) = delegate_type(&mut app, "Counter", Ty::I4, &[]);
+        app.type_def(PUBLIC, "", "Program", object);
+        let trace_field = app.field(STATIC_FIELD, "trace", Ty::I4);
+        let methods: Vec<_> = (1..4)
+            .map(|digit| {
+                let mut il = Il::new();
+                trace(&mut il, trace_field, digit);
+                il.ldc_i4(digit).op(Opcode::Ret);
+                let name = ["One", "Two", "Three"][digit as usize - 1];
+                app.method(STATIC, name, &method_sig(false, Ty::I4, &[]), Some(Body::new(vec![], il)))
+            })
+            .collect();
+
+        // Counter counter = One; counter += Two; counter += Three; counter += Two; counter();
+        // counter -= Two; int result = counter();
+        // Counter one = One; if (one - one != null) return -1;
+        // return trace * 10 + result;
+        let mut il = Il::new();
+        let removed = il.label();
+        for &method in &[methods[0], methods[1], methods[2], methods[1]] {
+            il.op(Opcode::Ldloc0).op(Opcode::Ldnull).arg(Opcode::Ldftn, method as i64);
+            il.arg(Opcode::Newobj, counter_ctor as i64).arg(Opcode::Call, combine as i64);
+            il.arg(Opcode::Castclass, counter as i64).op(Opcode::Stloc0);
+        }
+        il.op(Opcode::Ldloc0).arg(Opcode::Callvirt, invoke as i64).op(Opcode::Pop);
+        il.op(Opcode::Ldloc0).op(Opcode::Ldnull).arg(Opcode::Ldftn, methods[1] as i64);
+        il.arg(Opcode::Newobj, counter_ctor as i64).arg(Opcode::Call, remove as i64);
+        il.arg(Opcode::Castclass, counter as i64).op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc0).arg(Opcode::Callvirt, invoke as i64);
+        il.op(Opcode::Ldnull).arg(Opcode::Ldftn, methods[0] as i64).arg(Opcode::Newobj, counter_ctor as i64);
+        il.op(Opcode::Dup).arg(Opcode::Call, remove as i64).branch(Opcode::Brfalse, removed);
+        il.op(Opcode::Pop).ldc_i4(-1).op(Opcode::Ret);
+        il.mark(removed).arg(Opcode::Ldsfld, trace_field as i64).ldc_i4(10).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::Class(counter)], il));
+        assert_eq!(Ok(1_232_123 * 10 + 3), run("multicast_delegates", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn function_pointers_are_called_with_their_signatures_checked() {
+        // class Program { int amount; int Add(int x) { return amount + x; } static extern IntPtr Labs(); }
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+        app.type_def(PUBLIC, "", "Program", object);
+        let amount = app.field(0, "amount", Ty::I4);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64).op(Opcode::Ret);
+        let ctor = app.method(CONSTRUCTOR, ".ctor", &method_sig(true, Ty::Void, &[]), Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Ldfld, amount as i64).op(Opcode::Ldarg1).op(Opcode::Add).op(Opcode::Ret);
+        let add = app.method(0, "Add", &method_sig(true, Ty::I4, &[Ty::I4]), Some(Body::new(vec![], il)));
+        let twice = define_twice(&mut app);
+        let labs = app.internal_method(STATIC, "Labs", &method_sig(false, Ty::I, &[]));
+
+        // return ((delegate*<int, int>)&Twice)(21) * 10000 + ((delegate*<Program, int, int>)&Add)(new Program { amount
+        //     = 3 }, 4) * 100 + (int)((delegate* unmanaged[Cdecl]<long, long>)Labs())(-7);
+        let static_sig = app.stand_alone_sig(&method_sig(false, Ty::I4, &[Ty::I4]));
+        let instance_sig = app.stand_alone_sig(&method_sig(true, Ty::I4, &[Ty::I4]));
+        let mut cdecl_sig = method_sig(false, Ty::I8, &[Ty::I8]);
+        cdecl_sig[0] = 0x01;
+        let cdecl_sig = app.stand_alone_sig(&cdecl_sig);
+        let mut il = Il::new();
+        il.ldc_i4(21).arg(Opcode::Ldftn, twice as i64).arg(Opcode::Calli, static_sig as i64);
+        il.ldc_i4(100).op(Opcode::Mul);
+        il.arg(Opcode::Newobj, ctor as i64).op(Opcode::Dup).op(Opcode::LdcI43).arg(Opcode::Stfld, amount as i64);
+        il.op(Opcode::LdcI44).arg(Opcode::Ldftn, add as i64).arg(Opcode::Calli, instance_sig as i64);
+        il.op(Opcode::Add).ldc_i4(100).op(Opcode::Mul);
+        il.ldc_i4(-7).op(Opcode::ConvI8).arg(Opcode::Call, labs as i64).arg(Opcode::Calli, cdecl_sig as i64);
+        il.op(Opcode::ConvI4).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+        let labs = libc::labs as *const () as isize;
+        let builder =
+            RuntimeBuilder::new().internal_call("Program::Labs()", move |_, _| Ok(Some(Value::NativeInt(labs))));
+        let (_directory, mut runtime) = runtime_with("function_pointers", &corlib(), &app, builder);
+        assert_eq!(Ok(42_07_07), runtime.execute("App"));
+
+        // return ((delegate*<int, long>)&Twice)(1);
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        app.type_def(PUBLIC, "", "Program", object);
+        let twice = define_twice(&mut app);
+        let signature = app.stand_alone_sig(&method_sig(false, Ty::I8, &[Ty::I4]));
+        let mut il = Il::new();
+        il.op(Opcode::LdcI41).arg(Opcode::Ldftn, twice as i64).arg(Opcode::Calli, signature as i64);
+        il.op(Opcode::ConvI4).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+        let message = "Program::Main calls Program::Twice through a pointer with another signature";
+        assert_eq!(
+            Err(Error::InvalidProgram(message.into())),
+            run("function_pointer_signature", &corlib(), &app)
+        );
+    }
+
+    #[test]
+    pub fn unsupported_delegate_uses_throw_the_exceptions_dotnet_throws() {
+        let report = |name: &str, null_this: bool| {
+            // new Transform(Twice).BeginInvoke(1, null, null), or new Transform(((Program)null).Add)
+            let mut app = AssemblyBuilder::new("App");
+            let object = app.corlib_type("System", "Object");
+            let (transform, ctor, _) = delegate_type(&mut app, "Transform", Ty::I4, &[Ty::I4]);
+            let signature = method_sig(true, Ty::Object, &[Ty::I4, Ty::Object, Ty::Object]);
+            let begin_invoke = app.member_ref(transform, "BeginInvoke", &signature);
+            app.type_def(PUBLIC, "", "Program", object);
+            let twice = define_twice(&mut app);
+            let mut il = Il::new();
+            il.op(Opcode::Ldarg1).op(Opcode::Ret);
+            let add = app.method(0, "Add", &method_sig(true, Ty::I4, &[Ty::I4]), Some(Body::new(vec![], il)));
+            let mut il = Il::new();
+            il.op(Opcode::Ldnull);
+            if null_this {
+                il.arg(Opcode::Ldftn, add as i64).arg(Opcode::Newobj, ctor as i64);
+            } else {
+                il.arg(Opcode::Ldftn, twice as i64).arg(Opcode::Newobj, ctor as i64);
+                il.op(Opcode::LdcI41).op(Opcode::Ldnull).op(Opcode::Ldnull).arg(Opcode::Callvirt, begin_invoke as i64);
+            }
+            il.op(Opcode::Pop).op(Opcode::LdcI40).op(Opcode::Ret);
+            add_main(&mut app, Body::new(vec![], il));
+            let (_directory, mut runtime) = runtime(name, &corlib(), &app);
+            match runtime.run_main("App") {
+                Err(Error::UnhandledException(report)) => report,
+                result => panic!("{:?}", result),
+            }
+        };
+
+        assert_eq!(
+            "System.PlatformNotSupportedException: Operation is not supported on this platform.\n   at Program.Main",
+            report("begin_invoke", false)
+        );
+        assert_eq!(
+            "System.ArgumentException: Delegate to an instance method cannot have null 'this'.\n   at Program.Main",
+            report("null_this_delegate", true)
+        );
+    }
+}
@@ -0,0 +1,598 @@
+use std::rc::Rc;
+
+use error::{Error, ExceptionKind};
+use gc::ObjectRef;
+use interpreter::{ClauseKind, Flow, Frame, Value};
+use types::{MethodSig, Storage, TypeKind};
+use vm::Vm;
+
+/// An exception being thrown, with the stack trace of where it was first thrown.
+#[derive(Clone)]
+pub struct Thrown {
+    pub exception: ObjectRef,
+    pub stack_trace: Rc<str>,
+}
+
+/// A handler block a frame is executing.
+pub struct ActiveHandler {
+    /// The index of the handler's clause in the method's clauses.
+    pub clause: usize,
+    pub exit: HandlerExit,
+}
+
+/// What happens when a handler ends.
+pub enum HandlerExit {
+    /// The handler is a catch handler, which ends with `leave`. `rethrow` throws the exception it caught again.
+    Catch(Thrown),
+
+    /// The handler is a `finally` handler `leave` is running, after which the remaining `finally` handlers run and
+    /// execution continues at the instruction index `target`.
+    Leave { finallys: Vec<usize>, target: usize },
+
+    /// The handler is a `finally` or `fault` handler run while unwinding the stack for an exception.
+    Unwind(Unwind),
+}
+
+/// An exception being unwound to its handler.
+pub struct Unwind {
+    thrown: Thrown,
+
+    /// The frame and clause of the handler, or `None` if no frame `run` was called for handles the exception.
+    target: Option<(usize, usize)>,
+
+    /// The instruction the top frame is being unwound from, and the last of its clauses whose handler has run.
+    ip: usize,
+    last_clause: Option<usize>,
+}
+
+impl Frame {
+    /// Ends the handlers the frame is leaving by executing the instruction at an index.
+    fn exit_handlers(&mut self, index: usize) {
+        let code = self.code.clone();
+        self.handlers.retain(|handler| code.clauses[handler.clause].handles(index));
+    }
+
+    /// Calls `visit` with the location of the exception held by each handler.
+    pub(crate) fn visit_handler_roots<F: FnMut(*mut u8)>(&mut self, mut visit: F) {
+        for handler in &mut self.handlers {
+            match handler.exit {
+                HandlerExit::Catch(ref mut thrown) | HandlerExit::Unwind(Unwind { ref mut thrown, .. }) => {
+                    visit(&mut thrown.exception as *mut ObjectRef as *mut u8)
+                }
+                HandlerExit::Leave { .. } => {}
+            }
+        }
+    }
+}
+
+impl Vm {
+    /// Captures the stack trace for an exception being thrown by the current instruction.
+    pub(crate) fn thrown_here(&self, exception: ObjectRef) -> Thrown {
+        let mut stack_trace = String::new();
+        for frame in self.frames.iter().rev() {
+            let method = self.types.method(frame.method);
+            stack_trace += &format!("\n   at {}.{}", self.types.get(method.owner), method);
+        }
+        Thrown {
+            exception,
+            stack_trace: stack_trace.into(),
+        }
+    }
+
+    /// Creates an instance of the corlib exception type for an exception the runtime raised, with the message .NET
+    /// gives it, to throw from the current instruction.
+    pub(crate) fn raise(&mut self, kind: ExceptionKind) -> Result<Thrown, Error> {
+        self.raise_with_message(kind, kind.message())
+    }
+
+    /// Creates an instance of the corlib exception type for an exception the runtime raised, with a message, to
+    /// throw from the current instruction.
+    pub(crate) fn raise_with_message(&mut self, kind: ExceptionKind, message: &str) -> Result<Thrown, Error> {
+        let ty = self.types.corlib_type(kind.namespace(), kind.type_name())?;
+        let string = self.types.string()?;
+        self.types.prepare(ty)?;
+        let constructors: Vec<_> = self.types
+            .get(ty)
+            .methods
+            .iter()
+            .cloned()
+            .filter(|&method| self.types.method(method).name == ".ctor")
+            .collect();
+        let with_message = MethodSig {
+            has_this: true,
+            ret: None,
+            params: vec![string],
+        };
+        let without_message = MethodSig {
+            params: Vec::new(),
+            ..with_message.clone()
+        };
+        let constructor = |signature: &MethodSig| {
+            constructors.iter().cloned().find(|&method| self.types.method(method).signature == *signature)
+        };
+        let (with_message, without_message) = (constructor(&with_message), constructor(&without_message));
+
+        // The exception stays on the stack while the message is allocated and it is constructed, in case it moves
+        let exception = self.new_object(ty)?;
+        self.push(Value::Ref(exception));
+        let message = self.new_string(&message.encode_utf16().collect::<Vec<_>>())?;
+        let this = self.frame().stack.last().unwrap().clone();
+        let result = match (with_message, without_message) {
+            (Some(constructor), _) => self.invoke(constructor, vec![this, Value::Ref(message)]),
+            (None, Some(constructor)) => self.invoke(constructor, vec![this]),
+            (None, None) => Ok(None),
+        };
+        let exception = self.pop()?;
+        result?;
+        Ok(self.thrown_here(exception.as_ref().unwrap()))
+    }
+
+    /// Keeps an exception the runtime throws outside the interpreter's own instructions, such as from a type's
+    /// initializer, in `self.unhandled`, and gets the error the interpreter throws it on from.
+    pub(crate) fn throw_from_runtime(&mut self, thrown: Thrown) -> Error {
+        let description = self.describe(&thrown);
+        self.unhandled = Some(thrown);
+        Error::UnhandledException(description)
+    }
+
+    /// Throws an exception from the top frame: finds the handler that will catch it, running filters along the way,
+    /// then starts unwinding the stack to it.
+    pub(crate) fn throw(&mut self, base: usize, thrown: Thrown) -> Result<Flow, Error> {
+        self.thrown.push(thrown);
+        let target = self.find_handler(base);
+        let thrown = self.thrown.pop().unwrap();
+        let ip = self.frame().ip.saturating_sub(1);
+        self.unwind(base, Unwind {
+            thrown,
+            target: target?,
+            ip,
+            last_clause: None,
+        })
+    }
+
+    /// Finds the catch or filter clause that handles the exception on top of `self.thrown`, searching the frames
+    /// from the top down to `base`.
+    fn find_handler(&mut self, base: usize) -> Result<Option<(usize, usize)>, Error> {
+        for index in (base..self.frames.len()).rev() {
+            let code = self.frames[index].code.clone();
+            let ip = self.frames[index].ip.saturating_sub(1);
+            for (clause_index, clause) in code.clauses.iter().enumerate() {
+                if !clause.protects(ip) {
+                    continue;
+                }
+                let handles = match clause.kind {
+                    ClauseKind::Catch(ty) => {
+                        let exception_type = self.object_type(self.thrown.last().unwrap().exception);
+                        self.types.is_subclass_of(exception_type, ty)
+                    }
+                    ClauseKind::Filter(start) => self.run_filter(index, start)?,
+                    ClauseKind::Finally | ClauseKind::Fault => false,
+                };
+                if handles {
+                    return Ok(Some((index, clause_index)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs a filter for the exception on top of `self.thrown`, and gets whether it accepts it.
+    ///
+    /// The filter runs in a copy of the frame whose clause it belongs to, on top of the stack, and its changes to
+    /// arguments and locals are copied back when it ends.
+    fn run_filter(&mut self, index: usize, start: usize) -> Result<bool, Error> {
+        let filter = {
+            let frame = &self.frames[index];
+            Frame {
+                method: frame.method,
+                code: frame.code.clone(),
+                ip: start,
+                memory: frame.memory.clone(),
+                stack: vec![Value::Ref(self.thrown.last().unwrap().exception)],
+                constructing: None,
+                handlers: Vec::new(),
+                constrained: None,
+                volatile: false,
+                #[cfg(feature = "jit")]
+                compiled: None,
+            }
+        };
+        let filter_base = self.frames.len();
+        self.frames.push(filter);
+        match self.run(filter_base) {
+            Ok(result) if self.frames.len() == filter_base + 1 => {
+                let filter = self.frames.pop().unwrap();
+                self.frames[index].memory = filter.memory;
+                Ok(result.and_then(|result| result.as_i64()) == Some(1))
+            }
+            Ok(_) => Err(Error::InvalidProgram(format!("{} returns from a filter", self.current()))),
+            // An exception that escapes a filter is swallowed, and the filter rejects the exception being thrown
+            Err(Error::UnhandledException(_)) if self.unhandled.is_some() => {
+                self.unhandled = None;
+                Ok(false)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Unwinds the stack for an exception, starting from the top frame: runs the next `finally` or `fault` handler
+    /// between where the exception was thrown and its handler, which continues unwinding when it ends, or enters the
+    /// handler.
+    ///
+    /// If no frame `run` was called for handles the exception, those frames are all removed, and the exception is
+    /// kept in `self.unhandled` for the caller to throw on.
+    fn unwind(&mut self, base: usize, mut unwind: Unwind) -> Result<Flow, Error> {
+        loop {
+            let index = self.frames.len() - 1;
+            let code = self.frame().code.clone();
+            let target_clause = match unwind.target {
+                Some((frame, clause)) if frame == index => Some(clause),
+                _ => None,
+            };
+
+            let first = unwind.last_clause.map_or(0, |clause| clause + 1);
+            let next = (first..target_clause.unwrap_or(code.clauses.len())).find(|&clause| {
+                let clause = &code.clauses[clause];
+                (clause.kind == ClauseKind::Finally || clause.kind == ClauseKind::Fault) && clause.protects(unwind.ip)
+            });
+            if let Some(clause) = next {
+                unwind.last_clause = Some(clause);
+                self.enter_handler(clause, Vec::new(), HandlerExit::Unwind(unwind));
+                return Ok(Flow::Continue);
+            }
+            if let Some(clause) = target_clause {
+                let stack = vec![Value::Ref(unwind.thrown.exception)];
+                self.enter_handler(clause, stack, HandlerExit::Catch(unwind.thrown));
+                return Ok(Flow::Continue);
+            }
+
+            if index == base {
+                self.frames.truncate(base);
+                let description = self.describe(&unwind.thrown);
+                self.unhandled = Some(unwind.thrown);
+                return Err(Error::UnhandledException(description));
+            }
+            self.frames.pop();
+            unwind.ip = self.frame().ip.saturating_sub(1);
+            unwind.last_clause = None;
+        }
+    }
+
+    /// Starts executing the handler of a clause of the top frame, with an evaluation stack.
+    fn enter_handler(&mut self, clause: usize, stack: Vec<Value>, exit: HandlerExit) {
+        let frame = self.frame();
+        let start = frame.code.clauses[clause].handler_start;
+        frame.exit_handlers(start);
+        frame.stack = stack;
+        frame.ip = start;
+        frame.handlers.push(ActiveHandler { clause, exit });
+    }
+
+    /// Executes `leave`, running the `finally` handlers of the protected regions it leaves before branching to the
+    /// instruction index `target`.
+    pub(crate) fn leave(&mut self, target: usize) {
+        let ip = self.frame().ip - 1;
+        let finallys = self.frame()
+            .code
+            .clauses
+            .iter()
+            .enumerate()
+            .filter(|&(_, clause)| {
+                clause.kind == ClauseKind::Finally && clause.protects(ip) && !clause.protects(target)
+            })
+            .map(|(clause, _)| clause)
+            .collect();
+        self.frame().stack.clear();
+        self.run_finallys(finallys, target);
+    }
+
+    fn run_finallys(&mut self, mut finallys: Vec<usize>, target: usize) {
+        if finallys.is_empty() {
+            let frame = self.frame();
+            frame.exit_handlers(target);
+            frame.ip = target;
+        } else {
+            let clause = finallys.remove(0);
+            self.enter_handler(clause, Vec::new(), HandlerExit::Leave { finallys, target });
+        }
+    }
+
+    /// Executes `endfinally`, which continues whatever ran the `finally` or `fault` handler.
+    pub(crate) fn end_finally(&mut self, base: usize) -> Result<Flow, Error> {
+        let exit = match self.frame().handlers.pop() {
+            Some(handler) => handler.exit,
+            None => {
+                let message = format!("{} ends a finally handler outside one", self.current());
+                return Err(Error::InvalidProgram(message));
+            }
+        };
+        self.frame().stack.clear();
+        match exit {
+            HandlerExit::Leave { finallys, target } => {
+                self.run_finallys(finallys, target);
+                Ok(Flow::Continue)
+            }
+            HandlerExit::Unwind(unwind) => self.unwind(base, unwind),
+            HandlerExit::Catch(_) => {
+                Err(Error::InvalidProgram(format!("{} ends a catch handler with endfinally", self.current())))
+            }
+        }
+    }
+
+    /// Gets the exception the innermost catch handler the top frame is executing caught, for `rethrow`.
+    pub(crate) fn caught(&mut self) -> Result<Thrown, Error> {
+        self.frame()
+            .handlers
+            .iter()
+            .rev()
+            .filter_map(|handler| match handler.exit {
+                HandlerExit::Catch(ref thrown) => Some(thrown.clone()),
+                _ => None,
+            })
+            .next()
+            .ok_or_else(|| Error::InvalidProgram(format!("{} rethrows outside a catch handler", self.current())))
+    }
+
+    /// Describes an exception like .NET does when it is unhandled: its type, its message, the exceptions it wraps
+    /// and its stack trace.
+    pub(crate) fn describe(&self, thrown: &Thrown) -> String {
+        self.describe_exception(thrown.exception) + &thrown.stack_trace
+    }
+
+    /// Describes an exception's type and message, followed by those of the inner exception it wraps, if any.
+    fn describe_exception(&self, exception: ObjectRef) -> String {
+        let mut description = self.types.get(self.object_type(exception)).to_string();
+        match self.exception_field(exception, "_message") {
+            Some(message) if self.types.get(self.object_type(message)).kind == TypeKind::String => {
+                description += ": ";
+                description += &String::from_utf16_lossy(unsafe { message.string_chars() });
+            }
+            _ => {}
+        }
+        if let Some(inner) = self.exception_field(exception, "_innerException") {
+            description += "\n ---> ";
+            description += &self.describe_exception(inner);
+            description += "\n   --- End of inner exception stack trace ---";
+        }
+        description
+    }
+
+    /// Gets the object an instance field `System.Exception` declares refers to, such as its `_message`, or `None`
+    /// if it is null.
+    fn exception_field(&self, exception: ObjectRef, name: &str) -> Option<ObjectRef> {
+        let mut current = Some(self.object_type(exception));
+        while let Some(ty) = current {
+            let ty = self.types.get(ty);
+            if let Some(&field) = ty.fields.iter().find(|&&field| self.types.field(field).name == name) {
+                let field = self.types.field(field);
+                if field.is_static() || self.types.get(field.field_type).storage() != Storage::Ref {
+                    return None;
+                }
+                let object = unsafe { ::gc::read_ref(exception.data().add(field.offset as usize)) };
+                return Some(object).filter(|object| !object.is_null());
+            }
+            current = ty.base;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecma355metadata::cli::il::Opcode;
+
+    use error::Error;
+    use runtime::UNHANDLED_EXCEPTION_EXIT_CODE;
+    use test_assembly::*;
+
+    /// Builds an application with a `Program` class with a static `int trace` field and a static `void Thrower()`,
+    /// which throws null (so a `NullReferenceException`) in a `try` block whose `finally` handler traces 1.
+    /// `build_main` builds the body of `Main` from the tokens of `trace` and `Thrower`.
+    fn app_with_thrower<F: FnOnce(&mut AssemblyBuilder, u32, u32) -> Body>(build_main: F) -> AssemblyBuilder {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        app.type_def(PUBLIC, "", "Program", object);
+        let trace_field = app.field(STATIC_FIELD, "trace", Ty::I4);
+
+        let mut il = Il::new();
+        let (try_start, handler_start, handler_end) = (il.label(), il.label(), il.label());
+        il.mark(try_start).op(Opcode::Ldnull).op(Opcode::Throw);
+        il.mark(handler_start);
+        trace(&mut il, trace_field, 1);
+        il.op(Opcode::Endfinally).mark(handler_end).op(Opcode::Ret);
+        let body = Body::new(vec![], il).with_clauses(vec![Clause {
+            handler: Handler::Finally,
+            try_start,
+            try_end: handler_start,
+            handler_start,
+            handler_end,
+        }]);
+        let thrower = app.method(STATIC, "Thrower", &method_sig(false, Ty::Void, &[]), Some(body));
+
+        let main = build_main(&mut app, trace_field, thrower);
+        add_main(&mut app, main);
+        app
+    }
+
+    #[test]
+    pub fn catch_handlers_catch_exceptions_of_derived_types() {
+        // try { try { ((Program)null).value; } catch (InvalidCastException) { return 1; } }
+        // catch (SystemException) { return 2; }
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let invalid_cast = app.corlib_type("System", "InvalidCastException");
+        let system_exception = app.corlib_type("System", "SystemException");
+        app.type_def(PUBLIC, "", "Program", object);
+        let value = app.field(0, "value", Ty::I4);
+        let mut il = Il::new();
+        let labels: Vec<_> = (0..5).map(|_| il.label()).collect();
+        il.mark(labels[0]).op(Opcode::Ldnull).arg(Opcode::Ldfld, value as i64).op(Opcode::Pop);
+        il.branch(Opcode::LeaveS, labels[4]);
+        il.mark(labels[1]).op(Opcode::Pop).op(Opcode::LdcI41).op(Opcode::Stloc0).branch(Opcode::LeaveS, labels[4]);
+        il.mark(labels[2]).op(Opcode::Pop).op(Opcode::LdcI42).op(Opcode::Stloc0).branch(Opcode::LeaveS, labels[4]);
+        il.mark(labels[3]).mark(labels[4]).op(Opcode::Ldloc0).op(Opcode::Ret);
+        let body = Body::new(vec![Ty::I4], il).with_clauses(vec![
+            Clause {
+                handler: Handler::Catch(invalid_cast),
+                try_start: labels[0],
+                try_end: labels[1],
+                handler_start: labels[1],
+                handler_end: labels[2],
+            },
+            Clause {
+                handler: Handler::Catch(system_exception),
+                try_start: labels[0],
+                try_end: labels[2],
+                handler_start: labels[2],
+                handler_end: labels[3],
+            },
+        ]);
+        add_main(&mut app, body);
+
+        assert_eq!(Ok(2), run("catch_derived", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn finally_and_fault_handlers_run_while_unwinding_and_leaving() {
+        // try { try { Thrower(); } fault { trace 2 } } catch (Exception) { trace 3 }
+        // try { } finally { trace 4 }
+        // return trace;
+        let app = app_with_thrower(|app, trace_field, thrower| {
+            let exception = app.corlib_type("System", "Exception");
+            let mut il = Il::new();
+            let labels: Vec<_> = (0..8).map(|_| il.label()).collect();
+            il.mark(labels[0]).arg(Opcode::Call, thrower as i64).branch(Opcode::LeaveS, labels[4]);
+            il.mark(labels[1]);
+            trace(&mut il, trace_field, 2);
+            il.op(Opcode::Endfinally);
+            il.mark(labels[2]).op(Opcode::Pop);
+            trace(&mut il, trace_field, 3);
+            il.branch(Opcode::LeaveS, labels[4]);
+            il.mark(labels[3]).mark(labels[4]).branch(Opcode::LeaveS, labels[7]);
+            il.mark(labels[5]);
+            trace(&mut il, trace_field, 4);
+            il.op(Opcode::Endfinally);
+            il.mark(labels[6]).mark(labels[7]).arg(Opcode::Ldsfld, trace_field as i64).op(Opcode::Ret);
+            Body::new(vec![], il).with_clauses(vec![
+                Clause {
+                    handler: Handler::Fault,
+                    try_start: labels[0],
+                    try_end: labels[1],
+                    handler_start: labels[1],
+                    handler_end: labels[2],
+                },
+                Clause {
+                    handler: Handler::Catch(exception),
+                    try_start: labels[0],
+                    try_end: labels[2],
+                    handler_start: labels[2],
+                    handler_end: labels[3],
+                },
+                Clause {
+                    handler: Handler::Finally,
+                    try_start: labels[4],
+                    try_end: labels[5],
+                    handler_start: labels[5],
+                    handler_end: labels[6],
+                },
+            ])
+        });
+
+        assert_eq!(Ok(1234), run("finally_and_fault", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn filters_run_before_finally_handlers() {
+        // try { try { Thrower(); } catch when (trace 5, false) { trace 6 } }
+        // catch when (trace 7, true) { trace 3 }
+        // return trace;
+        let app = app_with_thrower(|_, trace_field, thrower| {
+            let mut il = Il::new();
+            let labels: Vec<_> = (0..7).map(|_| il.label()).collect();
+            il.mark(labels[0]).arg(Opcode::Call, thrower as i64).branch(Opcode::LeaveS, labels[6]);
+            il.mark(labels[1]).op(Opcode::Pop);
+            trace(&mut il, trace_field, 5);
+            il.op(Opcode::LdcI40).op(Opcode::Endfilter);
+            il.mark(labels[2]).op(Opcode::Pop);
+            trace(&mut il, trace_field, 6);
+            il.branch(Opcode::LeaveS, labels[6]);
+            il.mark(labels[3]).op(Opcode::Pop);
+            trace(&mut il, trace_field, 7);
+            il.op(Opcode::LdcI41).op(Opcode::Endfilter);
+            il.mark(labels[4]).op(Opcode::Pop);
+            trace(&mut il, trace_field, 3);
+            il.branch(Opcode::LeaveS, labels[6]);
+            il.mark(labels[5]).mark(labels[6]).arg(Opcode::Ldsfld, trace_field as i64).op(Opcode::Ret);
+            Body::new(vec![], il).with_clauses(vec![
+                Clause {
+                    handler: Handler::Filter(labels[1]),
+                    try_start: labels[0],
+                    try_end: labels[1],
+                    handler_start: labels[2],
+                    handler_end: labels[3],
+                },
+                Clause {
+                    handler: Handler::Filter(labels[3]),
+                    try_start: labels[0],
+                    try_end: labels[3],
+                    handler_start: labels[4],
+                    handler_end: labels[5],
+                },
+            ])
+        });
+
+        assert_eq!(Ok(5713), run("filters", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn rethrow_throws_the_caught_exception_again() {
+        // try { try { Thrower(); } catch (Exception e) { first = e; throw; } } catch (Exception e) { second = e; }
+        // return first == second ? trace : 0;
+        let app = app_with_thrower(|app, trace_field, thrower| {
+            let exception = app.corlib_type("System", "Exception");
+            let mut il = Il::new();
+            let labels: Vec<_> = (0..5).map(|_| il.label()).collect();
+            il.mark(labels[0]).arg(Opcode::Call, thrower as i64).branch(Opcode::LeaveS, labels[4]);
+            il.mark(labels[1]).op(Opcode::Stloc0).op(Opcode::Rethrow);
+            il.mark(labels[2]).op(Opcode::Stloc1).branch(Opcode::LeaveS, labels[4]);
+            il.mark(labels[3]).mark(labels[4]).op(Opcode::Ldloc0).op(Opcode::Ldloc1).op(Opcode::Ceq);
+            il.arg(Opcode::Ldsfld, trace_field as i64).op(Opcode::Mul).op(Opcode::Ret);
+            Body::new(vec![Ty::Object, Ty::Object], il).with_clauses(vec![
+                Clause {
+                    handler: Handler::Catch(exception),
+                    try_start: labels[0],
+                    try_end: labels[1],
+                    handler_start: labels[1],
+                    handler_end: labels[2],
+                },
+                Clause {
+                    handler: Handler::Catch(exception),
+                    try_start: labels[0],
+                    try_end: labels[2],
+                    handler_start: labels[2],
+                    handler_end: labels[3],
+                },
+            ])
+        });
+
+        assert_eq!(Ok(1), run("rethrow", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn unhandled_exceptions_report_a_stack_trace() {
+        let app = app_with_thrower(|_, trace_field, thrower| {
+            let mut il = Il::new();
+            il.arg(Opcode::Call, thrower as i64).arg(Opcode::Ldsfld, trace_field as i64).op(Opcode::Ret);
+            Body::new(vec![], il)
+        });
+
+        let (_directory, mut runtime) = runtime("unhandled", &corlib(), &app);
+        let report = concat!(
+            "System.NullReferenceException: Object reference not set to an instance of an object.\n",
+            "   at Program.Thrower\n",
+            "   at Program.Main"
+        );
+        assert_eq!(Err(Error::UnhandledException(report.into())), runtime.run_main("App"));
+        assert_eq!(Ok(UNHANDLED_EXCEPTION_EXIT_CODE), runtime.execute("App"));
+    }
+}
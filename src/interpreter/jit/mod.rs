@@ -0,0 +1,940 @@
+//! Compiles the methods the interpreter runs most to native code with Cranelift.
+//!
+//! Methods start out interpreted, and move up through tiers as they get hotter. A method is compiled to baseline
+//! code once it has been called, or has taken backward branches, as many times as its `TierThresholds` say, if every
+//! instruction in it is one the translator supports; otherwise it stays interpreted. A loop that makes a method hot
+//! continues in the new code from its header (on-stack replacement). Baseline code counts which types of objects its
+//! virtual calls are made on and which way its branches go, and once it has been called often enough the method is
+//! compiled again to optimized code, which calls the methods of virtual call sites that only saw one type directly
+//! and moves the blocks of branches that are almost never taken out of the way.
+//!
+//! Compiled code runs on the method's frame like the interpreter does: its arguments and locals stay in the frame's
+//! memory, where the collector finds their object references, and calls, allocations and backward branches go
+//! through helpers that run the interpreter. Values on the evaluation stack are kept in registers, so the translator
+//! refuses methods that would hold an object reference there while a helper that can collect garbage runs.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+use std::slice;
+
+use cranelift_codegen::ir::types::I64;
+use cranelift_codegen::ir::AbiParam;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{self, Module};
+
+use error::{Error, ExceptionKind};
+use gc::ObjectRef;
+use interpreter::{Flow, MethodCode, Value};
+use types::{MethodId, Storage, TypeId};
+use vm::Vm;
+
+mod translate;
+
+/// How deeply calls from compiled code into compiled code can nest on an OS thread, each taking a Rust frame for the
+/// interpreter and one for the native code. Deeper calls are interpreted, so recursion can't overflow the OS stack.
+const MAX_NATIVE_DEPTH: usize = 64;
+
+/// The exceptions compiled code raises itself, by their status codes less `FIRST_FAULT`.
+const FAULTS: [ExceptionKind; 4] = [
+    ExceptionKind::NullReference,
+    ExceptionKind::IndexOutOfRange,
+    ExceptionKind::DivideByZero,
+    ExceptionKind::Overflow,
+];
+
+/// The status code compiled code returns with in the low byte of its result when it returns normally, and when a
+/// helper it called failed with the error in `Jit::pending`. When it doesn't return normally, the rest of the result
+/// is the index of the instruction that failed.
+const RETURNED: u64 = 0;
+const FAILED: u64 = 1;
+const FIRST_FAULT: u64 = 2;
+
+thread_local! {
+    static NATIVE_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Compiled code, which takes the `Vm`, the frame's memory, where to write what the method returns and the index of
+/// the instruction to start at, and returns a status.
+type Entry = unsafe extern "C" fn(*mut Vm, *mut u64, *mut u64, u64) -> u64;
+
+/// The call sites of a compiled method. Each is boxed, since the code holds its address.
+#[allow(clippy::vec_box)]
+type CallSites = Vec<Box<CallSite>>;
+
+/// The branch counts of profiling code, boxed like its call sites.
+#[allow(clippy::vec_box)]
+type Branches = Vec<Box<BranchCounts>>;
+
+/// When methods move up to the next tier. They start out interpreted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TierThresholds {
+    /// How many times a method is called before it is compiled.
+    pub calls: u32,
+
+    /// How many backward branches the interpreter takes in a method, over all its calls, before it is compiled. The
+    /// loop that reaches the threshold continues in the compiled code.
+    pub back_edges: u32,
+
+    /// How many times a method's compiled code is called before it is compiled again using what the code saw, or
+    /// `None` to keep the first compiled code, which then doesn't count anything.
+    pub reoptimize: Option<u32>,
+}
+
+impl Default for TierThresholds {
+    fn default() -> TierThresholds {
+        TierThresholds {
+            calls: 100,
+            back_edges: 1000,
+            reoptimize: Some(1000),
+        }
+    }
+}
+
+/// The tiers a method runs in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Tier {
+    Interpreted,
+    Baseline,
+    Optimized,
+}
+
+impl Tier {
+    fn name(self) -> &'static str {
+        match self {
+            Tier::Interpreted => "interpreted",
+            Tier::Baseline => "baseline",
+            Tier::Optimized => "optimized",
+        }
+    }
+}
+
+/// The state of the compiler: the module compiled code is defined in, which is created the first time a method is
+/// compiled, and the methods that are hot enough to compile.
+pub struct Jit {
+    module: Option<JITModule>,
+    thresholds: TierThresholds,
+
+    /// How many times each method that is still interpreted has been called, and how many backward branches it has
+    /// taken.
+    counts: HashMap<MethodId, Counts>,
+
+    /// The latest code of each method that has been compiled, or `None` for a method that can't be.
+    compiled: HashMap<MethodId, Option<Rc<Compiled>>>,
+
+    /// What went wrong in a helper compiled code called, for the interpreter to throw from the frame.
+    pending: Option<Error>,
+}
+
+impl Jit {
+    pub fn new() -> Jit {
+        Jit {
+            module: None,
+            thresholds: TierThresholds::default(),
+            counts: HashMap::new(),
+            compiled: HashMap::new(),
+            pending: None,
+        }
+    }
+
+    pub fn set_thresholds(&mut self, thresholds: TierThresholds) {
+        self.thresholds = thresholds;
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    calls: u32,
+    back_edges: u32,
+}
+
+/// A compiled method.
+pub struct Compiled {
+    entry: Entry,
+    returns: Option<Kind>,
+    tier: Tier,
+
+    /// The instructions starting loops that the code can be started at, from a frame interpreting the method with
+    /// nothing on its stack.
+    loop_entries: Vec<usize>,
+
+    /// How many times baseline code has been called, toward compiling the method again.
+    calls: Cell<u32>,
+
+    /// The calls the code makes, which it refers to by address.
+    sites: CallSites,
+    branches: Branches,
+
+    /// How many virtual calls optimized code makes directly, and how many branches it expects to go one way.
+    devirtualized: usize,
+    biased_branches: usize,
+}
+
+impl Compiled {
+    /// Gets what profiling code has seen so far.
+    fn profile(&self) -> Profile {
+        let receivers = self.sites
+            .iter()
+            .filter_map(|site| match site.receivers.get() {
+                Receivers::One(ty) => Some((site.index, ty)),
+                _ => None,
+            })
+            .collect();
+        let branches = self.branches
+            .iter()
+            .map(|counts| (counts.index, (counts.taken.get(), counts.reached.get())))
+            .collect();
+        Profile { receivers, branches }
+    }
+}
+
+/// What a method's baseline code saw, for compiling it again.
+#[derive(Default)]
+struct Profile {
+    /// The type of the objects each virtual call site, by the index of its instruction, was called on, for the sites
+    /// that were only called on one.
+    receivers: HashMap<usize, TypeId>,
+
+    /// How many times each conditional branch was taken, and reached.
+    branches: HashMap<usize, (u64, u64)>,
+}
+
+/// The types of the objects a profiled virtual call site has been called on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Receivers {
+    None,
+    One(TypeId),
+    Many,
+}
+
+impl Receivers {
+    fn add(self, ty: TypeId) -> Receivers {
+        match self {
+            Receivers::None => Receivers::One(ty),
+            Receivers::One(seen) if seen == ty => self,
+            _ => Receivers::Many,
+        }
+    }
+}
+
+/// How many times profiling code has taken a conditional branch, and reached it. The code updates the counts in
+/// place.
+#[repr(C)]
+struct BranchCounts {
+    taken: Cell<u64>,
+    reached: Cell<u64>,
+
+    /// The index of the instruction.
+    index: usize,
+}
+
+/// How compiled code holds a value on the evaluation stack, a subset of the types the interpreter's `Value` has.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    I32,
+    I64,
+    Native,
+    F,
+    Ref,
+}
+
+impl Kind {
+    /// Gets how a value with a storage is held, or `None` if compiled code can't hold it.
+    fn for_storage(storage: Storage) -> Option<Kind> {
+        match storage {
+            Storage::I1 | Storage::U1 | Storage::I2 | Storage::U2 | Storage::I4 | Storage::U4 => Some(Kind::I32),
+            Storage::I8 => Some(Kind::I64),
+            Storage::NativeInt => Some(Kind::Native),
+            Storage::R4 | Storage::R8 => Some(Kind::F),
+            Storage::Ref => Some(Kind::Ref),
+            Storage::ByRef | Storage::Struct => None,
+        }
+    }
+
+    fn for_type(vm: &mut Vm, ty: TypeId) -> Result<Option<Kind>, Error> {
+        vm.types.prepare(ty)?;
+        Ok(Kind::for_storage(vm.types.get(ty).storage()))
+    }
+
+    /// Gets the value with a kind that compiled code passes in 64 bits.
+    fn value(self, bits: u64) -> Value {
+        match self {
+            Kind::I32 => Value::I32(bits as i32),
+            Kind::I64 => Value::I64(bits as i64),
+            Kind::Native => Value::NativeInt(bits as isize),
+            Kind::F => Value::F(f64::from_bits(bits)),
+            Kind::Ref => Value::Ref(ObjectRef(bits as usize)),
+        }
+    }
+}
+
+/// Gets the 64 bits compiled code passes a value in.
+fn bits(value: &Value) -> u64 {
+    match *value {
+        Value::F(x) => x.to_bits(),
+        Value::Ref(object) => object.address() as u64,
+        ref value => value.as_i64().unwrap_or(0) as u64,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum CallKind {
+    Call,
+    Callvirt,
+    Newobj,
+}
+
+/// A `call`, `callvirt` or `newobj` instruction in compiled code, which calls the method through the interpreter.
+struct CallSite {
+    kind: CallKind,
+    method: MethodId,
+
+    /// The index of the instruction.
+    index: usize,
+    args: Vec<Kind>,
+    returns: Option<Kind>,
+
+    /// Whether the site counts the types of the objects it is called on, and what it has counted.
+    profiled: bool,
+    receivers: Cell<Receivers>,
+}
+
+impl Vm {
+    /// Counts a call to a method, and gets its latest compiled code, compiling it first if the method has been called
+    /// often enough to move up a tier.
+    pub(super) fn compiled_code(&mut self, method: MethodId, code: &MethodCode) -> Option<Rc<Compiled>> {
+        if let Some(compiled) = self.jit.compiled.get(&method).cloned() {
+            let compiled = compiled?;
+            let threshold = match self.jit.thresholds.reoptimize {
+                Some(threshold) if compiled.tier == Tier::Baseline => threshold,
+                _ => return Some(compiled),
+            };
+            // Baseline code is only compiled again once, when it reaches the threshold
+            let calls = compiled.calls.get().saturating_add(1);
+            compiled.calls.set(calls);
+            if calls != threshold {
+                return Some(compiled);
+            }
+            let profile = compiled.profile();
+            return self.tier_up(method, code, Tier::Optimized, &profile, "calls").or(Some(compiled));
+        }
+        let calls = {
+            let counts = self.jit.counts.entry(method).or_default();
+            counts.calls += 1;
+            counts.calls
+        };
+        if calls < self.jit.thresholds.calls {
+            return None;
+        }
+        self.tier_up(method, code, Tier::Baseline, &Profile::default(), "calls")
+    }
+
+    /// Counts a backward branch the interpreter has taken in the current frame, compiling its method if it has taken
+    /// enough, and has the loop continue in the method's compiled code if it has been compiled.
+    pub(super) fn count_back_edge(&mut self) {
+        let (method, code) = {
+            let frame = self.frame();
+            (frame.method, frame.code.clone())
+        };
+        let compiled = match self.jit.compiled.get(&method).cloned() {
+            Some(compiled) => compiled,
+            None => {
+                let back_edges = {
+                    let counts = self.jit.counts.entry(method).or_default();
+                    counts.back_edges += 1;
+                    counts.back_edges
+                };
+                if back_edges < self.jit.thresholds.back_edges {
+                    return;
+                }
+                self.tier_up(method, &code, Tier::Baseline, &Profile::default(), "back edges")
+            }
+        };
+        if let Some(compiled) = compiled {
+            self.enter_loop(method, compiled);
+        }
+    }
+
+    /// Has the current frame continue in compiled code from the loop it is at, if the code can start there.
+    fn enter_loop(&mut self, method: MethodId, compiled: Rc<Compiled>) {
+        let ip = self.frame().ip;
+        if !self.frame().stack.is_empty() || !compiled.loop_entries.contains(&ip) {
+            return;
+        }
+        debug!(self.logger, "entering compiled loop";
+            "method" => self.method_name(method), "tier" => compiled.tier.name(), "instruction" => ip);
+        self.frame().compiled = Some(compiled);
+    }
+
+    /// Compiles a method for a higher tier, and gets the code if the method can be compiled.
+    fn tier_up(&mut self, method: MethodId, code: &MethodCode, tier: Tier, profile: &Profile, reason: &str)
+        -> Option<Rc<Compiled>>
+    {
+        self.jit.counts.remove(&method);
+        let name = self.method_name(method);
+        let from = if tier == Tier::Optimized { Tier::Baseline } else { Tier::Interpreted };
+        match self.compile(method, code, tier, profile) {
+            Ok(compiled) => {
+                debug!(self.logger, "method tiered up";
+                    "method" => name, "from" => from.name(), "to" => tier.name(), "reason" => reason,
+                    "devirtualized_calls" => compiled.devirtualized, "biased_branches" => compiled.biased_branches);
+                let compiled = Rc::new(compiled);
+                self.jit.compiled.insert(method, Some(compiled.clone()));
+                Some(compiled)
+            }
+            Err(error) => {
+                debug!(self.logger, "method can't be compiled";
+                    "method" => name, "tier" => tier.name(), "reason" => error);
+                // A method whose baseline code can't be optimized keeps running it
+                if tier == Tier::Baseline {
+                    self.jit.compiled.insert(method, None);
+                }
+                None
+            }
+        }
+    }
+
+    fn method_name(&self, method: MethodId) -> String {
+        let method = self.types.method(method);
+        format!("{}::{}", self.types.get(method.owner), method)
+    }
+
+    /// Gets the names of the methods that have been compiled.
+    #[cfg(test)]
+    pub(crate) fn compiled_methods(&self) -> Vec<String> {
+        self.methods_compiled(|_| true)
+    }
+
+    /// Gets the names of the methods that have been compiled again with what their baseline code saw.
+    #[cfg(test)]
+    pub(crate) fn optimized_methods(&self) -> Vec<String> {
+        self.methods_compiled(|compiled| compiled.tier == Tier::Optimized)
+    }
+
+    #[cfg(test)]
+    fn methods_compiled<F: Fn(&Compiled) -> bool>(&self, filter: F) -> Vec<String> {
+        let mut names: Vec<String> = self.jit.compiled
+            .iter()
+            .filter(|&(_, compiled)| compiled.as_ref().is_some_and(|compiled| filter(compiled)))
+            .map(|(&method, _)| self.method_name(method))
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn compile(&mut self, method: MethodId, code: &MethodCode, tier: Tier, profile: &Profile)
+        -> Result<Compiled, String>
+    {
+        if !code.clauses.is_empty() {
+            return Err("it has exception handling clauses".into());
+        }
+        let returns = match self.types.method(method).signature.ret {
+            Some(ty) => Some(Kind::for_type(self, ty).map_err(|error| format!("{:?}", error))?
+                .ok_or("it returns a struct or a managed pointer")?),
+            None => None,
+        };
+        let mut module = match self.jit.module.take() {
+            Some(module) => module,
+            None => new_module()?,
+        };
+        let profiling = tier == Tier::Baseline && self.jit.thresholds.reoptimize.is_some();
+        let profile = if tier == Tier::Optimized { Some(profile) } else { None };
+        let result = self.define(&mut module, code, returns, profiling, profile);
+        self.jit.module = Some(module);
+        let (entry, translation) = result?;
+        Ok(Compiled {
+            entry,
+            returns,
+            tier,
+            loop_entries: translation.loop_entries,
+            calls: Cell::new(0),
+            sites: translation.sites,
+            branches: translation.branches,
+            devirtualized: translation.devirtualized,
+            biased_branches: translation.biased_branches,
+        })
+    }
+
+    fn define(
+        &mut self,
+        module: &mut JITModule,
+        code: &MethodCode,
+        returns: Option<Kind>,
+        profiling: bool,
+        profile: Option<&Profile>,
+    ) -> Result<(Entry, translate::Translation), String> {
+        let mut context = module.make_context();
+        context.func.signature.params = vec![AbiParam::new(I64); 4];
+        context.func.signature.returns = vec![AbiParam::new(I64)];
+        let mut builder_context = FunctionBuilderContext::new();
+        let call_conv = module.isa().default_call_conv();
+        let translation = {
+            let builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
+            translate::translate(self, code, returns, profiling, profile, builder, call_conv)?
+        };
+        let id = module.declare_anonymous_function(&context.func.signature).map_err(|error| error.to_string())?;
+        module.define_function(id, &mut context).map_err(|error| format!("{:?}", error))?;
+        module.clear_context(&mut context);
+        module.finalize_definitions().map_err(|error| error.to_string())?;
+        let entry = unsafe { mem::transmute::<*const u8, Entry>(module.get_finalized_function(id)) };
+        Ok((entry, translation))
+    }
+
+    /// Runs the current frame's method as compiled code from the frame's next instruction, if the frame has been
+    /// given compiled code to run. Gets what the interpreter would have after the method returned or failed, or
+    /// `None` if the method is to be interpreted.
+    pub(super) fn run_compiled(&mut self, base: usize) -> Option<Result<Flow, Error>> {
+        let compiled = self.frame().compiled.take()?;
+        if NATIVE_DEPTH.with(Cell::get) >= MAX_NATIVE_DEPTH {
+            return None;
+        }
+        let memory = self.frame().memory.as_mut_ptr();
+        let start = self.frame().ip as u64;
+        let mut result = 0u64;
+        NATIVE_DEPTH.with(|depth| depth.set(depth.get() + 1));
+        let status = unsafe { (compiled.entry)(self, memory, &mut result, start) };
+        NATIVE_DEPTH.with(|depth| depth.set(depth.get() - 1));
+
+        let error = match status & 0xFF {
+            RETURNED => {
+                if let Some(kind) = compiled.returns {
+                    self.push(kind.value(result));
+                }
+                return Some(self.ret(base));
+            }
+            FAILED => self.jit.pending.take().unwrap(),
+            code => FAULTS[(code - FIRST_FAULT) as usize].into(),
+        };
+        self.frame().ip = (status >> 8) as usize + 1;
+        Some(Err(error))
+    }
+
+    /// Makes a call for compiled code, with the arguments in the order they were pushed, and gets what the method
+    /// returns.
+    fn call_from_compiled(&mut self, site: &CallSite, args: &[u64]) -> Result<Option<u64>, Error> {
+        if site.profiled && args[0] != 0 {
+            let ty = self.object_type(ObjectRef(args[0] as usize));
+            site.receivers.set(site.receivers.get().add(ty));
+        }
+        // The interpreter finds the call site by the index of the instruction after it
+        self.frame().ip = site.index + 1;
+        for (&kind, &arg) in site.args.iter().zip(args) {
+            self.push(kind.value(arg));
+        }
+        let base = self.frames.len();
+        let result = match site.kind {
+            CallKind::Call => self.call(site.method, false),
+            CallKind::Callvirt => self.call(site.method, true),
+            CallKind::Newobj => self.new_object_with(site.method),
+        };
+        let result = result.and_then(|()| {
+            if self.frames.len() > base {
+                self.run(base)
+            } else if site.returns.is_some() {
+                self.pop().map(Some)
+            } else {
+                Ok(None)
+            }
+        });
+        self.frames.truncate(base);
+        Ok(result?.map(|value| bits(&value)))
+    }
+
+    fn fail_from_compiled(&mut self, error: Error) -> u32 {
+        self.jit.pending = Some(error);
+        FAILED as u32
+    }
+}
+
+fn new_module() -> Result<JITModule, String> {
+    let builder = JITBuilder::with_flags(&[("opt_level", "speed")], cranelift_module::default_libcall_names())
+        .map_err(|error| error.to_string())?;
+    Ok(JITModule::new(builder))
+}
+
+unsafe extern "C" fn call_helper(vm: *mut Vm, site: *const CallSite, args: *const u64, result: *mut u64) -> u32 {
+    let vm = &mut *vm;
+    let site = &*site;
+    match vm.call_from_compiled(site, slice::from_raw_parts(args, site.args.len())) {
+        Ok(value) => {
+            *result = value.unwrap_or(0);
+            RETURNED as u32
+        }
+        Err(error) => vm.fail_from_compiled(error),
+    }
+}
+
+unsafe extern "C" fn new_array_helper(vm: *mut Vm, array_type: u64, length: i64, result: *mut u64) -> u32 {
+    let vm = &mut *vm;
+    if length < 0 {
+        return vm.fail_from_compiled(ExceptionKind::Overflow.into());
+    }
+    match vm.new_array(TypeId(array_type as u32), length as usize) {
+        Ok(array) => {
+            *result = array.address() as u64;
+            RETURNED as u32
+        }
+        Err(error) => vm.fail_from_compiled(error),
+    }
+}
+
+/// Stores an object reference to a field or static field, recording the store with the write barrier.
+unsafe extern "C" fn store_ref_helper(vm: *mut Vm, location: *mut u8, object: u64) {
+    (*vm).store_value(location, &Value::Ref(ObjectRef(object as usize)), Storage::Ref, 8);
+}
+
+unsafe extern "C" fn safepoint_helper(vm: *mut Vm) {
+    (*vm).safepoint();
+}
+
+extern "C" fn remainder_helper(x: f64, y: f64) -> f64 {
+    x % y
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use ecma355metadata::cli::il::Opcode;
+    use slog::{self, Drain, OwnedKVList, Record, KV};
+
+    use error::Error;
+    use gc::GcMode;
+    use runtime::RuntimeBuilder;
+    use test_assembly::*;
+
+    use super::TierThresholds;
+
+    /// Keeps the message and key-value pairs of each record logged, as lines like `message key=value`.
+    #[derive(Clone, Default)]
+    struct Records(Arc<Mutex<Vec<String>>>);
+
+    impl Records {
+        /// Checks whether a record with a message and some key-value pairs has been logged.
+        fn contains(&self, message: &str, pairs: &[(&str, &str)]) -> bool {
+            self.0.lock().unwrap().iter().any(|line| {
+                line.starts_with(message)
+                    && pairs.iter().all(|&(key, value)| line.contains(&format!(" {}={}", key, value)))
+            })
+        }
+    }
+
+    impl Drain for Records {
+        type Ok = ();
+        type Err = slog::Never;
+
+        fn log(&self, record: &Record, _values: &OwnedKVList) -> Result<(), slog::Never> {
+            let mut line = Line(record.msg().to_string());
+            record.kv().serialize(record, &mut line).unwrap();
+            self.0.lock().unwrap().push(line.0);
+            Ok(())
+        }
+    }
+
+    struct Line(String);
+
+    impl slog::Serializer for Line {
+        fn emit_arguments(&mut self, key: slog::Key, value: &fmt::Arguments) -> slog::Result {
+            self.0 += &format!(" {}={}", key, value);
+            Ok(())
+        }
+    }
+
+    #[test]
+    pub fn hot_methods_are_compiled() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let int32 = app.corlib_type("System", "Int32");
+        let program = app.type_def(PUBLIC, "", "Program", object);
+
+        // static int Fib(int n) { return n < 2 ? n : Fib(n - 1) + Fib(n - 2); }
+        let signature = method_sig(false, Ty::I4, &[Ty::I4]);
+        let fib_ref = app.member_ref(program, "Fib", &signature);
+        let mut il = Il::new();
+        let recurse = il.label();
+        il.op(Opcode::Ldarg0).op(Opcode::LdcI42).branch(Opcode::BgeS, recurse).op(Opcode::Ldarg0).op(Opcode::Ret);
+        il.mark(recurse).op(Opcode::Ldarg0).op(Opcode::LdcI41).op(Opcode::Sub).arg(Opcode::Call, fib_ref as i64);
+        il.op(Opcode::Ldarg0).op(Opcode::LdcI42).op(Opcode::Sub).arg(Opcode::Call, fib_ref as i64);
+        il.op(Opcode::Add).op(Opcode::Ret);
+        let fib = app.method(STATIC, "Fib", &signature, Some(Body::new(vec![], il)));
+
+        // static long Mix(int n) {
+        //     long total = 0; double half = 0;
+        //     for (int i = 0; i < n; i++) { total += (long)i * i ^ (uint)i >> 1; half += i / 2.0; }
+        //     return total + (long)half;
+        // }
+        let mut il = Il::new();
+        let (head, end) = (il.label(), il.label());
+        il.op(Opcode::LdcI40).op(Opcode::ConvI8).op(Opcode::Stloc0).ldc_r8(0.0).op(Opcode::Stloc1);
+        il.op(Opcode::LdcI40).op(Opcode::Stloc2);
+        il.mark(head).op(Opcode::Ldloc2).op(Opcode::Ldarg0).branch(Opcode::BgeS, end);
+        il.op(Opcode::Ldloc0).op(Opcode::Ldloc2).op(Opcode::ConvI8).op(Opcode::Ldloc2).op(Opcode::ConvI8);
+        il.op(Opcode::Mul);
+        il.op(Opcode::Ldloc2).op(Opcode::LdcI41).op(Opcode::ShrUn).op(Opcode::ConvU8).op(Opcode::Xor);
+        il.op(Opcode::Add).op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc1).op(Opcode::Ldloc2).op(Opcode::ConvR8).ldc_r8(2.0).op(Opcode::Div).op(Opcode::Add);
+        il.op(Opcode::Stloc1);
+        il.op(Opcode::Ldloc2).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc2).branch(Opcode::BrS, head);
+        il.mark(end).op(Opcode::Ldloc0).op(Opcode::Ldloc1).op(Opcode::ConvI8).op(Opcode::Add).op(Opcode::Ret);
+        let body = Body::new(vec![Ty::I8, Ty::R8, Ty::I4], il);
+        let mix = app.method(STATIC, "Mix", &method_sig(false, Ty::I8, &[Ty::I4]), Some(body));
+
+        // static int Boxed(int x) { object o = x; return x; }, which stays interpreted since it boxes
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Box, int32 as i64).op(Opcode::Pop).op(Opcode::Ldarg0).op(Opcode::Ret);
+        let boxed = app.method(STATIC, "Boxed", &signature, Some(Body::new(vec![], il)));
+
+        // long total = Fib(20); for (int i = 0; i < 200; i++) total += Mix(i) + Boxed(i); return total % 1000003;
+        let mut il = Il::new();
+        let (head, end) = (il.label(), il.label());
+        il.ldc_i4(20).arg(Opcode::Call, fib as i64).op(Opcode::ConvI8).op(Opcode::Stloc0);
+        il.op(Opcode::LdcI40).op(Opcode::Stloc1);
+        il.mark(head).op(Opcode::Ldloc1).ldc_i4(200).branch(Opcode::BgeS, end);
+        il.op(Opcode::Ldloc0).op(Opcode::Ldloc1).arg(Opcode::Call, mix as i64).op(Opcode::Add);
+        il.op(Opcode::Ldloc1).arg(Opcode::Call, boxed as i64).op(Opcode::ConvI8).op(Opcode::Add).op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc1).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc1).branch(Opcode::BrS, head);
+        il.mark(end).op(Opcode::Ldloc0).ldc_i4(1_000_003).op(Opcode::ConvI8).op(Opcode::Rem).op(Opcode::ConvI4);
+        il.op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::I8, Ty::I4], il));
+
+        let mix = |n: i64| {
+            let total: i64 = (0..n).map(|i| (i * i) ^ (i as u32 >> 1) as i64).sum();
+            total + (0..n).map(|i| i as f64 / 2.0).sum::<f64>() as i64
+        };
+        let expected = (6765 + (0..200).map(|i| mix(i) + i).sum::<i64>()) % 1_000_003;
+        let (_directory, mut runtime) = runtime("hot_methods", &corlib(), &app);
+        assert_eq!(Ok(expected as i32), runtime.execute("App"));
+        assert_eq!(vec!["Program::Fib".to_string(), "Program::Mix".to_string()], runtime.compiled_methods());
+    }
+
+    #[test]
+    pub fn compiled_code_allocates_and_uses_fields_arrays_and_statics() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let int32 = app.corlib_type("System", "Int32") as i64;
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[])) as i64;
+
+        // class Node { int value; Node next; }
+        let node = app.type_def(PUBLIC, "", "Node", object);
+        let value = app.field(0, "value", Ty::I4) as i64;
+        let next = app.field(0, "next", Ty::Class(node)) as i64;
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor).op(Opcode::Ret);
+        let signature = method_sig(true, Ty::Void, &[]);
+        let node_ctor = app.method(CONSTRUCTOR, ".ctor", &signature, Some(Body::new(vec![], il))) as i64;
+
+        // static int count;
+        // static int Build(int n) {
+        //     int[] values = new int[n]; Node head = null;
+        //     for (int i = 0; i < n; i++) {
+        //         values[i] = i * 3; Node node = new Node(); node.value = values[i]; node.next = head; head = node;
+        //         count++;
+        //     }
+        //     int total = values.Length;
+        //     for (; head != null; head = head.next) total += head.value;
+        //     return total;
+        // }
+        app.type_def(PUBLIC, "", "Program", object);
+        let count = app.field(STATIC_FIELD, "count", Ty::I4) as i64;
+        let mut il = Il::new();
+        let (fill, sum, walk, end) = (il.label(), il.label(), il.label(), il.label());
+        il.op(Opcode::Ldarg0).arg(Opcode::Newarr, int32).op(Opcode::Stloc0);
+        il.op(Opcode::Ldnull).op(Opcode::Stloc1).op(Opcode::LdcI40).op(Opcode::Stloc2);
+        il.mark(fill).op(Opcode::Ldloc2).op(Opcode::Ldarg0).branch(Opcode::Bge, sum);
+        il.op(Opcode::Ldloc0).op(Opcode::Ldloc2).op(Opcode::Ldloc2).op(Opcode::LdcI43).op(Opcode::Mul);
+        il.op(Opcode::StelemI4).arg(Opcode::Newobj, node_ctor).op(Opcode::Stloc3);
+        il.op(Opcode::Ldloc3).op(Opcode::Ldloc0).op(Opcode::Ldloc2).op(Opcode::LdelemI4).arg(Opcode::Stfld, value);
+        il.op(Opcode::Ldloc3).op(Opcode::Ldloc1).arg(Opcode::Stfld, next).op(Opcode::Ldloc3).op(Opcode::Stloc1);
+        il.arg(Opcode::Ldsfld, count).op(Opcode::LdcI41).op(Opcode::Add).arg(Opcode::Stsfld, count);
+        il.op(Opcode::Ldloc2).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc2).branch(Opcode::Br, fill);
+        il.mark(sum).op(Opcode::Ldloc0).op(Opcode::Ldlen).op(Opcode::ConvI4).op(Opcode::Stloc2);
+        il.mark(walk).op(Opcode::Ldloc1).branch(Opcode::Brfalse, end);
+        il.op(Opcode::Ldloc2).op(Opcode::Ldloc1).arg(Opcode::Ldfld, value).op(Opcode::Add).op(Opcode::Stloc2);
+        il.op(Opcode::Ldloc1).arg(Opcode::Ldfld, next).op(Opcode::Stloc1).branch(Opcode::Br, walk);
+        il.mark(end).op(Opcode::Ldloc2).op(Opcode::Ret);
+        let locals = vec![Ty::sz_array(Ty::I4), Ty::Class(node), Ty::I4, Ty::Class(node)];
+        let build = app.method(STATIC, "Build", &method_sig(false, Ty::I4, &[Ty::I4]), Some(Body::new(locals, il)));
+
+        // int total = 0; for (int i = 0; i < 150; i++) total += Build(i % 20); return total + count;
+        let mut il = Il::new();
+        let (head, end) = (il.label(), il.label());
+        il.op(Opcode::LdcI40).op(Opcode::Stloc0).op(Opcode::LdcI40).op(Opcode::Stloc1);
+        il.mark(head).op(Opcode::Ldloc1).ldc_i4(150).branch(Opcode::Bge, end);
+        il.op(Opcode::Ldloc0).op(Opcode::Ldloc1).ldc_i4(20).op(Opcode::Rem).arg(Opcode::Call, build as i64);
+        il.op(Opcode::Add).op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc1).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc1).branch(Opcode::Br, head);
+        il.mark(end).op(Opcode::Ldloc0).arg(Opcode::Ldsfld, count).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::I4, Ty::I4], il));
+
+        let expected: i32 = (0..150).map(|i| i % 20).map(|n| n + 3 * n * (n - 1) / 2 + n).sum();
+        let builder = RuntimeBuilder::new().gc_mode(GcMode::Generational).gc_stress(true);
+        let (_directory, mut runtime) = runtime_with("compiled_objects", &corlib(), &app, builder);
+        assert_eq!(Ok(expected), runtime.execute("App"));
+        let compiled = ["Node::.ctor", "Program::Build", "System.Object::.ctor"];
+        assert_eq!(compiled.iter().map(|name| name.to_string()).collect::<Vec<_>>(), runtime.compiled_methods());
+    }
+
+    #[test]
+    pub fn faults_in_compiled_code_are_thrown_from_its_frame() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).op(Opcode::Ldarg1).op(Opcode::Div).op(Opcode::Ret);
+        let signature = method_sig(false, Ty::I4, &[Ty::I4, Ty::I4]);
+        let divide = app.method(STATIC, "Divide", &signature, Some(Body::new(vec![], il))) as i64;
+
+        // for (int i = 1; i <= 200; i++) Divide(i, i); return Divide(1, 0);
+        let mut il = Il::new();
+        let (head, end) = (il.label(), il.label());
+        il.op(Opcode::LdcI41).op(Opcode::Stloc0);
+        il.mark(head).op(Opcode::Ldloc0).ldc_i4(200).branch(Opcode::Bgt, end);
+        il.op(Opcode::Ldloc0).op(Opcode::Ldloc0).arg(Opcode::Call, divide).op(Opcode::Pop);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc0).branch(Opcode::Br, head);
+        il.mark(end).op(Opcode::LdcI41).op(Opcode::LdcI40).arg(Opcode::Call, divide).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::I4], il));
+
+        let (_directory, mut runtime) = runtime("compiled_fault", &corlib(), &app);
+        let report = concat!(
+            "System.DivideByZeroException: Attempted to divide by zero.\n",
+            "   at Program.Divide\n",
+            "   at Program.Main"
+        );
+        assert_eq!(Err(Error::UnhandledException(report.into())), runtime.run_main("App"));
+        assert_eq!(vec!["Program::Divide".to_string()], runtime.compiled_methods());
+    }
+
+    #[test]
+    pub fn hot_loops_continue_in_compiled_code() {
+        // long total = 0; for (int i = 0; i < 100000; i++) total += i ^ (i >> 3); return (int)(total % 1000003);
+        let mut il = Il::new();
+        let (head, end) = (il.label(), il.label());
+        il.op(Opcode::LdcI40).op(Opcode::ConvI8).op(Opcode::Stloc0).op(Opcode::LdcI40).op(Opcode::Stloc1);
+        il.mark(head).op(Opcode::Ldloc1).ldc_i4(100_000).branch(Opcode::Bge, end);
+        il.op(Opcode::Ldloc0).op(Opcode::Ldloc1).op(Opcode::Ldloc1).op(Opcode::LdcI43).op(Opcode::Shr).op(Opcode::Xor);
+        il.op(Opcode::ConvI8).op(Opcode::Add).op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc1).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc1).branch(Opcode::Br, head);
+        il.mark(end).op(Opcode::Ldloc0).ldc_i4(1_000_003).op(Opcode::ConvI8).op(Opcode::Rem).op(Opcode::ConvI4);
+        il.op(Opcode::Ret);
+        let app = app_with_main(Body::new(vec![Ty::I8, Ty::I4], il));
+
+        let records = Records::default();
+        let thresholds = TierThresholds {
+            back_edges: 50,
+            reoptimize: None,
+            ..TierThresholds::default()
+        };
+        let builder = RuntimeBuilder::new()
+            .logger(slog::Logger::root(records.clone(), o!()))
+            .tier_thresholds(thresholds);
+        let (_directory, mut runtime) = runtime_with("hot_loops", &corlib(), &app, builder);
+        let expected = (0..100_000i64).map(|i| i ^ (i >> 3)).sum::<i64>() % 1_000_003;
+        assert_eq!(Ok(expected as i32), runtime.execute("App"));
+        assert_eq!(vec!["Program::Main".to_string()], runtime.compiled_methods());
+        let tiered_up = [
+            ("method", "Program::Main"),
+            ("from", "interpreted"),
+            ("to", "baseline"),
+            ("reason", "back edges"),
+        ];
+        assert!(records.contains("method tiered up", &tiered_up));
+        assert!(records.contains("entering compiled loop", &[("method", "Program::Main"), ("tier", "baseline")]));
+    }
+
+    #[test]
+    pub fn hot_methods_are_reoptimized_with_their_profile() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[])) as i64;
+        let default_ctor = method_sig(true, Ty::Void, &[]);
+        let area_sig = method_sig(true, Ty::I4, &[]);
+
+        // class Shape { int side; virtual int Area() { return 1; } }
+        let shape = app.type_def(PUBLIC, "", "Shape", object);
+        let side = app.field(0, "side", Ty::I4) as i64;
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor).op(Opcode::Ret);
+        let shape_ctor = app.method(CONSTRUCTOR, ".ctor", &default_ctor, Some(Body::new(vec![], il))) as i64;
+        let mut il = Il::new();
+        il.op(Opcode::LdcI41).op(Opcode::Ret);
+        let area = app.method(VIRTUAL | NEW_SLOT, "Area", &area_sig, Some(Body::new(vec![], il))) as i64;
+
+        // class Square : Shape { override int Area() { return side * side; } }
+        // class Circle : Shape { override int Area() { return 3; } }
+        app.type_def(PUBLIC, "", "Square", shape);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, shape_ctor).op(Opcode::Ret);
+        let square_ctor = app.method(CONSTRUCTOR, ".ctor", &default_ctor, Some(Body::new(vec![], il))) as i64;
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Ldfld, side).op(Opcode::Ldarg0).arg(Opcode::Ldfld, side).op(Opcode::Mul);
+        il.op(Opcode::Ret);
+        app.method(VIRTUAL, "Area", &area_sig, Some(Body::new(vec![], il)));
+        app.type_def(PUBLIC, "", "Circle", shape);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, shape_ctor).op(Opcode::Ret);
+        let circle_ctor = app.method(CONSTRUCTOR, ".ctor", &default_ctor, Some(Body::new(vec![], il))) as i64;
+        let mut il = Il::new();
+        il.op(Opcode::LdcI43).op(Opcode::Ret);
+        app.method(VIRTUAL, "Area", &area_sig, Some(Body::new(vec![], il)));
+
+        // static int Total(Shape shape, int n) {
+        //     int total = 0;
+        //     for (int i = 0; i < n; i++) { if (i == 1000000) total--; total += shape.Area(); }
+        //     return total;
+        // }
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        let (head, common, end) = (il.label(), il.label(), il.label());
+        il.op(Opcode::LdcI40).op(Opcode::Stloc0).op(Opcode::LdcI40).op(Opcode::Stloc1);
+        il.mark(head).op(Opcode::Ldloc1).op(Opcode::Ldarg1).branch(Opcode::Bge, end);
+        il.op(Opcode::Ldloc1).ldc_i4(1_000_000).branch(Opcode::BneUn, common);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI41).op(Opcode::Sub).op(Opcode::Stloc0);
+        il.mark(common).op(Opcode::Ldloc0).op(Opcode::Ldarg0).arg(Opcode::Callvirt, area).op(Opcode::Add);
+        il.op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc1).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc1).branch(Opcode::Br, head);
+        il.mark(end).op(Opcode::Ldloc0).op(Opcode::Ret);
+        let signature = method_sig(false, Ty::I4, &[Ty::Class(shape), Ty::I4]);
+        let total = app.method(STATIC, "Total", &signature, Some(Body::new(vec![Ty::I4, Ty::I4], il))) as i64;
+
+        // Square square = new Square(); square.side = 3; int total = 0;
+        // for (int i = 0; i < 300; i++) total += Total(square, 20);
+        // return total + Total(new Circle(), 20);
+        let mut il = Il::new();
+        let (head, end) = (il.label(), il.label());
+        il.arg(Opcode::Newobj, square_ctor).op(Opcode::Dup).op(Opcode::LdcI43).arg(Opcode::Stfld, side);
+        il.op(Opcode::Stloc0).op(Opcode::LdcI40).op(Opcode::Stloc1).op(Opcode::LdcI40).op(Opcode::Stloc2);
+        il.mark(head).op(Opcode::Ldloc2).ldc_i4(300).branch(Opcode::Bge, end);
+        il.op(Opcode::Ldloc1).op(Opcode::Ldloc0).ldc_i4(20).arg(Opcode::Call, total).op(Opcode::Add).op(Opcode::Stloc1);
+        il.op(Opcode::Ldloc2).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc2).branch(Opcode::Br, head);
+        il.mark(end).op(Opcode::Ldloc1).arg(Opcode::Newobj, circle_ctor).ldc_i4(20).arg(Opcode::Call, total);
+        il.op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::Class(shape), Ty::I4, Ty::I4], il));
+
+        let records = Records::default();
+        let thresholds = TierThresholds {
+            calls: 10,
+            back_edges: 100_000,
+            reoptimize: Some(100),
+        };
+        let builder = RuntimeBuilder::new()
+            .logger(slog::Logger::root(records.clone(), o!()))
+            .tier_thresholds(thresholds)
+            .gc_mode(GcMode::Generational)
+            .gc_stress(true);
+        let (_directory, mut runtime) = runtime_with("reoptimized", &corlib(), &app, builder);
+        assert_eq!(Ok(300 * 20 * 9 + 20 * 3), runtime.execute("App"));
+        assert!(runtime.optimized_methods().contains(&"Program::Total".to_string()));
+        let tiered_up = [
+            ("method", "Program::Total"),
+            ("from", "baseline"),
+            ("to", "optimized"),
+            ("devirtualized_calls", "1"),
+            ("biased_branches", "1"),
+        ];
+        assert!(records.contains("method tiered up", &tiered_up));
+    }
+}
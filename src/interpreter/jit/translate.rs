@@ -0,0 +1,1073 @@
+//! Translates a method's IL to Cranelift IR, keeping the evaluation stack in SSA values. Each instruction that is
+//! branched to, or follows a branch, starts a block that takes the values on the stack as parameters.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::types::{F32, F64, I16, I32, I64, I8};
+use cranelift_codegen::ir::{self, AbiParam, Block, InstBuilder, MemFlags, Signature, StackSlotData, StackSlotKind};
+use cranelift_codegen::isa::CallConv;
+use cranelift_frontend::FunctionBuilder;
+use ecma355metadata::cli::il::{FlowControl, Instruction, Opcode, Operand};
+
+use error::{Error, ExceptionKind};
+use gc;
+use interpreter::arrays::element_storage;
+use interpreter::ops::{Comparison, Conversion};
+use interpreter::{MethodCode, Slot, TypeInit};
+use types::{MethodId, Storage, TypeId, TypeKind};
+use vm::Vm;
+
+use super::{call_helper, new_array_helper, remainder_helper, safepoint_helper, store_ref_helper};
+use super::{BranchCounts, Branches, CallKind, CallSite, CallSites, Kind, Profile, Receivers};
+use super::{FAULTS, FIRST_FAULT, RETURNED};
+
+/// Why a method can't be compiled.
+type Unsupported = String;
+
+/// How often a branch has to have been reached for optimized code to trust its profile.
+const MIN_BRANCH_SAMPLES: u64 = 100;
+
+/// What translating a method made.
+pub(super) struct Translation {
+    pub sites: CallSites,
+    pub branches: Branches,
+    pub loop_entries: Vec<usize>,
+    pub devirtualized: usize,
+    pub biased_branches: usize,
+}
+
+fn describe(error: Error) -> Unsupported {
+    format!("{:?}", error)
+}
+
+/// Translates a method to the function `builder` builds. Profiling code counts what its call sites and branches
+/// see, and optimized code is translated with what profiling code saw.
+///
+/// The function starts at the instruction its last parameter gives, which is the first instruction, or one that
+/// starts a loop with nothing on the stack.
+pub(super) fn translate(
+    vm: &mut Vm,
+    code: &MethodCode,
+    returns: Option<Kind>,
+    profiling: bool,
+    profile: Option<&Profile>,
+    mut builder: FunctionBuilder,
+    call_conv: CallConv,
+) -> Result<Translation, Unsupported> {
+    let entry = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    builder.switch_to_block(entry);
+    let params = builder.block_params(entry).to_vec();
+    let dispatch = builder.create_block();
+    builder.ins().jump(dispatch, &[]);
+
+    let mut blocks = HashMap::new();
+    let mut loops = Vec::new();
+    blocks.insert(0, builder.create_block());
+    for (index, instruction) in code.instructions.iter().enumerate() {
+        for &target in instruction.branch_targets() {
+            let target = code.instruction_at(target).ok_or("it branches outside its code")?;
+            blocks.entry(target).or_insert_with(|| builder.create_block());
+            if target <= index && target != 0 && !loops.contains(&target) {
+                loops.push(target);
+            }
+        }
+        match instruction.opcode.flow_control() {
+            FlowControl::Branch | FlowControl::CondBranch | FlowControl::Return | FlowControl::Throw => {
+                blocks.entry(index + 1).or_insert_with(|| builder.create_block());
+            }
+            _ => {}
+        }
+    }
+
+    let mut translator = Translator {
+        vm,
+        code,
+        returns,
+        builder,
+        call_conv,
+        vm_pointer: params[0],
+        memory: params[1],
+        result: params[2],
+        blocks,
+        block_stacks: HashMap::new(),
+        stack: Vec::new(),
+        terminated: true,
+        sites: Vec::new(),
+        profiling,
+        profile,
+        branches: Vec::new(),
+        devirtualized: 0,
+        biased_branches: 0,
+        index: 0,
+    };
+    for (index, instruction) in code.instructions.iter().enumerate() {
+        translator.index = index;
+        if translator.blocks.contains_key(&index) {
+            translator.start_block()?;
+        } else if translator.terminated {
+            // Nothing branches here, so the instruction is never executed
+            continue;
+        }
+        translator.instruction(instruction)?;
+    }
+    if !translator.terminated {
+        return Err("it runs off the end of its code".into());
+    }
+
+    // Go to the instruction to start at
+    let start = params[3];
+    translator.builder.switch_to_block(dispatch);
+    let mut loop_entries = Vec::new();
+    for header in loops {
+        if !translator.block_stacks.get(&header).is_some_and(Vec::is_empty) {
+            continue;
+        }
+        let is_header = translator.builder.ins().icmp_imm(IntCC::Equal, start, header as i64);
+        let next = translator.builder.create_block();
+        translator.builder.ins().brif(is_header, translator.blocks[&header], &[], next, &[]);
+        translator.builder.switch_to_block(next);
+        loop_entries.push(header);
+    }
+    translator.builder.ins().jump(translator.blocks[&0], &[]);
+
+    translator.builder.seal_all_blocks();
+    translator.builder.finalize();
+    Ok(Translation {
+        sites: translator.sites,
+        branches: translator.branches,
+        loop_entries,
+        devirtualized: translator.devirtualized,
+        biased_branches: translator.biased_branches,
+    })
+}
+
+/// Gets the type of the SSA values that hold values of a kind.
+fn ir_type(kind: Kind) -> ir::Type {
+    match kind {
+        Kind::I32 => I32,
+        Kind::F => F64,
+        Kind::I64 | Kind::Native | Kind::Ref => I64,
+    }
+}
+
+fn int_cc(comparison: Comparison) -> IntCC {
+    match comparison {
+        Comparison::Eq => IntCC::Equal,
+        Comparison::Ne => IntCC::NotEqual,
+        Comparison::Gt => IntCC::SignedGreaterThan,
+        Comparison::Ge => IntCC::SignedGreaterThanOrEqual,
+        Comparison::Lt => IntCC::SignedLessThan,
+        Comparison::Le => IntCC::SignedLessThanOrEqual,
+        Comparison::GtUn => IntCC::UnsignedGreaterThan,
+        Comparison::GeUn => IntCC::UnsignedGreaterThanOrEqual,
+        Comparison::LtUn => IntCC::UnsignedLessThan,
+        Comparison::LeUn => IntCC::UnsignedLessThanOrEqual,
+    }
+}
+
+fn float_cc(comparison: Comparison) -> FloatCC {
+    match comparison {
+        Comparison::Eq => FloatCC::Equal,
+        Comparison::Ne => FloatCC::NotEqual,
+        Comparison::Gt => FloatCC::GreaterThan,
+        Comparison::Ge => FloatCC::GreaterThanOrEqual,
+        Comparison::Lt => FloatCC::LessThan,
+        Comparison::Le => FloatCC::LessThanOrEqual,
+        Comparison::GtUn => FloatCC::UnorderedOrGreaterThan,
+        Comparison::GeUn => FloatCC::UnorderedOrGreaterThanOrEqual,
+        Comparison::LtUn => FloatCC::UnorderedOrLessThan,
+        Comparison::LeUn => FloatCC::UnorderedOrLessThanOrEqual,
+    }
+}
+
+/// Gets the size of the array elements an `ldelem` or `stelem` instruction without a type operand accesses.
+fn element_size(storage: Storage) -> u32 {
+    match storage {
+        Storage::I1 | Storage::U1 => 1,
+        Storage::I2 | Storage::U2 => 2,
+        Storage::I4 | Storage::U4 | Storage::R4 => 4,
+        _ => 8,
+    }
+}
+
+struct Translator<'a> {
+    vm: &'a mut Vm,
+    code: &'a MethodCode,
+    returns: Option<Kind>,
+    builder: FunctionBuilder<'a>,
+    call_conv: CallConv,
+
+    /// The function's parameters: the `Vm`, the frame's memory and where to write what the method returns.
+    vm_pointer: ir::Value,
+    memory: ir::Value,
+    result: ir::Value,
+
+    /// The block each instruction that starts one starts, and the kinds of the values on the stack when it starts,
+    /// once something that goes to it has been translated.
+    blocks: HashMap<usize, Block>,
+    block_stacks: HashMap<usize, Vec<Kind>>,
+    stack: Vec<(ir::Value, Kind)>,
+
+    /// Whether the current block has ended, so the instruction being translated isn't reached from the one before.
+    terminated: bool,
+    sites: CallSites,
+
+    /// Whether the code counts what its call sites and branches see, and what code compiled before saw.
+    profiling: bool,
+    profile: Option<&'a Profile>,
+    branches: Branches,
+
+    /// How many virtual calls the code makes directly, and how many of its branches it expects to go one way.
+    devirtualized: usize,
+    biased_branches: usize,
+
+    /// The index of the instruction being translated.
+    index: usize,
+}
+
+impl<'a> Translator<'a> {
+    /// Starts the block the current instruction starts, going to it from the instruction before if that continues
+    /// to it. A block nothing has gone to yet starts with an empty stack.
+    fn start_block(&mut self) -> Result<(), Unsupported> {
+        let index = self.index;
+        if !self.terminated {
+            let (block, args) = self.branch_to(index)?;
+            self.builder.ins().jump(block, &args);
+        }
+        let block = self.blocks[&index];
+        let kinds = self.block_stacks.entry(index).or_default().clone();
+        self.builder.switch_to_block(block);
+        self.stack = self.builder.block_params(block).iter().cloned().zip(kinds).collect();
+        self.terminated = false;
+        Ok(())
+    }
+
+    /// Gets the block starting at an instruction, and the values on the stack to pass it when going to it.
+    fn branch_to(&mut self, index: usize) -> Result<(Block, Vec<ir::Value>), Unsupported> {
+        let block = *self.blocks.get(&index).ok_or("it branches into the middle of a block")?;
+        let kinds: Vec<Kind> = self.stack.iter().map(|&(_, kind)| kind).collect();
+        match self.block_stacks.get(&index) {
+            Some(expected) if *expected != kinds => return Err("its stack has different types where paths join".into()),
+            Some(_) => {}
+            None => {
+                for &kind in &kinds {
+                    self.builder.append_block_param(block, ir_type(kind));
+                }
+                self.block_stacks.insert(index, kinds);
+            }
+        }
+        Ok((block, self.stack.iter().map(|&(value, _)| value).collect()))
+    }
+
+    fn target(&self, instruction: &Instruction) -> Result<usize, Unsupported> {
+        match instruction.operand {
+            Operand::BranchTarget(target) => Ok(self.code.instruction_at(target).unwrap()),
+            _ => Err(format!("{} has an invalid operand", instruction)),
+        }
+    }
+
+    /// Passes a safepoint before a backward branch, so other threads get a turn during a long loop.
+    fn back_edge(&mut self, targets: &[usize]) -> Result<(), Unsupported> {
+        if targets.iter().all(|&target| target > self.index) {
+            return Ok(());
+        }
+        self.check_no_refs("a backward branch")?;
+        self.call_helper(safepoint_helper as *const u8, &[I64], &[], &[self.vm_pointer]);
+        Ok(())
+    }
+
+    /// Ends the block with a branch to an instruction if a condition is true, or to the next instruction if it isn't.
+    /// Profiling code counts how often the branch is taken, and optimized code moves the block a branch rarely goes
+    /// to out of the way.
+    fn branch_if(&mut self, taken: ir::Value, target: usize) -> Result<(), Unsupported> {
+        self.back_edge(&[target])?;
+        let (target_block, target_args) = self.branch_to(target)?;
+        let (next, next_args) = self.branch_to(self.index + 1)?;
+        if self.profiling {
+            self.count_branch(taken);
+        }
+        if let Some(&(count, reached)) = self.profile.and_then(|profile| profile.branches.get(&self.index)) {
+            // A loop's header isn't moved, since the loop may go to it from elsewhere
+            if reached >= MIN_BRANCH_SAMPLES && count * 100 <= reached && target > self.index {
+                self.builder.set_cold_block(target_block);
+                self.biased_branches += 1;
+            } else if reached >= MIN_BRANCH_SAMPLES && (reached - count) * 100 <= reached {
+                self.builder.set_cold_block(next);
+                self.biased_branches += 1;
+            }
+        }
+        self.builder.ins().brif(taken, target_block, &target_args, next, &next_args);
+        self.terminated = true;
+        Ok(())
+    }
+
+    /// Counts whether a branch is taken, in counts the code updates in place.
+    fn count_branch(&mut self, taken: ir::Value) {
+        let counts = Box::new(BranchCounts {
+            taken: Cell::new(0),
+            reached: Cell::new(0),
+            index: self.index,
+        });
+        let address = self.builder.ins().iconst(I64, &*counts as *const BranchCounts as i64);
+        self.branches.push(counts);
+        let flags = MemFlags::trusted();
+        let taken = self.builder.ins().icmp_imm(IntCC::NotEqual, taken, 0);
+        let taken = self.builder.ins().uextend(I64, taken);
+        let count = self.builder.ins().load(I64, flags, address, 0);
+        let count = self.builder.ins().iadd(count, taken);
+        self.builder.ins().store(flags, count, address, 0);
+        let reached = self.builder.ins().load(I64, flags, address, 8);
+        let reached = self.builder.ins().iadd_imm(reached, 1);
+        self.builder.ins().store(flags, reached, address, 8);
+    }
+
+    /// Checks that there are no object references on the stack, before something that can collect garbage and move
+    /// the objects they refer to.
+    fn check_no_refs(&self, what: &str) -> Result<(), Unsupported> {
+        if self.stack.iter().any(|&(_, kind)| kind == Kind::Ref) {
+            return Err(format!("it holds an object reference on the stack across {}", what));
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, value: ir::Value, kind: Kind) {
+        self.stack.push((value, kind));
+    }
+
+    fn pop(&mut self) -> Result<(ir::Value, Kind), Unsupported> {
+        self.stack.pop().ok_or_else(|| "it pops from an empty stack".into())
+    }
+
+    /// Creates an integer constant, which Cranelift wants zero-extended for 32-bit integers.
+    fn constant(&mut self, ty: ir::Type, x: i64) -> ir::Value {
+        let x = if ty == I32 { x as u32 as i64 } else { x };
+        self.builder.ins().iconst(ty, x)
+    }
+
+    /// Calls a helper function by address, and gets what it returns.
+    fn call_helper(&mut self, function: *const u8, params: &[ir::Type], returns: &[ir::Type], args: &[ir::Value])
+        -> Vec<ir::Value>
+    {
+        let mut signature = Signature::new(self.call_conv);
+        signature.params.extend(params.iter().map(|&ty| AbiParam::new(ty)));
+        signature.returns.extend(returns.iter().map(|&ty| AbiParam::new(ty)));
+        let signature = self.builder.import_signature(signature);
+        let callee = self.builder.ins().iconst(I64, function as i64);
+        let call = self.builder.ins().call_indirect(signature, callee, args);
+        self.builder.inst_results(call).to_vec()
+    }
+
+    /// Returns the status a helper returned from the function, with the index of the instruction, unless it is zero.
+    fn check_status(&mut self, status: ir::Value) {
+        let failed = self.builder.create_block();
+        let next = self.builder.create_block();
+        self.builder.set_cold_block(failed);
+        self.builder.ins().brif(status, failed, &[], next, &[]);
+        self.builder.switch_to_block(failed);
+        let status = self.builder.ins().uextend(I64, status);
+        let status = self.builder.ins().bor_imm(status, (self.index as i64) << 8);
+        self.builder.ins().return_(&[status]);
+        self.builder.switch_to_block(next);
+    }
+
+    /// Raises an exception if a condition is true.
+    fn fault_if(&mut self, condition: ir::Value, kind: ExceptionKind) {
+        let code = FIRST_FAULT + FAULTS.iter().position(|&fault| fault == kind).unwrap() as u64;
+        let failed = self.builder.create_block();
+        let next = self.builder.create_block();
+        self.builder.set_cold_block(failed);
+        self.builder.ins().brif(condition, failed, &[], next, &[]);
+        self.builder.switch_to_block(failed);
+        let status = self.builder.ins().iconst(I64, ((self.index as u64) << 8 | code) as i64);
+        self.builder.ins().return_(&[status]);
+        self.builder.switch_to_block(next);
+    }
+
+    /// Pops an object reference, raising a `NullReferenceException` if it is null.
+    fn pop_object(&mut self) -> Result<ir::Value, Unsupported> {
+        match self.pop()? {
+            (object, Kind::Ref) => {
+                let is_null = self.builder.ins().icmp_imm(IntCC::Equal, object, 0);
+                self.fault_if(is_null, ExceptionKind::NullReference);
+                Ok(object)
+            }
+            _ => Err("it uses a field or element of a struct or through a pointer".into()),
+        }
+    }
+
+    /// Pops an integer for an array index or length, as a 64-bit integer.
+    fn pop_index(&mut self) -> Result<ir::Value, Unsupported> {
+        match self.pop()? {
+            (index, Kind::I32) => Ok(self.builder.ins().sextend(I64, index)),
+            (index, Kind::Native) => Ok(index),
+            _ => Err("it indexes an array with a non-integer".into()),
+        }
+    }
+
+    /// Reads a value with a storage from memory.
+    fn load(&mut self, storage: Storage, address: ir::Value, offset: i32, flags: MemFlags)
+        -> Result<(ir::Value, Kind), Unsupported>
+    {
+        let ins = self.builder.ins();
+        Ok(match storage {
+            Storage::I1 => (ins.sload8(I32, flags, address, offset), Kind::I32),
+            Storage::U1 => (ins.uload8(I32, flags, address, offset), Kind::I32),
+            Storage::I2 => (ins.sload16(I32, flags, address, offset), Kind::I32),
+            Storage::U2 => (ins.uload16(I32, flags, address, offset), Kind::I32),
+            Storage::I4 | Storage::U4 => (ins.load(I32, flags, address, offset), Kind::I32),
+            Storage::I8 => (ins.load(I64, flags, address, offset), Kind::I64),
+            Storage::NativeInt => (ins.load(I64, flags, address, offset), Kind::Native),
+            Storage::R4 => {
+                let x = ins.load(F32, flags, address, offset);
+                (self.builder.ins().fpromote(F64, x), Kind::F)
+            }
+            Storage::R8 => (ins.load(F64, flags, address, offset), Kind::F),
+            Storage::Ref => (ins.load(I64, flags, address, offset), Kind::Ref),
+            Storage::ByRef | Storage::Struct => return Err("it uses a struct or a managed pointer".into()),
+        })
+    }
+
+    /// Writes a value to memory with a storage, truncating integers as `Value::store` does. Object references are
+    /// written without the write barrier, so only frames' memory can be written with them.
+    fn store(&mut self, storage: Storage, value: (ir::Value, Kind), address: ir::Value, offset: i32, flags: MemFlags)
+        -> Result<(), Unsupported>
+    {
+        let ins = self.builder.ins();
+        match (storage, value) {
+            (Storage::R4, (x, Kind::F)) => {
+                let x = ins.fdemote(F32, x);
+                self.builder.ins().store(flags, x, address, offset);
+            }
+            (Storage::R8, (x, Kind::F)) | (Storage::Ref, (x, Kind::Ref)) => {
+                ins.store(flags, x, address, offset);
+            }
+            (_, (_, Kind::F)) | (_, (_, Kind::Ref)) => {
+                return Err("it stores a value to a variable of another type".into())
+            }
+            (Storage::I1, (x, _)) | (Storage::U1, (x, _)) => {
+                ins.istore8(flags, x, address, offset);
+            }
+            (Storage::I2, (x, _)) | (Storage::U2, (x, _)) => {
+                ins.istore16(flags, x, address, offset);
+            }
+            (Storage::I4, (x, Kind::I32)) | (Storage::U4, (x, Kind::I32)) => {
+                ins.store(flags, x, address, offset);
+            }
+            (Storage::I4, (x, _)) | (Storage::U4, (x, _)) => {
+                ins.istore32(flags, x, address, offset);
+            }
+            (Storage::I8, (x, Kind::I32)) | (Storage::NativeInt, (x, Kind::I32)) => {
+                let x = ins.sextend(I64, x);
+                self.builder.ins().store(flags, x, address, offset);
+            }
+            (Storage::I8, (x, _)) | (Storage::NativeInt, (x, _)) => {
+                ins.store(flags, x, address, offset);
+            }
+            _ => return Err("it stores a value to a variable of another type".into()),
+        }
+        Ok(())
+    }
+
+    /// Writes a value to a field, static field or array element, going through the write barrier for an object
+    /// reference.
+    fn store_to_heap(&mut self, storage: Storage, value: (ir::Value, Kind), address: ir::Value, offset: i32)
+        -> Result<(), Unsupported>
+    {
+        match (storage, value) {
+            (Storage::Ref, (object, Kind::Ref)) => {
+                let location = self.builder.ins().iadd_imm(address, offset as i64);
+                let args = [self.vm_pointer, location, object];
+                self.call_helper(store_ref_helper as *const u8, &[I64, I64, I64], &[], &args);
+                Ok(())
+            }
+            _ => self.store(storage, value, address, offset, MemFlags::new()),
+        }
+    }
+
+    /// Pops the operands of a binary operator on integers, widening a 32-bit integer used with a native one as the
+    /// interpreter does, and gets their kind.
+    fn pop_int_operands(&mut self) -> Result<(ir::Value, ir::Value, Kind), Unsupported> {
+        let (b, b_kind) = self.pop()?;
+        let (a, a_kind) = self.pop()?;
+        match (a_kind, b_kind) {
+            (Kind::I32, Kind::I32) => Ok((a, b, Kind::I32)),
+            (Kind::I64, Kind::I64) => Ok((a, b, Kind::I64)),
+            (Kind::I64, _) | (_, Kind::I64) | (Kind::F, _) | (_, Kind::F) => {
+                Err("it operates on integers of different sizes, or integers and floats".into())
+            }
+            _ => {
+                let a = if a_kind == Kind::I32 { self.builder.ins().sextend(I64, a) } else { a };
+                let b = if b_kind == Kind::I32 { self.builder.ins().sextend(I64, b) } else { b };
+                Ok((a, b, Kind::Native))
+            }
+        }
+    }
+
+    fn binary(&mut self, opcode: Opcode) -> Result<(), Unsupported> {
+        let floats = matches!(self.stack[..], [.., (_, Kind::F), (_, Kind::F)]);
+        if floats {
+            let (y, _) = self.pop()?;
+            let (x, _) = self.pop()?;
+            let ins = self.builder.ins();
+            let result = match opcode {
+                Opcode::Add => ins.fadd(x, y),
+                Opcode::Sub => ins.fsub(x, y),
+                Opcode::Mul => ins.fmul(x, y),
+                Opcode::Div => ins.fdiv(x, y),
+                Opcode::Rem => self.call_helper(remainder_helper as *const u8, &[F64, F64], &[F64], &[x, y])[0],
+                _ => return Err(format!("it uses {} with floats", opcode)),
+            };
+            self.push(result, Kind::F);
+            return Ok(());
+        }
+
+        let (x, y, kind) = self.pop_int_operands()?;
+        let ty = ir_type(kind);
+        let result = match opcode {
+            Opcode::Add => self.builder.ins().iadd(x, y),
+            Opcode::Sub => self.builder.ins().isub(x, y),
+            Opcode::Mul => self.builder.ins().imul(x, y),
+            Opcode::And => self.builder.ins().band(x, y),
+            Opcode::Or => self.builder.ins().bor(x, y),
+            Opcode::Xor => self.builder.ins().bxor(x, y),
+            Opcode::Div | Opcode::Rem | Opcode::DivUn | Opcode::RemUn => {
+                let is_zero = self.builder.ins().icmp_imm(IntCC::Equal, y, 0);
+                self.fault_if(is_zero, ExceptionKind::DivideByZero);
+                if opcode == Opcode::Div || opcode == Opcode::Rem {
+                    let min = self.constant(ty, if kind == Kind::I32 { i32::MIN as i64 } else { i64::MIN });
+                    let minus_one = self.constant(ty, -1);
+                    let is_min = self.builder.ins().icmp(IntCC::Equal, x, min);
+                    let is_minus_one = self.builder.ins().icmp(IntCC::Equal, y, minus_one);
+                    let overflows = self.builder.ins().band(is_min, is_minus_one);
+                    self.fault_if(overflows, ExceptionKind::Overflow);
+                }
+                match opcode {
+                    Opcode::Div => self.builder.ins().sdiv(x, y),
+                    Opcode::Rem => self.builder.ins().srem(x, y),
+                    Opcode::DivUn => self.builder.ins().udiv(x, y),
+                    _ => self.builder.ins().urem(x, y),
+                }
+            }
+            Opcode::AddOvf | Opcode::AddOvfUn | Opcode::SubOvf | Opcode::SubOvfUn | Opcode::MulOvf
+            | Opcode::MulOvfUn => {
+                let ins = self.builder.ins();
+                let (result, overflowed) = match opcode {
+                    Opcode::AddOvf => ins.sadd_overflow(x, y),
+                    Opcode::AddOvfUn => ins.uadd_overflow(x, y),
+                    Opcode::SubOvf => ins.ssub_overflow(x, y),
+                    Opcode::SubOvfUn => ins.usub_overflow(x, y),
+                    Opcode::MulOvf => ins.smul_overflow(x, y),
+                    _ => ins.umul_overflow(x, y),
+                };
+                self.fault_if(overflowed, ExceptionKind::Overflow);
+                result
+            }
+            _ => return Err(format!("{} is not a binary operator", opcode)),
+        };
+        self.push(result, kind);
+        Ok(())
+    }
+
+    /// Pops two values and compares them, getting the result as an 8-bit integer.
+    fn compare(&mut self, comparison: Comparison) -> Result<ir::Value, Unsupported> {
+        let floats = matches!(self.stack[..], [.., (_, Kind::F), (_, Kind::F)]);
+        if floats {
+            let (y, _) = self.pop()?;
+            let (x, _) = self.pop()?;
+            return Ok(self.builder.ins().fcmp(float_cc(comparison), x, y));
+        }
+        let (x, y, _) = self.pop_int_operands()?;
+        Ok(self.builder.ins().icmp(int_cc(comparison), x, y))
+    }
+
+    /// Converts a 64-bit integer to a conversion's target type, truncating it.
+    fn narrow(&mut self, x: ir::Value, target: Conversion) -> (ir::Value, Kind) {
+        let ins = self.builder.ins();
+        match target {
+            Conversion::I1 | Conversion::U1 | Conversion::I2 | Conversion::U2 => {
+                let ty = if target == Conversion::I1 || target == Conversion::U1 { I8 } else { I16 };
+                let narrow = ins.ireduce(ty, x);
+                let ins = self.builder.ins();
+                match target {
+                    Conversion::I1 | Conversion::I2 => (ins.sextend(I32, narrow), Kind::I32),
+                    _ => (ins.uextend(I32, narrow), Kind::I32),
+                }
+            }
+            Conversion::I4 | Conversion::U4 => (ins.ireduce(I32, x), Kind::I32),
+            Conversion::I8 | Conversion::U8 => (x, Kind::I64),
+            Conversion::I | Conversion::U | Conversion::R4 | Conversion::R8 => (x, Kind::Native),
+        }
+    }
+
+    fn convert(&mut self, target: Conversion, unsigned: bool) -> Result<(), Unsupported> {
+        let (x, kind) = self.pop()?;
+        let to_float = target == Conversion::R4 || target == Conversion::R8;
+        let result = if kind == Kind::F {
+            let ins = self.builder.ins();
+            match target {
+                Conversion::R4 => {
+                    let x = ins.fdemote(F32, x);
+                    (self.builder.ins().fpromote(F64, x), Kind::F)
+                }
+                Conversion::R8 => (x, Kind::F),
+                Conversion::U1 | Conversion::U2 | Conversion::U4 | Conversion::U8 | Conversion::U => {
+                    let x = ins.fcvt_to_uint_sat(I64, x);
+                    self.narrow(x, target)
+                }
+                _ => {
+                    let x = ins.fcvt_to_sint_sat(I64, x);
+                    self.narrow(x, target)
+                }
+            }
+        } else {
+            let wide = match (kind, unsigned) {
+                (Kind::I32, true) => self.builder.ins().uextend(I64, x),
+                (Kind::I32, false) => self.builder.ins().sextend(I64, x),
+                _ => x,
+            };
+            if to_float {
+                let ty = if target == Conversion::R4 { F32 } else { F64 };
+                let ins = self.builder.ins();
+                let converted = if unsigned && kind != Kind::I32 {
+                    ins.fcvt_from_uint(ty, wide)
+                } else {
+                    ins.fcvt_from_sint(ty, wide)
+                };
+                let converted = if ty == F32 { self.builder.ins().fpromote(F64, converted) } else { converted };
+                (converted, Kind::F)
+            } else {
+                self.narrow(wide, target)
+            }
+        };
+        self.push(result.0, result.1);
+        Ok(())
+    }
+
+    /// Translates a call, which pops the arguments into a buffer for the call helper.
+    fn call(&mut self, kind: CallKind, instruction: &Instruction) -> Result<(), Unsupported> {
+        let token = self.vm.token(instruction).map_err(describe)?;
+        let method = self.vm.types.resolve_method_token(self.code.assembly, token, &self.code.generics)
+            .map_err(describe)?;
+        let (has_this, params, ret, owner) = {
+            let method = self.vm.types.method(method);
+            (method.signature.has_this, method.signature.params.clone(), method.signature.ret, method.owner)
+        };
+        let mut args = Vec::with_capacity(params.len() + 1);
+        if has_this && kind != CallKind::Newobj {
+            if self.vm.types.get(owner).is_value_type() {
+                return Err("it calls a method of a value type".into());
+            }
+            args.push(Kind::Ref);
+        }
+        for param in params {
+            args.push(Kind::for_type(self.vm, param).map_err(describe)?
+                .ok_or("it passes a struct or a managed pointer")?);
+        }
+        let returns = match (kind, ret) {
+            (CallKind::Newobj, _) => match self.vm.types.get(owner).kind {
+                TypeKind::Class => Some(Kind::Ref),
+                _ => return Err("it creates a value type or an array with newobj".into()),
+            },
+            (_, Some(ty)) => {
+                Some(Kind::for_type(self.vm, ty).map_err(describe)?.ok_or("it calls a method returning a struct")?)
+            }
+            (_, None) => None,
+        };
+
+        if self.stack.len() < args.len() {
+            return Err("it pops from an empty stack".into());
+        }
+        let values = self.stack.split_off(self.stack.len() - args.len());
+        self.check_no_refs("a call")?;
+        let buffer = StackSlotData::new(StackSlotKind::ExplicitSlot, 8 * args.len().max(1) as u32, 3);
+        let buffer = self.builder.create_sized_stack_slot(buffer);
+        for (i, (&(value, value_kind), &arg)) in values.iter().zip(&args).enumerate() {
+            // A 32-bit integer can be passed as a wider one, and is sign-extended for it
+            let value = match (value_kind, arg) {
+                (Kind::I32, _) if arg != Kind::F && arg != Kind::Ref => self.builder.ins().sextend(I64, value),
+                (Kind::I64, Kind::I32) | (Kind::Native, Kind::I32) | (Kind::Native, Kind::I64)
+                | (Kind::I64, Kind::Native) => value,
+                _ if value_kind == arg => value,
+                _ => return Err("it passes an argument of the wrong type".into()),
+            };
+            self.builder.ins().stack_store(value, buffer, 8 * i as i32);
+        }
+        let result = StackSlotData::new(StackSlotKind::ExplicitSlot, 8, 3);
+        let result = self.builder.create_sized_stack_slot(result);
+
+        let site_address = match self.monomorphic_target(kind, has_this, method) {
+            Some((ty, target)) => {
+                // Call the method the type implements directly, when the object is of the type profiling code saw
+                let receiver = values[0].0;
+                let direct = self.call_site(CallKind::Call, target, args.clone(), returns);
+                let indirect = self.call_site(kind, method, args, returns);
+                let check = self.builder.create_block();
+                let join = self.builder.create_block();
+                let site = self.builder.append_block_param(join, I64);
+                let is_null = self.builder.ins().icmp_imm(IntCC::Equal, receiver, 0);
+                self.builder.ins().brif(is_null, join, &[indirect], check, &[]);
+                self.builder.switch_to_block(check);
+                let object_type = self.builder.ins().load(I64, MemFlags::trusted(), receiver, 0);
+                let is_type = self.builder.ins().icmp_imm(IntCC::Equal, object_type, ty.0 as i64);
+                let chosen = self.builder.ins().select(is_type, direct, indirect);
+                self.builder.ins().jump(join, &[chosen]);
+                self.builder.switch_to_block(join);
+                self.devirtualized += 1;
+                site
+            }
+            None => self.call_site(kind, method, args, returns),
+        };
+        let buffer_address = self.builder.ins().stack_addr(I64, buffer, 0);
+        let result_address = self.builder.ins().stack_addr(I64, result, 0);
+        let args = [self.vm_pointer, site_address, buffer_address, result_address];
+        let status = self.call_helper(call_helper as *const u8, &[I64, I64, I64, I64], &[I32], &args)[0];
+        self.check_status(status);
+        if let Some(returns) = returns {
+            let value = self.builder.ins().stack_load(ir_type(returns), result, 0);
+            self.push(value, returns);
+        }
+        Ok(())
+    }
+
+    /// Creates a call site for the current instruction, and gets its address. Profiling code counts the types of the
+    /// objects its virtual calls are made on.
+    fn call_site(&mut self, kind: CallKind, method: MethodId, args: Vec<Kind>, returns: Option<Kind>) -> ir::Value {
+        let site = Box::new(CallSite {
+            kind,
+            method,
+            index: self.index,
+            profiled: self.profiling && kind == CallKind::Callvirt && args.first() == Some(&Kind::Ref),
+            args,
+            returns,
+            receivers: Cell::new(Receivers::None),
+        });
+        let address = self.builder.ins().iconst(I64, &*site as *const CallSite as i64);
+        self.sites.push(site);
+        address
+    }
+
+    /// Gets the type of the only objects profiling code saw a virtual call made on, and the method the type
+    /// implements, if optimized code can call the method directly for objects of the type.
+    fn monomorphic_target(&mut self, kind: CallKind, has_this: bool, method: MethodId) -> Option<(TypeId, MethodId)> {
+        if kind != CallKind::Callvirt || !has_this {
+            return None;
+        }
+        let ty = *self.profile?.receivers.get(&self.index)?;
+        let target = self.vm.types.find_implementation(ty, method).ok()?;
+        if self.vm.types.method(target).is_abstract() {
+            return None;
+        }
+        let owner = self.vm.types.get(self.vm.types.method(target).owner);
+        match owner.kind {
+            TypeKind::Array(..) => None,
+            _ if owner.is_value_type() => None,
+            _ => Some((ty, target)),
+        }
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) -> Result<(), Unsupported> {
+        let opcode = instruction.opcode;
+        let trusted = MemFlags::trusted();
+        match opcode {
+            Opcode::Nop | Opcode::Break => {}
+
+            Opcode::Ldarg0 | Opcode::Ldarg1 | Opcode::Ldarg2 | Opcode::Ldarg3 | Opcode::LdargS | Opcode::Ldarg => {
+                let slot = self.variable(&self.code.args, instruction, Opcode::Ldarg0)?;
+                let (value, kind) = self.load(slot.storage, self.memory, slot.offset as i32, trusted)?;
+                self.push(value, kind);
+            }
+            Opcode::Ldloc0 | Opcode::Ldloc1 | Opcode::Ldloc2 | Opcode::Ldloc3 | Opcode::LdlocS | Opcode::Ldloc => {
+                let slot = self.variable(&self.code.locals, instruction, Opcode::Ldloc0)?;
+                let (value, kind) = self.load(slot.storage, self.memory, slot.offset as i32, trusted)?;
+                self.push(value, kind);
+            }
+            Opcode::StargS | Opcode::Starg => {
+                let slot = self.variable(&self.code.args, instruction, Opcode::Starg)?;
+                let value = self.pop()?;
+                self.store(slot.storage, value, self.memory, slot.offset as i32, trusted)?;
+            }
+            Opcode::Stloc0 | Opcode::Stloc1 | Opcode::Stloc2 | Opcode::Stloc3 | Opcode::StlocS | Opcode::Stloc => {
+                let slot = self.variable(&self.code.locals, instruction, Opcode::Stloc0)?;
+                let value = self.pop()?;
+                self.store(slot.storage, value, self.memory, slot.offset as i32, trusted)?;
+            }
+
+            Opcode::Ldnull => {
+                let null = self.builder.ins().iconst(I64, 0);
+                self.push(null, Kind::Ref);
+            }
+            Opcode::LdcI4M1 => {
+                let x = self.constant(I32, -1);
+                self.push(x, Kind::I32);
+            }
+            Opcode::LdcI40 | Opcode::LdcI41 | Opcode::LdcI42 | Opcode::LdcI43 | Opcode::LdcI44 | Opcode::LdcI45
+            | Opcode::LdcI46 | Opcode::LdcI47 | Opcode::LdcI48 => {
+                let x = self.constant(I32, (opcode.value() - Opcode::LdcI40.value()) as i64);
+                self.push(x, Kind::I32);
+            }
+            Opcode::LdcI4S | Opcode::LdcI4 | Opcode::LdcI8 | Opcode::LdcR4 | Opcode::LdcR8 => {
+                let (value, kind) = match instruction.operand {
+                    Operand::Int8(x) => (self.constant(I32, x as i64), Kind::I32),
+                    Operand::Int32(x) => (self.constant(I32, x as i64), Kind::I32),
+                    Operand::Int64(x) => (self.constant(I64, x), Kind::I64),
+                    Operand::Float32(x) => (self.builder.ins().f64const(x as f64), Kind::F),
+                    Operand::Float64(x) => (self.builder.ins().f64const(x), Kind::F),
+                    _ => return Err(format!("{} has an invalid operand", instruction)),
+                };
+                self.push(value, kind);
+            }
+            Opcode::Dup => {
+                let top = self.pop()?;
+                self.stack.push(top);
+                self.stack.push(top);
+            }
+            Opcode::Pop => {
+                self.pop()?;
+            }
+
+            Opcode::Call => self.call(CallKind::Call, instruction)?,
+            Opcode::Callvirt => self.call(CallKind::Callvirt, instruction)?,
+            Opcode::Newobj => self.call(CallKind::Newobj, instruction)?,
+            Opcode::Ret => {
+                if let Some(returns) = self.returns {
+                    let value = match self.pop()? {
+                        (value, Kind::I32) if returns != Kind::F && returns != Kind::Ref => {
+                            self.builder.ins().sextend(I64, value)
+                        }
+                        (value, kind) if kind == returns => value,
+                        _ => return Err("it returns a value of another type".into()),
+                    };
+                    self.builder.ins().store(trusted, value, self.result, 0);
+                }
+                let status = self.builder.ins().iconst(I64, RETURNED as i64);
+                self.builder.ins().return_(&[status]);
+                self.terminated = true;
+            }
+
+            Opcode::Br | Opcode::BrS => {
+                let target = self.target(instruction)?;
+                self.back_edge(&[target])?;
+                let (block, args) = self.branch_to(target)?;
+                self.builder.ins().jump(block, &args);
+                self.terminated = true;
+            }
+            Opcode::Brtrue | Opcode::BrtrueS | Opcode::Brfalse | Opcode::BrfalseS => {
+                let condition = match self.pop()? {
+                    (_, Kind::F) => return Err("it branches on a float".into()),
+                    (condition, _) => condition,
+                };
+                let taken = if opcode == Opcode::Brtrue || opcode == Opcode::BrtrueS {
+                    condition
+                } else {
+                    self.builder.ins().icmp_imm(IntCC::Equal, condition, 0)
+                };
+                let target = self.target(instruction)?;
+                self.branch_if(taken, target)?;
+            }
+            Opcode::Beq | Opcode::BeqS | Opcode::BneUn | Opcode::BneUnS | Opcode::Bgt | Opcode::BgtS
+            | Opcode::Bge | Opcode::BgeS | Opcode::Blt | Opcode::BltS | Opcode::Ble | Opcode::BleS
+            | Opcode::BgtUn | Opcode::BgtUnS | Opcode::BgeUn | Opcode::BgeUnS | Opcode::BltUn | Opcode::BltUnS
+            | Opcode::BleUn | Opcode::BleUnS => {
+                let condition = self.compare(Comparison::for_opcode(opcode).unwrap())?;
+                let target = self.target(instruction)?;
+                self.branch_if(condition, target)?;
+            }
+            Opcode::Switch => {
+                let value = match self.pop()? {
+                    (value, Kind::I32) => value,
+                    (value, Kind::I64) | (value, Kind::Native) => self.builder.ins().ireduce(I32, value),
+                    _ => return Err("it switches on a non-integer".into()),
+                };
+                let targets: Vec<usize> = instruction.branch_targets()
+                    .iter()
+                    .map(|&target| self.code.instruction_at(target).unwrap())
+                    .collect();
+                self.back_edge(&targets)?;
+                for (case, &target) in targets.iter().enumerate() {
+                    let (target, args) = self.branch_to(target)?;
+                    let next = self.builder.create_block();
+                    let is_case = self.builder.ins().icmp_imm(IntCC::Equal, value, case as i64);
+                    self.builder.ins().brif(is_case, target, &args, next, &[]);
+                    self.builder.switch_to_block(next);
+                }
+                let (next, args) = self.branch_to(self.index + 1)?;
+                self.builder.ins().jump(next, &args);
+                self.terminated = true;
+            }
+
+            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div | Opcode::DivUn | Opcode::Rem | Opcode::RemUn
+            | Opcode::And | Opcode::Or | Opcode::Xor | Opcode::AddOvf | Opcode::AddOvfUn | Opcode::SubOvf
+            | Opcode::SubOvfUn | Opcode::MulOvf | Opcode::MulOvfUn => self.binary(opcode)?,
+            Opcode::Shl | Opcode::Shr | Opcode::ShrUn => {
+                let (amount, amount_kind) = self.pop()?;
+                let (x, kind) = self.pop()?;
+                if amount_kind == Kind::F || amount_kind == Kind::Ref || kind == Kind::F || kind == Kind::Ref {
+                    return Err("it shifts a non-integer".into());
+                }
+                let ins = self.builder.ins();
+                let result = match opcode {
+                    Opcode::Shl => ins.ishl(x, amount),
+                    Opcode::Shr => ins.sshr(x, amount),
+                    _ => ins.ushr(x, amount),
+                };
+                self.push(result, kind);
+            }
+            Opcode::Neg | Opcode::Not => {
+                let (x, kind) = self.pop()?;
+                let ins = self.builder.ins();
+                let result = match (opcode, kind) {
+                    (_, Kind::Ref) | (Opcode::Not, Kind::F) => {
+                        return Err(format!("it uses {} on a {:?}", opcode, kind))
+                    }
+                    (Opcode::Neg, Kind::F) => ins.fneg(x),
+                    (Opcode::Neg, _) => ins.ineg(x),
+                    _ => ins.bnot(x),
+                };
+                self.push(result, kind);
+            }
+            Opcode::Ceq | Opcode::Cgt | Opcode::CgtUn | Opcode::Clt | Opcode::CltUn => {
+                let result = self.compare(Comparison::for_opcode(opcode).unwrap())?;
+                let result = self.builder.ins().uextend(I32, result);
+                self.push(result, Kind::I32);
+            }
+
+            Opcode::Ldfld | Opcode::Stfld => {
+                let token = self.vm.token(instruction).map_err(describe)?;
+                let field = self.vm.types.resolve_field_token(self.code.assembly, token, &self.code.generics)
+                    .map_err(describe)?;
+                if self.vm.types.field(field).is_static() {
+                    return Err("it uses a static field as an instance field".into());
+                }
+                let (offset, field_type) = {
+                    let field = self.vm.types.field(field);
+                    (field.offset as usize, field.field_type)
+                };
+                self.vm.types.prepare(field_type).map_err(describe)?;
+                let storage = self.vm.types.get(field_type).storage();
+                let offset = (gc::HEADER_SIZE + offset) as i32;
+                if opcode == Opcode::Ldfld {
+                    let object = self.pop_object()?;
+                    let (value, kind) = self.load(storage, object, offset, MemFlags::new())?;
+                    self.push(value, kind);
+                } else {
+                    let value = self.pop()?;
+                    let object = self.pop_object()?;
+                    self.store_to_heap(storage, value, object, offset)?;
+                }
+            }
+            Opcode::Ldsfld | Opcode::Stsfld => {
+                let token = self.vm.token(instruction).map_err(describe)?;
+                let field = self.vm.types.resolve_field_token(self.code.assembly, token, &self.code.generics)
+                    .map_err(describe)?;
+                let (owner, field_type) = {
+                    let field = self.vm.types.field(field);
+                    (field.owner, field.field_type)
+                };
+                // The field's address is only fixed once its type has been initialized, on every thread
+                if !matches!(self.vm.type_inits.get(&owner), Some(&TypeInit::Done)) {
+                    return Err("it uses a static field of a type that hasn't been initialized".into());
+                }
+                if self.vm.is_thread_static(field).map_err(describe)? {
+                    return Err("it uses a thread-static field".into());
+                }
+                let address = self.vm.static_field_address(field).map_err(describe)?;
+                self.vm.types.prepare(field_type).map_err(describe)?;
+                let storage = self.vm.types.get(field_type).storage();
+                let address = self.builder.ins().iconst(I64, address as i64);
+                if opcode == Opcode::Ldsfld {
+                    let (value, kind) = self.load(storage, address, 0, MemFlags::new())?;
+                    self.push(value, kind);
+                } else {
+                    let value = self.pop()?;
+                    self.store_to_heap(storage, value, address, 0)?;
+                }
+            }
+
+            Opcode::Newarr => {
+                let token = self.vm.token(instruction).map_err(describe)?;
+                let element = self.vm.types.resolve_type_token(self.code.assembly, token, &self.code.generics)
+                    .map_err(describe)?;
+                let array_type = self.vm.types.sz_array(element).map_err(describe)?;
+                let length = self.pop_index()?;
+                self.check_no_refs("an allocation")?;
+                let result = StackSlotData::new(StackSlotKind::ExplicitSlot, 8, 3);
+                let result = self.builder.create_sized_stack_slot(result);
+                let array_type = self.builder.ins().iconst(I64, array_type.0 as i64);
+                let result_address = self.builder.ins().stack_addr(I64, result, 0);
+                let args = [self.vm_pointer, array_type, length, result_address];
+                let status = self.call_helper(new_array_helper as *const u8, &[I64, I64, I64, I64], &[I32], &args)[0];
+                self.check_status(status);
+                let array = self.builder.ins().stack_load(I64, result, 0);
+                self.push(array, Kind::Ref);
+            }
+            Opcode::Ldlen => {
+                let array = self.pop_object()?;
+                let length = self.builder.ins().load(I64, MemFlags::new(), array, gc::HEADER_SIZE as i32);
+                self.push(length, Kind::Native);
+            }
+            Opcode::LdelemI1 | Opcode::LdelemU1 | Opcode::LdelemI2 | Opcode::LdelemU2 | Opcode::LdelemI4
+            | Opcode::LdelemU4 | Opcode::LdelemI8 | Opcode::LdelemI | Opcode::LdelemR4 | Opcode::LdelemR8
+            | Opcode::LdelemRef | Opcode::Ldelem => {
+                let (storage, size) = self.element_layout(instruction)?;
+                let index = self.pop_index()?;
+                let address = self.element(index, size)?;
+                let (value, kind) = self.load(storage, address, 0, MemFlags::new())?;
+                self.push(value, kind);
+            }
+            Opcode::StelemI | Opcode::StelemI1 | Opcode::StelemI2 | Opcode::StelemI4 | Opcode::StelemI8
+            | Opcode::StelemR4 | Opcode::StelemR8 | Opcode::Stelem => {
+                let (storage, size) = self.element_layout(instruction)?;
+                if storage == Storage::Ref {
+                    return Err("it stores to an array of references, which needs a type check".into());
+                }
+                let value = self.pop()?;
+                let index = self.pop_index()?;
+                let address = self.element(index, size)?;
+                self.store(storage, value, address, 0, MemFlags::new())?;
+            }
+
+            _ => match Conversion::for_opcode(opcode) {
+                Some((target, false, unsigned)) => self.convert(target, unsigned)?,
+                _ => return Err(format!("it uses {}", opcode)),
+            },
+        }
+        Ok(())
+    }
+
+    /// Gets the argument or local variable an instruction refers to.
+    fn variable(&self, slots: &[Slot], instruction: &Instruction, first: Opcode) -> Result<Slot, Unsupported> {
+        self.vm.variable(slots, instruction, first).map_err(describe)
+    }
+
+    /// Gets the storage and size of the elements an `ldelem` or `stelem` instruction accesses.
+    fn element_layout(&mut self, instruction: &Instruction) -> Result<(Storage, u32), Unsupported> {
+        match instruction.operand {
+            Operand::Token(token) => {
+                let ty = self.vm.types.resolve_type_token(self.code.assembly, token, &self.code.generics)
+                    .map_err(describe)?;
+                self.vm.types.prepare(ty).map_err(describe)?;
+                let ty = self.vm.types.get(ty);
+                Ok((ty.storage(), ty.value_size()))
+            }
+            _ => {
+                let storage = element_storage(instruction.opcode);
+                Ok((storage, element_size(storage)))
+            }
+        }
+    }
+
+    /// Pops an array, and gets the address of the element at an index in it, raising an exception if the array is
+    /// null or the index is out of range.
+    fn element(&mut self, index: ir::Value, size: u32) -> Result<ir::Value, Unsupported> {
+        let array = self.pop_object()?;
+        let length = self.builder.ins().load(I64, MemFlags::new(), array, gc::HEADER_SIZE as i32);
+        let out_of_range = self.builder.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, index, length);
+        self.fault_if(out_of_range, ExceptionKind::IndexOutOfRange);
+        let offset = self.builder.ins().imul_imm(index, size as i64);
+        let element = self.builder.ins().iadd(array, offset);
+        Ok(self.builder.ins().iadd_imm(element, (gc::HEADER_SIZE + gc::ARRAY_ELEMENTS) as i64))
+    }
+}
@@ -0,0 +1,193 @@
+use ecma355metadata::cli::il::{ExceptionClauseKind, Instruction, InstructionReader};
+use ecma355metadata::cli::signatures::LocalVarSignature;
+use ecma355metadata::cli::tables::{self, TableHandle, TableIndex};
+
+use error::Error;
+use types::{AssemblyId, GenericContext, MethodId, Storage, TypeId, TypeSystem};
+
+/// An argument or local variable, stored in a frame's memory.
+#[derive(Copy, Clone, Debug)]
+pub struct Slot {
+    pub ty: TypeId,
+    pub storage: Storage,
+    pub size: u32,
+    pub offset: u32,
+}
+
+/// What an exception handling clause does when an exception is thrown in its protected region.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClauseKind {
+    /// Catches exceptions of a type and the types derived from it.
+    Catch(TypeId),
+
+    /// Catches exceptions the filter starting at an instruction index accepts.
+    Filter(usize),
+
+    Finally,
+    Fault,
+}
+
+/// An exception handling clause, with its regions as ranges of instruction indices.
+#[derive(Copy, Clone, Debug)]
+pub struct Clause {
+    pub kind: ClauseKind,
+    pub try_start: usize,
+    pub try_end: usize,
+    pub handler_start: usize,
+    pub handler_end: usize,
+}
+
+impl Clause {
+    pub fn protects(&self, index: usize) -> bool {
+        index >= self.try_start && index < self.try_end
+    }
+
+    /// Returns `true` if the instruction is in the handler, or in the filter of a filter clause.
+    pub fn handles(&self, index: usize) -> bool {
+        let start = match self.kind {
+            ClauseKind::Filter(filter_start) => filter_start,
+            _ => self.handler_start,
+        };
+        index >= start && index < self.handler_end
+    }
+}
+
+/// The decoded IL of a method, with the layout of the memory its frames hold its arguments and locals in.
+pub struct MethodCode {
+    pub assembly: AssemblyId,
+
+    /// The type arguments the generic parameters in the method's IL stand for.
+    pub generics: GenericContext,
+    pub instructions: Vec<Instruction>,
+    pub args: Vec<Slot>,
+    pub locals: Vec<Slot>,
+
+    /// The method's exception handling clauses, with nested clauses before the clauses they are nested in.
+    pub clauses: Vec<Clause>,
+
+    /// The size of a frame's memory, in 8-byte words.
+    pub frame_words: usize,
+
+    /// The offsets of the object references in a frame's memory.
+    pub ref_offsets: Vec<u32>,
+}
+
+impl MethodCode {
+    pub fn load(types: &mut TypeSystem, method: MethodId) -> Result<MethodCode, Error> {
+        let no_body = |types: &TypeSystem| {
+            let method = types.method(method);
+            Error::MissingMethod(format!("{}::{} has no IL body", types.get(method.owner), method))
+        };
+        let definition = types.method(method).definition.ok_or_else(|| no_body(types))?;
+        let assembly = definition.assembly;
+        let (instructions, locals, clauses) = {
+            let image = types.image(assembly);
+            let body = image.method_body(TableHandle::new(definition.row, TableIndex::MethodDef))?
+                .ok_or_else(|| no_body(types))?;
+            let instructions = InstructionReader::new(body.code).collect::<Result<Vec<_>, _>>()?;
+            let locals = match TableHandle::from_token(body.local_var_sig_token) {
+                Some(handle) if handle.table() == TableIndex::StandAloneSig => {
+                    let signature = image.table::<tables::StandAloneSigDecoder>().get(handle.index())?.signature;
+                    LocalVarSignature::read(&mut image.read_blob(signature)?)?.locals
+                }
+                _ => Vec::new(),
+            };
+            (instructions, locals, body.exception_clauses)
+        };
+
+        let mut arg_types = Vec::new();
+        if types.method(method).signature.has_this {
+            let owner = types.method(method).owner;
+            arg_types.push(if types.get(owner).is_value_type() {
+                types.by_ref(owner)?
+            } else {
+                owner
+            });
+        }
+        arg_types.extend(types.method(method).signature.params.iter().cloned());
+        let generics = types.method_context(method);
+        let mut local_types = Vec::with_capacity(locals.len());
+        for local in &locals {
+            let ty = types.resolve_signature_type(assembly, &local.local_type)?;
+            local_types.push(types.substitute(ty, &generics)?);
+        }
+        if arg_types.iter().chain(&local_types).any(|&ty| types.is_open(ty)) {
+            let method = types.method(method);
+            return Err(Error::InvalidProgram(format!(
+                "{}::{} is a method of an open generic type or method",
+                types.get(method.owner),
+                method
+            )));
+        }
+
+        let mut code = MethodCode {
+            assembly,
+            generics,
+            instructions,
+            args: Vec::new(),
+            locals: Vec::new(),
+            clauses: Vec::with_capacity(clauses.len()),
+            frame_words: 0,
+            ref_offsets: Vec::new(),
+        };
+        let mut offset = 0;
+        code.args = code.layout(types, &arg_types, &mut offset)?;
+        code.locals = code.layout(types, &local_types, &mut offset)?;
+        code.frame_words = offset as usize / 8;
+
+        for clause in clauses {
+            let kind = match clause.kind {
+                ExceptionClauseKind::Catch(handle) => {
+                    ClauseKind::Catch(types.resolve_type_token(assembly, handle.token(), &code.generics)?)
+                }
+                ExceptionClauseKind::Filter(offset) => ClauseKind::Filter(code.index_of(offset)?),
+                ExceptionClauseKind::Finally => ClauseKind::Finally,
+                ExceptionClauseKind::Fault => ClauseKind::Fault,
+            };
+            code.clauses.push(Clause {
+                kind,
+                try_start: code.index_of(clause.try_offset)?,
+                try_end: code.index_of(clause.try_offset + clause.try_length)?,
+                handler_start: code.index_of(clause.handler_offset)?,
+                handler_end: code.index_of(clause.handler_offset + clause.handler_length)?,
+            });
+        }
+        Ok(code)
+    }
+
+    /// Assigns each variable an 8-byte aligned slot, and records where the object references are.
+    fn layout(&mut self, types: &mut TypeSystem, variables: &[TypeId], offset: &mut u32) -> Result<Vec<Slot>, Error> {
+        let mut slots = Vec::with_capacity(variables.len());
+        for &ty in variables {
+            types.prepare(ty)?;
+            let variable = types.get(ty);
+            let slot = Slot {
+                ty,
+                storage: variable.storage(),
+                size: variable.value_size(),
+                offset: *offset,
+            };
+            self.ref_offsets.extend(variable.value_ref_offsets().iter().map(|x| x + slot.offset));
+            *offset += (slot.size + 7) & !7;
+            slots.push(slot);
+        }
+        Ok(slots)
+    }
+
+    /// Gets the index of the instruction at an IL offset, or the number of instructions for the offset of the end of
+    /// the code, for the end of a region.
+    fn index_of(&self, offset: u32) -> Result<usize, Error> {
+        match self.instruction_at(offset as i64) {
+            Some(index) => Ok(index),
+            None if self.instructions.last().map(|last| last.next_offset()) == Some(offset) => {
+                Ok(self.instructions.len())
+            }
+            None => Err(Error::InvalidProgram(format!("exception handling clause at offset IL_{:04x}", offset))),
+        }
+    }
+
+    /// Gets the index of the instruction at an IL offset.
+    pub fn instruction_at(&self, offset: i64) -> Option<usize> {
+        self.instructions.binary_search_by_key(&offset, |instruction| instruction.offset as i64).ok()
+    }
+}
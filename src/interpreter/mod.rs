@@ -0,0 +1,1013 @@
+use std::mem;
+use std::rc::Rc;
+use std::sync::atomic::{self, Ordering};
+
+use ecma355metadata::cli::il::{Instruction, Opcode, Operand};
+
+use error::{Error, ExceptionKind};
+use gc::ObjectRef;
+use internal_calls::{internal_call_name, CallContext};
+use types::{FieldId, MethodId, Storage, TypeId, TypeKind, TypeSystem};
+use vm::Vm;
+
+mod arrays;
+mod delegates;
+mod exceptions;
+#[cfg(feature = "jit")]
+mod jit;
+mod method_code;
+mod ops;
+mod pinvoke;
+mod reflection;
+mod statics;
+mod threading;
+mod value;
+mod value_types;
+
+pub use self::exceptions::Thrown;
+#[cfg(feature = "jit")]
+pub use self::jit::{Jit, TierThresholds};
+pub use self::method_code::{ClauseKind, MethodCode, Slot};
+pub use self::pinvoke::PInvokeTarget;
+pub use self::reflection::Member;
+pub(crate) use self::reflection::handle_id;
+pub use self::statics::TypeInit;
+pub use self::value::{Pointer, Value};
+
+use self::exceptions::ActiveHandler;
+
+use self::ops::{Comparison, Conversion};
+
+/// The deepest the interpreter lets calls nest before it gives up with a stack overflow.
+const MAX_FRAMES: usize = 10_000;
+
+/// The state of a method being interpreted.
+pub struct Frame {
+    pub method: MethodId,
+    pub code: Rc<MethodCode>,
+
+    /// The index of the next instruction to execute.
+    pub ip: usize,
+
+    /// The method's arguments and local variables, laid out as `code` describes.
+    pub memory: Box<[u64]>,
+    pub stack: Vec<Value>,
+
+    /// The object or value being initialized, when the method is a constructor called by `newobj`. It is pushed on
+    /// the caller's stack when the constructor returns.
+    pub constructing: Option<Value>,
+
+    /// The handlers the method is executing, innermost last.
+    pub handlers: Vec<ActiveHandler>,
+
+    /// The type a `constrained.` prefix names, for the `callvirt` that follows it.
+    pub constrained: Option<TypeId>,
+
+    /// Whether a `volatile.` prefix applies to the next instruction.
+    pub volatile: bool,
+
+    /// The native code to continue the method with from `ip`, instead of interpreting it.
+    #[cfg(feature = "jit")]
+    pub compiled: Option<Rc<jit::Compiled>>,
+}
+
+impl Frame {
+    fn slot_address(&mut self, slot: Slot) -> *mut u8 {
+        unsafe { (self.memory.as_mut_ptr() as *mut u8).add(slot.offset as usize) }
+    }
+
+    fn load(&mut self, slot: Slot) -> Value {
+        let address = self.slot_address(slot);
+        unsafe { Value::load(address, slot.storage, slot.ty, slot.size) }
+    }
+
+    fn store(&mut self, slot: Slot, value: &Value) {
+        let address = self.slot_address(slot);
+        unsafe { value.store(address, slot.storage) }
+    }
+
+    /// Calls `visit` with the location of each object reference the frame holds.
+    pub(crate) fn visit_roots<F: FnMut(*mut u8)>(&mut self, types: &TypeSystem, mut visit: F) {
+        let memory = self.memory.as_mut_ptr() as *mut u8;
+        for &offset in &self.code.ref_offsets {
+            visit(unsafe { memory.add(offset as usize) });
+        }
+        for value in self.stack.iter_mut().chain(self.constructing.as_mut()) {
+            let struct_refs: &[u32] = match *value {
+                Value::Struct(ty, _) => &types.get(ty).ref_offsets,
+                _ => &[],
+            };
+            value.visit_refs(struct_refs, &mut visit);
+        }
+        self.visit_handler_roots(visit);
+    }
+}
+
+/// The method a virtual call site last called `method` on an object of type `ty` with. Later calls from the site to
+/// the same method on objects of the same type call it again without looking it up.
+pub struct CachedDispatch {
+    pub method: MethodId,
+    pub ty: TypeId,
+    pub target: MethodId,
+}
+
+/// What the interpreter does after executing an instruction.
+pub(crate) enum Flow {
+    Continue,
+
+    /// The frame `run` was called for returned, or the filter it was called for ended.
+    Return(Option<Value>),
+
+    /// The instruction threw an exception.
+    Throw(Thrown),
+}
+
+impl Vm {
+    /// Calls a method with its arguments (including `this`), and gets what it returns.
+    pub fn invoke(&mut self, method: MethodId, args: Vec<Value>) -> Result<Option<Value>, Error> {
+        let base = self.frames.len();
+        let result = self.push_frame(method, args, None).and_then(|()| self.run(base));
+        self.frames.truncate(base);
+        result
+    }
+
+    /// Interprets instructions until the frame at index `base` returns.
+    fn run(&mut self, base: usize) -> Result<Option<Value>, Error> {
+        loop {
+            let code = self.frame().code.clone();
+            let ip = self.frame().ip;
+            if ip == 0 {
+                self.safepoint();
+            }
+            #[cfg(feature = "jit")]
+            let compiled = self.run_compiled(base);
+            #[cfg(not(feature = "jit"))]
+            let compiled = None;
+
+            // Throw exceptions until one is caught or leaves the frames this was called for. Another exception can be
+            // thrown while creating one, or by a handler that runs in the meantime.
+            let mut result = match compiled {
+                Some(result) => result,
+                None => {
+                    let instruction = code.instructions.get(ip).ok_or_else(|| {
+                        Error::InvalidProgram(format!("{} runs off the end of its code", self.current()))
+                    })?;
+                    self.frame().ip += 1;
+
+                    // A volatile access is fenced on both sides, so it isn't reordered with other memory accesses
+                    let volatile = match instruction.opcode {
+                        Opcode::Volatile | Opcode::Unaligned => false,
+                        _ => mem::replace(&mut self.frame().volatile, false),
+                    };
+                    let result = self.step(base, &code, instruction);
+                    if volatile {
+                        atomic::fence(Ordering::SeqCst);
+                    }
+                    result
+                }
+            };
+            loop {
+                let thrown = match result {
+                    Ok(Flow::Continue) => break,
+                    Ok(Flow::Return(value)) => return Ok(value),
+                    Ok(Flow::Throw(thrown)) => thrown,
+                    Err(error) if self.frames.len() == base => return Err(error),
+                    Err(Error::Exception(kind)) => match self.raise(kind) {
+                        Ok(thrown) => thrown,
+                        Err(error) => {
+                            result = Err(error);
+                            continue;
+                        }
+                    },
+                    Err(Error::UnhandledException(_)) if self.unhandled.is_some() => self.unhandled.take().unwrap(),
+                    Err(error) => return Err(error),
+                };
+                result = self.throw(base, thrown);
+            }
+        }
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    /// Gets the name of the method being interpreted, for error messages.
+    fn current(&self) -> String {
+        let method = self.types.method(self.frames.last().unwrap().method);
+        format!("{}::{}", self.types.get(method.owner), method)
+    }
+
+    fn push(&mut self, value: Value) {
+        self.frame().stack.push(value);
+    }
+
+    fn pop(&mut self) -> Result<Value, Error> {
+        match self.frame().stack.pop() {
+            Some(value) => Ok(value),
+            None => Err(Error::InvalidProgram(format!("{} pops from an empty stack", self.current()))),
+        }
+    }
+
+    /// Pops the arguments for a call, in the order they were pushed.
+    fn pop_args(&mut self, count: usize) -> Result<Vec<Value>, Error> {
+        let len = self.frame().stack.len();
+        if len < count {
+            return Err(Error::InvalidProgram(format!("{} pops from an empty stack", self.current())));
+        }
+        Ok(self.frame().stack.split_off(len - count))
+    }
+
+    fn push_frame(&mut self, method: MethodId, args: Vec<Value>, constructing: Option<Value>) -> Result<(), Error> {
+        if self.frames.len() >= MAX_FRAMES {
+            return Err(Error::UnhandledException("System.StackOverflowException".into()));
+        }
+        let code = self.method_code(method)?;
+        if args.len() != code.args.len() {
+            return Err(Error::InvalidProgram(format!(
+                "{} takes {} arguments, not {}",
+                self.types.method(method),
+                code.args.len(),
+                args.len()
+            )));
+        }
+        let mut frame = Frame {
+            method,
+            code: code.clone(),
+            ip: 0,
+            memory: vec![0; code.frame_words].into_boxed_slice(),
+            stack: Vec::new(),
+            constructing,
+            handlers: Vec::new(),
+            constrained: None,
+            volatile: false,
+            #[cfg(feature = "jit")]
+            compiled: self.compiled_code(method, &code),
+        };
+        for (&slot, arg) in code.args.iter().zip(&args) {
+            frame.store(slot, arg);
+        }
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    fn branch(&mut self, target: i64) -> Result<(), Error> {
+        let code = self.frame().code.clone();
+        let index = code.instruction_at(target)
+            .ok_or_else(|| Error::InvalidProgram(format!("{} branches to IL_{:04x}", self.current(), target)))?;
+        let backward = index < self.frame().ip;
+        self.frame().ip = index;
+        if backward {
+            self.safepoint();
+            #[cfg(feature = "jit")]
+            self.count_back_edge();
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, base: usize, code: &MethodCode, instruction: &Instruction) -> Result<Flow, Error> {
+        let opcode = instruction.opcode;
+        match opcode {
+            Opcode::Nop | Opcode::Break => {}
+            Opcode::Volatile => {
+                atomic::fence(Ordering::SeqCst);
+                self.frame().volatile = true;
+            }
+            // The other prefixes are only hints to the interpreter
+            Opcode::Unaligned | Opcode::Tail | Opcode::Readonly | Opcode::No => {}
+
+            Opcode::Ldarg0 | Opcode::Ldarg1 | Opcode::Ldarg2 | Opcode::Ldarg3 | Opcode::LdargS | Opcode::Ldarg => {
+                let slot = self.variable(&code.args, instruction, Opcode::Ldarg0)?;
+                let value = self.frame().load(slot);
+                self.push(value);
+            }
+            Opcode::Ldloc0 | Opcode::Ldloc1 | Opcode::Ldloc2 | Opcode::Ldloc3 | Opcode::LdlocS | Opcode::Ldloc => {
+                let slot = self.variable(&code.locals, instruction, Opcode::Ldloc0)?;
+                let value = self.frame().load(slot);
+                self.push(value);
+            }
+            Opcode::StargS | Opcode::Starg => {
+                let slot = self.variable(&code.args, instruction, Opcode::Starg)?;
+                let value = self.pop()?;
+                self.frame().store(slot, &value);
+            }
+            Opcode::Stloc0 | Opcode::Stloc1 | Opcode::Stloc2 | Opcode::Stloc3 | Opcode::StlocS | Opcode::Stloc => {
+                let slot = self.variable(&code.locals, instruction, Opcode::Stloc0)?;
+                let value = self.pop()?;
+                self.frame().store(slot, &value);
+            }
+            Opcode::LdargaS | Opcode::Ldarga => {
+                let slot = self.variable(&code.args, instruction, Opcode::Ldarga)?;
+                let address = self.frame().slot_address(slot);
+                self.push(Value::ByRef(Pointer::absolute(address)));
+            }
+            Opcode::LdlocaS | Opcode::Ldloca => {
+                let slot = self.variable(&code.locals, instruction, Opcode::Ldloca)?;
+                let address = self.frame().slot_address(slot);
+                self.push(Value::ByRef(Pointer::absolute(address)));
+            }
+
+            Opcode::Ldnull => self.push(Value::null()),
+            Opcode::LdcI4M1 => self.push(Value::I32(-1)),
+            Opcode::LdcI40 | Opcode::LdcI41 | Opcode::LdcI42 | Opcode::LdcI43 | Opcode::LdcI44 | Opcode::LdcI45
+            | Opcode::LdcI46 | Opcode::LdcI47 | Opcode::LdcI48 => {
+                self.push(Value::I32((opcode.value() - Opcode::LdcI40.value()) as i32))
+            }
+            Opcode::LdcI4S | Opcode::LdcI4 | Opcode::LdcI8 | Opcode::LdcR4 | Opcode::LdcR8 => {
+                self.push(match instruction.operand {
+                    Operand::Int8(x) => Value::I32(x as i32),
+                    Operand::Int32(x) => Value::I32(x),
+                    Operand::Int64(x) => Value::I64(x),
+                    Operand::Float32(x) => Value::F(x as f64),
+                    Operand::Float64(x) => Value::F(x),
+                    _ => return Err(self.bad_operand(instruction)),
+                })
+            }
+            Opcode::Ldstr => {
+                let token = self.token(instruction)?;
+                let chars = self.types
+                    .image(code.assembly)
+                    .user_string_heap()
+                    .get((token & 0x00FF_FFFF) as usize)
+                    .ok_or_else(|| Error::InvalidProgram(format!("invalid string token 0x{:08X}", token)))?;
+                let string = self.intern(&chars)?;
+                self.push(Value::Ref(string));
+            }
+            Opcode::Ldtoken => {
+                let token = self.token(instruction)?;
+                let member = if self.types.is_field_token(code.assembly, token)? {
+                    Member::Field(self.types.resolve_field_token(code.assembly, token, &code.generics)?)
+                } else if self.types.is_type_token(token) {
+                    Member::Type(self.types.resolve_type_token(code.assembly, token, &code.generics)?)
+                } else {
+                    Member::Method(self.types.resolve_method_token(code.assembly, token, &code.generics)?)
+                };
+                let handle = self.member_handle(member)?;
+                self.push(handle);
+            }
+            Opcode::Dup => {
+                let value = self.pop()?;
+                self.push(value.clone());
+                self.push(value);
+            }
+            Opcode::Pop => {
+                self.pop()?;
+            }
+
+            Opcode::Constrained => {
+                let ty = self.types.resolve_type_token(code.assembly, self.token(instruction)?, &code.generics)?;
+                self.frame().constrained = Some(ty);
+            }
+            Opcode::Call | Opcode::Callvirt => {
+                let method = self.types.resolve_method_token(code.assembly, self.token(instruction)?, &code.generics)?;
+                match self.frame().constrained.take() {
+                    Some(ty) if opcode == Opcode::Callvirt => self.constrained_call(ty, method)?,
+                    _ => self.call(method, opcode == Opcode::Callvirt)?,
+                }
+            }
+            Opcode::Calli => {
+                let token = self.token(instruction)?;
+                self.indirect_call(code.assembly, token, &code.generics)?;
+            }
+            Opcode::Ldftn | Opcode::Ldvirtftn => {
+                let token = self.token(instruction)?;
+                let mut method = self.types.resolve_method_token(code.assembly, token, &code.generics)?;
+                if opcode == Opcode::Ldvirtftn {
+                    match self.pop()? {
+                        Value::Ref(object) if object.is_null() => return Err(ExceptionKind::NullReference.into()),
+                        Value::Ref(object) => method = self.virtual_target(method, object)?,
+                        _ => return Err(self.bad_operand(instruction)),
+                    }
+                }
+                let pointer = self.method_pointer(method);
+                self.push(pointer);
+            }
+            Opcode::Newobj => {
+                let token = self.token(instruction)?;
+                let constructor = self.types.resolve_method_token(code.assembly, token, &code.generics)?;
+                self.new_object_with(constructor)?;
+            }
+            Opcode::Ret => return self.ret(base),
+
+            Opcode::Br | Opcode::BrS => self.branch(self.target(instruction)?)?,
+            Opcode::Leave | Opcode::LeaveS => {
+                let target = self.target(instruction)?;
+                let target = code.instruction_at(target)
+                    .ok_or_else(|| Error::InvalidProgram(format!("{} leaves to IL_{:04x}", self.current(), target)))?;
+                self.leave(target);
+            }
+            Opcode::Endfinally => return self.end_finally(base),
+            Opcode::Endfilter => {
+                let result = self.pop()?;
+                return Ok(Flow::Return(Some(result)));
+            }
+            Opcode::Throw => {
+                let exception = self.pop()?.as_ref().ok_or_else(|| self.bad_operand(instruction))?;
+                if exception.is_null() {
+                    return Err(ExceptionKind::NullReference.into());
+                }
+                return Ok(Flow::Throw(self.thrown_here(exception)));
+            }
+            Opcode::Rethrow => return Ok(Flow::Throw(self.caught()?)),
+            Opcode::Brtrue | Opcode::BrtrueS | Opcode::Brfalse | Opcode::BrfalseS => {
+                let value = self.pop()?;
+                let truth = match value {
+                    Value::F(_) | Value::Struct(..) => return Err(self.bad_operand(instruction)),
+                    Value::Ref(object) => !object.is_null(),
+                    value => value.as_i64() != Some(0),
+                };
+                if truth == (opcode == Opcode::Brtrue || opcode == Opcode::BrtrueS) {
+                    self.branch(self.target(instruction)?)?;
+                }
+            }
+            Opcode::Switch => {
+                let index = self.pop()?.as_i64().ok_or_else(|| self.bad_operand(instruction))? as u32;
+                if let Some(&target) = instruction.branch_targets().get(index as usize) {
+                    self.branch(target)?;
+                }
+            }
+
+            Opcode::LdindI1 | Opcode::LdindU1 | Opcode::LdindI2 | Opcode::LdindU2 | Opcode::LdindI4
+            | Opcode::LdindU4 | Opcode::LdindI8 | Opcode::LdindI | Opcode::LdindR4 | Opcode::LdindR8
+            | Opcode::LdindRef => {
+                let address = self.pop_address()?;
+                let value = unsafe { Value::load(address, indirect_storage(opcode), TypeId(0), 0) };
+                self.push(value);
+            }
+            Opcode::StindI1 | Opcode::StindI2 | Opcode::StindI4 | Opcode::StindI8 | Opcode::StindI
+            | Opcode::StindR4 | Opcode::StindR8 | Opcode::StindRef => {
+                let value = self.pop()?;
+                let address = self.pop_address()?;
+                self.store_value(address, &value, indirect_storage(opcode), 8);
+            }
+
+            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div | Opcode::DivUn | Opcode::Rem | Opcode::RemUn
+            | Opcode::And | Opcode::Or | Opcode::Xor | Opcode::AddOvf | Opcode::AddOvfUn | Opcode::SubOvf
+            | Opcode::SubOvfUn | Opcode::MulOvf | Opcode::MulOvfUn => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let result = ops::binary(opcode, &a, &b)?;
+                self.push(result);
+            }
+            Opcode::Shl | Opcode::Shr | Opcode::ShrUn => {
+                let amount = self.pop()?;
+                let value = self.pop()?;
+                let result = ops::shift(opcode, &value, &amount)?;
+                self.push(result);
+            }
+            Opcode::Neg | Opcode::Not => {
+                let value = self.pop()?;
+                let result = ops::unary(opcode, &value)?;
+                self.push(result);
+            }
+            Opcode::Ceq | Opcode::Cgt | Opcode::CgtUn | Opcode::Clt | Opcode::CltUn => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let result = ops::compare(Comparison::for_opcode(opcode).unwrap(), &a, &b)?;
+                self.push(Value::I32(result as i32));
+            }
+            Opcode::Beq | Opcode::BeqS | Opcode::BneUn | Opcode::BneUnS | Opcode::Bgt | Opcode::BgtS
+            | Opcode::Bge | Opcode::BgeS | Opcode::Blt | Opcode::BltS | Opcode::Ble | Opcode::BleS
+            | Opcode::BgtUn | Opcode::BgtUnS | Opcode::BgeUn | Opcode::BgeUnS | Opcode::BltUn | Opcode::BltUnS
+            | Opcode::BleUn | Opcode::BleUnS => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                if ops::compare(Comparison::for_opcode(opcode).unwrap(), &a, &b)? {
+                    self.branch(self.target(instruction)?)?;
+                }
+            }
+
+            Opcode::Ldfld | Opcode::Ldflda | Opcode::Stfld => {
+                let field = self.types.resolve_field_token(code.assembly, self.token(instruction)?, &code.generics)?;
+                self.instance_field(opcode, field)?;
+            }
+            Opcode::Ldsfld | Opcode::Ldsflda | Opcode::Stsfld => {
+                let field = self.types.resolve_field_token(code.assembly, self.token(instruction)?, &code.generics)?;
+                let address = self.static_field_address(field)?;
+                let (ty, storage, size) = self.value_layout(self.types.field(field).field_type);
+                match opcode {
+                    Opcode::Ldsfld => {
+                        let value = unsafe { Value::load(address, storage, ty, size) };
+                        self.push(value);
+                    }
+                    Opcode::Ldsflda => self.push(Value::ByRef(Pointer::absolute(address))),
+                    _ => {
+                        let value = self.pop()?;
+                        self.store_value(address, &value, storage, size);
+                    }
+                }
+            }
+
+            Opcode::Castclass | Opcode::Isinst => {
+                let ty = self.types.resolve_type_token(code.assembly, self.token(instruction)?, &code.generics)?;
+                self.cast(ty, opcode == Opcode::Isinst)?;
+            }
+            Opcode::Box | Opcode::Unbox | Opcode::UnboxAny | Opcode::Initobj | Opcode::Cpobj | Opcode::Ldobj
+            | Opcode::Stobj | Opcode::Sizeof => {
+                let ty = self.types.resolve_type_token(code.assembly, self.token(instruction)?, &code.generics)?;
+                self.value_instruction(opcode, ty)?;
+            }
+            Opcode::Newarr | Opcode::Ldlen | Opcode::Ldelema | Opcode::LdelemI1 | Opcode::LdelemU1
+            | Opcode::LdelemI2 | Opcode::LdelemU2 | Opcode::LdelemI4 | Opcode::LdelemU4 | Opcode::LdelemI8
+            | Opcode::LdelemI | Opcode::LdelemR4 | Opcode::LdelemR8 | Opcode::LdelemRef | Opcode::Ldelem
+            | Opcode::StelemI | Opcode::StelemI1 | Opcode::StelemI2 | Opcode::StelemI4 | Opcode::StelemI8
+            | Opcode::StelemR4 | Opcode::StelemR8 | Opcode::StelemRef | Opcode::Stelem => {
+                let ty = match instruction.operand {
+                    Operand::Token(token) => Some(self.types.resolve_type_token(code.assembly, token, &code.generics)?),
+                    _ => None,
+                };
+                self.array_instruction(opcode, ty)?;
+            }
+
+            _ => {
+                if let Some((target, overflow, unsigned)) = Conversion::for_opcode(opcode) {
+                    let value = self.pop()?;
+                    let result = ops::convert(&value, target, overflow, unsigned)?;
+                    self.push(result);
+                } else {
+                    return Err(Error::InvalidProgram(format!("{} is not supported", opcode)));
+                }
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Gets the argument or local variable an instruction refers to, either by its operand or, for the short
+    /// forms like `ldarg.0`, by its opcode.
+    fn variable(&self, slots: &[Slot], instruction: &Instruction, first: Opcode) -> Result<Slot, Error> {
+        let index = match instruction.operand {
+            Operand::Variable(index) => index as usize,
+            _ => (instruction.opcode.value() - first.value()) as usize,
+        };
+        slots.get(index).cloned().ok_or_else(|| self.bad_operand(instruction))
+    }
+
+    fn token(&self, instruction: &Instruction) -> Result<u32, Error> {
+        match instruction.operand {
+            Operand::Token(token) => Ok(token),
+            _ => Err(self.bad_operand(instruction)),
+        }
+    }
+
+    fn target(&self, instruction: &Instruction) -> Result<i64, Error> {
+        match instruction.operand {
+            Operand::BranchTarget(target) => Ok(target),
+            _ => Err(self.bad_operand(instruction)),
+        }
+    }
+
+    fn bad_operand(&self, instruction: &Instruction) -> Error {
+        Error::InvalidProgram(format!("{} has an invalid operand at IL_{:04x}", self.current(), instruction.offset))
+    }
+
+    /// Pops a managed or unmanaged pointer, and gets the address it points to.
+    fn pop_address(&mut self) -> Result<*mut u8, Error> {
+        let address = match self.pop()? {
+            Value::ByRef(pointer) => pointer.address(),
+            Value::NativeInt(address) => address as *mut u8,
+            _ => return Err(Error::InvalidProgram(format!("{} dereferences a non-pointer", self.current()))),
+        };
+        if address.is_null() {
+            return Err(ExceptionKind::NullReference.into());
+        }
+        Ok(address)
+    }
+
+    /// Gets how a value of a type is stored.
+    fn value_layout(&self, ty: TypeId) -> (TypeId, Storage, u32) {
+        let runtime_type = self.types.get(ty);
+        (ty, runtime_type.storage(), runtime_type.value_size())
+    }
+
+    /// Executes `castclass`, or `isinst` if `test` is set. A boxed value is an instance of `Nullable<T>` if it is a
+    /// boxed `T`.
+    fn cast(&mut self, ty: TypeId, test: bool) -> Result<(), Error> {
+        let ty = self.types.nullable_underlying(ty).unwrap_or(ty);
+        let object = match self.pop()? {
+            Value::Ref(object) => object,
+            _ => return Err(Error::InvalidProgram(format!("{} casts a non-object", self.current()))),
+        };
+        if object.is_null() || self.types.is_assignable_to(self.object_type(object), ty) {
+            self.push(Value::Ref(object));
+        } else if test {
+            self.push(Value::null());
+        } else {
+            return Err(ExceptionKind::InvalidCast.into());
+        }
+        Ok(())
+    }
+
+    /// Finds the method a virtual call to a method on an object calls, which the type system finds in the object
+    /// type's vtables. The instruction making the call caches it, so calls from it to the same method on objects of
+    /// the same type don't look it up again.
+    fn virtual_target(&mut self, method: MethodId, object: ObjectRef) -> Result<MethodId, Error> {
+        let ty = self.object_type(object);
+        let call_site = {
+            let frame = self.frames.last().unwrap();
+            (frame.method, frame.ip)
+        };
+        match self.dispatch_cache.get(&call_site) {
+            Some(cached) if cached.method == method && cached.ty == ty => return Ok(cached.target),
+            _ => {}
+        }
+        let target = self.types.find_implementation(ty, method)?;
+        self.dispatch_cache.insert(call_site, CachedDispatch { method, ty, target });
+        Ok(target)
+    }
+
+    fn call(&mut self, method: MethodId, is_virtual: bool) -> Result<(), Error> {
+        let (arg_count, has_this) = {
+            let method = self.types.method(method);
+            (method.arg_count(), method.signature.has_this)
+        };
+        let mut target = method;
+        if is_virtual && has_this {
+            let this = {
+                let stack = &self.frames.last().unwrap().stack;
+                if stack.len() < arg_count {
+                    return Err(Error::InvalidProgram(format!("{} pops from an empty stack", self.current())));
+                }
+                stack[stack.len() - arg_count].clone()
+            };
+            match this {
+                Value::Ref(object) if object.is_null() => return Err(ExceptionKind::NullReference.into()),
+                Value::Ref(object) => {
+                    target = self.virtual_target(method, object)?;
+                    // A value type's methods take a pointer to the value, which is the boxed object's data
+                    if self.types.get(self.types.method(target).owner).is_value_type() {
+                        let index = self.frame().stack.len() - arg_count;
+                        self.frame().stack[index] = Value::ByRef(Pointer::into_object(object, 0));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if self.types.method(target).is_abstract() {
+            return Err(Error::InvalidProgram(format!("{} is abstract", self.types.method(target))));
+        }
+        if let TypeKind::Array(..) = self.types.get(self.types.method(target).owner).kind {
+            return self.md_array_method(target);
+        }
+        self.initialize_for_call(target)?;
+        if self.types.method(target).is_internal_call() {
+            return self.internal_call(target);
+        }
+        if self.types.method(target).is_pinvoke() {
+            return self.pinvoke_call(target);
+        }
+        if self.types.method(target).is_runtime_impl() {
+            return self.delegate_call(target);
+        }
+        let args = self.pop_args(arg_count)?;
+        self.push_frame(target, args, None)
+    }
+
+    /// Calls the function an internal call is bound to with the arguments on the stack, binding it the first time.
+    /// Internal calls that aren't bound to anything throw a `MissingMethodException`.
+    fn internal_call(&mut self, method: MethodId) -> Result<(), Error> {
+        let call = match self.bound_calls.get(&method).cloned() {
+            Some(call) => call,
+            None => {
+                let name = internal_call_name(&self.types, method);
+                match self.internal_calls.get(&name) {
+                    Some(call) => {
+                        self.bound_calls.insert(method, call.clone());
+                        call
+                    }
+                    None => {
+                        let message = format!("Method not found: '{}'.", name);
+                        let thrown = self.raise_with_message(ExceptionKind::MissingMethod, &message)?;
+                        return Err(self.throw_from_runtime(thrown));
+                    }
+                }
+            }
+        };
+        let args = self.pop_args(self.types.method(method).arg_count())?;
+        let value = call(&mut CallContext { vm: self }, args)?;
+        if let (Some(value), Some(ty)) = (value, self.types.method(method).signature.ret) {
+            let value = self.coerce(value, ty);
+            self.push(value);
+        }
+        Ok(())
+    }
+
+    /// Executes `newobj`: allocates an object or value, then calls its constructor to initialize it.
+    fn new_object_with(&mut self, constructor: MethodId) -> Result<(), Error> {
+        let owner = self.types.method(constructor).owner;
+        let param_count = self.types.method(constructor).signature.params.len();
+        self.types.prepare(owner)?;
+        self.initialize_for_call(constructor)?;
+        let (this, constructing) = match self.types.get(owner).kind {
+            TypeKind::Class => {
+                // The constructor's arguments are still on the stack, so they stay alive while the object is allocated
+                let object = self.new_object(owner)?;
+                (Value::Ref(object), Value::Ref(object))
+            }
+            TypeKind::Array(..) => return self.new_md_array_with(constructor),
+            TypeKind::ValueType | TypeKind::Primitive(_) | TypeKind::Enum(_) => {
+                let mut value = Value::Struct(owner, vec![0; self.types.get(owner).value_size() as usize]);
+                let address = match value {
+                    Value::Struct(_, ref mut bytes) => bytes.as_mut_ptr(),
+                    _ => unreachable!(),
+                };
+                (Value::ByRef(Pointer::absolute(address)), value)
+            }
+            _ => {
+                return Err(Error::InvalidProgram(format!("{} can't be created with newobj", self.types.get(owner))));
+            }
+        };
+        let mut args = self.pop_args(param_count)?;
+        if self.types.method(constructor).is_runtime_impl() {
+            self.construct_delegate(&this, &args)?;
+            self.push(constructing);
+            return Ok(());
+        }
+        args.insert(0, this);
+        self.push_frame(constructor, args, Some(constructing))
+    }
+
+    fn ret(&mut self, base: usize) -> Result<Flow, Error> {
+        let method = self.frame().method;
+        let value = match self.types.method(method).signature.ret {
+            Some(ty) => {
+                let value = self.pop()?;
+                Some(self.coerce(value, ty))
+            }
+            None => None,
+        };
+        let frame = self.frames.pop().unwrap();
+        let value = match frame.constructing {
+            Some(Value::Struct(ty, bytes)) => {
+                let (ty, storage, size) = self.value_layout(ty);
+                Some(unsafe { Value::load(bytes.as_ptr(), storage, ty, size) })
+            }
+            Some(constructing) => Some(constructing),
+            None => value,
+        };
+        if self.frames.len() == base {
+            return Ok(Flow::Return(value));
+        }
+        if let Some(value) = value {
+            self.push(value);
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Converts a value on the stack to the representation of a type, truncating small integers.
+    fn coerce(&self, value: Value, ty: TypeId) -> Value {
+        let (ty, storage, size) = self.value_layout(ty);
+        match storage {
+            Storage::I1 | Storage::U1 | Storage::I2 | Storage::U2 | Storage::R4 => {
+                let mut buffer = [0u8; 8];
+                unsafe {
+                    value.store(buffer.as_mut_ptr(), storage);
+                    Value::load(buffer.as_ptr(), storage, ty, size)
+                }
+            }
+            _ => value,
+        }
+    }
+
+    fn instance_field(&mut self, opcode: Opcode, field: FieldId) -> Result<(), Error> {
+        let (offset, field_type) = {
+            let field = self.types.field(field);
+            (field.offset as usize, field.field_type)
+        };
+        if self.types.field(field).is_static() {
+            return Err(Error::InvalidProgram(format!("{} is static", self.types.field(field).name)));
+        }
+        let (ty, storage, size) = self.value_layout(field_type);
+        let value = if opcode == Opcode::Stfld { Some(self.pop()?) } else { None };
+        let receiver = self.pop()?;
+        let pointer = match receiver {
+            Value::Ref(object) if !object.is_null() => Pointer::into_object(object, offset),
+            Value::ByRef(pointer) if !pointer.is_null() => pointer.add(offset as isize),
+            Value::NativeInt(address) if address != 0 => Pointer::absolute((address as usize + offset) as *mut u8),
+            Value::Struct(_, ref bytes) if opcode == Opcode::Ldfld => {
+                let value = unsafe { Value::load(bytes.as_ptr().add(offset), storage, ty, size) };
+                self.push(value);
+                return Ok(());
+            }
+            Value::Ref(_) | Value::ByRef(_) | Value::NativeInt(_) => return Err(ExceptionKind::NullReference.into()),
+            _ => return Err(Error::InvalidProgram(format!("{} accesses a field of a non-object", self.current()))),
+        };
+        match opcode {
+            Opcode::Ldfld => {
+                let value = unsafe { Value::load(pointer.address(), storage, ty, size) };
+                self.push(value);
+            }
+            Opcode::Ldflda => self.push(Value::ByRef(pointer)),
+            _ => self.store_value(pointer.address(), &value.unwrap(), storage, size),
+        }
+        Ok(())
+    }
+
+    /// Stores a value to a location that may be in the heap, recording the store with the write barrier if it can
+    /// hold object references.
+    fn store_value(&mut self, address: *mut u8, value: &Value, storage: Storage, size: u32) {
+        unsafe { value.store(address, storage) };
+        if let Storage::Ref | Storage::ByRef | Storage::Struct = storage {
+            self.heap.write_barrier(address, size as usize);
+        }
+    }
+
+    fn static_field_address(&mut self, field: FieldId) -> Result<*mut u8, Error> {
+        let (owner, offset) = {
+            let field = self.types.field(field);
+            if !field.is_static() || field.is_literal() {
+                return Err(Error::InvalidProgram(format!("{} has no static storage", field.name)));
+            }
+            (field.owner, field.offset as usize)
+        };
+        self.initialize_type(owner)?;
+        if self.is_thread_static(field)? {
+            return Ok(unsafe { self.thread_static_storage(owner)?.add(offset) });
+        }
+        Ok(unsafe { self.static_storage(owner)?.add(offset) })
+    }
+}
+
+/// Gets the storage `ldind` and `stind` instructions read or write.
+fn indirect_storage(opcode: Opcode) -> Storage {
+    match opcode {
+        Opcode::LdindI1 | Opcode::StindI1 => Storage::I1,
+        Opcode::LdindU1 => Storage::U1,
+        Opcode::LdindI2 | Opcode::StindI2 => Storage::I2,
+        Opcode::LdindU2 => Storage::U2,
+        Opcode::LdindI4 | Opcode::StindI4 => Storage::I4,
+        Opcode::LdindU4 => Storage::U4,
+        Opcode::LdindI8 | Opcode::StindI8 => Storage::I8,
+        Opcode::LdindR4 | Opcode::StindR4 => Storage::R4,
+        Opcode::LdindR8 | Opcode::StindR8 => Storage::R8,
+        Opcode::LdindRef | Opcode::StindRef => Storage::Ref,
+        _ => Storage::NativeInt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecma355metadata::cli::il::Opcode;
+
+    use error::Error;
+    use gc::GcMode;
+    use runtime::RuntimeBuilder;
+    use test_assembly::*;
+
+    #[test]
+    pub fn arithmetic_branches_and_calls() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).op(Opcode::Ldarg1).op(Opcode::Add).op(Opcode::Ret);
+        let add = app.method(STATIC, "Add", &method_sig(false, Ty::I4, &[Ty::I4, Ty::I4]), Some(Body::new(vec![], il)));
+
+        // Add(2, 3 * 4) + the sum of 1 to 10
+        let mut il = Il::new();
+        let (head, end) = (il.label(), il.label());
+        il.ldc_i4(2).ldc_i4(3).ldc_i4(4).op(Opcode::Mul).arg(Opcode::Call, add as i64).op(Opcode::Stloc0);
+        il.op(Opcode::LdcI40).op(Opcode::Stloc1).op(Opcode::LdcI41).op(Opcode::Stloc2);
+        il.mark(head).op(Opcode::Ldloc2).ldc_i4(10).branch(Opcode::BgtS, end);
+        il.op(Opcode::Ldloc1).op(Opcode::Ldloc2).op(Opcode::Add).op(Opcode::Stloc1);
+        il.op(Opcode::Ldloc2).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc2).branch(Opcode::BrS, head);
+        il.mark(end).op(Opcode::Ldloc0).op(Opcode::Ldloc1).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::I4, Ty::I4, Ty::I4], il));
+
+        assert_eq!(Ok(69), run("arithmetic", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn arguments_take_the_representation_of_their_types() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let int32 = app.corlib_type("System", "Int32");
+        app.type_def(PUBLIC, "", "Program", object);
+        let params = [
+            Ty::I1, Ty::U1, Ty::I2, Ty::U2, Ty::Char, Ty::Boolean, Ty::U4, Ty::I8, Ty::U8, Ty::R4, Ty::R8, Ty::I, Ty::U,
+            Ty::ValueType(int32), Ty::String, Ty::Object, Ty::sz_array(Ty::I4), Ty::by_ref(Ty::I4),
+            Ty::Ptr(Box::new(Ty::I4)),
+        ];
+
+        // Adds up the integers, floats and native integers, the number of null references, and what the managed
+        // and unmanaged pointers point to
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0);
+        for i in 1..7 {
+            il.arg(Opcode::LdargS, i).op(Opcode::Add);
+        }
+        il.op(Opcode::ConvI8).arg(Opcode::LdargS, 7).op(Opcode::Add).arg(Opcode::LdargS, 8).op(Opcode::Add);
+        il.op(Opcode::ConvR8).arg(Opcode::LdargS, 9).op(Opcode::Add).arg(Opcode::LdargS, 10).op(Opcode::Add);
+        il.op(Opcode::ConvI4).arg(Opcode::LdargS, 11).op(Opcode::Add).arg(Opcode::LdargS, 12).op(Opcode::Add);
+        il.arg(Opcode::LdargS, 13).op(Opcode::Add).op(Opcode::ConvI4);
+        for i in 14..17 {
+            il.arg(Opcode::LdargS, i).op(Opcode::Ldnull).op(Opcode::Ceq).op(Opcode::Add);
+        }
+        il.arg(Opcode::LdargS, 17).op(Opcode::LdindI4).op(Opcode::Add);
+        il.arg(Opcode::LdargS, 18).op(Opcode::LdindI4).op(Opcode::Add).op(Opcode::Ret);
+        let sum = app.method(STATIC, "Sum", &method_sig(false, Ty::I4, &params), Some(Body::new(vec![], il)));
+
+        let mut il = Il::new();
+        let labels = [il.label(), il.label(), il.label()];
+        il.ldc_i4(1000).op(Opcode::Stloc1);
+        for _ in 0..7 {
+            il.ldc_i4(300);
+        }
+        il.arg(Opcode::LdcI8, 300).arg(Opcode::LdcI8, 300).ldc_r8(0.5).ldc_r8(0.25);
+        il.ldc_i4(300).op(Opcode::ConvI).ldc_i4(300).op(Opcode::ConvU).ldc_i4(7);
+        il.op(Opcode::Ldnull).op(Opcode::Ldnull).op(Opcode::Ldnull).arg(Opcode::LdlocaS, 1).arg(Opcode::LdlocaS, 1);
+        il.arg(Opcode::Call, sum as i64).op(Opcode::Stloc0);
+
+        // Return the sum if it's a multiple of 3
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI43).op(Opcode::Rem).switch(&labels).op(Opcode::LdcI4M1).op(Opcode::Ret);
+        il.mark(labels[0]).op(Opcode::Ldloc0).op(Opcode::Ret);
+        il.mark(labels[1]).op(Opcode::LdcI41).op(Opcode::Ret);
+        il.mark(labels[2]).op(Opcode::LdcI42).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::I4, Ty::I4], il));
+
+        // The small integers and the boolean hold 300 truncated to 44
+        assert_eq!(Ok(1332 + 600 + 600 + 7 + 3 + 2000), run("arguments", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn objects_fields_and_virtual_calls() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+
+        // class Animal { int legs; Animal() { legs = 2; } virtual int Legs() { return legs; } }
+        let animal = app.type_def(PUBLIC, "", "Animal", object);
+        let legs = app.field(0, "legs", Ty::I4);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64);
+        il.op(Opcode::Ldarg0).op(Opcode::LdcI42).arg(Opcode::Stfld, legs as i64).op(Opcode::Ret);
+        let animal_ctor = app.method(CONSTRUCTOR, ".ctor", &method_sig(true, Ty::Void, &[]), Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Ldfld, legs as i64).op(Opcode::Ret);
+        let animal_legs = app.method(VIRTUAL | NEW_SLOT, "Legs", &method_sig(true, Ty::I4, &[]), Some(Body::new(vec![], il)));
+
+        // class Dog : Animal { override int Legs() { return legs * 2; } }
+        app.type_def(PUBLIC, "", "Dog", animal);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, animal_ctor as i64).op(Opcode::Ret);
+        let dog_ctor = app.method(CONSTRUCTOR, ".ctor", &method_sig(true, Ty::Void, &[]), Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Ldfld, legs as i64).op(Opcode::LdcI42).op(Opcode::Mul).op(Opcode::Ret);
+        app.method(VIRTUAL, "Legs", &method_sig(true, Ty::I4, &[]), Some(Body::new(vec![], il)));
+
+        // return new Animal().Legs() * 10 + ((Animal)new Dog()).Legs()
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.arg(Opcode::Newobj, animal_ctor as i64).arg(Opcode::Callvirt, animal_legs as i64).ldc_i4(10).op(Opcode::Mul);
+        il.arg(Opcode::Newobj, dog_ctor as i64).arg(Opcode::Callvirt, animal_legs as i64).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        assert_eq!(Ok(24), run("virtual_calls", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn string_literals_are_interned() {
+        // return ("hello" == "hello" ? 1 : 0) + 10 * ("hello" == "world" ? 1 : 0), with the literals loaded by
+        // different tokens and a collection moving the first between the loads
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+        let hello = app.user_string("hello") as i64;
+        let hello_again = app.user_string("hello") as i64;
+        let world = app.user_string("world") as i64;
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.arg(Opcode::Ldstr, hello).arg(Opcode::Newobj, object_ctor as i64).op(Opcode::Pop);
+        il.arg(Opcode::Ldstr, hello_again).op(Opcode::Ceq);
+        il.arg(Opcode::Ldstr, hello).arg(Opcode::Ldstr, world).op(Opcode::Ceq).ldc_i4(10).op(Opcode::Mul);
+        il.op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        let builder = RuntimeBuilder::new().gc_mode(GcMode::Generational).gc_stress(true);
+        let (_directory, mut runtime) = runtime_with("interning", &corlib(), &app, builder);
+        assert_eq!(Ok(1), runtime.execute("App"));
+    }
+
+    #[test]
+    pub fn faults_are_unhandled_exceptions() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        app.type_def(PUBLIC, "", "Program", object);
+        let value = app.field(0, "value", Ty::I4);
+        let mut il = Il::new();
+        il.op(Opcode::Ldnull).arg(Opcode::Ldfld, value as i64).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        let (_directory, mut null_reference) = runtime("null_reference", &corlib(), &app);
+        let report = concat!(
+            "System.NullReferenceException: Object reference not set to an instance of an object.\n",
+            "   at Program.Main"
+        );
+        assert_eq!(Err(Error::UnhandledException(report.into())), null_reference.run_main("App"));
+
+        let mut il = Il::new();
+        il.op(Opcode::LdcI41).op(Opcode::LdcI40).op(Opcode::Div).op(Opcode::Ret);
+        let app = app_with_main(Body::new(vec![], il));
+        let (_directory, mut divide_by_zero) = runtime("divide_by_zero", &corlib(), &app);
+        let report = "System.DivideByZeroException: Attempted to divide by zero.\n   at Program.Main";
+        assert_eq!(Err(Error::UnhandledException(report.into())), divide_by_zero.run_main("App"));
+    }
+}
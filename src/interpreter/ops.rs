@@ -0,0 +1,394 @@
+use ecma355metadata::cli::il::Opcode;
+
+use error::ExceptionKind;
+use interpreter::value::Value;
+
+/// The width of integer operands, as decided by the types on the evaluation stack (ECMA-335 III.1.5).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Width {
+    I32,
+    I64,
+    Native,
+}
+
+fn int_operand(value: &Value) -> Option<(i64, Width)> {
+    match *value {
+        Value::I32(x) => Some((x as i64, Width::I32)),
+        Value::I64(x) => Some((x, Width::I64)),
+        Value::NativeInt(x) => Some((x as i64, Width::Native)),
+        Value::ByRef(pointer) => Some((pointer.address() as i64, Width::Native)),
+        Value::Ref(object) => Some((object.address() as i64, Width::Native)),
+        _ => None,
+    }
+}
+
+/// Gets two integer operands, where mixing `int32` with `native int` gives `native int`.
+fn int_operands(a: &Value, b: &Value) -> Result<(i64, i64, Width), ExceptionKind> {
+    let (x, a) = int_operand(a).ok_or(ExceptionKind::InvalidProgram)?;
+    let (y, b) = int_operand(b).ok_or(ExceptionKind::InvalidProgram)?;
+    let width = match (a, b) {
+        (Width::I32, Width::I32) => Width::I32,
+        (Width::I64, Width::I64) => Width::I64,
+        (Width::I64, _) | (_, Width::I64) => return Err(ExceptionKind::InvalidProgram),
+        _ => Width::Native,
+    };
+    Ok((x, y, width))
+}
+
+fn int_value(x: i64, width: Width) -> Value {
+    match width {
+        Width::I32 => Value::I32(x as i32),
+        Width::I64 => Value::I64(x),
+        Width::Native => Value::NativeInt(x as isize),
+    }
+}
+
+macro_rules! int_binary {
+    ($name:ident, $signed:ty, $unsigned:ty) => {
+        fn $name(opcode: Opcode, x: $signed, y: $signed) -> Result<$signed, ExceptionKind> {
+            let (ux, uy) = (x as $unsigned, y as $unsigned);
+            let overflow = ExceptionKind::Overflow;
+            if y == 0 {
+                match opcode {
+                    Opcode::Div | Opcode::DivUn | Opcode::Rem | Opcode::RemUn => {
+                        return Err(ExceptionKind::DivideByZero)
+                    }
+                    _ => {}
+                }
+            }
+            Ok(match opcode {
+                Opcode::Add => x.wrapping_add(y),
+                Opcode::Sub => x.wrapping_sub(y),
+                Opcode::Mul => x.wrapping_mul(y),
+                Opcode::Div => x.checked_div(y).ok_or(overflow)?,
+                Opcode::DivUn => (ux / uy) as $signed,
+                Opcode::Rem => x.checked_rem(y).ok_or(overflow)?,
+                Opcode::RemUn => (ux % uy) as $signed,
+                Opcode::And => x & y,
+                Opcode::Or => x | y,
+                Opcode::Xor => x ^ y,
+                Opcode::AddOvf => x.checked_add(y).ok_or(overflow)?,
+                Opcode::AddOvfUn => ux.checked_add(uy).ok_or(overflow)? as $signed,
+                Opcode::SubOvf => x.checked_sub(y).ok_or(overflow)?,
+                Opcode::SubOvfUn => ux.checked_sub(uy).ok_or(overflow)? as $signed,
+                Opcode::MulOvf => x.checked_mul(y).ok_or(overflow)?,
+                Opcode::MulOvfUn => ux.checked_mul(uy).ok_or(overflow)? as $signed,
+                _ => return Err(ExceptionKind::InvalidProgram),
+            })
+        }
+    };
+}
+
+int_binary!(binary_i32, i32, u32);
+int_binary!(binary_i64, i64, u64);
+
+/// Applies an arithmetic or bitwise binary operator.
+pub fn binary(opcode: Opcode, a: &Value, b: &Value) -> Result<Value, ExceptionKind> {
+    match (a, b) {
+        (&Value::F(x), &Value::F(y)) => {
+            return Ok(Value::F(match opcode {
+                Opcode::Add => x + y,
+                Opcode::Sub => x - y,
+                Opcode::Mul => x * y,
+                Opcode::Div => x / y,
+                Opcode::Rem => x % y,
+                _ => return Err(ExceptionKind::InvalidProgram),
+            }))
+        }
+        // Managed pointer arithmetic keeps the object the pointer points into
+        (&Value::ByRef(pointer), &Value::I32(_)) | (&Value::ByRef(pointer), &Value::NativeInt(_))
+            if opcode == Opcode::Add || opcode == Opcode::Sub =>
+        {
+            let offset = b.as_i64().unwrap() as isize;
+            return Ok(Value::ByRef(pointer.add(if opcode == Opcode::Add { offset } else { -offset })));
+        }
+        (&Value::I32(_), &Value::ByRef(pointer)) | (&Value::NativeInt(_), &Value::ByRef(pointer))
+            if opcode == Opcode::Add =>
+        {
+            return Ok(Value::ByRef(pointer.add(a.as_i64().unwrap() as isize)));
+        }
+        _ => {}
+    }
+
+    let (x, y, width) = int_operands(a, b)?;
+    match width {
+        Width::I32 => binary_i32(opcode, x as i32, y as i32).map(Value::I32),
+        _ => binary_i64(opcode, x, y).map(|result| int_value(result, width)),
+    }
+}
+
+/// Applies `shl`, `shr` or `shr.un`, whose result has the type of the value being shifted.
+pub fn shift(opcode: Opcode, value: &Value, amount: &Value) -> Result<Value, ExceptionKind> {
+    let (amount, _) = int_operand(amount).ok_or(ExceptionKind::InvalidProgram)?;
+    let amount = amount as u32;
+    Ok(match *value {
+        Value::I32(x) => Value::I32(match opcode {
+            Opcode::Shl => x.wrapping_shl(amount),
+            Opcode::Shr => x.wrapping_shr(amount),
+            _ => (x as u32).wrapping_shr(amount) as i32,
+        }),
+        _ => {
+            let (x, width) = int_operand(value).ok_or(ExceptionKind::InvalidProgram)?;
+            int_value(match opcode {
+                Opcode::Shl => x.wrapping_shl(amount),
+                Opcode::Shr => x.wrapping_shr(amount),
+                _ => (x as u64).wrapping_shr(amount) as i64,
+            }, width)
+        }
+    })
+}
+
+/// Applies `neg` or `not`.
+pub fn unary(opcode: Opcode, value: &Value) -> Result<Value, ExceptionKind> {
+    match (opcode, value) {
+        (Opcode::Neg, &Value::F(x)) => Ok(Value::F(-x)),
+        (Opcode::Neg, &Value::I32(x)) => Ok(Value::I32(x.wrapping_neg())),
+        (Opcode::Not, &Value::I32(x)) => Ok(Value::I32(!x)),
+        (_, value) => {
+            let (x, width) = int_operand(value).ok_or(ExceptionKind::InvalidProgram)?;
+            Ok(int_value(if opcode == Opcode::Neg { x.wrapping_neg() } else { !x }, width))
+        }
+    }
+}
+
+/// The comparisons made by `ceq`, `cgt`, `clt` and the conditional branches.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+
+    /// The unsigned or unordered forms, which are true for floats if either is NaN.
+    GtUn,
+    GeUn,
+    LtUn,
+    LeUn,
+}
+
+impl Comparison {
+    /// Gets the comparison a comparison or conditional branch instruction makes.
+    pub fn for_opcode(opcode: Opcode) -> Option<Comparison> {
+        Some(match opcode {
+            Opcode::Ceq | Opcode::Beq | Opcode::BeqS => Comparison::Eq,
+            Opcode::BneUn | Opcode::BneUnS => Comparison::Ne,
+            Opcode::Cgt | Opcode::Bgt | Opcode::BgtS => Comparison::Gt,
+            Opcode::Bge | Opcode::BgeS => Comparison::Ge,
+            Opcode::Clt | Opcode::Blt | Opcode::BltS => Comparison::Lt,
+            Opcode::Ble | Opcode::BleS => Comparison::Le,
+            Opcode::CgtUn | Opcode::BgtUn | Opcode::BgtUnS => Comparison::GtUn,
+            Opcode::BgeUn | Opcode::BgeUnS => Comparison::GeUn,
+            Opcode::CltUn | Opcode::BltUn | Opcode::BltUnS => Comparison::LtUn,
+            Opcode::BleUn | Opcode::BleUnS => Comparison::LeUn,
+            _ => return None,
+        })
+    }
+}
+
+pub fn compare(comparison: Comparison, a: &Value, b: &Value) -> Result<bool, ExceptionKind> {
+    if let (&Value::F(x), &Value::F(y)) = (a, b) {
+        let unordered = x.is_nan() || y.is_nan();
+        return Ok(match comparison {
+            Comparison::Eq => x == y,
+            Comparison::Ne => x != y,
+            Comparison::Gt => x > y,
+            Comparison::Ge => x >= y,
+            Comparison::Lt => x < y,
+            Comparison::Le => x <= y,
+            Comparison::GtUn => unordered || x > y,
+            Comparison::GeUn => unordered || x >= y,
+            Comparison::LtUn => unordered || x < y,
+            Comparison::LeUn => unordered || x <= y,
+        });
+    }
+    if let (&Value::Struct(..), _) | (_, &Value::Struct(..)) = (a, b) {
+        return Err(ExceptionKind::InvalidProgram);
+    }
+
+    let (x, y, width) = int_operands(a, b)?;
+    let (ux, uy) = match width {
+        Width::I32 => (x as u32 as u64, y as u32 as u64),
+        _ => (x as u64, y as u64),
+    };
+    Ok(match comparison {
+        Comparison::Eq => x == y,
+        Comparison::Ne => x != y,
+        Comparison::Gt => x > y,
+        Comparison::Ge => x >= y,
+        Comparison::Lt => x < y,
+        Comparison::Le => x <= y,
+        Comparison::GtUn => ux > uy,
+        Comparison::GeUn => ux >= uy,
+        Comparison::LtUn => ux < uy,
+        Comparison::LeUn => ux <= uy,
+    })
+}
+
+/// The target of a conversion instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Conversion {
+    I1,
+    U1,
+    I2,
+    U2,
+    I4,
+    U4,
+    I8,
+    U8,
+    I,
+    U,
+    R4,
+    R8,
+}
+
+impl Conversion {
+    /// Gets the target of a conversion instruction, whether it checks for overflow, and whether it treats its
+    /// operand as unsigned.
+    pub fn for_opcode(opcode: Opcode) -> Option<(Conversion, bool, bool)> {
+        Some(match opcode {
+            Opcode::ConvI1 => (Conversion::I1, false, false),
+            Opcode::ConvU1 => (Conversion::U1, false, false),
+            Opcode::ConvI2 => (Conversion::I2, false, false),
+            Opcode::ConvU2 => (Conversion::U2, false, false),
+            Opcode::ConvI4 => (Conversion::I4, false, false),
+            Opcode::ConvU4 => (Conversion::U4, false, false),
+            Opcode::ConvI8 => (Conversion::I8, false, false),
+            Opcode::ConvU8 => (Conversion::U8, false, false),
+            Opcode::ConvI => (Conversion::I, false, false),
+            Opcode::ConvU => (Conversion::U, false, false),
+            Opcode::ConvR4 => (Conversion::R4, false, false),
+            Opcode::ConvR8 => (Conversion::R8, false, false),
+            Opcode::ConvRUn => (Conversion::R8, false, true),
+            Opcode::ConvOvfI1 => (Conversion::I1, true, false),
+            Opcode::ConvOvfU1 => (Conversion::U1, true, false),
+            Opcode::ConvOvfI2 => (Conversion::I2, true, false),
+            Opcode::ConvOvfU2 => (Conversion::U2, true, false),
+            Opcode::ConvOvfI4 => (Conversion::I4, true, false),
+            Opcode::ConvOvfU4 => (Conversion::U4, true, false),
+            Opcode::ConvOvfI8 => (Conversion::I8, true, false),
+            Opcode::ConvOvfU8 => (Conversion::U8, true, false),
+            Opcode::ConvOvfI => (Conversion::I, true, false),
+            Opcode::ConvOvfU => (Conversion::U, true, false),
+            Opcode::ConvOvfI1Un => (Conversion::I1, true, true),
+            Opcode::ConvOvfU1Un => (Conversion::U1, true, true),
+            Opcode::ConvOvfI2Un => (Conversion::I2, true, true),
+            Opcode::ConvOvfU2Un => (Conversion::U2, true, true),
+            Opcode::ConvOvfI4Un => (Conversion::I4, true, true),
+            Opcode::ConvOvfU4Un => (Conversion::U4, true, true),
+            Opcode::ConvOvfI8Un => (Conversion::I8, true, true),
+            Opcode::ConvOvfU8Un => (Conversion::U8, true, true),
+            Opcode::ConvOvfIUn => (Conversion::I, true, true),
+            Opcode::ConvOvfUUn => (Conversion::U, true, true),
+            _ => return None,
+        })
+    }
+
+    fn range(self) -> (i128, i128) {
+        match self {
+            Conversion::I1 => (i8::MIN as i128, i8::MAX as i128),
+            Conversion::U1 => (0, u8::MAX as i128),
+            Conversion::I2 => (i16::MIN as i128, i16::MAX as i128),
+            Conversion::U2 => (0, u16::MAX as i128),
+            Conversion::I4 => (i32::MIN as i128, i32::MAX as i128),
+            Conversion::U4 => (0, u32::MAX as i128),
+            Conversion::I8 | Conversion::I => (i64::MIN as i128, i64::MAX as i128),
+            Conversion::U8 | Conversion::U => (0, u64::MAX as i128),
+            Conversion::R4 | Conversion::R8 => (i128::MIN, i128::MAX),
+        }
+    }
+}
+
+/// Converts a value, as the `conv.*` instructions do.
+pub fn convert(value: &Value, target: Conversion, overflow: bool, unsigned: bool) -> Result<Value, ExceptionKind> {
+    let x: i128 = match *value {
+        Value::F(f) => {
+            match target {
+                Conversion::R4 => return Ok(Value::F(f as f32 as f64)),
+                Conversion::R8 => return Ok(Value::F(f)),
+                _ => {}
+            }
+            let (min, max) = target.range();
+            let truncated = f.trunc();
+            if overflow && !(truncated >= min as f64 && truncated <= max as f64) {
+                return Err(ExceptionKind::Overflow);
+            }
+            // Out of range conversions are unspecified, so saturate like Rust does
+            match target {
+                Conversion::U1 | Conversion::U2 | Conversion::U4 | Conversion::U8 | Conversion::U => f as u64 as i128,
+                _ => f as i64 as i128,
+            }
+        }
+        _ => {
+            let (x, width) = int_operand(value).ok_or(ExceptionKind::InvalidProgram)?;
+            match (unsigned, width) {
+                (true, Width::I32) => x as u32 as i128,
+                (true, _) => x as u64 as i128,
+                (false, _) => x as i128,
+            }
+        }
+    };
+
+    if overflow {
+        let (min, max) = target.range();
+        if x < min || x > max {
+            return Err(ExceptionKind::Overflow);
+        }
+    }
+    Ok(match target {
+        Conversion::I1 => Value::I32(x as i8 as i32),
+        Conversion::U1 => Value::I32(x as u8 as i32),
+        Conversion::I2 => Value::I32(x as i16 as i32),
+        Conversion::U2 => Value::I32(x as u16 as i32),
+        Conversion::I4 | Conversion::U4 => Value::I32(x as i32),
+        Conversion::I8 | Conversion::U8 => Value::I64(x as i64),
+        Conversion::I | Conversion::U => Value::NativeInt(x as isize),
+        Conversion::R4 => Value::F(x as f32 as f64),
+        Conversion::R8 => Value::F(x as f64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn arithmetic() {
+        let add = |a, b| binary(Opcode::Add, &a, &b);
+        assert_eq!(Ok(Value::I32(i32::MIN)), add(Value::I32(i32::MAX), Value::I32(1)));
+        assert_eq!(Ok(Value::NativeInt(3)), add(Value::NativeInt(1), Value::I32(2)));
+        assert_eq!(Err(ExceptionKind::InvalidProgram), add(Value::I64(1), Value::I32(2)));
+        assert_eq!(Err(ExceptionKind::Overflow),
+                   binary(Opcode::AddOvf, &Value::I32(i32::MAX), &Value::I32(1)));
+        assert_eq!(Err(ExceptionKind::Overflow), binary(Opcode::SubOvfUn, &Value::I64(0), &Value::I64(1)));
+        assert_eq!(Err(ExceptionKind::DivideByZero), binary(Opcode::Rem, &Value::I32(1), &Value::I32(0)));
+        assert_eq!(Err(ExceptionKind::Overflow),
+                   binary(Opcode::Div, &Value::I32(i32::MIN), &Value::I32(-1)));
+        assert_eq!(Ok(Value::I32(0x7FFF_FFFF)), binary(Opcode::DivUn, &Value::I32(-2), &Value::I32(2)));
+        assert_eq!(Ok(Value::I32(-1)), shift(Opcode::Shr, &Value::I32(-2), &Value::I32(1)));
+        assert_eq!(Ok(Value::I64(0x7FFF_FFFF_FFFF_FFFF)), shift(Opcode::ShrUn, &Value::I64(-1), &Value::I32(1)));
+    }
+
+    #[test]
+    pub fn comparisons() {
+        assert_eq!(Ok(true), compare(Comparison::GtUn, &Value::I32(-1), &Value::I32(1)));
+        assert_eq!(Ok(false), compare(Comparison::Gt, &Value::I32(-1), &Value::I32(1)));
+        assert_eq!(Ok(true), compare(Comparison::LtUn, &Value::F(f64::NAN), &Value::F(1.0)));
+        assert_eq!(Ok(false), compare(Comparison::Lt, &Value::F(f64::NAN), &Value::F(1.0)));
+        assert_eq!(Ok(true), compare(Comparison::Eq, &Value::null(), &Value::null()));
+    }
+
+    #[test]
+    pub fn conversions() {
+        assert_eq!(Ok(Value::I32(-1)), convert(&Value::I32(0xFFFF), Conversion::I2, false, false));
+        assert_eq!(Ok(Value::I32(0xFF)), convert(&Value::I32(-1), Conversion::U1, false, false));
+        assert_eq!(Err(ExceptionKind::Overflow), convert(&Value::I32(-1), Conversion::U1, true, false));
+        assert_eq!(Err(ExceptionKind::Overflow), convert(&Value::I32(-1), Conversion::I4, true, true));
+        assert_eq!(Ok(Value::I64(0xFFFF_FFFF)), convert(&Value::I32(-1), Conversion::U8, false, true));
+        assert_eq!(Ok(Value::F(4294967295.0)), convert(&Value::I32(-1), Conversion::R8, false, true));
+        assert_eq!(Ok(Value::I32(-3)), convert(&Value::F(-3.9), Conversion::I4, false, false));
+        assert_eq!(Err(ExceptionKind::Overflow), convert(&Value::F(f64::NAN), Conversion::I4, true, false));
+        assert_eq!(Err(ExceptionKind::Overflow), convert(&Value::F(3e9), Conversion::I4, true, false));
+    }
+}
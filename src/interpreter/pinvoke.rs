@@ -0,0 +1,567 @@
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use ecma355metadata::cli::{NativeType, PInvokeCharSet, PInvokeFlags};
+use ecma355metadata::cli::signatures::SignatureCallingConvention;
+
+use error::{Error, ExceptionKind};
+use interpreter::Value;
+use native::{self, NativeArg, NativeReturn};
+use types::{MethodId, MethodSig, Primitive, Storage, TypeId, TypeKind};
+use vm::Vm;
+
+/// The class of register an eightbyte of a struct is passed in, as the System V x86-64 ABI classifies them: SSE if
+/// it only holds floating-point fields, and INTEGER otherwise.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum RegisterClass {
+    Int,
+    Float,
+}
+
+/// The encoding of a string passed to native code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum StringEncoding {
+    Utf8,
+    Utf16,
+}
+
+/// How a parameter or return value of a P/Invoke is passed to or from native code.
+#[derive(Clone, Debug)]
+enum Marshaler {
+    /// An integer, character, enum or unmanaged pointer, passed as it is stored.
+    Int(TypeId),
+    Float(TypeId),
+
+    /// A `bool`, as a native integer of a size: 4 for a Win32 `BOOL`, which is the default, or 1.
+    Bool(u32),
+
+    /// A string, copied to a null-terminated native buffer for the call.
+    String(StringEncoding),
+
+    /// A blittable struct of up to 16 bytes, passed by value in the registers for each of its eightbytes.
+    Struct(TypeId, Vec<RegisterClass>),
+
+    /// A managed pointer to a blittable value, or an array of blittable elements, passed as the address of the value
+    /// or the first element. Objects don't move while native code runs, so the address stays valid.
+    Address,
+}
+
+/// The marshalers of the parameters and return value of a signature.
+type Marshalers = (Vec<Marshaler>, Option<Marshaler>);
+
+/// How a P/Invoke calls the native function it imports.
+pub struct PInvokeTarget {
+    function: *const c_void,
+    params: Vec<Marshaler>,
+    ret: Option<Marshaler>,
+    set_last_error: bool,
+}
+
+impl Vm {
+    /// Calls the native function a P/Invoke method imports with the arguments on the stack, binding it the first
+    /// time. A library or function that can't be found, or a parameter that can't be marshaled, throws the exception
+    /// .NET throws.
+    pub(super) fn pinvoke_call(&mut self, method: MethodId) -> Result<(), Error> {
+        let target = match self.pinvokes.get(&method).cloned() {
+            Some(target) => target,
+            None => match self.bind_pinvoke(method)? {
+                Ok(target) => {
+                    let target = Rc::new(target);
+                    self.pinvokes.insert(method, target.clone());
+                    target
+                }
+                Err((kind, message)) => {
+                    let thrown = self.raise_with_message(kind, &message)?;
+                    return Err(self.throw_from_runtime(thrown));
+                }
+            },
+        };
+        self.native_call(&target)
+    }
+
+    /// Calls a native function through a pointer, as a `calli` with an unmanaged calling convention does, marshaling
+    /// the arguments on the stack as a P/Invoke with the call site's signature would.
+    pub(super) fn unmanaged_call(&mut self, function: *const c_void, signature: &MethodSig) -> Result<(), Error> {
+        if signature.has_this {
+            return Err(Error::InvalidProgram(format!("{} calls a native function with this", self.current())));
+        }
+        let param_types = vec![None; signature.params.len()];
+        let (params, ret) = match self.marshalers(signature, None, &param_types, PInvokeCharSet::NotSpec)? {
+            Ok(marshalers) => marshalers,
+            Err(message) => {
+                let thrown = self.raise_with_message(ExceptionKind::MarshalDirective, &message)?;
+                return Err(self.throw_from_runtime(thrown));
+            }
+        };
+        let target = PInvokeTarget {
+            function,
+            params,
+            ret,
+            set_last_error: false,
+        };
+        self.native_call(&target)
+    }
+
+    /// Calls a native function with the arguments on the stack, and pushes what it returns.
+    fn native_call(&mut self, target: &PInvokeTarget) -> Result<(), Error> {
+        // The buffers strings are copied to live until the call returns
+        let args = self.pop_args(target.params.len())?;
+        let mut buffers = Vec::new();
+        let mut native_args = Vec::new();
+        for (marshaler, arg) in target.params.iter().zip(args) {
+            self.marshal_arg(marshaler, arg, &mut buffers, &mut native_args)?;
+        }
+        let ret = match target.ret {
+            None => NativeReturn::Void,
+            Some(Marshaler::Float(_)) => NativeReturn::Float,
+            Some(Marshaler::Struct(_, ref classes)) => match (classes[0], classes.get(1)) {
+                (RegisterClass::Int, None) => NativeReturn::Int,
+                (RegisterClass::Float, None) => NativeReturn::Float,
+                (RegisterClass::Int, Some(&RegisterClass::Int)) => NativeReturn::IntInt,
+                (RegisterClass::Int, Some(&RegisterClass::Float)) => NativeReturn::IntFloat,
+                (RegisterClass::Float, Some(&RegisterClass::Int)) => NativeReturn::FloatInt,
+                (RegisterClass::Float, Some(&RegisterClass::Float)) => NativeReturn::FloatFloat,
+            },
+            Some(_) => NativeReturn::Int,
+        };
+
+        if target.set_last_error {
+            native::clear_last_error();
+        }
+        let bits = match unsafe { native::call(target.function, &native_args, ret) } {
+            Some(bits) => bits,
+            None => {
+                let message = "Cannot marshal more arguments than are passed in registers.";
+                let thrown = self.raise_with_message(ExceptionKind::MarshalDirective, message)?;
+                return Err(self.throw_from_runtime(thrown));
+            }
+        };
+        if target.set_last_error {
+            self.last_error = native::last_error();
+        }
+        drop(buffers);
+
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&bits[0].to_le_bytes());
+        bytes[8..].copy_from_slice(&bits[1].to_le_bytes());
+        let value = match target.ret {
+            None => return Ok(()),
+            Some(Marshaler::Int(ty)) | Some(Marshaler::Float(ty)) => {
+                let (ty, storage, size) = self.value_layout(ty);
+                unsafe { Value::load(bytes.as_ptr(), storage, ty, size) }
+            }
+            Some(Marshaler::Bool(1)) => Value::I32((bytes[0] != 0) as i32),
+            Some(Marshaler::Bool(_)) => Value::I32((bits[0] as u32 != 0) as i32),
+            Some(Marshaler::Struct(ty, _)) => {
+                let size = self.types.get(ty).value_size() as usize;
+                Value::Struct(ty, bytes[..size].to_vec())
+            }
+            Some(Marshaler::String(_)) | Some(Marshaler::Address) => unreachable!(),
+        };
+        self.push(value);
+        Ok(())
+    }
+
+    /// Finds the native function a P/Invoke imports and works out how to marshal its parameters and return value,
+    /// or gets the exception to throw if it can't.
+    fn bind_pinvoke(&mut self, method: MethodId) -> Result<Result<PInvokeTarget, (ExceptionKind, String)>, Error> {
+        let import = match self.types.pinvoke_import(method)? {
+            Some(import) => import,
+            None => return Err(Error::InvalidProgram(format!("{} has no ImplMap row", self.types.method(method)))),
+        };
+        if import.calling_convention == SignatureCallingConvention::VarArgs {
+            let message = "Cannot call a P/Invoke with a variable number of arguments.".to_string();
+            return Ok(Err((ExceptionKind::MarshalDirective, message)));
+        }
+
+        // Every other calling convention is the platform's C convention on 64-bit targets
+        let signature = self.types.method(method).signature.clone();
+        let char_set = import.attributes.char_set();
+        let (params, ret) = match self.marshalers(&signature, import.return_type, &import.param_types, char_set)? {
+            Ok(marshalers) => marshalers,
+            Err(message) => return Ok(Err((ExceptionKind::MarshalDirective, message))),
+        };
+
+        let function = match self.libraries.function(&import.library, &import.entry_point) {
+            Ok(Some(function)) => function,
+            Ok(None) => {
+                let message = format!(
+                    "Unable to find an entry point named '{}' in shared library '{}'.",
+                    import.entry_point, import.library
+                );
+                return Ok(Err((ExceptionKind::EntryPointNotFound, message)));
+            }
+            Err(reason) => {
+                let message = format!(
+                    "Unable to load shared library '{}' or one of its dependencies: {}",
+                    import.library, reason
+                );
+                return Ok(Err((ExceptionKind::DllNotFound, message)));
+            }
+        };
+        Ok(Ok(PInvokeTarget {
+            function,
+            params,
+            ret,
+            set_last_error: import.attributes.flags().contains(PInvokeFlags::SupportsLastError),
+        }))
+    }
+
+    /// Works out how to marshal the parameters and return value of a signature to a native function, with the
+    /// native types `[MarshalAs]` gives them, or gets the message of the exception to throw if one can't be.
+    fn marshalers(
+        &mut self,
+        signature: &MethodSig,
+        return_type: Option<NativeType>,
+        param_types: &[Option<NativeType>],
+        char_set: PInvokeCharSet,
+    ) -> Result<Result<Marshalers, String>, Error> {
+        let mut params = Vec::new();
+        for (index, (&param, &native_type)) in signature.params.iter().zip(param_types).enumerate() {
+            match self.marshaler(param, native_type, &char_set, false)? {
+                Ok(marshaler) => params.push(marshaler),
+                Err(reason) => return Ok(Err(format!("Cannot marshal 'parameter #{}': {}", index + 1, reason))),
+            }
+        }
+        let ret = match signature.ret {
+            Some(ret) => match self.marshaler(ret, return_type, &char_set, true)? {
+                Ok(marshaler) => Some(marshaler),
+                Err(reason) => return Ok(Err(format!("Cannot marshal 'return value': {}", reason))),
+            },
+            None => None,
+        };
+        Ok(Ok((params, ret)))
+    }
+
+    /// Works out how to marshal a parameter or return value of a type, with the native type `[MarshalAs]` gives it,
+    /// or gets the reason it can't be.
+    fn marshaler(
+        &mut self,
+        ty: TypeId,
+        native_type: Option<NativeType>,
+        char_set: &PInvokeCharSet,
+        is_return: bool,
+    ) -> Result<Result<Marshaler, &'static str>, Error> {
+        self.types.prepare(ty)?;
+        let kind = self.types.get(ty).kind.clone();
+        Ok(Ok(match kind {
+            TypeKind::Primitive(Primitive::Boolean) => match native_type {
+                None | Some(NativeType::Boolean) | Some(NativeType::I4) | Some(NativeType::U4) => Marshaler::Bool(4),
+                Some(NativeType::I1) | Some(NativeType::U1) => Marshaler::Bool(1),
+                Some(_) => return Ok(Err("Invalid managed/unmanaged type combination.")),
+            },
+            TypeKind::Primitive(Primitive::R4) | TypeKind::Primitive(Primitive::R8) => Marshaler::Float(ty),
+            TypeKind::Primitive(_) | TypeKind::Enum(_) | TypeKind::Pointer(_) => Marshaler::Int(ty),
+            TypeKind::String if !is_return => {
+                let utf16 = match native_type {
+                    Some(NativeType::LPStr) | Some(NativeType::LPUtf8Str) => false,
+                    Some(NativeType::LPWStr) => true,
+                    None => *char_set == PInvokeCharSet::Unicode,
+                    Some(_) => return Ok(Err("Invalid managed/unmanaged type combination.")),
+                };
+                Marshaler::String(if utf16 { StringEncoding::Utf16 } else { StringEncoding::Utf8 })
+            }
+            TypeKind::ValueType => match self.register_classes(ty)? {
+                Some(classes) => Marshaler::Struct(ty, classes),
+                None => return Ok(Err("Only blittable structs of up to 16 bytes can be passed by value.")),
+            },
+            TypeKind::ByRef(target) | TypeKind::SzArray(target) if !is_return && self.is_blittable(target)? => {
+                Marshaler::Address
+            }
+            _ => return Ok(Err("There is no marshaling support for this type.")),
+        }))
+    }
+
+    /// Returns `true` if values of a type have the same representation in managed and native code: primitive types
+    /// other than `bool` and `char`, and structs of them.
+    fn is_blittable(&mut self, ty: TypeId) -> Result<bool, Error> {
+        self.types.prepare(ty)?;
+        Ok(match self.types.get(ty).kind {
+            TypeKind::Primitive(Primitive::Boolean) | TypeKind::Primitive(Primitive::Char) => false,
+            TypeKind::Primitive(_) | TypeKind::Enum(_) | TypeKind::Pointer(_) => true,
+            TypeKind::ValueType => {
+                for field in self.types.get(ty).fields.clone() {
+                    let field = self.types.field(field);
+                    if !field.is_static() && !self.is_blittable(field.field_type)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            _ => false,
+        })
+    }
+
+    /// Classifies each eightbyte of a blittable struct of up to 16 bytes, or gets `None` for other structs, which
+    /// aren't passed in registers.
+    fn register_classes(&mut self, ty: TypeId) -> Result<Option<Vec<RegisterClass>>, Error> {
+        let size = self.types.get(ty).value_size();
+        if size == 0 || size > 16 || !self.is_blittable(ty)? {
+            return Ok(None);
+        }
+        let mut leaves = Vec::new();
+        self.leaf_fields(ty, 0, &mut leaves);
+        let classes = (0..size.div_ceil(8))
+            .map(|eightbyte| {
+                let range = (eightbyte * 8)..(eightbyte * 8 + 8);
+                let mut fields = leaves.iter().filter(|&&(offset, _)| range.contains(&offset));
+                if fields.all(|&(_, storage)| storage == Storage::R4 || storage == Storage::R8) {
+                    RegisterClass::Float
+                } else {
+                    RegisterClass::Int
+                }
+            })
+            .collect();
+        Ok(Some(classes))
+    }
+
+    /// Collects the offset and storage of each primitive field in a struct, looking into the fields that are structs.
+    fn leaf_fields(&self, ty: TypeId, offset: u32, leaves: &mut Vec<(u32, Storage)>) {
+        for &field in &self.types.get(ty).fields {
+            let field = self.types.field(field);
+            if field.is_static() {
+                continue;
+            }
+            match self.types.get(field.field_type).storage() {
+                Storage::Struct => self.leaf_fields(field.field_type, offset + field.offset, leaves),
+                storage => leaves.push((offset + field.offset, storage)),
+            }
+        }
+    }
+
+    /// Converts an argument to the native arguments it is passed as, copying strings to buffers that must outlive the
+    /// call.
+    fn marshal_arg(
+        &self,
+        marshaler: &Marshaler,
+        arg: Value,
+        buffers: &mut Vec<Vec<u8>>,
+        args: &mut Vec<NativeArg>,
+    ) -> Result<(), Error> {
+        let invalid = || Error::InvalidProgram(format!("{} passes {:?} to a P/Invoke", self.current(), arg));
+        match *marshaler {
+            Marshaler::Int(ty) => {
+                let bits = match self.coerce(arg.clone(), ty) {
+                    Value::I32(value) => value as i64 as u64,
+                    Value::I64(value) => value as u64,
+                    Value::NativeInt(value) => value as u64,
+                    Value::ByRef(pointer) => pointer.address() as u64,
+                    _ => return Err(invalid()),
+                };
+                args.push(NativeArg::Int(bits));
+            }
+            Marshaler::Float(ty) => {
+                let value = match arg {
+                    Value::F(value) => value,
+                    _ => return Err(invalid()),
+                };
+                let bits = match self.types.get(ty).storage() {
+                    Storage::R4 => (value as f32).to_bits() as u64,
+                    _ => value.to_bits(),
+                };
+                args.push(NativeArg::Float(bits));
+            }
+            Marshaler::Bool(_) => match arg.as_i64() {
+                Some(value) => args.push(NativeArg::Int((value != 0) as u64)),
+                None => return Err(invalid()),
+            },
+            Marshaler::String(encoding) => {
+                let object = match arg {
+                    Value::Ref(object) => object,
+                    _ => return Err(invalid()),
+                };
+                if object.is_null() {
+                    args.push(NativeArg::Int(0));
+                    return Ok(());
+                }
+                let chars = unsafe { object.string_chars() };
+                let mut buffer: Vec<u8> = match encoding {
+                    StringEncoding::Utf8 => String::from_utf16_lossy(chars).into_bytes(),
+                    StringEncoding::Utf16 => chars.iter().flat_map(|c| c.to_le_bytes().to_vec()).collect(),
+                };
+                buffer.extend_from_slice(&[0, 0]);
+                args.push(NativeArg::Int(buffer.as_ptr() as u64));
+                buffers.push(buffer);
+            }
+            Marshaler::Struct(_, ref classes) => {
+                let mut bytes = match arg {
+                    Value::Struct(_, bytes) => bytes,
+                    _ => return Err(invalid()),
+                };
+                bytes.resize(16, 0);
+                for (eightbyte, &class) in classes.iter().enumerate() {
+                    let mut bits = [0; 8];
+                    bits.copy_from_slice(&bytes[(eightbyte * 8)..(eightbyte * 8 + 8)]);
+                    let bits = u64::from_le_bytes(bits);
+                    args.push(match class {
+                        RegisterClass::Int => NativeArg::Int(bits),
+                        RegisterClass::Float => NativeArg::Float(bits),
+                    });
+                }
+            }
+            Marshaler::Address => {
+                let address = match arg {
+                    Value::ByRef(pointer) => pointer.address() as u64,
+                    Value::Ref(array) if array.is_null() => 0,
+                    Value::Ref(array) => unsafe { array.array_element(0, 0) as u64 },
+                    _ => return Err(invalid()),
+                };
+                args.push(NativeArg::Int(address));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use ecma355metadata::cli::il::Opcode;
+
+    use error::Error;
+    use test_assembly::*;
+
+    /// Defines a P/Invoke in the type defined last, with `[MarshalAs]` descriptors for the parameters (numbered from
+    /// 1, or 0 for the return value) in `marshals`.
+    fn pinvoke(
+        app: &mut AssemblyBuilder,
+        flags: u16,
+        library: &str,
+        name: &str,
+        signature: &[u8],
+        marshals: &[(u16, &[u8])],
+    ) -> u32 {
+        let method = app.method(STATIC | PINVOKE_IMPL, name, signature, None);
+        app.impl_map(method, flags, library, name);
+        for &(sequence, descriptor) in marshals {
+            let param = app.param(HAS_FIELD_MARSHAL, sequence, "");
+            app.field_marshal(param, descriptor);
+        }
+        method
+    }
+
+    #[test]
+    pub fn pinvokes_call_libc_functions_with_strings_in_their_char_set() {
+        // [DllImport("libc")] static extern int getpid();
+        // [DllImport("libc")] static extern int strlen(string s);
+        // [DllImport("libc", CharSet = CharSet.Unicode)] static extern int strlen(string s);
+        // [DllImport("libc", CharSet = CharSet.Unicode)] static extern int strlen([MarshalAs(LPUTF8Str)] string s);
+        // return (getpid() == pid) * 1000 + strlen("hello") * 100 + strlen_utf16("hi") * 10 + strlen_utf8("héllo");
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        app.type_def(PUBLIC, "", "Program", object);
+        let getpid = pinvoke(&mut app, CALL_CONV_WINAPI, "libc", "getpid", &method_sig(false, Ty::I4, &[]), &[]);
+        let strlen_sig = method_sig(false, Ty::I4, &[Ty::String]);
+        let strlen = pinvoke(&mut app, CALL_CONV_WINAPI, "libc", "strlen", &strlen_sig, &[]);
+        let unicode = CALL_CONV_WINAPI | CHAR_SET_UNICODE;
+        let strlen_utf16 = pinvoke(&mut app, unicode, "libc", "strlen", &strlen_sig, &[]);
+        let strlen_utf8 = pinvoke(&mut app, unicode, "libc", "strlen", &strlen_sig, &[(1, &[0x30])]);
+
+        let mut il = Il::new();
+        il.arg(Opcode::Call, getpid as i64).ldc_i4(process::id() as i32).op(Opcode::Ceq);
+        il.ldc_i4(1000).op(Opcode::Mul);
+        for &(strlen, value, scale) in &[(strlen, "hello", 100), (strlen_utf16, "hi", 10), (strlen_utf8, "héllo", 1)] {
+            let value = app.user_string(value);
+            il.arg(Opcode::Ldstr, value as i64).arg(Opcode::Call, strlen as i64);
+            il.ldc_i4(scale).op(Opcode::Mul).op(Opcode::Add);
+        }
+        il.op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+        assert_eq!(Ok(1516), run("pinvoke_strings", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn pinvokes_pass_floats_structs_and_pointers_in_registers() {
+        // struct LongDiv { long quot; long rem; }
+        // [DllImport("libm.so.6")] static extern double ldexp(double x, int exp);
+        // [DllImport("libm.so.6")] static extern double modf(double x, out double integral);
+        // [DllImport("libc")] static extern LongDiv ldiv(long numerator, long denominator);
+        // LongDiv d = ldiv(47, 5); double integral; double fraction = modf(3.25, out integral);
+        // return (((int)ldexp(3.0, 4) * 100 + d.quot * 10 + d.rem) * 100 + (int)(fraction * 100)) * 10 + integral;
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let value_type = app.corlib_type("System", "ValueType");
+        let long_div = app.type_def(PUBLIC | SEALED, "", "LongDiv", value_type);
+        let quot = app.field(0, "quot", Ty::I8);
+        let rem = app.field(0, "rem", Ty::I8);
+        app.type_def(PUBLIC, "", "Program", object);
+        let signature = method_sig(false, Ty::R8, &[Ty::R8, Ty::I4]);
+        let ldexp = pinvoke(&mut app, CALL_CONV_WINAPI, "libm.so.6", "ldexp", &signature, &[]);
+        let signature = method_sig(false, Ty::R8, &[Ty::R8, Ty::by_ref(Ty::R8)]);
+        let modf = pinvoke(&mut app, CALL_CONV_WINAPI, "libm.so.6", "modf", &signature, &[]);
+        let signature = method_sig(false, Ty::ValueType(long_div), &[Ty::I8, Ty::I8]);
+        let ldiv = pinvoke(&mut app, CALL_CONV_WINAPI, "libc", "ldiv", &signature, &[]);
+
+        let mut il = Il::new();
+        il.ldc_i4(47).op(Opcode::ConvI8).ldc_i4(5).op(Opcode::ConvI8).arg(Opcode::Call, ldiv as i64);
+        il.op(Opcode::Stloc0);
+        il.ldc_r8(3.0).ldc_i4(4).arg(Opcode::Call, ldexp as i64).op(Opcode::ConvI4).ldc_i4(100).op(Opcode::Mul);
+        il.arg(Opcode::LdlocaS, 0).arg(Opcode::Ldfld, quot as i64).op(Opcode::ConvI4).ldc_i4(10).op(Opcode::Mul);
+        il.op(Opcode::Add).arg(Opcode::LdlocaS, 0).arg(Opcode::Ldfld, rem as i64).op(Opcode::ConvI4).op(Opcode::Add);
+        il.ldc_i4(100).op(Opcode::Mul);
+        il.ldc_r8(3.25).arg(Opcode::LdlocaS, 1).arg(Opcode::Call, modf as i64).ldc_r8(100.0).op(Opcode::Mul);
+        il.op(Opcode::ConvI4).op(Opcode::Add).ldc_i4(10).op(Opcode::Mul);
+        il.op(Opcode::Ldloc1).op(Opcode::ConvI4).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::ValueType(long_div), Ty::R8], il));
+        assert_eq!(Ok(4_892_253), run("pinvoke_registers", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn pinvokes_that_set_the_last_error_keep_errno() {
+        // [DllImport("libc", SetLastError = true)] static extern int close(int fd);
+        // return close(-1) * 100 + Marshal.GetLastWin32Error();
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let marshal = app.corlib_type("System.Runtime.InteropServices", "Marshal");
+        let get_last_error = app.member_ref(marshal, "GetLastWin32Error", &method_sig(false, Ty::I4, &[]));
+        app.type_def(PUBLIC, "", "Program", object);
+        let flags = CALL_CONV_WINAPI | SUPPORTS_LAST_ERROR;
+        let close = pinvoke(&mut app, flags, "libc", "close", &method_sig(false, Ty::I4, &[Ty::I4]), &[]);
+        let mut il = Il::new();
+        il.ldc_i4(-1).arg(Opcode::Call, close as i64).ldc_i4(100).op(Opcode::Mul);
+        il.arg(Opcode::Call, get_last_error as i64).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+        assert_eq!(Ok(-100 + 9), run("pinvoke_last_error", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn unbindable_pinvokes_throw_the_exceptions_dotnet_throws() {
+        let report = |library: &str, name: &str, params: &[Ty]| {
+            let mut app = AssemblyBuilder::new("App");
+            let object = app.corlib_type("System", "Object");
+            app.type_def(PUBLIC, "", "Program", object);
+            let signature = method_sig(false, Ty::Void, params);
+            let method = pinvoke(&mut app, CALL_CONV_WINAPI, library, name, &signature, &[]);
+            let mut il = Il::new();
+            for _ in params {
+                il.op(Opcode::Ldnull);
+            }
+            il.arg(Opcode::Call, method as i64).op(Opcode::LdcI40).op(Opcode::Ret);
+            add_main(&mut app, Body::new(vec![], il));
+            let (_directory, mut runtime) = runtime("unbindable_pinvoke", &corlib(), &app);
+            match runtime.run_main("App") {
+                Err(Error::UnhandledException(report)) => report,
+                result => panic!("{:?}", result),
+            }
+        };
+
+        let missing_library = report("crustyclr-missing", "missing", &[]);
+        let prefix = "System.DllNotFoundException: Unable to load shared library 'crustyclr-missing' or one of its";
+        assert!(missing_library.starts_with(prefix), "{}", missing_library);
+        assert_eq!(
+            concat!(
+                "System.EntryPointNotFoundException: Unable to find an entry point named 'crustyclr_missing' in ",
+                "shared library 'libc'.\n   at Program.Main"
+            ),
+            report("libc", "crustyclr_missing", &[])
+        );
+        assert_eq!(
+            concat!(
+                "System.Runtime.InteropServices.MarshalDirectiveException: Cannot marshal 'parameter #1': There is no ",
+                "marshaling support for this type.\n   at Program.Main"
+            ),
+            report("libc", "free", &[Ty::Object])
+        );
+    }
+}
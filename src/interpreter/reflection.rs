@@ -0,0 +1,832 @@
+use ecma355metadata::cli::{Access, AttributeEnumType, AttributeValue, ConstantValue, CustomAttributeValue,
+                           NamedArgumentKind};
+use ecma355metadata::cli::tables::{TableHandle, TableIndex};
+
+use error::{Error, ExceptionKind};
+use gc::{self, ObjectRef};
+use interpreter::{Pointer, Value};
+use types::{AssemblyId, FieldId, MethodId, MethodSig, Primitive, TypeId, TypeKind};
+use vm::Vm;
+
+/// A type, method or field, as reflection refers to it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Member {
+    Type(TypeId),
+    Method(MethodId),
+    Field(FieldId),
+}
+
+/// Gets the id a `RuntimeTypeHandle`, `RuntimeMethodHandle` or `RuntimeFieldHandle` holds, which is the value of its
+/// only field.
+pub(crate) fn handle_id(handle: &Value) -> Option<u32> {
+    match *handle {
+        Value::Struct(_, ref bytes) if bytes.len() >= 8 => {
+            let mut id = [0; 8];
+            id.copy_from_slice(&bytes[..8]);
+            Some(u64::from_le_bytes(id) as u32)
+        }
+        _ => None,
+    }
+}
+
+impl Vm {
+    /// Executes `ldtoken`: creates the `RuntimeTypeHandle`, `RuntimeMethodHandle` or `RuntimeFieldHandle` of a
+    /// member, which holds its id.
+    pub(super) fn member_handle(&mut self, member: Member) -> Result<Value, Error> {
+        let (name, id) = match member {
+            Member::Type(ty) => ("RuntimeTypeHandle", ty.index()),
+            Member::Method(method) => ("RuntimeMethodHandle", method.index()),
+            Member::Field(field) => ("RuntimeFieldHandle", field.index()),
+        };
+        let handle_type = self.types.corlib_type("System", name)?;
+        self.types.prepare(handle_type)?;
+        let mut bytes = vec![0; self.types.get(handle_type).value_size() as usize];
+        if bytes.len() < 8 {
+            return Err(Error::TypeLoad(format!("{} is too small for an id", self.types.get(handle_type))));
+        }
+        bytes[..8].copy_from_slice(&(id as u64).to_le_bytes());
+        Ok(Value::Struct(handle_type, bytes))
+    }
+
+    /// Gets the object that reflects a member: a `System.RuntimeType`, `System.Reflection.RuntimeMethodInfo` or
+    /// `System.Reflection.RuntimeFieldInfo`. Each member has one, created the first time it is reflected, so they can
+    /// be compared by reference.
+    pub(crate) fn reflect(&mut self, member: Member) -> Result<ObjectRef, Error> {
+        if let Some(&object) = self.reflection_objects.get(&member) {
+            return Ok(object);
+        }
+        let (namespace, name, id) = match member {
+            Member::Type(ty) => ("System", "RuntimeType", ty.index()),
+            Member::Method(method) => ("System.Reflection", "RuntimeMethodInfo", method.index()),
+            Member::Field(field) => ("System.Reflection", "RuntimeFieldInfo", field.index()),
+        };
+        let ty = self.types.corlib_type(namespace, name)?;
+        let offset = self.member_value_offset()?;
+        let object = self.new_object(ty)?;
+        unsafe { *(object.data().add(offset) as *mut u64) = id as u64 };
+        self.reflection_objects.insert(member, object);
+        Ok(object)
+    }
+
+    /// Gets the member a reflection object reflects. Reflection objects the runtime didn't create aren't supported.
+    pub(crate) fn reflected(&mut self, value: &Value) -> Result<Member, Error> {
+        let object = match *value {
+            Value::Ref(object) if object.is_null() => return Err(ExceptionKind::NullReference.into()),
+            Value::Ref(object) => object,
+            _ => return Err(Error::InvalidProgram(format!("{:?} is not a reflection object", value))),
+        };
+        let offset = self.member_value_offset()?;
+        let id = unsafe { *(object.data().add(offset) as *const u64) } as u32;
+        let ty = self.object_type(object);
+        if ty == self.types.corlib_type("System", "RuntimeType")? {
+            Ok(Member::Type(TypeId(id)))
+        } else if ty == self.types.corlib_type("System.Reflection", "RuntimeMethodInfo")? {
+            Ok(Member::Method(MethodId(id)))
+        } else if ty == self.types.corlib_type("System.Reflection", "RuntimeFieldInfo")? {
+            Ok(Member::Field(FieldId(id)))
+        } else {
+            Err(ExceptionKind::NotSupported.into())
+        }
+    }
+
+    /// Gets the type a `System.Type` argument reflects, or `None` if it is null.
+    pub(crate) fn reflected_type(&mut self, value: &Value) -> Result<Option<TypeId>, Error> {
+        if value.as_ref().is_some_and(ObjectRef::is_null) {
+            return Ok(None);
+        }
+        match self.reflected(value)? {
+            Member::Type(ty) => Ok(Some(ty)),
+            _ => Err(Error::InvalidProgram(format!("{:?} is not a type", value))),
+        }
+    }
+
+    /// Creates an array of the objects that reflect members, with an element type such as `System.Type`.
+    pub(crate) fn reflect_all(&mut self, element_type: TypeId, members: &[Member]) -> Result<Value, Error> {
+        for &member in members {
+            self.reflect(member)?;
+        }
+        let array_type = self.types.sz_array(element_type)?;
+        let array = self.new_array(array_type, members.len())?;
+        // The objects may have moved while the array was allocated, so they are looked up again
+        unsafe {
+            for (index, member) in members.iter().enumerate() {
+                gc::write_ref(array.array_element(index, 8), self.reflection_objects[member]);
+            }
+            self.heap.write_barrier(array.array_element(0, 8), members.len() * 8);
+        }
+        Ok(Value::Ref(array))
+    }
+
+    /// Gets the name of a member, as `MemberInfo.Name` does.
+    pub(crate) fn member_name(&self, member: Member) -> String {
+        match member {
+            Member::Type(ty) => self.types.get(ty).name.clone(),
+            Member::Method(method) => self.types.method(method).name.clone(),
+            Member::Field(field) => self.types.field(field).name.clone(),
+        }
+    }
+
+    /// Gets the type that declares a member, as `MemberInfo.DeclaringType` does: a type's is the type it is nested
+    /// in, if any.
+    pub(crate) fn declaring_type(&mut self, member: Member) -> Result<Option<TypeId>, Error> {
+        match member {
+            Member::Type(ty) => self.types.enclosing_type(ty),
+            Member::Method(method) => Ok(Some(self.types.method(method).owner)),
+            Member::Field(field) => Ok(Some(self.types.field(field).owner)),
+        }
+    }
+
+    /// Gets the public methods of a type other than its constructors, as `Type.GetMethods()` does: the methods it
+    /// declares and the instance methods it inherits, leaving out inherited virtual methods it overrides.
+    pub(crate) fn public_methods(&mut self, ty: TypeId) -> Result<Vec<MethodId>, Error> {
+        self.types.prepare(ty)?;
+        let vtable = self.types.get(ty).vtable.clone();
+        let mut methods = Vec::new();
+        let mut current = Some(ty);
+        while let Some(id) = current {
+            self.types.prepare(id)?;
+            methods.extend(self.types.get(id).methods.iter().cloned().filter(|&method_id| {
+                let method = self.types.method(method_id);
+                let inherited = id != ty;
+                let overridden = inherited && method.slot.is_some_and(|slot| vtable.get(slot) != Some(&method_id));
+                let is_constructor = method.name == ".ctor" || method.name == ".cctor";
+                let hidden = is_constructor || overridden || (inherited && method.is_static());
+                method.flags.access() == Access::Public && !hidden
+            }));
+            current = self.types.get(id).base;
+        }
+        Ok(methods)
+    }
+
+    /// Gets the public fields of a type, as `Type.GetFields()` does: the fields it declares and the instance fields
+    /// it inherits.
+    pub(crate) fn public_fields(&mut self, ty: TypeId) -> Result<Vec<FieldId>, Error> {
+        let mut fields = Vec::new();
+        let mut current = Some(ty);
+        while let Some(id) = current {
+            self.types.prepare(id)?;
+            fields.extend(self.types.get(id).fields.iter().cloned().filter(|&field| {
+                let field = self.types.field(field);
+                field.flags.access() == Access::Public && (id == ty || !field.is_static())
+            }));
+            current = self.types.get(id).base;
+        }
+        Ok(fields)
+    }
+
+    /// Finds the public method of a type with a name, as `Type.GetMethod(string)` does, throwing
+    /// `AmbiguousMatchException` if it has more than one.
+    pub(crate) fn public_method(&mut self, ty: TypeId, name: &str) -> Result<Option<MethodId>, Error> {
+        let mut methods = self.public_methods(ty)?;
+        methods.retain(|&method| self.types.method(method).name == name);
+        match methods.len() {
+            0 | 1 => Ok(methods.pop()),
+            _ => Err(ExceptionKind::AmbiguousMatch.into()),
+        }
+    }
+
+    /// Creates the custom attributes applied to a member, as `GetCustomAttributes` does: an array of the attribute
+    /// type a caller asks for, holding the attributes of that type, or else an `object[]` holding all of them.
+    ///
+    /// With `inherit`, the attributes of a type's base types and of the methods a method overrides are included, if
+    /// their `[AttributeUsage]` lets them be inherited. An attribute that doesn't allow multiple uses is only
+    /// inherited if the member doesn't have one of the same type.
+    pub(crate) fn custom_attributes(
+        &mut self,
+        member: Member,
+        attribute_type: Option<TypeId>,
+        inherit: bool,
+    ) -> Result<Value, Error> {
+        if let Some(attribute_type) = attribute_type {
+            if self.types.get(attribute_type).is_value_type() {
+                let message = "Type passed in must be derived from System.Attribute or System.Attribute itself.";
+                let thrown = self.raise_with_message(ExceptionKind::Argument, message)?;
+                return Err(self.throw_from_runtime(thrown));
+            }
+        }
+        let mut found: Vec<(AssemblyId, MethodId, CustomAttributeValue)> = Vec::new();
+        for (index, (assembly, parent)) in self.attribute_parents(member, inherit)?.into_iter().enumerate() {
+            let declared = found.len();
+            for (constructor, value) in self.types.custom_attributes(assembly, parent)? {
+                let ty = self.types.method(constructor).owner;
+                if index > 0 {
+                    let (inherited, allow_multiple) = self.attribute_usage(ty)?;
+                    let has_one = found[..declared].iter().any(|attribute| self.types.method(attribute.1).owner == ty);
+                    if !inherited || (has_one && !allow_multiple) {
+                        continue;
+                    }
+                }
+                if attribute_type.is_none_or(|attribute_type| self.types.is_assignable_to(ty, attribute_type)) {
+                    found.push((assembly, constructor, value));
+                }
+            }
+        }
+
+        // The attributes stay on the stack as the others and the array are created, in case they move
+        for &(assembly, constructor, ref value) in &found {
+            self.new_attribute(assembly, constructor, value)?;
+        }
+        let element_type = match attribute_type {
+            Some(attribute_type) => attribute_type,
+            None => self.types.object()?,
+        };
+        let array_type = self.types.sz_array(element_type)?;
+        let array = self.new_array(array_type, found.len())?;
+        let attributes = self.pop_args(found.len())?;
+        unsafe {
+            for (index, attribute) in attributes.iter().enumerate() {
+                gc::write_ref(array.array_element(index, 8), attribute.as_ref().unwrap());
+            }
+            self.heap.write_barrier(array.array_element(0, 8), attributes.len() * 8);
+        }
+        Ok(Value::Ref(array))
+    }
+
+    /// Gets the metadata rows whose custom attributes a member has: its own, followed by the ones it inherits from
+    /// if `inherit` is set.
+    fn attribute_parents(&mut self, member: Member, inherit: bool) -> Result<Vec<(AssemblyId, TableHandle)>, Error> {
+        let mut parents = Vec::new();
+        match member {
+            Member::Type(ty) => {
+                let mut current = Some(ty);
+                while let Some(id) = current {
+                    if let Some(definition) = self.types.get(id).definition {
+                        parents.push((definition.assembly, TableHandle::new(definition.row, TableIndex::TypeDef)));
+                    }
+                    current = if inherit { self.types.get(id).base } else { None };
+                }
+            }
+            Member::Method(method) => {
+                let (owner, slot) = (self.types.method(method).owner, self.types.method(method).slot);
+                let mut overridden = vec![method];
+                if let (true, Some(slot)) = (inherit, slot) {
+                    let mut current = self.types.get(owner).base;
+                    while let Some(id) = current {
+                        self.types.prepare(id)?;
+                        let base_method = self.types.get(id).vtable.get(slot).cloned();
+                        if let Some(base_method) = base_method {
+                            if self.types.method(base_method).owner == id {
+                                overridden.push(base_method);
+                            }
+                        }
+                        current = self.types.get(id).base;
+                    }
+                }
+                for method in overridden {
+                    if let Some(definition) = self.types.method(method).definition {
+                        parents.push((definition.assembly, TableHandle::new(definition.row, TableIndex::MethodDef)));
+                    }
+                }
+            }
+            Member::Field(field) => {
+                if let Some(definition) = self.types.field(field).definition {
+                    parents.push((definition.assembly, TableHandle::new(definition.row, TableIndex::Field)));
+                }
+            }
+        }
+        Ok(parents)
+    }
+
+    /// Gets whether the `[AttributeUsage]` of an attribute type lets members inherit it and lets it be used more
+    /// than once on a member. An attribute type inherits its usage from its base types, and is inherited but can't
+    /// be used more than once without one.
+    fn attribute_usage(&mut self, attribute_type: TypeId) -> Result<(bool, bool), Error> {
+        let mut current = Some(attribute_type);
+        while let Some(id) = current {
+            if let Some(definition) = self.types.get(id).definition {
+                let parent = TableHandle::new(definition.row, TableIndex::TypeDef);
+                for (constructor, value) in self.types.custom_attributes(definition.assembly, parent)? {
+                    if !self.types.is_corlib_type(self.types.method(constructor).owner, "AttributeUsageAttribute") {
+                        continue;
+                    }
+                    let named = |name: &str, default: bool| {
+                        match value.named_arguments.iter().find(|argument| argument.name == name) {
+                            Some(argument) => argument.value == AttributeValue::Boolean(true),
+                            None => default,
+                        }
+                    };
+                    return Ok((named("Inherited", true), named("AllowMultiple", false)));
+                }
+            }
+            current = self.types.get(id).base;
+        }
+        Ok((true, false))
+    }
+
+    /// Creates a custom attribute by calling its constructor with its fixed arguments, then setting the fields and
+    /// properties its named arguments name, and pushes it.
+    fn new_attribute(
+        &mut self,
+        assembly: AssemblyId,
+        constructor: MethodId,
+        value: &CustomAttributeValue,
+    ) -> Result<(), Error> {
+        let (owner, params) = {
+            let constructor = self.types.method(constructor);
+            (constructor.owner, constructor.signature.params.clone())
+        };
+        if params.len() != value.fixed_arguments.len() {
+            let message = format!("{} has an attribute with the wrong number of arguments", self.types.get(owner));
+            return Err(Error::InvalidProgram(message));
+        }
+        self.initialize_for_call(constructor)?;
+        let object = self.new_object(owner)?;
+        self.push(Value::Ref(object));
+        for (argument, &param) in value.fixed_arguments.iter().zip(&params) {
+            self.attribute_value(assembly, argument, param)?;
+        }
+        let args = self.pop_args(params.len() + 1)?;
+
+        // The attribute is held as a handle while it is constructed and its named arguments are set
+        let base = self.handles.len();
+        self.handles.push(args[0].as_ref().unwrap());
+        let result = self.call_nested(constructor, args).and_then(|_| {
+            for argument in &value.named_arguments {
+                self.named_argument(assembly, owner, argument.kind, &argument.name, &argument.value)?;
+            }
+            Ok(())
+        });
+        let attribute = self.handles[base];
+        self.handles.truncate(base);
+        result?;
+        self.push(Value::Ref(attribute));
+        Ok(())
+    }
+
+    /// Sets a field or property of the attribute held by the last handle to the value of a named argument.
+    fn named_argument(
+        &mut self,
+        assembly: AssemblyId,
+        owner: TypeId,
+        kind: NamedArgumentKind,
+        name: &str,
+        value: &AttributeValue,
+    ) -> Result<(), Error> {
+        let missing = format!("{}::{}", self.types.get(owner), name);
+        match kind {
+            NamedArgumentKind::Field => {
+                let field = self.types.find_field(owner, name)?.ok_or(Error::MissingField(missing))?;
+                let (field_type, offset) = (self.types.field(field).field_type, self.types.field(field).offset);
+                self.attribute_value(assembly, value, field_type)?;
+                let value = self.pop()?;
+                let attribute = *self.handles.last().unwrap();
+                let (_, storage, size) = self.value_layout(field_type);
+                self.store_value(unsafe { attribute.data().add(offset as usize) }, &value, storage, size);
+            }
+            NamedArgumentKind::Property => {
+                let setter = self.property_setter(owner, name)?.ok_or(Error::MissingMethod(missing))?;
+                let property_type = self.types.method(setter).signature.params[0];
+                self.attribute_value(assembly, value, property_type)?;
+                let value = self.pop()?;
+                let attribute = *self.handles.last().unwrap();
+                self.call_nested(setter, vec![Value::Ref(attribute), value])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the setter of an instance property in a type or its base types.
+    fn property_setter(&mut self, owner: TypeId, name: &str) -> Result<Option<MethodId>, Error> {
+        let setter_name = format!("set_{}", name);
+        let mut current = Some(owner);
+        while let Some(id) = current {
+            self.types.prepare(id)?;
+            let setter = self.types.get(id).methods.iter().cloned().find(|&method| {
+                let method = self.types.method(method);
+                method.name == setter_name && !method.is_static() && method.signature.params.len() == 1
+            });
+            if setter.is_some() {
+                return Ok(setter);
+            }
+            current = self.types.get(id).base;
+        }
+        Ok(None)
+    }
+
+    /// Pushes the value of a custom attribute argument as a value of a type, boxing it if the type is `object`.
+    fn attribute_value(&mut self, assembly: AssemblyId, argument: &AttributeValue, ty: TypeId) -> Result<(), Error> {
+        let (value, value_type) = match *argument {
+            AttributeValue::Boolean(x) => (Value::I32(x as i32), self.types.primitive(Primitive::Boolean)?),
+            AttributeValue::Char(x) => (Value::I32(x as i32), self.types.primitive(Primitive::Char)?),
+            AttributeValue::I1(x) => (Value::I32(x as i32), self.types.primitive(Primitive::I1)?),
+            AttributeValue::U1(x) => (Value::I32(x as i32), self.types.primitive(Primitive::U1)?),
+            AttributeValue::I2(x) => (Value::I32(x as i32), self.types.primitive(Primitive::I2)?),
+            AttributeValue::U2(x) => (Value::I32(x as i32), self.types.primitive(Primitive::U2)?),
+            AttributeValue::I4(x) => (Value::I32(x), self.types.primitive(Primitive::I4)?),
+            AttributeValue::U4(x) => (Value::I32(x as i32), self.types.primitive(Primitive::U4)?),
+            AttributeValue::I8(x) => (Value::I64(x), self.types.primitive(Primitive::I8)?),
+            AttributeValue::U8(x) => (Value::I64(x as i64), self.types.primitive(Primitive::U8)?),
+            AttributeValue::R4(x) => (Value::F(x as f64), self.types.primitive(Primitive::R4)?),
+            AttributeValue::R8(x) => (Value::F(x), self.types.primitive(Primitive::R8)?),
+            AttributeValue::String(None) | AttributeValue::Type(None) | AttributeValue::Array(None) => {
+                (Value::null(), ty)
+            }
+            AttributeValue::String(Some(ref value)) => {
+                let chars: Vec<u16> = value.encode_utf16().collect();
+                (Value::Ref(self.new_string(&chars)?), self.types.string()?)
+            }
+            AttributeValue::Type(Some(ref name)) => {
+                let named = self.named_type(assembly, name)?;
+                let runtime_type = self.types.corlib_type("System", "RuntimeType")?;
+                (Value::Ref(self.reflect(Member::Type(named))?), runtime_type)
+            }
+            AttributeValue::Enum(ref enum_type, ref underlying) => {
+                // Enums are only referred to by handle by the constructor parameters of their type
+                let enum_type = match *enum_type {
+                    AttributeEnumType::Handle(_) => ty,
+                    AttributeEnumType::Name(ref name) => self.named_type(assembly, name)?,
+                };
+                self.attribute_value(assembly, underlying, enum_type)?;
+                (self.pop()?, enum_type)
+            }
+            AttributeValue::Array(Some(ref elements)) => {
+                let element_type = match self.types.get(ty).kind {
+                    TypeKind::SzArray(element_type) => element_type,
+                    _ => self.types.object()?,
+                };
+                let array_type = self.types.sz_array(element_type)?;
+                let array = self.new_array(array_type, elements.len())?;
+                self.push(Value::Ref(array));
+                let (_, storage, size) = self.value_layout(element_type);
+                for (index, element) in elements.iter().enumerate() {
+                    self.attribute_value(assembly, element, element_type)?;
+                    let value = self.pop()?;
+                    // The array may have moved while the element was created, so it is read from the stack again
+                    let array = self.frame().stack.last().and_then(Value::as_ref).unwrap();
+                    let address = unsafe { array.array_element(index, size as usize) };
+                    self.store_value(address, &value, storage, size);
+                }
+                (self.pop()?, array_type)
+            }
+        };
+        self.push(value);
+        if self.types.get(value_type).is_value_type() && !self.types.get(ty).is_value_type() {
+            self.box_value(value_type)?;
+        }
+        Ok(())
+    }
+
+    /// Finds the type a custom attribute argument refers to by its serialized name.
+    fn named_type(&mut self, assembly: AssemblyId, name: &str) -> Result<TypeId, Error> {
+        match self.types.find_type_by_name(assembly, name)? {
+            Some(ty) => Ok(ty),
+            None => Err(Error::TypeLoad(name.into())),
+        }
+    }
+
+    /// Calls the method a `MethodInfo` reflects, as `MethodBase.Invoke` does: a virtual method is called on the
+    /// implementation the target's type has, and the arguments are unboxed from an `object[]`, which gets the values
+    /// of `ref` parameters back. Gets what the method returns, boxed, or null for `void`.
+    ///
+    /// An exception the method throws is wrapped in a `TargetInvocationException`.
+    pub(crate) fn invoke_reflected(&mut self, method: MethodId, target: &Value, args: &Value) -> Result<Value, Error> {
+        let (owner, signature, is_virtual) = {
+            let method = self.types.method(method);
+            (method.owner, method.signature.clone(), method.is_virtual())
+        };
+        let (target, args) = match (target.as_ref(), args.as_ref()) {
+            (Some(target), Some(args)) => (target, args),
+            _ => return Err(Error::InvalidProgram("MethodBase.Invoke is given a non-object".into())),
+        };
+        let arg_count = if args.is_null() { 0 } else { unsafe { args.array_length() } };
+        if arg_count != signature.params.len() {
+            return Err(ExceptionKind::TargetParameterCount.into());
+        }
+
+        // Nothing is allocated until the arguments are on the stack, unless an exception is thrown
+        let mut values = Vec::new();
+        let mut method = method;
+        if signature.has_this {
+            if target.is_null() {
+                return self.throw_with_message(ExceptionKind::Target, "Non-static method requires a target.");
+            }
+            if !self.types.is_assignable_to(self.object_type(target), owner) {
+                return self.throw_with_message(ExceptionKind::Target, "Object does not match target type.");
+            }
+            if is_virtual {
+                method = self.types.find_implementation(self.object_type(target), method)?;
+            }
+            if self.types.get(self.types.method(method).owner).is_value_type() {
+                values.push(Value::ByRef(Pointer::into_object(target, 0)));
+            } else {
+                values.push(Value::Ref(target));
+            }
+        }
+        for (index, &param) in signature.params.iter().enumerate() {
+            let element = unsafe { args.array_element(index, 8) };
+            let arg = unsafe { gc::read_ref(element) };
+            let value = match self.types.get(param).kind {
+                TypeKind::ByRef(target_type) if self.types.get(target_type).is_value_type() => {
+                    if arg.is_null() || self.object_type(arg) != target_type {
+                        return Err(ExceptionKind::NotSupported.into());
+                    }
+                    Value::ByRef(Pointer::into_object(arg, 0))
+                }
+                TypeKind::ByRef(_) => Value::ByRef(Pointer::into_object(args, element as usize - args.data() as usize)),
+                TypeKind::Pointer(_) => return Err(ExceptionKind::NotSupported.into()),
+                _ => self.unbox_argument(arg, param)?,
+            };
+            values.push(value);
+        }
+
+        let result = match self.call_nested(method, values) {
+            Ok(result) => result,
+            Err(Error::Exception(kind)) => {
+                let inner = self.raise(kind)?.exception;
+                return self.throw_target_invocation(inner);
+            }
+            Err(Error::UnhandledException(_)) if self.unhandled.is_some() => {
+                let inner = self.unhandled.take().unwrap().exception;
+                return self.throw_target_invocation(inner);
+            }
+            Err(error) => return Err(error),
+        };
+        match (result, signature.ret) {
+            (Some(result), Some(ret)) => self.box_result(result, ret),
+            _ => Ok(Value::null()),
+        }
+    }
+
+    /// Gets the value of the field a `FieldInfo` reflects, boxed, as `FieldInfo.GetValue` does. Static fields
+    /// ignore the target.
+    pub(crate) fn field_value(&mut self, field: FieldId, target: &Value) -> Result<Value, Error> {
+        let (field_type, is_literal) = (self.types.field(field).field_type, self.types.field(field).is_literal());
+        if is_literal {
+            let value = match self.types.field(field).constant.clone() {
+                Some(ConstantValue::String(chars)) => return Ok(Value::Ref(self.new_string(&chars)?)),
+                Some(ConstantValue::Null) => return Ok(Value::null()),
+                Some(ConstantValue::R4(x)) => Value::F(x as f64),
+                Some(ConstantValue::R8(x)) => Value::F(x),
+                Some(ConstantValue::I8(x)) => Value::I64(x),
+                Some(ConstantValue::U8(x)) => Value::I64(x as i64),
+                Some(constant) => Value::I32(constant.as_i64().unwrap_or_default() as i32),
+                None => return Err(Error::InvalidProgram(format!("{} has no value", self.types.field(field).name))),
+            };
+            return self.box_result(value, field_type);
+        }
+        let address = self.field_address(field, target)?;
+        let (ty, storage, size) = self.value_layout(field_type);
+        let value = unsafe { Value::load(address, storage, ty, size) };
+        self.box_result(value, field_type)
+    }
+
+    /// Sets the field a `FieldInfo` reflects to a value unboxed from an object, as `FieldInfo.SetValue` does.
+    pub(crate) fn set_field_value(&mut self, field: FieldId, target: &Value, value: &Value) -> Result<(), Error> {
+        if self.types.field(field).is_literal() {
+            return Err(ExceptionKind::FieldAccess.into());
+        }
+        let field_type = self.types.field(field).field_type;
+        let address = self.field_address(field, target)?;
+        let value = self.unbox_argument(value.as_ref().unwrap_or(ObjectRef::NULL), field_type)?;
+        let (_, storage, size) = self.value_layout(field_type);
+        self.store_value(address, &value, storage, size);
+        Ok(())
+    }
+
+    /// Gets the address of a static field, initializing its type, or of an instance field in a target object.
+    fn field_address(&mut self, field: FieldId, target: &Value) -> Result<*mut u8, Error> {
+        if self.types.field(field).is_static() {
+            return self.static_field_address(field);
+        }
+        let (owner, offset) = (self.types.field(field).owner, self.types.field(field).offset);
+        let target = match *target {
+            Value::Ref(target) if target.is_null() => {
+                return self.throw_with_message(ExceptionKind::Target, "Non-static field requires a target.");
+            }
+            Value::Ref(target) => target,
+            _ => return Err(Error::InvalidProgram("FieldInfo is given a non-object target".into())),
+        };
+        if !self.types.is_assignable_to(self.object_type(target), owner) {
+            let message = format!(
+                "Field '{}' defined on type '{}' is not a field on the target object which is of type '{}'.",
+                self.types.field(field).name,
+                self.types.get(owner),
+                self.types.get(self.object_type(target))
+            );
+            return self.throw_with_message(ExceptionKind::Argument, &message);
+        }
+        Ok(unsafe { target.data().add(offset as usize) })
+    }
+
+    /// Gets the value an object passed to reflection holds as a value of a type: a reference if it is an instance of
+    /// a reference type, or the value it boxes for a value type. Null is the default value of a value type.
+    fn unbox_argument(&mut self, object: ObjectRef, ty: TypeId) -> Result<Value, Error> {
+        self.types.prepare(ty)?;
+        let (ty, storage, size) = self.value_layout(ty);
+        if !self.types.get(ty).is_value_type() {
+            if object.is_null() || self.types.is_assignable_to(self.object_type(object), ty) {
+                return Ok(Value::Ref(object));
+            }
+            return self.throw_conversion(object, ty);
+        }
+        let mut bytes = vec![0; size as usize];
+        match self.nullable_layout(ty)? {
+            Some(nullable) => {
+                if !object.is_null() && self.check_boxed(object, nullable.underlying).is_err() {
+                    return self.throw_conversion(object, ty);
+                }
+                unsafe { self.fill_nullable(bytes.as_mut_ptr(), &nullable, object) };
+                Ok(Value::Struct(ty, bytes))
+            }
+            None if object.is_null() => Ok(unsafe { Value::load(bytes.as_ptr(), storage, ty, size) }),
+            None => {
+                if self.check_boxed(object, ty).is_err() {
+                    return self.throw_conversion(object, ty);
+                }
+                Ok(unsafe { Value::load(object.data(), storage, ty, size) })
+            }
+        }
+    }
+
+    /// Boxes a value of a type returned to a caller of reflection, leaving references as they are.
+    fn box_result(&mut self, value: Value, ty: TypeId) -> Result<Value, Error> {
+        match self.types.get(ty).kind {
+            TypeKind::ByRef(_) | TypeKind::Pointer(_) => Err(ExceptionKind::NotSupported.into()),
+            _ if self.types.get(ty).is_value_type() => {
+                // The value stays on the stack while it is boxed, so the objects it refers to stay alive
+                self.types.prepare(ty)?;
+                self.push(value);
+                self.box_value(ty)?;
+                self.pop()
+            }
+            _ => Ok(value),
+        }
+    }
+
+    fn throw_conversion<T>(&mut self, object: ObjectRef, ty: TypeId) -> Result<T, Error> {
+        let message = format!(
+            "Object of type '{}' cannot be converted to type '{}'.",
+            self.types.get(self.object_type(object)),
+            self.types.get(ty)
+        );
+        self.throw_with_message(ExceptionKind::Argument, &message)
+    }
+
+    fn throw_with_message<T>(&mut self, kind: ExceptionKind, message: &str) -> Result<T, Error> {
+        let thrown = self.raise_with_message(kind, message)?;
+        Err(self.throw_from_runtime(thrown))
+    }
+
+    /// Throws a `TargetInvocationException` wrapping an exception a method called through reflection threw.
+    fn throw_target_invocation<T>(&mut self, inner: ObjectRef) -> Result<T, Error> {
+        let exception_type = self.types.corlib_type("System.Reflection", "TargetInvocationException")?;
+        let exception = self.types.corlib_type("System", "Exception")?;
+        self.types.prepare(exception_type)?;
+        let signature = MethodSig {
+            has_this: true,
+            ret: None,
+            params: vec![exception],
+        };
+        let constructor = self.types
+            .find_method(exception_type, ".ctor", &signature)?
+            .ok_or_else(|| Error::MissingMethod(format!("{}::.ctor", self.types.get(exception_type))))?;
+
+        // The inner exception is held as a handle while the exception is allocated, in case it moves
+        self.handles.push(inner);
+        let allocated = self.new_object(exception_type);
+        let inner = self.handles.pop().unwrap();
+        let object = allocated?;
+        self.handles.push(object);
+        let result = self.invoke(constructor, vec![Value::Ref(object), Value::Ref(inner)]);
+        let object = self.handles.pop().unwrap();
+        result?;
+        let thrown = self.thrown_here(object);
+        Err(self.throw_from_runtime(thrown))
+    }
+
+    /// Gets the offset of `MemberInfo._value`, which holds the id of the member a reflection object reflects.
+    fn member_value_offset(&mut self) -> Result<usize, Error> {
+        let member_info = self.types.corlib_type("System.Reflection", "MemberInfo")?;
+        self.field_offset(member_info, "_value")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecma355metadata::cli::il::Opcode;
+
+    use test_assembly::*;
+
+    /// References `Type.GetTypeFromHandle`, `Type.GetMethods`, `Type.GetMethod`, `MemberInfo.GetCustomAttributes`
+    /// and `MethodBase.Invoke` in the core library.
+    fn reflection_methods(app: &mut AssemblyBuilder) -> (u32, u32, u32, u32, u32) {
+        let ty = app.corlib_type("System", "Type");
+        let handle = app.corlib_type("System", "RuntimeTypeHandle");
+        let method_info = app.corlib_type("System.Reflection", "MethodInfo");
+        let member_info = app.corlib_type("System.Reflection", "MemberInfo");
+        let method_base = app.corlib_type("System.Reflection", "MethodBase");
+        let signature = method_sig(false, Ty::Class(ty), &[Ty::ValueType(handle)]);
+        let get_type_from_handle = app.member_ref(ty, "GetTypeFromHandle", &signature);
+        let signature = method_sig(true, Ty::sz_array(Ty::Class(method_info)), &[]);
+        let get_methods = app.member_ref(ty, "GetMethods", &signature);
+        let signature = method_sig(true, Ty::Class(method_info), &[Ty::String]);
+        let get_method = app.member_ref(ty, "GetMethod", &signature);
+        let signature = method_sig(true, Ty::sz_array(Ty::Object), &[Ty::Class(ty), Ty::Boolean]);
+        let get_custom_attributes = app.member_ref(member_info, "GetCustomAttributes", &signature);
+        let signature = method_sig(true, Ty::Object, &[Ty::Object, Ty::sz_array(Ty::Object)]);
+        let invoke = app.member_ref(method_base, "Invoke", &signature);
+        (get_type_from_handle, get_methods, get_method, get_custom_attributes, invoke)
+    }
+
+    /// Defines a public static method `int name() { return value; }` in the type defined last.
+    fn define_constant(app: &mut AssemblyBuilder, name: &str, value: i32) -> u32 {
+        let mut il = Il::new();
+        il.ldc_i4(value).op(Opcode::Ret);
+        app.method(STATIC | PUBLIC_METHOD, name, &method_sig(false, Ty::I4, &[]), Some(Body::new(vec![], il)))
+    }
+
+    #[test]
+    pub fn test_methods_are_found_by_their_custom_attribute_and_invoked() {
+        // class TestAttribute : Attribute { }
+        // class Tests { [Test] public static int One() => 1; [Test] public static int Twenty() => 20;
+        //               public static int Skipped() => 300; }
+        let mut app = AssemblyBuilder::new("App");
+        let (get_type_from_handle, get_methods, _, get_custom_attributes, invoke) = reflection_methods(&mut app);
+        let method_info = app.corlib_type("System.Reflection", "MethodInfo");
+        let int32 = app.corlib_type("System", "Int32");
+        let object = app.corlib_type("System", "Object");
+        let attribute = app.corlib_type("System", "Attribute");
+        let default_ctor = method_sig(true, Ty::Void, &[]);
+        let attribute_ctor = app.member_ref(attribute, ".ctor", &default_ctor);
+        let test_attribute = app.type_def(PUBLIC, "", "TestAttribute", attribute);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, attribute_ctor as i64).op(Opcode::Ret);
+        let test_ctor = app.method(CONSTRUCTOR, ".ctor", &default_ctor, Some(Body::new(vec![], il)));
+        let tests = app.type_def(PUBLIC, "", "Tests", object);
+        let one = define_constant(&mut app, "One", 1);
+        let twenty = define_constant(&mut app, "Twenty", 20);
+        define_constant(&mut app, "Skipped", 300);
+        app.custom_attribute(one, test_ctor);
+        app.custom_attribute(twenty, test_ctor);
+
+        // MethodInfo[] methods = typeof(Tests).GetMethods(); int total = 0;
+        // for (int i = 0; i < methods.Length; i++)
+        //     if (methods[i].GetCustomAttributes(typeof(TestAttribute), false).Length != 0)
+        //         total += (int)methods[i].Invoke(null, null);
+        // return total;
+        let mut il = Il::new();
+        let (body, next, condition) = (il.label(), il.label(), il.label());
+        il.arg(Opcode::Ldtoken, tests as i64).arg(Opcode::Call, get_type_from_handle as i64);
+        il.arg(Opcode::Callvirt, get_methods as i64).op(Opcode::Stloc0);
+        il.op(Opcode::LdcI40).op(Opcode::Stloc1).op(Opcode::LdcI40).op(Opcode::Stloc2).branch(Opcode::BrS, condition);
+        il.mark(body).op(Opcode::Ldloc0).op(Opcode::Ldloc2).op(Opcode::LdelemRef);
+        il.arg(Opcode::Ldtoken, test_attribute as i64).arg(Opcode::Call, get_type_from_handle as i64);
+        il.op(Opcode::LdcI40).arg(Opcode::Callvirt, get_custom_attributes as i64);
+        il.op(Opcode::Ldlen).op(Opcode::ConvI4).branch(Opcode::BrfalseS, next);
+        il.op(Opcode::Ldloc1).op(Opcode::Ldloc0).op(Opcode::Ldloc2).op(Opcode::LdelemRef);
+        il.op(Opcode::Ldnull).op(Opcode::Ldnull).arg(Opcode::Callvirt, invoke as i64);
+        il.arg(Opcode::UnboxAny, int32 as i64).op(Opcode::Add).op(Opcode::Stloc1);
+        il.mark(next).op(Opcode::Ldloc2).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc2);
+        il.mark(condition).op(Opcode::Ldloc2).op(Opcode::Ldloc0).op(Opcode::Ldlen).op(Opcode::ConvI4);
+        il.branch(Opcode::BltS, body).op(Opcode::Ldloc1).op(Opcode::Ret);
+        let locals = vec![Ty::sz_array(Ty::Class(method_info)), Ty::I4, Ty::I4];
+        add_main(&mut app, Body::new(locals, il));
+
+        assert_eq!(21, run("test_methods_are_found_by_their_custom_attribute_and_invoked", &corlib(), &app).unwrap());
+    }
+
+    #[test]
+    pub fn typeof_gives_one_type_object_and_invoke_wraps_thrown_exceptions() {
+        // class Program { public static int Throws() { throw new InvalidCastException(); } }
+        let mut app = AssemblyBuilder::new("App");
+        let (get_type_from_handle, _, get_method, _, invoke) = reflection_methods(&mut app);
+        let object = app.corlib_type("System", "Object");
+        let invalid_cast = app.corlib_type("System", "InvalidCastException");
+        let target_invocation = app.corlib_type("System.Reflection", "TargetInvocationException");
+        let invalid_cast_ctor = app.member_ref(invalid_cast, ".ctor", &method_sig(true, Ty::Void, &[]));
+        let program = app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.arg(Opcode::Newobj, invalid_cast_ctor as i64).op(Opcode::Throw);
+        let signature = method_sig(false, Ty::I4, &[]);
+        app.method(STATIC | PUBLIC_METHOD, "Throws", &signature, Some(Body::new(vec![], il)));
+
+        // int result = typeof(Program) == typeof(Program) ? 1 : 0;
+        // try { typeof(Program).GetMethod("Throws").Invoke(null, null); }
+        // catch (TargetInvocationException) { result += 10; }
+        // return result;
+        let throws = app.user_string("Throws");
+        let mut il = Il::new();
+        let (try_start, handler_start, end) = (il.label(), il.label(), il.label());
+        il.arg(Opcode::Ldtoken, program as i64).arg(Opcode::Call, get_type_from_handle as i64);
+        il.arg(Opcode::Ldtoken, program as i64).arg(Opcode::Call, get_type_from_handle as i64);
+        il.op(Opcode::Ceq).op(Opcode::Stloc0);
+        il.mark(try_start).arg(Opcode::Ldtoken, program as i64).arg(Opcode::Call, get_type_from_handle as i64);
+        il.arg(Opcode::Ldstr, throws as i64).arg(Opcode::Callvirt, get_method as i64);
+        il.op(Opcode::Ldnull).op(Opcode::Ldnull).arg(Opcode::Callvirt, invoke as i64).op(Opcode::Pop);
+        il.branch(Opcode::LeaveS, end);
+        il.mark(handler_start).op(Opcode::Pop).op(Opcode::Ldloc0).ldc_i4(10).op(Opcode::Add).op(Opcode::Stloc0);
+        il.branch(Opcode::LeaveS, end);
+        il.mark(end).op(Opcode::Ldloc0).op(Opcode::Ret);
+        let body = Body::new(vec![Ty::I4], il).with_clauses(vec![Clause {
+            handler: Handler::Catch(target_invocation),
+            try_start,
+            try_end: handler_start,
+            handler_start,
+            handler_end: end,
+        }]);
+        add_main(&mut app, body);
+
+        let name = "typeof_gives_one_type_object_and_invoke_wraps_thrown_exceptions";
+        assert_eq!(11, run(name, &corlib(), &app).unwrap());
+    }
+}
@@ -0,0 +1,342 @@
+use error::Error;
+use gc::ObjectRef;
+use interpreter::Value;
+use types::{MethodId, MethodSig, TypeId};
+use vm::Vm;
+
+/// How far a type's initializer (its `.cctor`) has got.
+#[derive(Copy, Clone, Debug)]
+pub enum TypeInit {
+    /// The initializer is running on a thread, by its index. Code it runs that uses the type, directly or through
+    /// other types' initializers, sees the type as it is rather than waiting for the initializer to finish, which
+    /// would never happen. Other threads wait for it to finish.
+    Running(usize),
+    Done,
+
+    /// The initializer threw an exception, and this `TypeInitializationException` wrapping it is thrown again by
+    /// every later use of the type.
+    Failed(ObjectRef),
+}
+
+impl Vm {
+    /// Runs a type's initializer, if it has one and it hasn't run yet, as ECMA-335 II.10.5.3 describes.
+    ///
+    /// If the initializer throws, the exception is wrapped in a `TypeInitializationException`, which is kept in
+    /// `self.unhandled` for the interpreter to throw from the current instruction.
+    pub(crate) fn initialize_type(&mut self, ty: TypeId) -> Result<(), Error> {
+        loop {
+            match self.type_inits.get(&ty) {
+                Some(&TypeInit::Running(thread)) if thread == self.current_thread => return Ok(()),
+                Some(&TypeInit::Running(_)) => {
+                    self.block_until(|vm| !matches!(vm.type_inits.get(&ty), Some(&TypeInit::Running(_))));
+                }
+                Some(&TypeInit::Done) => return Ok(()),
+                Some(&TypeInit::Failed(exception)) => {
+                    let thrown = self.thrown_here(exception);
+                    return Err(self.throw_from_runtime(thrown));
+                }
+                None => break,
+            }
+        }
+        self.types.prepare(ty)?;
+        let initializer = self.types.get(ty).methods.iter().cloned().find(|&method| {
+            let method = self.types.method(method);
+            method.is_static() && method.name == ".cctor"
+        });
+        let initializer = match initializer {
+            Some(initializer) => initializer,
+            None => {
+                self.type_inits.insert(ty, TypeInit::Done);
+                return Ok(());
+            }
+        };
+
+        debug!(self.logger, "running type initializer"; "type" => self.types.get(ty).to_string());
+        let thread = self.current_thread;
+        self.type_inits.insert(ty, TypeInit::Running(thread));
+        let result = self.invoke(initializer, Vec::new());
+        self.scheduler.signal();
+        match result {
+            Ok(_) => {
+                self.type_inits.insert(ty, TypeInit::Done);
+                Ok(())
+            }
+            Err(Error::UnhandledException(_)) if self.unhandled.is_some() => {
+                let inner = self.unhandled.take().unwrap().exception;
+                let exception = self.type_initialization_exception(ty, inner)?;
+                self.type_inits.insert(ty, TypeInit::Failed(exception));
+                let thrown = self.thrown_here(exception);
+                Err(self.throw_from_runtime(thrown))
+            }
+            Err(error) => {
+                self.type_inits.remove(&ty);
+                Err(error)
+            }
+        }
+    }
+
+    /// Runs the initializer of a method's type before the method is called, if that is the type's first use: a
+    /// call to a static method or constructor, or to any method of a value type. The initializers of `beforefieldinit`
+    /// types only run when their static fields are first accessed.
+    pub(crate) fn initialize_for_call(&mut self, method: MethodId) -> Result<(), Error> {
+        let (owner, first_use) = {
+            let method = self.types.method(method);
+            let owner = self.types.get(method.owner);
+            let first_use = method.is_static() || method.name == ".ctor" || owner.is_value_type();
+            (method.owner, first_use && method.name != ".cctor" && !owner.is_before_field_init())
+        };
+        if first_use {
+            self.initialize_type(owner)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Creates the `TypeInitializationException` thrown when a type's initializer throws an exception, with the
+    /// message .NET gives it.
+    fn type_initialization_exception(&mut self, ty: TypeId, inner: ObjectRef) -> Result<ObjectRef, Error> {
+        let exception_type = self.types.corlib_type("System", "TypeInitializationException")?;
+        let string = self.types.string()?;
+        let exception = self.types.corlib_type("System", "Exception")?;
+        self.types.prepare(exception_type)?;
+        let signature = MethodSig {
+            has_this: true,
+            ret: None,
+            params: vec![string, string, exception],
+        };
+        let constructor = self.types
+            .get(exception_type)
+            .methods
+            .iter()
+            .cloned()
+            .find(|&method| {
+                let method = self.types.method(method);
+                method.name == ".ctor" && method.signature == signature
+            })
+            .ok_or_else(|| Error::MissingMethod(format!("{}::.ctor", self.types.get(exception_type))))?;
+
+        // The objects are held as handles while the others are allocated, in case they move
+        let type_name: Vec<u16> = self.types.get(ty).to_string().encode_utf16().collect();
+        let message = format!("The type initializer for '{}' threw an exception.", self.types.get(ty));
+        let message: Vec<u16> = message.encode_utf16().collect();
+        let base = self.handles.len();
+        self.handles.push(inner);
+        let allocated = self.new_string(&type_name)
+            .map(|type_name| self.handles.push(type_name))
+            .and_then(|()| self.new_string(&message))
+            .map(|message| self.handles.push(message))
+            .and_then(|()| self.new_object(exception_type));
+        let held = self.handles.split_off(base);
+        let object = allocated?;
+        let args = vec![Value::Ref(object), Value::Ref(held[1]), Value::Ref(held[2]), Value::Ref(held[0])];
+        self.handles.push(object);
+        let result = self.invoke(constructor, args);
+        let object = self.handles.pop().unwrap();
+        result?;
+        Ok(object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecma355metadata::cli::il::Opcode;
+
+    use error::Error;
+    use gc::GcMode;
+    use runtime::RuntimeBuilder;
+    use test_assembly::*;
+
+    /// Defines a type initializer for the type defined last, whose body `build_body` emits before its `ret`.
+    fn type_initializer<F: FnOnce(&mut Il)>(app: &mut AssemblyBuilder, build_body: F) {
+        let mut il = Il::new();
+        build_body(&mut il);
+        il.op(Opcode::Ret);
+        app.method(TYPE_INITIALIZER, ".cctor", &method_sig(false, Ty::Void, &[]), Some(Body::new(vec![], il)));
+    }
+
+    /// Builds an application with a `Trace` class with a static `int trace` field, and a `Broken` class with a static
+    /// `int value` field, whose initializer traces 1 then throws null (so a `NullReferenceException`). `build_main`
+    /// builds the body of `Main` from the tokens of `trace` and `value`.
+    fn app_with_broken_type<F: FnOnce(&mut AssemblyBuilder, u32, u32) -> Body>(build_main: F) -> AssemblyBuilder {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        app.type_def(PUBLIC, "", "Trace", object);
+        let trace_field = app.field(STATIC_FIELD, "trace", Ty::I4);
+        app.type_def(PUBLIC, "", "Broken", object);
+        let value = app.field(STATIC_FIELD, "value", Ty::I4);
+        type_initializer(&mut app, |il| {
+            trace(il, trace_field, 1);
+            il.op(Opcode::Ldnull).op(Opcode::Throw);
+        });
+        app.type_def(PUBLIC, "", "Program", object);
+        let main = build_main(&mut app, trace_field, value);
+        add_main(&mut app, main);
+        app
+    }
+
+    #[test]
+    pub fn type_initializers_run_once_when_their_type_is_first_used() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        app.type_def(PUBLIC, "", "Trace", object);
+        let trace_field = app.field(STATIC_FIELD, "trace", Ty::I4);
+        let returns_7 = || {
+            let mut il = Il::new();
+            il.ldc_i4(7).op(Opcode::Ret);
+            Some(Body::new(vec![], il))
+        };
+
+        // Precise's initializer runs when one of its static methods is first called
+        app.type_def(PUBLIC, "", "Precise", object);
+        let precise_value = app.field(STATIC_FIELD, "value", Ty::I4);
+        type_initializer(&mut app, |il| {
+            trace(il, trace_field, 1);
+            il.ldc_i4(5).arg(Opcode::Stsfld, precise_value as i64);
+        });
+        let get = app.method(STATIC, "Get", &method_sig(false, Ty::I4, &[]), returns_7());
+
+        // Lazy is beforefieldinit, so its initializer only runs when one of its static fields is first accessed
+        app.type_def(PUBLIC | BEFORE_FIELD_INIT, "", "Lazy", object);
+        let lazy_value = app.field(STATIC_FIELD, "value", Ty::I4);
+        type_initializer(&mut app, |il| trace(il, trace_field, 2));
+        let touch = app.method(STATIC, "Touch", &method_sig(false, Ty::I4, &[]), returns_7());
+
+        // Lazy.Touch(); trace 3; Precise.Get(); Precise.Get(); _ = Lazy.value; return trace * 10 + Precise.value;
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.arg(Opcode::Call, touch as i64).op(Opcode::Pop);
+        trace(&mut il, trace_field, 3);
+        il.arg(Opcode::Call, get as i64).op(Opcode::Pop).arg(Opcode::Call, get as i64).op(Opcode::Pop);
+        il.arg(Opcode::Ldsfld, lazy_value as i64).op(Opcode::Pop);
+        il.arg(Opcode::Ldsfld, trace_field as i64).ldc_i4(10).op(Opcode::Mul);
+        il.arg(Opcode::Ldsfld, precise_value as i64).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        assert_eq!(Ok(3125), run("type_initializers", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn type_initializers_that_use_their_type_recursively_see_it_uninitialized() {
+        // A's initializer sets A.value = B.value + 10, and B's sets B.value = A.value + 1 while A.value is still 0
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        app.type_def(PUBLIC, "", "Trace", object);
+        let trace_field = app.field(STATIC_FIELD, "trace", Ty::I4);
+        app.type_def(PUBLIC, "", "A", object);
+        let a_value = app.field(STATIC_FIELD, "value", Ty::I4);
+        // B.value is defined next, so it is the next Field row
+        let b_value = a_value + 1;
+        type_initializer(&mut app, |il| {
+            trace(il, trace_field, 1);
+            il.arg(Opcode::Ldsfld, b_value as i64).ldc_i4(10).op(Opcode::Add);
+            il.arg(Opcode::Stsfld, a_value as i64);
+        });
+        app.type_def(PUBLIC, "", "B", object);
+        assert_eq!(b_value, app.field(STATIC_FIELD, "value", Ty::I4));
+        type_initializer(&mut app, |il| {
+            trace(il, trace_field, 2);
+            il.arg(Opcode::Ldsfld, a_value as i64).op(Opcode::LdcI41).op(Opcode::Add);
+            il.arg(Opcode::Stsfld, b_value as i64);
+        });
+
+        // return trace * 100 + A.value;
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.arg(Opcode::Ldsfld, a_value as i64).op(Opcode::Pop);
+        il.arg(Opcode::Ldsfld, trace_field as i64).ldc_i4(100).op(Opcode::Mul);
+        il.arg(Opcode::Ldsfld, a_value as i64).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        assert_eq!(Ok(1211), run("recursive_type_initializers", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn failed_type_initializers_throw_the_same_type_initialization_exception() {
+        // try { _ = Broken.value; } catch (TypeInitializationException e) { first = e; }
+        // try { _ = Broken.value; } catch (TypeInitializationException e) { second = e; }
+        // return (first != null) * 100 + (first == second) * 10 + trace;
+        let app = app_with_broken_type(|app, trace_field, value| {
+            let exception = app.corlib_type("System", "TypeInitializationException");
+            let mut il = Il::new();
+            let labels: Vec<_> = (0..5).map(|_| il.label()).collect();
+            il.mark(labels[0]).arg(Opcode::Ldsfld, value as i64).op(Opcode::Pop).branch(Opcode::LeaveS, labels[2]);
+            il.mark(labels[1]).op(Opcode::Stloc0).branch(Opcode::LeaveS, labels[2]);
+            il.mark(labels[2]).arg(Opcode::Ldsfld, value as i64).op(Opcode::Pop).branch(Opcode::LeaveS, labels[4]);
+            il.mark(labels[3]).op(Opcode::Stloc1).branch(Opcode::LeaveS, labels[4]);
+            il.mark(labels[4]).op(Opcode::Ldloc0).op(Opcode::Ldnull).op(Opcode::CgtUn).ldc_i4(100).op(Opcode::Mul);
+            il.op(Opcode::Ldloc0).op(Opcode::Ldloc1).op(Opcode::Ceq).ldc_i4(10).op(Opcode::Mul).op(Opcode::Add);
+            il.arg(Opcode::Ldsfld, trace_field as i64).op(Opcode::Add).op(Opcode::Ret);
+            Body::new(vec![Ty::Object, Ty::Object], il).with_clauses(vec![
+                Clause {
+                    handler: Handler::Catch(exception),
+                    try_start: labels[0],
+                    try_end: labels[1],
+                    handler_start: labels[1],
+                    handler_end: labels[2],
+                },
+                Clause {
+                    handler: Handler::Catch(exception),
+                    try_start: labels[2],
+                    try_end: labels[3],
+                    handler_start: labels[3],
+                    handler_end: labels[4],
+                },
+            ])
+        });
+
+        assert_eq!(Ok(111), run("failed_type_initializer", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn unhandled_type_initialization_exceptions_report_the_inner_exception() {
+        let app = app_with_broken_type(|_, _, value| {
+            let mut il = Il::new();
+            il.arg(Opcode::Ldsfld, value as i64).op(Opcode::Ret);
+            Body::new(vec![], il)
+        });
+
+        // The exceptions are created while objects move on every allocation
+        let builder = RuntimeBuilder::new().gc_mode(GcMode::Generational).gc_stress(true);
+        let (_directory, mut runtime) = runtime_with("unhandled_type_initializer", &corlib(), &app, builder);
+        let report = concat!(
+            "System.TypeInitializationException: The type initializer for 'Broken' threw an exception.\n",
+            " ---> System.NullReferenceException: Object reference not set to an instance of an object.\n",
+            "   --- End of inner exception stack trace ---\n",
+            "   at Program.Main"
+        );
+        assert_eq!(Err(Error::UnhandledException(report.into())), runtime.run_main("App"));
+    }
+
+    #[test]
+    pub fn arrays_are_initialized_from_field_data() {
+        // static __StaticArrayInitTypeSize=12 data = { 3, 5, 7 };
+        // int[] array = new int[3]; RuntimeHelpers.InitializeArray(array, ldtoken data);
+        // return (array[0] * 100 + array[1] * 10 + array[2]) * 10 + the last int of data;
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let value_type = app.corlib_type("System", "ValueType");
+        let int32 = app.corlib_type("System", "Int32");
+        let array = app.corlib_type("System", "Array");
+        let field_handle = app.corlib_type("System", "RuntimeFieldHandle");
+        let runtime_helpers = app.corlib_type("System.Runtime.CompilerServices", "RuntimeHelpers");
+        let signature = method_sig(false, Ty::Void, &[Ty::Class(array), Ty::ValueType(field_handle)]);
+        let initialize_array = app.member_ref(runtime_helpers, "InitializeArray", &signature);
+        let data_type = app.type_def(SEALED, "", "__StaticArrayInitTypeSize=12", value_type);
+        app.class_layout(data_type, 12);
+        app.type_def(PUBLIC, "", "Program", object);
+        let data = app.field(RVA_FIELD, "data", Ty::ValueType(data_type));
+        app.field_rva(data, &[3, 0, 0, 0, 5, 0, 0, 0, 7, 0, 0, 0]);
+
+        let mut il = Il::new();
+        il.op(Opcode::LdcI43).arg(Opcode::Newarr, int32 as i64).op(Opcode::Dup);
+        il.arg(Opcode::Ldtoken, data as i64).arg(Opcode::Call, initialize_array as i64).op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI40).op(Opcode::LdelemI4).ldc_i4(100).op(Opcode::Mul);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI41).op(Opcode::LdelemI4).ldc_i4(10).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI42).op(Opcode::LdelemI4).op(Opcode::Add).ldc_i4(10).op(Opcode::Mul);
+        il.arg(Opcode::Ldsflda, data as i64).op(Opcode::LdcI48).op(Opcode::Add).op(Opcode::LdindI4).op(Opcode::Add);
+        il.op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::sz_array(Ty::I4)], il));
+
+        assert_eq!(Ok(3577), run("initialize_array", &corlib(), &app));
+    }
+}
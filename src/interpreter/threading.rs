@@ -0,0 +1,538 @@
+use std::mem;
+use std::thread;
+use std::time::Duration;
+
+use ecma355metadata::cli::tables::{TableHandle, TableIndex};
+
+use error::{Error, ExceptionKind};
+use gc::ObjectRef;
+use interpreter::Value;
+use threads::{ManagedThread, SyncBlock};
+use types::{FieldId, MethodId, MethodSig, TypeId};
+use vm::Vm;
+
+/// How many safepoints a thread passes in its turn before it hands the lock to a thread waiting for one.
+const SAFEPOINTS_PER_TURN: u32 = 1000;
+
+/// The `Vm`, for the OS thread running a managed thread. The thread only uses it while it holds the scheduler's
+/// lock.
+struct VmPointer(*mut Vm);
+
+unsafe impl Send for VmPointer {}
+
+impl Vm {
+    /// Hands the lock to the threads waiting for a turn, if this thread has had a long enough one. Other threads,
+    /// including a collection, only run while this one is at a safepoint: at a backward branch or a method's start.
+    pub(super) fn safepoint(&mut self) {
+        if self.scheduler.is_contended() {
+            self.safepoints += 1;
+            if self.safepoints >= SAFEPOINTS_PER_TURN {
+                self.yield_turn();
+            }
+        }
+    }
+
+    /// Lets the threads waiting for a turn run, then waits for another turn.
+    pub(crate) fn yield_turn(&mut self) {
+        let thread = self.park();
+        self.scheduler.acquire();
+        self.unpark(thread);
+    }
+
+    /// Blocks the current thread until `ready` returns `true`, letting other threads run meanwhile. `ready` is
+    /// called with the lock held, and again each time another thread changes what threads wait for.
+    ///
+    /// Object references the caller holds that aren't in a frame, static field or handle must not be used after
+    /// this, since the objects may have moved.
+    pub(crate) fn block_until<F: FnMut(&mut Vm) -> bool>(&mut self, mut ready: F) {
+        while !ready(self) {
+            let generation = self.scheduler.generation();
+            let thread = self.park();
+            self.scheduler.wait_for_signal(generation);
+            self.scheduler.acquire();
+            self.unpark(thread);
+        }
+    }
+
+    /// Sleeps without the lock for a number of milliseconds, forever for -1, or just yields for 0, as
+    /// `Thread.Sleep` does.
+    pub(crate) fn sleep(&mut self, milliseconds: i32) -> Result<(), Error> {
+        match milliseconds {
+            -1 => self.block_until(|_| false),
+            0 => self.yield_turn(),
+            _ if milliseconds < 0 => return Err(ExceptionKind::Argument.into()),
+            _ => {
+                let thread = self.park();
+                thread::sleep(Duration::from_millis(milliseconds as u64));
+                self.scheduler.acquire();
+                self.unpark(thread);
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps the current thread's state in its `ManagedThread` and gives up the lock, and gets the thread's index.
+    fn park(&mut self) -> usize {
+        let thread = self.current_thread;
+        self.swap_state(thread);
+        self.scheduler.release();
+        thread
+    }
+
+    /// Restores a thread's state once it holds the lock.
+    fn unpark(&mut self, thread: usize) {
+        self.swap_state(thread);
+        self.current_thread = thread;
+        self.safepoints = 0;
+    }
+
+    fn swap_state(&mut self, thread: usize) {
+        let state = &mut self.threads[thread].state;
+        mem::swap(&mut self.frames, &mut state.frames);
+        mem::swap(&mut self.thrown, &mut state.thrown);
+        mem::swap(&mut self.unhandled, &mut state.unhandled);
+        mem::swap(&mut self.handles, &mut state.handles);
+        mem::swap(&mut self.last_error, &mut state.last_error);
+        mem::swap(&mut self.thread_statics, &mut state.thread_statics);
+    }
+
+    /// Gets the current thread's `Thread`, creating the main thread's the first time it is asked for, as
+    /// `Thread.CurrentThread` does.
+    pub(crate) fn current_thread_object(&mut self) -> Result<ObjectRef, Error> {
+        let current = self.current_thread;
+        if self.threads[current].object.is_null() {
+            let thread_type = self.types.corlib_type("System.Threading", "Thread")?;
+            let object = self.new_object(thread_type)?;
+            let offset = self.field_offset(thread_type, "_thread")?;
+            unsafe { *(object.data().add(offset) as *mut usize) = current + 1 };
+            self.threads[current].object = object;
+        }
+        Ok(self.threads[current].object)
+    }
+
+    /// Gets the id of a `Thread`, giving it the next one the first time it is asked for, as
+    /// `Thread.ManagedThreadId` does.
+    pub(crate) fn managed_thread_id(&mut self, object: ObjectRef) -> Result<i32, Error> {
+        let offset = self.thread_field_offset("_managedThreadId")?;
+        let id = unsafe { &mut *(object.data().add(offset) as *mut i32) };
+        if *id == 0 {
+            self.managed_thread_ids += 1;
+            *id = self.managed_thread_ids;
+        }
+        Ok(*id)
+    }
+
+    /// Starts a `Thread` on an OS thread of its own, which calls the `Thread`'s `StartCallback` when it gets its
+    /// first turn, as `Thread.Start` does.
+    pub(crate) fn start_thread(&mut self, object: ObjectRef) -> Result<(), Error> {
+        let offset = self.thread_field_offset("_thread")?;
+        if unsafe { *(object.data().add(offset) as *const usize) } != 0 {
+            let thrown = self.raise(ExceptionKind::ThreadState)?;
+            return Err(self.throw_from_runtime(thrown));
+        }
+        let thread_type = self.object_type(object);
+        let signature = MethodSig {
+            has_this: true,
+            ret: None,
+            params: vec![],
+        };
+        let callback = self.types
+            .find_method(thread_type, "StartCallback", &signature)?
+            .ok_or_else(|| Error::MissingMethod(format!("{}::StartCallback", self.types.get(thread_type))))?;
+
+        let index = self.threads.len();
+        debug!(self.logger, "starting thread"; "thread" => index);
+        self.threads.push(ManagedThread::new(object));
+        let scheduler = self.scheduler.clone();
+        let vm = VmPointer(self as *mut Vm);
+        let os_thread = thread::Builder::new().name(format!("managed thread {}", index)).spawn(move || {
+            scheduler.acquire();
+            let vm = unsafe { &mut *vm.0 };
+            vm.unpark(index);
+            vm.run_thread(index, callback);
+            vm.park();
+        });
+        match os_thread {
+            Ok(os_thread) => {
+                unsafe { *(object.data().add(offset) as *mut usize) = index + 1 };
+                self.threads[index].os_thread = Some(os_thread);
+                Ok(())
+            }
+            Err(error) => {
+                self.threads.pop();
+                Err(error.into())
+            }
+        }
+    }
+
+    /// Runs a thread's `StartCallback`. If it fails, the error is kept for the entry point to fail with once the
+    /// threads have finished.
+    fn run_thread(&mut self, index: usize, callback: MethodId) {
+        let object = self.threads[index].object;
+        let failure = match self.invoke(callback, vec![Value::Ref(object)]) {
+            Ok(_) => None,
+            Err(Error::UnhandledException(report)) => {
+                self.unhandled = None;
+                Some(Error::UnhandledException(report))
+            }
+            Err(error) => Some(error),
+        };
+        if let Some(failure) = failure {
+            debug!(self.logger, "thread failed"; "thread" => index, "error" => format!("{:?}", failure));
+            self.thread_failure.get_or_insert(failure);
+        }
+        self.threads[index].finished = true;
+        self.scheduler.signal();
+    }
+
+    /// Blocks until a `Thread` finishes, as `Thread.Join` does.
+    pub(crate) fn join_thread(&mut self, object: ObjectRef) -> Result<(), Error> {
+        let offset = self.thread_field_offset("_thread")?;
+        let index = match unsafe { *(object.data().add(offset) as *const usize) } {
+            0 => {
+                let thrown = self.raise_with_message(ExceptionKind::ThreadState, "Thread has not been started.")?;
+                return Err(self.throw_from_runtime(thrown));
+            }
+            thread => thread - 1,
+        };
+        self.block_until(|vm| vm.threads[index].finished);
+        Ok(())
+    }
+
+    /// Finishes the main thread, and waits for every other thread to finish, as .NET waits for foreground threads
+    /// before the process exits. This happens even if the entry point failed, since the threads use the runtime.
+    ///
+    /// Fails with what went wrong on the first thread that failed.
+    pub(crate) fn finish_threads(&mut self) -> Result<(), Error> {
+        let current = self.current_thread;
+        self.threads[current].finished = true;
+        self.scheduler.signal();
+        self.block_until(|vm| vm.threads.iter().all(|thread| thread.finished));
+        for thread in &mut self.threads {
+            if let Some(os_thread) = thread.os_thread.take() {
+                os_thread.join().expect("A managed thread panicked");
+            }
+        }
+        self.threads = vec![ManagedThread::new(ObjectRef::NULL)];
+        self.current_thread = 0;
+        self.thread_failure.take().map_or(Ok(()), Err)
+    }
+
+    fn thread_field_offset(&mut self, name: &str) -> Result<usize, Error> {
+        let thread_type = self.types.corlib_type("System.Threading", "Thread")?;
+        self.field_offset(thread_type, name)
+    }
+
+    /// Enters an object's monitor, blocking while another thread holds it, as `Monitor.Enter` does. A thread can
+    /// enter a monitor it holds again. `lock_taken` points to a `bool` to set once the monitor is entered.
+    pub(crate) fn monitor_enter(&mut self, object: ObjectRef, lock_taken: Option<Value>) -> Result<(), Error> {
+        if object.is_null() {
+            return Err(ExceptionKind::ArgumentNull.into());
+        }
+        let block = self.sync_block(object);
+        let thread = self.current_thread;
+
+        // The pointer stays on the stack while this thread is blocked, in case it points into an object that moves
+        let sets_lock_taken = lock_taken.is_some();
+        if let Some(lock_taken) = lock_taken {
+            self.push(lock_taken);
+        }
+        self.block_until(|vm| vm.sync_blocks[block].owner.is_none_or(|owner| owner == thread));
+        let sync_block = &mut self.sync_blocks[block];
+        if sync_block.owner.is_some() {
+            sync_block.recursion += 1;
+        } else {
+            sync_block.owner = Some(thread);
+        }
+        if sets_lock_taken {
+            let address = match self.pop()? {
+                Value::ByRef(pointer) if !pointer.is_null() => pointer.address(),
+                Value::NativeInt(address) if address != 0 => address as *mut u8,
+                _ => return Err(ExceptionKind::NullReference.into()),
+            };
+            unsafe { *address = 1 };
+        }
+        Ok(())
+    }
+
+    /// Exits an object's monitor, once for each time the current thread entered it, as `Monitor.Exit` does.
+    pub(crate) fn monitor_exit(&mut self, object: ObjectRef) -> Result<(), Error> {
+        let block = self.owned_sync_block(object)?;
+        let sync_block = &mut self.sync_blocks[block];
+        if sync_block.recursion > 0 {
+            sync_block.recursion -= 1;
+        } else {
+            sync_block.owner = None;
+            self.scheduler.signal();
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the current thread holds an object's monitor, as `Monitor.IsEntered` does.
+    pub(crate) fn monitor_is_entered(&mut self, object: ObjectRef) -> Result<bool, Error> {
+        if object.is_null() {
+            return Err(ExceptionKind::ArgumentNull.into());
+        }
+        let block = unsafe { object.sync_block() };
+        Ok(block.is_some_and(|block| self.sync_blocks[block].owner == Some(self.current_thread)))
+    }
+
+    /// Exits an object's monitor and waits for another thread to pulse it, then enters it again as many times as
+    /// before, as `Monitor.Wait` does.
+    pub(crate) fn monitor_wait(&mut self, object: ObjectRef) -> Result<(), Error> {
+        let block = self.owned_sync_block(object)?;
+        let thread = self.current_thread;
+        let recursion = mem::replace(&mut self.sync_blocks[block].recursion, 0);
+        self.sync_blocks[block].owner = None;
+        self.sync_blocks[block].waiting.push_back(thread);
+        self.scheduler.signal();
+        self.block_until(|vm| {
+            let sync_block = &vm.sync_blocks[block];
+            sync_block.owner.is_none() && !sync_block.waiting.contains(&thread)
+        });
+        self.sync_blocks[block].owner = Some(thread);
+        self.sync_blocks[block].recursion = recursion;
+        Ok(())
+    }
+
+    /// Wakes the first thread waiting on an object's monitor, or all of them, as `Monitor.Pulse` and
+    /// `Monitor.PulseAll` do. They enter the monitor again once the current thread exits it.
+    pub(crate) fn monitor_pulse(&mut self, object: ObjectRef, all: bool) -> Result<(), Error> {
+        let block = self.owned_sync_block(object)?;
+        let waiting = &mut self.sync_blocks[block].waiting;
+        if all {
+            waiting.clear();
+        } else {
+            waiting.pop_front();
+        }
+        self.scheduler.signal();
+        Ok(())
+    }
+
+    /// Gets the index of an object's sync block, giving it one the first time.
+    fn sync_block(&mut self, object: ObjectRef) -> usize {
+        if let Some(block) = unsafe { object.sync_block() } {
+            return block;
+        }
+        let block = self.sync_blocks.len();
+        self.sync_blocks.push(SyncBlock::default());
+        unsafe { object.set_sync_block(block) };
+        block
+    }
+
+    /// Gets the index of the sync block of an object whose monitor the current thread holds.
+    fn owned_sync_block(&mut self, object: ObjectRef) -> Result<usize, Error> {
+        if object.is_null() {
+            return Err(ExceptionKind::ArgumentNull.into());
+        }
+        match unsafe { object.sync_block() } {
+            Some(block) if self.sync_blocks[block].owner == Some(self.current_thread) => Ok(block),
+            _ => Err(ExceptionKind::SynchronizationLock.into()),
+        }
+    }
+
+    /// Returns `true` if a static field is marked `[ThreadStatic]`, so each thread has its own.
+    pub(super) fn is_thread_static(&mut self, field: FieldId) -> Result<bool, Error> {
+        if let Some(&thread_static) = self.thread_static_fields.get(&field) {
+            return Ok(thread_static);
+        }
+        let thread_static = match self.types.field(field).definition {
+            Some(definition) => {
+                let parent = TableHandle::new(definition.row, TableIndex::Field);
+                let attributes = self.types.custom_attributes(definition.assembly, parent)?;
+                attributes.iter().any(|&(constructor, _)| {
+                    self.types.is_corlib_type(self.types.method(constructor).owner, "ThreadStaticAttribute")
+                })
+            }
+            None => false,
+        };
+        self.thread_static_fields.insert(field, thread_static);
+        Ok(thread_static)
+    }
+
+    /// Gets the address of the current thread's storage for a type's `[ThreadStatic]` fields, allocating it the
+    /// first time the thread uses them. The fields start zeroed on every thread.
+    pub(super) fn thread_static_storage(&mut self, ty: TypeId) -> Result<*mut u8, Error> {
+        self.types.prepare(ty)?;
+        if self.thread_statics.len() <= ty.index() {
+            self.thread_statics.resize(ty.index() + 1, None);
+        }
+        if self.thread_statics[ty.index()].is_none() {
+            let size = self.types.get(ty).static_size as usize;
+            self.thread_statics[ty.index()] = Some(vec![0u64; size.div_ceil(8)].into_boxed_slice());
+        }
+        Ok(self.thread_statics[ty.index()].as_mut().unwrap().as_mut_ptr() as *mut u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecma355metadata::cli::il::Opcode;
+
+    use gc::GcMode;
+    use runtime::{RuntimeBuilder, UNHANDLED_EXCEPTION_EXIT_CODE};
+    use test_assembly::*;
+
+    /// Gets the `Thread` type, its constructor and the `ThreadStart` constructor, for an application to start
+    /// threads with.
+    fn thread_refs(app: &mut AssemblyBuilder) -> (u32, u32, u32) {
+        let thread = app.corlib_type("System.Threading", "Thread");
+        let thread_start = app.corlib_type("System.Threading", "ThreadStart");
+        let thread_ctor = app.member_ref(thread, ".ctor", &method_sig(true, Ty::Void, &[Ty::Class(thread_start)]));
+        let signature = method_sig(true, Ty::Void, &[Ty::Object, Ty::I]);
+        let thread_start_ctor = app.member_ref(thread_start, ".ctor", &signature);
+        (thread, thread_ctor, thread_start_ctor)
+    }
+
+    #[test]
+    pub fn threads_share_a_counter_through_monitors_and_interlocked() {
+        // static object gate; static int locked; static int interlocked;
+        // static void Work() {
+        //     for (int i = 0; i < 200; i++) {
+        //         Monitor.Enter(gate); int x = locked; Thread.Yield(); locked = x + 1; Monitor.Exit(gate);
+        //         Interlocked.Increment(ref interlocked);
+        //     }
+        // }
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+        let (thread, thread_ctor, thread_start_ctor) = thread_refs(&mut app);
+        let start = app.member_ref(thread, "Start", &method_sig(true, Ty::Void, &[]));
+        let join = app.member_ref(thread, "Join", &method_sig(true, Ty::Void, &[]));
+        let yield_turn = app.member_ref(thread, "Yield", &method_sig(false, Ty::Boolean, &[]));
+        let monitor = app.corlib_type("System.Threading", "Monitor");
+        let enter = app.member_ref(monitor, "Enter", &method_sig(false, Ty::Void, &[Ty::Object]));
+        let exit = app.member_ref(monitor, "Exit", &method_sig(false, Ty::Void, &[Ty::Object]));
+        let interlocked = app.corlib_type("System.Threading", "Interlocked");
+        let signature = method_sig(false, Ty::I4, &[Ty::by_ref(Ty::I4)]);
+        let increment = app.member_ref(interlocked, "Increment", &signature);
+        app.type_def(PUBLIC, "", "Program", object);
+        let gate = app.field(STATIC_FIELD, "gate", Ty::Object);
+        let locked = app.field(STATIC_FIELD, "locked", Ty::I4);
+        let counted = app.field(STATIC_FIELD, "interlocked", Ty::I4);
+        let mut il = Il::new();
+        let (body, condition) = (il.label(), il.label());
+        il.op(Opcode::LdcI40).op(Opcode::Stloc0).branch(Opcode::BrS, condition);
+        il.mark(body).arg(Opcode::Ldsfld, gate as i64).arg(Opcode::Call, enter as i64);
+        il.arg(Opcode::Ldsfld, locked as i64).op(Opcode::Stloc1);
+        il.arg(Opcode::Call, yield_turn as i64).op(Opcode::Pop);
+        il.op(Opcode::Ldloc1).op(Opcode::LdcI41).op(Opcode::Add).arg(Opcode::Stsfld, locked as i64);
+        il.arg(Opcode::Ldsfld, gate as i64).arg(Opcode::Call, exit as i64);
+        il.arg(Opcode::Ldsflda, counted as i64).arg(Opcode::Call, increment as i64).op(Opcode::Pop);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc0);
+        il.mark(condition).op(Opcode::Ldloc0).ldc_i4(200).branch(Opcode::Blt, body);
+        il.op(Opcode::Ret);
+        let body = Body::new(vec![Ty::I4, Ty::I4], il);
+        let work = app.method(STATIC, "Work", &method_sig(false, Ty::Void, &[]), Some(body));
+
+        // gate = new object();
+        // Thread t0 = new Thread(Work); t0.Start(); and t1 and t2 likewise
+        // t0.Join(); t1.Join(); t2.Join();
+        // return locked * 10000 + interlocked;
+        let mut il = Il::new();
+        il.arg(Opcode::Newobj, object_ctor as i64).arg(Opcode::Stsfld, gate as i64);
+        for local in 0..3 {
+            il.op(Opcode::Ldnull).arg(Opcode::Ldftn, work as i64).arg(Opcode::Newobj, thread_start_ctor as i64);
+            il.arg(Opcode::Newobj, thread_ctor as i64).op(Opcode::Dup).arg(Opcode::StlocS, local);
+            il.arg(Opcode::Call, start as i64);
+        }
+        for local in 0..3 {
+            il.arg(Opcode::LdlocS, local).arg(Opcode::Call, join as i64);
+        }
+        il.arg(Opcode::Ldsfld, locked as i64).ldc_i4(10000).op(Opcode::Mul);
+        il.arg(Opcode::Ldsfld, counted as i64).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::Class(thread); 3], il));
+        assert_eq!(Ok(6_000_600), run("threads", &corlib(), &app));
+
+        // Collections stop the threads at safepoints, and move the objects they use
+        let builder = RuntimeBuilder::new().gc_mode(GcMode::Generational).gc_stress(true);
+        let (_directory, mut runtime) = runtime_with("threads_gc", &corlib(), &app, builder);
+        assert_eq!(Ok(6_000_600), runtime.execute("App"));
+    }
+
+    #[test]
+    pub fn threads_have_their_own_thread_static_fields_and_wait_for_pulses() {
+        // static object gate; static bool ready; static int seen; [ThreadStatic] static int local;
+        // static void Work() {
+        //     local = 5;
+        //     Monitor.Enter(gate); ready = true; Monitor.Pulse(gate); Monitor.Exit(gate);
+        //     seen = local * 10 + Thread.CurrentThread.ManagedThreadId;
+        // }
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+        let (thread, thread_ctor, thread_start_ctor) = thread_refs(&mut app);
+        let start = app.member_ref(thread, "Start", &method_sig(true, Ty::Void, &[]));
+        let join = app.member_ref(thread, "Join", &method_sig(true, Ty::Void, &[]));
+        let current = app.member_ref(thread, "get_CurrentThread", &method_sig(false, Ty::Class(thread), &[]));
+        let id = app.member_ref(thread, "get_ManagedThreadId", &method_sig(true, Ty::I4, &[]));
+        let monitor = app.corlib_type("System.Threading", "Monitor");
+        let signature = method_sig(false, Ty::Void, &[Ty::Object]);
+        let enter = app.member_ref(monitor, "Enter", &signature);
+        let exit = app.member_ref(monitor, "Exit", &signature);
+        let pulse = app.member_ref(monitor, "Pulse", &signature);
+        let wait = app.member_ref(monitor, "Wait", &method_sig(false, Ty::Boolean, &[Ty::Object]));
+        let thread_static = app.corlib_type("System", "ThreadStaticAttribute");
+        let thread_static_ctor = app.member_ref(thread_static, ".ctor", &method_sig(true, Ty::Void, &[]));
+        app.type_def(PUBLIC, "", "Program", object);
+        let gate = app.field(STATIC_FIELD, "gate", Ty::Object);
+        let ready = app.field(STATIC_FIELD, "ready", Ty::Boolean);
+        let seen = app.field(STATIC_FIELD, "seen", Ty::I4);
+        let local = app.field(STATIC_FIELD, "local", Ty::I4);
+        app.custom_attribute(local, thread_static_ctor);
+        let mut il = Il::new();
+        il.op(Opcode::LdcI45).arg(Opcode::Stsfld, local as i64);
+        il.arg(Opcode::Ldsfld, gate as i64).arg(Opcode::Call, enter as i64);
+        il.op(Opcode::LdcI41).arg(Opcode::Stsfld, ready as i64);
+        il.arg(Opcode::Ldsfld, gate as i64).arg(Opcode::Call, pulse as i64);
+        il.arg(Opcode::Ldsfld, gate as i64).arg(Opcode::Call, exit as i64);
+        il.arg(Opcode::Ldsfld, local as i64).ldc_i4(10).op(Opcode::Mul);
+        il.arg(Opcode::Call, current as i64).arg(Opcode::Call, id as i64).op(Opcode::Add);
+        il.arg(Opcode::Stsfld, seen as i64).op(Opcode::Ret);
+        let work = app.method(STATIC, "Work", &method_sig(false, Ty::Void, &[]), Some(Body::new(vec![], il)));
+
+        // int mainId = Thread.CurrentThread.ManagedThreadId;
+        // gate = new object(); local = 1; Thread thread = new Thread(Work);
+        // Monitor.Enter(gate); thread.Start(); while (!ready) Monitor.Wait(gate); Monitor.Exit(gate);
+        // thread.Join();
+        // return local * 1000 + seen * 10 + mainId;
+        let mut il = Il::new();
+        let (body, condition) = (il.label(), il.label());
+        il.arg(Opcode::Call, current as i64).arg(Opcode::Call, id as i64).op(Opcode::Stloc0);
+        il.arg(Opcode::Newobj, object_ctor as i64).arg(Opcode::Stsfld, gate as i64);
+        il.op(Opcode::LdcI41).arg(Opcode::Stsfld, local as i64);
+        il.op(Opcode::Ldnull).arg(Opcode::Ldftn, work as i64).arg(Opcode::Newobj, thread_start_ctor as i64);
+        il.arg(Opcode::Newobj, thread_ctor as i64).op(Opcode::Stloc1);
+        il.arg(Opcode::Ldsfld, gate as i64).arg(Opcode::Call, enter as i64);
+        il.op(Opcode::Ldloc1).arg(Opcode::Call, start as i64).branch(Opcode::BrS, condition);
+        il.mark(body).arg(Opcode::Ldsfld, gate as i64).arg(Opcode::Call, wait as i64).op(Opcode::Pop);
+        il.mark(condition).arg(Opcode::Ldsfld, ready as i64).branch(Opcode::BrfalseS, body);
+        il.arg(Opcode::Ldsfld, gate as i64).arg(Opcode::Call, exit as i64);
+        il.op(Opcode::Ldloc1).arg(Opcode::Call, join as i64);
+        il.arg(Opcode::Ldsfld, local as i64).ldc_i4(1000).op(Opcode::Mul);
+        il.arg(Opcode::Ldsfld, seen as i64).ldc_i4(10).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::Ldloc0).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::I4, Ty::Class(thread)], il));
+        assert_eq!(Ok(1_521), run("thread_statics", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn an_unhandled_exception_on_a_thread_fails_the_program() {
+        // static void Work() { throw null; }
+        // static int Main() { new Thread(Work).Start(); return 0; }
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let (thread, thread_ctor, thread_start_ctor) = thread_refs(&mut app);
+        let start = app.member_ref(thread, "Start", &method_sig(true, Ty::Void, &[]));
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::Ldnull).op(Opcode::Throw);
+        let work = app.method(STATIC, "Work", &method_sig(false, Ty::Void, &[]), Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.op(Opcode::Ldnull).arg(Opcode::Ldftn, work as i64).arg(Opcode::Newobj, thread_start_ctor as i64);
+        il.arg(Opcode::Newobj, thread_ctor as i64).arg(Opcode::Call, start as i64);
+        il.op(Opcode::LdcI40).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+        assert_eq!(Ok(UNHANDLED_EXCEPTION_EXIT_CODE), run("thread_exception", &corlib(), &app));
+    }
+}
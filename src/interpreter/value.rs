@@ -0,0 +1,154 @@
+use std::ptr;
+
+use gc::{self, ObjectRef};
+use types::{Storage, TypeId};
+
+/// A managed pointer, to a location inside an object, or to a local variable, static field or unmanaged memory.
+///
+/// Pointers into objects keep the object they point into, so the object stays alive (and the pointer can be
+/// updated if it moves). Other pointers have a null base, and their offset is the address.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Pointer {
+    pub base: ObjectRef,
+    pub offset: usize,
+}
+
+impl Pointer {
+    pub fn absolute(address: *mut u8) -> Pointer {
+        Pointer {
+            base: ObjectRef::NULL,
+            offset: address as usize,
+        }
+    }
+
+    /// Creates a pointer to the location `offset` bytes into an object's data.
+    pub fn into_object(object: ObjectRef, offset: usize) -> Pointer {
+        Pointer {
+            base: object,
+            offset: gc::HEADER_SIZE + offset,
+        }
+    }
+
+    pub fn address(self) -> *mut u8 {
+        (self.base.address() + self.offset) as *mut u8
+    }
+
+    pub fn is_null(self) -> bool {
+        self.address().is_null()
+    }
+
+    pub fn add(self, offset: isize) -> Pointer {
+        Pointer {
+            base: self.base,
+            offset: (self.offset as isize).wrapping_add(offset) as usize,
+        }
+    }
+}
+
+/// A value on the evaluation stack. Small integers are widened to `I32`, and both float types are held as `F`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    NativeInt(isize),
+    F(f64),
+    Ref(ObjectRef),
+    ByRef(Pointer),
+
+    /// A value type other than a primitive, with the bytes of the value.
+    Struct(TypeId, Vec<u8>),
+}
+
+impl Value {
+    pub fn null() -> Value {
+        Value::Ref(ObjectRef::NULL)
+    }
+
+    /// Gets an integer, native integer or pointer value as a 64-bit integer, sign-extending `I32`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::I32(x) => Some(x as i64),
+            Value::I64(x) => Some(x),
+            Value::NativeInt(x) => Some(x as i64),
+            Value::ByRef(pointer) => Some(pointer.address() as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_ref(&self) -> Option<ObjectRef> {
+        match *self {
+            Value::Ref(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    /// Reads a value of a type stored at a location, which may not be aligned.
+    pub(crate) unsafe fn load(location: *const u8, storage: Storage, ty: TypeId, size: u32) -> Value {
+        match storage {
+            Storage::I1 => Value::I32(*(location as *const i8) as i32),
+            Storage::U1 => Value::I32(*location as i32),
+            Storage::I2 => Value::I32(ptr::read_unaligned(location as *const i16) as i32),
+            Storage::U2 => Value::I32(ptr::read_unaligned(location as *const u16) as i32),
+            Storage::I4 | Storage::U4 => Value::I32(ptr::read_unaligned(location as *const i32)),
+            Storage::I8 => Value::I64(ptr::read_unaligned(location as *const i64)),
+            Storage::R4 => Value::F(ptr::read_unaligned(location as *const f32) as f64),
+            Storage::R8 => Value::F(ptr::read_unaligned(location as *const f64)),
+            Storage::NativeInt => Value::NativeInt(ptr::read_unaligned(location as *const isize)),
+            Storage::Ref => Value::Ref(gc::read_ref(location)),
+            Storage::ByRef => Value::ByRef(Pointer {
+                base: gc::read_ref(location),
+                offset: ptr::read_unaligned(location.add(8) as *const usize),
+            }),
+            Storage::Struct => {
+                Value::Struct(ty, ::std::slice::from_raw_parts(location, size as usize).to_vec())
+            }
+        }
+    }
+
+    /// Writes a value to a location with the specified storage, truncating or converting numbers as `stind` and
+    /// `stfld` do.
+    pub(crate) unsafe fn store(&self, location: *mut u8, storage: Storage) {
+        match (storage, self) {
+            (Storage::R4, &Value::F(x)) => ptr::write_unaligned(location as *mut f32, x as f32),
+            (Storage::R8, &Value::F(x)) => ptr::write_unaligned(location as *mut f64, x),
+            (Storage::Ref, &Value::Ref(object)) => gc::write_ref(location, object),
+            (Storage::ByRef, &Value::ByRef(pointer)) => {
+                gc::write_ref(location, pointer.base);
+                ptr::write_unaligned(location.add(8) as *mut usize, pointer.offset);
+            }
+            (Storage::Struct, Value::Struct(_, bytes)) => {
+                ptr::copy_nonoverlapping(bytes.as_ptr(), location, bytes.len())
+            }
+            (_, value) => {
+                let x = value.as_i64().unwrap_or(0);
+                match storage {
+                    Storage::I1 | Storage::U1 => *location = x as u8,
+                    Storage::I2 | Storage::U2 => ptr::write_unaligned(location as *mut u16, x as u16),
+                    Storage::I4 | Storage::U4 => ptr::write_unaligned(location as *mut u32, x as u32),
+                    Storage::I8 => ptr::write_unaligned(location as *mut i64, x),
+                    Storage::NativeInt => ptr::write_unaligned(location as *mut isize, x as isize),
+                    // A mismatched value can only come from unverifiable code, so store zero rather than garbage
+                    Storage::R4 => ptr::write_unaligned(location as *mut f32, 0.0),
+                    Storage::R8 => ptr::write_unaligned(location as *mut f64, 0.0),
+                    Storage::Ref => gc::write_ref(location, ObjectRef::NULL),
+                    Storage::ByRef => Value::ByRef(Pointer::absolute(x as usize as *mut u8)).store(location, storage),
+                    Storage::Struct => {}
+                }
+            }
+        }
+    }
+
+    /// Calls `visit` with the location of each object reference in the value.
+    pub fn visit_refs<F: FnMut(*mut u8)>(&mut self, struct_refs: &[u32], mut visit: F) {
+        match *self {
+            Value::Ref(ref mut object) => visit(&mut object.0 as *mut usize as *mut u8),
+            Value::ByRef(ref mut pointer) => visit(&mut pointer.base.0 as *mut usize as *mut u8),
+            Value::Struct(_, ref mut bytes) => {
+                for &offset in struct_refs {
+                    visit(unsafe { bytes.as_mut_ptr().add(offset as usize) });
+                }
+            }
+            _ => {}
+        }
+    }
+}
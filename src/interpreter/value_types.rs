@@ -0,0 +1,363 @@
+use std::ptr;
+
+use ecma355metadata::cli::il::Opcode;
+
+use error::{Error, ExceptionKind};
+use gc::{self, ObjectRef};
+use interpreter::{Pointer, Value};
+use types::{MethodId, TypeId};
+use vm::Vm;
+
+/// Where the fields of an instantiation of `System.Nullable<T>` are, and the `T` it holds.
+pub(super) struct NullableLayout {
+    pub(super) underlying: TypeId,
+    has_value: usize,
+    value: usize,
+    value_size: usize,
+}
+
+impl Vm {
+    /// Executes an instruction with a type operand that boxes, unboxes, copies or measures a value of the type.
+    pub(super) fn value_instruction(&mut self, opcode: Opcode, ty: TypeId) -> Result<(), Error> {
+        self.types.prepare(ty)?;
+        let (ty, storage, size) = self.value_layout(ty);
+        match opcode {
+            Opcode::Box => self.box_value(ty)?,
+            Opcode::Unbox => self.unbox(ty)?,
+            Opcode::UnboxAny => self.unbox_any(ty)?,
+            Opcode::Initobj => {
+                let address = self.pop_address()?;
+                unsafe { ptr::write_bytes(address, 0, size as usize) };
+            }
+            Opcode::Cpobj => {
+                let source = self.pop_address()?;
+                let destination = self.pop_address()?;
+                unsafe { ptr::copy(source, destination, size as usize) };
+                if !self.types.get(ty).value_ref_offsets().is_empty() {
+                    self.heap.write_barrier(destination, size as usize);
+                }
+            }
+            Opcode::Ldobj => {
+                let address = self.pop_address()?;
+                let value = unsafe { Value::load(address, storage, ty, size) };
+                self.push(value);
+            }
+            Opcode::Stobj => {
+                let value = self.pop()?;
+                let address = self.pop_address()?;
+                self.store_value(address, &value, storage, size);
+            }
+            Opcode::Sizeof => self.push(Value::I32(size as i32)),
+            _ => return Err(Error::InvalidProgram(format!("{} has no type operand", opcode))),
+        }
+        Ok(())
+    }
+
+    /// Executes `box`: copies the value on top of the stack into a new object. References are left as they are,
+    /// and a `Nullable<T>` is boxed as the `T` it holds, or as null if it holds nothing.
+    pub(super) fn box_value(&mut self, ty: TypeId) -> Result<(), Error> {
+        if !self.types.get(ty).is_value_type() {
+            return Ok(());
+        }
+        // The value stays on the stack while the box is allocated, so the objects it refers to stay alive
+        match self.nullable_layout(ty)? {
+            Some(nullable) => {
+                let has_value = match self.frame().stack.last() {
+                    Some(Value::Struct(_, bytes)) => bytes[nullable.has_value] != 0,
+                    _ => return Err(Error::InvalidProgram(format!("{} boxes a non-Nullable", self.current()))),
+                };
+                if !has_value {
+                    self.pop()?;
+                    self.push(Value::null());
+                    return Ok(());
+                }
+                let object = self.new_object(nullable.underlying)?;
+                if let Value::Struct(_, bytes) = self.pop()? {
+                    let value = &bytes[nullable.value..nullable.value + nullable.value_size];
+                    unsafe { ptr::copy_nonoverlapping(value.as_ptr(), object.data(), value.len()) };
+                }
+                self.push(Value::Ref(object));
+            }
+            None => {
+                let object = self.new_object(ty)?;
+                let value = self.pop()?;
+                let (_, storage, size) = self.value_layout(ty);
+                self.store_value(object.data(), &value, storage, size);
+                self.push(Value::Ref(object));
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes `unbox`: gets a managed pointer to the value in a boxed value type. A `Nullable<T>` is copied into
+    /// a new object, since a boxed `T` has none to point to.
+    fn unbox(&mut self, ty: TypeId) -> Result<(), Error> {
+        match self.nullable_layout(ty)? {
+            Some(nullable) => {
+                let object = self.peek_ref()?;
+                self.check_boxed(object, nullable.underlying)?;
+                let copy = self.new_object(ty)?;
+                let object = self.pop()?.as_ref().unwrap();
+                unsafe { self.fill_nullable(copy.data(), &nullable, object) };
+                self.push(Value::ByRef(Pointer::into_object(copy, 0)));
+            }
+            None => {
+                let object = self.peek_ref()?;
+                self.check_boxed(object, ty)?;
+                self.pop()?;
+                self.push(Value::ByRef(Pointer::into_object(object, 0)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes `unbox.any`: copies the value out of a boxed value type. For a reference type it is `castclass`,
+    /// and a null reference unboxes to a `Nullable<T>` that holds nothing.
+    fn unbox_any(&mut self, ty: TypeId) -> Result<(), Error> {
+        if !self.types.get(ty).is_value_type() {
+            return self.cast(ty, false);
+        }
+        let object = self.peek_ref()?;
+        let value = match self.nullable_layout(ty)? {
+            Some(nullable) => {
+                if !object.is_null() {
+                    self.check_boxed(object, nullable.underlying)?;
+                }
+                let mut bytes = vec![0; self.types.get(ty).value_size() as usize];
+                unsafe { self.fill_nullable(bytes.as_mut_ptr(), &nullable, object) };
+                Value::Struct(ty, bytes)
+            }
+            None => {
+                self.check_boxed(object, ty)?;
+                let (ty, storage, size) = self.value_layout(ty);
+                unsafe { Value::load(object.data(), storage, ty, size) }
+            }
+        };
+        self.pop()?;
+        self.push(value);
+        Ok(())
+    }
+
+    /// Executes `constrained. ty callvirt method`, whose `this` is a managed pointer to a `ty`. A reference type's
+    /// pointer is dereferenced, a value type that implements the method itself is called with the pointer, and
+    /// otherwise the value is boxed, so the call works the same way whichever `ty` generic code is instantiated with.
+    pub(super) fn constrained_call(&mut self, ty: TypeId, method: MethodId) -> Result<(), Error> {
+        self.types.prepare(ty)?;
+        let arg_count = self.types.method(method).arg_count();
+        let index = match self.frame().stack.len().checked_sub(arg_count) {
+            Some(index) => index,
+            None => return Err(Error::InvalidProgram(format!("{} pops from an empty stack", self.current()))),
+        };
+        let pointer = match self.frame().stack[index] {
+            Value::ByRef(pointer) if pointer.is_null() => return Err(ExceptionKind::NullReference.into()),
+            Value::ByRef(pointer) => pointer,
+            _ => {
+                let message = format!("{} makes a constrained call on a non-pointer", self.current());
+                return Err(Error::InvalidProgram(message));
+            }
+        };
+        if !self.types.get(ty).is_value_type() {
+            self.frame().stack[index] = Value::Ref(unsafe { gc::read_ref(pointer.address()) });
+            return self.call(method, true);
+        }
+
+        if let Some(implementation) = self.value_type_implementation(ty, method)? {
+            return self.call(implementation, false);
+        }
+        let object = self.new_object(ty)?;
+        // The allocation may have moved the object the pointer points into, so it is read from the stack again
+        let pointer = match self.frame().stack[index] {
+            Value::ByRef(pointer) => pointer,
+            _ => unreachable!(),
+        };
+        let (ty, storage, size) = self.value_layout(ty);
+        let value = unsafe { Value::load(pointer.address(), storage, ty, size) };
+        self.store_value(object.data(), &value, storage, size);
+        self.frame().stack[index] = Value::Ref(object);
+        self.call(method, true)
+    }
+
+    /// Finds the method a value type declares that a call to `method` on one of its values runs, if the value type
+    /// overrides or implements it rather than inheriting it.
+    fn value_type_implementation(&mut self, ty: TypeId, method: MethodId) -> Result<Option<MethodId>, Error> {
+        let implementation = self.types.find_implementation(ty, method)?;
+        if self.types.method(implementation).owner == ty {
+            Ok(Some(implementation))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub(super) fn nullable_layout(&mut self, ty: TypeId) -> Result<Option<NullableLayout>, Error> {
+        let underlying = match self.types.nullable_underlying(ty) {
+            Some(underlying) => underlying,
+            None => return Ok(None),
+        };
+        match (self.types.find_field(ty, "hasValue")?, self.types.find_field(ty, "value")?) {
+            (Some(has_value), Some(value)) => Ok(Some(NullableLayout {
+                underlying,
+                has_value: self.types.field(has_value).offset as usize,
+                value: self.types.field(value).offset as usize,
+                value_size: self.types.get(underlying).value_size() as usize,
+            })),
+            _ => Err(Error::TypeLoad(format!("{} has no hasValue and value fields", self.types.get(ty)))),
+        }
+    }
+
+    /// Writes a `Nullable<T>` holding the value boxed in an object, or nothing if it is null, to zeroed memory.
+    pub(super) unsafe fn fill_nullable(&mut self, location: *mut u8, nullable: &NullableLayout, object: ObjectRef) {
+        if object.is_null() {
+            return;
+        }
+        *location.add(nullable.has_value) = 1;
+        ptr::copy_nonoverlapping(object.data(), location.add(nullable.value), nullable.value_size);
+        self.heap.write_barrier(location.add(nullable.value), nullable.value_size);
+    }
+
+    /// Checks that an object is a boxed value of a type. An enum and its underlying type can be unboxed as each
+    /// other.
+    pub(super) fn check_boxed(&self, object: ObjectRef, ty: TypeId) -> Result<(), Error> {
+        if object.is_null() {
+            return Err(ExceptionKind::NullReference.into());
+        }
+        let boxed = self.object_type(object);
+        let underlying = self.types.get(ty).underlying_primitive();
+        if boxed != ty && (underlying.is_none() || self.types.get(boxed).underlying_primitive() != underlying) {
+            Err(ExceptionKind::InvalidCast.into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Gets the object reference on top of the stack, leaving it there so it stays alive.
+    fn peek_ref(&mut self) -> Result<ObjectRef, Error> {
+        match self.frame().stack.last() {
+            Some(&Value::Ref(object)) => Ok(object),
+            Some(_) => Err(Error::InvalidProgram(format!("{} unboxes a non-object", self.current()))),
+            None => Err(Error::InvalidProgram(format!("{} pops from an empty stack", self.current()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecma355metadata::cli::il::Opcode;
+
+    use error::Error;
+    use test_assembly::*;
+
+    #[test]
+    pub fn value_types_are_copied_boxed_and_unboxed() {
+        // struct Point { int x; object tag; }
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let value_type = app.corlib_type("System", "ValueType");
+        let point = app.type_def(PUBLIC | SEALED, "", "Point", value_type);
+        let x = app.field(0, "x", Ty::I4);
+        app.field(0, "tag", Ty::Object);
+
+        // Point a, b; object boxed;
+        // a.x = 3; b = a; b.x = 4; boxed = a; a = default;
+        // result = ((Point)boxed).x, via unbox, then the box is changed to hold 7
+        // result += 10 * ((Point)boxed).x + 100 * (a = b).x + 1000 * (b = (Point)boxed).x + 10000 * sizeof(Point)
+        app.type_def(PUBLIC, "", "Program", object);
+        let point_ty = point as i64;
+        let mut il = Il::new();
+        il.arg(Opcode::LdlocaS, 0).op(Opcode::LdcI43).arg(Opcode::Stfld, x as i64);
+        il.op(Opcode::Ldloc0).op(Opcode::Stloc1);
+        il.arg(Opcode::LdlocaS, 1).op(Opcode::LdcI44).arg(Opcode::Stfld, x as i64);
+        il.op(Opcode::Ldloc0).arg(Opcode::Box, point_ty).op(Opcode::Stloc2);
+        il.arg(Opcode::LdlocaS, 0).arg(Opcode::Initobj, point_ty);
+        il.op(Opcode::Ldloc2).arg(Opcode::Unbox, point_ty).arg(Opcode::Ldfld, x as i64);
+        il.op(Opcode::Ldloc2).arg(Opcode::Unbox, point_ty).op(Opcode::LdcI47).arg(Opcode::Stfld, x as i64);
+        il.op(Opcode::Ldloc2).arg(Opcode::UnboxAny, point_ty).arg(Opcode::Ldfld, x as i64);
+        il.ldc_i4(10).op(Opcode::Mul).op(Opcode::Add);
+        il.arg(Opcode::LdlocaS, 0).arg(Opcode::LdlocaS, 1).arg(Opcode::Cpobj, point_ty);
+        il.arg(Opcode::LdlocaS, 0).arg(Opcode::Ldobj, point_ty).arg(Opcode::Ldfld, x as i64);
+        il.ldc_i4(100).op(Opcode::Mul).op(Opcode::Add);
+        il.arg(Opcode::LdlocaS, 1).op(Opcode::Ldloc2).arg(Opcode::UnboxAny, point_ty).arg(Opcode::Stobj, point_ty);
+        il.op(Opcode::Ldloc1).arg(Opcode::Ldfld, x as i64).ldc_i4(1000).op(Opcode::Mul).op(Opcode::Add);
+        il.arg(Opcode::Sizeof, point_ty).ldc_i4(10000).op(Opcode::Mul).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::ValueType(point), Ty::ValueType(point), Ty::Object], il));
+        assert_eq!(Ok(167_473), run("value_types", &corlib(), &app));
+
+        // Unboxing a boxed value as another value type throws
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let int32 = app.corlib_type("System", "Int32");
+        let int64 = app.corlib_type("System", "Int64");
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::LdcI41).arg(Opcode::Box, int32 as i64).arg(Opcode::UnboxAny, int64 as i64);
+        il.op(Opcode::ConvI4).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+        let (_directory, mut runtime) = runtime("unbox_mismatch", &corlib(), &app);
+        let report = "System.InvalidCastException: Specified cast is not valid.\n   at Program.Main";
+        assert_eq!(Err(Error::UnhandledException(report.into())), runtime.run_main("App"));
+    }
+
+    #[test]
+    pub fn nullables_box_as_their_value_or_null() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let int32 = app.corlib_type("System", "Int32");
+        let nullable = app.corlib_type("System", "Nullable`1");
+        let nullable_int = app.type_spec(Ty::generic_inst(Ty::ValueType(nullable), vec![Ty::I4]));
+        let has_value = app.member_ref(nullable_int, "hasValue", &field_sig(Ty::Boolean));
+        let value = app.member_ref(nullable_int, "value", &field_sig(Ty::Var(0)));
+
+        // int? n = null; result = (object)n == null ? 1 : 0;
+        // n = 5; object o = n; result += 10 * (int)o + 100 * ((int?)o).Value;
+        // n = (int?)null; result += 1000 * (n.HasValue ? 1 : 0) + 10000 * (o is int? ? 1 : 0);
+        app.type_def(PUBLIC, "", "Program", object);
+        let nullable_ty = nullable_int as i64;
+        let mut il = Il::new();
+        il.arg(Opcode::LdlocaS, 0).arg(Opcode::Initobj, nullable_ty);
+        il.op(Opcode::Ldloc0).arg(Opcode::Box, nullable_ty).op(Opcode::Ldnull).op(Opcode::Ceq);
+        il.arg(Opcode::LdlocaS, 0).op(Opcode::LdcI41).arg(Opcode::Stfld, has_value as i64);
+        il.arg(Opcode::LdlocaS, 0).op(Opcode::LdcI45).arg(Opcode::Stfld, value as i64);
+        il.op(Opcode::Ldloc0).arg(Opcode::Box, nullable_ty).op(Opcode::Stloc1);
+        il.op(Opcode::Ldloc1).arg(Opcode::UnboxAny, int32 as i64).ldc_i4(10).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::Ldloc1).arg(Opcode::UnboxAny, nullable_ty).op(Opcode::Stloc0);
+        il.arg(Opcode::LdlocaS, 0).arg(Opcode::Ldfld, value as i64).ldc_i4(100).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::Ldnull).arg(Opcode::UnboxAny, nullable_ty).op(Opcode::Stloc0);
+        il.arg(Opcode::LdlocaS, 0).arg(Opcode::Ldfld, has_value as i64).ldc_i4(1000).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::Ldloc1).arg(Opcode::Isinst, nullable_ty).op(Opcode::Ldnull).op(Opcode::CgtUn);
+        il.ldc_i4(10000).op(Opcode::Mul).op(Opcode::Add).op(Opcode::Ret);
+        let locals = vec![Ty::generic_inst(Ty::ValueType(nullable), vec![Ty::I4]), Ty::Object];
+        add_main(&mut app, Body::new(locals, il));
+
+        assert_eq!(Ok(10_551), run("nullables", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn constrained_calls_use_the_value_types_own_methods_or_box() {
+        // struct Point { int x; override int GetHashCode() { return x; } }
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let value_type = app.corlib_type("System", "ValueType");
+        let int32 = app.corlib_type("System", "Int32");
+        let get_hash_code = app.member_ref(object, "GetHashCode", &method_sig(true, Ty::I4, &[]));
+        let point = app.type_def(PUBLIC | SEALED, "", "Point", value_type);
+        let x = app.field(0, "x", Ty::I4);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Ldfld, x as i64).op(Opcode::Ret);
+        app.method(VIRTUAL, "GetHashCode", &method_sig(true, Ty::I4, &[]), Some(Body::new(vec![], il)));
+
+        // Point p; p.x = 5; int i; object o;
+        // return p.GetHashCode() + 10 * i.GetHashCode() + 100 * ((object)p).GetHashCode() + 1000 * o.GetHashCode()
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.arg(Opcode::LdlocaS, 0).op(Opcode::LdcI45).arg(Opcode::Stfld, x as i64);
+        il.arg(Opcode::LdlocaS, 0).arg(Opcode::Constrained, point as i64).arg(Opcode::Callvirt, get_hash_code as i64);
+        il.arg(Opcode::LdlocaS, 1).arg(Opcode::Constrained, int32 as i64).arg(Opcode::Callvirt, get_hash_code as i64);
+        il.ldc_i4(10).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::Ldloc0).arg(Opcode::Box, point as i64).op(Opcode::Stloc2);
+        il.op(Opcode::Ldloc2).arg(Opcode::Callvirt, get_hash_code as i64).ldc_i4(100).op(Opcode::Mul).op(Opcode::Add);
+        il.arg(Opcode::LdlocaS, 2).arg(Opcode::Constrained, object as i64).arg(Opcode::Callvirt, get_hash_code as i64);
+        il.ldc_i4(1000).op(Opcode::Mul).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::ValueType(point), Ty::I4, Ty::Object], il));
+
+        assert_eq!(Ok(5925), run("constrained_calls", &corlib(), &app));
+    }
+}
@@ -1,15 +1,47 @@
+#[cfg(feature = "jit")]
+extern crate cranelift_codegen;
+#[cfg(feature = "jit")]
+extern crate cranelift_frontend;
+#[cfg(feature = "jit")]
+extern crate cranelift_jit;
+#[cfg(feature = "jit")]
+extern crate cranelift_module;
 extern crate ecma355metadata;
 
+extern crate libc;
 extern crate memmap;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 #[macro_use]
 extern crate slog;
+extern crate xml;
 
 mod app_context;
 mod assembly;
+mod gc;
+mod internal_calls;
+mod interpreter;
+mod native;
 mod runtime;
+mod threads;
+mod types;
+mod vm;
+
+#[cfg(test)]
+mod test_assembly;
+#[cfg(test)]
+mod test_directory;
 
+pub mod config;
 pub mod error;
 
 pub use app_context::AppContext;
 pub use assembly::Assembly;
-pub use runtime::{Runtime, RuntimeBuilder};
+pub use gc::{GcMode, GcStats};
+pub use internal_calls::{CallContext, InternalCall, InternalCalls};
+#[cfg(feature = "jit")]
+pub use interpreter::TierThresholds;
+pub use interpreter::Value;
+pub use runtime::{Runtime, RuntimeBuilder, UNHANDLED_EXCEPTION_EXIT_CODE};
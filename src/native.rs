@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::c_void;
+use std::path::PathBuf;
+
+use libc;
+
+/// The most arguments of each class that are passed in registers on the 64-bit targets the runtime supports. Native
+/// functions are called as if they took this many of each, so the unused ones are ignored.
+pub const MAX_REGISTER_ARGS: usize = 8;
+
+/// An argument in the class of register the C calling convention passes it in, as its bits.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NativeArg {
+    Int(u64),
+
+    /// A `double`, or a `float` in the low 32 bits.
+    Float(u64),
+}
+
+/// The registers a native function returns its value in. Structs of up to 16 bytes are returned in two registers,
+/// one for each eightbyte.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NativeReturn {
+    Void,
+    Int,
+    Float,
+    IntInt,
+    IntFloat,
+    FloatInt,
+    FloatFloat,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Pair<A, B>(A, B);
+
+/// Calls a native function with the C calling convention, and gets the bits of the registers it returns its value in.
+///
+/// Returns `None` if there are more arguments of a class than are passed in registers.
+///
+/// The function must take the arguments as given, with at most `MAX_REGISTER_ARGS` of each class.
+pub unsafe fn call(function: *const c_void, args: &[NativeArg], ret: NativeReturn) -> Option<[u64; 2]> {
+    let mut ints = [0u64; MAX_REGISTER_ARGS];
+    let mut floats = [0f64; MAX_REGISTER_ARGS];
+    let (mut int_count, mut float_count) = (0, 0);
+    for &arg in args {
+        match arg {
+            NativeArg::Int(bits) => {
+                *ints.get_mut(int_count)? = bits;
+                int_count += 1;
+            }
+            NativeArg::Float(bits) => {
+                *floats.get_mut(float_count)? = f64::from_bits(bits);
+                float_count += 1;
+            }
+        }
+    }
+
+    Some(match ret {
+        NativeReturn::Void | NativeReturn::Int => [invoke::<u64>(function, &ints, &floats), 0],
+        NativeReturn::Float => [invoke::<f64>(function, &ints, &floats).to_bits(), 0],
+        NativeReturn::IntInt => {
+            let Pair(first, second) = invoke::<Pair<u64, u64>>(function, &ints, &floats);
+            [first, second]
+        }
+        NativeReturn::IntFloat => {
+            let Pair(first, second) = invoke::<Pair<u64, f64>>(function, &ints, &floats);
+            [first, second.to_bits()]
+        }
+        NativeReturn::FloatInt => {
+            let Pair(first, second) = invoke::<Pair<f64, u64>>(function, &ints, &floats);
+            [first.to_bits(), second]
+        }
+        NativeReturn::FloatFloat => {
+            let Pair(first, second) = invoke::<Pair<f64, f64>>(function, &ints, &floats);
+            [first.to_bits(), second.to_bits()]
+        }
+    })
+}
+
+/// Calls a function as one that takes every register argument, so each argument reaches the register it would if the
+/// function were called with its own signature.
+#[allow(clippy::type_complexity)]
+unsafe fn invoke<R>(function: *const c_void, ints: &[u64; MAX_REGISTER_ARGS], floats: &[f64; MAX_REGISTER_ARGS]) -> R {
+    let function: unsafe extern "C" fn(u64, u64, u64, u64, u64, u64, u64, u64, f64, f64, f64, f64, f64, f64, f64, f64)
+        -> R = mem::transmute(function);
+    function(
+        ints[0], ints[1], ints[2], ints[3], ints[4], ints[5], ints[6], ints[7], floats[0], floats[1], floats[2],
+        floats[3], floats[4], floats[5], floats[6], floats[7],
+    )
+}
+
+/// The native libraries P/Invoke methods have loaded, which stay loaded until the runtime exits.
+pub struct NativeLibraries {
+    /// The application's directory, which is searched before the system's library path.
+    directory: PathBuf,
+    libraries: HashMap<String, usize>,
+}
+
+impl NativeLibraries {
+    pub fn new(directory: PathBuf) -> NativeLibraries {
+        NativeLibraries {
+            directory,
+            libraries: HashMap::new(),
+        }
+    }
+
+    /// Finds a function in a library, loading the library the first time. Fails with the reason the library couldn't
+    /// be loaded, or `Ok(None)` if it has no such function.
+    pub fn function(&mut self, library: &str, name: &str) -> Result<Option<*const c_void>, String> {
+        let handle = match self.libraries.get(library) {
+            Some(&handle) => handle,
+            None => {
+                let handle = self.load(library)?;
+                self.libraries.insert(library.into(), handle);
+                handle
+            }
+        };
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => return Ok(None),
+        };
+        let function = unsafe { libc::dlsym(handle as *mut c_void, name.as_ptr()) };
+        Ok(if function.is_null() { None } else { Some(function as *const c_void) })
+    }
+
+    /// Loads a library by the name a `[DllImport]` gives it, trying it with and without the `lib` prefix and `.so`
+    /// suffix, first in the application's directory.
+    fn load(&self, library: &str) -> Result<usize, String> {
+        let mut names = vec![library.to_string()];
+        if !library.contains(".so") {
+            names.push(format!("{}.so", library));
+            if !library.starts_with("lib") {
+                names.push(format!("lib{}.so", library));
+            }
+        }
+        if !library.starts_with("lib") {
+            names.push(format!("lib{}", library));
+        }
+        // "libc.so" is a linker script rather than a library, so "libc" is the C library applications expect
+        if library == "libc" && cfg!(target_os = "linux") {
+            names.push("libc.so.6".into());
+        }
+
+        let local = names.iter().map(|name| self.directory.join(name)).filter(|path| path.is_file());
+        let paths: Vec<_> = local.map(|path| path.to_string_lossy().into_owned()).chain(names.clone()).collect();
+        for path in paths {
+            let path = match CString::new(path) {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            let handle = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW) };
+            if !handle.is_null() {
+                return Ok(handle as usize);
+            }
+        }
+        let error = unsafe { libc::dlerror() };
+        Err(if error.is_null() { String::new() } else { unsafe { CStr::from_ptr(error) }.to_string_lossy().into() })
+    }
+}
+
+/// Clears `errno`, so a P/Invoke that sets the last error only sees what the native function sets.
+pub fn clear_last_error() {
+    unsafe { *errno() = 0 };
+}
+
+/// Gets the error code the last native function set in `errno`.
+pub fn last_error() -> i32 {
+    unsafe { *errno() }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn errno() -> *mut i32 {
+    libc::__errno_location()
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+unsafe fn errno() -> *mut i32 {
+    libc::__error()
+}
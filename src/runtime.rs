@@ -5,10 +5,31 @@ use slog;
 
 use error::Error;
 use app_context::AppContext;
+use config::{DepsFile, Framework, RuntimeConfig};
+use gc::{GcMode, GcStats};
+use internal_calls::{CallContext, InternalCalls};
+#[cfg(feature = "jit")]
+use interpreter::TierThresholds;
+use interpreter::Value;
+use types::{MethodId, TypeSystem};
+use vm::Vm;
+
+/// The exit code of a program that ends with an unhandled exception, which is the code of the SEH exception the .NET
+/// runtime raises for managed exceptions.
+pub const UNHANDLED_EXCEPTION_EXIT_CODE: i32 = 0xE043_4352u32 as i32;
 
 pub struct RuntimeBuilder {
     base_directory: Option<PathBuf>,
     logger: Option<slog::Logger>,
+    runtime_config: Option<PathBuf>,
+    deps_file: Option<PathBuf>,
+    framework_root: Option<PathBuf>,
+    verify_strong_names: bool,
+    gc_mode: GcMode,
+    gc_stress: bool,
+    #[cfg(feature = "jit")]
+    tier_thresholds: TierThresholds,
+    internal_calls: InternalCalls,
 }
 
 impl RuntimeBuilder {
@@ -17,17 +38,42 @@ impl RuntimeBuilder {
         RuntimeBuilder {
             base_directory: None,
             logger: None,
+            runtime_config: None,
+            deps_file: None,
+            framework_root: None,
+            verify_strong_names: false,
+            gc_mode: GcMode::MarkSweep,
+            gc_stress: false,
+            #[cfg(feature = "jit")]
+            tier_thresholds: TierThresholds::default(),
+            internal_calls: InternalCalls::new(),
         }
     }
 
     /// Consumes the builder and creates an Runtime from the result.
-    pub fn build(self) -> Runtime {
-        Runtime::new(
-            self.base_directory.unwrap_or_else(|| {
-                env::current_dir().expect("Failed to get the current directory")
-            }),
-            self.logger
-                .unwrap_or_else(|| slog::Logger::root(slog::Discard, o!())))
+    ///
+    /// Fails if a configured `.runtimeconfig.json` or `.deps.json` file can't be read, or refers to a framework
+    /// that can't be found.
+    pub fn build(self) -> Result<Runtime, Error> {
+        let base_directory = self.base_directory.unwrap_or_else(|| {
+            env::current_dir().expect("Failed to get the current directory")
+        });
+        let logger = self.logger
+            .unwrap_or_else(|| slog::Logger::root(slog::Discard, o!()));
+
+        let mut runtime = Runtime::new(base_directory, logger);
+        runtime.app_context().set_verify_strong_names(self.verify_strong_names);
+        runtime.vm.heap.set_mode(self.gc_mode);
+        runtime.vm.heap.set_stress(self.gc_stress);
+        #[cfg(feature = "jit")]
+        runtime.vm.jit.set_thresholds(self.tier_thresholds);
+        runtime.vm.internal_calls = self.internal_calls;
+        runtime.configure(
+            self.runtime_config.as_deref(),
+            self.deps_file.as_deref(),
+            self.framework_root.as_deref(),
+        )?;
+        Ok(runtime)
     }
 
     /// Sets the base directory for the Runtime and returns the builder (for method chaining)
@@ -40,31 +86,190 @@ impl RuntimeBuilder {
         self.logger = Some(logger);
         self
     }
+
+    /// Sets the `.runtimeconfig.json` file to read the application's framework references and probing paths from.
+    pub fn runtime_config(mut self, runtime_config: &Path) -> RuntimeBuilder {
+        self.runtime_config = Some(runtime_config.into());
+        self
+    }
+
+    /// Sets the `.deps.json` file to read the application's dependency paths from.
+    pub fn deps_file(mut self, deps_file: &Path) -> RuntimeBuilder {
+        self.deps_file = Some(deps_file.into());
+        self
+    }
+
+    /// Sets whether assemblies are refused when loaded unless their strong-name signature matches their contents,
+    /// or they have no strong name.
+    pub fn verify_strong_names(mut self, verify_strong_names: bool) -> RuntimeBuilder {
+        self.verify_strong_names = verify_strong_names;
+        self
+    }
+
+    /// Sets how the managed heap collects garbage. The default is `GcMode::MarkSweep`.
+    pub fn gc_mode(mut self, gc_mode: GcMode) -> RuntimeBuilder {
+        self.gc_mode = gc_mode;
+        self
+    }
+
+    /// Sets whether the runtime collects garbage before every allocation, to find references the collector
+    /// doesn't know about. This makes programs very slow.
+    pub fn gc_stress(mut self, gc_stress: bool) -> RuntimeBuilder {
+        self.gc_stress = gc_stress;
+        self
+    }
+
+    /// Sets when methods are compiled to native code, and compiled again with what their first compiled code saw.
+    #[cfg(feature = "jit")]
+    pub fn tier_thresholds(mut self, tier_thresholds: TierThresholds) -> RuntimeBuilder {
+        self.tier_thresholds = tier_thresholds;
+        self
+    }
+
+    /// Sets the framework root (the `fx` directory), used to locate frameworks referenced by the `.runtimeconfig.json` file.
+    pub fn framework_root(mut self, framework_root: &Path) -> RuntimeBuilder {
+        self.framework_root = Some(framework_root.into());
+        self
+    }
+
+    /// Binds the internal calls with a name, such as `System.Console::WriteLine(System.String)`, to a function,
+    /// replacing the intrinsic or function they were bound to.
+    pub fn internal_call<F>(mut self, name: &str, call: F) -> RuntimeBuilder
+    where
+        F: Fn(&mut CallContext, Vec<Value>) -> Result<Option<Value>, Error> + 'static,
+    {
+        self.internal_calls.register(name, call);
+        self
+    }
 }
 
 pub struct Runtime {
-    app_context: AppContext,
+    vm: Vm,
     logger: slog::Logger,
 }
 
 impl Runtime {
     fn new(base_directory: PathBuf, logger: slog::Logger) -> Runtime {
         let base_dir_str = base_directory.clone().into_os_string().into_string().expect("Unable to convert path to string!");
+        let app_context = AppContext::new(
+            base_directory,
+            logger.new(o!("base_directory" => base_dir_str)),
+        );
         Runtime {
-            app_context: AppContext::new(
-                base_directory,
-                logger.new(o!("base_directory" => base_dir_str)),
-            ),
+            vm: Vm::new(TypeSystem::new(app_context, logger.clone()), logger.clone()),
             logger: logger,
         }
     }
 
+    fn app_context(&mut self) -> &mut AppContext {
+        self.vm.types.app_context_mut()
+    }
+
+    fn configure(&mut self, runtime_config: Option<&Path>, deps_file: Option<&Path>, framework_root: Option<&Path>) -> Result<(), Error> {
+        let mut package_roots = Vec::new();
+
+        if let Some(path) = runtime_config {
+            debug!(self.logger, "reading runtime config"; "path" => path.display());
+            let config = RuntimeConfig::load(path)?;
+
+            for reference in config.frameworks() {
+                let fx_root = framework_root
+                    .ok_or_else(|| Error::FrameworkNotFound(format!("{} v{}", reference.name, reference.version)))?;
+                let framework = Framework::resolve(fx_root, reference)?;
+                info!(self.logger, "using framework {} v{} from {}", framework.name, framework.version, framework.directory.display());
+                for dir in framework.probe_directories(fx_root) {
+                    self.app_context().add_framework_directory(dir);
+                }
+            }
+
+            package_roots.extend(config.additional_probing_paths().iter().cloned());
+        }
+
+        if let Some(path) = deps_file {
+            debug!(self.logger, "reading deps file"; "path" => path.display());
+            let deps = DepsFile::load(path)?;
+            let app_directory = path.parent().unwrap_or_else(|| Path::new("."));
+            for assembly in deps.resolve_assemblies(app_directory, &package_roots) {
+                debug!(self.logger, "dependency {} at {}", assembly.name, assembly.path.display());
+                self.app_context().add_known_assembly(assembly.name, assembly.path);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs an assembly's entry point, and gets its exit code.
+    ///
+    /// A `Main` that returns `void` exits with 0. If the program doesn't handle an exception, its type, message and
+    /// stack trace are printed to stderr, and it exits with `UNHANDLED_EXCEPTION_EXIT_CODE`.
     pub fn execute(&mut self, assembly_name: &str) -> Result<i32, Error> {
+        match self.run_main(assembly_name) {
+            Err(Error::UnhandledException(report)) => {
+                self.vm.unhandled = None;
+                eprintln!("Unhandled exception. {}", report);
+                Ok(UNHANDLED_EXCEPTION_EXIT_CODE)
+            }
+            result => result,
+        }
+    }
+
+    /// Runs an assembly's entry point and waits for the threads it started, and gets its exit code, or the report of
+    /// an unhandled exception as an error.
+    pub(crate) fn run_main(&mut self, assembly_name: &str) -> Result<i32, Error> {
         debug!(self.logger, "executing assembly"; "assembly" => assembly_name);
 
-        // Load the assembly
-        let assembly = self.app_context.load(assembly_name)?;
+        let assembly = self.vm.types.load_assembly(assembly_name)?;
+        let entry_point = self.vm.types
+            .entry_point(assembly)?
+            .ok_or_else(|| Error::MissingMethod(format!("{} has no entry point", assembly_name)))?;
+
+        // The other threads run until they finish even if the main thread fails, and what went wrong on the main
+        // thread is reported first
+        let result = self.run_entry_point(assembly_name, entry_point);
+        let finished = self.vm.finish_threads();
+        let exit_code = result?;
+        finished?;
+        Ok(exit_code)
+    }
+
+    fn run_entry_point(&mut self, assembly_name: &str, entry_point: MethodId) -> Result<i32, Error> {
+        self.vm.initialize_for_call(entry_point)?;
+        let mut args = Vec::new();
+        if self.vm.types.method(entry_point).signature.params.len() == 1 {
+            let string = self.vm.types.string()?;
+            let string_array = self.vm.types.sz_array(string)?;
+            args.push(Value::Ref(self.vm.new_array(string_array, 0)?));
+        }
+
+        match self.vm.invoke(entry_point, args)? {
+            Some(Value::I32(exit_code)) => Ok(exit_code),
+            None => Ok(0),
+            Some(_) => Err(Error::InvalidProgram(format!("{} has an invalid entry point", assembly_name))),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn types(&mut self) -> &mut TypeSystem {
+        &mut self.vm.types
+    }
+
+    #[cfg(all(test, feature = "jit"))]
+    pub(crate) fn compiled_methods(&self) -> Vec<String> {
+        self.vm.compiled_methods()
+    }
+
+    #[cfg(all(test, feature = "jit"))]
+    pub(crate) fn optimized_methods(&self) -> Vec<String> {
+        self.vm.optimized_methods()
+    }
+
+    /// Gets statistics about the managed heap.
+    pub fn gc_stats(&self) -> GcStats {
+        self.vm.heap.stats()
+    }
 
-        Ok(1)
+    /// Collects garbage now, rather than waiting until enough has been allocated.
+    pub fn collect_garbage(&mut self) {
+        self.vm.collect();
     }
 }
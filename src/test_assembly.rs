@@ -0,0 +1,1300 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use ecma355metadata::cli::il::{Opcode, OperandType};
+use ecma355metadata::cli::tables::TableIndex;
+
+use error::Error;
+use runtime::{Runtime, RuntimeBuilder};
+use test_directory::TestDirectory;
+
+/// The RVA of the only section in the images `AssemblyBuilder` writes, which starts with the CLI header.
+const SECTION_RVA: u32 = 0x1000;
+
+/// The RVA of the method bodies, immediately after the CLI header.
+const CODE_RVA: u32 = SECTION_RVA + 72;
+
+// Type, method and field attributes used by tests
+pub const PUBLIC: u32 = 0x1;
+pub const ABSTRACT_CLASS: u32 = 0x80;
+pub const SEALED: u32 = 0x100;
+pub const BEFORE_FIELD_INIT: u32 = 0x0010_0000;
+pub const STATIC: u16 = 0x10;
+pub const VIRTUAL: u16 = 0x40;
+pub const FINAL: u16 = 0x20;
+pub const ABSTRACT: u16 = 0x400;
+pub const PRIVATE_METHOD: u16 = 0x1;
+pub const NEW_SLOT: u16 = 0x100;
+pub const CONSTRUCTOR: u16 = 0x1800;
+pub const TYPE_INITIALIZER: u16 = 0x1810;
+pub const STATIC_FIELD: u16 = 0x10;
+pub const PRIVATE_FIELD: u16 = 0x1;
+pub const LITERAL_FIELD: u16 = 0x8056;
+pub const RVA_FIELD: u16 = 0x110;
+pub const INTERFACE: u32 = 0xA0;
+pub const PUBLIC_METHOD: u16 = 0x6;
+pub const INTERNAL_CALL: u16 = 0x1000;
+pub const RUNTIME_CODE: u16 = 0x3;
+pub const PINVOKE_IMPL: u16 = 0x2000;
+pub const HAS_FIELD_MARSHAL: u16 = 0x2000;
+
+// ImplMap mapping flags
+pub const CHAR_SET_UNICODE: u16 = 0x4;
+pub const SUPPORTS_LAST_ERROR: u16 = 0x40;
+pub const CALL_CONV_WINAPI: u16 = 0x100;
+
+// Generic parameter variance and constraint flags
+pub const COVARIANT: u16 = 0x1;
+pub const CONTRAVARIANT: u16 = 0x2;
+pub const REFERENCE_TYPE: u16 = 0x4;
+pub const VALUE_TYPE: u16 = 0x8;
+pub const DEFAULT_CONSTRUCTOR: u16 = 0x10;
+
+/// A type in a signature, encoded as ECMA-335 II.23.2.12 describes.
+#[derive(Clone, Debug)]
+pub enum Ty {
+    Void,
+    Boolean,
+    Char,
+    I1,
+    U1,
+    I2,
+    U2,
+    I4,
+    U4,
+    I8,
+    U8,
+    R4,
+    R8,
+    String,
+    Object,
+    I,
+    U,
+
+    /// A class, given as a TypeDef, TypeRef or TypeSpec token.
+    Class(u32),
+    ValueType(u32),
+    SzArray(Box<Ty>),
+
+    /// An array with a rank, with no sizes or lower bounds in its shape.
+    Array(Box<Ty>, u32),
+    ByRef(Box<Ty>),
+    Ptr(Box<Ty>),
+
+    /// A generic type instantiated with type arguments, which is a `Class` or `ValueType`.
+    GenericInst(Box<Ty>, Vec<Ty>),
+    Var(u32),
+    MVar(u32),
+}
+
+impl Ty {
+    pub fn sz_array(element: Ty) -> Ty {
+        Ty::SzArray(Box::new(element))
+    }
+
+    pub fn array(element: Ty, rank: u32) -> Ty {
+        Ty::Array(Box::new(element), rank)
+    }
+
+    pub fn by_ref(target: Ty) -> Ty {
+        Ty::ByRef(Box::new(target))
+    }
+
+    pub fn generic_inst(definition: Ty, args: Vec<Ty>) -> Ty {
+        Ty::GenericInst(Box::new(definition), args)
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match *self {
+            Ty::Void => buf.push(0x01),
+            Ty::Boolean => buf.push(0x02),
+            Ty::Char => buf.push(0x03),
+            Ty::I1 => buf.push(0x04),
+            Ty::U1 => buf.push(0x05),
+            Ty::I2 => buf.push(0x06),
+            Ty::U2 => buf.push(0x07),
+            Ty::I4 => buf.push(0x08),
+            Ty::U4 => buf.push(0x09),
+            Ty::I8 => buf.push(0x0A),
+            Ty::U8 => buf.push(0x0B),
+            Ty::R4 => buf.push(0x0C),
+            Ty::R8 => buf.push(0x0D),
+            Ty::String => buf.push(0x0E),
+            Ty::Ptr(ref target) => {
+                buf.push(0x0F);
+                target.encode(buf);
+            }
+            Ty::ByRef(ref target) => {
+                buf.push(0x10);
+                target.encode(buf);
+            }
+            Ty::ValueType(token) => {
+                buf.push(0x11);
+                write_compressed(buf, type_def_or_ref(token));
+            }
+            Ty::Class(token) => {
+                buf.push(0x12);
+                write_compressed(buf, type_def_or_ref(token));
+            }
+            Ty::I => buf.push(0x18),
+            Ty::U => buf.push(0x19),
+            Ty::Object => buf.push(0x1C),
+            Ty::SzArray(ref element) => {
+                buf.push(0x1D);
+                element.encode(buf);
+            }
+            Ty::Array(ref element, rank) => {
+                buf.push(0x14);
+                element.encode(buf);
+                write_compressed(buf, rank);
+                buf.extend_from_slice(&[0, 0]);
+            }
+            Ty::GenericInst(ref definition, ref args) => {
+                buf.push(0x15);
+                definition.encode(buf);
+                write_compressed(buf, args.len() as u32);
+                for arg in args {
+                    arg.encode(buf);
+                }
+            }
+            Ty::Var(index) => {
+                buf.push(0x13);
+                write_compressed(buf, index);
+            }
+            Ty::MVar(index) => {
+                buf.push(0x1E);
+                write_compressed(buf, index);
+            }
+        }
+    }
+}
+
+pub fn method_sig(has_this: bool, ret: Ty, params: &[Ty]) -> Vec<u8> {
+    let mut buf = vec![if has_this { 0x20 } else { 0 }];
+    write_compressed(&mut buf, params.len() as u32);
+    ret.encode(&mut buf);
+    for param in params {
+        param.encode(&mut buf);
+    }
+    buf
+}
+
+/// Encodes the signature of a generic method with `arity` type parameters.
+pub fn generic_method_sig(has_this: bool, arity: u32, ret: Ty, params: &[Ty]) -> Vec<u8> {
+    let mut buf = method_sig(has_this, ret, params);
+    buf[0] |= 0x10;
+    let mut arity_buf = Vec::new();
+    write_compressed(&mut arity_buf, arity);
+    buf.splice(1..1, arity_buf);
+    buf
+}
+
+pub fn field_sig(ty: Ty) -> Vec<u8> {
+    let mut buf = vec![0x06];
+    ty.encode(&mut buf);
+    buf
+}
+
+fn write_compressed(buf: &mut Vec<u8>, value: u32) {
+    if value < 0x80 {
+        buf.push(value as u8);
+    } else if value < 0x4000 {
+        buf.extend_from_slice(&(0x8000 | value as u16).to_be_bytes());
+    } else {
+        buf.extend_from_slice(&(0xC000_0000 | value).to_be_bytes());
+    }
+}
+
+fn row(token: u32) -> u32 {
+    token & 0x00FF_FFFF
+}
+
+fn table(token: u32) -> u8 {
+    (token >> 24) as u8
+}
+
+/// Encodes a token as a TypeDefOrRef coded index, which signatures also use.
+fn type_def_or_ref(token: u32) -> u32 {
+    let tag = match table(token) {
+        0x02 => 0,
+        0x01 => 1,
+        0x1B => 2,
+        _ => panic!("0x{:08X} is not a type token", token),
+    };
+    (row(token) << 2) | tag
+}
+
+fn method_def_or_ref(token: u32) -> u32 {
+    let tag = match table(token) {
+        0x06 => 0,
+        0x0A => 1,
+        _ => panic!("0x{:08X} is not a method token", token),
+    };
+    (row(token) << 1) | tag
+}
+
+fn member_ref_parent(token: u32) -> u32 {
+    let tag = match table(token) {
+        0x02 => 0,
+        0x01 => 1,
+        0x1A => 2,
+        0x06 => 3,
+        0x1B => 4,
+        _ => panic!("0x{:08X} can't be the parent of a member", token),
+    };
+    (row(token) << 3) | tag
+}
+
+fn resolution_scope(token: u32) -> u32 {
+    let tag = match table(token) {
+        0x00 => 0,
+        0x1A => 1,
+        0x23 => 2,
+        0x01 => 3,
+        _ => panic!("0x{:08X} can't be a resolution scope", token),
+    };
+    (row(token) << 2) | tag
+}
+
+/// A position in IL code, which branches can refer to before it is marked.
+#[derive(Copy, Clone, Debug)]
+pub struct Label(usize);
+
+/// Assembles IL code. Operands are encoded as each opcode's operand type requires.
+pub struct Il {
+    code: Vec<u8>,
+    labels: Vec<Option<u32>>,
+
+    /// The position of each branch operand to fill in, its size, the offset branches are relative to, and the label.
+    fixups: Vec<(usize, usize, u32, Label)>,
+}
+
+impl Il {
+    pub fn new() -> Il {
+        Il {
+            code: Vec::new(),
+            labels: Vec::new(),
+            fixups: Vec::new(),
+        }
+    }
+
+    fn opcode(&mut self, opcode: Opcode) {
+        if opcode.size() == 2 {
+            self.code.push(Opcode::EXTENDED_PREFIX);
+        }
+        self.code.push(opcode.value() as u8);
+    }
+
+    /// Emits an instruction without an operand.
+    pub fn op(&mut self, opcode: Opcode) -> &mut Il {
+        assert_eq!(OperandType::InlineNone, opcode.operand_type(), "{} takes an operand", opcode);
+        self.opcode(opcode);
+        self
+    }
+
+    /// Emits an instruction with an integer, variable index or token operand.
+    pub fn arg(&mut self, opcode: Opcode, operand: i64) -> &mut Il {
+        self.opcode(opcode);
+        match opcode.operand_type() {
+            OperandType::ShortInlineI | OperandType::ShortInlineVar => self.code.push(operand as u8),
+            OperandType::InlineVar => self.code.extend_from_slice(&(operand as u16).to_le_bytes()),
+            OperandType::InlineI8 => self.code.extend_from_slice(&operand.to_le_bytes()),
+            OperandType::InlineI
+            | OperandType::InlineMethod
+            | OperandType::InlineField
+            | OperandType::InlineType
+            | OperandType::InlineString
+            | OperandType::InlineSig
+            | OperandType::InlineTok => self.code.extend_from_slice(&(operand as u32).to_le_bytes()),
+            other => panic!("{} takes a {:?} operand", opcode, other),
+        }
+        self
+    }
+
+    pub fn ldc_i4(&mut self, value: i32) -> &mut Il {
+        self.arg(Opcode::LdcI4, value as i64)
+    }
+
+    pub fn ldc_r8(&mut self, value: f64) -> &mut Il {
+        self.opcode(Opcode::LdcR8);
+        self.code.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Places a label at the next instruction.
+    pub fn mark(&mut self, label: Label) -> &mut Il {
+        self.labels[label.0] = Some(self.code.len() as u32);
+        self
+    }
+
+    pub fn branch(&mut self, opcode: Opcode, label: Label) -> &mut Il {
+        self.opcode(opcode);
+        let size = if opcode.operand_type() == OperandType::ShortInlineBrTarget { 1 } else { 4 };
+        let position = self.code.len();
+        self.code.extend(vec![0; size]);
+        let next = self.code.len() as u32;
+        self.fixups.push((position, size, next, label));
+        self
+    }
+
+    pub fn switch(&mut self, labels: &[Label]) -> &mut Il {
+        self.opcode(Opcode::Switch);
+        self.code.extend_from_slice(&(labels.len() as u32).to_le_bytes());
+        let position = self.code.len();
+        self.code.extend(vec![0; 4 * labels.len()]);
+        let next = self.code.len() as u32;
+        for (i, &label) in labels.iter().enumerate() {
+            self.fixups.push((position + 4 * i, 4, next, label));
+        }
+        self
+    }
+
+    fn offset(&self, label: Label) -> u32 {
+        self.labels[label.0].expect("A clause's region was never marked")
+    }
+
+    fn finish(&self) -> Vec<u8> {
+        let mut code = self.code.clone();
+        for &(position, size, next, label) in &self.fixups {
+            let target = self.labels[label.0].expect("A branch target was never marked");
+            let delta = target as i64 - next as i64;
+            if size == 1 {
+                code[position] = delta as i8 as u8;
+            } else {
+                code[position..(position + 4)].copy_from_slice(&(delta as i32).to_le_bytes());
+            }
+        }
+        code
+    }
+}
+
+/// What an exception handling clause does, with the type of a catch clause given as a token.
+#[derive(Copy, Clone, Debug)]
+pub enum Handler {
+    Catch(u32),
+    Filter(Label),
+    Finally,
+    Fault,
+}
+
+/// An exception handling clause, with its protected region and handler between labels.
+#[derive(Copy, Clone, Debug)]
+pub struct Clause {
+    pub handler: Handler,
+    pub try_start: Label,
+    pub try_end: Label,
+    pub handler_start: Label,
+    pub handler_end: Label,
+}
+
+/// The body of a method: its IL, local variables and exception handling clauses.
+pub struct Body {
+    pub max_stack: u16,
+    pub locals: Vec<Ty>,
+    pub il: Il,
+    pub clauses: Vec<Clause>,
+}
+
+impl Body {
+    pub fn new(locals: Vec<Ty>, il: Il) -> Body {
+        Body {
+            max_stack: 8,
+            locals,
+            il,
+            clauses: Vec::new(),
+        }
+    }
+
+    /// Adds exception handling clauses, innermost first, and returns the body.
+    pub fn with_clauses(mut self, clauses: Vec<Clause>) -> Body {
+        self.clauses = clauses;
+        self
+    }
+}
+
+/// Builds an assembly's metadata, and writes it as a PE image.
+///
+/// Fields and methods belong to the last type defined. All heaps and tables must be small enough to use 2-byte
+/// indexes.
+pub struct AssemblyBuilder {
+    strings: Vec<u8>,
+    string_offsets: HashMap<String, u32>,
+    blobs: Vec<u8>,
+    user_strings: Vec<u8>,
+    tables: BTreeMap<u8, Vec<Vec<u8>>>,
+    code: Vec<u8>,
+    entry_point: u32,
+    type_refs: HashMap<(String, String), u32>,
+}
+
+impl AssemblyBuilder {
+    pub fn new(name: &str) -> AssemblyBuilder {
+        let mut builder = AssemblyBuilder {
+            strings: vec![0],
+            string_offsets: HashMap::new(),
+            blobs: vec![0],
+            user_strings: vec![0],
+            tables: BTreeMap::new(),
+            code: Vec::new(),
+            entry_point: 0,
+            type_refs: HashMap::new(),
+        };
+
+        let mut module = Vec::new();
+        let name_index = builder.string(name);
+        put_u16(&mut module, 0);
+        put_u16(&mut module, name_index);
+        put_u16(&mut module, 1);
+        put_u16(&mut module, 0);
+        put_u16(&mut module, 0);
+        builder.add_row(TableIndex::Module, module);
+
+        let mut assembly = Vec::new();
+        put_u32(&mut assembly, 0x8004);
+        for _ in 0..4 {
+            put_u16(&mut assembly, 0);
+        }
+        put_u32(&mut assembly, 0);
+        put_u16(&mut assembly, 0);
+        put_u16(&mut assembly, name_index);
+        put_u16(&mut assembly, 0);
+        builder.add_row(TableIndex::Assembly, assembly);
+
+        builder.type_def(0, "", "<Module>", 0);
+        builder
+    }
+
+    fn string(&mut self, value: &str) -> u32 {
+        if value.is_empty() {
+            return 0;
+        }
+        if let Some(&offset) = self.string_offsets.get(value) {
+            return offset;
+        }
+        let offset = self.strings.len() as u32;
+        self.strings.extend_from_slice(value.as_bytes());
+        self.strings.push(0);
+        self.string_offsets.insert(value.into(), offset);
+        offset
+    }
+
+    fn blob(&mut self, value: &[u8]) -> u32 {
+        let offset = self.blobs.len() as u32;
+        write_compressed(&mut self.blobs, value.len() as u32);
+        self.blobs.extend_from_slice(value);
+        offset
+    }
+
+    /// Adds a row to a table, and gets its token.
+    fn add_row(&mut self, table: TableIndex, row: Vec<u8>) -> u32 {
+        let rows = self.tables.entry(table as u8).or_default();
+        rows.push(row);
+        ((table as u32) << 24) | rows.len() as u32
+    }
+
+    fn row_count(&self, table: TableIndex) -> u32 {
+        self.tables.get(&(table as u8)).map_or(0, |rows| rows.len() as u32)
+    }
+
+    pub fn assembly_ref(&mut self, name: &str) -> u32 {
+        let mut row = Vec::new();
+        for _ in 0..4 {
+            put_u16(&mut row, 0);
+        }
+        put_u32(&mut row, 0);
+        put_u16(&mut row, 0);
+        let name = self.string(name);
+        put_u16(&mut row, name);
+        put_u16(&mut row, 0);
+        put_u16(&mut row, 0);
+        self.add_row(TableIndex::AssemblyRef, row)
+    }
+
+    pub fn type_ref(&mut self, scope: u32, namespace: &str, name: &str) -> u32 {
+        let mut row = Vec::new();
+        put_u16(&mut row, resolution_scope(scope));
+        let (name, namespace) = (self.string(name), self.string(namespace));
+        put_u16(&mut row, name);
+        put_u16(&mut row, namespace);
+        self.add_row(TableIndex::TypeRef, row)
+    }
+
+    /// Gets a TypeRef to a type in the core library, adding the reference to the core library the first time.
+    pub fn corlib_type(&mut self, namespace: &str, name: &str) -> u32 {
+        let key = (namespace.to_owned(), name.to_owned());
+        if let Some(&token) = self.type_refs.get(&key) {
+            return token;
+        }
+        let corlib = match self.type_refs.get(&(String::new(), String::new())) {
+            Some(&token) => token,
+            None => {
+                let token = self.assembly_ref("corlib");
+                self.type_refs.insert((String::new(), String::new()), token);
+                token
+            }
+        };
+        let token = self.type_ref(corlib, namespace, name);
+        self.type_refs.insert(key, token);
+        token
+    }
+
+    /// Defines a type, which extends the type `extends` refers to, or nothing if it is 0.
+    pub fn type_def(&mut self, flags: u32, namespace: &str, name: &str, extends: u32) -> u32 {
+        let mut row = Vec::new();
+        put_u32(&mut row, flags);
+        let (name, namespace) = (self.string(name), self.string(namespace));
+        put_u16(&mut row, name);
+        put_u16(&mut row, namespace);
+        put_u16(&mut row, if extends == 0 { 0 } else { type_def_or_ref(extends) });
+        let (fields, methods) = (self.row_count(TableIndex::Field), self.row_count(TableIndex::MethodDef));
+        put_u16(&mut row, fields + 1);
+        put_u16(&mut row, methods + 1);
+        self.add_row(TableIndex::TypeDef, row)
+    }
+
+    pub fn field(&mut self, flags: u16, name: &str, ty: Ty) -> u32 {
+        let mut row = Vec::new();
+        put_u16(&mut row, flags as u32);
+        let name = self.string(name);
+        put_u16(&mut row, name);
+        let signature = self.blob(&field_sig(ty));
+        put_u16(&mut row, signature);
+        self.add_row(TableIndex::Field, row)
+    }
+
+    /// Defines a method, with a body unless it is abstract.
+    pub fn method(&mut self, flags: u16, name: &str, signature: &[u8], body: Option<Body>) -> u32 {
+        let rva = match body {
+            Some(body) => self.method_body(body),
+            None => 0,
+        };
+        self.method_row(rva, 0, flags, name, signature)
+    }
+
+    /// Defines a method marked `[MethodImpl(MethodImplOptions.InternalCall)]`, which has no body.
+    pub fn internal_method(&mut self, flags: u16, name: &str, signature: &[u8]) -> u32 {
+        self.method_row(0, INTERNAL_CALL, flags, name, signature)
+    }
+
+    /// Defines a method whose code the runtime provides, as it does for the methods of delegate types.
+    pub fn runtime_method(&mut self, flags: u16, name: &str, signature: &[u8]) -> u32 {
+        self.method_row(0, RUNTIME_CODE, flags, name, signature)
+    }
+
+    fn method_row(&mut self, rva: u32, impl_flags: u16, flags: u16, name: &str, signature: &[u8]) -> u32 {
+        let mut row = Vec::new();
+        put_u32(&mut row, rva);
+        put_u16(&mut row, impl_flags as u32);
+        put_u16(&mut row, flags as u32);
+        let name = self.string(name);
+        put_u16(&mut row, name);
+        let signature = self.blob(signature);
+        put_u16(&mut row, signature);
+        let params = self.row_count(TableIndex::Param);
+        put_u16(&mut row, params + 1);
+        self.add_row(TableIndex::MethodDef, row)
+    }
+
+    /// Adds a Param row to the method defined last, numbered from 1 for its parameters or 0 for its return value.
+    pub fn param(&mut self, flags: u16, sequence: u16, name: &str) -> u32 {
+        let mut row = Vec::new();
+        put_u16(&mut row, flags as u32);
+        put_u16(&mut row, sequence as u32);
+        let name = self.string(name);
+        put_u16(&mut row, name);
+        self.add_row(TableIndex::Param, row)
+    }
+
+    /// Gives a Param a marshaling descriptor in the FieldMarshal table, as `[MarshalAs]` does. The Param needs
+    /// `HAS_FIELD_MARSHAL` in its flags.
+    pub fn field_marshal(&mut self, param: u32, descriptor: &[u8]) {
+        let mut row = Vec::new();
+        put_u16(&mut row, (self::row(param) << 1) | 1);
+        let descriptor = self.blob(descriptor);
+        put_u16(&mut row, descriptor);
+        self.add_row(TableIndex::FieldMarshal, row);
+    }
+
+    /// Makes a static method without a body a P/Invoke of a function in a native library, as `[DllImport]` does.
+    /// The method needs `PINVOKE_IMPL` in its flags.
+    pub fn impl_map(&mut self, method: u32, flags: u16, library: &str, entry_point: &str) {
+        let mut module_ref = Vec::new();
+        let library = self.string(library);
+        put_u16(&mut module_ref, library);
+        let module_ref = self.add_row(TableIndex::ModuleRef, module_ref);
+        let mut row = Vec::new();
+        put_u16(&mut row, flags as u32);
+        put_u16(&mut row, (self::row(method) << 1) | 1);
+        let entry_point = self.string(entry_point);
+        put_u16(&mut row, entry_point);
+        put_u16(&mut row, self::row(module_ref));
+        self.add_row(TableIndex::ImplMap, row);
+    }
+
+    /// Writes a method body with a fat header, and a fat exception handling section if it has clauses, and gets its
+    /// RVA.
+    fn method_body(&mut self, body: Body) -> u32 {
+        let local_var_sig = if body.locals.is_empty() {
+            0
+        } else {
+            let mut signature = vec![0x07];
+            write_compressed(&mut signature, body.locals.len() as u32);
+            for local in &body.locals {
+                local.encode(&mut signature);
+            }
+            let signature = self.blob(&signature);
+            let mut row = Vec::new();
+            put_u16(&mut row, signature);
+            self.add_row(TableIndex::StandAloneSig, row)
+        };
+
+        let code = body.il.finish();
+        let rva = CODE_RVA + self.code.len() as u32;
+        let more_sects = if body.clauses.is_empty() { 0 } else { 0x8 };
+        put_u16(&mut self.code, 0x3 | more_sects | 0x10 | (3 << 12));
+        put_u16(&mut self.code, body.max_stack as u32);
+        put_u32(&mut self.code, code.len() as u32);
+        put_u32(&mut self.code, local_var_sig);
+        self.code.extend(code);
+        pad(&mut self.code);
+
+        if !body.clauses.is_empty() {
+            // EHTable | FatFormat, then the 3-byte size of the section
+            put_u32(&mut self.code, 0x41 | (body.clauses.len() as u32 * 24 + 4) << 8);
+            for clause in &body.clauses {
+                let (flags, extra) = match clause.handler {
+                    Handler::Catch(token) => (0, token),
+                    Handler::Filter(filter) => (1, body.il.offset(filter)),
+                    Handler::Finally => (2, 0),
+                    Handler::Fault => (4, 0),
+                };
+                let try_start = body.il.offset(clause.try_start);
+                let handler_start = body.il.offset(clause.handler_start);
+                put_u32(&mut self.code, flags);
+                put_u32(&mut self.code, try_start);
+                put_u32(&mut self.code, body.il.offset(clause.try_end) - try_start);
+                put_u32(&mut self.code, handler_start);
+                put_u32(&mut self.code, body.il.offset(clause.handler_end) - handler_start);
+                put_u32(&mut self.code, extra);
+            }
+        }
+        rva
+    }
+
+    /// Declares a generic parameter of a TypeDef or MethodDef, with the constraint flags.
+    pub fn generic_param(&mut self, owner: u32, number: u16, flags: u16, name: &str) -> u32 {
+        let tag = match table(owner) {
+            0x02 => 0,
+            0x06 => 1,
+            _ => panic!("0x{:08X} can't have generic parameters", owner),
+        };
+        let mut row = Vec::new();
+        put_u16(&mut row, number as u32);
+        put_u16(&mut row, flags as u32);
+        put_u16(&mut row, (self::row(owner) << 1) | tag);
+        let name = self.string(name);
+        put_u16(&mut row, name);
+        self.add_row(TableIndex::GenericParam, row)
+    }
+
+    /// Constrains a generic parameter to types that derive from or implement a type.
+    pub fn generic_param_constraint(&mut self, param: u32, constraint: u32) {
+        let mut row = Vec::new();
+        put_u16(&mut row, self::row(param));
+        put_u16(&mut row, type_def_or_ref(constraint));
+        self.add_row(TableIndex::GenericParamConstraint, row);
+    }
+
+    /// Declares that a type implements an interface.
+    pub fn interface_impl(&mut self, class: u32, interface: u32) {
+        let mut row = Vec::new();
+        put_u16(&mut row, self::row(class));
+        put_u16(&mut row, type_def_or_ref(interface));
+        self.add_row(TableIndex::InterfaceImpl, row);
+    }
+
+    /// Declares that a method of a type implements or overrides a method, given their MethodDef or MemberRef tokens,
+    /// as explicit interface implementations do.
+    pub fn method_impl(&mut self, class: u32, body: u32, declaration: u32) {
+        let mut row = Vec::new();
+        put_u16(&mut row, self::row(class));
+        put_u16(&mut row, method_def_or_ref(body));
+        put_u16(&mut row, method_def_or_ref(declaration));
+        self.add_row(TableIndex::MethodImpl, row);
+    }
+
+    /// Adds a StandAloneSig for the signature of a `calli` call site, and gets its token.
+    pub fn stand_alone_sig(&mut self, signature: &[u8]) -> u32 {
+        let signature = self.blob(signature);
+        let mut row = Vec::new();
+        put_u16(&mut row, signature);
+        self.add_row(TableIndex::StandAloneSig, row)
+    }
+
+    /// Adds a TypeSpec for a type, such as a generic instantiation, and gets its token.
+    pub fn type_spec(&mut self, ty: Ty) -> u32 {
+        let mut signature = Vec::new();
+        ty.encode(&mut signature);
+        let signature = self.blob(&signature);
+        let mut row = Vec::new();
+        put_u16(&mut row, signature);
+        self.add_row(TableIndex::TypeSpec, row)
+    }
+
+    /// Adds a MethodSpec instantiating a generic MethodDef or MemberRef, and gets its token.
+    pub fn method_spec(&mut self, method: u32, args: &[Ty]) -> u32 {
+        let tag = match table(method) {
+            0x06 => 0,
+            0x0A => 1,
+            _ => panic!("0x{:08X} is not a method token", method),
+        };
+        let mut instantiation = vec![0x0A];
+        write_compressed(&mut instantiation, args.len() as u32);
+        for arg in args {
+            arg.encode(&mut instantiation);
+        }
+        let instantiation = self.blob(&instantiation);
+        let mut row = Vec::new();
+        put_u16(&mut row, (self::row(method) << 1) | tag);
+        put_u16(&mut row, instantiation);
+        self.add_row(TableIndex::MethodSpec, row)
+    }
+
+    /// Gives a literal field its value in the Constant table, as an integer of a primitive type.
+    pub fn constant(&mut self, field: u32, ty: Ty, value: i64) {
+        let size = match ty {
+            Ty::Boolean | Ty::I1 | Ty::U1 => 1,
+            Ty::Char | Ty::I2 | Ty::U2 => 2,
+            Ty::I4 | Ty::U4 => 4,
+            Ty::I8 | Ty::U8 => 8,
+            _ => panic!("{:?} is not an integer type", ty),
+        };
+        let mut row = Vec::new();
+        ty.encode(&mut row);
+        row.push(0);
+        put_u16(&mut row, self::row(field) << 2);
+        let value = self.blob(&value.to_le_bytes()[..size]);
+        put_u16(&mut row, value);
+        self.add_row(TableIndex::Constant, row);
+    }
+
+    /// Gives a static field initial data in the image, through the FieldRva table. The field needs `RVA_FIELD` in
+    /// its flags.
+    pub fn field_rva(&mut self, field: u32, data: &[u8]) {
+        let rva = CODE_RVA + self.code.len() as u32;
+        self.code.extend_from_slice(data);
+        pad(&mut self.code);
+        let mut row = Vec::new();
+        put_u32(&mut row, rva);
+        put_u16(&mut row, self::row(field));
+        self.add_row(TableIndex::FieldRva, row);
+    }
+
+    /// Gives a type the size of its instances in the ClassLayout table.
+    pub fn class_layout(&mut self, ty: u32, size: u32) {
+        let mut row = Vec::new();
+        put_u16(&mut row, 0);
+        put_u32(&mut row, size);
+        put_u16(&mut row, self::row(ty));
+        self.add_row(TableIndex::ClassLayout, row);
+    }
+
+    /// Applies a custom attribute without arguments to a type, method or field, given its constructor's MethodDef
+    /// or MemberRef.
+    pub fn custom_attribute(&mut self, parent: u32, constructor: u32) {
+        let tag = match table(constructor) {
+            0x06 => 2,
+            0x0A => 3,
+            _ => panic!("0x{:08X} is not a constructor token", constructor),
+        };
+        let parent_tag = match table(parent) {
+            0x06 => 0,
+            0x04 => 1,
+            0x02 => 3,
+            _ => panic!("0x{:08X} cannot have custom attributes", parent),
+        };
+        let mut row = Vec::new();
+        put_u16(&mut row, (self::row(parent) << 5) | parent_tag);
+        put_u16(&mut row, (self::row(constructor) << 3) | tag);
+        let value = self.blob(&[1, 0, 0, 0]);
+        put_u16(&mut row, value);
+        self.add_row(TableIndex::CustomAttribute, row);
+    }
+
+    pub fn member_ref(&mut self, parent: u32, name: &str, signature: &[u8]) -> u32 {
+        let mut row = Vec::new();
+        put_u16(&mut row, member_ref_parent(parent));
+        let name = self.string(name);
+        put_u16(&mut row, name);
+        let signature = self.blob(signature);
+        put_u16(&mut row, signature);
+        self.add_row(TableIndex::MemberRef, row)
+    }
+
+    /// Adds a string literal, and gets its token for `ldstr`.
+    pub fn user_string(&mut self, value: &str) -> u32 {
+        let offset = self.user_strings.len() as u32;
+        let chars: Vec<u16> = value.encode_utf16().collect();
+        write_compressed(&mut self.user_strings, chars.len() as u32 * 2 + 1);
+        for c in chars {
+            self.user_strings.extend_from_slice(&c.to_le_bytes());
+        }
+        self.user_strings.push(0);
+        0x7000_0000 | offset
+    }
+
+    pub fn set_entry_point(&mut self, method: u32) {
+        self.entry_point = method;
+    }
+
+    pub fn write(&self, path: &Path) {
+        fs::write(path, self.build()).unwrap();
+    }
+
+    fn build(&self) -> Vec<u8> {
+        // The "#~" stream
+        let mut table_stream = Vec::new();
+        put_u32(&mut table_stream, 0);
+        table_stream.extend_from_slice(&[2, 0, 0, 1]);
+        let valid = self.tables.keys().fold(0u64, |mask, &table| mask | (1 << table));
+        put_u32(&mut table_stream, valid as u32);
+        put_u32(&mut table_stream, (valid >> 32) as u32);
+        table_stream.extend_from_slice(&[0; 8]);
+        for rows in self.tables.values() {
+            put_u32(&mut table_stream, rows.len() as u32);
+        }
+        for rows in self.tables.values() {
+            for row in rows {
+                table_stream.extend_from_slice(row);
+            }
+        }
+
+        let streams: Vec<(&[u8], &[u8])> = vec![
+            (b"#~", &table_stream),
+            (b"#Strings", &self.strings),
+            (b"#US", &self.user_strings),
+            (b"#Blob", &self.blobs),
+            (b"#GUID", &[0x11; 16]),
+        ];
+
+        // The metadata header and stream headers
+        let mut metadata = Vec::new();
+        put_u32(&mut metadata, 0x424A_5342);
+        put_u16(&mut metadata, 1);
+        put_u16(&mut metadata, 1);
+        put_u32(&mut metadata, 0);
+        put_u32(&mut metadata, 12);
+        metadata.extend_from_slice(b"v4.0.30319\0\0");
+        put_u16(&mut metadata, 0);
+        put_u16(&mut metadata, streams.len() as u32);
+        let header_size = metadata.len() + streams.iter().map(|s| 8 + (s.0.len() + 4) / 4 * 4).sum::<usize>();
+        let mut offset = header_size;
+        for &(name, data) in &streams {
+            let size = (data.len() as u32 + 3) & !3;
+            put_u32(&mut metadata, offset as u32);
+            put_u32(&mut metadata, size);
+            metadata.extend_from_slice(name);
+            metadata.extend(vec![0; 4 - name.len() % 4]);
+            offset += size as usize;
+        }
+        for &(_, data) in &streams {
+            metadata.extend_from_slice(data);
+            pad(&mut metadata);
+        }
+
+        // The section, containing the CLI header, the method bodies and the metadata
+        let metadata_rva = CODE_RVA + self.code.len() as u32;
+        let mut section = Vec::new();
+        put_u32(&mut section, 72);
+        put_u16(&mut section, 2);
+        put_u16(&mut section, 5);
+        put_u32(&mut section, metadata_rva);
+        put_u32(&mut section, metadata.len() as u32);
+        put_u32(&mut section, 1);
+        put_u32(&mut section, self.entry_point);
+        section.extend_from_slice(&[0; 48]);
+        section.extend_from_slice(&self.code);
+        section.extend(metadata);
+
+        build_image(&section)
+    }
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&(value as u16).to_le_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn pad(buf: &mut Vec<u8>) {
+    let padded = (buf.len() + 3) & !3;
+    buf.resize(padded, 0);
+}
+
+/// Builds a PE32 image with a single section at `SECTION_RVA`, which starts with the CLI header.
+fn build_image(section: &[u8]) -> Vec<u8> {
+    let mut image = vec![0u8; 0x200];
+    image[0..2].copy_from_slice(b"MZ");
+    image[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+    image[0x40..0x44].copy_from_slice(b"PE\0\0");
+    image[0x46] = 1;
+    image[0x54] = 224;
+    image[0x58] = 0x0B;
+    image[0x59] = 0x01;
+    image[(0x58 + 92)..(0x58 + 96)].copy_from_slice(&16u32.to_le_bytes());
+    image[(0x58 + 96 + 14 * 8)..(0x58 + 96 + 14 * 8 + 4)].copy_from_slice(&SECTION_RVA.to_le_bytes());
+    image[(0x58 + 96 + 14 * 8 + 4)..(0x58 + 96 + 15 * 8)].copy_from_slice(&72u32.to_le_bytes());
+
+    let header = 0x58 + 224;
+    image[header..(header + 5)].copy_from_slice(b".text");
+    image[(header + 8)..(header + 12)].copy_from_slice(&(section.len() as u32).to_le_bytes());
+    image[(header + 12)..(header + 16)].copy_from_slice(&SECTION_RVA.to_le_bytes());
+    image[(header + 16)..(header + 20)].copy_from_slice(&(section.len() as u32).to_le_bytes());
+    image[(header + 20)..(header + 24)].copy_from_slice(&0x200u32.to_le_bytes());
+    image.extend_from_slice(section);
+    image
+}
+
+/// Builds a core library with `System.Object` (with its constructor, a virtual `GetHashCode` that returns 42 and a
+/// virtual `ToString` that returns null), `System.ValueType`, `System.Enum` (with `ToString` an internal call),
+/// `System.String`, `System.Array`, the primitive types, `System.Nullable<T>`, `System.Delegate` (with internal
+/// `Combine` and `Remove` methods) and `System.MulticastDelegate`, the exceptions the runtime raises, the types it
+/// uses to initialize arrays, the reflection types with a few of their internal calls, `System.Attribute`,
+/// `System.ThreadStaticAttribute`, `System.Threading.Thread` with its `ThreadStart` delegate, a few of the internal
+/// calls of `Monitor` and `Interlocked`, `System.Console` with an internal `WriteLine(string)`, and
+/// `System.Runtime.InteropServices.Marshal` with an internal `GetLastWin32Error()`.
+pub fn corlib() -> AssemblyBuilder {
+    let mut corlib = AssemblyBuilder::new("corlib");
+    let object = corlib.type_def(PUBLIC, "System", "Object", 0);
+    let mut il = Il::new();
+    il.op(Opcode::Ret);
+    let default_ctor = method_sig(true, Ty::Void, &[]);
+    let object_ctor = corlib.method(CONSTRUCTOR, ".ctor", &default_ctor, Some(Body::new(vec![], il)));
+    let mut il = Il::new();
+    il.ldc_i4(42).op(Opcode::Ret);
+    let get_hash_code = method_sig(true, Ty::I4, &[]);
+    corlib.method(VIRTUAL | NEW_SLOT, "GetHashCode", &get_hash_code, Some(Body::new(vec![], il)));
+    let mut il = Il::new();
+    il.op(Opcode::Ldnull).op(Opcode::Ret);
+    let to_string = method_sig(true, Ty::String, &[]);
+    corlib.method(VIRTUAL | NEW_SLOT, "ToString", &to_string, Some(Body::new(vec![], il)));
+
+    let value_type = corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System", "ValueType", object);
+    corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System", "Enum", value_type);
+    corlib.internal_method(VIRTUAL, "ToString", &to_string);
+    corlib.type_def(PUBLIC | SEALED, "System", "String", object);
+    let array = corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System", "Array", object);
+    for name in &[
+        "Void", "Boolean", "Char", "SByte", "Byte", "Int16", "UInt16", "Int32", "UInt32", "Int64", "UInt64", "Single",
+        "Double", "IntPtr", "UIntPtr",
+    ] {
+        corlib.type_def(PUBLIC | SEALED, "System", name, value_type);
+    }
+
+    // struct Nullable<T> { bool hasValue; T value; }
+    let nullable = corlib.type_def(PUBLIC | SEALED, "System", "Nullable`1", value_type);
+    corlib.generic_param(nullable, 0, VALUE_TYPE, "T");
+    corlib.field(PRIVATE_FIELD, "hasValue", Ty::Boolean);
+    corlib.field(PRIVATE_FIELD, "value", Ty::Var(0));
+
+    // abstract class Delegate {
+    //     object _target; IntPtr _methodPtr;
+    //     static extern Delegate Combine(Delegate a, Delegate b); static extern Delegate Remove(Delegate source, ...);
+    // }
+    // abstract class MulticastDelegate : Delegate { object _invocationList; }
+    let delegate = corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System", "Delegate", object);
+    corlib.field(PRIVATE_FIELD, "_target", Ty::Object);
+    corlib.field(PRIVATE_FIELD, "_methodPtr", Ty::I);
+    let signature = method_sig(false, Ty::Class(delegate), &[Ty::Class(delegate), Ty::Class(delegate)]);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Combine", &signature);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Remove", &signature);
+    let multicast_delegate = corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System", "MulticastDelegate", delegate);
+    corlib.field(PRIVATE_FIELD, "_invocationList", Ty::Object);
+
+    // class Exception {
+    //     string _message; Exception _innerException;
+    //     Exception() { } Exception(string message) { ... } Exception(string message, Exception innerException) { ... }
+    // }
+    let exception = corlib.type_def(PUBLIC, "System", "Exception", object);
+    let message = corlib.field(PRIVATE_FIELD, "_message", Ty::String);
+    let inner_exception = corlib.field(PRIVATE_FIELD, "_innerException", Ty::Class(exception));
+    let message_ctor = method_sig(true, Ty::Void, &[Ty::String]);
+    let inner_ctor = method_sig(true, Ty::Void, &[Ty::String, Ty::Class(exception)]);
+    let mut il = Il::new();
+    il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64).op(Opcode::Ret);
+    let ctor = corlib.method(CONSTRUCTOR, ".ctor", &default_ctor, Some(Body::new(vec![], il)));
+    let mut il = Il::new();
+    il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64);
+    il.op(Opcode::Ldarg0).op(Opcode::Ldarg1).arg(Opcode::Stfld, message as i64).op(Opcode::Ret);
+    let with_message = corlib.method(CONSTRUCTOR, ".ctor", &message_ctor, Some(Body::new(vec![], il)));
+    let mut il = Il::new();
+    il.op(Opcode::Ldarg0).op(Opcode::Ldarg1).arg(Opcode::Call, with_message as i64);
+    il.op(Opcode::Ldarg0).op(Opcode::Ldarg2).arg(Opcode::Stfld, inner_exception as i64).op(Opcode::Ret);
+    let ctors = (ctor, with_message, corlib.method(CONSTRUCTOR, ".ctor", &inner_ctor, Some(Body::new(vec![], il))));
+
+    // Each derived exception has the same constructors, which call its base type's
+    let mut bases = HashMap::new();
+    bases.insert("Exception", (exception, ctors));
+    for &(namespace, name, base) in &[
+        ("System", "SystemException", "Exception"),
+        ("System", "ArgumentException", "SystemException"),
+        ("System", "ArithmeticException", "SystemException"),
+        ("System", "ArrayTypeMismatchException", "SystemException"),
+        ("System", "DivideByZeroException", "ArithmeticException"),
+        ("System", "IndexOutOfRangeException", "SystemException"),
+        ("System", "InvalidCastException", "SystemException"),
+        ("System", "InvalidProgramException", "SystemException"),
+        ("System", "MissingMethodException", "SystemException"),
+        ("System", "NotSupportedException", "SystemException"),
+        ("System", "NullReferenceException", "SystemException"),
+        ("System", "OverflowException", "ArithmeticException"),
+        ("System", "PlatformNotSupportedException", "NotSupportedException"),
+        ("System", "TypeLoadException", "SystemException"),
+        ("System", "DllNotFoundException", "TypeLoadException"),
+        ("System", "EntryPointNotFoundException", "TypeLoadException"),
+        ("System.Runtime.InteropServices", "MarshalDirectiveException", "SystemException"),
+        ("System", "ApplicationException", "Exception"),
+        ("System", "FieldAccessException", "SystemException"),
+        ("System.Reflection", "AmbiguousMatchException", "SystemException"),
+        ("System.Reflection", "TargetException", "ApplicationException"),
+        ("System.Reflection", "TargetParameterCountException", "ApplicationException"),
+        ("System", "ArgumentNullException", "ArgumentException"),
+        ("System.Threading", "SynchronizationLockException", "SystemException"),
+        ("System.Threading", "ThreadStateException", "SystemException"),
+    ] {
+        let (base, (base_ctor, base_message_ctor, base_inner_ctor)) = bases[base];
+        let ty = corlib.type_def(PUBLIC, namespace, name, base);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, base_ctor as i64).op(Opcode::Ret);
+        let ctor = corlib.method(CONSTRUCTOR, ".ctor", &default_ctor, Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).op(Opcode::Ldarg1).arg(Opcode::Call, base_message_ctor as i64).op(Opcode::Ret);
+        let with_message = corlib.method(CONSTRUCTOR, ".ctor", &message_ctor, Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).op(Opcode::Ldarg1).op(Opcode::Ldarg2).arg(Opcode::Call, base_inner_ctor as i64);
+        il.op(Opcode::Ret);
+        let ctors = (ctor, with_message, corlib.method(CONSTRUCTOR, ".ctor", &inner_ctor, Some(Body::new(vec![], il))));
+        bases.insert(name, (ty, ctors));
+    }
+
+    // sealed class TypeInitializationException : SystemException {
+    //     string _typeName;
+    //     TypeInitializationException(string fullTypeName, string message, Exception innerException) { ... }
+    // }
+    let (system_exception, (_, _, base_inner_ctor)) = bases["SystemException"];
+    corlib.type_def(PUBLIC | SEALED, "System", "TypeInitializationException", system_exception);
+    let type_name = corlib.field(PRIVATE_FIELD, "_typeName", Ty::String);
+    let mut il = Il::new();
+    il.op(Opcode::Ldarg0).op(Opcode::Ldarg2).op(Opcode::Ldarg3).arg(Opcode::Call, base_inner_ctor as i64);
+    il.op(Opcode::Ldarg0).op(Opcode::Ldarg1).arg(Opcode::Stfld, type_name as i64).op(Opcode::Ret);
+    let signature = method_sig(true, Ty::Void, &[Ty::String, Ty::String, Ty::Class(exception)]);
+    corlib.method(CONSTRUCTOR, ".ctor", &signature, Some(Body::new(vec![], il)));
+
+    // struct RuntimeFieldHandle { IntPtr value; }, and the internal call RuntimeHelpers.InitializeArray
+    let runtime_field_handle = corlib.type_def(PUBLIC | SEALED, "System", "RuntimeFieldHandle", value_type);
+    corlib.field(PRIVATE_FIELD, "value", Ty::I);
+    corlib.type_def(PUBLIC | ABSTRACT_CLASS | SEALED, "System.Runtime.CompilerServices", "RuntimeHelpers", object);
+    let signature = method_sig(false, Ty::Void, &[Ty::Class(array), Ty::ValueType(runtime_field_handle)]);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "InitializeArray", &signature);
+
+    // sealed class TargetInvocationException : ApplicationException {
+    //     TargetInvocationException(Exception inner) : base(null, inner) { }
+    // }
+    let (application_exception, (_, _, base_inner_ctor)) = bases["ApplicationException"];
+    corlib.type_def(PUBLIC | SEALED, "System.Reflection", "TargetInvocationException", application_exception);
+    let mut il = Il::new();
+    il.op(Opcode::Ldarg0).op(Opcode::Ldnull).op(Opcode::Ldarg1).arg(Opcode::Call, base_inner_ctor as i64);
+    il.op(Opcode::Ret);
+    let signature = method_sig(true, Ty::Void, &[Ty::Class(exception)]);
+    corlib.method(CONSTRUCTOR, ".ctor", &signature, Some(Body::new(vec![], il)));
+
+    // struct RuntimeTypeHandle { IntPtr value; } struct RuntimeMethodHandle { IntPtr value; }
+    // abstract class MemberInfo {
+    //     IntPtr _value; extern object[] GetCustomAttributes(Type attributeType, bool inherit);
+    // }
+    // abstract class Type : MemberInfo {
+    //     static extern Type GetTypeFromHandle(RuntimeTypeHandle handle);
+    //     extern MethodInfo[] GetMethods(); extern MethodInfo GetMethod(string name);
+    // }
+    // abstract class MethodBase : MemberInfo { extern object Invoke(object obj, object[] parameters); }
+    // abstract class MethodInfo : MethodBase, abstract class FieldInfo : MemberInfo, and their sealed runtime types
+    let runtime_type_handle = corlib.type_def(PUBLIC | SEALED, "System", "RuntimeTypeHandle", value_type);
+    corlib.field(PRIVATE_FIELD, "value", Ty::I);
+    corlib.type_def(PUBLIC | SEALED, "System", "RuntimeMethodHandle", value_type);
+    corlib.field(PRIVATE_FIELD, "value", Ty::I);
+    let member_info = corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System.Reflection", "MemberInfo", object);
+    corlib.field(PRIVATE_FIELD, "_value", Ty::I);
+    // Type is the next type defined
+    let ty = member_info + 1;
+    let signature = method_sig(true, Ty::sz_array(Ty::Object), &[Ty::Class(ty), Ty::Boolean]);
+    corlib.internal_method(PUBLIC_METHOD, "GetCustomAttributes", &signature);
+    assert_eq!(ty, corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System", "Type", member_info));
+    // MethodBase and MethodInfo follow RuntimeType
+    let method_info = ty + 3;
+    let signature = method_sig(false, Ty::Class(ty), &[Ty::ValueType(runtime_type_handle)]);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "GetTypeFromHandle", &signature);
+    let signature = method_sig(true, Ty::sz_array(Ty::Class(method_info)), &[]);
+    corlib.internal_method(PUBLIC_METHOD, "GetMethods", &signature);
+    let signature = method_sig(true, Ty::Class(method_info), &[Ty::String]);
+    corlib.internal_method(PUBLIC_METHOD, "GetMethod", &signature);
+    corlib.type_def(PUBLIC | SEALED, "System", "RuntimeType", ty);
+    let method_base = corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System.Reflection", "MethodBase", member_info);
+    let signature = method_sig(true, Ty::Object, &[Ty::Object, Ty::sz_array(Ty::Object)]);
+    corlib.internal_method(PUBLIC_METHOD, "Invoke", &signature);
+    assert_eq!(method_info, corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System.Reflection", "MethodInfo", method_base));
+    corlib.type_def(PUBLIC | SEALED, "System.Reflection", "RuntimeMethodInfo", method_info);
+    let field_info = corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System.Reflection", "FieldInfo", member_info);
+    corlib.type_def(PUBLIC | SEALED, "System.Reflection", "RuntimeFieldInfo", field_info);
+
+    // abstract class Attribute { Attribute() { } }
+    let attribute = corlib.type_def(PUBLIC | ABSTRACT_CLASS, "System", "Attribute", object);
+    let mut il = Il::new();
+    il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64).op(Opcode::Ret);
+    let attribute_ctor = corlib.method(CONSTRUCTOR, ".ctor", &default_ctor, Some(Body::new(vec![], il)));
+
+    // sealed class ThreadStaticAttribute : Attribute { ThreadStaticAttribute() { } }
+    corlib.type_def(PUBLIC | SEALED, "System", "ThreadStaticAttribute", attribute);
+    let mut il = Il::new();
+    il.op(Opcode::Ldarg0).arg(Opcode::Call, attribute_ctor as i64).op(Opcode::Ret);
+    corlib.method(CONSTRUCTOR | PUBLIC_METHOD, ".ctor", &default_ctor, Some(Body::new(vec![], il)));
+
+    // sealed delegate void ThreadStart();
+    // sealed class Thread {
+    //     ThreadStart _start; int _managedThreadId; IntPtr _thread;
+    //     Thread(ThreadStart start) { _start = start; }
+    //     static extern Thread CurrentThread { get; } extern int ManagedThreadId { get; }
+    //     extern void Start(); extern void Join(); static extern void Sleep(int ms); static extern bool Yield();
+    //     void StartCallback() { _start(); }
+    // }
+    let thread_start = corlib.type_def(PUBLIC | SEALED, "System.Threading", "ThreadStart", multicast_delegate);
+    corlib.runtime_method(CONSTRUCTOR | PUBLIC_METHOD, ".ctor", &method_sig(true, Ty::Void, &[Ty::Object, Ty::I]));
+    let signature = method_sig(true, Ty::Void, &[]);
+    let invoke = corlib.runtime_method(VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Invoke", &signature);
+    let thread = corlib.type_def(PUBLIC | SEALED, "System.Threading", "Thread", object);
+    let start = corlib.field(PRIVATE_FIELD, "_start", Ty::Class(thread_start));
+    corlib.field(PRIVATE_FIELD, "_managedThreadId", Ty::I4);
+    corlib.field(PRIVATE_FIELD, "_thread", Ty::I);
+    let mut il = Il::new();
+    il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64);
+    il.op(Opcode::Ldarg0).op(Opcode::Ldarg1).arg(Opcode::Stfld, start as i64).op(Opcode::Ret);
+    let signature = method_sig(true, Ty::Void, &[Ty::Class(thread_start)]);
+    corlib.method(CONSTRUCTOR | PUBLIC_METHOD, ".ctor", &signature, Some(Body::new(vec![], il)));
+    let signature = method_sig(false, Ty::Class(thread), &[]);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "get_CurrentThread", &signature);
+    corlib.internal_method(PUBLIC_METHOD, "get_ManagedThreadId", &method_sig(true, Ty::I4, &[]));
+    corlib.internal_method(PUBLIC_METHOD, "Start", &method_sig(true, Ty::Void, &[]));
+    corlib.internal_method(PUBLIC_METHOD, "Join", &method_sig(true, Ty::Void, &[]));
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Sleep", &method_sig(false, Ty::Void, &[Ty::I4]));
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Yield", &method_sig(false, Ty::Boolean, &[]));
+    let mut il = Il::new();
+    il.op(Opcode::Ldarg0).arg(Opcode::Ldfld, start as i64).arg(Opcode::Callvirt, invoke as i64).op(Opcode::Ret);
+    let signature = method_sig(true, Ty::Void, &[]);
+    corlib.method(PRIVATE_METHOD, "StartCallback", &signature, Some(Body::new(vec![], il)));
+
+    // static class Monitor {
+    //     static extern void Enter(object obj); static extern void Enter(object obj, ref bool lockTaken);
+    //     static extern void Exit(object obj); static extern bool IsEntered(object obj);
+    //     static extern bool Wait(object obj); static extern void Pulse(object obj); static extern void PulseAll(...);
+    // }
+    corlib.type_def(PUBLIC | ABSTRACT_CLASS | SEALED, "System.Threading", "Monitor", object);
+    let signature = method_sig(false, Ty::Void, &[Ty::Object]);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Enter", &signature);
+    let lock_taken = method_sig(false, Ty::Void, &[Ty::Object, Ty::by_ref(Ty::Boolean)]);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Enter", &lock_taken);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Exit", &signature);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "IsEntered", &method_sig(false, Ty::Boolean, &[Ty::Object]));
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Wait", &method_sig(false, Ty::Boolean, &[Ty::Object]));
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Pulse", &signature);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "PulseAll", &signature);
+
+    // static class Interlocked {
+    //     static extern int Increment(ref int location); static extern int Add(ref int location1, int value);
+    //     static extern object CompareExchange(ref object location1, object value, object comparand);
+    // }
+    corlib.type_def(PUBLIC | ABSTRACT_CLASS | SEALED, "System.Threading", "Interlocked", object);
+    let signature = method_sig(false, Ty::I4, &[Ty::by_ref(Ty::I4)]);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Increment", &signature);
+    let signature = method_sig(false, Ty::I4, &[Ty::by_ref(Ty::I4), Ty::I4]);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "Add", &signature);
+    let signature = method_sig(false, Ty::Object, &[Ty::by_ref(Ty::Object), Ty::Object, Ty::Object]);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "CompareExchange", &signature);
+
+    corlib.type_def(PUBLIC | ABSTRACT_CLASS | SEALED, "System", "Console", object);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "WriteLine", &method_sig(false, Ty::Void, &[Ty::String]));
+    corlib.type_def(PUBLIC | ABSTRACT_CLASS | SEALED, "System.Runtime.InteropServices", "Marshal", object);
+    corlib.internal_method(STATIC | PUBLIC_METHOD, "GetLastWin32Error", &method_sig(false, Ty::I4, &[]));
+    corlib
+}
+
+/// Defines a sealed delegate type deriving from `System.MulticastDelegate`, with the methods the C# compiler declares
+/// for the runtime to provide, and gets the tokens of the type, its constructor and its `Invoke` method.
+/// `BeginInvoke` takes an `object` for its callback, and returns an `object` for its result.
+pub fn delegate_type(app: &mut AssemblyBuilder, name: &str, ret: Ty, params: &[Ty]) -> (u32, u32, u32) {
+    let multicast_delegate = app.corlib_type("System", "MulticastDelegate");
+    let ty = app.type_def(PUBLIC | SEALED, "", name, multicast_delegate);
+    let signature = method_sig(true, Ty::Void, &[Ty::Object, Ty::I]);
+    let ctor = app.runtime_method(CONSTRUCTOR | PUBLIC_METHOD, ".ctor", &signature);
+    let signature = method_sig(true, ret.clone(), params);
+    let invoke = app.runtime_method(VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Invoke", &signature);
+    let mut begin_params = params.to_vec();
+    begin_params.extend(vec![Ty::Object, Ty::Object]);
+    let signature = method_sig(true, Ty::Object, &begin_params);
+    app.runtime_method(VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "BeginInvoke", &signature);
+    app.runtime_method(VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "EndInvoke", &method_sig(true, ret, &[Ty::Object]));
+    (ty, ctor, invoke)
+}
+
+/// Appends a digit to the static `int` field `trace`, as `trace = trace * 10 + digit`.
+pub fn trace(il: &mut Il, trace: u32, digit: i32) {
+    il.arg(Opcode::Ldsfld, trace as i64).ldc_i4(10).op(Opcode::Mul).ldc_i4(digit).op(Opcode::Add);
+    il.arg(Opcode::Stsfld, trace as i64);
+}
+
+/// Writes a core library and an application to a new directory, and creates a runtime for them.
+pub fn runtime(name: &str, corlib: &AssemblyBuilder, app: &AssemblyBuilder) -> (TestDirectory, Runtime) {
+    runtime_with(name, corlib, app, RuntimeBuilder::new())
+}
+
+/// Like `runtime`, but builds the runtime with the options already set on a builder.
+pub fn runtime_with(
+    name: &str,
+    corlib: &AssemblyBuilder,
+    app: &AssemblyBuilder,
+    builder: RuntimeBuilder,
+) -> (TestDirectory, Runtime) {
+    let directory = TestDirectory::new(name);
+    corlib.write(&directory.join("corlib.dll"));
+    app.write(&directory.join("App.exe"));
+    let runtime = builder.base_directory(&directory).build().unwrap();
+    (directory, runtime)
+}
+
+/// Runs an application with a core library, and gets its exit code.
+pub fn run(name: &str, corlib: &AssemblyBuilder, app: &AssemblyBuilder) -> Result<i32, Error> {
+    let (_directory, mut runtime) = runtime(name, corlib, app);
+    runtime.execute("App")
+}
+
+/// Defines a static `int Main()` with the given body in the last type defined, and makes it the entry point.
+pub fn add_main(app: &mut AssemblyBuilder, body: Body) -> u32 {
+    let main = app.method(STATIC, "Main", &method_sig(false, Ty::I4, &[]), Some(body));
+    app.set_entry_point(main);
+    main
+}
+
+/// Builds an application with a `Program` class, whose entry point is a static `int Main()` with the given body.
+pub fn app_with_main(body: Body) -> AssemblyBuilder {
+    let mut app = AssemblyBuilder::new("App");
+    let object = app.corlib_type("System", "Object");
+    app.type_def(PUBLIC, "", "Program", object);
+    add_main(&mut app, body);
+    app
+}
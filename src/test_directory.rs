@@ -0,0 +1,37 @@
+use std::env;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// An empty temporary directory for a test, which is removed when dropped.
+///
+/// The name includes the process ID and a counter, so tests running in parallel (or in concurrent runs) never share
+/// a directory.
+pub struct TestDirectory(PathBuf);
+
+impl TestDirectory {
+    pub fn new(name: &str) -> TestDirectory {
+        let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+        let path = env::temp_dir().join(format!("crustyclr_{}_{}_{}", name, process::id(), id));
+        fs::create_dir_all(&path).unwrap();
+        TestDirectory(path)
+    }
+}
+
+impl Deref for TestDirectory {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
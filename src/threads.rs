@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread::JoinHandle;
+
+use gc::ObjectRef;
+use interpreter::{Frame, Thrown};
+use types::TypeSystem;
+use vm;
+
+/// The lock a managed thread holds while it runs managed code, so only one runs at a time. Threads take turns in
+/// the order they asked for one, and hand the lock on at safepoints, which is when a collection can happen: a thread
+/// that collects garbage has stopped all the others.
+///
+/// Threads blocked on the runtime's state, such as a monitor or another thread finishing, wait for a signal without
+/// a turn, then take one to check it again.
+pub struct Scheduler {
+    turns: Mutex<Turns>,
+    changed: Condvar,
+
+    /// How many threads are waiting for a turn, which the thread with the lock checks at safepoints.
+    waiting: AtomicUsize,
+}
+
+struct Turns {
+    held: bool,
+    next_ticket: u64,
+    serving: u64,
+
+    /// How many times the state blocked threads wait for has changed.
+    generation: u64,
+}
+
+impl Scheduler {
+    /// Creates a scheduler whose lock is held by the thread creating the runtime.
+    pub fn new() -> Scheduler {
+        Scheduler {
+            turns: Mutex::new(Turns {
+                held: true,
+                next_ticket: 0,
+                serving: 0,
+                generation: 0,
+            }),
+            changed: Condvar::new(),
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits for a turn, and takes the lock.
+    pub fn acquire(&self) {
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let mut turns = self.turns.lock().unwrap();
+        let ticket = turns.next_ticket;
+        turns.next_ticket += 1;
+        while turns.held || turns.serving != ticket {
+            turns = self.changed.wait(turns).unwrap();
+        }
+        turns.held = true;
+        turns.serving += 1;
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Gives up the lock, for the next thread waiting for a turn.
+    pub fn release(&self) {
+        self.turns.lock().unwrap().held = false;
+        self.changed.notify_all();
+    }
+
+    /// Returns `true` if another thread is waiting for a turn.
+    pub fn is_contended(&self) -> bool {
+        self.waiting.load(Ordering::Relaxed) > 0
+    }
+
+    /// Gets the number of times the state blocked threads wait for has changed.
+    pub fn generation(&self) -> u64 {
+        self.turns.lock().unwrap().generation
+    }
+
+    /// Wakes the blocked threads, since the state they wait for has changed.
+    pub fn signal(&self) {
+        self.turns.lock().unwrap().generation += 1;
+        self.changed.notify_all();
+    }
+
+    /// Waits without the lock until the state blocked threads wait for changes from how it was at a generation.
+    pub fn wait_for_signal(&self, generation: u64) {
+        let mut turns = self.turns.lock().unwrap();
+        while turns.generation == generation {
+            turns = self.changed.wait(turns).unwrap();
+        }
+    }
+}
+
+/// What a thread is executing. The `Vm` holds the state of the thread with the lock, and the state of each of the
+/// others is kept in its `ManagedThread` while it waits.
+#[derive(Default)]
+pub struct ThreadState {
+    pub frames: Vec<Frame>,
+    pub thrown: Vec<Thrown>,
+    pub unhandled: Option<Thrown>,
+    pub handles: Vec<ObjectRef>,
+    pub last_error: i32,
+
+    /// The storage for the `[ThreadStatic]` fields of each type, indexed by `TypeId`.
+    pub thread_statics: Vec<Option<Box<[u64]>>>,
+}
+
+impl ThreadState {
+    /// Calls `visit` with the location of each object reference the state holds.
+    pub(crate) fn visit_roots<F: FnMut(*mut u8)>(&mut self, types: &TypeSystem, mut visit: F) {
+        for frame in &mut self.frames {
+            frame.visit_roots(types, &mut visit);
+        }
+        for thrown in self.thrown.iter_mut().chain(self.unhandled.as_mut()) {
+            visit(&mut thrown.exception as *mut ObjectRef as *mut u8);
+        }
+        for object in &mut self.handles {
+            visit(object as *mut ObjectRef as *mut u8);
+        }
+        vm::visit_static_roots(types, &mut self.thread_statics, visit);
+    }
+}
+
+/// A managed thread, which runs on an OS thread of its own.
+pub struct ManagedThread {
+    /// The thread's `System.Threading.Thread`, or null until the main thread asks for one.
+    pub object: ObjectRef,
+    pub state: ThreadState,
+    pub finished: bool,
+    pub os_thread: Option<JoinHandle<()>>,
+}
+
+impl ManagedThread {
+    pub fn new(object: ObjectRef) -> ManagedThread {
+        ManagedThread {
+            object,
+            state: ThreadState::default(),
+            finished: false,
+            os_thread: None,
+        }
+    }
+}
+
+/// The monitor of an object that has been locked, which the object's sync word refers to.
+#[derive(Default)]
+pub struct SyncBlock {
+    /// The thread holding the monitor, by its index, and how many more times than once it has entered it.
+    pub owner: Option<usize>,
+    pub recursion: u32,
+
+    /// The threads waiting in `Monitor.Wait` to be pulsed, in the order they started waiting.
+    pub waiting: VecDeque<usize>,
+}
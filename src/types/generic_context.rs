@@ -0,0 +1,22 @@
+use types::TypeId;
+
+/// The type arguments that `!n` and `!!n` in a signature stand for: those of the type and the method whose IL or
+/// signature it is. Either list is empty if it isn't instantiated, in which case its parameters are left as they are.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GenericContext {
+    pub type_args: Vec<TypeId>,
+    pub method_args: Vec<TypeId>,
+}
+
+impl GenericContext {
+    pub fn new(type_args: Vec<TypeId>, method_args: Vec<TypeId>) -> GenericContext {
+        GenericContext {
+            type_args,
+            method_args,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.type_args.is_empty() && self.method_args.is_empty()
+    }
+}
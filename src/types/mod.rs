@@ -0,0 +1,13 @@
+mod generic_context;
+mod primitive;
+mod runtime_field;
+mod runtime_method;
+mod runtime_type;
+mod type_system;
+
+pub use self::generic_context::GenericContext;
+pub use self::primitive::Primitive;
+pub use self::runtime_field::{FieldDefinition, FieldId, RuntimeField};
+pub use self::runtime_method::{MethodDefinition, MethodId, MethodSig, PInvokeImport, RuntimeMethod};
+pub use self::runtime_type::{LoadState, RuntimeType, Storage, TypeDefinition, TypeId, TypeKind};
+pub use self::type_system::{AssemblyId, TypeSystem};
@@ -0,0 +1,97 @@
+use ecma355metadata::cli::signatures::TypeReference;
+
+/// The built-in value types of ECMA-335 Partition I, 8.2.2, which the runtime stores and operates on directly.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Primitive {
+    Boolean,
+    Char,
+    I1,
+    U1,
+    I2,
+    U2,
+    I4,
+    U4,
+    I8,
+    U8,
+    R4,
+    R8,
+    I,
+    U,
+}
+
+impl Primitive {
+    /// Gets the primitive type implemented by a type in corlib, such as `System.Int32`.
+    pub fn from_name(namespace: &str, name: &str) -> Option<Primitive> {
+        if namespace != "System" {
+            return None;
+        }
+        Some(match name {
+            "Boolean" => Primitive::Boolean,
+            "Char" => Primitive::Char,
+            "SByte" => Primitive::I1,
+            "Byte" => Primitive::U1,
+            "Int16" => Primitive::I2,
+            "UInt16" => Primitive::U2,
+            "Int32" => Primitive::I4,
+            "UInt32" => Primitive::U4,
+            "Int64" => Primitive::I8,
+            "UInt64" => Primitive::U8,
+            "Single" => Primitive::R4,
+            "Double" => Primitive::R8,
+            "IntPtr" => Primitive::I,
+            "UIntPtr" => Primitive::U,
+            _ => return None,
+        })
+    }
+
+    /// Gets the primitive type a signature refers to with its element type, such as `int32`.
+    pub fn from_signature(ty: &TypeReference) -> Option<Primitive> {
+        Some(match *ty {
+            TypeReference::Boolean => Primitive::Boolean,
+            TypeReference::Char => Primitive::Char,
+            TypeReference::I1 => Primitive::I1,
+            TypeReference::U1 => Primitive::U1,
+            TypeReference::I2 => Primitive::I2,
+            TypeReference::U2 => Primitive::U2,
+            TypeReference::I4 => Primitive::I4,
+            TypeReference::U4 => Primitive::U4,
+            TypeReference::I8 => Primitive::I8,
+            TypeReference::U8 => Primitive::U8,
+            TypeReference::R4 => Primitive::R4,
+            TypeReference::R8 => Primitive::R8,
+            TypeReference::I => Primitive::I,
+            TypeReference::U => Primitive::U,
+            _ => return None,
+        })
+    }
+
+    /// Gets the name of the corlib type that implements the primitive type.
+    pub fn name(self) -> &'static str {
+        match self {
+            Primitive::Boolean => "Boolean",
+            Primitive::Char => "Char",
+            Primitive::I1 => "SByte",
+            Primitive::U1 => "Byte",
+            Primitive::I2 => "Int16",
+            Primitive::U2 => "UInt16",
+            Primitive::I4 => "Int32",
+            Primitive::U4 => "UInt32",
+            Primitive::I8 => "Int64",
+            Primitive::U8 => "UInt64",
+            Primitive::R4 => "Single",
+            Primitive::R8 => "Double",
+            Primitive::I => "IntPtr",
+            Primitive::U => "UIntPtr",
+        }
+    }
+
+    pub fn size(self) -> u32 {
+        match self {
+            Primitive::Boolean | Primitive::I1 | Primitive::U1 => 1,
+            Primitive::Char | Primitive::I2 | Primitive::U2 => 2,
+            Primitive::I4 | Primitive::U4 | Primitive::R4 => 4,
+            Primitive::I8 | Primitive::U8 | Primitive::R8 | Primitive::I | Primitive::U => 8,
+        }
+    }
+
+}
@@ -0,0 +1,51 @@
+use ecma355metadata::cli::{ConstantValue, FieldAttributes, FieldFlags};
+
+use types::{AssemblyId, TypeId};
+
+/// Identifies a field loaded by a `TypeSystem`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FieldId(pub(crate) u32);
+
+impl FieldId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// The Field row a field was loaded from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FieldDefinition {
+    pub assembly: AssemblyId,
+    pub row: usize,
+}
+
+pub struct RuntimeField {
+    pub name: String,
+    pub owner: TypeId,
+    pub definition: Option<FieldDefinition>,
+    pub flags: FieldAttributes,
+    pub field_type: TypeId,
+
+    /// The offset of an instance field from the start of the instance's fields (or of a value), or of a static
+    /// field from the start of its type's static storage.
+    pub offset: u32,
+
+    /// The value of a literal, from the Constant table.
+    pub constant: Option<ConstantValue>,
+}
+
+impl RuntimeField {
+    pub fn is_static(&self) -> bool {
+        self.flags.flags().contains(FieldFlags::Static)
+    }
+
+    /// Returns `true` for static fields whose initial value is data in the image, from the FieldRva table.
+    pub fn has_rva(&self) -> bool {
+        self.flags.flags().contains(FieldFlags::HasFieldRVA)
+    }
+
+    /// Returns `true` for constants, which have no storage.
+    pub fn is_literal(&self) -> bool {
+        self.flags.flags().contains(FieldFlags::Literal)
+    }
+}
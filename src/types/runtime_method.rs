@@ -0,0 +1,105 @@
+use std::fmt;
+
+use ecma355metadata::cli::{MethodAttributes, MethodCodeType, MethodFlags, MethodImplAttributes, MethodImplFlags,
+                           NativeType, PInvokeAttributes};
+use ecma355metadata::cli::signatures::SignatureCallingConvention;
+
+use types::{AssemblyId, TypeId};
+
+/// Identifies a method loaded by a `TypeSystem`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MethodId(pub(crate) u32);
+
+impl MethodId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A method signature with its types resolved. `ret` is `None` for methods returning `void`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MethodSig {
+    pub has_this: bool,
+    pub ret: Option<TypeId>,
+    pub params: Vec<TypeId>,
+}
+
+/// The MethodDef row a method was loaded from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MethodDefinition {
+    pub assembly: AssemblyId,
+    pub row: usize,
+}
+
+/// How a P/Invoke method calls the native function it imports, from its ImplMap row, its signature and the
+/// FieldMarshal rows of its parameters.
+#[derive(Clone)]
+pub struct PInvokeImport {
+    pub library: String,
+    pub entry_point: String,
+    pub attributes: PInvokeAttributes,
+
+    /// The calling convention of the method's signature, or of its ImplMap row if the signature uses the default.
+    pub calling_convention: SignatureCallingConvention,
+
+    /// The native types `[MarshalAs]` gives the return value and each parameter.
+    pub return_type: Option<NativeType>,
+    pub param_types: Vec<Option<NativeType>>,
+}
+
+pub struct RuntimeMethod {
+    pub name: String,
+    pub owner: TypeId,
+    pub definition: Option<MethodDefinition>,
+    pub flags: MethodAttributes,
+    pub impl_flags: MethodImplAttributes,
+    pub signature: MethodSig,
+
+    /// The vtable slot of a virtual method.
+    pub slot: Option<usize>,
+
+    /// For an instantiation of a generic method, the generic method definition and the method's type arguments.
+    pub generic_definition: Option<MethodId>,
+    pub method_args: Vec<TypeId>,
+}
+
+impl RuntimeMethod {
+    pub fn is_virtual(&self) -> bool {
+        self.flags.flags().contains(MethodFlags::Virtual)
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.flags.flags().contains(MethodFlags::Abstract)
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.flags.flags().contains(MethodFlags::Static)
+    }
+
+    /// Returns `true` for methods marked `[DllImport]`, which call a function in a native library.
+    pub fn is_pinvoke(&self) -> bool {
+        self.flags.flags().contains(MethodFlags::PInvokeImpl)
+    }
+
+    /// Returns `true` for methods marked `[MethodImpl(MethodImplOptions.InternalCall)]`, which the runtime implements.
+    pub fn is_internal_call(&self) -> bool {
+        self.impl_flags.flags().contains(MethodImplFlags::InternalCall)
+    }
+
+    /// Returns `true` for methods the runtime provides the code for, such as the constructor and `Invoke` method of a
+    /// delegate type.
+    pub fn is_runtime_impl(&self) -> bool {
+        self.impl_flags.code_type() == MethodCodeType::Runtime
+    }
+
+    /// Gets the number of arguments the method takes, including `this`.
+    pub fn arg_count(&self) -> usize {
+        self.signature.params.len() + if self.signature.has_this { 1 } else { 0 }
+    }
+}
+
+impl fmt::Display for RuntimeMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.name)
+    }
+}
@@ -0,0 +1,263 @@
+use std::fmt;
+
+use ecma355metadata::cli::{GenericParamVariance, TypeAttributes, TypeFlags};
+
+use types::{AssemblyId, FieldId, MethodId, Primitive};
+
+/// Identifies a type loaded by a `TypeSystem`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TypeId(pub(crate) u32);
+
+impl TypeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// What kind of type a `RuntimeType` is, which determines how its values are stored.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TypeKind {
+    Class,
+    Interface,
+
+    /// A value type defined in metadata, other than a primitive type.
+    ValueType,
+
+    /// One of the built-in value types, such as `System.Int32`.
+    Primitive(Primitive),
+
+    /// An enum, whose values are stored as its underlying primitive type.
+    Enum(Primitive),
+
+    /// `System.String`, whose instances hold their characters inline.
+    String,
+
+    /// A single-dimensional, zero-based array of the element type.
+    SzArray(TypeId),
+
+    /// An array of the element type with a rank, whose dimensions each have a length and lower bound.
+    Array(TypeId, u32),
+
+    /// A managed pointer to the element type.
+    ByRef(TypeId),
+
+    /// An unmanaged pointer to the element type.
+    Pointer(TypeId),
+
+    /// The generic parameter of a type with an index, `!n` in signatures. It only appears in the members of generic
+    /// type definitions and of instantiations that aren't closed.
+    Var(u32),
+
+    /// The generic parameter of a method with an index, `!!n` in signatures.
+    MVar(u32),
+}
+
+/// How a value of a type is stored in memory, in a field, array element, argument or local variable.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Storage {
+    I1,
+    U1,
+    I2,
+    U2,
+    I4,
+    U4,
+    I8,
+    R4,
+    R8,
+    NativeInt,
+
+    /// An object reference.
+    Ref,
+
+    /// A managed pointer, stored as the object it points into (or null) and an offset or address.
+    ByRef,
+
+    /// A value type, stored inline.
+    Struct,
+}
+
+impl Storage {
+    /// The size of a managed pointer, which is the object it points into followed by an offset.
+    pub const BY_REF_SIZE: u32 = 16;
+
+    pub fn for_primitive(primitive: Primitive) -> Storage {
+        match primitive {
+            Primitive::Boolean | Primitive::U1 => Storage::U1,
+            Primitive::I1 => Storage::I1,
+            Primitive::Char | Primitive::U2 => Storage::U2,
+            Primitive::I2 => Storage::I2,
+            Primitive::I4 => Storage::I4,
+            Primitive::U4 => Storage::U4,
+            Primitive::I8 | Primitive::U8 => Storage::I8,
+            Primitive::R4 => Storage::R4,
+            Primitive::R8 => Storage::R8,
+            Primitive::I | Primitive::U => Storage::NativeInt,
+        }
+    }
+}
+
+/// The stages of loading a type. Types can be referred to as soon as they are created, but their fields, methods
+/// and layout are only available once they are prepared.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    Created,
+    Preparing,
+    Prepared,
+}
+
+/// The TypeDef row a type was loaded from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TypeDefinition {
+    pub assembly: AssemblyId,
+    pub row: usize,
+}
+
+/// A type loaded by the runtime, with its fields laid out and its virtual methods assigned to vtable slots.
+pub struct RuntimeType {
+    pub namespace: String,
+    pub name: String,
+    pub definition: Option<TypeDefinition>,
+    pub flags: TypeAttributes,
+    pub kind: TypeKind,
+    pub base: Option<TypeId>,
+    pub state: LoadState,
+
+    /// For an instantiation of a generic type, the generic type definition and the type arguments. The
+    /// instantiation shares the definition's metadata, but has its own fields, methods and layout.
+    pub generic_definition: Option<TypeId>,
+    pub type_args: Vec<TypeId>,
+
+    /// For a generic type definition, the variance of each of its generic parameters. Only the parameters of
+    /// interfaces and delegates can be covariant or contravariant.
+    pub variances: Vec<GenericParamVariance>,
+
+    /// The interfaces the type implements, including those of its base types and the interfaces they inherit from.
+    pub interfaces: Vec<TypeId>,
+
+    /// For a class or value type, the implementation of each slot of each interface in `interfaces`, in the same
+    /// order.
+    pub interface_vtables: Vec<Vec<MethodId>>,
+
+    /// The fields declared by the type, both instance and static.
+    pub fields: Vec<FieldId>,
+
+    /// The methods declared by the type.
+    pub methods: Vec<MethodId>,
+
+    /// The implementation of each virtual method slot, starting with the slots inherited from the base type.
+    pub vtable: Vec<MethodId>,
+
+    /// For value types, the size of a value. For other types, the size of an instance's fields, not including the
+    /// object header, or of the fixed part of an array or string.
+    pub size: u32,
+    pub alignment: u32,
+
+    /// The offsets of the object references in a value or in an instance's fields. Managed pointers are included,
+    /// since they start with the object they point into.
+    pub ref_offsets: Vec<u32>,
+
+    /// The size of the type's static fields, and the offsets of the object references among them.
+    pub static_size: u32,
+    pub static_ref_offsets: Vec<u32>,
+}
+
+impl RuntimeType {
+    pub fn new(namespace: &str, name: &str, kind: TypeKind) -> RuntimeType {
+        RuntimeType {
+            namespace: namespace.into(),
+            name: name.into(),
+            definition: None,
+            flags: TypeAttributes::new(0),
+            kind,
+            base: None,
+            state: LoadState::Created,
+            generic_definition: None,
+            type_args: Vec::new(),
+            variances: Vec::new(),
+            interfaces: Vec::new(),
+            interface_vtables: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            vtable: Vec::new(),
+            size: 0,
+            alignment: 1,
+            ref_offsets: Vec::new(),
+            static_size: 0,
+            static_ref_offsets: Vec::new(),
+        }
+    }
+
+    pub fn is_value_type(&self) -> bool {
+        matches!(self.kind, TypeKind::ValueType | TypeKind::Primitive(_) | TypeKind::Enum(_))
+    }
+
+    /// Returns `true` if the type's initializer only has to run before its static fields are first accessed, rather
+    /// than exactly when it is first used.
+    pub fn is_before_field_init(&self) -> bool {
+        self.flags.flags().contains(TypeFlags::BeforeFieldInit)
+    }
+
+    /// Gets the primitive type a primitive type's or enum's values are stored as.
+    pub fn underlying_primitive(&self) -> Option<Primitive> {
+        match self.kind {
+            TypeKind::Primitive(primitive) | TypeKind::Enum(primitive) => Some(primitive),
+            _ => None,
+        }
+    }
+
+    pub fn storage(&self) -> Storage {
+        match self.kind {
+            TypeKind::Primitive(primitive) | TypeKind::Enum(primitive) => Storage::for_primitive(primitive),
+            TypeKind::ValueType => Storage::Struct,
+            TypeKind::Class
+            | TypeKind::Interface
+            | TypeKind::String
+            | TypeKind::SzArray(_)
+            | TypeKind::Array(..)
+            | TypeKind::Var(_)
+            | TypeKind::MVar(_) => Storage::Ref,
+            TypeKind::ByRef(_) => Storage::ByRef,
+            TypeKind::Pointer(_) => Storage::NativeInt,
+        }
+    }
+
+    /// Gets the number of bytes a value of the type occupies in a field, array element or variable.
+    pub fn value_size(&self) -> u32 {
+        match self.storage() {
+            Storage::I1 | Storage::U1 => 1,
+            Storage::I2 | Storage::U2 => 2,
+            Storage::I4 | Storage::U4 | Storage::R4 => 4,
+            Storage::I8 | Storage::R8 | Storage::NativeInt | Storage::Ref => 8,
+            Storage::ByRef => Storage::BY_REF_SIZE,
+            Storage::Struct => self.size,
+        }
+    }
+
+    /// Gets the alignment of a value of the type in a field, array element or variable.
+    pub fn value_alignment(&self) -> u32 {
+        match self.storage() {
+            Storage::Struct => self.alignment,
+            Storage::ByRef => 8,
+            _ => self.value_size(),
+        }
+    }
+
+    /// Gets the offsets of the object references in a value of the type.
+    pub fn value_ref_offsets(&self) -> &[u32] {
+        match self.storage() {
+            Storage::Ref | Storage::ByRef => &[0],
+            Storage::Struct => &self.ref_offsets,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for RuntimeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        if self.namespace.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}.{}", self.namespace, self.name)
+        }
+    }
+}
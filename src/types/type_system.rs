@@ -0,0 +1,2425 @@
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+
+use memmap::Mmap;
+use slog;
+
+use ecma355metadata::MetadataImage;
+use ecma355metadata::cli::{Access, ConstantValue, CustomAttributeValue, EnumResolver, FieldFlags,
+                           GenericParamAttributes, GenericParamConstraints, GenericParamVariance, MethodAttributes,
+                           MethodImplAttributes, MethodVTableLayout, NativeType, PInvokeCallingConvention,
+                           TypeAttributes, TypeFlags, TypeSemantics};
+use ecma355metadata::cli::signatures::{FieldSignature, MethodSignature, MethodSpecSignature,
+                                       SignatureCallingConvention, SignatureHeader, SignatureKind, TypeReference};
+use ecma355metadata::cli::tables::{self, TableHandle, TableIndex};
+
+use app_context::AppContext;
+use assembly::Assembly;
+use error::{Error, ExceptionKind};
+use types::{FieldDefinition, FieldId, GenericContext, LoadState, MethodDefinition, MethodId, MethodSig,
+            PInvokeImport, Primitive, RuntimeField, RuntimeMethod, RuntimeType, TypeDefinition, TypeId, TypeKind};
+
+/// The flags of the methods the runtime provides for arrays: public and hide-by-sig, and for constructors, also
+/// special name and runtime special name.
+const ARRAY_METHOD: u16 = 0x0086;
+const ARRAY_CONSTRUCTOR: u16 = 0x1886;
+
+/// Identifies an assembly loaded by a `TypeSystem`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssemblyId(pub(crate) u32);
+
+struct LoadedAssembly {
+    assembly: Assembly,
+
+    /// The TypeDef row that owns each MethodDef and Field row, indexed by row number.
+    method_owners: Vec<usize>,
+    field_owners: Vec<usize>,
+}
+
+/// A generic parameter of a type or method definition, with its constraints.
+struct GenericParamDef {
+    name: String,
+    flags: GenericParamAttributes,
+
+    /// The TypeDef, TypeRef or TypeSpec handles of the types the argument must derive from or implement.
+    constraints: Vec<TableHandle>,
+}
+
+/// Loads types, methods and fields from assemblies as they are needed, and owns everything it loads.
+///
+/// Types are created as soon as something refers to them, knowing only their name, kind and base type. Their
+/// fields, methods and layout are loaded when they are prepared, which only needs the types they refer to to be
+/// created, so types can refer to each other freely.
+pub struct TypeSystem {
+    app_context: AppContext,
+    assemblies: Vec<LoadedAssembly>,
+    assembly_names: HashMap<String, AssemblyId>,
+    corlib: Option<AssemblyId>,
+
+    types: Vec<RuntimeType>,
+    methods: Vec<RuntimeMethod>,
+    fields: Vec<RuntimeField>,
+
+    type_defs: HashMap<(AssemblyId, usize), TypeId>,
+    type_refs: HashMap<(AssemblyId, usize), TypeId>,
+    creating: HashSet<(AssemblyId, usize)>,
+    method_defs: HashMap<(AssemblyId, usize), MethodId>,
+    field_defs: HashMap<(AssemblyId, usize), FieldId>,
+    member_methods: HashMap<(AssemblyId, u32), MethodId>,
+    member_fields: HashMap<(AssemblyId, u32), FieldId>,
+    corlib_types: HashMap<(&'static str, &'static str), TypeId>,
+    primitives: HashMap<Primitive, TypeId>,
+    sz_arrays: HashMap<TypeId, TypeId>,
+    arrays: HashMap<(TypeId, u32), TypeId>,
+    by_refs: HashMap<TypeId, TypeId>,
+    pointers: HashMap<TypeId, TypeId>,
+    generic_params: HashMap<TypeKind, TypeId>,
+    instantiations: HashMap<(TypeId, Vec<TypeId>), TypeId>,
+    method_instantiations: HashMap<(MethodId, Vec<TypeId>), MethodId>,
+
+    logger: slog::Logger,
+}
+
+impl TypeSystem {
+    pub fn new(app_context: AppContext, logger: slog::Logger) -> TypeSystem {
+        TypeSystem {
+            app_context,
+            assemblies: Vec::new(),
+            assembly_names: HashMap::new(),
+            corlib: None,
+            types: Vec::new(),
+            methods: Vec::new(),
+            fields: Vec::new(),
+            type_defs: HashMap::new(),
+            type_refs: HashMap::new(),
+            creating: HashSet::new(),
+            method_defs: HashMap::new(),
+            field_defs: HashMap::new(),
+            member_methods: HashMap::new(),
+            member_fields: HashMap::new(),
+            corlib_types: HashMap::new(),
+            primitives: HashMap::new(),
+            sz_arrays: HashMap::new(),
+            arrays: HashMap::new(),
+            by_refs: HashMap::new(),
+            pointers: HashMap::new(),
+            generic_params: HashMap::new(),
+            instantiations: HashMap::new(),
+            method_instantiations: HashMap::new(),
+            logger,
+        }
+    }
+
+    pub fn app_context(&self) -> &AppContext {
+        &self.app_context
+    }
+
+    pub fn app_context_mut(&mut self) -> &mut AppContext {
+        &mut self.app_context
+    }
+
+    /// Loads an assembly through the `AppContext`, unless it is already loaded.
+    pub fn load_assembly(&mut self, name: &str) -> Result<AssemblyId, Error> {
+        if let Some(&id) = self.assembly_names.get(name) {
+            return Ok(id);
+        }
+
+        let assembly = self.app_context.load(name)?;
+        let (method_owners, field_owners) = {
+            let image = assembly.image();
+            let sizes = image.metadata_sizes();
+            let mut method_owners = vec![0; sizes.row_count(TableIndex::MethodDef) + 1];
+            let mut field_owners = vec![0; sizes.row_count(TableIndex::Field) + 1];
+            for row in 1..=image.table::<tables::TypeDefDecoder>().len() {
+                let handle = TableHandle::new(row, TableIndex::TypeDef);
+                for method in image.type_def_methods(handle)? {
+                    if let Some(owner) = method_owners.get_mut(method.index()) {
+                        *owner = row;
+                    }
+                }
+                for field in image.type_def_fields(handle)? {
+                    if let Some(owner) = field_owners.get_mut(field.index()) {
+                        *owner = row;
+                    }
+                }
+            }
+            (method_owners, field_owners)
+        };
+        let is_corlib = defines_root_object(assembly.image());
+
+        let id = AssemblyId(self.assemblies.len() as u32);
+        self.assemblies.push(LoadedAssembly {
+            assembly,
+            method_owners,
+            field_owners,
+        });
+        self.assembly_names.insert(name.into(), id);
+        if is_corlib && self.corlib.is_none() {
+            debug!(self.logger, "using {} as the core library", name);
+            self.corlib = Some(id);
+        }
+        Ok(id)
+    }
+
+    pub fn image(&self, assembly: AssemblyId) -> &MetadataImage<Mmap> {
+        self.assemblies[assembly.0 as usize].assembly.image()
+    }
+
+    /// Gets the assembly that defines `System.Object`, loading the assemblies referenced by the loaded assemblies
+    /// until it is found.
+    pub fn corlib(&mut self) -> Result<AssemblyId, Error> {
+        let mut next = 0;
+        while self.corlib.is_none() && next < self.assemblies.len() {
+            let references = {
+                let image = self.image(AssemblyId(next as u32));
+                let mut references = Vec::new();
+                for reference in image.table::<tables::AssemblyRefDecoder>().iter() {
+                    references.push(image.read_string(reference?.name)?.to_owned());
+                }
+                references
+            };
+            for reference in references {
+                self.load_assembly(&reference)?;
+                if self.corlib.is_some() {
+                    break;
+                }
+            }
+            next += 1;
+        }
+        self.corlib.ok_or_else(|| Error::TypeLoad("System.Object".into()))
+    }
+
+    pub fn get(&self, id: TypeId) -> &RuntimeType {
+        &self.types[id.index()]
+    }
+
+    pub fn method(&self, id: MethodId) -> &RuntimeMethod {
+        &self.methods[id.index()]
+    }
+
+    pub fn field(&self, id: FieldId) -> &RuntimeField {
+        &self.fields[id.index()]
+    }
+
+    /// Gets a type defined by the core library, which must exist.
+    pub fn corlib_type(&mut self, namespace: &'static str, name: &'static str) -> Result<TypeId, Error> {
+        self.find_corlib_type(namespace, name)?
+            .ok_or_else(|| Error::TypeLoad(format!("{}.{}", namespace, name)))
+    }
+
+    /// Gets a type defined by the core library, or `None` if it doesn't define one.
+    pub fn find_corlib_type(&mut self, namespace: &'static str, name: &'static str) -> Result<Option<TypeId>, Error> {
+        if let Some(&id) = self.corlib_types.get(&(namespace, name)) {
+            return Ok(Some(id));
+        }
+        let corlib = self.corlib()?;
+        match self.image(corlib).find_type_def(namespace, name) {
+            Some(handle) => {
+                let id = self.type_def(corlib, handle.index())?;
+                self.corlib_types.insert((namespace, name), id);
+                Ok(Some(id))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn object(&mut self) -> Result<TypeId, Error> {
+        self.corlib_type("System", "Object")
+    }
+
+    pub fn string(&mut self) -> Result<TypeId, Error> {
+        self.corlib_type("System", "String")
+    }
+
+    /// Gets the type for a primitive, creating one if the core library doesn't define it.
+    pub fn primitive(&mut self, primitive: Primitive) -> Result<TypeId, Error> {
+        if let Some(&id) = self.primitives.get(&primitive) {
+            return Ok(id);
+        }
+        let corlib = self.corlib()?;
+        let id = match self.image(corlib).find_type_def("System", primitive.name()) {
+            Some(handle) => self.type_def(corlib, handle.index())?,
+            None => {
+                let base = match self.find_corlib_type("System", "ValueType")? {
+                    Some(value_type) => value_type,
+                    None => self.object()?,
+                };
+                self.synthesize(RuntimeType::new("System", primitive.name(), TypeKind::Primitive(primitive)), base)?
+            }
+        };
+        self.primitives.insert(primitive, id);
+        Ok(id)
+    }
+
+    /// Gets the type of single-dimensional, zero-based arrays of an element type.
+    pub fn sz_array(&mut self, element: TypeId) -> Result<TypeId, Error> {
+        if let Some(&id) = self.sz_arrays.get(&element) {
+            return Ok(id);
+        }
+        let base = match self.find_corlib_type("System", "Array")? {
+            Some(array) => array,
+            None => self.object()?,
+        };
+        let name = format!("{}[]", self.get(element).name);
+        let array = RuntimeType::new(&self.get(element).namespace.clone(), &name, TypeKind::SzArray(element));
+        let id = self.synthesize(array, base)?;
+        self.sz_arrays.insert(element, id);
+        Ok(id)
+    }
+
+    /// Gets the type of arrays of an element type with a rank, which may have any lengths and lower bounds.
+    ///
+    /// The runtime provides their methods, which have no bodies: constructors taking the length of each dimension,
+    /// or the lower bound and length of each, and `Get`, `Set` and `Address` taking an index for each dimension.
+    pub fn array(&mut self, element: TypeId, rank: u32) -> Result<TypeId, Error> {
+        if let Some(&id) = self.arrays.get(&(element, rank)) {
+            return Ok(id);
+        }
+        let base = match self.find_corlib_type("System", "Array")? {
+            Some(array) => array,
+            None => self.object()?,
+        };
+        let name = match rank {
+            1 => format!("{}[*]", self.get(element).name),
+            _ => format!("{}[{}]", self.get(element).name, ",".repeat(rank as usize - 1)),
+        };
+        let array = RuntimeType::new(&self.get(element).namespace.clone(), &name, TypeKind::Array(element, rank));
+        let id = self.synthesize(array, base)?;
+        self.arrays.insert((element, rank), id);
+
+        let int32 = self.primitive(Primitive::I4)?;
+        let element_ref = self.by_ref(element)?;
+        let indices = vec![int32; rank as usize];
+        let mut setter = indices.clone();
+        setter.push(element);
+        for (name, ret, params) in [
+            (".ctor", None, indices.clone()),
+            (".ctor", None, vec![int32; 2 * rank as usize]),
+            ("Get", Some(element), indices.clone()),
+            ("Set", None, setter),
+            ("Address", Some(element_ref), indices),
+        ] {
+            let method = MethodId(self.methods.len() as u32);
+            let flags = if name == ".ctor" { ARRAY_CONSTRUCTOR } else { ARRAY_METHOD };
+            self.methods.push(RuntimeMethod {
+                name: name.into(),
+                owner: id,
+                definition: None,
+                flags: MethodAttributes::new(flags),
+                impl_flags: MethodImplAttributes::new(0),
+                signature: MethodSig {
+                    has_this: true,
+                    ret,
+                    params,
+                },
+                slot: None,
+                generic_definition: None,
+                method_args: Vec::new(),
+            });
+            self.types[id.index()].methods.push(method);
+        }
+        Ok(id)
+    }
+
+    /// Gets the type of managed pointers to a type.
+    pub fn by_ref(&mut self, target: TypeId) -> Result<TypeId, Error> {
+        if let Some(&id) = self.by_refs.get(&target) {
+            return Ok(id);
+        }
+        let name = format!("{}&", self.get(target).name);
+        let mut by_ref = RuntimeType::new(&self.get(target).namespace.clone(), &name, TypeKind::ByRef(target));
+        by_ref.state = LoadState::Prepared;
+        let id = self.add_type(by_ref);
+        self.by_refs.insert(target, id);
+        Ok(id)
+    }
+
+    /// Gets the type of unmanaged pointers to a type.
+    pub fn pointer(&mut self, target: TypeId) -> Result<TypeId, Error> {
+        if let Some(&id) = self.pointers.get(&target) {
+            return Ok(id);
+        }
+        let name = format!("{}*", self.get(target).name);
+        let mut pointer = RuntimeType::new(&self.get(target).namespace.clone(), &name, TypeKind::Pointer(target));
+        pointer.state = LoadState::Prepared;
+        let id = self.add_type(pointer);
+        self.pointers.insert(target, id);
+        Ok(id)
+    }
+
+    /// Gets the type that stands for a generic parameter, `TypeKind::Var` or `TypeKind::MVar`.
+    fn generic_param(&mut self, kind: TypeKind) -> TypeId {
+        if let Some(&id) = self.generic_params.get(&kind) {
+            return id;
+        }
+        let name = match kind {
+            TypeKind::MVar(index) => format!("!!{}", index),
+            TypeKind::Var(index) => format!("!{}", index),
+            _ => unreachable!(),
+        };
+        let mut param = RuntimeType::new("", &name, kind.clone());
+        param.state = LoadState::Prepared;
+        let id = self.add_type(param);
+        self.generic_params.insert(kind, id);
+        id
+    }
+
+    /// Gets the instantiation of a generic type definition with type arguments, creating it the first time.
+    ///
+    /// Instantiating a definition with its own generic parameters gets the definition itself.
+    pub fn instantiate(&mut self, definition: TypeId, type_args: Vec<TypeId>) -> Result<TypeId, Error> {
+        if let Some(&id) = self.instantiations.get(&(definition, type_args.clone())) {
+            return Ok(id);
+        }
+        let generic = self.get(definition);
+        if generic.generic_definition.is_some() || generic.type_args.len() != type_args.len() || type_args.is_empty()
+        {
+            return Err(Error::TypeLoad(format!(
+                "{} can't be instantiated with {} type arguments",
+                generic,
+                type_args.len()
+            )));
+        }
+        if generic.type_args == type_args {
+            return Ok(definition);
+        }
+
+        let context = GenericContext::new(type_args.clone(), Vec::new());
+        let base = match self.get(definition).base {
+            Some(base) => Some(self.substitute(base, &context)?),
+            None => None,
+        };
+        let generic = self.get(definition);
+        let mut ty = RuntimeType::new(&generic.namespace, &generic.name, generic.kind.clone());
+        ty.definition = generic.definition;
+        ty.flags = generic.flags;
+        ty.base = base;
+        ty.generic_definition = Some(definition);
+        ty.type_args = type_args.clone();
+        debug!(self.logger, "instantiated type {}", ty; "arity" => type_args.len());
+        let id = self.add_type(ty);
+        self.instantiations.insert((definition, type_args), id);
+        Ok(id)
+    }
+
+    /// Gets the instantiation of a generic method definition with type arguments, creating it the first time, after
+    /// checking the arguments satisfy the method's constraints.
+    pub fn instantiate_method(&mut self, method: MethodId, method_args: Vec<TypeId>) -> Result<MethodId, Error> {
+        if let Some(&id) = self.method_instantiations.get(&(method, method_args.clone())) {
+            return Ok(id);
+        }
+        let generic = self.method(method);
+        let cant_instantiate = || {
+            Error::TypeLoad(format!("{} can't be instantiated with {} type arguments", generic, method_args.len()))
+        };
+        let definition = generic.definition.ok_or_else(cant_instantiate)?;
+        let handle = TableHandle::new(definition.row, TableIndex::MethodDef);
+        let params = self.generic_params(definition.assembly, handle)?;
+        if generic.generic_definition.is_some() || params.is_empty() || params.len() != method_args.len() {
+            return Err(cant_instantiate());
+        }
+
+        let owner = generic.owner;
+        let context = GenericContext::new(self.get(owner).type_args.clone(), method_args.clone());
+        if !method_args.iter().any(|&arg| self.is_open(arg)) {
+            let name = format!("{}::{}", self.get(owner), self.method(method));
+            self.check_constraints(definition.assembly, &params, &context, &method_args, &name)?;
+        }
+        let signature = self.method(method).signature.clone();
+        let signature = self.substitute_signature(&signature, &context)?;
+        let generic = self.method(method);
+        let instantiation = RuntimeMethod {
+            name: generic.name.clone(),
+            owner,
+            definition: Some(definition),
+            flags: generic.flags,
+            impl_flags: generic.impl_flags,
+            signature,
+            slot: None,
+            generic_definition: Some(method),
+            method_args: method_args.clone(),
+        };
+        let id = MethodId(self.methods.len() as u32);
+        self.methods.push(instantiation);
+        self.method_instantiations.insert((method, method_args), id);
+        Ok(id)
+    }
+
+    /// Returns `true` if a type is or contains generic parameters, so there can't be values of it. Generic type
+    /// definitions are open, since their type arguments are their own parameters.
+    pub fn is_open(&self, ty: TypeId) -> bool {
+        let ty = self.get(ty);
+        match ty.kind {
+            TypeKind::Var(_) | TypeKind::MVar(_) => true,
+            TypeKind::SzArray(element)
+            | TypeKind::Array(element, _)
+            | TypeKind::ByRef(element)
+            | TypeKind::Pointer(element) => self.is_open(element),
+            _ => ty.type_args.iter().any(|&arg| self.is_open(arg)),
+        }
+    }
+
+    /// Replaces the generic parameters in a type with the type arguments of a context.
+    pub fn substitute(&mut self, ty: TypeId, context: &GenericContext) -> Result<TypeId, Error> {
+        if context.is_empty() || !self.is_open(ty) {
+            return Ok(ty);
+        }
+        let arg = |args: &[TypeId], index: u32| {
+            if args.is_empty() {
+                Ok(ty)
+            } else {
+                args.get(index as usize).cloned().ok_or_else(|| {
+                    Error::TypeLoad(format!("{} is out of range of {} type arguments", self.get(ty), args.len()))
+                })
+            }
+        };
+        match self.get(ty).kind {
+            TypeKind::Var(index) => arg(&context.type_args, index),
+            TypeKind::MVar(index) => arg(&context.method_args, index),
+            TypeKind::SzArray(element) => {
+                let element = self.substitute(element, context)?;
+                self.sz_array(element)
+            }
+            TypeKind::Array(element, rank) => {
+                let element = self.substitute(element, context)?;
+                self.array(element, rank)
+            }
+            TypeKind::ByRef(target) => {
+                let target = self.substitute(target, context)?;
+                self.by_ref(target)
+            }
+            TypeKind::Pointer(target) => {
+                let target = self.substitute(target, context)?;
+                self.pointer(target)
+            }
+            _ => {
+                let definition = self.get(ty).generic_definition.unwrap_or(ty);
+                let mut type_args = self.get(ty).type_args.clone();
+                for arg in &mut type_args {
+                    *arg = self.substitute(*arg, context)?;
+                }
+                self.instantiate(definition, type_args)
+            }
+        }
+    }
+
+    fn substitute_signature(&mut self, signature: &MethodSig, context: &GenericContext) -> Result<MethodSig, Error> {
+        let ret = match signature.ret {
+            Some(ret) => Some(self.substitute(ret, context)?),
+            None => None,
+        };
+        let mut params = Vec::with_capacity(signature.params.len());
+        for &param in &signature.params {
+            params.push(self.substitute(param, context)?);
+        }
+        Ok(MethodSig {
+            has_this: signature.has_this,
+            ret,
+            params,
+        })
+    }
+
+    /// Replaces the generic parameters in a method's owner and type arguments with those of a context, getting the
+    /// same method of the resulting instantiations.
+    pub fn substitute_method(&mut self, method: MethodId, context: &GenericContext) -> Result<MethodId, Error> {
+        if context.is_empty() {
+            return Ok(method);
+        }
+        if let Some(generic_definition) = self.method(method).generic_definition {
+            let generic_definition = self.substitute_method(generic_definition, context)?;
+            let mut method_args = self.method(method).method_args.clone();
+            for arg in &mut method_args {
+                *arg = self.substitute(*arg, context)?;
+            }
+            return self.instantiate_method(generic_definition, method_args);
+        }
+        let owner = self.method(method).owner;
+        let substituted = self.substitute(owner, context)?;
+        if substituted == owner {
+            return Ok(method);
+        }
+        self.prepare(substituted)?;
+        let index = self.get(owner).methods.iter().position(|&other| other == method).unwrap();
+        Ok(self.get(substituted).methods[index])
+    }
+
+    /// Replaces the generic parameters in a field's owner with those of a context, getting the same field of the
+    /// resulting instantiation.
+    pub fn substitute_field(&mut self, field: FieldId, context: &GenericContext) -> Result<FieldId, Error> {
+        let owner = self.field(field).owner;
+        let substituted = self.substitute(owner, context)?;
+        if substituted == owner {
+            return Ok(field);
+        }
+        self.prepare(substituted)?;
+        let index = self.get(owner).fields.iter().position(|&other| other == field).unwrap();
+        Ok(self.get(substituted).fields[index])
+    }
+
+    /// Gets the generic context of a method's signature and IL: its owner's type arguments and its own.
+    pub fn method_context(&self, method: MethodId) -> GenericContext {
+        let method = self.method(method);
+        GenericContext::new(self.get(method.owner).type_args.clone(), method.method_args.clone())
+    }
+
+    /// Gets the generic parameters a TypeDef or MethodDef declares, in order.
+    fn generic_params(&self, assembly: AssemblyId, owner: TableHandle) -> Result<Vec<GenericParamDef>, Error> {
+        let image = self.image(assembly);
+        let mut params = Vec::new();
+        for (index, param) in image.table::<tables::GenericParamDecoder>().iter().enumerate() {
+            let param = param?;
+            if param.owner == owner {
+                let definition = GenericParamDef {
+                    name: image.read_string(param.name)?.to_owned(),
+                    flags: param.flags,
+                    constraints: Vec::new(),
+                };
+                params.push((param.number, TableHandle::new(index + 1, TableIndex::GenericParam), definition));
+            }
+        }
+        if !params.is_empty() {
+            for constraint in image.table::<tables::GenericParamConstraintDecoder>().iter() {
+                let constraint = constraint?;
+                if let Some(param) = params.iter_mut().find(|param| param.1 == constraint.owner) {
+                    param.2.constraints.push(constraint.constraint);
+                }
+            }
+        }
+        params.sort_by_key(|param| param.0);
+        Ok(params.into_iter().map(|param| param.2).collect())
+    }
+
+    /// Checks that type arguments satisfy the constraints of the generic parameters of a type or method, named
+    /// `owner` in errors. Constraint types are resolved in `context`, since they can refer to the parameters.
+    fn check_constraints(
+        &mut self,
+        assembly: AssemblyId,
+        params: &[GenericParamDef],
+        context: &GenericContext,
+        args: &[TypeId],
+        owner: &str,
+    ) -> Result<(), Error> {
+        for (index, (param, &arg)) in params.iter().zip(args).enumerate() {
+            self.prepare(arg)?;
+            let flags = param.flags.constraints();
+            let ty = self.get(arg);
+            let is_value_type = ty.is_value_type();
+            let mut satisfied = if flags.contains(GenericParamConstraints::ReferenceTypeConstraint) {
+                !is_value_type
+            } else if flags.contains(GenericParamConstraints::NotNullableValueTypeConstraint) {
+                is_value_type && !self.is_corlib_type(arg, "Nullable`1")
+            } else {
+                true
+            };
+            if flags.contains(GenericParamConstraints::DefaultConstructorConstraint) && !is_value_type {
+                satisfied &= !ty.flags.flags().contains(::ecma355metadata::cli::TypeFlags::Abstract)
+                    && ty.methods.iter().any(|&method| {
+                        let method = self.method(method);
+                        method.name == ".ctor" && method.signature.params.is_empty()
+                            && method.flags.access() == Access::Public
+                    });
+            }
+            for &constraint in &param.constraints {
+                let constraint = self.resolve_type_handle(assembly, constraint)?;
+                let constraint = self.substitute(constraint, context)?;
+                // An argument that is still being prepared doesn't know its interfaces yet, which happens when a
+                // type implements an interface instantiated with itself
+                satisfied &= self.is_assignable_to(arg, constraint) || self.get(arg).state == LoadState::Preparing;
+            }
+            if !satisfied {
+                return Err(Error::TypeLoad(format!(
+                    "GenericArguments[{}], '{}', on '{}' violates the constraint of type parameter '{}'",
+                    index,
+                    self.get(arg),
+                    owner,
+                    param.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if values of type `from` can be used as `to`: it is `to`, derives from it or implements it.
+    ///
+    /// Arrays are covariant: an array of a reference type can be used as an array of the same rank of any type the
+    /// element type can be used as. Generic interfaces and delegates are covariant or contravariant in the
+    /// parameters they declare so, such as `IEnumerable<out T>`.
+    pub fn is_assignable_to(&self, from: TypeId, to: TypeId) -> bool {
+        if self.is_subclass_of(from, to) || self.get(from).interfaces.contains(&to) {
+            return true;
+        }
+        let interfaces = &self.get(from).interfaces;
+        if self.is_variant_of(from, to) || interfaces.iter().any(|&interface| self.is_variant_of(interface, to)) {
+            return true;
+        }
+        let (from_element, to_element) = match (&self.get(from).kind, &self.get(to).kind) {
+            (&TypeKind::SzArray(from), &TypeKind::SzArray(to)) => (from, to),
+            (&TypeKind::Array(from, from_rank), &TypeKind::Array(to, to_rank)) if from_rank == to_rank => (from, to),
+            _ => return false,
+        };
+        !self.get(from_element).is_value_type() && !self.get(to_element).is_value_type()
+            && self.is_assignable_to(from_element, to_element)
+    }
+
+    /// Returns `true` if `from` and `to` are instantiations of the same generic interface or delegate, and each type
+    /// argument of `from` can be used as the one of `to`: it is the same, or the parameter is covariant and it is a
+    /// reference type that can be used as `to`'s, or the parameter is contravariant and it is the other way around.
+    fn is_variant_of(&self, from: TypeId, to: TypeId) -> bool {
+        let (from, to) = (self.get(from), self.get(to));
+        let variances = match (from.generic_definition, to.generic_definition) {
+            (Some(from_definition), Some(to_definition)) if from_definition == to_definition => {
+                &self.get(from_definition).variances
+            }
+            _ => return false,
+        };
+        from.type_args.iter().zip(&to.type_args).zip(variances).all(|((&from_arg, &to_arg), &variance)| {
+            from_arg == to_arg || match variance {
+                GenericParamVariance::Covariant => {
+                    !self.get(from_arg).is_value_type() && self.is_assignable_to(from_arg, to_arg)
+                }
+                GenericParamVariance::Contravariant => {
+                    !self.get(to_arg).is_value_type() && self.is_assignable_to(to_arg, from_arg)
+                }
+                _ => false,
+            }
+        })
+    }
+
+    /// Adds a type that isn't defined in metadata, deriving from `base` and inheriting its vtable.
+    fn synthesize(&mut self, mut ty: RuntimeType, base: TypeId) -> Result<TypeId, Error> {
+        self.prepare(base)?;
+        if let TypeKind::Primitive(primitive) = ty.kind {
+            ty.size = primitive.size();
+            ty.alignment = primitive.size();
+        } else if let TypeKind::SzArray(_) = ty.kind {
+            // The length, before the elements
+            ty.size = 8;
+        } else if let TypeKind::Array(_, rank) = ty.kind {
+            // The total length, then the length and lower bound of each dimension
+            ty.size = 8 + 8 * rank;
+        }
+        ty.base = Some(base);
+        ty.vtable = self.get(base).vtable.clone();
+        ty.state = LoadState::Prepared;
+        debug!(self.logger, "synthesized type {}", ty);
+        Ok(self.add_type(ty))
+    }
+
+    fn add_type(&mut self, ty: RuntimeType) -> TypeId {
+        self.types.push(ty);
+        TypeId(self.types.len() as u32 - 1)
+    }
+
+    /// Resolves a TypeDef, TypeRef or TypeSpec token used by an assembly, in the generic context of the method or
+    /// type using it.
+    pub fn resolve_type_token(
+        &mut self,
+        assembly: AssemblyId,
+        token: u32,
+        context: &GenericContext,
+    ) -> Result<TypeId, Error> {
+        let handle = TableHandle::from_token(token).ok_or_else(|| bad_token(token))?;
+        let ty = self.resolve_type_handle(assembly, handle)?;
+        self.substitute(ty, context)
+    }
+
+    fn resolve_type_handle(&mut self, assembly: AssemblyId, handle: TableHandle) -> Result<TypeId, Error> {
+        match handle.table() {
+            TableIndex::TypeDef => self.type_def(assembly, handle.index()),
+            TableIndex::TypeRef => self.type_ref(assembly, handle.index()),
+            TableIndex::TypeSpec => {
+                let signature = {
+                    let image = self.image(assembly);
+                    let spec = image.table::<tables::TypeSpecDecoder>().get(handle.index())?;
+                    TypeReference::read(&mut image.read_blob(spec.signature)?)?
+                };
+                self.resolve_signature_type(assembly, &signature)
+            }
+            _ => Err(bad_token(handle.token())),
+        }
+    }
+
+    /// Gets the type for a TypeDef row, creating it if this is the first reference to it.
+    fn type_def(&mut self, assembly: AssemblyId, row: usize) -> Result<TypeId, Error> {
+        if let Some(&id) = self.type_defs.get(&(assembly, row)) {
+            return Ok(id);
+        }
+
+        let (flags, namespace, name, extends) = {
+            let image = self.image(assembly);
+            let type_def = image.table::<tables::TypeDefDecoder>().get(row)?;
+            let namespace = image.read_string(type_def.type_namespace)?.to_owned();
+            let name = image.read_string(type_def.type_name)?.to_owned();
+            (type_def.flags, namespace, name, type_def.extends)
+        };
+        if !self.creating.insert((assembly, row)) {
+            return Err(Error::TypeLoad(format!("{}.{} inherits from itself", namespace, name)));
+        }
+        let base = if extends.index() == 0 {
+            None
+        } else {
+            match self.resolve_type_handle(assembly, extends) {
+                Ok(base) => Some(base),
+                Err(e) => {
+                    self.creating.remove(&(assembly, row));
+                    return Err(e);
+                }
+            }
+        };
+        self.creating.remove(&(assembly, row));
+
+        let kind = self.type_def_kind(assembly, row, &namespace, &name, flags, base)?;
+        let params = self.generic_params(assembly, TableHandle::new(row, TableIndex::TypeDef))?;
+        let mut ty = RuntimeType::new(&namespace, &name, kind);
+        ty.definition = Some(TypeDefinition { assembly, row });
+        ty.flags = flags;
+        ty.base = base;
+        ty.type_args = (0..params.len()).map(|index| self.generic_param(TypeKind::Var(index as u32))).collect();
+        ty.variances = params.iter().map(|param| param.flags.variance()).collect();
+        debug!(self.logger, "created type {}", ty);
+        let id = self.add_type(ty);
+        self.type_defs.insert((assembly, row), id);
+        Ok(id)
+    }
+
+    fn type_def_kind(
+        &self,
+        assembly: AssemblyId,
+        row: usize,
+        namespace: &str,
+        name: &str,
+        flags: TypeAttributes,
+        base: Option<TypeId>,
+    ) -> Result<TypeKind, Error> {
+        let in_corlib = self.corlib == Some(assembly);
+        if flags.semantics() == TypeSemantics::Interface {
+            return Ok(TypeKind::Interface);
+        }
+        let base = match base {
+            Some(base) => base,
+            None => return Ok(TypeKind::Class),
+        };
+        if in_corlib && namespace == "System" && name == "String" {
+            return Ok(TypeKind::String);
+        }
+        if self.is_corlib_type(base, "ValueType") && !(in_corlib && namespace == "System" && name == "Enum") {
+            return Ok(match Primitive::from_name(namespace, name) {
+                Some(primitive) if in_corlib => TypeKind::Primitive(primitive),
+                _ => TypeKind::ValueType,
+            });
+        }
+        if self.is_corlib_type(base, "Enum") {
+            // An enum's only instance field, value__, has its underlying type
+            let handle = TableHandle::new(row, TableIndex::TypeDef);
+            let underlying = self.image(assembly).enum_underlying_type(handle)?;
+            return match underlying.as_ref().and_then(Primitive::from_signature) {
+                Some(primitive) => Ok(TypeKind::Enum(primitive)),
+                None => Err(Error::TypeLoad(format!("{}.{} has an invalid underlying type", namespace, name))),
+            };
+        }
+        Ok(TypeKind::Class)
+    }
+
+    /// Returns `true` if the type is the core library's type with the specified name in the `System` namespace.
+    pub fn is_corlib_type(&self, id: TypeId, name: &str) -> bool {
+        let ty = self.get(id);
+        ty.namespace == "System" && ty.name == name && match ty.definition {
+            Some(definition) => Some(definition.assembly) == self.corlib,
+            None => false,
+        }
+    }
+
+    /// Gets the type argument of an instantiation of `System.Nullable<T>`, or `None` for other types.
+    pub fn nullable_underlying(&self, id: TypeId) -> Option<TypeId> {
+        match self.get(id).generic_definition {
+            Some(definition) if self.is_corlib_type(definition, "Nullable`1") => Some(self.get(id).type_args[0]),
+            _ => None,
+        }
+    }
+
+    /// Gets the names and values of an enum's literals, in the order they are declared. Values of unsigned types are
+    /// given as the signed integers with the same bits.
+    pub fn enum_literals(&mut self, id: TypeId) -> Result<Vec<(String, i64)>, Error> {
+        self.prepare(id)?;
+        let mut literals = Vec::new();
+        for &field in &self.get(id).fields {
+            let field = self.field(field);
+            let value = match field.constant {
+                Some(ConstantValue::U8(value)) => Some(value as i64),
+                Some(ref constant) => constant.as_i64(),
+                None => None,
+            };
+            if let Some(value) = value {
+                literals.push((field.name.clone(), value));
+            }
+        }
+        Ok(literals)
+    }
+
+    /// Gets the name of an enum value, as `Enum.ToString` formats it: the name of the literal with the value, or for
+    /// a `[Flags]` enum, the names of the literals that make up the value, separated by ", ". Gets `None` if no
+    /// literals make up the value, when it is formatted as a number.
+    pub fn enum_name(&mut self, id: TypeId, value: i64) -> Result<Option<String>, Error> {
+        let literals = self.enum_literals(id)?;
+        if let Some((name, _)) = literals.iter().find(|literal| literal.1 == value) {
+            return Ok(Some(name.clone()));
+        }
+        if value == 0 || !self.is_flags_enum(id)? {
+            return Ok(None);
+        }
+
+        // The largest literals are taken first, and the names are listed from the smallest
+        let mut flags: Vec<_> = literals.into_iter().filter(|literal| literal.1 != 0).collect();
+        flags.sort_by_key(|literal| !(literal.1 as u64));
+        let mut remaining = value;
+        let mut names = Vec::new();
+        for (name, flag) in flags {
+            if remaining & flag == flag {
+                names.push(name);
+                remaining &= !flag;
+            }
+        }
+        if remaining != 0 {
+            return Ok(None);
+        }
+        names.reverse();
+        Ok(Some(names.join(", ")))
+    }
+
+    /// Returns `true` if a type has `System.FlagsAttribute` applied to it.
+    fn is_flags_enum(&self, id: TypeId) -> Result<bool, Error> {
+        let definition = match self.get(id).definition {
+            Some(definition) => definition,
+            None => return Ok(false),
+        };
+        let loaded = &self.assemblies[definition.assembly.0 as usize];
+        let image = self.image(definition.assembly);
+        for attribute in image.custom_attributes(TableHandle::new(definition.row, TableIndex::TypeDef))? {
+            let index = attribute.attribute_type.index();
+            let attribute_type = match attribute.attribute_type.table() {
+                TableIndex::MemberRef => image.table::<tables::MemberRefDecoder>().get(index)?.class,
+                _ => TableHandle::new(loaded.method_owners.get(index).cloned().unwrap_or(0), TableIndex::TypeDef),
+            };
+            if let TableIndex::TypeRef | TableIndex::TypeDef = attribute_type.table() {
+                if attribute_type.index() != 0 && image.type_name(attribute_type)? == ("System", "FlagsAttribute") {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Gets the custom attributes applied to a row of an assembly, such as a TypeDef, MethodDef or Field row: the
+    /// constructor of each one, with its decoded arguments.
+    ///
+    /// The enums the arguments use are looked up among the types already loaded, which include the types of the
+    /// constructor's parameters and of the attribute's fields and properties.
+    pub fn custom_attributes(
+        &mut self,
+        assembly: AssemblyId,
+        parent: TableHandle,
+    ) -> Result<Vec<(MethodId, CustomAttributeValue)>, Error> {
+        let attributes = self.image(assembly).custom_attributes(parent)?;
+        let mut decoded = Vec::new();
+        for attribute in attributes {
+            let constructor = self.resolve_method_handle(assembly, attribute.attribute_type)?;
+            self.prepare(self.method(constructor).owner)?;
+            let signature = match attribute.attribute_type.table() {
+                TableIndex::MemberRef => self.member_ref(assembly, attribute.attribute_type.index())?.2,
+                _ => {
+                    let image = self.image(assembly);
+                    let method = image.table::<tables::MethodDefDecoder>().get(attribute.attribute_type.index())?;
+                    image.read_blob(method.signature)?.to_vec()
+                }
+            };
+            let signature = MethodSignature::read(&mut &signature[..])?;
+            let blob = self.image(assembly).read_blob(attribute.value)?;
+            let value = CustomAttributeValue::decode(&signature, blob, &AttributeEnums { types: self, assembly })?;
+            decoded.push((constructor, value));
+        }
+        Ok(decoded)
+    }
+
+    fn type_ref(&mut self, assembly: AssemblyId, row: usize) -> Result<TypeId, Error> {
+        if let Some(&id) = self.type_refs.get(&(assembly, row)) {
+            return Ok(id);
+        }
+
+        let (scope, namespace, name) = {
+            let image = self.image(assembly);
+            let type_ref = image.table::<tables::TypeRefDecoder>().get(row)?;
+            let namespace = image.read_string(type_ref.namespace)?.to_owned();
+            let name = image.read_string(type_ref.name)?.to_owned();
+            (type_ref.resolution_scope, namespace, name)
+        };
+        let not_found = || Error::TypeLoad(format!("{}.{}", namespace, name));
+        let id = match scope.table() {
+            TableIndex::AssemblyRef => {
+                let target = {
+                    let image = self.image(assembly);
+                    let reference = image.table::<tables::AssemblyRefDecoder>().get(scope.index())?;
+                    image.read_string(reference.name)?.to_owned()
+                };
+                let target = self.load_assembly(&target)?;
+                let handle = self.image(target).find_type_def(&namespace, &name).ok_or_else(not_found)?;
+                self.type_def(target, handle.index())?
+            }
+            TableIndex::TypeRef => {
+                let enclosing = self.type_ref(assembly, scope.index())?;
+                let definition = self.get(enclosing).definition.ok_or_else(not_found)?;
+                let nested = self.find_nested_type(definition, &name)?.ok_or_else(not_found)?;
+                self.type_def(definition.assembly, nested)?
+            }
+            TableIndex::Module | TableIndex::ModuleRef => {
+                let handle = self.image(assembly).find_type_def(&namespace, &name).ok_or_else(not_found)?;
+                self.type_def(assembly, handle.index())?
+            }
+            _ => return Err(not_found()),
+        };
+        self.type_refs.insert((assembly, row), id);
+        Ok(id)
+    }
+
+    /// Finds the row of a type nested in a TypeDef by its name.
+    fn find_nested_type(&self, enclosing: TypeDefinition, name: &str) -> Result<Option<usize>, Error> {
+        let image = self.image(enclosing.assembly);
+        let enclosing_handle = TableHandle::new(enclosing.row, TableIndex::TypeDef);
+        for nested in image.table::<tables::NestedClassDecoder>().iter() {
+            let nested = nested?;
+            if nested.enclosing_class == enclosing_handle && image.type_name(nested.nested_class)?.1 == name {
+                return Ok(Some(nested.nested_class.index()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finds a type by its serialized name, as custom attributes refer to types: its full name, with `+` before the
+    /// names of nested types, optionally followed by the name of its assembly. A type without an assembly name is
+    /// looked for in the assembly using it, then in the core library.
+    pub fn find_type_by_name(&mut self, assembly: AssemblyId, name: &str) -> Result<Option<TypeId>, Error> {
+        let mut parts = name.splitn(2, ',');
+        let full_name = parts.next().unwrap_or_default().trim();
+        let scopes = match parts.next().and_then(|rest| rest.split(',').next()) {
+            Some(assembly_name) => vec![self.load_assembly(assembly_name.trim())?],
+            None => vec![assembly, self.corlib()?],
+        };
+        let mut names = full_name.split('+');
+        let outermost = names.next().unwrap_or_default();
+        let (namespace, outermost) = match outermost.rfind('.') {
+            Some(index) => (&outermost[..index], &outermost[index + 1..]),
+            None => ("", outermost),
+        };
+        for scope in scopes {
+            let handle = match self.image(scope).find_type_def(namespace, outermost) {
+                Some(handle) => handle,
+                None => continue,
+            };
+            let mut id = self.type_def(scope, handle.index())?;
+            for nested in names {
+                let definition = self.get(id).definition.unwrap();
+                match self.find_nested_type(definition, nested)? {
+                    Some(row) => id = self.type_def(definition.assembly, row)?,
+                    None => return Ok(None),
+                }
+            }
+            return Ok(Some(id));
+        }
+        Ok(None)
+    }
+
+    /// Gets the type a type is nested in, if it is nested.
+    pub fn enclosing_type(&mut self, id: TypeId) -> Result<Option<TypeId>, Error> {
+        let definition = match self.get(id).definition {
+            Some(definition) => definition,
+            None => return Ok(None),
+        };
+        let handle = TableHandle::new(definition.row, TableIndex::TypeDef);
+        let mut enclosing = None;
+        for nested in self.image(definition.assembly).table::<tables::NestedClassDecoder>().iter() {
+            let nested = nested?;
+            if nested.nested_class == handle {
+                enclosing = Some(nested.enclosing_class.index());
+                break;
+            }
+        }
+        match enclosing {
+            Some(row) => self.type_def(definition.assembly, row).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Resolves a type in a signature blob of an assembly.
+    pub fn resolve_signature_type(&mut self, assembly: AssemblyId, ty: &TypeReference) -> Result<TypeId, Error> {
+        if let Some(primitive) = Primitive::from_signature(ty) {
+            return self.primitive(primitive);
+        }
+        let primitive = match *ty {
+            TypeReference::FnPtr(_) => Primitive::I,
+            TypeReference::String => return self.string(),
+            TypeReference::Object => return self.object(),
+            TypeReference::Class(handle) | TypeReference::ValueType(handle) => {
+                return self.resolve_type_handle(assembly, handle)
+            }
+            TypeReference::SzArray(_, ref element) => {
+                let element = self.resolve_signature_type(assembly, element)?;
+                return self.sz_array(element);
+            }
+            TypeReference::Array(ref element, ref shape) => {
+                let element = self.resolve_signature_type(assembly, element)?;
+                return self.array(element, shape.rank);
+            }
+            TypeReference::ByRef(ref target) => {
+                let target = self.resolve_signature_type(assembly, target)?;
+                return self.by_ref(target);
+            }
+            TypeReference::Ptr(_, ref target) => {
+                let target = match **target {
+                    TypeReference::Void => self.corlib_void()?,
+                    ref target => self.resolve_signature_type(assembly, target)?,
+                };
+                return self.pointer(target);
+            }
+            TypeReference::Var(index) => return Ok(self.generic_param(TypeKind::Var(index))),
+            TypeReference::MVar(index) => return Ok(self.generic_param(TypeKind::MVar(index))),
+            TypeReference::GenericInst(ref definition, ref args) => {
+                let definition = self.resolve_signature_type(assembly, definition)?;
+                let mut type_args = Vec::with_capacity(args.len());
+                for arg in args {
+                    type_args.push(self.resolve_signature_type(assembly, arg)?);
+                }
+                return self.instantiate(definition, type_args);
+            }
+            _ => return Err(Error::TypeLoad(format!("{} is not supported", ty))),
+        };
+        self.primitive(primitive)
+    }
+
+    /// Gets `System.Void`, which is only used as the target of `void*`.
+    fn corlib_void(&mut self) -> Result<TypeId, Error> {
+        match self.find_corlib_type("System", "Void")? {
+            Some(void) => Ok(void),
+            None => {
+                let base = self.object()?;
+                let id = self.synthesize(RuntimeType::new("System", "Void", TypeKind::ValueType), base)?;
+                self.corlib_types.insert(("System", "Void"), id);
+                Ok(id)
+            }
+        }
+    }
+
+    /// Resolves the types of a method signature. Returning `void` is represented as no return type.
+    pub fn resolve_method_signature(
+        &mut self,
+        assembly: AssemblyId,
+        signature: &MethodSignature,
+    ) -> Result<MethodSig, Error> {
+        let ret = match signature.return_type.type_reference {
+            TypeReference::Void => None,
+            ref ty => Some(self.resolve_signature_type(assembly, ty)?),
+        };
+        let mut params = Vec::with_capacity(signature.parameters.len());
+        for param in &signature.parameters {
+            params.push(self.resolve_signature_type(assembly, &param.type_reference)?);
+        }
+        Ok(MethodSig {
+            has_this: signature.header.has_this(),
+            ret,
+            params,
+        })
+    }
+
+    /// Loads the fields, methods and layout of a type, after loading its base type.
+    pub fn prepare(&mut self, id: TypeId) -> Result<(), Error> {
+        if self.get(id).state != LoadState::Created {
+            return Ok(());
+        }
+        self.types[id.index()].state = LoadState::Preparing;
+        if let Some(base) = self.get(id).base {
+            self.prepare(base)?;
+        }
+        let definition = match self.get(id).definition {
+            Some(definition) => definition,
+            None => {
+                self.types[id.index()].state = LoadState::Prepared;
+                return Ok(());
+            }
+        };
+
+        match self.get(id).generic_definition {
+            Some(generic_definition) => self.instantiate_members(id, generic_definition)?,
+            None => {
+                self.load_fields(id, definition)?;
+                self.load_methods(id, definition)?;
+                self.load_interfaces(id)?;
+            }
+        }
+        self.layout_instance_fields(id)?;
+        self.layout_static_fields(id)?;
+        let method_impls = self.method_impls(id)?;
+        self.build_vtable(id, &method_impls);
+        self.build_interface_vtables(id, &method_impls)?;
+
+        let ty = &mut self.types[id.index()];
+        ty.state = LoadState::Prepared;
+        debug!(self.logger, "prepared type {}", ty; "size" => ty.size, "vtable" => ty.vtable.len());
+        Ok(())
+    }
+
+    fn load_fields(&mut self, id: TypeId, definition: TypeDefinition) -> Result<(), Error> {
+        let rows = {
+            let image = self.image(definition.assembly);
+            let fields = image.table::<tables::FieldDecoder>();
+            let mut rows = Vec::new();
+            for handle in image.type_def_fields(TableHandle::new(definition.row, TableIndex::TypeDef))? {
+                let field = fields.get(handle.index())?;
+                let signature = FieldSignature::read(&mut image.read_blob(field.signature)?)?;
+                let constant = if field.flags.flags().contains(FieldFlags::Literal) {
+                    image.constant(handle)?
+                } else {
+                    None
+                };
+                let name = image.read_string(field.name)?.to_owned();
+                rows.push((handle.index(), field.flags, name, signature, constant));
+            }
+            rows
+        };
+
+        for (row, flags, name, signature, constant) in rows {
+            let field_type = self.resolve_signature_type(definition.assembly, &signature.field_type)?;
+            let field = FieldId(self.fields.len() as u32);
+            self.fields.push(RuntimeField {
+                name,
+                owner: id,
+                definition: Some(FieldDefinition {
+                    assembly: definition.assembly,
+                    row,
+                }),
+                flags,
+                field_type,
+                offset: 0,
+                constant,
+            });
+            self.field_defs.insert((definition.assembly, row), field);
+            self.types[id.index()].fields.push(field);
+        }
+        Ok(())
+    }
+
+    /// Creates the fields, methods and interfaces of a generic type instantiation from its definition's, with the
+    /// type arguments substituted, after checking the arguments satisfy the definition's constraints.
+    fn instantiate_members(&mut self, id: TypeId, generic_definition: TypeId) -> Result<(), Error> {
+        self.prepare(generic_definition)?;
+        let context = GenericContext::new(self.get(id).type_args.clone(), Vec::new());
+        if !self.is_open(id) {
+            let definition = self.get(id).definition.unwrap();
+            let handle = TableHandle::new(definition.row, TableIndex::TypeDef);
+            let params = self.generic_params(definition.assembly, handle)?;
+            let name = self.get(generic_definition).to_string();
+            self.check_constraints(definition.assembly, &params, &context, &context.type_args, &name)?;
+        }
+
+        for field in self.get(generic_definition).fields.clone() {
+            let field_type = self.substitute(self.field(field).field_type, &context)?;
+            let generic = self.field(field);
+            let instantiated = RuntimeField {
+                name: generic.name.clone(),
+                owner: id,
+                definition: generic.definition,
+                flags: generic.flags,
+                field_type,
+                offset: 0,
+                constant: generic.constant.clone(),
+            };
+            self.types[id.index()].fields.push(FieldId(self.fields.len() as u32));
+            self.fields.push(instantiated);
+        }
+        for method in self.get(generic_definition).methods.clone() {
+            let signature = self.method(method).signature.clone();
+            let signature = self.substitute_signature(&signature, &context)?;
+            let generic = self.method(method);
+            let instantiated = RuntimeMethod {
+                name: generic.name.clone(),
+                owner: id,
+                definition: generic.definition,
+                flags: generic.flags,
+                impl_flags: generic.impl_flags,
+                signature,
+                slot: None,
+                generic_definition: None,
+                method_args: Vec::new(),
+            };
+            self.types[id.index()].methods.push(MethodId(self.methods.len() as u32));
+            self.methods.push(instantiated);
+        }
+        let mut interfaces = self.get(generic_definition).interfaces.clone();
+        for interface in &mut interfaces {
+            *interface = self.substitute(*interface, &context)?;
+        }
+        self.types[id.index()].interfaces = interfaces;
+        Ok(())
+    }
+
+    /// Loads the interfaces a type implements: those of its base type, the ones it declares, and the ones those
+    /// inherit from.
+    fn load_interfaces(&mut self, id: TypeId) -> Result<(), Error> {
+        let mut interfaces = match self.get(id).base {
+            Some(base) => self.get(base).interfaces.clone(),
+            None => Vec::new(),
+        };
+        for interface in self.declared_interfaces(id)? {
+            self.prepare(interface)?;
+            for &interface in Some(&interface).into_iter().chain(self.get(interface).interfaces.iter()) {
+                if !interfaces.contains(&interface) {
+                    interfaces.push(interface);
+                }
+            }
+        }
+        self.types[id.index()].interfaces = interfaces;
+        Ok(())
+    }
+
+    /// Gets the interfaces a type's InterfaceImpl rows say it implements, instantiated like the type.
+    fn declared_interfaces(&mut self, id: TypeId) -> Result<Vec<TypeId>, Error> {
+        let definition = match self.get(id).definition {
+            Some(definition) => definition,
+            None => return Ok(Vec::new()),
+        };
+        let handles = {
+            let image = self.image(definition.assembly);
+            let class = TableHandle::new(definition.row, TableIndex::TypeDef);
+            let mut handles = Vec::new();
+            for interface_impl in image.table::<tables::InterfaceImplDecoder>().iter() {
+                let interface_impl = interface_impl?;
+                if interface_impl.class == class {
+                    handles.push(interface_impl.interface);
+                }
+            }
+            handles
+        };
+        let context = GenericContext::new(self.get(id).type_args.clone(), Vec::new());
+        let mut declared = Vec::with_capacity(handles.len());
+        for handle in handles {
+            let interface = self.resolve_type_handle(definition.assembly, handle)?;
+            declared.push(self.substitute(interface, &context)?);
+        }
+        Ok(declared)
+    }
+
+    fn layout_instance_fields(&mut self, id: TypeId) -> Result<(), Error> {
+        if let TypeKind::Primitive(primitive) = self.get(id).kind {
+            let ty = &mut self.types[id.index()];
+            ty.size = primitive.size();
+            ty.alignment = primitive.size();
+            return Ok(());
+        }
+
+        let is_value_type = self.get(id).is_value_type();
+        let (mut offset, mut alignment, mut ref_offsets) = match self.get(id).base {
+            Some(base) if !is_value_type => {
+                let base = self.get(base);
+                (base.size, base.alignment, base.ref_offsets.clone())
+            }
+            _ => (0, 1, Vec::new()),
+        };
+        for field in self.get(id).fields.clone() {
+            if self.field(field).is_static() || self.field(field).is_literal() {
+                continue;
+            }
+            let (field_size, field_alignment, field_refs) = self.value_layout(id, field)?;
+            offset = align(offset, field_alignment);
+            self.fields[field.index()].offset = offset;
+            ref_offsets.extend(field_refs.iter().map(|x| x + offset));
+            alignment = cmp::max(alignment, field_alignment);
+            offset += field_size;
+        }
+
+        // A value type can be given a size larger than its fields need, such as one holding the data of a static
+        // field with an RVA
+        if let (true, Some(definition)) = (is_value_type, self.get(id).definition) {
+            let handle = TableHandle::new(definition.row, TableIndex::TypeDef);
+            if let Some(class_size) = self.image(definition.assembly).class_size(handle)? {
+                offset = cmp::max(offset, class_size);
+            }
+        }
+
+        let ty = &mut self.types[id.index()];
+        ty.size = if is_value_type {
+            cmp::max(align(offset, alignment), 1)
+        } else {
+            offset
+        };
+        ty.alignment = alignment;
+        ty.ref_offsets = ref_offsets;
+        Ok(())
+    }
+
+    fn layout_static_fields(&mut self, id: TypeId) -> Result<(), Error> {
+        let mut offset = 0;
+        let mut ref_offsets = Vec::new();
+        for field in self.get(id).fields.clone() {
+            if !self.field(field).is_static() || self.field(field).is_literal() {
+                continue;
+            }
+            let (field_size, field_alignment, field_refs) = self.value_layout(id, field)?;
+            offset = align(offset, field_alignment);
+            self.fields[field.index()].offset = offset;
+            ref_offsets.extend(field_refs.iter().map(|x| x + offset));
+            offset += field_size;
+        }
+
+        let ty = &mut self.types[id.index()];
+        ty.static_size = offset;
+        ty.static_ref_offsets = ref_offsets;
+        Ok(())
+    }
+
+    /// Gets the size, alignment and object reference offsets of a field's value, preparing its type if it is a
+    /// value type stored inline.
+    fn value_layout(&mut self, owner: TypeId, field: FieldId) -> Result<(u32, u32, Vec<u32>), Error> {
+        let field_type = self.field(field).field_type;
+        if self.get(field_type).is_value_type() {
+            if field_type == owner && !self.field(field).is_static() {
+                return Err(Error::TypeLoad(format!("{} contains itself", self.get(owner))));
+            }
+            self.prepare(field_type)?;
+            if self.get(field_type).state != LoadState::Prepared && !self.field(field).is_static() {
+                return Err(Error::TypeLoad(format!("{} contains itself", self.get(field_type))));
+            }
+        }
+        let ty = self.get(field_type);
+        Ok((ty.value_size(), ty.value_alignment(), ty.value_ref_offsets().to_vec()))
+    }
+
+    fn load_methods(&mut self, id: TypeId, definition: TypeDefinition) -> Result<(), Error> {
+        let rows = {
+            let image = self.image(definition.assembly);
+            let methods = image.table::<tables::MethodDefDecoder>();
+            let mut rows = Vec::new();
+            for handle in image.type_def_methods(TableHandle::new(definition.row, TableIndex::TypeDef))? {
+                let method = methods.get(handle.index())?;
+                let signature = MethodSignature::read(&mut image.read_blob(method.signature)?)?;
+                let name = image.read_string(method.name)?.to_owned();
+                rows.push((handle.index(), method.flags, method.impl_flags, name, signature));
+            }
+            rows
+        };
+
+        for (row, flags, impl_flags, name, signature) in rows {
+            let signature = self.resolve_method_signature(definition.assembly, &signature)?;
+            let method = MethodId(self.methods.len() as u32);
+            self.methods.push(RuntimeMethod {
+                name,
+                owner: id,
+                definition: Some(MethodDefinition {
+                    assembly: definition.assembly,
+                    row,
+                }),
+                flags,
+                impl_flags,
+                signature,
+                slot: None,
+                generic_definition: None,
+                method_args: Vec::new(),
+            });
+            self.method_defs.insert((definition.assembly, row), method);
+            self.types[id.index()].methods.push(method);
+        }
+        Ok(())
+    }
+
+    /// Gets the methods a type's MethodImpl rows say its methods explicitly implement or override, each with the
+    /// method implementing it, instantiated like the type.
+    fn method_impls(&mut self, id: TypeId) -> Result<Vec<(MethodId, MethodId)>, Error> {
+        let definition = match self.get(id).definition {
+            Some(definition) => definition,
+            None => return Ok(Vec::new()),
+        };
+        let rows = {
+            let image = self.image(definition.assembly);
+            let class = TableHandle::new(definition.row, TableIndex::TypeDef);
+            let mut rows = Vec::new();
+            for method_impl in image.table::<tables::MethodImplDecoder>().iter() {
+                let method_impl = method_impl?;
+                if method_impl.class == class {
+                    rows.push((method_impl.method_declaration, method_impl.method_body));
+                }
+            }
+            rows
+        };
+        let context = GenericContext::new(self.get(id).type_args.clone(), Vec::new());
+        let mut method_impls = Vec::with_capacity(rows.len());
+        for (declaration, body) in rows {
+            let declaration = self.resolve_method_handle(definition.assembly, declaration)?;
+            let declaration = self.substitute_method(declaration, &context)?;
+            let body = self.resolve_method_handle(definition.assembly, body)?;
+            let body = self.substitute_method(body, &context)?;
+            method_impls.push((declaration, body));
+        }
+        Ok(method_impls)
+    }
+
+    /// Assigns each virtual method a vtable slot, reusing the slot of the base type's method with the same name and
+    /// signature unless the method asks for a new one. Methods a MethodImpl row says override a base type's method
+    /// also take its slot, whatever they are named.
+    fn build_vtable(&mut self, id: TypeId, method_impls: &[(MethodId, MethodId)]) {
+        let mut vtable = match self.get(id).base {
+            Some(base) => self.get(base).vtable.clone(),
+            None => Vec::new(),
+        };
+        for method in self.get(id).methods.clone() {
+            if !self.method(method).is_virtual() {
+                continue;
+            }
+            let reused = if self.method(method).flags.vtable_layout() == MethodVTableLayout::ReuseSlot {
+                let candidate = self.method(method);
+                vtable.iter().rposition(|&other| {
+                    let other = self.method(other);
+                    other.name == candidate.name && other.signature == candidate.signature
+                })
+            } else {
+                None
+            };
+            let slot = match reused {
+                Some(slot) => {
+                    vtable[slot] = method;
+                    slot
+                }
+                None => {
+                    vtable.push(method);
+                    vtable.len() - 1
+                }
+            };
+            self.methods[method.index()].slot = Some(slot);
+        }
+        for &(declaration, body) in method_impls {
+            let declaration = self.method(declaration);
+            if self.get(declaration.owner).kind == TypeKind::Interface {
+                continue;
+            }
+            if let Some(implementation) = declaration.slot.and_then(|slot| vtable.get_mut(slot)) {
+                *implementation = body;
+            }
+        }
+        self.types[id.index()].vtable = vtable;
+    }
+
+    /// Maps each slot of each interface a class or value type implements to the method that implements it. That is
+    /// the method a MethodImpl row names, or a virtual method with the same name and signature if the type declares
+    /// the interface itself, or else the method its base type maps the slot to. Failing those, it is the most
+    /// specific default implementation the interfaces give.
+    fn build_interface_vtables(&mut self, id: TypeId, method_impls: &[(MethodId, MethodId)]) -> Result<(), Error> {
+        if self.get(id).kind == TypeKind::Interface {
+            return Ok(());
+        }
+        let declared = self.declared_interfaces(id)?;
+        let mut default_impls = Vec::new();
+        for interface in self.get(id).interfaces.clone() {
+            default_impls.extend(self.method_impls(interface)?);
+        }
+
+        let mut interface_vtables = Vec::new();
+        for interface in self.get(id).interfaces.clone() {
+            let inherited = self.get(id).base.and_then(|base| {
+                let base = self.get(base);
+                let index = base.interfaces.iter().position(|&other| other == interface)?;
+                base.interface_vtables.get(index).cloned()
+            });
+            let redeclared = inherited.is_none() || declared.contains(&interface);
+            let mut interface_vtable = Vec::new();
+            for (slot, method) in self.get(interface).vtable.clone().into_iter().enumerate() {
+                let mut implementation = method_impls.iter().find(|pair| pair.0 == method).map(|pair| pair.1);
+                if implementation.is_none() && redeclared {
+                    implementation = self.matching_virtual(id, method);
+                }
+                if implementation.is_none() {
+                    // An implementation the base type inherits from an interface is kept, while one it declares is
+                    // replaced by whatever overrides it
+                    implementation = inherited.as_ref().map(|vtable| vtable[slot]).filter(|&other| other != method);
+                    if let Some(inherited) = implementation {
+                        let inherited = self.method(inherited);
+                        let is_default = self.get(inherited.owner).kind == TypeKind::Interface;
+                        if let (Some(slot), false) = (inherited.slot, is_default) {
+                            implementation = Some(self.get(id).vtable[slot]);
+                        }
+                    }
+                }
+                let implementation = match implementation.or_else(|| self.matching_virtual(id, method)) {
+                    Some(implementation) => implementation,
+                    None => self.default_implementation(id, method, &default_impls)?,
+                };
+                interface_vtable.push(implementation);
+            }
+            interface_vtables.push(interface_vtable);
+        }
+        self.types[id.index()].interface_vtables = interface_vtables;
+        Ok(())
+    }
+
+    /// Finds the virtual method of a type, declared or inherited, with the same name and signature as an interface
+    /// method.
+    fn matching_virtual(&self, id: TypeId, method: MethodId) -> Option<MethodId> {
+        let method = self.method(method);
+        self.get(id).vtable.iter().rev().cloned().find(|&candidate| {
+            let candidate = self.method(candidate);
+            candidate.name == method.name && candidate.signature == method.signature
+        })
+    }
+
+    /// Finds the most specific default implementation of an interface method a type gets from the interfaces it
+    /// implements: the one given by the interface that derives from the interfaces of all the others. The interface
+    /// method's own body is one, and `default_impls` has the overrides interfaces give with MethodImpl rows. An
+    /// abstract type can leave the method unimplemented.
+    fn default_implementation(
+        &self,
+        id: TypeId,
+        method: MethodId,
+        default_impls: &[(MethodId, MethodId)],
+    ) -> Result<MethodId, Error> {
+        let mut candidates: Vec<_> = default_impls.iter().filter(|pair| pair.0 == method).map(|pair| pair.1).collect();
+        candidates.push(method);
+        candidates.retain(|&candidate| !self.method(candidate).is_abstract());
+        let most_specific = candidates.iter().cloned().find(|&candidate| {
+            let owner = self.method(candidate).owner;
+            candidates.iter().all(|&other| {
+                let other = self.method(other).owner;
+                other == owner || self.get(owner).interfaces.contains(&other)
+            })
+        });
+        let ty = self.get(id);
+        let interface = self.get(self.method(method).owner);
+        match most_specific {
+            Some(implementation) => Ok(implementation),
+            None if candidates.is_empty() && ty.flags.flags().contains(TypeFlags::Abstract) => Ok(method),
+            None if candidates.is_empty() => Err(Error::TypeLoad(format!(
+                "Method '{}' in type '{}' does not have an implementation.",
+                self.method(method),
+                ty
+            ))),
+            None => Err(Error::TypeLoad(format!(
+                "'{}' has no most specific implementation of '{}::{}'.",
+                ty,
+                interface,
+                self.method(method)
+            ))),
+        }
+    }
+
+    /// Finds the method a virtual call to `method` on an instance of `ty` runs: the one in the type's vtable slot for
+    /// it, or for an interface method, in the type's vtable for the interface or one it can be used as through
+    /// variance. A call to an instantiation of a generic virtual method runs the implementation instantiated with
+    /// the same type arguments.
+    pub fn find_implementation(&mut self, ty: TypeId, method: MethodId) -> Result<MethodId, Error> {
+        self.prepare(ty)?;
+        let (definition, method_args) = match self.method(method).generic_definition {
+            Some(definition) => (definition, Some(self.method(method).method_args.clone())),
+            None => (method, None),
+        };
+        let (owner, slot) = (self.method(definition).owner, self.method(definition).slot);
+        let slot = match slot {
+            Some(slot) => slot,
+            None => return Ok(method),
+        };
+        let target = if self.get(owner).kind == TypeKind::Interface {
+            match self.interface_index(ty, owner) {
+                Some(index) => self.get(ty).interface_vtables[index][slot],
+                None => return Err(ExceptionKind::EntryPointNotFound.into()),
+            }
+        } else {
+            self.get(ty).vtable[slot]
+        };
+        match method_args {
+            Some(method_args) => self.instantiate_method(target, method_args),
+            None => Ok(target),
+        }
+    }
+
+    /// Gets the index in a type's interfaces of an interface, or failing that, of the first one that can be used as
+    /// it through variance.
+    fn interface_index(&self, ty: TypeId, interface: TypeId) -> Option<usize> {
+        let interfaces = &self.get(ty).interfaces;
+        interfaces.iter().position(|&other| other == interface)
+            .or_else(|| interfaces.iter().position(|&other| self.is_variant_of(other, interface)))
+    }
+
+    /// Resolves a MethodDef, MemberRef or MethodSpec token used by an assembly, in the generic context of the method
+    /// using it, preparing the method's type.
+    pub fn resolve_method_token(
+        &mut self,
+        assembly: AssemblyId,
+        token: u32,
+        context: &GenericContext,
+    ) -> Result<MethodId, Error> {
+        let handle = TableHandle::from_token(token).ok_or_else(|| bad_token(token))?;
+        let method = self.resolve_method_handle(assembly, handle)?;
+        self.substitute_method(method, context)
+    }
+
+    /// Resolves the StandAloneSig token of a `calli` used by an assembly, in the generic context of the method using
+    /// it, and gets the calling convention of the call site's signature with its types.
+    pub fn resolve_call_site_token(
+        &mut self,
+        assembly: AssemblyId,
+        token: u32,
+        context: &GenericContext,
+    ) -> Result<(SignatureCallingConvention, MethodSig), Error> {
+        let handle = TableHandle::from_token(token)
+            .filter(|handle| handle.table() == TableIndex::StandAloneSig)
+            .ok_or_else(|| bad_token(token))?;
+        let signature = {
+            let image = self.image(assembly);
+            let signature = image.table::<tables::StandAloneSigDecoder>().get(handle.index())?.signature;
+            MethodSignature::read(&mut image.read_blob(signature)?)?
+        };
+        let resolved = self.resolve_method_signature(assembly, &signature)?;
+        let ret = match resolved.ret {
+            Some(ret) => Some(self.substitute(ret, context)?),
+            None => None,
+        };
+        let mut params = Vec::with_capacity(resolved.params.len());
+        for &param in &resolved.params {
+            params.push(self.substitute(param, context)?);
+        }
+        let signature_with_types = MethodSig {
+            has_this: resolved.has_this,
+            ret,
+            params,
+        };
+        Ok((signature.header.calling_convention(), signature_with_types))
+    }
+
+    fn resolve_method_handle(&mut self, assembly: AssemblyId, handle: TableHandle) -> Result<MethodId, Error> {
+        let token = handle.token();
+        match handle.table() {
+            TableIndex::MethodDef => self.method_def(assembly, handle.index()),
+            TableIndex::MethodSpec => {
+                if let Some(&method) = self.member_methods.get(&(assembly, token)) {
+                    return Ok(method);
+                }
+                let (generic, instantiation) = {
+                    let image = self.image(assembly);
+                    let spec = image.table::<tables::MethodSpecDecoder>().get(handle.index())?;
+                    (spec.method, MethodSpecSignature::read(&mut image.read_blob(spec.instantiation)?)?)
+                };
+                let generic = self.resolve_method_handle(assembly, generic)?;
+                let mut method_args = Vec::with_capacity(instantiation.arguments.len());
+                for arg in &instantiation.arguments {
+                    method_args.push(self.resolve_signature_type(assembly, arg)?);
+                }
+                let method = self.instantiate_method(generic, method_args)?;
+                self.member_methods.insert((assembly, token), method);
+                Ok(method)
+            }
+            TableIndex::MemberRef => {
+                if let Some(&method) = self.member_methods.get(&(assembly, token)) {
+                    return Ok(method);
+                }
+                let (class, name, signature) = self.member_ref(assembly, handle.index())?;
+                let signature = MethodSignature::read(&mut &signature[..])?;
+                let signature = self.resolve_method_signature(assembly, &signature)?;
+                let owner = self.resolve_type_handle(assembly, class)?;
+                let method = self.find_method(owner, &name, &signature)?
+                    .ok_or_else(|| Error::MissingMethod(format!("{}::{}", self.get(owner), name)))?;
+                self.member_methods.insert((assembly, token), method);
+                Ok(method)
+            }
+            _ => Err(bad_token(token)),
+        }
+    }
+
+    fn method_def(&mut self, assembly: AssemblyId, row: usize) -> Result<MethodId, Error> {
+        if let Some(&method) = self.method_defs.get(&(assembly, row)) {
+            return Ok(method);
+        }
+        let owner = self.member_owner(assembly, row, TableIndex::MethodDef)?;
+        self.prepare(owner)?;
+        self.method_defs
+            .get(&(assembly, row))
+            .cloned()
+            .ok_or_else(|| bad_token(TableHandle::new(row, TableIndex::MethodDef).token()))
+    }
+
+    /// Resolves a Field or MemberRef token used by an assembly, in the generic context of the method using it,
+    /// preparing the field's type.
+    pub fn resolve_field_token(
+        &mut self,
+        assembly: AssemblyId,
+        token: u32,
+        context: &GenericContext,
+    ) -> Result<FieldId, Error> {
+        let field = self.resolve_field_handle(assembly, token)?;
+        self.substitute_field(field, context)
+    }
+
+    fn resolve_field_handle(&mut self, assembly: AssemblyId, token: u32) -> Result<FieldId, Error> {
+        let handle = TableHandle::from_token(token).ok_or_else(|| bad_token(token))?;
+        match handle.table() {
+            TableIndex::Field => {
+                if let Some(&field) = self.field_defs.get(&(assembly, handle.index())) {
+                    return Ok(field);
+                }
+                let owner = self.member_owner(assembly, handle.index(), TableIndex::Field)?;
+                self.prepare(owner)?;
+                self.field_defs.get(&(assembly, handle.index())).cloned().ok_or_else(|| bad_token(token))
+            }
+            TableIndex::MemberRef => {
+                if let Some(&field) = self.member_fields.get(&(assembly, token)) {
+                    return Ok(field);
+                }
+                let (class, name, _) = self.member_ref(assembly, handle.index())?;
+                let owner = self.resolve_type_handle(assembly, class)?;
+                let field = self.find_field(owner, &name)?
+                    .ok_or_else(|| Error::MissingField(format!("{}::{}", self.get(owner), name)))?;
+                self.member_fields.insert((assembly, token), field);
+                Ok(field)
+            }
+            _ => Err(bad_token(token)),
+        }
+    }
+
+    /// Returns `true` if a token used by an assembly refers to a field: a Field token, or a MemberRef token with a
+    /// field signature.
+    pub fn is_field_token(&self, assembly: AssemblyId, token: u32) -> Result<bool, Error> {
+        let handle = TableHandle::from_token(token).ok_or_else(|| bad_token(token))?;
+        match handle.table() {
+            TableIndex::Field => Ok(true),
+            TableIndex::MemberRef => {
+                let (_, _, signature) = self.member_ref(assembly, handle.index())?;
+                Ok(SignatureHeader::read(&mut &signature[..])?.kind() == SignatureKind::Field)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Returns `true` if a token refers to a type: a TypeDef, TypeRef or TypeSpec token.
+    pub fn is_type_token(&self, token: u32) -> bool {
+        matches!(
+            TableHandle::from_token(token).map(|handle| handle.table()),
+            Some(TableIndex::TypeDef) | Some(TableIndex::TypeRef) | Some(TableIndex::TypeSpec)
+        )
+    }
+
+    fn member_ref(&self, assembly: AssemblyId, row: usize) -> Result<(TableHandle, String, Vec<u8>), Error> {
+        let image = self.image(assembly);
+        let member = image.table::<tables::MemberRefDecoder>().get(row)?;
+        Ok((member.class, image.read_string(member.name)?.to_owned(), image.read_blob(member.signature)?.to_vec()))
+    }
+
+    /// Gets the type that owns a MethodDef or Field row.
+    fn member_owner(&mut self, assembly: AssemblyId, row: usize, table: TableIndex) -> Result<TypeId, Error> {
+        let owner = {
+            let loaded = &self.assemblies[assembly.0 as usize];
+            let owners = if table == TableIndex::MethodDef {
+                &loaded.method_owners
+            } else {
+                &loaded.field_owners
+            };
+            owners.get(row).cloned().unwrap_or(0)
+        };
+        if owner == 0 {
+            return Err(bad_token(TableHandle::new(row, table).token()));
+        }
+        self.type_def(assembly, owner)
+    }
+
+    /// Finds a method by name and signature in a type or its base types.
+    ///
+    /// The signature is compared with the generic type definition's methods for instantiations, since references
+    /// to members of instantiations use the definition's signatures, in terms of its generic parameters.
+    pub fn find_method(&mut self, owner: TypeId, name: &str, signature: &MethodSig) -> Result<Option<MethodId>, Error> {
+        let mut current = Some(owner);
+        while let Some(id) = current {
+            self.prepare(id)?;
+            let definition = self.get(id).generic_definition.unwrap_or(id);
+            let found = self.get(definition).methods.iter().position(|&method| {
+                let method = self.method(method);
+                method.name == name && method.signature == *signature
+            });
+            if let Some(index) = found {
+                return Ok(Some(self.get(id).methods[index]));
+            }
+            current = self.get(id).base;
+        }
+        Ok(None)
+    }
+
+    /// Gets the initial value of a static field with an RVA, which is data in the image as large as a value of the
+    /// field's type.
+    pub fn field_data(&mut self, field: FieldId) -> Result<Option<&[u8]>, Error> {
+        let (field_type, definition) = match *self.field(field) {
+            RuntimeField {
+                field_type,
+                definition: Some(definition),
+                ..
+            } if self.field(field).has_rva() => (field_type, definition),
+            _ => return Ok(None),
+        };
+        self.prepare(field_type)?;
+        let size = self.get(field_type).value_size();
+        let handle = TableHandle::new(definition.row, TableIndex::Field);
+        Ok(self.image(definition.assembly).field_data(handle, size)?)
+    }
+
+    /// Gets how a P/Invoke method calls the native function it imports, or `None` if it isn't a P/Invoke.
+    pub fn pinvoke_import(&self, method: MethodId) -> Result<Option<PInvokeImport>, Error> {
+        let definition = match self.method(method).definition {
+            Some(definition) if self.method(method).is_pinvoke() => definition,
+            _ => return Ok(None),
+        };
+        let image = self.image(definition.assembly);
+        let handle = TableHandle::new(definition.row, TableIndex::MethodDef);
+        let impl_map = match image.impl_map(handle)? {
+            Some(impl_map) => impl_map,
+            None => return Ok(None),
+        };
+        let module_ref = image.table::<tables::ModuleRefDecoder>().get(impl_map.import_scope.index())?;
+        let signature = image.table::<tables::MethodDefDecoder>().get(definition.row)?.signature;
+        let header = SignatureHeader::read(&mut image.read_blob(signature)?)?;
+        let calling_convention = match header.calling_convention() {
+            SignatureCallingConvention::Default => match impl_map.mapping_flags.calling_convention() {
+                PInvokeCallingConvention::Cdecl => SignatureCallingConvention::CDecl,
+                PInvokeCallingConvention::Stdcall => SignatureCallingConvention::StdCall,
+                PInvokeCallingConvention::Thiscall => SignatureCallingConvention::ThisCall,
+                PInvokeCallingConvention::Fastcall => SignatureCallingConvention::FastCall,
+                PInvokeCallingConvention::Platformapi | PInvokeCallingConvention::Reserved => {
+                    SignatureCallingConvention::Default
+                }
+            },
+            calling_convention => calling_convention,
+        };
+
+        // Param rows are numbered from 1, with 0 for the return value
+        let params = image.table::<tables::ParamDecoder>();
+        let mut return_type = None;
+        let mut param_types = vec![None; self.method(method).signature.params.len()];
+        for param in image.method_params(handle)? {
+            let native_type = match image.field_marshal(param)? {
+                Some(descriptor) => NativeType::read(descriptor)?,
+                None => continue,
+            };
+            match params.get(param.index())?.sequence as usize {
+                0 => return_type = Some(native_type),
+                sequence if sequence <= param_types.len() => param_types[sequence - 1] = Some(native_type),
+                _ => {}
+            }
+        }
+
+        Ok(Some(PInvokeImport {
+            library: image.read_string(module_ref.name)?.to_owned(),
+            entry_point: image.read_string(impl_map.import_name)?.to_owned(),
+            attributes: impl_map.mapping_flags,
+            calling_convention,
+            return_type,
+            param_types,
+        }))
+    }
+
+    /// Finds a field by name in a type or its base types.
+    pub fn find_field(&mut self, owner: TypeId, name: &str) -> Result<Option<FieldId>, Error> {
+        let mut current = Some(owner);
+        while let Some(id) = current {
+            self.prepare(id)?;
+            let ty = self.get(id);
+            if let Some(field) = ty.fields.iter().cloned().find(|&field| self.field(field).name == name) {
+                return Ok(Some(field));
+            }
+            current = ty.base;
+        }
+        Ok(None)
+    }
+
+    /// Gets the method an assembly's CLI header names as its entry point.
+    pub fn entry_point(&mut self, assembly: AssemblyId) -> Result<Option<MethodId>, Error> {
+        match self.image(assembly).cli_header().entry_point_token {
+            0 => Ok(None),
+            token => self.resolve_method_token(assembly, token, &GenericContext::default()).map(Some),
+        }
+    }
+
+    /// Returns `true` if `from` is `to` or derives from it.
+    pub fn is_subclass_of(&self, from: TypeId, to: TypeId) -> bool {
+        let mut current = Some(from);
+        while let Some(id) = current {
+            if id == to {
+                return true;
+            }
+            current = self.get(id).base;
+        }
+        false
+    }
+}
+
+/// Returns `true` if the image defines `System.Object`, as the root of the type hierarchy.
+fn defines_root_object(image: &MetadataImage<Mmap>) -> bool {
+    image.find_type_def("System", "Object")
+        .and_then(|handle| image.table::<tables::TypeDefDecoder>().get(handle.index()).ok())
+        .is_some_and(|object| object.extends.index() == 0)
+}
+
+/// Finds the underlying types of the enums a custom attribute of an assembly uses, among the types that are loaded.
+struct AttributeEnums<'a> {
+    types: &'a TypeSystem,
+    assembly: AssemblyId,
+}
+
+impl<'a> AttributeEnums<'a> {
+    fn underlying_type(&self, id: TypeId) -> Option<TypeReference> {
+        Some(match self.types.get(id).kind {
+            TypeKind::Enum(Primitive::Boolean) => TypeReference::Boolean,
+            TypeKind::Enum(Primitive::Char) => TypeReference::Char,
+            TypeKind::Enum(Primitive::I1) => TypeReference::I1,
+            TypeKind::Enum(Primitive::U1) => TypeReference::U1,
+            TypeKind::Enum(Primitive::I2) => TypeReference::I2,
+            TypeKind::Enum(Primitive::U2) => TypeReference::U2,
+            TypeKind::Enum(Primitive::I4) => TypeReference::I4,
+            TypeKind::Enum(Primitive::U4) => TypeReference::U4,
+            TypeKind::Enum(Primitive::I8) => TypeReference::I8,
+            TypeKind::Enum(Primitive::U8) => TypeReference::U8,
+            _ => return None,
+        })
+    }
+}
+
+impl<'a> EnumResolver for AttributeEnums<'a> {
+    fn resolve_enum(&self, enum_type: TableHandle) -> Option<TypeReference> {
+        let key = (self.assembly, enum_type.index());
+        let id = match enum_type.table() {
+            TableIndex::TypeDef => self.types.type_defs.get(&key),
+            TableIndex::TypeRef => self.types.type_refs.get(&key),
+            _ => None,
+        };
+        id.and_then(|&id| self.underlying_type(id))
+    }
+
+    fn resolve_enum_by_name(&self, name: &str) -> Option<TypeReference> {
+        let full_name = name.split(',').next().unwrap_or_default().trim().replace('+', ".");
+        (0..self.types.types.len())
+            .map(|index| TypeId(index as u32))
+            .find(|&id| self.underlying_type(id).is_some() && self.types.get(id).to_string() == full_name)
+            .and_then(|id| self.underlying_type(id))
+    }
+}
+
+fn bad_token(token: u32) -> Error {
+    Error::InvalidProgram(format!("invalid token 0x{:08X}", token))
+}
+
+fn align(offset: u32, alignment: u32) -> u32 {
+    offset.div_ceil(alignment) * alignment
+}
+
+#[cfg(test)]
+mod tests {
+    use ecma355metadata::cli::il::Opcode;
+
+    use error::Error;
+    use test_assembly::*;
+    use types::{Primitive, TypeKind};
+
+    #[test]
+    pub fn generic_types_are_instantiated_with_their_own_fields_and_statics() {
+        // class Box<T> { static int count; T value; Box(T value) { this.value = value; } T Get() { return value; } }
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+        let generic_box = app.type_def(PUBLIC, "", "Box`1", object);
+        app.generic_param(generic_box, 0, 0, "T");
+        let box_of_t = app.type_spec(Ty::generic_inst(Ty::Class(generic_box), vec![Ty::Var(0)]));
+        app.field(STATIC_FIELD, "count", Ty::I4);
+        let value = app.field(0, "value", Ty::Var(0));
+        let value_of_t = app.member_ref(box_of_t, "value", &field_sig(Ty::Var(0)));
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64);
+        il.op(Opcode::Ldarg0).op(Opcode::Ldarg1).arg(Opcode::Stfld, value_of_t as i64).op(Opcode::Ret);
+        app.method(CONSTRUCTOR, ".ctor", &method_sig(true, Ty::Void, &[Ty::Var(0)]), Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Ldfld, value as i64).op(Opcode::Ret);
+        app.method(0, "Get", &method_sig(true, Ty::Var(0), &[]), Some(Body::new(vec![], il)));
+
+        // Box<int>.count = 1; Box<long>.count = 5;
+        // return new Box<int>(40).Get() + (int)new Box<long>(2).Get() + Box<int>.count * 100;
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut members = Vec::new();
+        for arg in [Ty::I4, Ty::I8].iter().cloned() {
+            let instantiation = app.type_spec(Ty::generic_inst(Ty::Class(generic_box), vec![arg]));
+            members.push((
+                app.member_ref(instantiation, ".ctor", &method_sig(true, Ty::Void, &[Ty::Var(0)])),
+                app.member_ref(instantiation, "Get", &method_sig(true, Ty::Var(0), &[])),
+                app.member_ref(instantiation, "count", &field_sig(Ty::I4)),
+            ));
+        }
+        let mut il = Il::new();
+        il.op(Opcode::LdcI41).arg(Opcode::Stsfld, members[0].2 as i64);
+        il.op(Opcode::LdcI45).arg(Opcode::Stsfld, members[1].2 as i64);
+        il.ldc_i4(40).arg(Opcode::Newobj, members[0].0 as i64).arg(Opcode::Call, members[0].1 as i64);
+        il.op(Opcode::LdcI42).op(Opcode::ConvI8).arg(Opcode::Newobj, members[1].0 as i64);
+        il.arg(Opcode::Call, members[1].1 as i64).op(Opcode::ConvI4).op(Opcode::Add);
+        il.arg(Opcode::Ldsfld, members[0].2 as i64).ldc_i4(100).op(Opcode::Mul).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        assert_eq!(Ok(142), run("generic_types", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn generic_methods_are_instantiated_and_dispatched_virtually() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+
+        // class Counter { Counter() { } virtual int Count<T>(T item) { return 1; } }
+        let counter = app.type_def(PUBLIC, "", "Counter", object);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64).op(Opcode::Ret);
+        let counter_ctor =
+            app.method(CONSTRUCTOR, ".ctor", &method_sig(true, Ty::Void, &[]), Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.op(Opcode::LdcI41).op(Opcode::Ret);
+        let count_signature = generic_method_sig(true, 1, Ty::I4, &[Ty::MVar(0)]);
+        let count = app.method(VIRTUAL | NEW_SLOT, "Count", &count_signature, Some(Body::new(vec![], il)));
+        app.generic_param(count, 0, 0, "T");
+
+        // class TenCounter : Counter { TenCounter() { } override int Count<T>(T item) { return 10; } }
+        app.type_def(PUBLIC, "", "TenCounter", counter);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, counter_ctor as i64).op(Opcode::Ret);
+        let ten_counter_ctor =
+            app.method(CONSTRUCTOR, ".ctor", &method_sig(true, Ty::Void, &[]), Some(Body::new(vec![], il)));
+        let mut il = Il::new();
+        il.ldc_i4(10).op(Opcode::Ret);
+        let ten_count = app.method(VIRTUAL, "Count", &count_signature, Some(Body::new(vec![], il)));
+        app.generic_param(ten_count, 0, 0, "T");
+
+        // static T Id<T>(T x) { return x; }
+        // static T Forward<T>(T x) { T local = Id<T>(x); return local; }
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).op(Opcode::Ret);
+        let signature = generic_method_sig(false, 1, Ty::MVar(0), &[Ty::MVar(0)]);
+        let id = app.method(STATIC, "Id", &signature, Some(Body::new(vec![], il)));
+        app.generic_param(id, 0, 0, "T");
+        let id_of_t = app.method_spec(id, &[Ty::MVar(0)]);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, id_of_t as i64).op(Opcode::Stloc0).op(Opcode::Ldloc0).op(Opcode::Ret);
+        let forward = app.method(STATIC, "Forward", &signature, Some(Body::new(vec![Ty::MVar(0)], il)));
+        app.generic_param(forward, 0, 0, "T");
+
+        // return (int)Forward<long>(40) + Forward<int>(2) + ((Counter)new TenCounter()).Count<int>(0);
+        let forward_long = app.method_spec(forward, &[Ty::I8]);
+        let forward_int = app.method_spec(forward, &[Ty::I4]);
+        let count_int = app.method_spec(count, &[Ty::I4]);
+        let mut il = Il::new();
+        il.ldc_i4(40).op(Opcode::ConvI8).arg(Opcode::Call, forward_long as i64).op(Opcode::ConvI4);
+        il.op(Opcode::LdcI42).arg(Opcode::Call, forward_int as i64).op(Opcode::Add);
+        il.arg(Opcode::Newobj, ten_counter_ctor as i64).op(Opcode::LdcI40).arg(Opcode::Callvirt, count_int as i64);
+        il.op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        assert_eq!(Ok(52), run("generic_methods", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn generic_arguments_must_satisfy_constraints() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+        let int32 = app.corlib_type("System", "Int32");
+
+        // interface IShape { } class Square : IShape { public Square() { } }
+        let shape = app.type_def(PUBLIC | INTERFACE | ABSTRACT_CLASS, "", "IShape", 0);
+        let square = app.type_def(PUBLIC, "", "Square", object);
+        app.interface_impl(square, shape);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64).op(Opcode::Ret);
+        app.method(CONSTRUCTOR | PUBLIC_METHOD, ".ctor", &method_sig(true, Ty::Void, &[]), Some(Body::new(vec![], il)));
+
+        // class Holder<T> where T : class, IShape, new() { public static int value; }
+        let holder = app.type_def(PUBLIC, "", "Holder`1", object);
+        let param = app.generic_param(holder, 0, REFERENCE_TYPE | DEFAULT_CONSTRUCTOR, "T");
+        app.generic_param_constraint(param, shape);
+        app.field(STATIC_FIELD, "value", Ty::I4);
+
+        // class ValueHolder<T> where T : struct { public static int value; }
+        let value_holder = app.type_def(PUBLIC, "", "ValueHolder`1", object);
+        app.generic_param(value_holder, 0, VALUE_TYPE, "T");
+        app.field(STATIC_FIELD, "value", Ty::I4);
+
+        // class Program { static int Main() { return Holder<T>.value + ValueHolder<U>.value; } }
+        let program = app.type_def(PUBLIC, "", "Program", object);
+        let main = |app: &mut AssemblyBuilder, shape_arg: Ty, value_arg: Ty| {
+            let holder = app.type_spec(Ty::generic_inst(Ty::Class(holder), vec![shape_arg]));
+            let holder_value = app.member_ref(holder, "value", &field_sig(Ty::I4));
+            let value_holder = app.type_spec(Ty::generic_inst(Ty::Class(value_holder), vec![value_arg]));
+            let value_holder_value = app.member_ref(value_holder, "value", &field_sig(Ty::I4));
+            let mut il = Il::new();
+            il.arg(Opcode::Ldsfld, holder_value as i64).arg(Opcode::Ldsfld, value_holder_value as i64);
+            il.op(Opcode::Add).op(Opcode::Ret);
+            Body::new(vec![], il)
+        };
+
+        let mut satisfied = AssemblyBuilder::new("App");
+        ::std::mem::swap(&mut satisfied, &mut app);
+        let body = main(&mut satisfied, Ty::Class(square), Ty::ValueType(int32));
+        add_main(&mut satisfied, body);
+        assert_eq!(Ok(0), run("satisfied_constraints", &corlib(), &satisfied));
+
+        let mut violated = satisfied;
+        let body = main(&mut violated, Ty::Class(program), Ty::ValueType(int32));
+        add_main(&mut violated, body);
+        let message = "GenericArguments[0], 'Program', on 'Holder`1' violates the constraint of type parameter 'T'";
+        assert_eq!(Err(Error::TypeLoad(message.into())), run("violated_constraints", &corlib(), &violated));
+    }
+
+    #[test]
+    pub fn enums_are_stored_as_their_underlying_type() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let system_enum = app.corlib_type("System", "Enum");
+        let byte = app.corlib_type("System", "Byte");
+        let flags_attribute = app.corlib_type("System", "FlagsAttribute");
+        let flags_ctor = app.member_ref(flags_attribute, ".ctor", &method_sig(true, Ty::Void, &[]));
+
+        // enum Color : byte { Red = 1, Green = 2 }
+        let color = app.type_def(PUBLIC | SEALED, "", "Color", system_enum);
+        app.field(0, "value__", Ty::U1);
+        for &(name, value) in &[("Red", 1), ("Green", 2)] {
+            let field = app.field(LITERAL_FIELD, name, Ty::ValueType(color));
+            app.constant(field, Ty::U1, value);
+        }
+
+        // [Flags] enum Access { None = 0, Read = 1, Write = 2, ReadWrite = 3, Execute = 4 }
+        let access = app.type_def(PUBLIC | SEALED, "", "Access", system_enum);
+        app.custom_attribute(access, flags_ctor);
+        app.field(0, "value__", Ty::I4);
+        for &(name, value) in &[("None", 0), ("Read", 1), ("Write", 2), ("ReadWrite", 3), ("Execute", 4)] {
+            let field = app.field(LITERAL_FIELD, name, Ty::ValueType(access));
+            app.constant(field, Ty::I4, value);
+        }
+
+        // Color c = (Color)300, which is truncated to 44;
+        // return (byte)(object)c + (Color)(object)(byte)c + 1000 * sizeof(Color) + 10000 * ((object)c is Enum)
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.ldc_i4(300).op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc0).arg(Opcode::Box, color as i64).arg(Opcode::UnboxAny, byte as i64);
+        il.op(Opcode::Ldloc0).arg(Opcode::Box, byte as i64).arg(Opcode::UnboxAny, color as i64).op(Opcode::Add);
+        il.arg(Opcode::Sizeof, color as i64).ldc_i4(1000).op(Opcode::Mul).op(Opcode::Add);
+        il.op(Opcode::Ldloc0).arg(Opcode::Box, color as i64).arg(Opcode::Isinst, system_enum as i64);
+        il.op(Opcode::Ldnull).op(Opcode::CgtUn).ldc_i4(10000).op(Opcode::Mul).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::ValueType(color)], il));
+
+        let (_directory, mut runtime) = runtime("enums", &corlib(), &app);
+        assert_eq!(Ok(11_088), runtime.run_main("App"));
+
+        let types = runtime.types();
+        let assembly = types.load_assembly("App").unwrap();
+        let color = types.type_def(assembly, (color & 0x00FF_FFFF) as usize).unwrap();
+        let access = types.type_def(assembly, (access & 0x00FF_FFFF) as usize).unwrap();
+        assert_eq!(TypeKind::Enum(Primitive::U1), types.get(color).kind);
+        assert_eq!(TypeKind::Enum(Primitive::I4), types.get(access).kind);
+        assert_eq!(vec![("Red".to_string(), 1), ("Green".to_string(), 2)], types.enum_literals(color).unwrap());
+
+        let mut name = |ty, value| types.enum_name(ty, value).unwrap();
+        assert_eq!(Some("Green".to_string()), name(color, 2));
+        assert_eq!(None, name(color, 3));
+        assert_eq!(Some("None".to_string()), name(access, 0));
+        assert_eq!(Some("ReadWrite".to_string()), name(access, 3));
+        assert_eq!(Some("Read, Execute".to_string()), name(access, 5));
+        assert_eq!(Some("ReadWrite, Execute".to_string()), name(access, 7));
+        assert_eq!(None, name(access, 8));
+    }
+
+    /// Defines a public constructor for the last type defined, which calls a base type constructor.
+    fn constructor(app: &mut AssemblyBuilder, base_ctor: u32) -> u32 {
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, base_ctor as i64).op(Opcode::Ret);
+        app.method(CONSTRUCTOR | PUBLIC_METHOD, ".ctor", &method_sig(true, Ty::Void, &[]), Some(Body::new(vec![], il)))
+    }
+
+    fn returning(value: i32) -> Option<Body> {
+        let mut il = Il::new();
+        il.ldc_i4(value).op(Opcode::Ret);
+        Some(Body::new(vec![], il))
+    }
+
+    #[test]
+    pub fn interface_calls_dispatch_to_implicit_explicit_and_default_implementations() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+        let get_hash_code = app.member_ref(object, "GetHashCode", &method_sig(true, Ty::I4, &[]));
+        let int_sig = method_sig(true, Ty::I4, &[]);
+        let explicit = PRIVATE_METHOD | VIRTUAL | FINAL | NEW_SLOT;
+
+        // interface IAnimal { int Legs(); int Sound() { return 7; } }
+        let animal = app.type_def(PUBLIC | INTERFACE | ABSTRACT_CLASS, "", "IAnimal", 0);
+        let legs = app.method(ABSTRACT | VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Legs", &int_sig, None);
+        let sound = app.method(VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Sound", &int_sig, returning(7));
+
+        // interface IPet : IAnimal { int IAnimal.Sound() { return 8; } }
+        let pet = app.type_def(PUBLIC | INTERFACE | ABSTRACT_CLASS, "", "IPet", 0);
+        app.interface_impl(pet, animal);
+        let pet_sound = app.method(explicit, "IAnimal.Sound", &int_sig, returning(8));
+        app.method_impl(pet, pet_sound, sound);
+
+        // class Dog : IAnimal { public virtual int Legs() { return 4; } }
+        let dog = app.type_def(PUBLIC, "", "Dog", object);
+        app.interface_impl(dog, animal);
+        let dog_ctor = constructor(&mut app, object_ctor);
+        app.method(VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Legs", &int_sig, returning(4));
+
+        // class Puppy : Dog, IPet { }
+        let puppy = app.type_def(PUBLIC, "", "Puppy", dog);
+        app.interface_impl(puppy, pet);
+        let puppy_ctor = constructor(&mut app, dog_ctor);
+
+        // class Cat : IAnimal {
+        //     int IAnimal.Legs() { return 3; } public int Sound() { return 9; }
+        //     int Hash() { return 5; } // .override object::GetHashCode
+        // }
+        let cat = app.type_def(PUBLIC, "", "Cat", object);
+        app.interface_impl(cat, animal);
+        let cat_ctor = constructor(&mut app, object_ctor);
+        let cat_legs = app.method(explicit, "IAnimal.Legs", &int_sig, returning(3));
+        app.method_impl(cat, cat_legs, legs);
+        app.method(VIRTUAL | NEW_SLOT | FINAL | PUBLIC_METHOD, "Sound", &int_sig, returning(9));
+        let hash = app.method(VIRTUAL | PUBLIC_METHOD, "Hash", &int_sig, returning(5));
+        app.method_impl(cat, hash, get_hash_code);
+
+        // static int Describe(IAnimal animal) { return animal.Legs() * 10 + animal.Sound(); }
+        app.type_def(PUBLIC, "", "Program", object);
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Callvirt, legs as i64).ldc_i4(10).op(Opcode::Mul);
+        il.op(Opcode::Ldarg0).arg(Opcode::Callvirt, sound as i64).op(Opcode::Add).op(Opcode::Ret);
+        let describe_sig = method_sig(false, Ty::I4, &[Ty::Class(animal)]);
+        let describe = app.method(STATIC, "Describe", &describe_sig, Some(Body::new(vec![], il)));
+
+        // return ((Describe(new Dog()) * 100 + Describe(new Puppy())) * 100 + Describe(new Cat())) * 10
+        //     + new Cat().GetHashCode();
+        let mut il = Il::new();
+        il.arg(Opcode::Newobj, dog_ctor as i64).arg(Opcode::Call, describe as i64);
+        il.ldc_i4(100).op(Opcode::Mul).arg(Opcode::Newobj, puppy_ctor as i64).arg(Opcode::Call, describe as i64);
+        il.op(Opcode::Add).ldc_i4(100).op(Opcode::Mul);
+        il.arg(Opcode::Newobj, cat_ctor as i64).arg(Opcode::Call, describe as i64).op(Opcode::Add);
+        il.ldc_i4(10).op(Opcode::Mul).arg(Opcode::Newobj, cat_ctor as i64);
+        il.arg(Opcode::Callvirt, get_hash_code as i64).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        assert_eq!(Ok(4_748_395), run("interface_dispatch", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn variant_interfaces_are_cast_to_and_called_through() {
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+
+        // interface IProducer<out T> { T Get(); } interface IConsumer<in T> { int Take(T item); }
+        let producer = app.type_def(PUBLIC | INTERFACE | ABSTRACT_CLASS, "", "IProducer`1", 0);
+        app.generic_param(producer, 0, COVARIANT, "T");
+        app.method(ABSTRACT | VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Get", &method_sig(true, Ty::Var(0), &[]), None);
+        let consumer = app.type_def(PUBLIC | INTERFACE | ABSTRACT_CLASS, "", "IConsumer`1", 0);
+        app.generic_param(consumer, 0, CONTRAVARIANT, "T");
+        let take_sig = method_sig(true, Ty::I4, &[Ty::Var(0)]);
+        app.method(ABSTRACT | VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Take", &take_sig, None);
+        let instance = |app: &mut AssemblyBuilder, interface, arg| {
+            app.type_spec(Ty::generic_inst(Ty::Class(interface), vec![arg]))
+        };
+        let producer_of = |app: &mut AssemblyBuilder, arg| instance(app, producer, arg);
+        let consumer_of = |app: &mut AssemblyBuilder, arg| instance(app, consumer, arg);
+
+        // class Names : IProducer<string> { public virtual string Get() { return "name"; } }
+        let names = app.type_def(PUBLIC, "", "Names", object);
+        let producer_of_string = producer_of(&mut app, Ty::String);
+        app.interface_impl(names, producer_of_string);
+        let names_ctor = constructor(&mut app, object_ctor);
+        let name = app.user_string("name");
+        let mut il = Il::new();
+        il.arg(Opcode::Ldstr, name as i64).op(Opcode::Ret);
+        let get_sig = method_sig(true, Ty::String, &[]);
+        app.method(VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Get", &get_sig, Some(Body::new(vec![], il)));
+
+        // class Counts : IProducer<int> { public virtual int Get() { return 1; } }
+        let counts = app.type_def(PUBLIC, "", "Counts", object);
+        let producer_of_int = producer_of(&mut app, Ty::I4);
+        app.interface_impl(counts, producer_of_int);
+        let counts_ctor = constructor(&mut app, object_ctor);
+        app.method(VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Get", &method_sig(true, Ty::I4, &[]), returning(1));
+
+        // class Sink : IConsumer<object> { public virtual int Take(object item) { return 2; } }
+        let sink = app.type_def(PUBLIC, "", "Sink", object);
+        let consumer_of_object = consumer_of(&mut app, Ty::Object);
+        app.interface_impl(sink, consumer_of_object);
+        let sink_ctor = constructor(&mut app, object_ctor);
+        app.method(VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Take", &method_sig(true, Ty::I4, &[Ty::Object]), returning(2));
+
+        // return (((IProducer<object>)new Names()).Get() != null) * 1000 + (new Counts() is IProducer<object>) * 100
+        //     + ((IConsumer<string>)new Sink()).Take(null) * 10 + (new Names() is IConsumer<string>)
+        app.type_def(PUBLIC, "", "Program", object);
+        let producer_of_object = producer_of(&mut app, Ty::Object);
+        let get = app.member_ref(producer_of_object, "Get", &method_sig(true, Ty::Var(0), &[]));
+        let consumer_of_string = consumer_of(&mut app, Ty::String);
+        let take = app.member_ref(consumer_of_string, "Take", &take_sig);
+        let mut il = Il::new();
+        il.arg(Opcode::Newobj, names_ctor as i64).arg(Opcode::Castclass, producer_of_object as i64);
+        il.arg(Opcode::Callvirt, get as i64).op(Opcode::Ldnull).op(Opcode::CgtUn).ldc_i4(1000).op(Opcode::Mul);
+        il.arg(Opcode::Newobj, counts_ctor as i64).arg(Opcode::Isinst, producer_of_object as i64);
+        il.op(Opcode::Ldnull).op(Opcode::CgtUn).ldc_i4(100).op(Opcode::Mul).op(Opcode::Add);
+        il.arg(Opcode::Newobj, sink_ctor as i64).arg(Opcode::Castclass, consumer_of_string as i64);
+        il.op(Opcode::Ldnull).arg(Opcode::Callvirt, take as i64).ldc_i4(10).op(Opcode::Mul).op(Opcode::Add);
+        il.arg(Opcode::Newobj, names_ctor as i64).arg(Opcode::Isinst, consumer_of_string as i64);
+        il.op(Opcode::Ldnull).op(Opcode::CgtUn).op(Opcode::Add).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![], il));
+
+        assert_eq!(Ok(1020), run("variance", &corlib(), &app));
+    }
+
+    #[test]
+    pub fn classes_must_have_one_implementation_of_each_interface_method() {
+        let int_sig = method_sig(true, Ty::I4, &[]);
+        let new_app = || {
+            let mut app = AssemblyBuilder::new("App");
+            let object = app.corlib_type("System", "Object");
+            let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+            (app, object, object_ctor)
+        };
+        let main = |app: &mut AssemblyBuilder, object: u32, ctor: u32| {
+            app.type_def(PUBLIC, "", "Program", object);
+            let mut il = Il::new();
+            il.arg(Opcode::Newobj, ctor as i64).op(Opcode::Pop).op(Opcode::LdcI40).op(Opcode::Ret);
+            add_main(app, Body::new(vec![], il));
+        };
+
+        // interface IShape { int Sides(); } class Blob : IShape { }
+        let (mut app, object, object_ctor) = new_app();
+        let shape = app.type_def(PUBLIC | INTERFACE | ABSTRACT_CLASS, "", "IShape", 0);
+        app.method(ABSTRACT | VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Sides", &int_sig, None);
+        let blob = app.type_def(PUBLIC, "", "Blob", object);
+        app.interface_impl(blob, shape);
+        let blob_ctor = constructor(&mut app, object_ctor);
+        main(&mut app, object, blob_ctor);
+        let message = "Method 'Sides' in type 'Blob' does not have an implementation.";
+        assert_eq!(Err(Error::TypeLoad(message.into())), run("unimplemented", &corlib(), &app));
+
+        // interface IBase { int Id() { return 1; } } interface ILeft : IBase { int IBase.Id() { return 2; } }
+        // interface IRight : IBase { int IBase.Id() { return 3; } } class Both : ILeft, IRight { }
+        let (mut app, object, object_ctor) = new_app();
+        let base = app.type_def(PUBLIC | INTERFACE | ABSTRACT_CLASS, "", "IBase", 0);
+        let id = app.method(VIRTUAL | NEW_SLOT | PUBLIC_METHOD, "Id", &int_sig, returning(1));
+        let mut sides = Vec::new();
+        for &(name, value) in &[("ILeft", 2), ("IRight", 3)] {
+            let side = app.type_def(PUBLIC | INTERFACE | ABSTRACT_CLASS, "", name, 0);
+            app.interface_impl(side, base);
+            let explicit = PRIVATE_METHOD | VIRTUAL | FINAL | NEW_SLOT;
+            let side_id = app.method(explicit, "IBase.Id", &int_sig, returning(value));
+            app.method_impl(side, side_id, id);
+            sides.push(side);
+        }
+        let both = app.type_def(PUBLIC, "", "Both", object);
+        for &side in &sides {
+            app.interface_impl(both, side);
+        }
+        let both_ctor = constructor(&mut app, object_ctor);
+        main(&mut app, object, both_ctor);
+        let message = "'Both' has no most specific implementation of 'IBase::Id'.";
+        assert_eq!(Err(Error::TypeLoad(message.into())), run("ambiguous", &corlib(), &app));
+    }
+}
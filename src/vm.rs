@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use slog;
+
+use error::{Error, ExceptionKind};
+use gc::{Heap, ObjectLayout, ObjectRef, STRING_CHARS};
+use internal_calls::{InternalCall, InternalCalls};
+#[cfg(feature = "jit")]
+use interpreter::Jit;
+use interpreter::{CachedDispatch, Frame, Member, MethodCode, PInvokeTarget, Thrown, TypeInit};
+use native::NativeLibraries;
+use threads::{ManagedThread, Scheduler, SyncBlock};
+use types::{FieldId, MethodId, TypeId, TypeKind, TypeSystem};
+
+/// The state of the runtime while it executes managed code: the types it has loaded, the managed heap, static
+/// fields and the interpreter's frames.
+///
+/// Every managed thread uses the same `Vm`, but only the one holding the scheduler's lock touches it. The frames,
+/// exceptions, handles, last error and thread-static storage here belong to that thread.
+pub struct Vm {
+    pub types: TypeSystem,
+    pub heap: Heap,
+
+    /// The storage for each type's static fields, indexed by `TypeId`. The storage never moves, so static fields
+    /// can be referred to by address.
+    statics: Vec<Option<Box<[u64]>>>,
+
+    /// How far each type's initializer has got, for the types whose initialization has started.
+    pub(crate) type_inits: HashMap<TypeId, TypeInit>,
+    codes: HashMap<MethodId, Rc<MethodCode>>,
+
+    /// The functions internal calls can be bound to, and the function each internal call that has been called is
+    /// bound to.
+    pub(crate) internal_calls: InternalCalls,
+    pub(crate) bound_calls: HashMap<MethodId, InternalCall>,
+
+    /// The method each virtual call site called last, by the method the call site is in and the index of the
+    /// instruction after it.
+    pub(crate) dispatch_cache: HashMap<(MethodId, usize), CachedDispatch>,
+
+    /// The native libraries P/Invoke methods have loaded, how each P/Invoke that has been called calls its native
+    /// function, and the error code the last P/Invoke that sets it got from its function.
+    pub(crate) libraries: NativeLibraries,
+    pub(crate) pinvokes: HashMap<MethodId, Rc<PInvokeTarget>>,
+    pub(crate) last_error: i32,
+
+    /// The function pointers `ldftn` and `ldvirtftn` have loaded, and the method at each one. A pointer is the
+    /// address of a box holding its method, so it is never the address of a native function.
+    pub(crate) method_pointers: HashMap<MethodId, Box<MethodId>>,
+    pub(crate) pointer_methods: HashMap<usize, MethodId>,
+
+    /// The strings `ldstr` has loaded, which are shared by every assembly in the app context. They are never freed.
+    interned: HashMap<Vec<u16>, ObjectRef>,
+
+    /// The objects reflection has created to reflect types, methods and fields, one for each. They are never freed.
+    pub(crate) reflection_objects: HashMap<Member, ObjectRef>,
+
+    /// Objects the runtime holds while it allocates, when they can't be kept on a frame's stack.
+    pub(crate) handles: Vec<ObjectRef>,
+    pub(crate) frames: Vec<Frame>,
+
+    /// The exceptions whose handlers are being looked for, innermost last.
+    pub(crate) thrown: Vec<Thrown>,
+
+    /// An exception that wasn't handled by the frames the interpreter was last run for, for the caller to throw on
+    /// to its own frames.
+    pub(crate) unhandled: Option<Thrown>,
+
+    /// The storage for the `[ThreadStatic]` fields of each type, indexed by `TypeId`, and whether each static field
+    /// that has been accessed is one.
+    pub(crate) thread_statics: Vec<Option<Box<[u64]>>>,
+    pub(crate) thread_static_fields: HashMap<FieldId, bool>,
+
+    /// The lock managed threads take turns to hold, which is shared with the OS threads running them.
+    pub(crate) scheduler: Arc<Scheduler>,
+
+    /// Every managed thread that has been started, by index, starting with the thread that runs the entry point,
+    /// and the index of the one holding the lock.
+    pub(crate) threads: Vec<ManagedThread>,
+    pub(crate) current_thread: usize,
+
+    /// How many safepoints the current thread has passed since its turn began.
+    pub(crate) safepoints: u32,
+
+    /// The ids given to `Thread` objects so far, and what went wrong on the first thread to fail other than the
+    /// main thread, which the entry point fails with when it returns.
+    pub(crate) managed_thread_ids: i32,
+    pub(crate) thread_failure: Option<Error>,
+
+    /// The monitors of objects that have been locked. They are never freed.
+    pub(crate) sync_blocks: Vec<SyncBlock>,
+
+    /// The methods compiled to native code, and how often the others have been called.
+    #[cfg(feature = "jit")]
+    pub(crate) jit: Jit,
+    pub(crate) logger: slog::Logger,
+}
+
+impl Vm {
+    pub fn new(types: TypeSystem, logger: slog::Logger) -> Vm {
+        let libraries = NativeLibraries::new(types.app_context().base_directory().into());
+        Vm {
+            types,
+            heap: Heap::new(),
+            statics: Vec::new(),
+            type_inits: HashMap::new(),
+            codes: HashMap::new(),
+            internal_calls: InternalCalls::new(),
+            bound_calls: HashMap::new(),
+            dispatch_cache: HashMap::new(),
+            libraries,
+            pinvokes: HashMap::new(),
+            last_error: 0,
+            method_pointers: HashMap::new(),
+            pointer_methods: HashMap::new(),
+            interned: HashMap::new(),
+            reflection_objects: HashMap::new(),
+            handles: Vec::new(),
+            frames: Vec::new(),
+            thrown: Vec::new(),
+            unhandled: None,
+            thread_statics: Vec::new(),
+            thread_static_fields: HashMap::new(),
+            scheduler: Arc::new(Scheduler::new()),
+            threads: vec![ManagedThread::new(ObjectRef::NULL)],
+            current_thread: 0,
+            safepoints: 0,
+            managed_thread_ids: 0,
+            thread_failure: None,
+            sync_blocks: Vec::new(),
+            #[cfg(feature = "jit")]
+            jit: Jit::new(),
+            logger,
+        }
+    }
+
+    /// Gets the decoded IL of a method, decoding it the first time it is needed.
+    pub fn method_code(&mut self, method: MethodId) -> Result<Rc<MethodCode>, Error> {
+        if let Some(code) = self.codes.get(&method) {
+            return Ok(code.clone());
+        }
+        let code = Rc::new(MethodCode::load(&mut self.types, method)?);
+        self.codes.insert(method, code.clone());
+        Ok(code)
+    }
+
+    pub fn object_type(&self, object: ObjectRef) -> TypeId {
+        unsafe { object.type_id() }
+    }
+
+    /// Allocates an instance of a class, or a boxed value type, with its fields zeroed.
+    pub fn new_object(&mut self, ty: TypeId) -> Result<ObjectRef, Error> {
+        self.ensure_layout(ty)?;
+        let size = match *self.heap.layout(ty).unwrap() {
+            ObjectLayout::Fixed { size, .. } => ObjectLayout::fixed_size(size),
+            _ => return Err(Error::InvalidProgram(format!("{} has no fixed size", self.types.get(ty)))),
+        };
+        Ok(self.allocate(ty, size))
+    }
+
+    /// Allocates an SZ array with zeroed elements.
+    pub fn new_array(&mut self, array_type: TypeId, length: usize) -> Result<ObjectRef, Error> {
+        self.ensure_layout(array_type)?;
+        let size = match *self.heap.layout(array_type).unwrap() {
+            ObjectLayout::Array { element_size, .. } => ObjectLayout::array_size(element_size, length),
+            _ => return Err(Error::InvalidProgram(format!("{} is not an array", self.types.get(array_type)))),
+        };
+        let array = self.allocate(array_type, size);
+        unsafe { *(array.data() as *mut usize) = length };
+        Ok(array)
+    }
+
+    /// Allocates an array with a rank, with zeroed elements, given the lower bound and length of each dimension.
+    pub fn new_md_array(&mut self, array_type: TypeId, bounds: &[(i32, i32)]) -> Result<ObjectRef, Error> {
+        self.ensure_layout(array_type)?;
+        let (rank, element_size) = match *self.heap.layout(array_type).unwrap() {
+            ObjectLayout::MdArray { rank, element_size, .. } => (rank, element_size),
+            _ => return Err(Error::InvalidProgram(format!("{} has no rank", self.types.get(array_type)))),
+        };
+        if bounds.len() != rank as usize {
+            return Err(Error::InvalidProgram(format!("{} needs {} bounds", self.types.get(array_type), rank)));
+        }
+        let mut length = 1usize;
+        for &(_, dimension) in bounds {
+            if dimension < 0 {
+                return Err(ExceptionKind::Overflow.into());
+            }
+            length = length.checked_mul(dimension as usize).ok_or(ExceptionKind::Overflow)?;
+        }
+        let array = self.allocate(array_type, ObjectLayout::md_array_size(rank, element_size, length));
+        unsafe {
+            *(array.data() as *mut usize) = length;
+            let dimensions = array.md_array_bounds();
+            for (index, &(lower_bound, dimension)) in bounds.iter().enumerate() {
+                *dimensions.add(index) = dimension;
+                *dimensions.add(rank as usize + index) = lower_bound;
+            }
+        }
+        Ok(array)
+    }
+
+    pub fn new_string(&mut self, chars: &[u16]) -> Result<ObjectRef, Error> {
+        let string = self.types.string()?;
+        self.ensure_layout(string)?;
+        let object = self.allocate(string, ObjectLayout::string_size(chars.len()));
+        unsafe {
+            *(object.data() as *mut u32) = chars.len() as u32;
+            let data = object.data().add(STRING_CHARS) as *mut u16;
+            ::std::ptr::copy_nonoverlapping(chars.as_ptr(), data, chars.len());
+        }
+        Ok(object)
+    }
+
+    /// Gets the string with some characters that is shared by every `ldstr` that loads them, allocating it the first
+    /// time.
+    pub fn intern(&mut self, chars: &[u16]) -> Result<ObjectRef, Error> {
+        if let Some(&string) = self.interned.get(chars) {
+            return Ok(string);
+        }
+        let string = self.new_string(chars)?;
+        self.interned.insert(chars.to_vec(), string);
+        Ok(string)
+    }
+
+    /// Allocates an object, collecting garbage first if enough has been allocated since the last collection.
+    ///
+    /// Any object references the caller holds that aren't in a frame, static field or handle must not be used after
+    /// this, since the objects may have been freed.
+    fn allocate(&mut self, ty: TypeId, size: usize) -> ObjectRef {
+        if self.heap.should_collect(size) {
+            self.collect();
+        }
+        self.heap.allocate(ty, size)
+    }
+
+    /// Records the layout of a type's instances with the heap, the first time one is allocated.
+    fn ensure_layout(&mut self, ty: TypeId) -> Result<(), Error> {
+        if self.heap.layout(ty).is_some() {
+            return Ok(());
+        }
+        self.types.prepare(ty)?;
+        let layout = match self.types.get(ty).kind {
+            TypeKind::String => ObjectLayout::String,
+            TypeKind::SzArray(element) => {
+                self.types.prepare(element)?;
+                let element = self.types.get(element);
+                ObjectLayout::Array {
+                    element_size: element.value_size(),
+                    ref_offsets: element.value_ref_offsets().to_vec(),
+                }
+            }
+            TypeKind::Array(element, rank) => {
+                self.types.prepare(element)?;
+                let element = self.types.get(element);
+                ObjectLayout::MdArray {
+                    rank,
+                    element_size: element.value_size(),
+                    ref_offsets: element.value_ref_offsets().to_vec(),
+                }
+            }
+            TypeKind::Class | TypeKind::ValueType | TypeKind::Primitive(_) | TypeKind::Enum(_) => {
+                let ty = self.types.get(ty);
+                if ty.is_value_type() {
+                    ObjectLayout::Fixed {
+                        size: ty.value_size(),
+                        ref_offsets: ty.value_ref_offsets().to_vec(),
+                    }
+                } else {
+                    ObjectLayout::Fixed {
+                        size: ty.size,
+                        ref_offsets: ty.ref_offsets.clone(),
+                    }
+                }
+            }
+            TypeKind::Interface | TypeKind::ByRef(_) | TypeKind::Pointer(_) | TypeKind::Var(_) | TypeKind::MVar(_) => {
+                return Err(Error::InvalidProgram(format!("{} can't be instantiated", self.types.get(ty))));
+            }
+        };
+        if self.types.is_open(ty) {
+            return Err(Error::InvalidProgram(format!("{} is an open generic type", self.types.get(ty))));
+        }
+        self.heap.set_layout(ty, layout);
+        Ok(())
+    }
+
+    /// Collects garbage, using the interpreter's frames and the static fields as roots.
+    pub fn collect(&mut self) {
+        let roots = self.roots();
+        debug!(self.logger, "collecting garbage"; "roots" => roots.len());
+        unsafe { self.heap.collect(&roots) };
+    }
+
+    /// Gets the location of every object reference outside the heap: in frames (including values on the evaluation
+    /// stacks), static and thread-static fields, exceptions being thrown or that failed a type's initialization,
+    /// interned strings, reflection objects, handles and threads. Threads without the lock are stopped at safepoints,
+    /// with their state kept in their `ManagedThread`.
+    fn roots(&mut self) -> Vec<*mut u8> {
+        let mut roots = Vec::new();
+        let types = &self.types;
+        for frame in &mut self.frames {
+            frame.visit_roots(types, |root| roots.push(root));
+        }
+        for thrown in self.thrown.iter_mut().chain(self.unhandled.as_mut()) {
+            roots.push(&mut thrown.exception as *mut ObjectRef as *mut u8);
+        }
+        for init in self.type_inits.values_mut() {
+            if let TypeInit::Failed(ref mut exception) = *init {
+                roots.push(exception as *mut ObjectRef as *mut u8);
+            }
+        }
+        let reflection_objects = self.reflection_objects.values_mut();
+        for object in self.interned.values_mut().chain(reflection_objects).chain(self.handles.iter_mut()) {
+            roots.push(object as *mut ObjectRef as *mut u8);
+        }
+        for thread in &mut self.threads {
+            roots.push(&mut thread.object as *mut ObjectRef as *mut u8);
+            thread.state.visit_roots(types, |root| roots.push(root));
+        }
+        visit_static_roots(types, &mut self.statics, |root| roots.push(root));
+        visit_static_roots(types, &mut self.thread_statics, |root| roots.push(root));
+        roots
+    }
+
+    /// Gets the address of a type's static field storage, allocating it the first time. Static fields with an RVA
+    /// start with the data the image has for them.
+    pub fn static_storage(&mut self, ty: TypeId) -> Result<*mut u8, Error> {
+        self.types.prepare(ty)?;
+        if self.statics.len() <= ty.index() {
+            self.statics.resize(ty.index() + 1, None);
+        }
+        if self.statics[ty.index()].is_none() {
+            let size = self.types.get(ty).static_size as usize;
+            let mut storage = vec![0u64; size.div_ceil(8)].into_boxed_slice();
+            for field in self.types.get(ty).fields.clone() {
+                let offset = self.types.field(field).offset as usize;
+                if let Some(data) = self.types.field_data(field)? {
+                    let address = unsafe { (storage.as_mut_ptr() as *mut u8).add(offset) };
+                    unsafe { ::std::ptr::copy_nonoverlapping(data.as_ptr(), address, data.len()) };
+                }
+            }
+            self.statics[ty.index()] = Some(storage);
+        }
+        Ok(self.statics[ty.index()].as_mut().unwrap().as_mut_ptr() as *mut u8)
+    }
+}
+
+/// Calls `visit` with the location of each object reference in the static field storage of types, indexed by
+/// `TypeId`.
+pub(crate) fn visit_static_roots<F: FnMut(*mut u8)>(
+    types: &TypeSystem,
+    statics: &mut [Option<Box<[u64]>>],
+    mut visit: F,
+) {
+    for (index, storage) in statics.iter_mut().enumerate() {
+        if let Some(ref mut storage) = *storage {
+            let base = storage.as_mut_ptr() as *mut u8;
+            for &offset in &types.get(TypeId(index as u32)).static_ref_offsets {
+                visit(unsafe { base.add(offset as usize) });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ecma355metadata::cli::il::Opcode;
+
+    use gc::GcMode;
+    use runtime::RuntimeBuilder;
+    use test_assembly::*;
+
+    /// Builds an app that allocates a node and an array on each of `iterations` iterations, keeping every 1000th
+    /// node in a static list, then returns the length of the list.
+    fn linked_list_app(iterations: i32) -> AssemblyBuilder {
+        // class Node { Node next; }, with a static list that keeps every 1000th node, and arrays that are all garbage
+        let mut app = AssemblyBuilder::new("App");
+        let object = app.corlib_type("System", "Object");
+        let object_ctor = app.member_ref(object, ".ctor", &method_sig(true, Ty::Void, &[]));
+        let node = app.type_def(PUBLIC, "", "Node", object);
+        let next = app.field(0, "next", Ty::Class(node));
+        let mut il = Il::new();
+        il.op(Opcode::Ldarg0).arg(Opcode::Call, object_ctor as i64).op(Opcode::Ret);
+        let node_ctor = app.method(CONSTRUCTOR, ".ctor", &method_sig(true, Ty::Void, &[]), Some(Body::new(vec![], il)));
+
+        app.type_def(PUBLIC, "", "Program", object);
+        let list = app.field(STATIC_FIELD, "list", Ty::Class(node));
+        let byte = app.corlib_type("System", "Byte");
+        let mut il = Il::new();
+        let (head, skip, end) = (il.label(), il.label(), il.label());
+        let (count, done) = (il.label(), il.label());
+        il.op(Opcode::LdcI40).op(Opcode::Stloc0);
+        il.mark(head).op(Opcode::Ldloc0).ldc_i4(iterations).branch(Opcode::Bge, end);
+        il.arg(Opcode::Newobj, node_ctor as i64).op(Opcode::Stloc1);
+        il.op(Opcode::LdcI44).arg(Opcode::Newarr, byte as i64).op(Opcode::Pop);
+        il.op(Opcode::Ldloc0).ldc_i4(1000).op(Opcode::Rem).branch(Opcode::Brtrue, skip);
+        il.op(Opcode::Ldloc1).arg(Opcode::Ldsfld, list as i64).arg(Opcode::Stfld, next as i64);
+        il.op(Opcode::Ldloc1).arg(Opcode::Stsfld, list as i64);
+        il.mark(skip).op(Opcode::Ldloc0).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc0).branch(Opcode::Br, head);
+
+        // Count the nodes in the list
+        il.mark(end).op(Opcode::LdcI40).op(Opcode::Stloc0).arg(Opcode::Ldsfld, list as i64).op(Opcode::Stloc1);
+        il.mark(count).op(Opcode::Ldloc1).branch(Opcode::Brfalse, done);
+        il.op(Opcode::Ldloc0).op(Opcode::LdcI41).op(Opcode::Add).op(Opcode::Stloc0);
+        il.op(Opcode::Ldloc1).arg(Opcode::Ldfld, next as i64).op(Opcode::Stloc1).branch(Opcode::Br, count);
+        il.mark(done).op(Opcode::Ldloc0).op(Opcode::Ret);
+        add_main(&mut app, Body::new(vec![Ty::I4, Ty::Class(node)], il));
+        app
+    }
+
+    #[test]
+    pub fn collects_garbage_while_running() {
+        let (_directory, mut runtime) = runtime("gc", &corlib(), &linked_list_app(300_000));
+        assert_eq!(Ok(300), runtime.execute("App"));
+        let stats = runtime.gc_stats();
+        assert!(stats.collections > 0, "{:?}", stats);
+        assert_eq!(600_000, stats.objects_allocated);
+        assert!(stats.heap_size <= 8 << 20, "{:?}", stats);
+    }
+
+    #[test]
+    pub fn generational_mode_promotes_survivors() {
+        let builder = RuntimeBuilder::new().gc_mode(GcMode::Generational);
+        let (_directory, mut runtime) = runtime_with("gc_generational", &corlib(), &linked_list_app(300_000), builder);
+        assert_eq!(Ok(300), runtime.execute("App"));
+        let stats = runtime.gc_stats();
+        assert!(stats.collections > stats.full_collections, "{:?}", stats);
+        assert!(stats.bytes_promoted < 64 << 10, "{:?}", stats);
+        assert!(stats.heap_size <= 8 << 20, "{:?}", stats);
+    }
+
+    #[test]
+    pub fn gc_stress_moves_objects_on_every_allocation() {
+        let builder = RuntimeBuilder::new().gc_mode(GcMode::Generational).gc_stress(true);
+        let (_directory, mut runtime) = runtime_with("gc_stress", &corlib(), &linked_list_app(3000), builder);
+        assert_eq!(Ok(3), runtime.execute("App"));
+        let stats = runtime.gc_stats();
+        assert_eq!(6000, stats.collections);
+        assert_eq!(6000, stats.full_collections);
+    }
+}